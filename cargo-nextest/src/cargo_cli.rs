@@ -6,10 +6,12 @@
 use crate::output::OutputContext;
 use camino::{Utf8Path, Utf8PathBuf};
 use clap::{AppSettings, Args};
+use nextest_metadata::{FeatureSelectionSummary, RustcVersionSummary};
+use nextest_runner::reuse_build::CurrentBuildConfig;
 use std::path::PathBuf;
 
 /// Options passed down to cargo.
-#[derive(Debug, Args)]
+#[derive(Clone, Debug, Args)]
 #[clap(
     next_help_heading = "CARGO OPTIONS",
     group = clap::ArgGroup::new("cargo-opts").multiple(true),
@@ -48,6 +50,13 @@ pub(crate) struct CargoOptions {
     #[clap(long, group = "cargo-opts")]
     all_targets: bool,
 
+    /// Also build and run doctests for each library being tested
+    ///
+    /// This uses unstable rustdoc options, which are enabled with RUSTC_BOOTSTRAP=1 while compiling
+    /// doctests.
+    #[clap(long, group = "cargo-opts")]
+    pub(crate) doctests: bool,
+
     //  TODO: doc?
     // no-run is handled by test runner
     /// Package to test
@@ -67,12 +76,14 @@ pub(crate) struct CargoOptions {
     all: bool,
 
     // jobs is handled by test runner
+    // The profile, feature and target options are not part of the cargo-opts group: while reusing
+    // builds, they're checked against the build configuration the build was produced with.
     /// Build artifacts in release mode, with optimizations
-    #[clap(long, short = 'r', group = "cargo-opts")]
+    #[clap(long, short = 'r')]
     release: bool,
 
     /// Build artifacts with the specified Cargo profile
-    #[clap(long, value_name = "NAME", group = "cargo-opts")]
+    #[clap(long, value_name = "NAME")]
     cargo_profile: Option<String>,
 
     /// Number of build jobs to run
@@ -80,20 +91,20 @@ pub(crate) struct CargoOptions {
     build_jobs: Option<String>,
 
     /// Space or comma separated list of features to activate
-    #[clap(long, short = 'F', group = "cargo-opts")]
+    #[clap(long, short = 'F')]
     features: Vec<String>,
 
     /// Activate all available features
-    #[clap(long, group = "cargo-opts")]
+    #[clap(long)]
     all_features: bool,
 
     /// Do not activate the `default` feature
-    #[clap(long, group = "cargo-opts")]
+    #[clap(long)]
     no_default_features: bool,
 
-    /// Build for the target triple
-    #[clap(long, value_name = "TRIPLE", group = "cargo-opts")]
-    pub(crate) target: Option<String>,
+    /// Build for the target triple (can be specified more than once)
+    #[clap(long, value_name = "TRIPLE")]
+    pub(crate) target: Vec<String>,
 
     /// Directory for all generated artifacts
     #[clap(long, value_name = "DIR", group = "cargo-opts")]
@@ -136,6 +147,74 @@ pub(crate) struct CargoOptions {
     unstable_flags: Vec<String>,
 }
 
+impl CargoOptions {
+    /// Returns the Cargo feature selection passed in.
+    pub(crate) fn feature_selection(&self) -> FeatureSelectionSummary {
+        FeatureSelectionSummary {
+            features: self
+                .features
+                .iter()
+                .flat_map(|s| s.split(|c: char| c == ',' || c.is_whitespace()))
+                .filter(|feature| !feature.is_empty())
+                .map(|feature| feature.to_owned())
+                .collect(),
+            all_features: self.all_features,
+            no_default_features: self.no_default_features,
+        }
+    }
+
+    /// Returns the name of the Cargo profile tests are built with.
+    pub(crate) fn profile_name(&self) -> String {
+        match &self.cargo_profile {
+            Some(profile) => profile.clone(),
+            None if self.release => "release".to_owned(),
+            // cargo test uses the test profile by default.
+            None => "test".to_owned(),
+        }
+    }
+
+    /// Returns the name of the Cargo profile benchmarks are built with.
+    pub(crate) fn bench_profile_name(&self) -> String {
+        // cargo bench uses the bench profile by default.
+        self.cargo_profile
+            .clone()
+            .unwrap_or_else(|| "bench".to_owned())
+    }
+
+    /// Returns true if any targets were selected to be built.
+    pub(crate) fn has_target_selection(&self) -> bool {
+        self.lib
+            || !self.bin.is_empty()
+            || self.bins
+            || !self.test.is_empty()
+            || self.tests
+            || !self.bench.is_empty()
+            || self.benches
+            || self.all_targets
+    }
+
+    /// Returns the build configuration specified by these options, to be compared against a
+    /// reused build.
+    ///
+    /// Features, the profile and the target triple are only included if they were explicitly
+    /// specified.
+    pub(crate) fn current_build_config(
+        &self,
+        rustc_version: Option<RustcVersionSummary>,
+        target_triple: Option<String>,
+    ) -> CurrentBuildConfig {
+        let features_specified =
+            !self.features.is_empty() || self.all_features || self.no_default_features;
+        let profile_specified = self.release || self.cargo_profile.is_some();
+        CurrentBuildConfig {
+            rustc_version,
+            features: features_specified.then(|| self.feature_selection()),
+            profile: profile_specified.then(|| self.profile_name()),
+            target_triple,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct CargoCli<'a> {
     cargo_path: Utf8PathBuf,
@@ -161,7 +240,6 @@ impl<'a> CargoCli<'a> {
         }
     }
 
-    #[allow(dead_code)]
     pub(crate) fn add_arg(&mut self, arg: &'a str) -> &mut Self {
         self.args.push(arg);
         self
@@ -173,6 +251,18 @@ impl<'a> CargoCli<'a> {
     }
 
     pub(crate) fn add_options(&mut self, options: &'a CargoOptions) -> &mut Self {
+        self.add_target_selection(options);
+        self.add_package_selection(options);
+        self.add_build_options(options)
+    }
+
+    /// Adds options for building doctests: the same as [`Self::add_options`], except for target
+    /// and package selection.
+    pub(crate) fn add_doctest_options(&mut self, options: &'a CargoOptions) -> &mut Self {
+        self.add_build_options(options)
+    }
+
+    fn add_target_selection(&mut self, options: &'a CargoOptions) {
         if options.lib {
             self.args.push("--lib");
         }
@@ -194,6 +284,9 @@ impl<'a> CargoCli<'a> {
         if options.all_targets {
             self.args.push("--all-targets");
         }
+    }
+
+    fn add_package_selection(&mut self, options: &'a CargoOptions) {
         self.args.extend(
             options
                 .packages
@@ -212,6 +305,9 @@ impl<'a> CargoCli<'a> {
         if options.all {
             self.args.push("--all");
         }
+    }
+
+    fn add_build_options(&mut self, options: &'a CargoOptions) -> &mut Self {
         if options.release {
             self.args.push("--release");
         }
@@ -229,9 +325,8 @@ impl<'a> CargoCli<'a> {
         if options.no_default_features {
             self.args.push("--no-default-features");
         }
-        if let Some(target) = &options.target {
-            self.args.extend(["--target", target]);
-        }
+        self.args
+            .extend(options.target.iter().flat_map(|s| ["--target", s.as_str()]));
         if let Some(target_dir) = &options.target_dir {
             self.args.extend(["--target-dir", target_dir.as_str()]);
         }
@@ -0,0 +1,132 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Writing out shard plans for CI systems, for `cargo nextest ci plan`.
+
+use clap::ArgEnum;
+use nextest_metadata::CiMatrixSummary;
+use std::io::{self, Write};
+
+/// The format to write a CI shard plan out in.
+#[derive(Copy, Clone, Debug, ArgEnum)]
+pub(crate) enum CiPlanFormat {
+    /// JSON, for GitHub Actions' `fromJSON`
+    Json,
+    /// YAML, for GitLab and other CI systems configured with YAML
+    Yaml,
+}
+
+impl Default for CiPlanFormat {
+    fn default() -> Self {
+        Self::Json
+    }
+}
+
+impl CiPlanFormat {
+    pub(crate) fn write(self, summary: &CiMatrixSummary, mut writer: impl Write) -> io::Result<()> {
+        match self {
+            Self::Json => {
+                // GitHub Actions reads outputs line by line, so write the summary out on one line.
+                serde_json::to_writer(&mut writer, summary)?;
+                writeln!(writer)
+            }
+            Self::Yaml => write_yaml(summary, writer),
+        }
+    }
+}
+
+/// Writes out a summary as YAML.
+///
+/// Strings are written out JSON-quoted, which is valid in YAML and avoids having to work out which
+/// strings need quoting.
+fn write_yaml(summary: &CiMatrixSummary, mut writer: impl Write) -> io::Result<()> {
+    if summary.include.is_empty() {
+        return writeln!(writer, "include: []");
+    }
+    writeln!(writer, "include:")?;
+    for shard in &summary.include {
+        writeln!(writer, "  - shard: {}", shard.shard)?;
+        writeln!(writer, "    total-shards: {}", shard.total_shards)?;
+        if let Some(partition) = &shard.partition {
+            writeln!(
+                writer,
+                "    partition: {}",
+                serde_json::to_string(partition)?
+            )?;
+        }
+        writeln!(writer, "    test-count: {}", shard.test_count)?;
+        writeln!(
+            writer,
+            "    estimated-duration-secs: {}",
+            serde_json::to_string(&shard.estimated_duration_secs)?
+        )?;
+        match &shard.tests {
+            Some(tests) if tests.is_empty() => writeln!(writer, "    tests: []")?,
+            Some(tests) => {
+                writeln!(writer, "    tests:")?;
+                for test in tests {
+                    writeln!(writer, "      - {}", serde_json::to_string(test)?)?;
+                }
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nextest_metadata::CiShardSummary;
+
+    #[test]
+    fn test_write_yaml() {
+        let summary = CiMatrixSummary {
+            include: vec![
+                CiShardSummary {
+                    shard: 1,
+                    total_shards: 2,
+                    partition: Some("plan:1:plan.json".to_owned()),
+                    test_count: 2,
+                    estimated_duration_secs: 1.5,
+                    tests: Some(vec![
+                        "my-crate test_a".to_owned(),
+                        "my-crate::doctests src/lib.rs - foo (line 3)".to_owned(),
+                    ]),
+                },
+                CiShardSummary {
+                    shard: 2,
+                    total_shards: 2,
+                    partition: None,
+                    test_count: 0,
+                    estimated_duration_secs: 0.0,
+                    tests: Some(vec![]),
+                },
+            ],
+        };
+
+        let mut yaml = Vec::new();
+        CiPlanFormat::Yaml
+            .write(&summary, &mut yaml)
+            .expect("writing to a Vec succeeds");
+        assert_eq!(
+            String::from_utf8(yaml).expect("YAML is valid UTF-8"),
+            concat!(
+                "include:\n",
+                "  - shard: 1\n",
+                "    total-shards: 2\n",
+                "    partition: \"plan:1:plan.json\"\n",
+                "    test-count: 2\n",
+                "    estimated-duration-secs: 1.5\n",
+                "    tests:\n",
+                "      - \"my-crate test_a\"\n",
+                "      - \"my-crate::doctests src/lib.rs - foo (line 3)\"\n",
+                "  - shard: 2\n",
+                "    total-shards: 2\n",
+                "    test-count: 0\n",
+                "    estimated-duration-secs: 0.0\n",
+                "    tests: []\n",
+            )
+        );
+    }
+}
@@ -3,37 +3,65 @@
 
 use crate::{
     cargo_cli::{CargoCli, CargoOptions},
+    ci::CiPlanFormat,
     output::{OutputContext, OutputOpts, OutputWriter},
-    reuse_build::{make_path_mapper, ArchiveFormatOpt, ReuseBuildOpts},
+    reuse_build::{
+        make_path_mapper, mismatch_hint, ArchiveFormatOpt, BuildMismatchOpt, ReuseBuildOpts,
+    },
     ExpectedError, Result, ReuseBuildKind,
 };
 use camino::{Utf8Path, Utf8PathBuf};
 use clap::{ArgEnum, Args, Parser, Subcommand};
 use guppy::graph::PackageGraph;
 use itertools::Itertools;
-use nextest_filtering::FilteringExpr;
-use nextest_metadata::{BinaryListSummary, BuildPlatform};
+use nextest_filtering::{FilteringExpr, ParseContext, PreviousRun, TestTags};
+use nextest_metadata::{
+    BinaryListSummary, BuildPlatform, CiMatrixSummary, CiShardSummary, RustTestBinaryKind,
+    RustcVersionSummary, TestListSummary,
+};
 use nextest_runner::{
+    bench::{criterion_home, BenchResultsCollector},
     cargo_config::{CargoConfigs, TargetTriple},
     config::{NextestConfig, NextestProfile, TestThreads, ToolConfigFile},
-    errors::WriteTestListError,
-    list::{BinaryList, OutputFormat, RustTestArtifact, SerializableFormat, TestList},
-    partition::PartitionerBuilder,
-    reporter::{FinalStatusLevel, StatusLevel, TestOutputDisplay, TestReporterBuilder},
-    reuse_build::{archive_to_file, ArchiveReporter, MetadataOrPath, PathMapper, ReuseBuildInfo},
-    runner::{configure_handle_inheritance, TestRunnerBuilder},
+    errors::{FromMessagesError, WriteTestListError},
+    flake_hunt::{FlakeHuntCollector, LoadGenerator},
+    git::{BisectState, BisectVerdict, GitBisect, GitChangedFiles},
+    list::{
+        doctest_persist_dir, BinaryList, BinaryListState, OutputFormat, RustBuildMeta,
+        RustTestArtifact, SerializableFormat, TestList, TestListBatches, TestListDiff,
+        TestListPrefetcher, TestListState,
+    },
+    order_deps::OrderDepsCollector,
+    partition::{PartitionerBuilder, ShardEstimate, ShardPlan},
+    reporter::{
+        CancelReason, FinalStatusLevel, MessageFormat, StatusLevel, TestEvent, TestOutputDisplay,
+        TestReporterBuilder,
+    },
+    reuse_build::{
+        archive_to_file, ArchiveBaseline, ArchiveCompression, ArchiveContents, ArchiveDebuginfo,
+        ArchiveReporter, MetadataOrPath, PathMapper, PathRemap, ReuseBuildInfo, ReuseBuildMismatch,
+    },
+    run_store::{FailureRetry, RunStore, TestTimings},
+    runner::{configure_handle_inheritance, TestListSender, TestRunnerBuilder},
     signal::SignalHandlerKind,
     target_runner::{PlatformRunner, TargetRunner},
     test_filter::{RunIgnored, TestFilterBuilder},
 };
 use once_cell::sync::OnceCell;
 use owo_colors::{OwoColorize, Style};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use std::{
+    collections::{hash_map::DefaultHasher, BTreeSet, HashSet},
     fmt::Write as _,
-    io::{Cursor, Write},
+    hash::{Hash, Hasher},
+    io::{BufReader, Read, Write},
+    num::{NonZeroU64, NonZeroUsize},
+    ops::ControlFlow,
     sync::Arc,
+    time::{Duration, Instant, SystemTime},
 };
 use supports_color::Stream;
+use uuid::Uuid;
 
 /// A next-generation test runner for Rust.
 ///
@@ -91,10 +119,13 @@ impl AppOpts {
 
         match self.command {
             Command::List {
+                profile,
                 cargo_options,
                 build_filter,
                 message_format,
                 list_type,
+                diff,
+                fail_on_removed,
                 reuse_build,
                 ..
             } => {
@@ -108,16 +139,29 @@ impl AppOpts {
                     output_writer,
                 )?;
                 let app = App::new(base, build_filter)?;
-                app.exec_list(message_format, list_type, output_writer)?;
+                let list_diff = diff.map(|previous| ListDiffOpts {
+                    previous,
+                    fail_on_removed,
+                });
+                app.exec_list(
+                    profile.as_deref(),
+                    message_format,
+                    list_type,
+                    list_diff.as_ref(),
+                    output_writer,
+                )?;
                 Ok(0)
             }
             Command::Run {
                 profile,
                 no_capture,
+                live_output,
+                live_output_filter,
                 cargo_options,
                 build_filter,
                 runner_opts,
                 reporter_opts,
+                message_format,
                 reuse_build,
                 ..
             } => {
@@ -134,17 +178,198 @@ impl AppOpts {
                 app.exec_run(
                     profile.as_deref(),
                     no_capture,
+                    live_output,
+                    &live_output_filter,
+                    &runner_opts,
+                    &reporter_opts,
+                    message_format,
+                    output_writer,
+                )?;
+                Ok(0)
+            }
+            Command::Bench {
+                profile,
+                no_capture,
+                cargo_options,
+                build_filter,
+                message_format,
+                runner_opts,
+                reporter_opts,
+            } => {
+                let base = BaseApp::new(
+                    self.output,
+                    ReuseBuildOpts::default(),
+                    cargo_options,
+                    self.config_opts,
+                    self.manifest_path,
+                    build_filter_needs_deps(&build_filter),
+                    output_writer,
+                )?;
+                let app = App::new(base, build_filter)?;
+                app.exec_bench(
+                    profile.as_deref(),
+                    no_capture,
+                    message_format,
                     &runner_opts,
                     &reporter_opts,
                     output_writer,
                 )?;
                 Ok(0)
             }
+            Command::DetectOrderDeps {
+                profile,
+                cargo_options,
+                build_filter,
+                runs,
+                test_threads,
+                seed,
+                message_format,
+                reuse_build,
+            } => {
+                let base = BaseApp::new(
+                    self.output,
+                    reuse_build,
+                    cargo_options,
+                    self.config_opts,
+                    self.manifest_path,
+                    build_filter_needs_deps(&build_filter),
+                    output_writer,
+                )?;
+                let app = App::new(base, build_filter)?;
+                app.exec_detect_order_deps(
+                    profile.as_deref(),
+                    runs,
+                    test_threads,
+                    seed,
+                    message_format,
+                    output_writer,
+                )?;
+                Ok(0)
+            }
+            Command::FlakeHunt {
+                profile,
+                cargo_options,
+                build_filter,
+                hunt_opts,
+                message_format,
+                reuse_build,
+            } => {
+                let base = BaseApp::new(
+                    self.output,
+                    reuse_build,
+                    cargo_options,
+                    self.config_opts,
+                    self.manifest_path,
+                    build_filter_needs_deps(&build_filter),
+                    output_writer,
+                )?;
+                let app = App::new(base, build_filter)?;
+                app.exec_flake_hunt(
+                    profile.as_deref(),
+                    &hunt_opts,
+                    message_format,
+                    output_writer,
+                )?;
+                Ok(0)
+            }
+            Command::Bisect {
+                profile,
+                cargo_options,
+                build_filter,
+                bisect_opts,
+            } => {
+                let app = BisectApp {
+                    output: self.output,
+                    config_opts: self.config_opts,
+                    manifest_path: self.manifest_path,
+                    profile,
+                    cargo_options,
+                    build_filter,
+                    bisect_opts,
+                };
+                app.exec(output_writer)?;
+                Ok(0)
+            }
+            Command::Plan {
+                profile,
+                cargo_options,
+                build_filter,
+                shards,
+                timings,
+                output_file,
+                reuse_build,
+            } => {
+                let base = BaseApp::new(
+                    self.output,
+                    reuse_build,
+                    cargo_options,
+                    self.config_opts,
+                    self.manifest_path,
+                    build_filter_needs_deps(&build_filter),
+                    output_writer,
+                )?;
+                let app = App::new(base, build_filter)?;
+                app.exec_plan(
+                    profile.as_deref(),
+                    shards,
+                    timings.as_deref(),
+                    output_file.as_deref(),
+                    output_writer,
+                )?;
+                Ok(0)
+            }
+            Command::Ci {
+                command:
+                    CiCommand::Plan {
+                        profile,
+                        cargo_options,
+                        build_filter,
+                        shards,
+                        timings,
+                        format,
+                        output_file,
+                        plan_file,
+                        test_lists,
+                        reuse_build,
+                    },
+            } => {
+                let base = BaseApp::new(
+                    self.output,
+                    reuse_build,
+                    cargo_options,
+                    self.config_opts,
+                    self.manifest_path,
+                    build_filter_needs_deps(&build_filter),
+                    output_writer,
+                )?;
+                let app = App::new(base, build_filter)?;
+                app.exec_ci_plan(
+                    profile.as_deref(),
+                    shards,
+                    timings.as_deref(),
+                    format,
+                    output_file.as_deref(),
+                    plan_file.as_deref(),
+                    test_lists,
+                    output_writer,
+                )?;
+                Ok(0)
+            }
+            Command::Archive {
+                command: Some(command),
+                ..
+            } => command.exec(self.output, output_writer),
             Command::Archive {
+                command: None,
                 cargo_options,
                 archive_file,
                 archive_format,
+                compression,
                 zstd_level,
+                profile,
+                baseline,
+                debuginfo,
+                debuginfo_file,
             } => {
                 let app = BaseApp::new(
                     self.output,
@@ -155,7 +380,20 @@ impl AppOpts {
                     true,
                     output_writer,
                 )?;
-                app.exec_archive(&archive_file, archive_format, zstd_level, output_writer)?;
+                let compression = compression
+                    .or_else(|| zstd_level.map(|level| ArchiveCompression::Zstd { level }));
+                let archive_file =
+                    archive_file.expect("--archive-file is required without a subcommand");
+                app.exec_archive(
+                    &archive_file,
+                    archive_format,
+                    compression,
+                    profile.as_deref(),
+                    baseline.as_deref(),
+                    debuginfo.map(DebuginfoOpt::to_archive_debuginfo),
+                    debuginfo_file.as_deref(),
+                    output_writer,
+                )?;
                 Ok(0)
             }
             Command::Self_ { command } => command.exec(self.output),
@@ -163,7 +401,7 @@ impl AppOpts {
     }
 }
 
-#[derive(Debug, Args)]
+#[derive(Clone, Debug, Args)]
 #[clap(next_help_heading = "CONFIG OPTIONS")]
 struct ConfigOpts {
     /// Config file [default: workspace-root/.config/nextest.toml]
@@ -217,6 +455,10 @@ enum Command {
     ///
     /// For more information, see <https://nexte.st/book/listing>.
     List {
+        /// Nextest profile to use
+        #[clap(long, short = 'P', env = "NEXTEST_PROFILE")]
+        profile: Option<String>,
+
         #[clap(flatten)]
         cargo_options: CargoOptions,
 
@@ -244,6 +486,22 @@ enum Command {
         )]
         list_type: ListType,
 
+        /// Compare with a previous test list and print out added and removed tests
+        ///
+        /// The previous list must have been produced by `cargo nextest list --message-format json`.
+        /// Renamed tests are shown as removed and added.
+        #[clap(
+            long,
+            value_name = "PATH",
+            conflicts_with = "list-type",
+            help_heading = "DIFF OPTIONS"
+        )]
+        diff: Option<Utf8PathBuf>,
+
+        /// Exit with a nonzero code if any tests were removed compared to the previous list
+        #[clap(long, requires = "diff", help_heading = "DIFF OPTIONS")]
+        fail_on_removed: bool,
+
         #[clap(flatten)]
         reuse_build: ReuseBuildOpts,
     },
@@ -267,6 +525,28 @@ enum Command {
         )]
         no_capture: bool,
 
+        /// Show each line of output as tests write it, tagged with the test's name, while still
+        /// running tests in parallel
+        #[clap(
+            long,
+            conflicts_with_all = &["no-capture", "no-run", "failure-output", "success-output", "dashboard"],
+            help_heading = "RUNNER OPTIONS",
+            display_order = 101,
+            env = "NEXTEST_LIVE_OUTPUT"
+        )]
+        live_output: bool,
+
+        /// Like --live-output, but only for tests that match this filter expression
+        #[clap(
+            long,
+            multiple_occurrences(true),
+            conflicts_with_all = &["no-capture", "no-run", "dashboard"],
+            help_heading = "RUNNER OPTIONS",
+            display_order = 102,
+            value_name = "EXPRESSION"
+        )]
+        live_output_filter: Vec<String>,
+
         #[clap(flatten)]
         cargo_options: CargoOptions,
 
@@ -279,90 +559,512 @@ enum Command {
         #[clap(flatten)]
         reporter_opts: TestReporterOpts,
 
+        /// Format of the messages written to standard output while tests run
+        #[clap(
+            long,
+            arg_enum,
+            default_value_t,
+            conflicts_with_all = &["no-capture", "no-run"],
+            help_heading = "REPORTER OPTIONS",
+            value_name = "FMT"
+        )]
+        message_format: RunMessageFormatOpt,
+
         #[clap(flatten)]
         reuse_build: ReuseBuildOpts,
     },
-    /// Build and archive tests
+    /// Build and run benchmarks
     ///
-    /// This command builds test binaries and archives them to a file. The archive can then be
-    /// transferred to another machine, and tests within it can be run with `cargo nextest run
-    /// --archive-file`.
+    /// This command builds bench targets with `cargo bench --no-run` and queries them for the
+    /// benchmarks they contain, then runs each benchmark serially. Once the run is done, the
+    /// results reported by criterion benchmarks are printed out.
     ///
-    /// The archive is a tarball compressed with Zstandard (.tar.zst).
-    Archive {
+    /// Use --message-format json to get machine-readable results.
+    ///
+    /// For more information, see <https://nexte.st/book/running-benchmarks>.
+    Bench {
+        /// Nextest profile to use
+        #[clap(long, short = 'P', env = "NEXTEST_PROFILE")]
+        profile: Option<String>,
+
+        /// Do not capture benchmark output
+        #[clap(
+            long,
+            alias = "nocapture",
+            help_heading = "RUNNER OPTIONS",
+            display_order = 100
+        )]
+        no_capture: bool,
+
         #[clap(flatten)]
         cargo_options: CargoOptions,
 
-        /// File to write archive to
-        #[clap(long, help_heading = "ARCHIVE OPTIONS", value_name = "PATH")]
-        archive_file: Utf8PathBuf,
+        #[clap(flatten)]
+        build_filter: TestBuildFilter,
 
-        /// Archive format
-        ///
-        /// `auto` uses the file extension to determine the archive format. Currently supported is
-        /// `.tar.zst`.
+        /// Output format for benchmark results
         #[clap(
+            short = 'T',
             long,
             arg_enum,
-            help_heading = "ARCHIVE OPTIONS",
-            value_name = "FORMAT",
-            default_value_t
+            default_value_t,
+            help_heading = "OUTPUT OPTIONS",
+            value_name = "FMT"
         )]
-        archive_format: ArchiveFormatOpt,
+        message_format: MessageFormatOpts,
 
-        /// Zstandard compression level (-7 to 22, higher is more compressed + slower)
-        #[clap(
-            long,
-            help_heading = "ARCHIVE OPTIONS",
-            value_name = "LEVEL",
-            default_value_t = 0,
-            allow_hyphen_values = true
-        )]
-        zstd_level: i32,
-        // ReuseBuildOpts, while it can theoretically work, is way too confusing so skip it.
-    },
-    /// Manage the nextest installation
-    #[clap(name = "self")]
-    Self_ {
-        #[clap(subcommand)]
-        command: SelfCommand,
+        #[clap(flatten)]
+        runner_opts: TestRunnerOpts,
+
+        #[clap(flatten)]
+        reporter_opts: TestReporterOpts,
     },
-}
+    /// Look for tests that fail depending on which other tests run before or alongside them
+    ///
+    /// This command builds test binaries, then runs the tests several times, each time in a
+    /// different shuffled order. Runs alternate between running tests in parallel and running them
+    /// one at a time. Retries are turned off, and failures don't stop a run.
+    ///
+    /// Once the runs are done, tests that failed in some runs and passed in others are listed,
+    /// along with the tests that ran before or alongside them in every run they failed in, but in
+    /// at most half of the runs they passed in. These are the tests most likely to be interfering
+    /// with them.
+    ///
+    /// Use --message-format json to get machine-readable results.
+    ///
+    /// For more information, see <https://nexte.st/book/order-deps>.
+    DetectOrderDeps {
+        /// Nextest profile to use
+        #[clap(long, short = 'P', env = "NEXTEST_PROFILE")]
+        profile: Option<String>,
 
-#[derive(Copy, Clone, Debug, ArgEnum)]
-pub(crate) enum PlatformFilterOpts {
-    Target,
-    Host,
-    Any,
-}
+        #[clap(flatten)]
+        cargo_options: CargoOptions,
 
-impl Default for PlatformFilterOpts {
-    fn default() -> Self {
-        Self::Any
-    }
-}
+        #[clap(flatten)]
+        build_filter: TestBuildFilter,
 
-impl From<PlatformFilterOpts> for Option<BuildPlatform> {
-    fn from(opt: PlatformFilterOpts) -> Self {
-        match opt {
-            PlatformFilterOpts::Target => Some(BuildPlatform::Target),
-            PlatformFilterOpts::Host => Some(BuildPlatform::Host),
-            PlatformFilterOpts::Any => None,
-        }
-    }
-}
+        /// Number of times to run the tests
+        #[clap(
+            long,
+            value_name = "N",
+            default_value = "6",
+            help_heading = "DETECTION OPTIONS"
+        )]
+        runs: NonZeroUsize,
 
-#[derive(Copy, Clone, Debug, ArgEnum)]
-enum ListType {
-    Full,
-    BinariesOnly,
-}
+        /// Number of tests to run simultaneously in parallel runs [default: from profile]
+        #[clap(
+            long,
+            short = 'j',
+            visible_alias = "jobs",
+            value_name = "THREADS",
+            help_heading = "DETECTION OPTIONS"
+        )]
+        test_threads: Option<TestThreads>,
 
-impl Default for ListType {
-    fn default() -> Self {
-        Self::Full
-    }
-}
+        /// Seed to pick the order of each run with [default: random]
+        #[clap(long, value_name = "SEED", help_heading = "DETECTION OPTIONS")]
+        seed: Option<u64>,
+
+        /// Output format
+        #[clap(
+            short = 'T',
+            long,
+            arg_enum,
+            default_value_t,
+            help_heading = "OUTPUT OPTIONS",
+            value_name = "FMT"
+        )]
+        message_format: MessageFormatOpts,
+
+        #[clap(flatten)]
+        reuse_build: ReuseBuildOpts,
+    },
+    /// Run tests over and over under load for a while, and report how often each test failed
+    ///
+    /// This command builds test binaries, then runs the tests in rounds until the given duration is
+    /// up. Each round starts tests in a different shuffled order, and runs more tests at a time than
+    /// the profile does. Threads that keep CPUs and disks busy can be started alongside the tests.
+    /// Retries are turned off, and failures don't stop a round. Tests still running once the time
+    /// is up are terminated and left out.
+    ///
+    /// Once the time is up, tests that failed at least once are listed along with how often they
+    /// failed, and the output of the first failure of each kind.
+    ///
+    /// Use --message-format json to get machine-readable results.
+    ///
+    /// For more information, see <https://nexte.st/book/flake-hunt>.
+    FlakeHunt {
+        /// Nextest profile to use
+        #[clap(long, short = 'P', env = "NEXTEST_PROFILE")]
+        profile: Option<String>,
+
+        #[clap(flatten)]
+        cargo_options: CargoOptions,
+
+        #[clap(flatten)]
+        build_filter: TestBuildFilter,
+
+        #[clap(flatten)]
+        hunt_opts: FlakeHuntOpts,
+
+        /// Output format
+        #[clap(
+            short = 'T',
+            long,
+            arg_enum,
+            default_value_t,
+            help_heading = "OUTPUT OPTIONS",
+            value_name = "FMT"
+        )]
+        message_format: MessageFormatOpts,
+
+        #[clap(flatten)]
+        reuse_build: ReuseBuildOpts,
+    },
+    /// Find the commit that broke a test by bisecting git history
+    ///
+    /// This command runs `git bisect` between commits where the tests pass and a commit where they
+    /// fail. At each commit git checks out, test binaries are built and archived, and the tests
+    /// that match the filters are run from the archive with the profile's retries. Commits where
+    /// the tests pass are marked good and commits where they fail are marked bad. Commits that
+    /// don't build, or that have no tests matching the filters, are skipped.
+    ///
+    /// Archives are kept in the target directory, and reused if a commit is tested again. Once the
+    /// first bad commit is found, the commit checked out beforehand is checked out again.
+    ///
+    /// For more information, see <https://nexte.st/book/bisect>.
+    Bisect {
+        /// Nextest profile to use
+        #[clap(long, short = 'P', env = "NEXTEST_PROFILE")]
+        profile: Option<String>,
+
+        #[clap(flatten)]
+        cargo_options: CargoOptions,
+
+        #[clap(flatten)]
+        build_filter: TestBuildFilter,
+
+        #[clap(flatten)]
+        bisect_opts: BisectOpts,
+    },
+    /// Plan how to partition tests across shards
+    ///
+    /// This command builds test binaries, lists the tests that match the filters, and assigns them
+    /// to shards based on how long they took to run, so that each shard takes roughly the same
+    /// amount of time. The plan is written out as JSON, which can be edited to pin tests to
+    /// particular shards. Run the tests in shard M of a plan with `cargo nextest run --partition
+    /// plan:M:PATH`.
+    ///
+    /// For more information, see <https://nexte.st/book/partitioning>.
+    Plan {
+        /// Nextest profile to use
+        #[clap(long, short = 'P', env = "NEXTEST_PROFILE")]
+        profile: Option<String>,
+
+        #[clap(flatten)]
+        cargo_options: CargoOptions,
+
+        #[clap(flatten)]
+        build_filter: TestBuildFilter,
+
+        /// Number of shards to plan for
+        #[clap(long, value_name = "N", help_heading = "PLAN OPTIONS")]
+        shards: NonZeroU64,
+
+        /// Test durations to plan with [default: durations recorded with the profile]
+        #[clap(long, value_name = "PATH", help_heading = "PLAN OPTIONS")]
+        timings: Option<Utf8PathBuf>,
+
+        /// File to write the plan to [default: standard output]
+        #[clap(long, short = 'o', value_name = "PATH", help_heading = "PLAN OPTIONS")]
+        output_file: Option<Utf8PathBuf>,
+
+        #[clap(flatten)]
+        reuse_build: ReuseBuildOpts,
+    },
+    /// Build and archive tests
+    ///
+    /// This command builds test binaries and archives them to a file. The archive can then be
+    /// transferred to another machine, and tests within it can be run with `cargo nextest run
+    /// --archive-file`.
+    ///
+    /// The archive is a tarball, compressed with Zstandard (.tar.zst) by default.
+    #[clap(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
+    Archive {
+        #[clap(subcommand)]
+        command: Option<ArchiveCommand>,
+
+        #[clap(flatten)]
+        cargo_options: CargoOptions,
+
+        /// File to write archive to
+        #[clap(
+            long,
+            required = true,
+            help_heading = "ARCHIVE OPTIONS",
+            value_name = "PATH"
+        )]
+        archive_file: Option<Utf8PathBuf>,
+
+        /// Archive format
+        ///
+        /// `auto` uses the file extension to determine the archive format, or the compression if
+        /// the extension isn't recognized. Currently supported are `.tar.zst`, `.tar.gz` and
+        /// `.tar`.
+        #[clap(
+            long,
+            arg_enum,
+            help_heading = "ARCHIVE OPTIONS",
+            value_name = "FORMAT",
+            default_value_t
+        )]
+        archive_format: ArchiveFormatOpt,
+
+        /// Compression to use [default: from profile]
+        ///
+        /// Supported values are `zstd`, `zstd:<LEVEL>` (-7 to 22, higher is more compressed +
+        /// slower), `gzip` and `none`.
+        #[clap(long, help_heading = "ARCHIVE OPTIONS", value_name = "COMPRESSION")]
+        compression: Option<ArchiveCompression>,
+
+        /// Zstandard compression level (-7 to 22, higher is more compressed + slower)
+        ///
+        /// Equivalent to `--compression zstd:<LEVEL>`.
+        #[clap(
+            long,
+            help_heading = "ARCHIVE OPTIONS",
+            value_name = "LEVEL",
+            conflicts_with = "compression",
+            allow_hyphen_values = true
+        )]
+        zstd_level: Option<i32>,
+
+        /// What to do with debug info in binaries [default: from profile]
+        ///
+        /// `strip` strips debug info out of binaries, and `split` strips it out into a separate
+        /// debuginfo archive. Only ELF binaries are stripped, using `objcopy`.
+        #[clap(
+            long,
+            arg_enum,
+            help_heading = "ARCHIVE OPTIONS",
+            value_name = "ACTION"
+        )]
+        debuginfo: Option<DebuginfoOpt>,
+
+        /// File to write split debug info to [default: <ARCHIVE_STEM>-debuginfo.<EXT>]
+        ///
+        /// Only used if debug info is split out of binaries. To symbolize backtraces while running
+        /// tests from the archive, pass in this file with `--archive-debuginfo`.
+        #[clap(long, help_heading = "ARCHIVE OPTIONS", value_name = "PATH")]
+        debuginfo_file: Option<Utf8PathBuf>,
+
+        /// Nextest profile to read archive settings (compression, included files) from
+        #[clap(long, short = 'P', env = "NEXTEST_PROFILE")]
+        profile: Option<String>,
+
+        /// Create a delta archive against this baseline archive
+        ///
+        /// Files with the same contents as in the baseline are left out of the archive. To run
+        /// tests from a delta archive, pass in the baseline with `--archive-baseline`.
+        #[clap(long, help_heading = "ARCHIVE OPTIONS", value_name = "PATH")]
+        baseline: Option<Utf8PathBuf>,
+        // ReuseBuildOpts, while it can theoretically work, is way too confusing so skip it.
+    },
+    /// Generate shard definitions for CI systems
+    Ci {
+        #[clap(subcommand)]
+        command: CiCommand,
+    },
+    /// Manage the nextest installation
+    #[clap(name = "self")]
+    Self_ {
+        #[clap(subcommand)]
+        command: SelfCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum CiCommand {
+    /// Plan shards for a CI matrix
+    ///
+    /// This command builds test binaries, lists the tests that match the filters, and splits them
+    /// into shards based on how long they took to run, like `cargo nextest plan`. The result is
+    /// written out as a list of shards with the number of tests in each and how long each is
+    /// expected to take, in the shape of a GitHub Actions matrix (`{"include": [...]}`).
+    ///
+    /// To run a shard, either write out a shard plan with `--plan-file` and pass in each shard's
+    /// `partition` to `--partition`, or request the tests in each shard with `--test-lists` and
+    /// pass them in with `--test-list-file`.
+    ///
+    /// For more information, see <https://nexte.st/book/partitioning>.
+    Plan {
+        /// Nextest profile to use
+        #[clap(long, short = 'P', env = "NEXTEST_PROFILE")]
+        profile: Option<String>,
+
+        #[clap(flatten)]
+        cargo_options: CargoOptions,
+
+        #[clap(flatten)]
+        build_filter: TestBuildFilter,
+
+        /// Number of shards to plan for
+        #[clap(long, value_name = "N", help_heading = "PLAN OPTIONS")]
+        shards: NonZeroU64,
+
+        /// Test durations to plan with [default: durations recorded with the profile]
+        #[clap(long, value_name = "PATH", help_heading = "PLAN OPTIONS")]
+        timings: Option<Utf8PathBuf>,
+
+        /// Output format
+        #[clap(
+            long,
+            arg_enum,
+            default_value_t,
+            value_name = "FMT",
+            help_heading = "PLAN OPTIONS"
+        )]
+        format: CiPlanFormat,
+
+        /// File to write the shards to [default: standard output]
+        #[clap(long, short = 'o', value_name = "PATH", help_heading = "PLAN OPTIONS")]
+        output_file: Option<Utf8PathBuf>,
+
+        /// Also write out a shard plan to this file, for `--partition plan:M:PATH`
+        #[clap(long, value_name = "PATH", help_heading = "PLAN OPTIONS")]
+        plan_file: Option<Utf8PathBuf>,
+
+        /// Include the tests in each shard in the output
+        #[clap(long, help_heading = "PLAN OPTIONS")]
+        test_lists: bool,
+
+        #[clap(flatten)]
+        reuse_build: ReuseBuildOpts,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ArchiveCommand {
+    /// Print out the contents of an archive without extracting it
+    ///
+    /// This command prints out the test binaries in an archive, the target triple and rustc
+    /// version it was built with, any files included from the workspace, and the sizes of the
+    /// files within it.
+    Inspect {
+        /// Archive to inspect
+        #[clap(long, value_name = "PATH")]
+        archive_file: Utf8PathBuf,
+
+        /// Archive format
+        #[clap(long, arg_enum, default_value_t, value_name = "FORMAT")]
+        archive_format: ArchiveFormatOpt,
+
+        /// Output format
+        #[clap(short = 'T', long, arg_enum, default_value_t, value_name = "FMT")]
+        message_format: MessageFormatOpts,
+    },
+}
+
+impl ArchiveCommand {
+    fn exec(self, output: OutputOpts, output_writer: &mut OutputWriter) -> Result<i32> {
+        let output = output.init();
+
+        match self {
+            Self::Inspect {
+                archive_file,
+                archive_format,
+                message_format,
+            } => {
+                let format = archive_format.to_extract_format(&archive_file)?;
+                let contents = ArchiveContents::read(&archive_file, format).map_err(|err| {
+                    ExpectedError::ArchiveInspectError {
+                        archive_file: archive_file.clone(),
+                        err,
+                    }
+                })?;
+
+                let mut writer = output_writer.stdout_writer();
+                contents.write(
+                    message_format.to_output_format(output.verbose),
+                    &mut writer,
+                    output.color.should_colorize(Stream::Stdout),
+                )?;
+                writer.flush().map_err(WriteTestListError::Io)?;
+                Ok(0)
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, ArgEnum)]
+pub(crate) enum PlatformFilterOpts {
+    Target,
+    Host,
+    Any,
+}
+
+impl Default for PlatformFilterOpts {
+    fn default() -> Self {
+        Self::Any
+    }
+}
+
+impl From<PlatformFilterOpts> for Option<BuildPlatform> {
+    fn from(opt: PlatformFilterOpts) -> Self {
+        match opt {
+            PlatformFilterOpts::Target => Some(BuildPlatform::Target),
+            PlatformFilterOpts::Host => Some(BuildPlatform::Host),
+            PlatformFilterOpts::Any => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, ArgEnum)]
+enum DebuginfoOpt {
+    Keep,
+    Strip,
+    Split,
+}
+
+impl DebuginfoOpt {
+    fn to_archive_debuginfo(self) -> ArchiveDebuginfo {
+        match self {
+            Self::Keep => ArchiveDebuginfo::Keep,
+            Self::Strip => ArchiveDebuginfo::Strip,
+            Self::Split => ArchiveDebuginfo::Split,
+        }
+    }
+}
+
+/// Options for comparing the test list with a previous one.
+#[derive(Clone, Debug)]
+struct ListDiffOpts {
+    previous: Utf8PathBuf,
+    fail_on_removed: bool,
+}
+
+impl ListDiffOpts {
+    fn read_previous(&self) -> Result<TestListSummary> {
+        let contents = std::fs::read_to_string(&self.previous)
+            .map_err(|err| ExpectedError::argument_file_read_error("diff", &self.previous, err))?;
+        serde_json::from_str(&contents)
+            .map_err(|err| ExpectedError::argument_json_parse_error("diff", &self.previous, err))
+    }
+}
+
+#[derive(Copy, Clone, Debug, ArgEnum)]
+enum ListType {
+    Full,
+    BinariesOnly,
+}
+
+impl Default for ListType {
+    fn default() -> Self {
+        Self::Full
+    }
+}
 
 #[derive(Copy, Clone, Debug, ArgEnum)]
 enum MessageFormatOpts {
@@ -387,7 +1089,7 @@ impl Default for MessageFormatOpts {
     }
 }
 
-#[derive(Debug, Args)]
+#[derive(Clone, Debug, Args)]
 #[clap(next_help_heading = "FILTER OPTIONS")]
 struct TestBuildFilter {
     /// Run ignored tests
@@ -398,10 +1100,17 @@ struct TestBuildFilter {
     )]
     run_ignored: Option<RunIgnored>,
 
-    /// Test partition, e.g. hash:1/2 or count:2/3
+    /// Test partition, e.g. hash:1/2, count:2/3, stable:1/3, timed:1/4 or plan:1:plan.json
     #[clap(long)]
     partition: Option<PartitionerBuilder>,
 
+    /// Test durations to use for timed partitioning [default: durations recorded with the profile]
+    ///
+    /// This is a timings file recorded by a previous run, for example one copied over from an
+    /// earlier CI job.
+    #[clap(long, value_name = "PATH", requires = "partition")]
+    partition_timings: Option<Utf8PathBuf>,
+
     /// Filter test binaries by build platform (DEPRECATED)
     ///
     /// Instead, use -E with 'platform(host)' or 'platform(target)'.
@@ -414,6 +1123,14 @@ struct TestBuildFilter {
     )]
     pub(crate) platform_filter: PlatformFilterOpts,
 
+    /// Do not add the rustc sysroot library directory to the dynamic library path
+    ///
+    /// By default, nextest runs `rustc --print target-libdir` and adds the resulting directory to
+    /// the dynamic library path, similar to `cargo test`. This is required for tests that link
+    /// against the standard library dynamically (e.g. with `-C prefer-dynamic`).
+    #[clap(long, hide_short_help = true, env = "NEXTEST_NO_SYSROOT_LIBDIR")]
+    no_sysroot_libdir: bool,
+
     /// Test filter expression (see {n}<https://nexte.st/book/filter-expressions>)
     #[clap(
         long,
@@ -423,6 +1140,13 @@ struct TestBuildFilter {
     )]
     filter_expr: Vec<String>,
 
+    /// Only include the tests listed in this file, or stdin if `-`
+    ///
+    /// Each line is a test ID of the form `<binary-id> <test-name>`, as printed out while running
+    /// tests. Empty lines are skipped.
+    #[clap(long, value_name = "PATH")]
+    test_list_file: Option<Utf8PathBuf>,
+
     // TODO: add regex-based filtering in the future?
     /// Test name filter
     #[clap(name = "FILTERS", help_heading = None)]
@@ -434,92 +1158,176 @@ struct TestBuildFilter {
 }
 
 impl TestBuildFilter {
+    #[allow(clippy::too_many_arguments)]
     fn compute_test_list<'g>(
         &self,
         graph: &'g PackageGraph,
         binary_list: Arc<BinaryList>,
         test_filter_builder: TestFilterBuilder,
+        profile: &NextestProfile<'_>,
         runner: &TargetRunner,
-        reuse_build: &ReuseBuildInfo,
+        path_mapper: &PathMapper,
+        prefetcher: &mut TestListPrefetcher,
     ) -> Result<TestList<'g>> {
-        let path_mapper = make_path_mapper(
-            reuse_build,
-            graph,
-            &binary_list.rust_build_meta.target_directory,
-        )?;
-
-        let rust_build_meta = binary_list.rust_build_meta.map_paths(&path_mapper);
-        let test_artifacts = RustTestArtifact::from_binary_list(
-            graph,
-            binary_list,
-            &rust_build_meta,
-            &path_mapper,
-            self.platform_filter.into(),
-        )?;
-        TestList::new(
+        let sysroot_libdir =
+            self.sysroot_libdir(binary_list.rust_build_meta.target_triple.as_ref());
+        let (test_artifacts, rust_build_meta) =
+            self.test_artifacts(graph, binary_list, profile, path_mapper, sysroot_libdir)?;
+        TestList::new_with_prefetcher(
             test_artifacts,
             rust_build_meta,
             &test_filter_builder,
             runner,
-            // TODO: do we need to allow customizing this?
-            num_cpus::get(),
+            prefetcher,
         )
         .map_err(|err| ExpectedError::CreateTestListError { err })
     }
 
+    /// Returns the test artifacts for the binaries in this list, along with the build metadata to
+    /// list and run them with.
+    fn test_artifacts<'g>(
+        &self,
+        graph: &'g PackageGraph,
+        binary_list: Arc<BinaryList>,
+        profile: &NextestProfile<'_>,
+        path_mapper: &PathMapper,
+        sysroot_libdir: Option<Utf8PathBuf>,
+    ) -> Result<(Vec<RustTestArtifact<'g>>, RustBuildMeta<TestListState>)> {
+        let mut rust_build_meta = binary_list.rust_build_meta.map_paths(path_mapper);
+        rust_build_meta.sysroot_libdir = sysroot_libdir;
+        let mut test_artifacts = RustTestArtifact::from_binary_list(
+            graph,
+            binary_list,
+            &rust_build_meta,
+            path_mapper,
+            self.platform_filter.into(),
+        )?;
+        for test_artifact in &mut test_artifacts {
+            test_artifact.single_test = profile.single_test_for(&test_artifact.to_binary_query());
+        }
+        Ok((test_artifacts, rust_build_meta))
+    }
+
+    /// Returns the sysroot library directory to add to the dynamic library path, unless
+    /// `--no-sysroot-libdir` was passed in.
+    fn sysroot_libdir(&self, target_triple: Option<&TargetTriple>) -> Option<Utf8PathBuf> {
+        if self.no_sysroot_libdir {
+            None
+        } else {
+            discover_sysroot_libdir(target_triple)
+        }
+    }
+
     fn make_test_filter_builder(
         &self,
         filter_exprs: Vec<FilteringExpr>,
+        test_tags: &TestTags,
+        profile: Option<&NextestProfile<'_>>,
+        shard_plan: Option<ShardPlan>,
     ) -> Result<TestFilterBuilder> {
         // Merge the test binary args into the patterns.
         let mut run_ignored = self.run_ignored;
         let mut patterns = self.filter.clone();
-        self.merge_test_binary_args(&mut run_ignored, &mut patterns)?;
+        let mut skip_patterns = Vec::new();
+        let mut exact = false;
+        self.merge_test_binary_args(
+            &mut run_ignored,
+            &mut patterns,
+            &mut skip_patterns,
+            &mut exact,
+        )?;
 
-        Ok(TestFilterBuilder::new(
+        let mut partition = self.partition.clone();
+        if let Some(partition) = &mut partition {
+            if partition.needs_timings() {
+                partition.set_timings(&self.load_partition_timings(profile)?);
+            }
+            if let Some(shard_plan) = shard_plan {
+                partition
+                    .set_plan(shard_plan)
+                    .map_err(|err| ExpectedError::ShardPlanError { err })?;
+            }
+        }
+
+        let mut test_filter_builder = TestFilterBuilder::new(
             run_ignored.unwrap_or_default(),
-            self.partition.clone(),
+            partition,
             &patterns,
             filter_exprs,
-        ))
+        );
+        test_filter_builder
+            .set_skip_patterns(skip_patterns)
+            .set_exact(exact);
+        test_filter_builder.set_test_tags(test_tags.clone());
+        if let Some(path) = &self.test_list_file {
+            test_filter_builder.set_test_ids(read_test_ids(path)?);
+        }
+        Ok(test_filter_builder)
     }
 
+    /// Returns the test durations to partition tests with: either the ones passed in with
+    /// `--partition-timings`, or the ones recorded with the profile.
+    fn load_partition_timings(&self, profile: Option<&NextestProfile<'_>>) -> Result<TestTimings> {
+        if let Some(path) = &self.partition_timings {
+            return TestTimings::read_from(path)
+                .map_err(|err| ExpectedError::TimingsReadError { err });
+        }
+        let profile = match profile {
+            Some(profile) => profile,
+            // Tests aren't partitioned without a profile, for example while listing binaries.
+            None => return Ok(TestTimings::default()),
+        };
+        let timings = RunStore::new(profile)
+            .read_timings()
+            .map_err(|err| ExpectedError::TimingsReadError { err })?;
+        if timings.is_none() {
+            log::warn!(
+                "no test timings recorded with this profile: \
+                 timed partitioning will fall back to partitioning by hash\n\
+                 (hint: pass in timings from an earlier run with --partition-timings)"
+            );
+        }
+        Ok(timings.unwrap_or_default())
+    }
+
+    /// Translates libtest-style arguments passed in after `--` into nextest's filtering model.
     fn merge_test_binary_args(
         &self,
         run_ignored: &mut Option<RunIgnored>,
         patterns: &mut Vec<String>,
+        skip_patterns: &mut Vec<String>,
+        exact: &mut bool,
     ) -> Result<()> {
         let mut ignore_filters = Vec::new();
         let mut read_trailing_filters = false;
 
-        let mut skip_exact = Vec::new();
+        let mut exact_args = Vec::new();
+        let mut missing_values = Vec::new();
         let mut unsupported_args = Vec::new();
 
-        patterns.extend(
-            self.test_binary_args
-                .iter()
-                .filter(|&s| {
-                    if read_trailing_filters || !s.starts_with('-') {
-                        true
-                    } else if s == "--include-ignored" {
-                        ignore_filters.push((s.clone(), RunIgnored::All));
-                        false
-                    } else if s == "--ignored" {
-                        ignore_filters.push((s.clone(), RunIgnored::IgnoredOnly));
-                        false
-                    } else if s == "--" {
-                        read_trailing_filters = true;
-                        false
-                    } else if s == "--skip" || s == "--exact" {
-                        skip_exact.push(s.clone());
-                        false
-                    } else {
-                        unsupported_args.push(s.clone());
-                        true
-                    }
-                })
-                .cloned(),
-        );
+        let mut args = self.test_binary_args.iter();
+        while let Some(s) = args.next() {
+            if read_trailing_filters || !s.starts_with('-') {
+                patterns.push(s.clone());
+            } else if s == "--include-ignored" {
+                ignore_filters.push((s.clone(), RunIgnored::All));
+            } else if s == "--ignored" {
+                ignore_filters.push((s.clone(), RunIgnored::IgnoredOnly));
+            } else if s == "--" {
+                read_trailing_filters = true;
+            } else if s == "--exact" {
+                exact_args.push(s.clone());
+            } else if s == "--skip" {
+                match args.next() {
+                    Some(pattern) => skip_patterns.push(pattern.clone()),
+                    None => missing_values.push(s.clone()),
+                }
+            } else if let Some(pattern) = s.strip_prefix("--skip=") {
+                skip_patterns.push(pattern.to_owned());
+            } else {
+                unsupported_args.push(s.clone());
+            }
+        }
 
         for (s, f) in ignore_filters {
             if let Some(run_ignored) = run_ignored {
@@ -539,10 +1347,18 @@ impl TestBuildFilter {
             }
         }
 
-        if !skip_exact.is_empty() {
+        if exact_args.len() > 1 {
+            return Err(ExpectedError::test_binary_args_parse_error(
+                "duplicated",
+                exact_args,
+            ));
+        }
+        *exact = !exact_args.is_empty();
+
+        if !missing_values.is_empty() {
             return Err(ExpectedError::test_binary_args_parse_error(
-                "unsupported\n(hint: use a filter expression instead: <https://nexte.st/book/filter-expressions>)",
-                skip_exact,
+                "missing a value",
+                missing_values,
             ));
         }
 
@@ -559,25 +1375,58 @@ impl TestBuildFilter {
 impl CargoOptions {
     fn compute_binary_list(
         &self,
+        bench: bool,
         graph: &PackageGraph,
         manifest_path: Option<&Utf8Path>,
-        output: OutputContext,
+        output_context: OutputContext,
         target_triple: Option<TargetTriple>,
+        mut on_test_binary: impl FnMut(BinaryList) -> ControlFlow<()>,
     ) -> Result<BinaryList> {
+        // Benchmarks are built with cargo bench so that they're built with the bench profile.
+        let command = if bench { "bench" } else { "test" };
+
         // Don't use the manifest path from the graph to ensure that if the user cd's into a
         // particular crate and runs cargo nextest, then it behaves identically to cargo test.
-        let mut cargo_cli = CargoCli::new("test", manifest_path, output);
+        let mut cargo_cli = CargoCli::new(command, manifest_path, output_context);
 
         // Only build tests in the cargo test invocation, do not run them.
         cargo_cli.add_args(["--no-run", "--message-format", "json-render-diagnostics"]);
         cargo_cli.add_options(self);
+        if bench && !self.has_target_selection() {
+            // By default, cargo bench also builds libraries and binaries with the test harness.
+            // Only bench targets are run, so don't build anything else.
+            cargo_cli.add_arg("--benches");
+        }
 
+        // Read Cargo's messages while the build is in progress, so that test binaries are passed
+        // in to on_test_binary as soon as they're built.
         let expression = cargo_cli.to_expression();
-        let output = expression
-            .stdout_capture()
+        let reader = expression
             .unchecked()
-            .run()
+            .reader()
             .map_err(|err| ExpectedError::build_exec_failed(cargo_cli.all_args(), err))?;
+        let mut stopped = false;
+        let mut test_binaries = BinaryList::from_messages_with(
+            BufReader::new(&reader),
+            graph,
+            target_triple,
+            |binary_list| {
+                let flow = on_test_binary(binary_list);
+                stopped = flow.is_break();
+                flow
+            },
+        )?;
+        if stopped {
+            // The rest of the build isn't needed.
+            reader
+                .kill()
+                .map_err(|err| ExpectedError::build_exec_failed(cargo_cli.all_args(), err))?;
+            return Ok(test_binaries);
+        }
+        let output = reader
+            .try_wait()
+            .map_err(|err| ExpectedError::build_exec_failed(cargo_cli.all_args(), err))?
+            .expect("cargo has exited once its output has been read");
         if !output.status.success() {
             return Err(ExpectedError::build_failed(
                 cargo_cli.all_args(),
@@ -585,10 +1434,86 @@ impl CargoOptions {
             ));
         }
 
-        let test_binaries =
-            BinaryList::from_messages(Cursor::new(output.stdout), graph, target_triple)?;
+        if self.doctests && !bench {
+            self.add_doctests(&mut test_binaries, graph, manifest_path, output_context)?;
+        }
+        // Record what the build was produced with, so that reused builds can be checked against
+        // the invocation they're reused in.
+        test_binaries.rust_build_meta.rustc_version = discover_rustc_version();
+        test_binaries.rust_build_meta.features = Some(self.feature_selection());
+        test_binaries.rust_build_meta.profile = Some(if bench {
+            self.bench_profile_name()
+        } else {
+            self.profile_name()
+        });
         Ok(test_binaries)
     }
+
+    /// Compiles doctests for each library being tested into their own binaries, and adds them to
+    /// `binary_list`.
+    fn add_doctests(
+        &self,
+        binary_list: &mut BinaryList,
+        graph: &PackageGraph,
+        manifest_path: Option<&Utf8Path>,
+        output_context: OutputContext,
+    ) -> Result<()> {
+        // Collecting doctests for the libraries whose unit tests were built reuses Cargo's package
+        // selection.
+        let package_ids: BTreeSet<_> = binary_list
+            .rust_binaries
+            .iter()
+            .filter(|binary| {
+                binary.kind == RustTestBinaryKind::LIB
+                    || binary.kind == RustTestBinaryKind::PROC_MACRO
+            })
+            .map(|binary| guppy::PackageId::new(binary.package_id.clone()))
+            .collect();
+
+        for package_id in package_ids {
+            let package = graph
+                .metadata(&package_id)
+                .map_err(FromMessagesError::PackageGraph)?;
+            let persist_dir =
+                doctest_persist_dir(&binary_list.rust_build_meta.target_directory, package);
+            // Clear out doctests persisted by earlier runs. (rustdoc creates the directory if it
+            // doesn't exist.)
+            let _ = std::fs::remove_dir_all(&persist_dir);
+
+            let package_spec = format!("{}@{}", package.name(), package.version());
+            let mut cargo_cli = CargoCli::new("test", manifest_path, output_context);
+            cargo_cli.add_args(["--doc", "--package", &package_spec]);
+            cargo_cli.add_doctest_options(self);
+            cargo_cli.add_args(["--", "-Zunstable-options", "--format", "json"]);
+
+            let expression = cargo_cli.to_expression().env("RUSTC_BOOTSTRAP", "1").env(
+                "CARGO_ENCODED_RUSTDOCFLAGS",
+                doctest_rustdocflags(&persist_dir),
+            );
+            let output = expression
+                .stdout_capture()
+                .unchecked()
+                .run()
+                .map_err(|err| ExpectedError::build_exec_failed(cargo_cli.all_args(), err))?;
+
+            // Parse the output before checking the exit status, so that errors for doctests that
+            // failed to compile are reported.
+            binary_list.add_doctests(
+                package,
+                graph.workspace().root(),
+                &persist_dir,
+                &String::from_utf8_lossy(&output.stdout),
+            )?;
+            if !output.status.success() {
+                return Err(ExpectedError::build_failed(
+                    cargo_cli.all_args(),
+                    output.status.code(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
 }
 
 /// Test runner options.
@@ -621,6 +1546,113 @@ pub struct TestRunnerOpts {
     /// Run all tests regardless of failure
     #[clap(long, conflicts_with = "no-run", overrides_with = "fail-fast")]
     no_fail_fast: bool,
+
+    /// Cancel test run once this many tests have failed
+    #[clap(
+        long,
+        value_name = "N",
+        conflicts_with_all = &["no-run", "fail-fast", "no-fail-fast"]
+    )]
+    max_fail: Option<NonZeroUsize>,
+
+    /// Run each test this many times, and report how often each test passed
+    #[clap(long, value_name = "N", conflicts_with_all = &["no-run", "until-failure"])]
+    iterations: Option<NonZeroUsize>,
+
+    /// Run tests over and over until one of them fails
+    #[clap(
+        long,
+        conflicts_with_all = &["no-run", "fail-fast", "no-fail-fast", "max-fail"]
+    )]
+    until_failure: bool,
+
+    /// With --until-failure, stop after running tests this many times
+    #[clap(long, value_name = "N", requires = "until-failure")]
+    max_iterations: Option<NonZeroUsize>,
+
+    /// Start tests in a random order
+    #[clap(long, conflicts_with = "no-run")]
+    shuffle: bool,
+
+    /// Start tests in the random order given by this seed [implies --shuffle]
+    #[clap(long, value_name = "SEED", conflicts_with = "no-run")]
+    shuffle_seed: Option<u64>,
+
+    /// Derive the NEXTEST_TEST_SEED value given to each test from this seed
+    #[clap(long, value_name = "SEED", conflicts_with = "no-run")]
+    seed: Option<u64>,
+
+    /// Run tests that pass a second time, and fail the run if their output differs
+    #[clap(long, conflicts_with_all = &["no-capture", "no-run"])]
+    check_determinism: bool,
+
+    /// Stop starting tests once the run has taken this long, and terminate tests still running
+    #[clap(
+        long,
+        value_name = "DURATION",
+        parse(try_from_str = humantime::parse_duration),
+        conflicts_with = "no-run",
+        env = "NEXTEST_MAX_RUN_TIME"
+    )]
+    max_run_time: Option<Duration>,
+
+    /// Resume an interrupted run, running only the tests that didn't finish
+    #[clap(
+        long,
+        value_name = "RUN_ID",
+        conflicts_with_all = &["no-run", "iterations", "until-failure", "test-list-file"]
+    )]
+    resume: Option<Uuid>,
+}
+
+/// Options for `cargo nextest flake-hunt`.
+#[derive(Debug, Args)]
+#[clap(next_help_heading = "HUNT OPTIONS")]
+struct FlakeHuntOpts {
+    /// How long to keep running tests for
+    #[clap(
+        long,
+        value_name = "DURATION",
+        parse(try_from_str = humantime::parse_duration)
+    )]
+    duration: Duration,
+
+    /// Run this many times as many tests at a time as the profile does
+    #[clap(long, value_name = "FACTOR", default_value = "2")]
+    oversubscribe: NonZeroUsize,
+
+    /// Number of threads to keep CPUs busy with while tests run
+    #[clap(long, value_name = "THREADS", default_value = "0")]
+    cpu_load: usize,
+
+    /// Number of threads to keep disks busy with while tests run
+    #[clap(long, value_name = "THREADS", default_value = "0")]
+    io_load: usize,
+
+    /// Seed to pick the order of each round with [default: random]
+    #[clap(long, value_name = "SEED")]
+    seed: Option<u64>,
+}
+
+/// Options for `cargo nextest bisect`.
+#[derive(Debug, Args)]
+#[clap(next_help_heading = "BISECT OPTIONS")]
+struct BisectOpts {
+    /// A commit where the tests pass (can be specified multiple times)
+    #[clap(long, value_name = "REV", required = true, multiple_occurrences(true))]
+    good: Vec<String>,
+
+    /// A commit where the tests fail
+    #[clap(long, value_name = "REV", default_value = "HEAD")]
+    bad: String,
+
+    /// Number of retries for failing tests [default: from profile]
+    #[clap(long, value_name = "N")]
+    retries: Option<usize>,
+
+    /// Directory to keep archives of each commit's tests in [default: target/nextest/bisect]
+    #[clap(long, value_name = "DIR")]
+    archive_dir: Option<Utf8PathBuf>,
 }
 
 impl TestRunnerOpts {
@@ -639,9 +1671,27 @@ impl TestRunnerOpts {
         } else if self.fail_fast {
             builder.set_fail_fast(true);
         }
+        if let Some(max_fail) = self.max_fail {
+            builder.set_max_fail(max_fail.get());
+        }
+        if let Some(iterations) = self.iterations.or(self.max_iterations) {
+            builder.set_iterations(iterations.get());
+        }
+        builder.set_until_failure(self.until_failure);
+        builder.set_shuffle(self.shuffle);
+        if let Some(seed) = self.shuffle_seed {
+            builder.set_shuffle_seed(seed);
+        }
+        if let Some(seed) = self.seed {
+            builder.set_seed(seed);
+        }
+        builder.set_check_determinism(self.check_determinism);
         if let Some(test_threads) = self.test_threads {
             builder.set_test_threads(test_threads);
         }
+        if let Some(max_run_time) = self.max_run_time {
+            builder.set_max_run_time(max_run_time);
+        }
 
         Some(builder)
     }
@@ -696,6 +1746,40 @@ struct TestReporterOpts {
         env = "NEXTEST_FINAL_STATUS_LEVEL"
     )]
     final_status_level: Option<FinalStatusLevelOpt>,
+
+    /// Append a Markdown summary of the run to this file, such as $GITHUB_STEP_SUMMARY
+    #[clap(
+        long,
+        conflicts_with = "no-run",
+        value_name = "PATH",
+        env = "NEXTEST_SUMMARY_MARKDOWN"
+    )]
+    summary_markdown: Option<Utf8PathBuf>,
+
+    /// Show a full-screen dashboard while tests run, if stderr is a terminal
+    #[clap(
+        long,
+        conflicts_with_all = &["no-capture", "no-run"],
+        env = "NEXTEST_DASHBOARD"
+    )]
+    dashboard: bool,
+
+    /// List each failing test separately at the end of the run, rather than grouping tests that
+    /// failed the same way
+    #[clap(long, conflicts_with = "no-run", env = "NEXTEST_NO_GROUP_FAILURES")]
+    no_group_failures: bool,
+
+    /// Break the results down by package and test binary at the end of the run
+    #[clap(long, conflicts_with = "no-run", env = "NEXTEST_BREAKDOWN")]
+    breakdown: bool,
+
+    /// Prefix lines of test output with the time since the test started
+    #[clap(
+        long,
+        conflicts_with_all = &["no-capture", "no-run"],
+        env = "NEXTEST_OUTPUT_TIMESTAMPS"
+    )]
+    output_timestamps: bool,
 }
 
 impl TestReporterOpts {
@@ -714,6 +1798,13 @@ impl TestReporterOpts {
         if let Some(final_status_level) = self.final_status_level {
             builder.set_final_status_level(final_status_level.into_final_status_level());
         }
+        if let Some(summary_markdown) = &self.summary_markdown {
+            builder.set_summary_markdown(summary_markdown);
+        }
+        builder.set_dashboard(self.dashboard);
+        builder.set_no_group_failures(self.no_group_failures);
+        builder.set_breakdown(self.breakdown);
+        builder.set_output_timestamps(self.output_timestamps);
         builder
     }
 }
@@ -745,6 +1836,226 @@ impl FinalStatusLevelOpt {
     }
 }
 
+#[derive(Clone, Copy, Debug, ArgEnum)]
+enum RunMessageFormatOpt {
+    Human,
+    Json,
+    LibtestJson,
+    Tap,
+    GithubActions,
+    Teamcity,
+    Sarif,
+}
+
+impl RunMessageFormatOpt {
+    fn into_message_format(self) -> MessageFormat {
+        match self {
+            Self::Human => MessageFormat::Human,
+            Self::Json => MessageFormat::Json,
+            Self::LibtestJson => MessageFormat::LibtestJson,
+            Self::Tap => MessageFormat::Tap,
+            Self::GithubActions => MessageFormat::GithubActions,
+            Self::Teamcity => MessageFormat::Teamcity,
+            Self::Sarif => MessageFormat::Sarif,
+        }
+    }
+}
+
+impl Default for RunMessageFormatOpt {
+    fn default() -> Self {
+        Self::Human
+    }
+}
+
+/// Builds and runs tests at each commit `cargo nextest bisect` checks out.
+///
+/// The workspace can change from one commit to the next, so it's loaded again at each commit.
+#[derive(Debug)]
+struct BisectApp {
+    output: OutputOpts,
+    config_opts: ConfigOpts,
+    manifest_path: Option<Utf8PathBuf>,
+    profile: Option<String>,
+    cargo_options: CargoOptions,
+    build_filter: TestBuildFilter,
+    bisect_opts: BisectOpts,
+}
+
+impl BisectApp {
+    fn exec(&self, output_writer: &mut OutputWriter) -> Result<()> {
+        // Find the workspace and the target directory before any other commit is checked out.
+        let base = self.base_app(ReuseBuildOpts::default(), output_writer)?;
+        let archive_dir = match &self.bisect_opts.archive_dir {
+            Some(archive_dir) => archive_dir.clone(),
+            None => base
+                .graph()
+                .workspace()
+                .target_directory()
+                .join("nextest")
+                .join("bisect"),
+        };
+        std::fs::create_dir_all(&archive_dir).map_err(|err| {
+            ExpectedError::BisectArchiveWriteError {
+                path: archive_dir.clone(),
+                err,
+            }
+        })?;
+        let archive_key = self.archive_key(base.output);
+
+        let (mut bisect, mut state) = GitBisect::start(
+            &base.workspace_root,
+            &self.bisect_opts.bad,
+            &self.bisect_opts.good,
+        )?;
+        while state == BisectState::Testing {
+            let commit = bisect.head()?;
+            let description = bisect.describe(&commit)?;
+            log::info!("testing {description}");
+            let archive_file = archive_dir.join(format!("{commit}-{archive_key}.tar.zst"));
+            let verdict = self.test_commit(&archive_file, output_writer)?;
+            log::info!("marking {description} as {verdict}");
+            state = bisect.mark(verdict)?;
+        }
+
+        let mut writer = output_writer.stdout_writer();
+        match &state {
+            BisectState::FirstBad(commit) => {
+                writeln!(writer, "first bad commit: {}", bisect.describe(commit)?)
+            }
+            BisectState::OnlySkipped(commits) => {
+                let descriptions: Vec<_> = commits
+                    .iter()
+                    .map(|commit| bisect.describe(commit))
+                    .collect::<Result<_, _>>()?;
+                writeln!(
+                    writer,
+                    "some commits were skipped, so the first bad commit is one of these {}:",
+                    descriptions.len()
+                )
+                .and_then(|()| {
+                    descriptions
+                        .iter()
+                        .try_for_each(|description| writeln!(writer, "    {description}"))
+                })
+            }
+            BisectState::Testing => unreachable!("bisection ended"),
+        }
+        .map_err(WriteTestListError::Io)?;
+        writer.flush().map_err(WriteTestListError::Io)?;
+
+        bisect.reset()?;
+        Ok(())
+    }
+
+    /// Builds and runs tests at the commit that's checked out, reusing the archive at
+    /// `archive_file` if the commit has been built before.
+    fn test_commit(
+        &self,
+        archive_file: &Utf8Path,
+        output_writer: &mut OutputWriter,
+    ) -> Result<BisectVerdict> {
+        if archive_file.exists() {
+            log::info!("reusing archive at {archive_file}");
+        } else {
+            // Archive to another file first, so that archives cut short by an interruption aren't
+            // reused.
+            let partial_file = Utf8PathBuf::from(format!("{archive_file}.partial"));
+            if let Err(err) = self.archive_commit(&partial_file, output_writer) {
+                err.display_to_stderr();
+                log::warn!("failed to build tests, skipping commit");
+                return Ok(BisectVerdict::Skip);
+            }
+            std::fs::rename(&partial_file, archive_file).map_err(|err| {
+                ExpectedError::BisectArchiveWriteError {
+                    path: archive_file.to_owned(),
+                    err,
+                }
+            })?;
+        }
+
+        match self.run_commit(archive_file, output_writer) {
+            Ok(Some(true)) => Ok(BisectVerdict::Good),
+            Ok(Some(false)) => Ok(BisectVerdict::Bad),
+            Ok(None) => {
+                log::warn!("no tests match the filters, skipping commit");
+                Ok(BisectVerdict::Skip)
+            }
+            // The run was interrupted.
+            Err(ExpectedError::TestRunFailed) => Err(ExpectedError::test_run_failed()),
+            Err(err) => {
+                err.display_to_stderr();
+                log::warn!("failed to run tests, skipping commit");
+                Ok(BisectVerdict::Skip)
+            }
+        }
+    }
+
+    fn archive_commit(
+        &self,
+        archive_file: &Utf8Path,
+        output_writer: &mut OutputWriter,
+    ) -> Result<()> {
+        let base = self.base_app(ReuseBuildOpts::default(), output_writer)?;
+        base.exec_archive(
+            archive_file,
+            ArchiveFormatOpt::Auto,
+            Some(ArchiveCompression::Zstd { level: 0 }),
+            self.profile.as_deref(),
+            None,
+            Some(ArchiveDebuginfo::Keep),
+            None,
+            output_writer,
+        )
+    }
+
+    fn run_commit(
+        &self,
+        archive_file: &Utf8Path,
+        output_writer: &mut OutputWriter,
+    ) -> Result<Option<bool>> {
+        let reuse_build = ReuseBuildOpts {
+            archive_file: Some(archive_file.to_owned()),
+            ..ReuseBuildOpts::default()
+        };
+        let base = self.base_app(reuse_build, output_writer)?;
+        let app = App::new(base, self.build_filter.clone())?;
+        app.exec_bisect_run(
+            self.profile.as_deref(),
+            self.bisect_opts.retries,
+            output_writer,
+        )
+    }
+
+    fn base_app(
+        &self,
+        reuse_build: ReuseBuildOpts,
+        output_writer: &mut OutputWriter,
+    ) -> Result<BaseApp> {
+        BaseApp::new(
+            self.output,
+            reuse_build,
+            self.cargo_options.clone(),
+            self.config_opts.clone(),
+            self.manifest_path.clone(),
+            // Archives include cargo metadata with dependencies, as with `cargo nextest archive`.
+            true,
+            output_writer,
+        )
+    }
+
+    /// Returns a key for the options that archives depend on, so that archives built with
+    /// different options aren't mixed up.
+    fn archive_key(&self, output: OutputContext) -> String {
+        let mut cargo_cli = CargoCli::new("test", self.manifest_path.as_deref(), output);
+        cargo_cli.add_options(&self.cargo_options);
+        let mut hasher = DefaultHasher::new();
+        cargo_cli.all_args().hash(&mut hasher);
+        // The profile decides which other files are included.
+        self.profile.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+}
+
 #[derive(Debug)]
 struct BaseApp {
     output: OutputContext,
@@ -753,6 +2064,10 @@ struct BaseApp {
     workspace_root: Utf8PathBuf,
     manifest_path: Option<Utf8PathBuf>,
     reuse_build: ReuseBuildInfo,
+    // Path remap rules passed in on the command line.
+    path_remaps: Vec<PathRemap>,
+    build_mismatch: BuildMismatchOpt,
+    force: bool,
     cargo_opts: CargoOptions,
     config_opts: ConfigOpts,
 
@@ -773,6 +2088,9 @@ impl BaseApp {
         let output = output.init();
         reuse_build.check_experimental(output);
 
+        let build_mismatch = reuse_build.build_mismatch;
+        let force = reuse_build.force;
+        let path_remaps = reuse_build.path_remap.clone();
         let reuse_build = reuse_build.process(output, writer)?;
 
         let graph_data = match reuse_build.cargo_metadata() {
@@ -811,7 +2129,8 @@ impl BaseApp {
         };
 
         let root_manifest_path = workspace_root.join("Cargo.toml");
-        if !root_manifest_path.exists() {
+        // Workspaces with test binaries built outside of Cargo don't need a Cargo.toml.
+        if !reuse_build.is_external() && !root_manifest_path.exists() {
             // This doesn't happen in normal use, but is a common situation if the build is being
             // reused.
             let reuse_build_kind = if reuse_build.workspace_remap().is_some() {
@@ -828,6 +2147,13 @@ impl BaseApp {
             });
         }
 
+        if cfg!(target_os = "macos") && reuse_build.is_extracted() {
+            let config = config_opts.make_config(&workspace_root, &graph_data.1)?;
+            if config.macos_resign() {
+                reuse_build.resign_extracted_binaries();
+            }
+        }
+
         let cargo_configs = CargoConfigs::new(&cargo_opts.config)?;
 
         Ok(Self {
@@ -835,6 +2161,9 @@ impl BaseApp {
             graph_data,
             workspace_root,
             reuse_build,
+            path_remaps,
+            build_mismatch,
+            force,
             manifest_path,
             cargo_opts,
             config_opts,
@@ -849,16 +2178,63 @@ impl BaseApp {
             .get_or_init(|| runner_for_target(&self.cargo_configs, triple))
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn exec_archive(
         &self,
         output_file: &Utf8Path,
         format: ArchiveFormatOpt,
-        zstd_level: i32,
+        compression: Option<ArchiveCompression>,
+        profile_name: Option<&str>,
+        baseline: Option<&Utf8Path>,
+        debuginfo: Option<ArchiveDebuginfo>,
+        debuginfo_file: Option<&Utf8Path>,
         output_writer: &mut OutputWriter,
     ) -> Result<()> {
+        let config = self
+            .config_opts
+            .make_config(&self.workspace_root, self.graph())?;
+        let profile = config
+            .profile(profile_name.unwrap_or(NextestConfig::DEFAULT_PROFILE))
+            .map_err(ExpectedError::profile_not_found)?;
+
         // Do format detection first so we fail immediately.
-        let format = format.to_archive_format(output_file)?;
-        let binary_list = self.build_binary_list()?;
+        let compression = compression.unwrap_or_else(|| profile.archive_compression());
+        if let Some(format) = format.to_create_format(output_file) {
+            if format != compression.format() {
+                return Err(ExpectedError::ArchiveCompressionMismatch {
+                    archive_file: output_file.to_owned(),
+                    format,
+                    compression,
+                });
+            }
+        }
+
+        let debuginfo = debuginfo.unwrap_or_else(|| profile.archive_debuginfo());
+        let debuginfo_file = match debuginfo_file {
+            Some(debuginfo_file) => debuginfo_file.to_owned(),
+            None => ArchiveDebuginfo::default_debuginfo_file(output_file, compression),
+        };
+        if debuginfo == ArchiveDebuginfo::Split && debuginfo_file == output_file {
+            return Err(ExpectedError::ArchiveDebuginfoFileConflict {
+                archive_file: output_file.to_owned(),
+            });
+        }
+
+        let baseline = match baseline {
+            Some(baseline) => {
+                let format = ArchiveFormatOpt::Auto.to_extract_format(baseline)?;
+                let baseline = ArchiveBaseline::read(baseline, format).map_err(|err| {
+                    ExpectedError::ArchiveCreateError {
+                        archive_file: output_file.to_owned(),
+                        err,
+                    }
+                })?;
+                Some(baseline)
+            }
+            None => None,
+        };
+
+        let binary_list = self.build_binary_list(|_| ControlFlow::Continue(()))?;
         let path_mapper = PathMapper::noop();
 
         let mut reporter = ArchiveReporter::new(self.output.verbose);
@@ -873,8 +2249,12 @@ impl BaseApp {
             // Note that path_mapper is currently a no-op -- we don't support reusing builds for
             // archive creation because it's too confusing.
             &path_mapper,
-            format,
-            zstd_level,
+            compression,
+            &self.workspace_root,
+            profile.archive_include(),
+            baseline.as_ref(),
+            debuginfo,
+            &debuginfo_file,
             output_file,
             |event| {
                 reporter.report_event(event, &mut writer)?;
@@ -889,9 +2269,20 @@ impl BaseApp {
         Ok(())
     }
 
-    fn build_binary_list(&self) -> Result<Arc<BinaryList>> {
+    /// Builds test binaries unless a build is being reused, calling `on_test_binary` as soon as
+    /// each one is built.
+    ///
+    /// If `on_test_binary` returns [`ControlFlow::Break`], the build is stopped, and the binaries
+    /// built so far are returned.
+    fn build_binary_list(
+        &self,
+        on_test_binary: impl FnMut(BinaryList) -> ControlFlow<()>,
+    ) -> Result<Arc<BinaryList>> {
         let binary_list = match self.reuse_build.binaries_metadata() {
-            Some(MetadataOrPath::Metadata(binary_list)) => binary_list.clone(),
+            Some(MetadataOrPath::Metadata(binary_list)) => {
+                self.check_reused_build(binary_list)?;
+                binary_list.clone()
+            }
             Some(MetadataOrPath::Path(path)) => {
                 let raw_binary_list = std::fs::read_to_string(path).map_err(|err| {
                     ExpectedError::argument_file_read_error("binaries-metadata", path, err)
@@ -900,22 +2291,91 @@ impl BaseApp {
                     .map_err(|err| {
                         ExpectedError::argument_json_parse_error("binaries-metadata", path, err)
                     })?;
-                Arc::new(BinaryList::from_summary(binary_list))
-            }
-            None => {
-                let target_triple =
-                    discover_target_triple(&self.cargo_configs, self.cargo_opts.target.as_deref());
-                Arc::new(self.cargo_opts.compute_binary_list(
-                    self.graph(),
-                    self.manifest_path.as_deref(),
-                    self.output,
-                    target_triple,
-                )?)
+                let binary_list = BinaryList::from_summary(binary_list);
+                self.check_reused_build(&binary_list)?;
+                Arc::new(binary_list)
             }
+            None => Arc::new(self.cargo_opts.compute_binary_list(
+                false,
+                self.graph(),
+                self.manifest_path.as_deref(),
+                self.output,
+                self.build_target_triple(),
+                on_test_binary,
+            )?),
         };
         Ok(binary_list)
     }
 
+    /// Builds bench targets, and returns a binary list containing just them.
+    fn build_bench_binary_list(&self) -> Result<Arc<BinaryList>> {
+        let mut binary_list = self.cargo_opts.compute_binary_list(
+            true,
+            self.graph(),
+            self.manifest_path.as_deref(),
+            self.output,
+            self.build_target_triple(),
+            |_| ControlFlow::Continue(()),
+        )?;
+        binary_list
+            .rust_binaries
+            .retain(|binary| binary.kind == RustTestBinaryKind::BENCH);
+        Ok(Arc::new(binary_list))
+    }
+
+    /// Returns the target triple binaries are built for.
+    fn build_target_triple(&self) -> Option<TargetTriple> {
+        // With more than one --target, the binary list is split up by target triple once it's
+        // built.
+        match self.cargo_opts.target.as_slice() {
+            [] => discover_target_triple(&self.cargo_configs, None),
+            [target] => discover_target_triple(&self.cargo_configs, Some(target)),
+            _ => None,
+        }
+    }
+
+    /// Checks a reused build against the target triple, workspace, rustc version, features and
+    /// profile for this invocation.
+    fn check_reused_build(&self, binary_list: &BinaryList) -> Result<()> {
+        let target_triple = match self.cargo_opts.target.as_slice() {
+            [target] => discover_target_triple(&self.cargo_configs, Some(target))
+                .map(|triple| triple.triple),
+            _ => None,
+        };
+        let current = self
+            .cargo_opts
+            .current_build_config(discover_rustc_version(), target_triple);
+        let mismatches = ReuseBuildMismatch::compute(binary_list, self.graph(), &current);
+
+        // Incompatible builds are errors unless --force is passed in, and other mismatches are
+        // errors with --build-mismatch error.
+        let (errors, warnings): (Vec<_>, Vec<_>) = mismatches.into_iter().partition(|mismatch| {
+            !self.force
+                && (mismatch.is_incompatible() || self.build_mismatch == BuildMismatchOpt::Error)
+        });
+        if !errors.is_empty() {
+            return Err(ExpectedError::ReuseBuildMismatch { mismatches: errors });
+        }
+        for mismatch in &warnings {
+            log::warn!(
+                "reused build doesn't match the current invocation: {mismatch}\n(hint: {})",
+                mismatch_hint(mismatch),
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns the target triples passed in with `--target`, if there's more than one.
+    fn multiple_target_triples(&self) -> Option<Vec<TargetTriple>> {
+        (self.cargo_opts.target.len() > 1).then(|| {
+            self.cargo_opts
+                .target
+                .iter()
+                .filter_map(|triple| discover_target_triple(&self.cargo_configs, Some(triple)))
+                .collect()
+        })
+    }
+
     #[inline]
     fn graph(&self) -> &PackageGraph {
         &self.graph_data.1
@@ -937,6 +2397,61 @@ fn check_experimental_filtering(_output: OutputContext) {
     }
 }
 
+fn load_previous_run(profile: &NextestProfile<'_>) -> Result<PreviousRun> {
+    let run_store = RunStore::new(profile);
+    let last_run = run_store.read_last_run()?;
+    let timings = run_store.read_timings()?;
+    if last_run.is_none() && timings.is_none() {
+        log::warn!(
+            "no previous run recorded with this profile: \
+             filter expressions based on previous runs won't match any tests"
+        );
+    }
+
+    let mut previous_run = last_run.unwrap_or_default();
+    for (binary_id, test_name, duration) in timings.iter().flat_map(|timings| timings.iter()) {
+        previous_run.insert_duration(binary_id.to_owned(), test_name.to_owned(), duration);
+    }
+    Ok(previous_run)
+}
+
+fn read_test_ids(path: &Utf8Path) -> Result<Vec<(String, String)>> {
+    let contents = if path == "-" {
+        let mut contents = String::new();
+        std::io::stdin()
+            .read_to_string(&mut contents)
+            .map(|_| contents)
+    } else {
+        std::fs::read_to_string(path)
+    };
+    let contents = contents
+        .map_err(|err| ExpectedError::argument_file_read_error("test-list-file", path, err))?;
+    parse_test_ids(path, &contents)
+}
+
+fn parse_test_ids(path: &Utf8Path, contents: &str) -> Result<Vec<(String, String)>> {
+    let mut test_ids = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        // Binary IDs can't contain spaces, but test names (e.g. for doctests) can.
+        match line.trim().split_once(' ') {
+            Some((binary_id, test_name)) if !test_name.trim().is_empty() => {
+                test_ids.push((binary_id.to_owned(), test_name.trim().to_owned()));
+            }
+            _ => {
+                return Err(ExpectedError::TestListFileParseError {
+                    file_name: path.to_owned(),
+                    line_number: index + 1,
+                    line: line.to_owned(),
+                })
+            }
+        }
+    }
+    Ok(test_ids)
+}
+
 impl App {
     fn new(base: BaseApp, build_filter: TestBuildFilter) -> Result<Self> {
         check_experimental_filtering(base.output);
@@ -944,12 +2459,47 @@ impl App {
         Ok(Self { base, build_filter })
     }
 
-    fn build_filtering_expressions(&self) -> Result<Vec<FilteringExpr>> {
-        let (exprs, all_errors): (Vec<_>, Vec<_>) = self
-            .build_filter
-            .filter_expr
+    /// Parses filter expressions, which can refer to aliases and tags defined in the config.
+    ///
+    /// `since()` runs git within the workspace to find changed packages.
+    ///
+    /// If a profile is passed in, the outcome of the last run with it is used for the `failed()`,
+    /// `flaky()` and `skipped()` predicates, and recorded test timings for the `slower_than()` and
+    /// `faster_than()` predicates.
+    fn build_filtering_expressions(
+        &self,
+        config: &NextestConfig,
+        profile: Option<&NextestProfile<'_>>,
+    ) -> Result<Vec<FilteringExpr>> {
+        self.parse_filtering_expressions(&self.build_filter.filter_expr, config, profile)
+    }
+
+    /// Parses the given filter expressions, the same way as [`Self::build_filtering_expressions`].
+    fn parse_filtering_expressions(
+        &self,
+        inputs: &[String],
+        config: &NextestConfig,
+        profile: Option<&NextestProfile<'_>>,
+    ) -> Result<Vec<FilteringExpr>> {
+        let aliases = config.filterset_aliases();
+        let needs_previous_run = inputs.iter().any(|input| aliases.needs_previous_run(input));
+        let previous_run = match profile {
+            Some(profile) if needs_previous_run => Some(load_previous_run(profile)?),
+            _ => None,
+        };
+
+        let changed_files = GitChangedFiles::new(&self.base.workspace_root);
+        let mut cx = ParseContext::new();
+        cx.set_aliases(aliases)
+            .set_tags(config.test_tags())
+            .set_changed_files(&changed_files);
+        if let Some(previous_run) = &previous_run {
+            cx.set_previous_run(previous_run);
+        }
+
+        let (exprs, all_errors): (Vec<_>, Vec<_>) = inputs
             .iter()
-            .map(|input| FilteringExpr::parse(input, self.base.graph()))
+            .map(|input| FilteringExpr::parse_with_context(input, self.base.graph(), &cx))
             .partition_result();
 
         if !all_errors.is_empty() {
@@ -959,18 +2509,161 @@ impl App {
         }
     }
 
+    /// Parses filter expressions and reads the shard plan, if any, then makes a test filter
+    /// builder from them.
+    fn make_test_filter_builder(
+        &self,
+        config: &NextestConfig,
+        profile: Option<&NextestProfile<'_>>,
+    ) -> Result<TestFilterBuilder> {
+        let filter_exprs = self.build_filtering_expressions(config, profile)?;
+        let shard_plan = self.read_shard_plan(config)?;
+        self.build_filter.make_test_filter_builder(
+            filter_exprs,
+            config.test_tags(),
+            profile,
+            shard_plan,
+        )
+    }
+
+    /// Reads the shard plan passed in with `--partition plan:M:PATH`.
+    ///
+    /// Filtersets in the plan can refer to aliases and tags, but not to previous runs.
+    fn read_shard_plan(&self, config: &NextestConfig) -> Result<Option<ShardPlan>> {
+        let path = match self
+            .build_filter
+            .partition
+            .as_ref()
+            .and_then(|partition| partition.plan_path())
+        {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        let changed_files = GitChangedFiles::new(&self.base.workspace_root);
+        let mut cx = ParseContext::new();
+        cx.set_aliases(config.filterset_aliases())
+            .set_tags(config.test_tags())
+            .set_changed_files(&changed_files);
+        ShardPlan::read_from(path, self.base.graph(), &cx)
+            .map(Some)
+            .map_err(|err| ExpectedError::ShardPlanError { err })
+    }
+
     fn build_test_list(
         &self,
         binary_list: Arc<BinaryList>,
         test_filter_builder: TestFilterBuilder,
-        target_runner: &TargetRunner,
-    ) -> Result<TestList> {
-        self.build_filter.compute_test_list(
-            self.base.graph(),
+        config: &NextestConfig,
+        profile: &NextestProfile<'_>,
+        mut prefetcher: TestListPrefetcher,
+    ) -> Result<(TestList, TargetRunner)> {
+        let path_mapper =
+            self.make_path_mapper(config, &binary_list.rust_build_meta.target_directory)?;
+
+        let target_triples = match self.base.multiple_target_triples() {
+            Some(target_triples) => target_triples,
+            None => {
+                let target_runner = self
+                    .base
+                    .load_runner(binary_list.rust_build_meta.target_triple.as_ref());
+                let test_list = self.build_filter.compute_test_list(
+                    self.base.graph(),
+                    binary_list,
+                    test_filter_builder,
+                    profile,
+                    target_runner,
+                    &path_mapper,
+                    &mut prefetcher,
+                )?;
+                return Ok((test_list, target_runner.clone()));
+            }
+        };
+
+        // Build a test list for each target triple, then merge them.
+        let (host_binary_list, target_binary_lists) = binary_list.split_by_target(&target_triples);
+        let mut test_lists = Vec::with_capacity(target_triples.len() + 1);
+        let mut target_runners = Vec::with_capacity(target_triples.len());
+        for binary_list in target_binary_lists {
+            let triple = binary_list
+                .rust_build_meta
+                .target_triple
+                .clone()
+                .expect("split binary lists always have a target triple");
+            let target_runner = runner_for_target(&self.base.cargo_configs, Some(&triple));
+            test_lists.push(self.build_filter.compute_test_list(
+                self.base.graph(),
+                Arc::new(binary_list),
+                test_filter_builder.clone(),
+                profile,
+                &target_runner,
+                &path_mapper,
+                &mut prefetcher,
+            )?);
+            target_runners.push((triple.triple, target_runner));
+        }
+
+        // Binaries built for the host platform are only listed once, and they come first so that
+        // the merged list uses the host platform's build metadata for them.
+        let target_runner = TargetRunner::for_target_triples(target_runners);
+        test_lists.insert(
+            0,
+            self.build_filter.compute_test_list(
+                self.base.graph(),
+                Arc::new(host_binary_list),
+                test_filter_builder,
+                profile,
+                &target_runner,
+                &path_mapper,
+                &mut prefetcher,
+            )?,
+        );
+
+        Ok((TestList::merge(test_lists), target_runner))
+    }
+
+    /// Builds test binaries and creates a test list from them.
+    ///
+    /// While the build is in progress, the tests in each binary are listed as soon as it's built.
+    fn build_and_list_tests(
+        &self,
+        test_filter_builder: TestFilterBuilder,
+        config: &NextestConfig,
+        profile: &NextestProfile<'_>,
+        test_threads: Option<TestThreads>,
+    ) -> Result<(TestList<'_>, TargetRunner)> {
+        let mut prefetcher = make_test_list_prefetcher(profile, test_threads)?;
+        let lister = TestLister::new(self, &test_filter_builder, config, profile)?;
+        let binary_list = match &lister.reused_binary_list {
+            Some(binary_list) => binary_list.clone(),
+            None => self.base.build_binary_list(|built| {
+                lister.prefetch(built, &mut prefetcher);
+                ControlFlow::Continue(())
+            })?,
+        };
+
+        self.build_test_list(
             binary_list,
             test_filter_builder,
-            target_runner,
+            config,
+            profile,
+            prefetcher,
+        )
+    }
+
+    fn make_path_mapper(
+        &self,
+        config: &NextestConfig,
+        target_directory: &Utf8Path,
+    ) -> Result<PathMapper> {
+        // Rules passed in on the command line take precedence over ones in the config.
+        let mut path_remaps = self.base.path_remaps.clone();
+        path_remaps.extend(config.path_remaps());
+        make_path_mapper(
             &self.base.reuse_build,
+            self.base.graph(),
+            target_directory,
+            &path_remaps,
         )
     }
 
@@ -1001,17 +2694,23 @@ impl App {
 
     fn exec_list(
         &self,
+        profile_name: Option<&str>,
         message_format: MessageFormatOpts,
         list_type: ListType,
+        list_diff: Option<&ListDiffOpts>,
         output_writer: &mut OutputWriter,
     ) -> Result<()> {
-        let filter_exprs = self.build_filtering_expressions()?;
-        let test_filter_builder = self.build_filter.make_test_filter_builder(filter_exprs)?;
-
-        let binary_list = self.base.build_binary_list()?;
-
         match list_type {
             ListType::BinariesOnly => {
+                // Check filter expressions for errors, even though they aren't used to list
+                // binaries.
+                let config = self
+                    .base
+                    .config_opts
+                    .make_config(&self.base.workspace_root, self.base.graph())?;
+                self.make_test_filter_builder(&config, None)?;
+
+                let binary_list = self.base.build_binary_list(|_| ControlFlow::Continue(()))?;
                 let mut writer = output_writer.stdout_writer();
                 binary_list.write(
                     message_format.to_output_format(self.base.output.verbose),
@@ -1021,28 +2720,549 @@ impl App {
                 writer.flush().map_err(WriteTestListError::Io)?;
             }
             ListType::Full => {
-                let target_runner = self
+                let config = self
                     .base
-                    .load_runner(binary_list.rust_build_meta.target_triple.as_ref());
-                let test_list =
-                    self.build_test_list(binary_list, test_filter_builder, target_runner)?;
+                    .config_opts
+                    .make_config(&self.base.workspace_root, self.base.graph())?;
+                let profile = self.load_profile(profile_name, &config)?;
+                let test_filter_builder = self.make_test_filter_builder(&config, Some(&profile))?;
+                let (test_list, _) =
+                    self.build_and_list_tests(test_filter_builder, &config, &profile, None)?;
+
+                let mut writer = output_writer.stdout_writer();
+                match list_diff {
+                    Some(list_diff) => {
+                        let previous = list_diff.read_previous()?;
+                        let diff = TestListDiff::new(&previous, &test_list.to_summary());
+                        diff.write(
+                            message_format.to_output_format(self.base.output.verbose),
+                            &mut writer,
+                            self.base.output.color.should_colorize(Stream::Stdout),
+                        )?;
+                        writer.flush().map_err(WriteTestListError::Io)?;
+                        if list_diff.fail_on_removed && diff.has_removed() {
+                            return Err(ExpectedError::TestsRemoved);
+                        }
+                    }
+                    None => {
+                        test_list.write(
+                            message_format.to_output_format(self.base.output.verbose),
+                            &mut writer,
+                            self.base.output.color.should_colorize(Stream::Stdout),
+                        )?;
+                        writer.flush().map_err(WriteTestListError::Io)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn exec_run(
+        &self,
+        profile_name: Option<&str>,
+        no_capture: bool,
+        live_output: bool,
+        live_output_filter: &[String],
+        runner_opts: &TestRunnerOpts,
+        reporter_opts: &TestReporterOpts,
+        message_format: RunMessageFormatOpt,
+        output_writer: &mut OutputWriter,
+    ) -> Result<()> {
+        let config = self
+            .base
+            .config_opts
+            .make_config(&self.base.workspace_root, self.base.graph())?;
+        let profile = self.load_profile(profile_name, &config)?;
+
+        let mut test_filter_builder = self.make_test_filter_builder(&config, Some(&profile))?;
+        // A resumed run is made up of the tests the interrupted run was started with.
+        let resumed_run = match runner_opts.resume {
+            Some(run_id) => {
+                let resumed_run = RunStore::new(&profile)
+                    .read_checkpoint(run_id)
+                    .map_err(|err| ExpectedError::CheckpointReadError { err })?;
+                test_filter_builder.set_test_ids(resumed_run.test_ids());
+                Some(resumed_run)
+            }
+            None => None,
+        };
+
+        let live_output_filter =
+            self.parse_filtering_expressions(live_output_filter, &config, Some(&profile))?;
+
+        if runner_opts.no_run {
+            self.build_and_list_tests(
+                test_filter_builder,
+                &config,
+                &profile,
+                runner_opts.test_threads,
+            )?;
+            return Ok(());
+        }
+
+        // Tests are run as soon as the binaries they're in are built and listed.
+        let mut prefetcher = make_test_list_prefetcher(&profile, runner_opts.test_threads)?;
+        let lister = TestLister::new(self, &test_filter_builder, &config, &profile)?;
+        let test_list = lister.initial_test_list();
+        // The test lists created during the first run, which are run again if failed tests are
+        // retried from the dashboard.
+        let mut listed: Option<Vec<TestList>> = None;
+
+        let mut resumed_run = resumed_run;
+        loop {
+            let output = output_writer.reporter_output();
+
+            let mut reporter = reporter_opts
+                .to_builder(no_capture)
+                .set_live_output(live_output)
+                .set_verbose(self.base.output.verbose)
+                .set_message_format(message_format.into_message_format())
+                .build(&test_list, &profile, output);
+            if self.base.output.color.should_colorize(Stream::Stderr) {
+                reporter.colorize();
+            }
+
+            let handler = SignalHandlerKind::Standard;
+            let mut runner_builder = runner_opts
+                .to_builder(no_capture)
+                .expect("--no-run was handled above");
+            runner_builder
+                .set_live_output(live_output)
+                .set_live_output_filter(live_output_filter.clone());
+            if let Some(resumed_run) = resumed_run.take() {
+                runner_builder.set_resumed_run(resumed_run);
+            }
+
+            let batches = TestListBatches::new();
+            let mut runner = runner_builder.build(
+                &test_list,
+                profile.clone(),
+                handler,
+                lister.target_runner.clone(),
+            )?;
+
+            configure_handle_inheritance(no_capture)?;
+            let mut failure_retry = FailureRetry::new();
+            let callback = |event| {
+                failure_retry.write_event(&event);
+                // Write and flush the event.
+                reporter.report_event(event).map_err(ExpectedError::from)
+            };
+            let run_stats = match &listed {
+                None => runner.try_execute_while_listing(
+                    &batches,
+                    |sender| lister.list_tests(&mut prefetcher, sender),
+                    callback,
+                )?,
+                Some(test_lists) => {
+                    let test_lists = test_lists.clone();
+                    runner.try_execute_while_listing(
+                        &batches,
+                        |sender| {
+                            for test_list in test_lists {
+                                sender.send(async move { Ok(test_list) });
+                            }
+                            Ok(())
+                        },
+                        callback,
+                    )?
+                }
+            };
+            if listed.is_none() {
+                listed = Some(batches.iter().cloned().collect());
+            }
+            // Failed tests are run again if that was asked for from the dashboard, by resuming the
+            // run with every other test counted as finished.
+            if reporter.retry_failures_requested() {
+                if let Some(retry) = failure_retry.into_resumed_run() {
+                    resumed_run = Some(retry);
+                    continue;
+                }
+            }
+            if !run_stats.is_success() {
+                return Err(ExpectedError::test_run_failed());
+            }
+            return Ok(());
+        }
+    }
+
+    fn exec_flake_hunt(
+        &self,
+        profile_name: Option<&str>,
+        hunt_opts: &FlakeHuntOpts,
+        message_format: MessageFormatOpts,
+        output_writer: &mut OutputWriter,
+    ) -> Result<()> {
+        let config = self
+            .base
+            .config_opts
+            .make_config(&self.base.workspace_root, self.base.graph())?;
+        let profile = self.load_profile(profile_name, &config)?;
+
+        let test_filter_builder = self.make_test_filter_builder(&config, Some(&profile))?;
+
+        let (test_list, target_runner) =
+            self.build_and_list_tests(test_filter_builder, &config, &profile, None)?;
+
+        let test_threads = profile.test_threads().compute() * hunt_opts.oversubscribe.get();
+        // Each round's seed is picked from the seed passed in, as with detect-order-deps.
+        let mut seeds = StdRng::seed_from_u64(hunt_opts.seed.unwrap_or_else(rand::random));
+        let mut collector = FlakeHuntCollector::new();
+        configure_handle_inheritance(false)?;
+        let _load = LoadGenerator::start(hunt_opts.cpu_load, hunt_opts.io_load)
+            .map_err(|err| ExpectedError::LoadGeneratorStartError { err })?;
+
+        let started = Instant::now();
+        // The end of the hunt as wall-clock time, to compare with when tests finished.
+        let deadline = SystemTime::now() + hunt_opts.duration;
+        for round in 1.. {
+            let remaining = match hunt_opts.duration.checked_sub(started.elapsed()) {
+                Some(remaining) if !remaining.is_zero() => remaining,
+                _ => break,
+            };
+            let round_seed = seeds.gen();
+
+            let mut runner_builder = TestRunnerBuilder::default();
+            runner_builder
+                .set_retries(0)
+                .set_fail_fast(false)
+                .set_test_threads(TestThreads::Count(test_threads))
+                .set_shuffle_seed(round_seed)
+                .set_max_run_time(remaining);
+            let mut runner = runner_builder.build(
+                &test_list,
+                profile.clone(),
+                SignalHandlerKind::Standard,
+                target_runner.clone(),
+            )?;
+
+            let mut interrupted = false;
+            let run_stats = runner.try_execute(|event| {
+                match &event {
+                    TestEvent::TestFinished {
+                        test_instance,
+                        run_statuses,
+                        ..
+                    } => {
+                        // Tests are terminated once the time is up, so failures after that
+                        // aren't the test's fault.
+                        let last_status = run_statuses.last_status();
+                        if last_status.start_time + last_status.time_taken <= deadline {
+                            collector.record(test_instance, run_statuses);
+                        }
+                    }
+                    TestEvent::RunBeginCancel { reason, .. } => {
+                        interrupted = *reason >= CancelReason::Signal;
+                    }
+                    _ => {}
+                }
+                Ok::<_, std::convert::Infallible>(())
+            });
+            let run_stats = match run_stats {
+                Ok(run_stats) => run_stats,
+                Err(never) => match never {},
+            };
+            if interrupted {
+                return Err(ExpectedError::test_run_failed());
+            }
+            if run_stats.finished_count == 0 {
+                // No tests would have finished in the time left.
+                break;
+            }
+            collector.finish_round(round_seed, test_threads);
+            log::info!(
+                "round {round} (seed {round_seed}, {test_threads} test threads): {} passed, {} failed",
+                run_stats.passed,
+                run_stats.failed_count(),
+            );
+        }
+
+        let flake_hunt = collector.finish();
+        let mut writer = output_writer.stdout_writer();
+        flake_hunt.write(
+            message_format.to_output_format(self.base.output.verbose),
+            &mut writer,
+            self.base.output.color.should_colorize(Stream::Stdout),
+        )?;
+        writer.flush().map_err(WriteTestListError::Io)?;
+        Ok(())
+    }
+
+    /// Runs tests at a commit being bisected.
+    ///
+    /// Returns whether the tests passed, or `None` if no tests match the filters.
+    fn exec_bisect_run(
+        &self,
+        profile_name: Option<&str>,
+        retries: Option<usize>,
+        output_writer: &mut OutputWriter,
+    ) -> Result<Option<bool>> {
+        let config = self
+            .base
+            .config_opts
+            .make_config(&self.base.workspace_root, self.base.graph())?;
+        let profile = self.load_profile(profile_name, &config)?;
+
+        let test_filter_builder = self.make_test_filter_builder(&config, Some(&profile))?;
+
+        let (test_list, target_runner) =
+            self.build_and_list_tests(test_filter_builder, &config, &profile, None)?;
+        if test_list.run_count() == 0 {
+            return Ok(None);
+        }
+
+        let output = output_writer.reporter_output();
+        let mut reporter = TestReporterBuilder::default()
+            .set_verbose(self.base.output.verbose)
+            .build(&test_list, &profile, output);
+        if self.base.output.color.should_colorize(Stream::Stderr) {
+            reporter.colorize();
+        }
+
+        let mut runner_builder = TestRunnerBuilder::default();
+        if let Some(retries) = retries {
+            runner_builder.set_retries(retries);
+        }
+        let mut runner = runner_builder.build(
+            &test_list,
+            profile,
+            SignalHandlerKind::Standard,
+            target_runner,
+        )?;
+
+        configure_handle_inheritance(false)?;
+        let mut interrupted = false;
+        let run_stats = runner.try_execute(|event| {
+            if let TestEvent::RunBeginCancel { reason, .. } = &event {
+                interrupted = *reason >= CancelReason::Signal;
+            }
+            reporter.report_event(event)
+        })?;
+        if interrupted {
+            return Err(ExpectedError::test_run_failed());
+        }
+        Ok(Some(run_stats.is_success()))
+    }
+
+    fn exec_detect_order_deps(
+        &self,
+        profile_name: Option<&str>,
+        runs: NonZeroUsize,
+        test_threads: Option<TestThreads>,
+        seed: Option<u64>,
+        message_format: MessageFormatOpts,
+        output_writer: &mut OutputWriter,
+    ) -> Result<()> {
+        let config = self
+            .base
+            .config_opts
+            .make_config(&self.base.workspace_root, self.base.graph())?;
+        let profile = self.load_profile(profile_name, &config)?;
+
+        let test_filter_builder = self.make_test_filter_builder(&config, Some(&profile))?;
+
+        let (test_list, target_runner) =
+            self.build_and_list_tests(test_filter_builder, &config, &profile, test_threads)?;
+
+        let parallel_threads = test_threads
+            .unwrap_or_else(|| profile.test_threads())
+            .compute();
+        // Each run's seed is picked from the seed passed in, so that all the runs can be repeated.
+        let mut seeds = StdRng::seed_from_u64(seed.unwrap_or_else(rand::random));
+        let mut collector = OrderDepsCollector::new();
+        configure_handle_inheritance(false)?;
+        for run in 0..runs.get() {
+            // Alternate between parallel and serial runs, so that tests interfering with tests
+            // running alongside them and tests affecting tests that run after them both show up.
+            let run_threads = match run % 2 {
+                0 => parallel_threads,
+                _ => 1,
+            };
+            let run_seed = seeds.gen();
+
+            let mut runner_builder = TestRunnerBuilder::default();
+            runner_builder
+                .set_retries(0)
+                .set_fail_fast(false)
+                .set_test_threads(TestThreads::Count(run_threads))
+                .set_shuffle_seed(run_seed);
+            let mut runner = runner_builder.build(
+                &test_list,
+                profile.clone(),
+                SignalHandlerKind::Standard,
+                target_runner.clone(),
+            )?;
+
+            let mut interrupted = false;
+            let run_stats = runner.try_execute(|event| {
+                match &event {
+                    TestEvent::TestFinished {
+                        test_instance,
+                        run_statuses,
+                        ..
+                    } => collector.record(test_instance, run_statuses),
+                    TestEvent::RunBeginCancel { reason, .. } => {
+                        interrupted = *reason >= CancelReason::Signal;
+                    }
+                    _ => {}
+                }
+                Ok::<_, std::convert::Infallible>(())
+            });
+            let run_stats = match run_stats {
+                Ok(run_stats) => run_stats,
+                Err(never) => match never {},
+            };
+            if interrupted {
+                return Err(ExpectedError::test_run_failed());
+            }
+            collector.finish_run(run_seed, run_threads);
+            log::info!(
+                "run {}/{} (seed {run_seed}, {run_threads} test threads): {} passed, {} failed",
+                run + 1,
+                runs,
+                run_stats.passed,
+                run_stats.failed_count(),
+            );
+        }
+
+        let order_deps = collector.analyze();
+        let mut writer = output_writer.stdout_writer();
+        order_deps.write(
+            message_format.to_output_format(self.base.output.verbose),
+            &mut writer,
+            self.base.output.color.should_colorize(Stream::Stdout),
+        )?;
+        writer.flush().map_err(WriteTestListError::Io)?;
+        Ok(())
+    }
+
+    fn exec_plan(
+        &self,
+        profile_name: Option<&str>,
+        shards: NonZeroU64,
+        timings_path: Option<&Utf8Path>,
+        output_file: Option<&Utf8Path>,
+        output_writer: &mut OutputWriter,
+    ) -> Result<()> {
+        let (plan, _) = self.plan_shards(profile_name, shards, timings_path)?;
+        write_shard_plan(&plan, output_file, output_writer)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn exec_ci_plan(
+        &self,
+        profile_name: Option<&str>,
+        shards: NonZeroU64,
+        timings_path: Option<&Utf8Path>,
+        format: CiPlanFormat,
+        output_file: Option<&Utf8Path>,
+        plan_file: Option<&Utf8Path>,
+        test_lists: bool,
+        output_writer: &mut OutputWriter,
+    ) -> Result<()> {
+        let (plan, estimates) = self.plan_shards(profile_name, shards, timings_path)?;
+        if let Some(plan_file) = plan_file {
+            write_shard_plan(&plan, Some(plan_file), output_writer)?;
+        }
 
+        let total_shards = plan.total_shards();
+        let summary = CiMatrixSummary {
+            include: estimates
+                .into_iter()
+                .map(|estimate| CiShardSummary {
+                    shard: estimate.shard(),
+                    total_shards,
+                    partition: plan_file
+                        .map(|plan_file| format!("plan:{}:{plan_file}", estimate.shard())),
+                    test_count: estimate.tests().len(),
+                    // Round to the millisecond, which is as precise as the timings are anyway.
+                    estimated_duration_secs: estimate.estimated_duration().as_millis() as f64
+                        / 1000.0,
+                    tests: test_lists.then(|| estimate.tests().to_vec()),
+                })
+                .collect(),
+        };
+
+        let write_error = |err| ExpectedError::CiPlanWriteError {
+            path: output_file.map(ToOwned::to_owned),
+            err,
+        };
+        match output_file {
+            Some(path) => {
+                let file = std::fs::File::create(path).map_err(write_error)?;
+                let mut writer = std::io::BufWriter::new(file);
+                format
+                    .write(&summary, &mut writer)
+                    .and_then(|()| writer.flush())
+                    .map_err(write_error)?;
+                log::info!("wrote {} shards to `{path}`", total_shards);
+            }
+            None => {
                 let mut writer = output_writer.stdout_writer();
-                test_list.write(
-                    message_format.to_output_format(self.base.output.verbose),
-                    &mut writer,
-                    self.base.output.color.should_colorize(Stream::Stdout),
-                )?;
-                writer.flush().map_err(WriteTestListError::Io)?;
+                format
+                    .write(&summary, &mut writer)
+                    .and_then(|()| writer.flush())
+                    .map_err(write_error)?;
             }
         }
         Ok(())
     }
 
-    fn exec_run(
+    /// Lists tests and splits them into shards based on how long they took to run.
+    fn plan_shards(
+        &self,
+        profile_name: Option<&str>,
+        shards: NonZeroU64,
+        timings_path: Option<&Utf8Path>,
+    ) -> Result<(ShardPlan, Vec<ShardEstimate>)> {
+        let config = self
+            .base
+            .config_opts
+            .make_config(&self.base.workspace_root, self.base.graph())?;
+        let profile = self.load_profile(profile_name, &config)?;
+        let test_filter_builder = self.make_test_filter_builder(&config, Some(&profile))?;
+        let (test_list, _) =
+            self.build_and_list_tests(test_filter_builder, &config, &profile, None)?;
+
+        let timings = match timings_path {
+            Some(path) => Some(TestTimings::read_from(path)),
+            None => RunStore::new(&profile).read_timings().transpose(),
+        }
+        .transpose()
+        .map_err(|err| ExpectedError::TimingsReadError { err })?;
+        if timings.is_none() {
+            log::warn!(
+                "no test timings recorded with this profile: \
+                 tests will be planned as if they all take the same amount of time\n\
+                 (hint: pass in timings from an earlier run with --timings)"
+            );
+        }
+        let timings = timings.unwrap_or_default();
+
+        let tests = test_list
+            .iter_tests()
+            .filter(|test_instance| test_instance.test_info.filter_match.is_match())
+            .map(|test_instance| {
+                let binary_id = test_instance.bin_info.binary_id.as_str();
+                (
+                    binary_id,
+                    test_instance.name,
+                    timings.get(binary_id, test_instance.name),
+                )
+            });
+        Ok(ShardPlan::from_durations_with_estimates(
+            tests,
+            shards.get(),
+        ))
+    }
+
+    fn exec_bench(
         &self,
         profile_name: Option<&str>,
         no_capture: bool,
+        message_format: MessageFormatOpts,
         runner_opts: &TestRunnerOpts,
         reporter_opts: &TestReporterOpts,
         output_writer: &mut OutputWriter,
@@ -1053,15 +3273,17 @@ impl App {
             .make_config(&self.base.workspace_root, self.base.graph())?;
         let profile = self.load_profile(profile_name, &config)?;
 
-        let filter_exprs = self.build_filtering_expressions()?;
-        let test_filter_builder = self.build_filter.make_test_filter_builder(filter_exprs)?;
-
-        let binary_list = self.base.build_binary_list()?;
-        let target_runner = self
-            .base
-            .load_runner(binary_list.rust_build_meta.target_triple.as_ref());
+        let test_filter_builder = self.make_test_filter_builder(&config, Some(&profile))?;
 
-        let test_list = self.build_test_list(binary_list, test_filter_builder, target_runner)?;
+        let binary_list = self.base.build_bench_binary_list()?;
+        let prefetcher = make_test_list_prefetcher(&profile, runner_opts.test_threads)?;
+        let (test_list, target_runner) = self.build_test_list(
+            binary_list,
+            test_filter_builder,
+            &config,
+            &profile,
+            prefetcher,
+        )?;
 
         let output = output_writer.reporter_output();
 
@@ -1074,22 +3296,51 @@ impl App {
         }
 
         let handler = SignalHandlerKind::Standard;
-        let runner_builder = match runner_opts.to_builder(no_capture) {
+        let mut runner_builder = match runner_opts.to_builder(no_capture) {
             Some(runner_builder) => runner_builder,
             None => {
                 // This means --no-run was passed in. Exit.
                 return Ok(());
             }
         };
+        runner_builder.set_bench(true);
 
-        let mut runner =
-            runner_builder.build(&test_list, profile, handler, target_runner.clone())?;
+        let mut runner = runner_builder.build(&test_list, profile, handler, target_runner)?;
 
         configure_handle_inheritance(no_capture)?;
+        let mut collector = BenchResultsCollector::new();
         let run_stats = runner.try_execute(|event| {
+            if let TestEvent::TestFinished {
+                test_instance,
+                run_statuses,
+                ..
+            } = &event
+            {
+                if run_statuses.last_status().result.is_success() {
+                    collector.record(test_instance);
+                }
+            }
             // Write and flush the event.
             reporter.report_event(event)
         })?;
+
+        let results = collector.collect(&criterion_home(
+            &test_list.rust_build_meta().target_directory,
+        ))?;
+        for (binary_id, name) in results.missing() {
+            log::warn!(
+                "benchmark `{name}` in `{binary_id}` didn't report any results \
+                 (only results from criterion benchmarks are collected)"
+            );
+        }
+        let mut writer = output_writer.stdout_writer();
+        results.write(
+            message_format.to_output_format(self.base.output.verbose),
+            &mut writer,
+            self.base.output.color.should_colorize(Stream::Stdout),
+        )?;
+        writer.flush().map_err(WriteTestListError::Io)?;
+
         if !run_stats.is_success() {
             return Err(ExpectedError::test_run_failed());
         }
@@ -1097,6 +3348,259 @@ impl App {
     }
 }
 
+/// Lists the tests in test binaries as they're built, so that they can be run while the rest of
+/// the build is still in progress.
+struct TestLister<'a, 'cfg> {
+    app: &'a App,
+    profile: &'a NextestProfile<'cfg>,
+    test_filter_builder: &'a TestFilterBuilder,
+    path_mapper: PathMapper,
+    // Set if a build is being reused, in which case its binaries are listed instead of built ones.
+    reused_binary_list: Option<Arc<BinaryList>>,
+    // The build metadata for the run, before any binaries are built.
+    rust_build_meta: RustBuildMeta<BinaryListState>,
+    // With more than one --target, binaries are split up by these target triples.
+    target_triples: Vec<TargetTriple>,
+    // How binaries built for each of target_triples are listed, followed by how binaries built
+    // for the host platform are. With a single target, only the first platform is used.
+    platforms: Vec<ListPlatform>,
+    // The target runner to run tests with.
+    target_runner: TargetRunner,
+}
+
+/// How the test binaries built for a platform are listed.
+struct ListPlatform {
+    target_runner: TargetRunner,
+    sysroot_libdir: Option<Utf8PathBuf>,
+    // Set if this is one of several target triples, in which case binary IDs are suffixed with it.
+    is_split_target: bool,
+}
+
+impl<'a, 'cfg> TestLister<'a, 'cfg> {
+    fn new(
+        app: &'a App,
+        test_filter_builder: &'a TestFilterBuilder,
+        config: &NextestConfig,
+        profile: &'a NextestProfile<'cfg>,
+    ) -> Result<Self> {
+        let reused_binary_list = match app.base.reuse_build.binaries_metadata() {
+            // Reused builds are read and checked up front, since nothing needs to be built.
+            Some(_) => Some(app.base.build_binary_list(|_| ControlFlow::Continue(()))?),
+            None => None,
+        };
+        let rust_build_meta = match &reused_binary_list {
+            Some(binary_list) => binary_list.rust_build_meta.clone(),
+            None => RustBuildMeta::new(
+                app.base.graph().workspace().target_directory(),
+                app.base.build_target_triple(),
+            ),
+        };
+        let path_mapper = app.make_path_mapper(config, &rust_build_meta.target_directory)?;
+
+        let (target_triples, platforms, target_runner) = match app.base.multiple_target_triples() {
+            Some(target_triples) => {
+                let target_runners: Vec<_> = target_triples
+                    .iter()
+                    .map(|triple| {
+                        (
+                            triple.triple.clone(),
+                            runner_for_target(&app.base.cargo_configs, Some(triple)),
+                        )
+                    })
+                    .collect();
+                let target_runner = TargetRunner::for_target_triples(target_runners.clone());
+                let mut platforms: Vec<_> = target_triples
+                    .iter()
+                    .zip(target_runners)
+                    .map(|(triple, (_, target_runner))| ListPlatform {
+                        target_runner,
+                        sysroot_libdir: app.build_filter.sysroot_libdir(Some(triple)),
+                        is_split_target: true,
+                    })
+                    .collect();
+                // Binaries built for the host platform are listed with the combined runner, as in
+                // App::build_test_list.
+                platforms.push(ListPlatform {
+                    target_runner: target_runner.clone(),
+                    sysroot_libdir: app.build_filter.sysroot_libdir(None),
+                    is_split_target: false,
+                });
+                (target_triples, platforms, target_runner)
+            }
+            None => {
+                let triple = rust_build_meta.target_triple.as_ref();
+                let target_runner = app.base.load_runner(triple).clone();
+                let platform = ListPlatform {
+                    target_runner: target_runner.clone(),
+                    sysroot_libdir: app.build_filter.sysroot_libdir(triple),
+                    is_split_target: false,
+                };
+                (Vec::new(), vec![platform], target_runner)
+            }
+        };
+
+        Ok(Self {
+            app,
+            profile,
+            test_filter_builder,
+            path_mapper,
+            reused_binary_list,
+            rust_build_meta,
+            target_triples,
+            platforms,
+            target_runner,
+        })
+    }
+
+    /// Returns the empty test list a run starts out with, before any binaries are listed.
+    fn initial_test_list(&self) -> TestList<'a> {
+        let mut rust_build_meta = self.rust_build_meta.map_paths(&self.path_mapper);
+        rust_build_meta.sysroot_libdir = self
+            .platforms
+            .last()
+            .expect("there's always at least one platform")
+            .sysroot_libdir
+            .clone();
+        TestList::new_empty(rust_build_meta)
+    }
+
+    /// Starts listing the tests in these newly built binaries in the background.
+    fn prefetch(&self, binary_list: BinaryList, prefetcher: &mut TestListPrefetcher) {
+        for (binary_list, platform) in self.split(binary_list) {
+            // Errors are reported while creating the test list after the build.
+            if let Ok((test_artifacts, rust_build_meta)) =
+                self.test_artifacts(binary_list, platform)
+            {
+                for test_artifact in &test_artifacts {
+                    prefetcher.prefetch(
+                        test_artifact,
+                        &rust_build_meta,
+                        self.test_filter_builder,
+                        &platform.target_runner,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Builds test binaries unless a build is being reused, and sends a test list for each binary
+    /// as soon as it's built.
+    ///
+    /// The build is stopped if the run is canceled.
+    fn list_tests<'s>(
+        &'s self,
+        prefetcher: &mut TestListPrefetcher,
+        sender: &TestListSender<'s, 'a, ExpectedError>,
+    ) -> Result<()> {
+        let mut sent = HashSet::new();
+        let binary_list = match &self.reused_binary_list {
+            Some(binary_list) => binary_list.clone(),
+            None => self.app.base.build_binary_list(|built| {
+                if sender.is_canceled() {
+                    return ControlFlow::Break(());
+                }
+                sent.extend(built.rust_binaries.iter().map(|binary| binary.path.clone()));
+                self.send(built, prefetcher, sender);
+                ControlFlow::Continue(())
+            })?,
+        };
+
+        // Binaries in a reused build, along with doctests (which are compiled once the build is
+        // done), are sent one at a time here.
+        for binary in &binary_list.rust_binaries {
+            if sender.is_canceled() {
+                break;
+            }
+            if sent.contains(&binary.path) {
+                continue;
+            }
+            let binary_list = BinaryList {
+                rust_build_meta: binary_list.rust_build_meta.clone(),
+                rust_binaries: vec![binary.clone()],
+            };
+            self.send(binary_list, prefetcher, sender);
+        }
+        Ok(())
+    }
+
+    /// Sends test lists for these binaries, which are listed in the background right away.
+    fn send<'s>(
+        &'s self,
+        binary_list: BinaryList,
+        prefetcher: &mut TestListPrefetcher,
+        sender: &TestListSender<'s, 'a, ExpectedError>,
+    ) {
+        for (binary_list, platform) in self.split(binary_list) {
+            let (test_artifacts, rust_build_meta) = match self.test_artifacts(binary_list, platform)
+            {
+                Ok(artifacts) => artifacts,
+                Err(err) => {
+                    sender.send(async move { Err(err) });
+                    continue;
+                }
+            };
+            for test_artifact in &test_artifacts {
+                prefetcher.prefetch(
+                    test_artifact,
+                    &rust_build_meta,
+                    self.test_filter_builder,
+                    &platform.target_runner,
+                );
+            }
+
+            let test_list = prefetcher.test_list_future(
+                test_artifacts,
+                rust_build_meta,
+                self.test_filter_builder,
+                &platform.target_runner,
+            );
+            let is_split_target = platform.is_split_target;
+            sender.send(async move {
+                let test_list = test_list
+                    .await
+                    .map_err(|err| ExpectedError::CreateTestListError { err })?;
+                if is_split_target {
+                    // This records the target triple in each test suite, as with the merged lists
+                    // in App::build_test_list.
+                    Ok(TestList::merge(std::iter::once(test_list)))
+                } else {
+                    Ok(test_list)
+                }
+            });
+        }
+    }
+
+    /// Splits these binaries up by the platform they were built for, leaving out platforms
+    /// without any binaries.
+    fn split(&self, binary_list: BinaryList) -> Vec<(BinaryList, &ListPlatform)> {
+        if self.target_triples.is_empty() {
+            return vec![(binary_list, &self.platforms[0])];
+        }
+        let (host_binary_list, target_binary_lists) =
+            binary_list.split_by_target(&self.target_triples);
+        target_binary_lists
+            .into_iter()
+            .chain(std::iter::once(host_binary_list))
+            .zip(&self.platforms)
+            .filter(|(binary_list, _)| !binary_list.rust_binaries.is_empty())
+            .collect()
+    }
+
+    fn test_artifacts(
+        &self,
+        binary_list: BinaryList,
+        platform: &ListPlatform,
+    ) -> Result<(Vec<RustTestArtifact<'a>>, RustBuildMeta<TestListState>)> {
+        self.app.build_filter.test_artifacts(
+            self.app.base.graph(),
+            Arc::new(binary_list),
+            self.profile,
+            &self.path_mapper,
+            platform.sysroot_libdir.clone(),
+        )
+    }
+}
+
 #[derive(Debug, Subcommand)]
 enum SelfCommand {
     #[cfg_attr(
@@ -1173,6 +3677,61 @@ impl SelfCommand {
     }
 }
 
+/// Creates a prefetcher for test lists, which uses the list cache if it's enabled for this profile.
+fn write_shard_plan(
+    plan: &ShardPlan,
+    output_file: Option<&Utf8Path>,
+    output_writer: &mut OutputWriter,
+) -> Result<()> {
+    let write_error = |err| ExpectedError::ShardPlanWriteError {
+        path: output_file.map(ToOwned::to_owned),
+        err,
+    };
+    match output_file {
+        Some(path) => {
+            let file = std::fs::File::create(path)
+                .map_err(|err| write_error(serde_json::Error::io(err)))?;
+            let mut writer = std::io::BufWriter::new(file);
+            plan.write(&mut writer).map_err(write_error)?;
+            writeln!(writer)
+                .and_then(|()| writer.flush())
+                .map_err(|err| write_error(serde_json::Error::io(err)))?;
+            log::info!(
+                "wrote shard plan for {} shards to `{path}`",
+                plan.total_shards()
+            );
+        }
+        None => {
+            let mut writer = output_writer.stdout_writer();
+            plan.write(&mut writer).map_err(write_error)?;
+            writeln!(writer)
+                .and_then(|()| writer.flush())
+                .map_err(|err| write_error(serde_json::Error::io(err)))?;
+        }
+    }
+    Ok(())
+}
+
+/// Creates a prefetcher that lists as many test binaries at a time as tests are run at a time.
+///
+/// Listing the tests in a binary runs it, much like running a test does, so the same limit applies.
+/// `--build-jobs` isn't used: it limits how many crates Cargo compiles at a time, while binaries
+/// are listed alongside the build rather than as a part of it.
+fn make_test_list_prefetcher(
+    profile: &NextestProfile<'_>,
+    test_threads: Option<TestThreads>,
+) -> Result<TestListPrefetcher> {
+    let list_threads = test_threads
+        .unwrap_or_else(|| profile.test_threads())
+        .compute();
+    let mut prefetcher = TestListPrefetcher::new(list_threads)
+        .map_err(|err| ExpectedError::CreateTestListError { err })?;
+    if let Some(cache_dir) = profile.list_cache_dir() {
+        prefetcher.set_cache_dir(cache_dir);
+    }
+    Ok(prefetcher)
+}
+
 fn acquire_graph_data(
     manifest_path: Option<&Utf8Path>,
     target_dir: Option<&Utf8Path>,
@@ -1231,6 +3790,120 @@ fn discover_target_triple(
     }
 }
 
+/// Returns the rustdoc flags to persist doctests to `persist_dir`, in the format used by
+/// `CARGO_ENCODED_RUSTDOCFLAGS`.
+fn doctest_rustdocflags(persist_dir: &Utf8Path) -> String {
+    // CARGO_ENCODED_RUSTDOCFLAGS takes precedence over RUSTDOCFLAGS, so carry over any flags
+    // that are already set.
+    let mut flags: Vec<String> = match std::env::var("CARGO_ENCODED_RUSTDOCFLAGS") {
+        Ok(flags) if !flags.is_empty() => flags.split('\x1f').map(|flag| flag.to_owned()).collect(),
+        _ => std::env::var("RUSTDOCFLAGS")
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|flag| flag.to_owned())
+            .collect(),
+    };
+    flags.extend([
+        "-Zunstable-options".to_owned(),
+        "--persist-doctests".to_owned(),
+        persist_dir.to_string(),
+        "--no-run".to_owned(),
+    ]);
+    flags.join("\x1f")
+}
+
+fn discover_rustc_version() -> Option<RustcVersionSummary> {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned());
+    match duct::cmd!(&rustc, "-vV")
+        .stdout_capture()
+        .stderr_null()
+        .unchecked()
+        .run()
+    {
+        Ok(output) if output.status.success() => {
+            let version = String::from_utf8(output.stdout)
+                .ok()
+                .and_then(|stdout| parse_rustc_version(&stdout));
+            if version.is_none() {
+                log::debug!("unable to parse output of `{rustc} -vV`");
+            }
+            version
+        }
+        Ok(output) => {
+            log::debug!("`{rustc} -vV` failed with {}", output.status);
+            None
+        }
+        Err(err) => {
+            log::debug!("unable to execute `{rustc}`, not recording rustc version: {err}");
+            None
+        }
+    }
+}
+
+fn parse_rustc_version(output: &str) -> Option<RustcVersionSummary> {
+    let mut release = None;
+    let mut commit_hash = None;
+    for line in output.lines() {
+        if let Some(value) = line.strip_prefix("release: ") {
+            release = Some(value.trim().to_owned());
+        } else if let Some(value) = line.strip_prefix("commit-hash: ") {
+            let value = value.trim();
+            // Locally built compilers report "unknown".
+            if value != "unknown" {
+                commit_hash = Some(value.to_owned());
+            }
+        }
+    }
+
+    Some(RustcVersionSummary {
+        release: release?,
+        commit_hash,
+    })
+}
+
+fn discover_sysroot_libdir(target_triple: Option<&TargetTriple>) -> Option<Utf8PathBuf> {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned());
+    let mut args = vec!["--print", "target-libdir"];
+    if let Some(triple) = target_triple {
+        args.extend(["--target", triple.target_arg()]);
+    }
+
+    match duct::cmd(&rustc, &args)
+        .stdout_capture()
+        .stderr_capture()
+        .unchecked()
+        .run()
+    {
+        Ok(output) if output.status.success() => match String::from_utf8(output.stdout) {
+            Ok(stdout) => {
+                let libdir = Utf8PathBuf::from(stdout.trim_end());
+                log::debug!("using sysroot library directory `{libdir}`");
+                Some(libdir)
+            }
+            Err(_) => {
+                log::debug!("`{rustc} --print target-libdir` produced non-UTF-8 output");
+                None
+            }
+        },
+        Ok(output) => {
+            log::debug!(
+                "`{rustc} --print target-libdir` failed with {}, not adding sysroot library \
+                 directory to the dynamic library path: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end(),
+            );
+            None
+        }
+        Err(err) => {
+            log::debug!(
+                "unable to execute `{rustc}`, not adding sysroot library directory to the \
+                 dynamic library path: {err}"
+            );
+            None
+        }
+    }
+}
+
 fn runner_for_target(cargo_configs: &CargoConfigs, triple: Option<&TargetTriple>) -> TargetRunner {
     match TargetRunner::new(cargo_configs, triple) {
         Ok(runner) => {
@@ -1302,26 +3975,82 @@ mod tests {
             // ---
             "cargo nextest list",
             "cargo nextest run",
+            "cargo nextest bench",
             // ---
             // Commands with arguments
             // ---
             "cargo nextest list --list-type binaries-only",
             "cargo nextest list --list-type full",
             "cargo nextest list --message-format json-pretty",
+            "cargo nextest list -P ci",
+            "cargo nextest list --diagnostic-format json -E 'test(foo)'",
+            "cargo nextest list --diff previous.json",
+            "cargo nextest list --diff previous.json --fail-on-removed --message-format json",
             "cargo nextest run --failure-output never",
             "cargo nextest run --success-output=immediate",
             "cargo nextest run --status-level=all",
             "cargo nextest run --no-capture",
+            "cargo nextest run --partition stable:1/2",
+            "cargo nextest run --partition timed:1/2",
+            "cargo nextest run --partition plan:1:plan.json",
+            "cargo nextest plan --shards 4",
+            "cargo nextest plan --shards 4 --timings timings.json -o plan.json",
+            "cargo nextest ci plan --shards 4",
+            "cargo nextest ci plan --shards 4 --format yaml --test-lists",
+            "cargo nextest ci plan --shards 4 --plan-file plan.json -o matrix.json",
+            "cargo nextest run --partition timed:1/2 --partition-timings timings.json",
             "cargo nextest run --nocapture",
             "cargo nextest run --no-run",
             "cargo nextest run --final-status-level flaky",
             // retry is an alias for flaky -- ensure that it parses
             "cargo nextest run --final-status-level retry",
+            "cargo nextest run --no-sysroot-libdir",
+            "cargo nextest list --no-sysroot-libdir",
+            "cargo nextest run --max-fail 3",
+            "cargo nextest run --iterations 10",
+            "cargo nextest run --until-failure",
+            "cargo nextest run --until-failure --max-iterations 100",
+            "cargo nextest run --shuffle",
+            "cargo nextest run --shuffle --shuffle-seed 42",
+            "cargo nextest run --shuffle-seed 42",
+            "cargo nextest run --seed 42",
+            "cargo nextest run --seed 42 --iterations 3",
+            "cargo nextest run --resume 67e55044-10b1-426f-9247-bb680e5fe0c8",
+            "cargo nextest run --max-run-time 25m",
+            "cargo nextest run --message-format libtest-json",
+            "cargo nextest run --message-format json",
+            "cargo nextest run --message-format tap",
+            "cargo nextest run --message-format github-actions",
+            "cargo nextest run --message-format teamcity",
+            "cargo nextest run --message-format sarif",
+            "cargo nextest run --summary-markdown summary.md --message-format tap",
+            "cargo nextest run --dashboard",
+            "cargo nextest run --live-output",
+            "cargo nextest run --live-output --message-format json",
+            "cargo nextest run --live-output-filter 'test(serve_)' --live-output-filter 'test(db_)'",
+            "cargo nextest run --live-output --output-timestamps",
+            "cargo nextest run --resume 67e55044-10b1-426f-9247-bb680e5fe0c8 -E 'package(foo)'",
+            "cargo nextest run --check-determinism",
+            "cargo nextest detect-order-deps",
+            "cargo nextest bisect --good v1.0 parse::roundtrip",
+            "cargo nextest bisect --good abc123 --good v1.0 --bad main -E 'test(net)' --retries 2",
+            "cargo nextest bisect --good HEAD~20 --archive-dir /tmp/archives -p my-crate",
+            "cargo nextest flake-hunt --duration 10m",
+            "cargo nextest flake-hunt --duration 30s -E 'test(net)' --oversubscribe 4 --cpu-load 2 --io-load 1 --seed 3",
+            "cargo nextest detect-order-deps --runs 10 --seed 42 -j 4",
+            "cargo nextest detect-order-deps -E 'test(net)' --message-format json",
+            "cargo nextest run --test-list-file failures.txt",
+            "cargo nextest list --test-list-file -",
+            "cargo nextest bench --message-format json",
+            "cargo nextest bench --no-capture --bench my-bench",
+            "cargo nextest bench -E 'package(foo)' fib",
             // ---
             // Cargo options
             // ---
             "cargo nextest list --lib --bins",
             "cargo nextest run --ignore-rust-version --unit-graph",
+            "cargo nextest run --target x86_64-unknown-linux-gnu --target thumbv7em-none-eabihf",
+            "cargo nextest run --doctests --workspace",
             // ---
             // Reuse build options
             // ---
@@ -1332,6 +4061,18 @@ mod tests {
             "cargo nextest archive --archive-file my-archive.tar.zst --zstd-level -1",
             "cargo nextest archive --archive-file my-archive.foo --archive-format tar-zst",
             "cargo nextest archive --archive-file my-archive.foo --archive-format tar-zstd",
+            "cargo nextest archive --archive-file my-archive.tar.gz --compression gzip",
+            "cargo nextest archive --archive-file my-archive.tar --compression none",
+            "cargo nextest archive --archive-file my-archive.tar.zst --compression zstd:19",
+            "cargo nextest archive --archive-file my-archive.tar.zst --profile ci",
+            "cargo nextest archive --archive-file delta.tar.zst --baseline full.tar.zst",
+            "cargo nextest archive --archive-file my-archive.tar.zst --debuginfo strip",
+            "cargo nextest archive --archive-file my-archive.tar.zst --debuginfo split --debuginfo-file debug.tar.zst",
+            "cargo nextest archive inspect --archive-file my-archive.tar.zst",
+            "cargo nextest archive inspect --archive-file my-archive.foo --archive-format tar-gz --message-format json",
+            "cargo nextest list --archive-file my-archive.tgz --archive-format tar-gz",
+            "cargo nextest run --archive-file https://example.com/my-archive.tar.zst",
+            "cargo nextest run --archive-file s3://my-bucket/my-archive.tar.zst --archive-checksum sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
             "cargo nextest list --archive-file my-archive.tar.zst",
             "cargo nextest list --archive-file my-archive.tar.zst --archive-format tar-zst",
             "cargo nextest list --archive-file my-archive.tar.zst --extract-to my-path",
@@ -1339,6 +4080,15 @@ mod tests {
             "cargo nextest list --archive-file my-archive.tar.zst --persist-extract-tempdir",
             "cargo nextest list --archive-file my-archive.tar.zst --workspace-remap foo",
             "cargo nextest list --archive-file my-archive.tar.zst --config target.'cfg(all())'.runner=\"my-runner\"",
+            "cargo nextest run --archive-file my-archive.tar.zst --release --features foo,bar",
+            "cargo nextest run --archive-file my-archive.tar.zst --build-mismatch error",
+            "cargo nextest run --archive-file delta.tar.zst --archive-baseline full.tar.zst",
+            "cargo nextest run --archive-file my-archive.tar.zst --archive-debuginfo my-archive-debuginfo.tar.zst",
+            "cargo nextest run --archive-file foo --path-remap /build/vendor=vendor --path-remap /build=/mnt/build",
+            "cargo nextest run --binaries-metadata foo --build-mismatch warn",
+            "cargo nextest run --archive-file my-archive.tar.zst --target aarch64-unknown-linux-gnu --force",
+            "cargo nextest run --external-binaries binaries.json --workspace-remap foo",
+            "cargo nextest list --external-binaries binaries.json --build-mismatch error",
             // ---
             // Filter expressions
             // ---
@@ -1351,6 +4101,24 @@ mod tests {
         ];
 
         let invalid: &[(&'static str, ErrorKind)] = &[
+            // ---
+            // --diff only works with full test lists, and --fail-on-removed requires it
+            // ---
+            (
+                "cargo nextest list --diff previous.json --list-type binaries-only",
+                ArgumentConflict,
+            ),
+            (
+                "cargo nextest list --fail-on-removed",
+                MissingRequiredArgument,
+            ),
+            // ---
+            // Doctests can't be built while reusing builds
+            // ---
+            (
+                "cargo nextest run --archive-file foo --doctests",
+                ArgumentConflict,
+            ),
             // ---
             // --no-capture and these options conflict
             // ---
@@ -1370,8 +4138,22 @@ mod tests {
             // --no-run and these options conflict
             // ---
             ("cargo nextest run --no-run -j8", ArgumentConflict),
+            ("cargo nextest bench --no-run --retries 3", ArgumentConflict),
             ("cargo nextest run --no-run --retries 3", ArgumentConflict),
             ("cargo nextest run --no-run --fail-fast", ArgumentConflict),
+            ("cargo nextest run --no-run --iterations 3", ArgumentConflict),
+            (
+                "cargo nextest run --until-failure --iterations 3",
+                ArgumentConflict,
+            ),
+            (
+                "cargo nextest run --until-failure --no-fail-fast",
+                ArgumentConflict,
+            ),
+            (
+                "cargo nextest run --max-fail 3 --no-fail-fast",
+                ArgumentConflict,
+            ),
             (
                 "cargo nextest run --no-run --no-fail-fast",
                 ArgumentConflict,
@@ -1430,6 +4212,64 @@ mod tests {
                 "cargo nextest run --archive-file foo --archive-format no",
                 InvalidValue,
             ),
+            (
+                "cargo nextest list --diagnostic-format xml",
+                InvalidValue,
+            ),
+            (
+                "cargo nextest run --partition-timings timings.json",
+                MissingRequiredArgument,
+            ),
+            ("cargo nextest plan", MissingRequiredArgument),
+            ("cargo nextest plan --shards 0", ValueValidation),
+            ("cargo nextest run --max-fail 0", ValueValidation),
+            ("cargo nextest run --iterations 0", ValueValidation),
+            ("cargo nextest run --shuffle-seed abc", ValueValidation),
+            ("cargo nextest run --seed abc", ValueValidation),
+            ("cargo nextest run --seed 42 --no-run", ArgumentConflict),
+            ("cargo nextest run --resume abc", ValueValidation),
+            ("cargo nextest run --max-run-time 5m --no-run", ArgumentConflict),
+            ("cargo nextest run --max-run-time soon", ValueValidation),
+            (
+                "cargo nextest run --message-format libtest-json --no-capture",
+                ArgumentConflict,
+            ),
+            ("cargo nextest run --message-format json-pretty", InvalidValue),
+            (
+                "cargo nextest run --summary-markdown summary.md --no-run",
+                ArgumentConflict,
+            ),
+            ("cargo nextest run --dashboard --no-capture", ArgumentConflict),
+            ("cargo nextest run --live-output --no-capture", ArgumentConflict),
+            (
+                "cargo nextest run --output-timestamps --no-capture",
+                ArgumentConflict,
+            ),
+            (
+                "cargo nextest run --live-output --failure-output final",
+                ArgumentConflict,
+            ),
+            (
+                "cargo nextest run --resume 67e55044-10b1-426f-9247-bb680e5fe0c8 --iterations 2",
+                ArgumentConflict,
+            ),
+            (
+                "cargo nextest run --resume 67e55044-10b1-426f-9247-bb680e5fe0c8 --test-list-file tests.txt",
+                ArgumentConflict,
+            ),
+            ("cargo nextest detect-order-deps --runs 0", ValueValidation),
+            ("cargo nextest flake-hunt", MissingRequiredArgument),
+            ("cargo nextest bisect", MissingRequiredArgument),
+            ("cargo nextest bisect --good v1.0 --retries abc", ValueValidation),
+            (
+                "cargo nextest flake-hunt --duration 1m --oversubscribe 0",
+                ValueValidation,
+            ),
+            (
+                "cargo nextest run --check-determinism --no-capture",
+                ArgumentConflict,
+            ),
+            ("cargo nextest run --max-iterations 3", MissingRequiredArgument),
             (
                 "cargo nextest run --extract-to foo",
                 MissingRequiredArgument,
@@ -1462,6 +4302,60 @@ mod tests {
                 "cargo nextest run --archive-file foo --target-dir-remap bar",
                 ArgumentConflict,
             ),
+            (
+                "cargo nextest run --external-binaries foo --archive-file bar",
+                ArgumentConflict,
+            ),
+            (
+                "cargo nextest run --external-binaries foo --lib",
+                ArgumentConflict,
+            ),
+            (
+                "cargo nextest run --build-mismatch error",
+                MissingRequiredArgument,
+            ),
+            ("cargo nextest run --force", MissingRequiredArgument),
+            (
+                "cargo nextest run --archive-checksum sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+                MissingRequiredArgument,
+            ),
+            (
+                "cargo nextest run --archive-file foo --archive-checksum md5:d41d8cd98f00b204e9800998ecf8427e",
+                ValueValidation,
+            ),
+            (
+                "cargo nextest run --archive-baseline foo",
+                MissingRequiredArgument,
+            ),
+            (
+                "cargo nextest run --archive-debuginfo foo",
+                MissingRequiredArgument,
+            ),
+            (
+                "cargo nextest run --archive-file foo --path-remap /build",
+                ValueValidation,
+            ),
+            // ---
+            // Archive options
+            // ---
+            (
+                "cargo nextest archive --archive-file foo --compression gzip --zstd-level 3",
+                ArgumentConflict,
+            ),
+            (
+                "cargo nextest archive --archive-file foo --compression zstd:high",
+                ValueValidation,
+            ),
+            (
+                "cargo nextest archive --archive-file foo --debuginfo none",
+                InvalidValue,
+            ),
+            ("cargo nextest archive", MissingRequiredArgument),
+            ("cargo nextest archive inspect", MissingRequiredArgument),
+            (
+                "cargo nextest archive --compression gzip inspect --archive-file foo",
+                InvalidSubcommand,
+            ),
         ];
 
         for valid_args in valid {
@@ -1498,6 +4392,66 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_rustc_version() {
+        let output = "rustc 1.63.0 (4b91a6ea7 2022-08-08)\n\
+                      binary: rustc\n\
+                      commit-hash: 4b91a6ea7258a947e59c6522cd5898e7c0a6a88f\n\
+                      commit-date: 2022-08-08\n\
+                      host: x86_64-unknown-linux-gnu\n\
+                      release: 1.63.0\n\
+                      LLVM version: 14.0.5\n";
+        assert_eq!(
+            parse_rustc_version(output),
+            Some(RustcVersionSummary {
+                release: "1.63.0".to_owned(),
+                commit_hash: Some("4b91a6ea7258a947e59c6522cd5898e7c0a6a88f".to_owned()),
+            })
+        );
+
+        let output = "rustc 1.65.0-dev\n\
+                      commit-hash: unknown\n\
+                      release: 1.65.0-dev\n";
+        assert_eq!(
+            parse_rustc_version(output),
+            Some(RustcVersionSummary {
+                release: "1.65.0-dev".to_owned(),
+                commit_hash: None,
+            })
+        );
+
+        assert_eq!(parse_rustc_version("not rustc output"), None);
+    }
+
+    #[test]
+    fn test_parse_test_ids() {
+        let path = Utf8Path::new("failures.txt");
+        let contents = "my-package::basic tests::test_foo\n\
+                        \n\
+                        my-package::doctest/src_lib_rs_3_0 src/lib.rs - foo (line 3)\r\n";
+        assert_eq!(
+            parse_test_ids(path, contents).expect("test IDs are valid"),
+            vec![
+                ("my-package::basic".to_owned(), "tests::test_foo".to_owned()),
+                (
+                    "my-package::doctest/src_lib_rs_3_0".to_owned(),
+                    "src/lib.rs - foo (line 3)".to_owned()
+                ),
+            ]
+        );
+
+        let err = parse_test_ids(path, "my-package::basic tests::test_foo\nmy-package::basic")
+            .expect_err("test name is missing");
+        assert!(
+            matches!(
+                &err,
+                ExpectedError::TestListFileParseError { line_number: 2, line, .. }
+                    if line == "my-package::basic"
+            ),
+            "unexpected error: {err:?}"
+        );
+    }
+
     #[derive(Debug, Parser)]
     struct TestCli {
         #[structopt(flatten)]
@@ -1509,7 +4463,8 @@ mod tests {
         fn get_test_filter_builder(cmd: &str) -> Result<TestFilterBuilder> {
             let app = TestCli::try_parse_from(shell_words::split(cmd).expect("valid command line"))
                 .unwrap_or_else(|_| panic!("{} should have successfully parsed", cmd));
-            app.build_filter.make_test_filter_builder(vec![])
+            app.build_filter
+                .make_test_filter_builder(vec![], &TestTags::default(), None, None)
         }
 
         let valid = &[
@@ -1547,8 +4502,8 @@ mod tests {
             // unsupported
             // ---
             ("foo -- --bar", "unsupported"),
-            ("foo -- --exact", "unsupported\n(hint: use a filter expression instead: <https://nexte.st/book/filter-expressions>)"),
-            ("foo -- --skip", "unsupported\n(hint: use a filter expression instead: <https://nexte.st/book/filter-expressions>)"),
+            ("foo -- --exact --exact", "duplicated"),
+            ("foo -- --skip", "missing a value"),
         ];
 
         for (a, b) in valid {
@@ -1573,5 +4528,26 @@ mod tests {
                 );
             }
         }
+
+        // --skip and --exact don't have nextest equivalents, so compare against test filter
+        // builders directly.
+        let libtest_valid: &[(&str, &[&str], &[&str], bool)] = &[
+            ("foo -- --skip bar str", &["str"], &["bar"], false),
+            ("foo str -- --skip=bar --exact", &["str"], &["bar"], true),
+            ("foo -- --skip a --skip b", &[], &["a", "b"], false),
+            ("foo -- --exact -- --skip", &["--skip"], &[], true),
+        ];
+        for &(cmd, patterns, skip_patterns, exact) in libtest_valid {
+            let mut expected =
+                TestFilterBuilder::new(RunIgnored::Default, None, patterns.to_vec(), Vec::new());
+            expected
+                .set_skip_patterns(skip_patterns.to_vec())
+                .set_exact(exact);
+            assert_eq!(
+                get_test_filter_builder(cmd).unwrap_or_else(|_| panic!("failed to parse {}", cmd)),
+                expected,
+                "{cmd} matches expected test filter builder"
+            );
+        }
     }
 }
@@ -1,10 +1,14 @@
 // Copyright (c) The nextest Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use crate::reuse_build::mismatch_hint;
 use camino::Utf8PathBuf;
 use nextest_filtering::errors::FilterExpressionParseErrors;
 use nextest_metadata::NextestExitCode;
-use nextest_runner::errors::*;
+use nextest_runner::{
+    errors::*,
+    reuse_build::{ArchiveChecksum, ArchiveCompression, ArchiveFormat, ReuseBuildMismatch},
+};
 use owo_colors::{OwoColorize, Stream};
 use std::error::Error;
 use thiserror::Error;
@@ -59,6 +63,38 @@ pub enum ExpectedError {
         #[from]
         err: ConfigParseError,
     },
+    #[error("run store read error")]
+    RunStoreReadError {
+        #[from]
+        err: RunStoreReadError,
+    },
+    #[error("timings read error")]
+    TimingsReadError {
+        #[source]
+        err: RunStoreReadError,
+    },
+    #[error("checkpoint read error")]
+    CheckpointReadError {
+        #[source]
+        err: RunStoreReadError,
+    },
+    #[error("shard plan error")]
+    ShardPlanError {
+        #[source]
+        err: ShardPlanError,
+    },
+    #[error("shard plan write error")]
+    ShardPlanWriteError {
+        path: Option<Utf8PathBuf>,
+        #[source]
+        err: serde_json::Error,
+    },
+    #[error("CI plan write error")]
+    CiPlanWriteError {
+        path: Option<Utf8PathBuf>,
+        #[source]
+        err: std::io::Error,
+    },
     #[error("argument file read error")]
     ArgumentFileReadError {
         arg_name: &'static str,
@@ -72,6 +108,14 @@ pub enum ExpectedError {
         #[source]
         err: UnknownArchiveFormat,
     },
+    #[error("archive compression mismatch")]
+    ArchiveCompressionMismatch {
+        archive_file: Utf8PathBuf,
+        format: ArchiveFormat,
+        compression: ArchiveCompression,
+    },
+    #[error("archive debuginfo file conflict")]
+    ArchiveDebuginfoFileConflict { archive_file: Utf8PathBuf },
     #[error("archive create error")]
     ArchiveCreateError {
         archive_file: Utf8PathBuf,
@@ -84,6 +128,23 @@ pub enum ExpectedError {
         #[source]
         err: ArchiveExtractError,
     },
+    #[error("external binaries error")]
+    ExternalBinariesError {
+        path: Utf8PathBuf,
+        #[source]
+        err: ExternalBinariesError,
+    },
+    #[error("archive inspect error")]
+    ArchiveInspectError {
+        archive_file: Utf8PathBuf,
+        #[source]
+        err: ArchiveReadError,
+    },
+    #[error("archive baseline required")]
+    ArchiveBaselineRequired {
+        archive_file: Utf8PathBuf,
+        checksum: ArchiveChecksum,
+    },
     #[error("path mapper construct error")]
     PathMapperConstructError {
         arg_name: &'static str,
@@ -146,6 +207,13 @@ pub enum ExpectedError {
     },
     #[error("test run failed")]
     TestRunFailed,
+    #[error("tests were removed")]
+    TestsRemoved,
+    #[error("reading benchmark results failed")]
+    BenchResultsError {
+        #[from]
+        err: BenchResultsError,
+    },
     #[cfg(feature = "self-update")]
     #[error("failed to parse --version")]
     UpdateVersionParseError {
@@ -168,6 +236,22 @@ pub enum ExpectedError {
         #[from]
         err: SignalHandlerSetupError,
     },
+    #[error("failed to start load generator")]
+    LoadGeneratorStartError {
+        #[source]
+        err: std::io::Error,
+    },
+    #[error("git bisect error")]
+    GitBisectError {
+        #[from]
+        err: GitBisectError,
+    },
+    #[error("bisect archive write error")]
+    BisectArchiveWriteError {
+        path: Utf8PathBuf,
+        #[source]
+        err: std::io::Error,
+    },
     #[error("experimental feature not enabled")]
     ExperimentalFeatureNotEnabled {
         name: &'static str,
@@ -182,6 +266,14 @@ pub enum ExpectedError {
         reason: &'static str,
         args: Vec<String>,
     },
+    #[error("test list file parse error")]
+    TestListFileParseError {
+        file_name: Utf8PathBuf,
+        line_number: usize,
+        line: String,
+    },
+    #[error("reused build mismatch")]
+    ReuseBuildMismatch { mismatches: Vec<ReuseBuildMismatch> },
 }
 
 impl ExpectedError {
@@ -294,17 +386,31 @@ impl ExpectedError {
             | Self::RootManifestNotFound { .. }
             | Self::CargoConfigError { .. }
             | Self::ConfigParseError { .. }
+            | Self::RunStoreReadError { .. }
+            | Self::TimingsReadError { .. }
+            | Self::CheckpointReadError { .. }
+            | Self::ShardPlanError { .. }
             | Self::ArgumentFileReadError { .. }
             | Self::UnknownArchiveFormat { .. }
+            | Self::ArchiveCompressionMismatch { .. }
+            | Self::ArchiveDebuginfoFileConflict { .. }
             | Self::ArchiveExtractError { .. }
+            | Self::ExternalBinariesError { .. }
+            | Self::ArchiveInspectError { .. }
+            | Self::ArchiveBaselineRequired { .. }
             | Self::PathMapperConstructError { .. }
             | Self::ArgumentJsonParseError { .. }
             | Self::TestRunnerBuildError { .. }
             | Self::ConfigureHandleInheritanceError { .. }
             | Self::CargoMetadataParseError { .. }
             | Self::TestBinaryArgsParseError { .. }
+            | Self::TestListFileParseError { .. }
+            | Self::ReuseBuildMismatch { .. }
             | Self::DialoguerError { .. }
-            | Self::SignalHandlerSetupError { .. } => NextestExitCode::SETUP_ERROR,
+            | Self::SignalHandlerSetupError { .. }
+            | Self::LoadGeneratorStartError { .. }
+            | Self::GitBisectError { .. }
+            | Self::BisectArchiveWriteError { .. } => NextestExitCode::SETUP_ERROR,
             #[cfg(feature = "self-update")]
             Self::UpdateVersionParseError { .. } => NextestExitCode::SETUP_ERROR,
             Self::FromMessagesError { .. } | Self::CreateTestListError { .. } => {
@@ -314,10 +420,13 @@ impl ExpectedError {
                 NextestExitCode::BUILD_FAILED
             }
             Self::TestRunFailed => NextestExitCode::TEST_RUN_FAILED,
+            Self::TestsRemoved => NextestExitCode::TESTS_REMOVED,
+            Self::BenchResultsError { .. } => NextestExitCode::BENCH_RESULTS_READ_FAILED,
             Self::ArchiveCreateError { .. } => NextestExitCode::ARCHIVE_CREATION_FAILED,
-            Self::WriteTestListError { .. } | Self::WriteEventError { .. } => {
-                NextestExitCode::WRITE_OUTPUT_ERROR
-            }
+            Self::WriteTestListError { .. }
+            | Self::WriteEventError { .. }
+            | Self::ShardPlanWriteError { .. }
+            | Self::CiPlanWriteError { .. } => NextestExitCode::WRITE_OUTPUT_ERROR,
             #[cfg(feature = "self-update")]
             Self::UpdateError { .. } => NextestExitCode::UPDATE_ERROR,
             Self::ExperimentalFeatureNotEnabled { .. } => {
@@ -380,6 +489,49 @@ impl ExpectedError {
                 log::error!("{}", err);
                 err.source()
             }
+            Self::RunStoreReadError { err } => {
+                log::error!("failed to read the outcome of the previous run");
+                Some(err as &dyn Error)
+            }
+            Self::TimingsReadError { err } => {
+                log::error!("failed to read test timings");
+                Some(err as &dyn Error)
+            }
+            Self::CheckpointReadError { err } => {
+                log::error!("failed to read the checkpoint of the run to resume");
+                Some(err as &dyn Error)
+            }
+            Self::ShardPlanWriteError { path, err } => {
+                match path {
+                    Some(path) => log::error!("failed to write shard plan to `{path}`"),
+                    None => log::error!("failed to write shard plan to standard output"),
+                }
+                Some(err as &dyn Error)
+            }
+            Self::CiPlanWriteError { path, err } => {
+                match path {
+                    Some(path) => log::error!("failed to write CI plan to `{path}`"),
+                    None => log::error!("failed to write CI plan to standard output"),
+                }
+                Some(err as &dyn Error)
+            }
+            Self::ShardPlanError { err } => match err {
+                ShardPlanError::FilterParse { errors, .. } => {
+                    log::error!("{}", err);
+                    for parse_errors in errors {
+                        for single_error in &parse_errors.errors {
+                            let report = miette::Report::new(single_error.clone())
+                                .with_source_code(parse_errors.input.to_owned());
+                            log::error!(target: "cargo_nextest::no_heading", "{report:?}");
+                        }
+                    }
+                    None
+                }
+                _ => {
+                    log::error!("{}", err);
+                    err.source()
+                }
+            },
             Self::ConfigParseError { err } => {
                 match err.kind() {
                     ConfigParseErrorKind::OverrideError(errors) => {
@@ -400,6 +552,38 @@ impl ExpectedError {
                         }
                         None
                     }
+                    ConfigParseErrorKind::FiltersetAliasError(errors) => {
+                        // Alias errors are printed out using miette, like override errors.
+                        for alias_error in errors {
+                            log::error!(
+                                "for config file `{}`, failed to parse filterset alias: {}",
+                                err.config_file(),
+                                alias_error
+                                    .name
+                                    .if_supports_color(Stream::Stderr, |p| p.bold()),
+                            );
+                            for single_error in &alias_error.parse_errors.errors {
+                                let report = miette::Report::new(single_error.clone())
+                                    .with_source_code(alias_error.parse_errors.input.to_owned());
+                                log::error!(target: "cargo_nextest::no_heading", "{report:?}");
+                            }
+                        }
+                        None
+                    }
+                    ConfigParseErrorKind::TagRuleError(errors) => {
+                        log::error!(
+                            "for config file `{}`, failed to parse test tag rules",
+                            err.config_file(),
+                        );
+                        for parse_errors in errors {
+                            for single_error in &parse_errors.errors {
+                                let report = miette::Report::new(single_error.clone())
+                                    .with_source_code(parse_errors.input.to_owned());
+                                log::error!(target: "cargo_nextest::no_heading", "{report:?}");
+                            }
+                        }
+                        None
+                    }
                     _ => {
                         // These other errors are printed out normally.
                         log::error!("{}", err);
@@ -426,6 +610,31 @@ impl ExpectedError {
                 );
                 Some(err as &dyn Error)
             }
+            Self::ArchiveCompressionMismatch {
+                archive_file,
+                format,
+                compression,
+            } => {
+                log::error!(
+                    "archive format for {} is `{}`, but compression {} produces `{}` archives\n\
+                     (hint: pass in a matching --compression or --archive-format)",
+                    archive_file.if_supports_color(Stream::Stderr, |x| x.bold()),
+                    format.extension(),
+                    compression
+                        .to_string()
+                        .if_supports_color(Stream::Stderr, |x| x.bold()),
+                    compression.format().extension(),
+                );
+                None
+            }
+            Self::ArchiveDebuginfoFileConflict { archive_file } => {
+                log::error!(
+                    "debuginfo file is the same as the archive file {}\n\
+                     (hint: pass in a different --debuginfo-file)",
+                    archive_file.if_supports_color(Stream::Stderr, |x| x.bold()),
+                );
+                None
+            }
             Self::ArchiveCreateError { archive_file, err } => {
                 log::error!(
                     "error creating archive `{}`",
@@ -440,6 +649,34 @@ impl ExpectedError {
                 );
                 Some(err as &dyn Error)
             }
+            Self::ExternalBinariesError { path, err } => {
+                log::error!(
+                    "error reading external binaries from `{}`",
+                    path.if_supports_color(Stream::Stderr, |x| x.bold())
+                );
+                Some(err as &dyn Error)
+            }
+            Self::ArchiveInspectError { archive_file, err } => {
+                log::error!(
+                    "error reading archive `{}`",
+                    archive_file.if_supports_color(Stream::Stderr, |x| x.bold())
+                );
+                Some(err as &dyn Error)
+            }
+            Self::ArchiveBaselineRequired {
+                archive_file,
+                checksum,
+            } => {
+                log::error!(
+                    "archive `{}` is a delta archive, and requires its baseline archive\n\
+                     (hint: pass in the baseline with checksum {} using --archive-baseline)",
+                    archive_file.if_supports_color(Stream::Stderr, |x| x.bold()),
+                    checksum
+                        .to_string()
+                        .if_supports_color(Stream::Stderr, |x| x.bold()),
+                );
+                None
+            }
             Self::ArgumentJsonParseError {
                 arg_name,
                 file_name,
@@ -521,10 +758,18 @@ impl ExpectedError {
                 log::error!("failed to write event to output");
                 Some(err as &dyn Error)
             }
+            Self::BenchResultsError { err } => {
+                log::error!("failed to read benchmark results");
+                Some(err as &dyn Error)
+            }
             Self::TestRunFailed => {
                 log::error!("test run failed");
                 None
             }
+            Self::TestsRemoved => {
+                log::error!("tests were removed compared to the previous test list");
+                None
+            }
             #[cfg(feature = "self-update")]
             Self::UpdateVersionParseError { err } => {
                 log::error!("failed to parse --version");
@@ -546,6 +791,21 @@ impl ExpectedError {
                 log::error!("error setting up signal handler");
                 Some(err as &dyn Error)
             }
+            Self::LoadGeneratorStartError { err } => {
+                log::error!("failed to start threads to generate I/O load");
+                Some(err as &dyn Error)
+            }
+            Self::GitBisectError { err } => {
+                log::error!("{}", err);
+                err.source()
+            }
+            Self::BisectArchiveWriteError { path, err } => {
+                log::error!(
+                    "failed to write bisect archive to `{}`",
+                    path.if_supports_color(Stream::Stderr, |x| x.bold())
+                );
+                Some(err as &dyn Error)
+            }
             Self::ExperimentalFeatureNotEnabled { name, var_name } => {
                 log::error!(
                     "{} is an experimental feature and must be enabled with {}=1",
@@ -573,6 +833,40 @@ impl ExpectedError {
                 );
                 None
             }
+            Self::TestListFileParseError {
+                file_name,
+                line_number,
+                line,
+            } => {
+                log::error!(
+                    "line {line_number} of test list file `{}` is not of the form \
+                     `<binary-id> <test-name>`: {}",
+                    file_name.if_supports_color(Stream::Stderr, |x| x.bold()),
+                    line.if_supports_color(Stream::Stderr, |x| x.bold()),
+                );
+                None
+            }
+            Self::ReuseBuildMismatch { mismatches } => {
+                let mut s = String::new();
+                for mismatch in mismatches {
+                    s.push_str("\n  - ");
+                    s.push_str(&mismatch.to_string());
+                    s.push_str("\n    (hint: ");
+                    s.push_str(&mismatch_hint(mismatch));
+                    s.push(')');
+                }
+                let escape_hatch = if mismatches.iter().any(|m| m.is_incompatible()) {
+                    "--force"
+                } else {
+                    "--build-mismatch warn"
+                };
+                log::error!(
+                    "reused build doesn't match the current invocation:{s}\n\
+                     (pass in {} to run tests anyway)",
+                    escape_hatch.if_supports_color(Stream::Stderr, |x| x.bold()),
+                );
+                None
+            }
         };
 
         while let Some(err) = next_error {
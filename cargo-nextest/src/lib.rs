@@ -14,6 +14,7 @@
 #![warn(missing_docs)]
 
 mod cargo_cli;
+mod ci;
 mod dispatch;
 mod errors;
 mod output;
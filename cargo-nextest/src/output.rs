@@ -5,7 +5,7 @@
 use clap::{ArgEnum, Args};
 use env_logger::fmt::Formatter;
 use log::{Level, LevelFilter, Record};
-use miette::{GraphicalTheme, MietteHandlerOpts, ThemeStyles};
+use miette::{GraphicalTheme, JSONReportHandler, MietteHandlerOpts, ThemeStyles};
 use nextest_runner::reporter::ReporterStderr;
 use owo_colors::{style, OwoColorize, Style};
 use std::{
@@ -32,13 +32,29 @@ pub(crate) struct OutputOpts {
         env = "CARGO_TERM_COLOR"
     )]
     pub(crate) color: Color,
+
+    /// Format for diagnostics such as filter expression errors: human, json
+    #[clap(
+        long,
+        arg_enum,
+        default_value_t,
+        hide_possible_values = true,
+        global = true,
+        value_name = "FORMAT",
+        env = "NEXTEST_DIAGNOSTIC_FORMAT"
+    )]
+    pub(crate) diagnostic_format: DiagnosticFormat,
 }
 
 impl OutputOpts {
     pub(crate) fn init(self) -> OutputContext {
-        let OutputOpts { verbose, color } = self;
+        let OutputOpts {
+            verbose,
+            color,
+            diagnostic_format,
+        } = self;
 
-        color.init();
+        color.init(diagnostic_format);
 
         OutputContext { verbose, color }
     }
@@ -65,10 +81,26 @@ impl Default for Color {
     }
 }
 
+/// The format diagnostics are printed out in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ArgEnum)]
+#[must_use]
+pub enum DiagnosticFormat {
+    /// Graphical diagnostics meant to be read by people.
+    Human,
+    /// One JSON object per diagnostic, for editors and other tools.
+    Json,
+}
+
+impl Default for DiagnosticFormat {
+    fn default() -> Self {
+        DiagnosticFormat::Human
+    }
+}
+
 static INIT_LOGGER: std::sync::Once = std::sync::Once::new();
 
 impl Color {
-    fn init(self) {
+    fn init(self, diagnostic_format: DiagnosticFormat) {
         match self {
             Color::Auto => owo_colors::unset_override(),
             Color::Always => owo_colors::set_override(true),
@@ -83,6 +115,10 @@ impl Color {
                 .init();
 
             miette::set_hook(Box::new(move |_| {
+                if diagnostic_format == DiagnosticFormat::Json {
+                    return Box::new(JSONReportHandler::new());
+                }
+
                 let theme_styles = if self.should_colorize(Stream::Stderr) {
                     ThemeStyles {
                         error: style().bright_red().bold(),
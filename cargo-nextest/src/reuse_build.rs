@@ -5,15 +5,17 @@ use crate::{output::OutputContext, ExpectedError, OutputWriter, Result};
 use camino::{Utf8Path, Utf8PathBuf};
 use clap::{ArgEnum, Args};
 use guppy::graph::PackageGraph;
+use nextest_metadata::ExternalBinaryListSummary;
 use nextest_runner::{
     errors::PathMapperConstructKind,
     reuse_build::{
-        ArchiveFormat, ArchiveReporter, ExtractDestination, MetadataWithRemap, PathMapper,
-        ReuseBuildInfo,
+        is_archive_url, ArchiveChecksum, ArchiveEvent, ArchiveFormat, ArchiveReporter,
+        ExtractDestination, MetadataWithRemap, PathMapper, PathRemap, ReuseBuildInfo,
+        ReuseBuildMismatch,
     },
 };
 use owo_colors::Stream;
-use std::io::Write;
+use std::io::{Read, Write};
 
 #[derive(Debug, Default, Args)]
 #[clap(
@@ -24,7 +26,9 @@ use std::io::Write;
     group = clap::ArgGroup::new("target-dir-remap-sources"),
 )]
 pub(crate) struct ReuseBuildOpts {
-    /// Path to nextest archive
+    /// Path or URL to nextest archive
+    ///
+    /// `http://`, `https://` and `s3://` URLs are fetched and extracted while being downloaded.
     #[clap(
         long,
         groups = &["cargo-metadata-sources", "binaries-metadata-sources", "target-dir-remap-sources"],
@@ -33,6 +37,18 @@ pub(crate) struct ReuseBuildOpts {
     )]
     pub(crate) archive_file: Option<Utf8PathBuf>,
 
+    /// Path to a list of test binaries built outside of Cargo
+    ///
+    /// The list contains the test binaries along with the packages they're a part of. Cargo is
+    /// not invoked.
+    #[clap(
+        long,
+        groups = &["cargo-metadata-sources", "binaries-metadata-sources"],
+        conflicts_with_all = &["cargo-opts", "archive-file", "binaries-metadata", "cargo-metadata"],
+        value_name = "PATH",
+    )]
+    pub(crate) external_binaries: Option<Utf8PathBuf>,
+
     /// Archive format
     #[clap(
         long,
@@ -43,6 +59,18 @@ pub(crate) struct ReuseBuildOpts {
     )]
     pub(crate) archive_format: ArchiveFormatOpt,
 
+    /// Expected checksum of the archive, in the form `sha256:<HEX>`
+    #[clap(long, requires = "archive-file", value_name = "CHECKSUM")]
+    pub(crate) archive_checksum: Option<ArchiveChecksum>,
+
+    /// Baseline archive to layer a delta archive over
+    #[clap(long, requires = "archive-file", value_name = "PATH")]
+    pub(crate) archive_baseline: Option<Utf8PathBuf>,
+
+    /// Debuginfo archive with debug info split out of the binaries in the archive
+    #[clap(long, requires = "archive-file", value_name = "PATH")]
+    pub(crate) archive_debuginfo: Option<Utf8PathBuf>,
+
     /// Destination directory to extract archive to [default: temporary directory]
     #[clap(
         long,
@@ -92,6 +120,32 @@ pub(crate) struct ReuseBuildOpts {
         value_name = "PATH"
     )]
     pub(crate) target_dir_remap: Option<Utf8PathBuf>,
+
+    /// Remap paths starting with FROM to TO (can be specified multiple times)
+    ///
+    /// Useful for paths outside the workspace and target directory, e.g. vendored dependencies.
+    /// Rules are tried in order, after --workspace-remap and --target-dir-remap, and before rules in
+    /// the config.
+    #[clap(long, multiple_occurrences(true), value_name = "FROM=TO")]
+    pub(crate) path_remap: Vec<PathRemap>,
+
+    /// What to do if the reused build doesn't match the rustc version, features or profile
+    #[clap(
+        long,
+        arg_enum,
+        default_value_t,
+        requires = "binaries-metadata-sources",
+        value_name = "ACTION"
+    )]
+    pub(crate) build_mismatch: BuildMismatchOpt,
+
+    /// Run tests even if the reused build is incompatible with the current invocation
+    ///
+    /// By default, nextest exits with an error if the reused build was produced for a different
+    /// target triple than the one passed in with --target, or if it contains test binaries from
+    /// packages that aren't in the current workspace.
+    #[clap(long, requires = "binaries-metadata-sources")]
+    pub(crate) force: bool,
 }
 
 impl ReuseBuildOpts {
@@ -111,7 +165,6 @@ impl ReuseBuildOpts {
         output_writer: &mut OutputWriter,
     ) -> Result<ReuseBuildInfo> {
         if let Some(archive_file) = &self.archive_file {
-            let format = self.archive_format.to_archive_format(archive_file)?;
             // Process this archive.
             let dest = match &self.extract_to {
                 Some(dir) => ExtractDestination::Destination {
@@ -129,19 +182,83 @@ impl ReuseBuildOpts {
             }
 
             let mut writer = output_writer.stderr_writer();
-            return ReuseBuildInfo::extract_archive(
-                archive_file,
-                format,
-                dest,
-                |event| {
-                    reporter.report_event(event, &mut writer)?;
-                    writer.flush()
-                },
-                self.workspace_remap.as_deref(),
-            )
-            .map_err(|err| ExpectedError::ArchiveExtractError {
+            let mut callback = |event: ArchiveEvent<'_>| {
+                reporter.report_event(event, &mut writer)?;
+                writer.flush()
+            };
+            let info = if is_archive_url(archive_file.as_str()) {
+                // For URLs, auto-detection happens while the archive is being fetched.
+                ReuseBuildInfo::extract_archive_from_url(
+                    archive_file.as_str(),
+                    self.archive_format.to_explicit_format(),
+                    self.archive_checksum.as_ref(),
+                    dest,
+                    &mut callback,
+                    self.workspace_remap.as_deref(),
+                )
+            } else {
+                ReuseBuildInfo::extract_archive(
+                    archive_file,
+                    self.archive_format.to_extract_format(archive_file)?,
+                    self.archive_checksum.as_ref(),
+                    dest,
+                    &mut callback,
+                    self.workspace_remap.as_deref(),
+                )
+            };
+            let mut info = info.map_err(|err| ExpectedError::ArchiveExtractError {
                 archive_file: archive_file.clone(),
                 err,
+            })?;
+
+            match (info.baseline_checksum(), &self.archive_baseline) {
+                (Some(_), Some(baseline_file)) => {
+                    let format = ArchiveFormatOpt::Auto.to_extract_format(baseline_file)?;
+                    info.extract_baseline(baseline_file, format, &mut callback)
+                        .map_err(|err| ExpectedError::ArchiveExtractError {
+                            archive_file: baseline_file.clone(),
+                            err,
+                        })?;
+                }
+                (Some(checksum), None) => {
+                    return Err(ExpectedError::ArchiveBaselineRequired {
+                        archive_file: archive_file.clone(),
+                        checksum: checksum.clone(),
+                    });
+                }
+                (None, Some(_)) => {
+                    log::warn!(
+                        "`{archive_file}` is not a delta archive, ignoring --archive-baseline"
+                    );
+                }
+                (None, None) => {}
+            }
+
+            if let Some(debuginfo_file) = &self.archive_debuginfo {
+                let format = ArchiveFormatOpt::Auto.to_extract_format(debuginfo_file)?;
+                info.extract_debuginfo(debuginfo_file, format, callback)
+                    .map_err(|err| ExpectedError::ArchiveExtractError {
+                        archive_file: debuginfo_file.clone(),
+                        err,
+                    })?;
+            }
+
+            return Ok(info);
+        }
+
+        if let Some(path) = &self.external_binaries {
+            let json = std::fs::read_to_string(path).map_err(|err| {
+                ExpectedError::argument_file_read_error("external-binaries", path, err)
+            })?;
+            let summary: ExternalBinaryListSummary =
+                serde_json::from_str(&json).map_err(|err| {
+                    ExpectedError::argument_json_parse_error("external-binaries", path, err)
+                })?;
+            return ReuseBuildInfo::from_external(summary).map_err(|err| {
+                ExpectedError::ExternalBinariesError {
+                    path: path.clone(),
+                    err,
+                }
             });
         }
 
@@ -167,13 +284,42 @@ pub(crate) enum ArchiveFormatOpt {
     Auto,
     #[clap(alias = "tar-zstd")]
     TarZst,
+    #[clap(alias = "tgz")]
+    TarGz,
+    Tar,
 }
 
 impl ArchiveFormatOpt {
-    pub(crate) fn to_archive_format(self, archive_file: &Utf8Path) -> Result<ArchiveFormat> {
+    /// Returns the archive format if it was explicitly specified, or `None` for `auto`.
+    pub(crate) fn to_explicit_format(self) -> Option<ArchiveFormat> {
         match self {
-            Self::TarZst => Ok(ArchiveFormat::TarZst),
-            Self::Auto => ArchiveFormat::autodetect(archive_file).map_err(|err| {
+            Self::TarZst => Some(ArchiveFormat::TarZst),
+            Self::TarGz => Some(ArchiveFormat::TarGz),
+            Self::Tar => Some(ArchiveFormat::Tar),
+            Self::Auto => None,
+        }
+    }
+
+    /// Returns the archive format to create, or `None` if it should be determined by the
+    /// compression.
+    pub(crate) fn to_create_format(self, archive_file: &Utf8Path) -> Option<ArchiveFormat> {
+        self.to_explicit_format()
+            .or_else(|| ArchiveFormat::autodetect(archive_file).ok())
+    }
+
+    /// Returns the format of an existing archive.
+    ///
+    /// `auto` looks at the contents of the archive, falling back to the file extension if they
+    /// aren't recognized.
+    pub(crate) fn to_extract_format(self, archive_file: &Utf8Path) -> Result<ArchiveFormat> {
+        if self == Self::Auto {
+            if let Some(format) = detect_format_from_contents(archive_file) {
+                return Ok(format);
+            }
+        }
+        match self.to_create_format(archive_file) {
+            Some(format) => Ok(format),
+            None => ArchiveFormat::autodetect(archive_file).map_err(|err| {
                 ExpectedError::UnknownArchiveFormat {
                     archive_file: archive_file.to_owned(),
                     err,
@@ -183,27 +329,80 @@ impl ArchiveFormatOpt {
     }
 }
 
+fn detect_format_from_contents(archive_file: &Utf8Path) -> Option<ArchiveFormat> {
+    // Errors here are ignored: the same errors will be hit and reported while extracting the
+    // archive.
+    let file = std::fs::File::open(archive_file).ok()?;
+    let mut contents = Vec::with_capacity(ArchiveFormat::DETECT_LEN);
+    file.take(ArchiveFormat::DETECT_LEN as u64)
+        .read_to_end(&mut contents)
+        .ok()?;
+    ArchiveFormat::autodetect_from_contents(&contents)
+}
+
 impl Default for ArchiveFormatOpt {
     fn default() -> Self {
         Self::Auto
     }
 }
 
+/// What to do if a reused build doesn't match the current invocation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub(crate) enum BuildMismatchOpt {
+    /// Print out a warning and continue.
+    Warn,
+
+    /// Exit with an error.
+    Error,
+}
+
+impl Default for BuildMismatchOpt {
+    fn default() -> Self {
+        Self::Warn
+    }
+}
+
+/// Returns a hint for how to resolve a mismatch between a reused build and the current invocation.
+pub(crate) fn mismatch_hint(mismatch: &ReuseBuildMismatch) -> String {
+    match mismatch {
+        ReuseBuildMismatch::RustcVersion { build, .. } => format!(
+            "rebuild with the current rustc, or switch to rustc {} while running tests",
+            build.release,
+        ),
+        ReuseBuildMismatch::Features { .. } => {
+            "pass in the same feature options the build was produced with, or leave them out"
+                .to_owned()
+        }
+        ReuseBuildMismatch::Profile { build, .. } => {
+            format!("pass in `--cargo-profile {build}`, or leave out --release and --cargo-profile")
+        }
+        ReuseBuildMismatch::TargetTriple { build, current } => {
+            format!("pass in `--target {build}`, or rebuild with `--target {current}`")
+        }
+        ReuseBuildMismatch::MissingPackages { .. } => "run tests from the workspace the build was \
+             produced in, or pass in its Cargo metadata with --cargo-metadata"
+            .to_owned(),
+    }
+}
+
 pub(crate) fn make_path_mapper(
     info: &ReuseBuildInfo,
     graph: &PackageGraph,
     orig_target_dir: &Utf8Path,
+    path_remaps: &[PathRemap],
 ) -> Result<PathMapper> {
     PathMapper::new(
         graph.workspace().root(),
         info.workspace_remap(),
         orig_target_dir,
         info.target_dir_remap(),
+        path_remaps,
     )
     .map_err(|err| {
         let arg_name = match err.kind() {
             PathMapperConstructKind::WorkspaceRoot => "workspace-remap",
             PathMapperConstructKind::TargetDir => "target-dir-remap",
+            PathMapperConstructKind::PathRemap => "path-remap",
         };
         ExpectedError::PathMapperConstructError { arg_name, err }
     })
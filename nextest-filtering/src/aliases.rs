@@ -0,0 +1,178 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    compile::compile,
+    errors::{FilterAliasParseErrors, FilterExpressionParseErrors, ParseSingleError},
+    expression::{parse_expr, ExprLayer, Wrapped},
+    parsing::{Expr, SetDef},
+    FilteringExpr, ParseContext,
+};
+use guppy::graph::PackageGraph;
+use miette::SourceSpan;
+use recursion::Collapse;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+/// Named filter expressions, which other filter expressions can refer to with `alias(name)`.
+///
+/// Aliases are checked for errors when they're defined, including references to aliases that
+/// don't exist and aliases that refer back to themselves.
+#[derive(Clone, Debug, Default)]
+pub struct FilterAliases {
+    aliases: BTreeMap<String, Alias>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Alias {
+    input: String,
+    pub(crate) expr: Expr,
+}
+
+impl FilterAliases {
+    /// Parses the given `(name, expression)` pairs into a set of aliases.
+    ///
+    /// Aliases may refer to each other in any order.
+    pub fn new(
+        graph: &PackageGraph,
+        aliases: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, Vec<FilterAliasParseErrors>> {
+        let mut errors = vec![];
+
+        // First, parse each alias on its own.
+        let mut defined = BTreeSet::new();
+        let mut parsed = BTreeMap::new();
+        for (name, input) in aliases {
+            defined.insert(name.clone());
+            if !is_valid_name(&name) {
+                let span = (0, name.len()).into();
+                let parse_errors = FilterExpressionParseErrors::new(
+                    name.clone(),
+                    vec![ParseSingleError::InvalidAliasName(span)],
+                );
+                errors.push(FilterAliasParseErrors::new(name, parse_errors));
+                continue;
+            }
+            match parse_expr(&input) {
+                Ok(expr) => {
+                    parsed.insert(name, Alias { input, expr });
+                }
+                Err(parse_errors) => errors.push(FilterAliasParseErrors::new(name, parse_errors)),
+            }
+        }
+
+        // Next, check that aliases only refer to aliases that exist, without any cycles.
+        for (name, alias) in &parsed {
+            let mut alias_errors = vec![];
+            for (reference, span) in alias_references(&alias.expr) {
+                if !defined.contains(reference) {
+                    alias_errors.push(ParseSingleError::UnknownAlias(span));
+                } else if let Some(path) = find_path(&parsed, reference, name, &mut HashSet::new())
+                {
+                    let cycle = std::iter::once(name.as_str())
+                        .chain(path)
+                        .collect::<Vec<_>>();
+                    alias_errors.push(ParseSingleError::AliasCycle {
+                        span,
+                        cycle: cycle.join(" -> "),
+                    });
+                }
+            }
+            if !alias_errors.is_empty() {
+                let parse_errors = FilterExpressionParseErrors::new(&alias.input, alias_errors);
+                errors.push(FilterAliasParseErrors::new(name, parse_errors));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        // Finally, check aliases against the package graph. Whether predicates based on tags,
+        // previous runs or changed files can be used depends on where the alias is used, so
+        // they're checked there.
+        let aliases = Self { aliases: parsed };
+        let mut cx = ParseContext::new();
+        cx.set_aliases(&aliases);
+        for (name, alias) in &aliases.aliases {
+            if let Err(mut compile_errors) = compile(&alias.expr, graph, &cx) {
+                compile_errors.retain(|error| {
+                    !matches!(
+                        error,
+                        ParseSingleError::PreviousRunUnavailable(_)
+                            | ParseSingleError::TagsUnavailable(_)
+                            | ParseSingleError::ChangedFilesUnavailable(_)
+                    )
+                });
+                if !compile_errors.is_empty() {
+                    let parse_errors =
+                        FilterExpressionParseErrors::new(&alias.input, compile_errors);
+                    errors.push(FilterAliasParseErrors::new(name, parse_errors));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(aliases)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Returns true if the given expression needs information about previous runs to work, either
+    /// directly or through the aliases it refers to.
+    pub fn needs_previous_run(&self, raw_expr: &str) -> bool {
+        FilteringExpr::needs_previous_run(raw_expr)
+            || (raw_expr.contains("alias")
+                && self
+                    .aliases
+                    .values()
+                    .any(|alias| FilteringExpr::needs_previous_run(&alias.input)))
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&Alias> {
+        self.aliases.get(name)
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Returns the aliases an expression refers to directly.
+fn alias_references(expr: &Expr) -> Vec<(&str, SourceSpan)> {
+    use ExprLayer::*;
+    Wrapped(expr).collapse_layers(|layer: ExprLayer<&SetDef, Vec<_>>| match layer {
+        Set(SetDef::Alias(name, span)) => vec![(name.as_str(), *span)],
+        Set(_) => vec![],
+        Not(references) => references,
+        Union(mut references_1, references_2) | Intersection(mut references_1, references_2) => {
+            references_1.extend(references_2);
+            references_1
+        }
+    })
+}
+
+/// Returns the aliases passed through to get from `from` to `to`, including both ends.
+fn find_path<'a>(
+    aliases: &'a BTreeMap<String, Alias>,
+    from: &'a str,
+    to: &str,
+    visited: &mut HashSet<&'a str>,
+) -> Option<Vec<&'a str>> {
+    if from == to {
+        return Some(vec![from]);
+    }
+    if !visited.insert(from) {
+        return None;
+    }
+    let alias = aliases.get(from)?;
+    alias_references(&alias.expr)
+        .into_iter()
+        .find_map(|(reference, _)| {
+            let mut path = find_path(aliases, reference, to, visited)?;
+            path.insert(0, from);
+            Some(path)
+        })
+}
@@ -0,0 +1,55 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use camino::Utf8PathBuf;
+use guppy::{
+    graph::{DependsCache, PackageMetadata},
+    PackageId,
+};
+use std::{collections::HashSet, fmt};
+
+/// Finds the files that changed since a git ref, for the `since()` predicate.
+pub trait ChangedFiles: fmt::Debug {
+    /// Returns the absolute paths of the files that changed since `git_ref`.
+    ///
+    /// Returns an error message if the changes couldn't be determined, for example because
+    /// `git_ref` doesn't exist.
+    fn changed_files(&self, git_ref: &str) -> Result<Vec<Utf8PathBuf>, String>;
+}
+
+/// Returns the workspace packages that contain any of the changed files, along with all the
+/// workspace packages that (possibly transitively) depend on them.
+///
+/// Each file belongs to the package with the innermost directory containing it. Files outside of
+/// every package, such as those at the root of a virtual workspace, don't change any packages.
+pub(crate) fn changed_packages(
+    changed_files: &[Utf8PathBuf],
+    all_packages: &[PackageMetadata],
+    cache: &mut DependsCache,
+) -> HashSet<PackageId> {
+    let package_dirs: Vec<_> = all_packages
+        .iter()
+        .filter_map(|p| Some((p.id(), p.manifest_path().parent()?)))
+        .collect();
+    let changed: HashSet<_> = changed_files
+        .iter()
+        .filter_map(|file| {
+            package_dirs
+                .iter()
+                .filter(|(_, dir)| file.starts_with(dir))
+                .max_by_key(|(_, dir)| dir.components().count())
+                .map(|(id, _)| *id)
+        })
+        .collect();
+
+    let mut set: HashSet<_> = changed.iter().map(|id| (*id).clone()).collect();
+    for id1 in changed {
+        for p2 in all_packages {
+            let id2 = p2.id();
+            if cache.depends_on(id2, id1).unwrap_or(false) {
+                set.insert(id2.clone());
+            }
+        }
+    }
+    set
+}
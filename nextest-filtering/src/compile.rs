@@ -2,9 +2,11 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
+    changed::changed_packages,
     errors::ParseSingleError,
     expression::*,
     parsing::{Expr, SetDef},
+    suggest::did_you_mean,
 };
 use guppy::{
     graph::{DependsCache, PackageGraph, PackageMetadata},
@@ -17,6 +19,7 @@ use std::collections::HashSet;
 pub(crate) fn compile(
     expr: &Expr,
     graph: &PackageGraph,
+    cx: &ParseContext<'_>,
 ) -> Result<FilteringExpr, Vec<ParseSingleError>> {
     let in_workspace_packages: Vec<_> = graph
         .resolve_workspace()
@@ -24,7 +27,7 @@ pub(crate) fn compile(
         .collect();
     let mut cache = graph.new_depends_cache();
     let mut errors = vec![];
-    let expr = compile_expr(expr, &in_workspace_packages, &mut cache, &mut errors);
+    let expr = compile_expr(expr, &in_workspace_packages, &mut cache, cx, &mut errors);
 
     if errors.is_empty() {
         Ok(expr)
@@ -90,53 +93,130 @@ fn compile_set_def(
     set: &SetDef,
     packages: &[PackageMetadata],
     cache: &mut DependsCache,
+    cx: &ParseContext<'_>,
     errors: &mut Vec<ParseSingleError>,
 ) -> FilteringSet {
     match set {
         SetDef::Package(matcher, span) => FilteringSet::Packages(expect_non_empty(
             matching_packages(matcher, packages),
+            matcher,
             *span,
+            packages,
             errors,
         )),
         SetDef::Deps(matcher, span) => FilteringSet::Packages(expect_non_empty(
             dependencies_packages(matcher, packages, cache),
+            matcher,
             *span,
+            packages,
             errors,
         )),
         SetDef::Rdeps(matcher, span) => FilteringSet::Packages(expect_non_empty(
             rdependencies_packages(matcher, packages, cache),
+            matcher,
             *span,
+            packages,
             errors,
         )),
         SetDef::Kind(matcher, span) => FilteringSet::Kind(matcher.clone(), *span),
+        SetDef::Feature(matcher, span) => FilteringSet::Feature(matcher.clone(), *span),
         SetDef::Binary(matcher, span) => FilteringSet::Binary(matcher.clone(), *span),
+        SetDef::BinaryPath(matcher, span) => FilteringSet::BinaryPath(matcher.clone(), *span),
         SetDef::Platform(platform, span) => FilteringSet::Platform(*platform, *span),
         SetDef::Test(matcher, span) => FilteringSet::Test(matcher.clone(), *span),
+        SetDef::RunStatus(status, span) => match cx.previous_run {
+            Some(previous_run) => {
+                FilteringSet::RunStatus(*status, previous_run.tests_with_status(*status))
+            }
+            None => {
+                errors.push(ParseSingleError::PreviousRunUnavailable(*span));
+                FilteringSet::None
+            }
+        },
+        SetDef::SlowerThan(threshold, span) => match cx.previous_run {
+            Some(previous_run) => FilteringSet::SlowerThan(
+                *threshold,
+                previous_run.tests_with_duration(|duration| duration > *threshold),
+            ),
+            None => {
+                errors.push(ParseSingleError::PreviousRunUnavailable(*span));
+                FilteringSet::None
+            }
+        },
+        SetDef::FasterThan(threshold, span) => match cx.previous_run {
+            Some(previous_run) => FilteringSet::FasterThan(
+                *threshold,
+                previous_run.tests_with_duration(|duration| duration < *threshold),
+            ),
+            None => {
+                errors.push(ParseSingleError::PreviousRunUnavailable(*span));
+                FilteringSet::None
+            }
+        },
+        SetDef::Since(git_ref, span) => match cx.changed_files {
+            Some(changed_files) => match changed_files.changed_files(git_ref) {
+                Ok(files) => FilteringSet::Packages(changed_packages(&files, packages, cache)),
+                Err(message) => {
+                    errors.push(ParseSingleError::ChangedFilesError {
+                        span: *span,
+                        message,
+                    });
+                    FilteringSet::None
+                }
+            },
+            None => {
+                errors.push(ParseSingleError::ChangedFilesUnavailable(*span));
+                FilteringSet::None
+            }
+        },
+        SetDef::Alias(..) | SetDef::Tag(..) => {
+            unreachable!("aliases and tags are expanded by compile_expr")
+        }
         SetDef::All => FilteringSet::All,
         SetDef::None => FilteringSet::None,
     }
 }
 
 fn expect_non_empty(
-    packages: HashSet<PackageId>,
+    matched: HashSet<PackageId>,
+    matcher: &NameMatcher,
     span: SourceSpan,
+    all_packages: &[PackageMetadata],
     errors: &mut Vec<ParseSingleError>,
 ) -> HashSet<PackageId> {
-    if packages.is_empty() {
-        errors.push(ParseSingleError::NoPackageMatch(span));
+    if matched.is_empty() {
+        // Only exact names are likely to be typos of package names.
+        let help = match matcher {
+            NameMatcher::Equal(name) => did_you_mean(
+                name,
+                all_packages.iter().map(|p| p.name()),
+                ToOwned::to_owned,
+            ),
+            NameMatcher::Contains(_) | NameMatcher::Regex(_) => None,
+        };
+        errors.push(ParseSingleError::NoPackageMatch { span, help });
     }
-    packages
+    matched
 }
 
 fn compile_expr(
     expr: &Expr,
     packages: &[PackageMetadata],
     cache: &mut DependsCache,
+    cx: &ParseContext<'_>,
     errors: &mut Vec<ParseSingleError>,
 ) -> FilteringExpr {
     use crate::expression::ExprLayer::*;
     Wrapped(expr).collapse_layers(|layer: ExprLayer<&SetDef, FilteringExpr>| match layer {
-        Set(set) => FilteringExpr::Set(compile_set_def(set, packages, cache, errors)),
+        Set(SetDef::Alias(name, span)) => compile_alias(name, *span, packages, cache, cx, errors),
+        Set(SetDef::Tag(matcher, span)) => match cx.tags {
+            Some(tags) => tags.matching(matcher),
+            None => {
+                errors.push(ParseSingleError::TagsUnavailable(*span));
+                FilteringExpr::Set(FilteringSet::None)
+            }
+        },
+        Set(set) => FilteringExpr::Set(compile_set_def(set, packages, cache, cx, errors)),
         Not(expr) => FilteringExpr::Not(Box::new(expr)),
         Union(expr_1, expr_2) => FilteringExpr::Union(Box::new(expr_1), Box::new(expr_2)),
         Intersection(expr_1, expr_2) => {
@@ -144,3 +224,45 @@ fn compile_expr(
         }
     })
 }
+
+fn compile_alias(
+    name: &str,
+    span: SourceSpan,
+    packages: &[PackageMetadata],
+    cache: &mut DependsCache,
+    cx: &ParseContext<'_>,
+    errors: &mut Vec<ParseSingleError>,
+) -> FilteringExpr {
+    let alias = match cx.aliases.and_then(|aliases| aliases.get(name)) {
+        Some(alias) => alias,
+        None => {
+            errors.push(ParseSingleError::UnknownAlias(span));
+            return FilteringExpr::Set(FilteringSet::None);
+        }
+    };
+
+    // Aliases are checked for errors when they're defined, so the only errors that can happen here
+    // come from where the alias is used. Report them against the alias, since spans within it don't
+    // make sense in this expression.
+    let mut alias_errors = vec![];
+    let expr = compile_expr(&alias.expr, packages, cache, cx, &mut alias_errors);
+    for error in alias_errors {
+        let error = match error {
+            ParseSingleError::PreviousRunUnavailable(_) => {
+                ParseSingleError::PreviousRunUnavailable(span)
+            }
+            ParseSingleError::TagsUnavailable(_) => ParseSingleError::TagsUnavailable(span),
+            ParseSingleError::ChangedFilesUnavailable(_) => {
+                ParseSingleError::ChangedFilesUnavailable(span)
+            }
+            ParseSingleError::ChangedFilesError { message, .. } => {
+                ParseSingleError::ChangedFilesError { span, message }
+            }
+            _ => continue,
+        };
+        if !errors.contains(&error) {
+            errors.push(error);
+        }
+    }
+    expr
+}
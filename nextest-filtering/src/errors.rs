@@ -26,6 +26,26 @@ impl FilterExpressionParseErrors {
     }
 }
 
+/// A set of errors that occurred while parsing a filterset alias.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct FilterAliasParseErrors {
+    /// The name of the alias.
+    pub name: String,
+
+    /// The alias's expression, and the errors that occurred.
+    pub parse_errors: FilterExpressionParseErrors,
+}
+
+impl FilterAliasParseErrors {
+    pub(crate) fn new(name: impl Into<String>, parse_errors: FilterExpressionParseErrors) -> Self {
+        Self {
+            name: name.into(),
+            parse_errors,
+        }
+    }
+}
+
 /// An individual error that occurred while parsing a filter expression.
 #[derive(Clone, Debug, Error, Diagnostic, PartialEq, Eq)]
 #[non_exhaustive]
@@ -79,7 +99,89 @@ pub enum ParseSingleError {
 
     /// This matcher didn't match any packages.
     #[error("operator didn't match any packages")]
-    NoPackageMatch(#[label("no packages matched this")] SourceSpan),
+    NoPackageMatch {
+        /// The matcher.
+        #[label("no packages matched this")]
+        span: SourceSpan,
+
+        /// A suggestion for a package with a similar name, if there is one.
+        #[help]
+        help: Option<String>,
+    },
+
+    /// A predicate that doesn't exist was used.
+    #[error("unknown predicate")]
+    UnknownPredicate {
+        /// The name of the predicate.
+        #[label("no predicate with this name")]
+        span: SourceSpan,
+
+        /// A suggestion for a predicate with a similar name, if there is one.
+        #[help]
+        help: Option<String>,
+    },
+
+    /// A predicate based on previous runs, such as `failed()`, was used where information about
+    /// previous runs isn't available.
+    #[error("previous run status not available")]
+    PreviousRunUnavailable(#[label("this predicate can't be used here")] SourceSpan),
+
+    /// The `tag()` predicate was used where test tags aren't available, for example in the rules
+    /// that assign tags.
+    #[error("test tags not available")]
+    TagsUnavailable(#[label("tags can't be used here")] SourceSpan),
+
+    /// The `since()` predicate was used where changed files can't be determined, for example in
+    /// the config.
+    #[error("changed files not available")]
+    ChangedFilesUnavailable(#[label("this predicate can't be used here")] SourceSpan),
+
+    /// The files changed since the git ref passed into `since()` couldn't be determined.
+    #[error("failed to determine changed files")]
+    ChangedFilesError {
+        /// The git ref.
+        #[label("{}", message)]
+        span: SourceSpan,
+
+        /// A message indicating the failure.
+        message: String,
+    },
+
+    /// Expected a duration for a `slower_than()` or `faster_than()` predicate.
+    #[error("invalid argument for duration")]
+    InvalidDurationArgument(#[label("expected a duration like \"5s\" or \"500ms\"")] SourceSpan),
+
+    /// An invalid glob was passed into `binary_path()`.
+    #[error("invalid glob")]
+    InvalidGlob {
+        /// The part of the input that failed.
+        #[label("{}", message)]
+        span: SourceSpan,
+
+        /// A message indicating the failure.
+        message: String,
+    },
+
+    /// An `alias()` predicate referred to an alias that isn't defined.
+    #[error("unknown alias")]
+    UnknownAlias(#[label("no alias with this name is defined")] SourceSpan),
+
+    /// An alias referred back to itself, directly or through other aliases.
+    #[error("alias refers to itself")]
+    AliasCycle {
+        /// The part of the input that starts the cycle.
+        #[label("cycle: {}", cycle)]
+        span: SourceSpan,
+
+        /// The aliases that make up the cycle, separated by `->`.
+        cycle: String,
+    },
+
+    /// The name of an alias contained characters other than letters, numbers, `-` and `_`.
+    #[error("invalid alias name")]
+    InvalidAliasName(
+        #[label("alias names can only contain letters, numbers, `-` and `_`")] SourceSpan,
+    ),
 
     /// Expected "host" or "target" for a `platform()` predicate.
     #[error("invalid argument for platform")]
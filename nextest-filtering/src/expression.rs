@@ -4,7 +4,10 @@
 use crate::{
     errors::{FilterExpressionParseErrors, ParseSingleError, State},
     parsing::{parse, Expr, ParsedExpr, SetDef, Span},
+    ChangedFiles, FilterAliases, TestTags,
 };
+use camino::Utf8Path;
+use globset::{GlobBuilder, GlobMatcher};
 use guppy::{
     graph::{cargo::BuildPlatform, PackageGraph},
     PackageId,
@@ -14,7 +17,12 @@ use recursion::{
     map_layer::{MapLayer, Project},
     Collapse,
 };
-use std::{cell::RefCell, collections::HashSet};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt,
+    time::Duration,
+};
 
 /// Matcher for name
 ///
@@ -42,6 +50,28 @@ impl PartialEq for NameMatcher {
 
 impl Eq for NameMatcher {}
 
+/// Matcher for paths, using glob syntax
+///
+/// Used for binary paths
+#[derive(Clone)]
+pub struct PathMatcher(GlobMatcher);
+
+impl fmt::Debug for PathMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PathMatcher")
+            .field(&self.0.glob().glob())
+            .finish()
+    }
+}
+
+impl PartialEq for PathMatcher {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.glob() == other.0.glob()
+    }
+}
+
+impl Eq for PathMatcher {}
+
 /// Define a set of tests
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum FilteringSet {
@@ -49,12 +79,25 @@ pub enum FilteringSet {
     Packages(HashSet<PackageId>),
     /// All tests present in this kind of binary.
     Kind(NameMatcher, SourceSpan),
+    /// All tests in binaries built with a Cargo feature matching a name.
+    Feature(NameMatcher, SourceSpan),
     /// The platform a test is built for.
     Platform(BuildPlatform, SourceSpan),
+    /// All binaries with a path matching a glob
+    BinaryPath(PathMatcher, SourceSpan),
     /// All binaries matching a name
     Binary(NameMatcher, SourceSpan),
     /// All tests matching a name
     Test(NameMatcher, SourceSpan),
+    /// All tests with this status in a previous run, keyed by binary ID
+    RunStatus(RunStatus, HashMap<String, HashSet<String>>),
+    /// All tests that took longer than this duration in previous runs, keyed by binary ID
+    SlowerThan(Duration, HashMap<String, HashSet<String>>),
+    /// All tests that took less time than this duration in previous runs, keyed by binary ID
+    FasterThan(Duration, HashMap<String, HashSet<String>>),
+    /// All tests with a component in their name that starts with this prefix, followed by a tag
+    /// matching a name
+    NameTag(String, NameMatcher),
     /// All tests
     All,
     /// No tests
@@ -67,12 +110,22 @@ pub struct BinaryQuery<'a> {
     /// The package ID.
     pub package_id: &'a PackageId,
 
+    /// The unique ID of the binary, as used by the `failed()`, `flaky()` and `skipped()`
+    /// predicates.
+    pub binary_id: &'a str,
+
     /// The name of the binary.
     pub binary_name: &'a str,
 
+    /// The path to the binary.
+    pub binary_path: &'a Utf8Path,
+
     /// The kind of binary this test is (lib, test etc).
     pub kind: &'a str,
 
+    /// The Cargo features the binary's package was built with.
+    pub features: &'a [String],
+
     /// The platform this test is built for.
     pub platform: BuildPlatform,
 }
@@ -87,6 +140,103 @@ pub struct TestQuery<'a> {
     pub test_name: &'a str,
 }
 
+/// The status of a test in a previous run.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum RunStatus {
+    /// The test passed on the first attempt.
+    Passed,
+    /// The test failed on every attempt.
+    Failed,
+    /// The test failed at first, then passed on a retry.
+    Flaky,
+    /// The test wasn't run, for example because it was filtered out or the run was cancelled.
+    Skipped,
+}
+
+/// Information about tests in previous runs, passed into
+/// [`FilteringExpr::parse_with_previous_run`].
+///
+/// Statuses are used by the `failed()`, `flaky()` and `skipped()` predicates, and durations by the
+/// `slower_than()` and `faster_than()` predicates.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PreviousRun {
+    // binary ID -> test name -> status
+    tests: HashMap<String, HashMap<String, RunStatus>>,
+    // binary ID -> test name -> duration
+    durations: HashMap<String, HashMap<String, Duration>>,
+}
+
+impl PreviousRun {
+    /// Creates a new, empty `PreviousRun`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the status of a test.
+    pub fn insert(
+        &mut self,
+        binary_id: impl Into<String>,
+        test_name: impl Into<String>,
+        status: RunStatus,
+    ) {
+        self.tests
+            .entry(binary_id.into())
+            .or_default()
+            .insert(test_name.into(), status);
+    }
+
+    /// Returns the status of a test, or `None` if it wasn't part of the previous run.
+    pub fn status(&self, binary_id: &str, test_name: &str) -> Option<RunStatus> {
+        self.tests.get(binary_id)?.get(test_name).copied()
+    }
+
+    /// Records how long a test took to run.
+    pub fn insert_duration(
+        &mut self,
+        binary_id: impl Into<String>,
+        test_name: impl Into<String>,
+        duration: Duration,
+    ) {
+        self.durations
+            .entry(binary_id.into())
+            .or_default()
+            .insert(test_name.into(), duration);
+    }
+
+    /// Returns how long a test took to run, or `None` if that wasn't recorded.
+    pub fn duration(&self, binary_id: &str, test_name: &str) -> Option<Duration> {
+        self.durations.get(binary_id)?.get(test_name).copied()
+    }
+
+    pub(crate) fn tests_with_status(&self, status: RunStatus) -> HashMap<String, HashSet<String>> {
+        tests_where(&self.tests, |test_status| *test_status == status)
+    }
+
+    pub(crate) fn tests_with_duration(
+        &self,
+        f: impl Fn(Duration) -> bool,
+    ) -> HashMap<String, HashSet<String>> {
+        tests_where(&self.durations, |duration| f(*duration))
+    }
+}
+
+fn tests_where<T>(
+    tests: &HashMap<String, HashMap<String, T>>,
+    f: impl Fn(&T) -> bool,
+) -> HashMap<String, HashSet<String>> {
+    tests
+        .iter()
+        .filter_map(|(binary_id, tests)| {
+            let tests: HashSet<_> = tests
+                .iter()
+                .filter(|(_, value)| f(value))
+                .map(|(test_name, _)| test_name.clone())
+                .collect();
+            (!tests.is_empty()).then(|| (binary_id.clone(), tests))
+        })
+        .collect()
+}
+
 /// Filtering expression
 ///
 /// Used to filter tests to run.
@@ -112,6 +262,18 @@ impl NameMatcher {
     }
 }
 
+impl PathMatcher {
+    /// Creates a matcher for a glob, in which `*` and `?` don't match path separators.
+    pub(crate) fn new(glob: &str) -> Result<Self, globset::Error> {
+        let glob = GlobBuilder::new(glob).literal_separator(true).build()?;
+        Ok(Self(glob.compile_matcher()))
+    }
+
+    pub(crate) fn is_match(&self, path: &Utf8Path) -> bool {
+        self.0.is_match(path)
+    }
+}
+
 impl FilteringSet {
     fn matches_test(&self, query: &TestQuery<'_>) -> bool {
         match self {
@@ -119,9 +281,23 @@ impl FilteringSet {
             Self::None => false,
             Self::Test(matcher, _) => matcher.is_match(query.test_name),
             Self::Binary(matcher, _) => matcher.is_match(query.binary_query.binary_name),
+            Self::BinaryPath(matcher, _) => matcher.is_match(query.binary_query.binary_path),
             Self::Platform(platform, _) => query.binary_query.platform == *platform,
             Self::Kind(matcher, _) => matcher.is_match(query.binary_query.kind),
+            Self::Feature(matcher, _) => query
+                .binary_query
+                .features
+                .iter()
+                .any(|feature| matcher.is_match(feature)),
             Self::Packages(packages) => packages.contains(query.binary_query.package_id),
+            Self::RunStatus(_, tests) | Self::SlowerThan(_, tests) | Self::FasterThan(_, tests) => {
+                tests
+                    .get(query.binary_query.binary_id)
+                    .map_or(false, |tests| tests.contains(query.test_name))
+            }
+            Self::NameTag(prefix, matcher) => {
+                name_tags(prefix, query.test_name).any(|tag| matcher.is_match(tag))
+            }
         }
     }
 
@@ -129,55 +305,122 @@ impl FilteringSet {
         match self {
             Self::All => Logic::top(),
             Self::None => Logic::bottom(),
-            Self::Test(_, _) => None,
+            Self::Test(_, _) | Self::NameTag(_, _) => None,
             Self::Binary(matcher, _) => Some(matcher.is_match(query.binary_name)),
+            Self::BinaryPath(matcher, _) => Some(matcher.is_match(query.binary_path)),
             Self::Platform(platform, _) => Some(query.platform == *platform),
             Self::Kind(matcher, _) => Some(matcher.is_match(query.kind)),
+            Self::Feature(matcher, _) => Some(
+                query
+                    .features
+                    .iter()
+                    .any(|feature| matcher.is_match(feature)),
+            ),
             Self::Packages(packages) => Some(packages.contains(query.package_id)),
+            Self::RunStatus(_, tests) | Self::SlowerThan(_, tests) | Self::FasterThan(_, tests) => {
+                if tests.contains_key(query.binary_id) {
+                    None
+                } else {
+                    // No tests in this binary are in the set.
+                    Some(false)
+                }
+            }
         }
     }
 }
 
+/// Returns the tags a test has due to its name: the rest of each `::`-separated component of the
+/// name that starts with `prefix`.
+pub(crate) fn name_tags<'a>(prefix: &'a str, test_name: &'a str) -> impl Iterator<Item = &'a str> {
+    test_name
+        .split("::")
+        .filter_map(move |component| component.strip_prefix(prefix))
+        .filter(|tag| !tag.is_empty())
+}
+
+/// Information that filter expressions can refer to, other than the package graph.
+///
+/// Passed in to [`FilteringExpr::parse_with_context`]. Predicates that need information that isn't
+/// set here are rejected.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ParseContext<'a> {
+    pub(crate) aliases: Option<&'a FilterAliases>,
+    pub(crate) tags: Option<&'a TestTags>,
+    pub(crate) previous_run: Option<&'a PreviousRun>,
+    pub(crate) changed_files: Option<&'a dyn ChangedFiles>,
+}
+
+impl<'a> ParseContext<'a> {
+    /// Creates a new, empty context.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the aliases that `alias()` refers to.
+    pub fn set_aliases(&mut self, aliases: &'a FilterAliases) -> &mut Self {
+        self.aliases = Some(aliases);
+        self
+    }
+
+    /// Sets the tags that `tag()` matches against.
+    pub fn set_tags(&mut self, tags: &'a TestTags) -> &mut Self {
+        self.tags = Some(tags);
+        self
+    }
+
+    /// Sets the information about previous runs used by the `failed()`, `flaky()`, `skipped()`,
+    /// `slower_than()` and `faster_than()` predicates.
+    pub fn set_previous_run(&mut self, previous_run: &'a PreviousRun) -> &mut Self {
+        self.previous_run = Some(previous_run);
+        self
+    }
+
+    /// Sets how the `since()` predicate finds the files changed since a git ref.
+    ///
+    /// Changed files are only looked up for the git refs used in expressions.
+    pub fn set_changed_files(&mut self, changed_files: &'a dyn ChangedFiles) -> &mut Self {
+        self.changed_files = Some(changed_files);
+        self
+    }
+}
+
 impl FilteringExpr {
     /// Parse a filtering expression
+    ///
+    /// The `alias()`, `tag()` and `since()` predicates, and predicates based on previous runs,
+    /// aren't supported by expressions parsed with this method. To use them, call
+    /// [`parse_with_context`](Self::parse_with_context).
     pub fn parse(
         input: &str,
         graph: &PackageGraph,
     ) -> Result<FilteringExpr, FilterExpressionParseErrors> {
-        let errors = RefCell::new(Vec::new());
-        match parse(Span::new_extra(input, State::new(&errors))) {
-            Ok(parsed_expr) => {
-                let errors = errors.into_inner();
+        Self::parse_with_context(input, graph, &ParseContext::new())
+    }
 
-                if !errors.is_empty() {
-                    return Err(FilterExpressionParseErrors::new(input, errors));
-                }
+    /// Parse a filtering expression, using the given information about previous runs for the
+    /// `failed()`, `flaky()`, `skipped()`, `slower_than()` and `faster_than()` predicates.
+    pub fn parse_with_previous_run(
+        input: &str,
+        graph: &PackageGraph,
+        previous_run: &PreviousRun,
+    ) -> Result<FilteringExpr, FilterExpressionParseErrors> {
+        Self::parse_with_context(
+            input,
+            graph,
+            ParseContext::new().set_previous_run(previous_run),
+        )
+    }
 
-                match parsed_expr {
-                    ParsedExpr::Valid(expr) => crate::compile::compile(&expr, graph)
-                        .map_err(|errors| FilterExpressionParseErrors::new(input, errors)),
-                    _ => {
-                        // should not happen
-                        // If an ParsedExpr::Error is produced, we should also have an error inside
-                        // errors and we should already have returned
-                        // IMPROVE this is an internal error => add log to suggest opening an bug ?
-                        Err(FilterExpressionParseErrors::new(
-                            input,
-                            vec![ParseSingleError::Unknown],
-                        ))
-                    }
-                }
-            }
-            Err(_) => {
-                // should not happen
-                // According to our parsing strategy we should never produce an Err(_)
-                // IMPROVE this is an internal error => add log to suggest opening an bug ?
-                Err(FilterExpressionParseErrors::new(
-                    input,
-                    vec![ParseSingleError::Unknown],
-                ))
-            }
-        }
+    /// Parse a filtering expression, using the given context for the `alias()`, `tag()` and
+    /// `since()` predicates, and for predicates based on previous runs.
+    pub fn parse_with_context(
+        input: &str,
+        graph: &PackageGraph,
+        cx: &ParseContext<'_>,
+    ) -> Result<FilteringExpr, FilterExpressionParseErrors> {
+        let expr = parse_expr(input)?;
+        crate::compile::compile(&expr, graph, cx)
+            .map_err(|errors| FilterExpressionParseErrors::new(input, errors))
     }
 
     /// Returns a value indicating if the given binary is accepted by this filter expression.
@@ -212,8 +455,59 @@ impl FilteringExpr {
 
     /// Returns true if the given expression needs dependencies information to work
     pub fn needs_deps(raw_expr: &str) -> bool {
-        // the expression needs dependencies expression if it uses deps(..) or rdeps(..)
-        raw_expr.contains("deps")
+        // the expression needs dependencies expression if it uses deps(..), rdeps(..) or
+        // since(..)
+        // aliases and tags aren't known at this point, so assume that any alias(..) or tag(..)
+        // might use them
+        ["deps", "since", "alias", "tag"]
+            .iter()
+            .any(|name| raw_expr.contains(name))
+    }
+
+    /// Returns true if the given expression needs information about previous runs to work
+    pub fn needs_previous_run(raw_expr: &str) -> bool {
+        // the expression needs a previous run if it uses failed(), flaky(), skipped(),
+        // slower_than() or faster_than()
+        ["failed", "flaky", "skipped", "slower_than", "faster_than"]
+            .iter()
+            .any(|name| raw_expr.contains(name))
+    }
+}
+
+/// Parses the syntax of a filtering expression, without resolving it against a package graph.
+pub(crate) fn parse_expr(input: &str) -> Result<Expr, FilterExpressionParseErrors> {
+    let errors = RefCell::new(Vec::new());
+    match parse(Span::new_extra(input, State::new(&errors))) {
+        Ok(parsed_expr) => {
+            let errors = errors.into_inner();
+
+            if !errors.is_empty() {
+                return Err(FilterExpressionParseErrors::new(input, errors));
+            }
+
+            match parsed_expr {
+                ParsedExpr::Valid(expr) => Ok(expr),
+                _ => {
+                    // should not happen
+                    // If an ParsedExpr::Error is produced, we should also have an error inside
+                    // errors and we should already have returned
+                    // IMPROVE this is an internal error => add log to suggest opening an bug ?
+                    Err(FilterExpressionParseErrors::new(
+                        input,
+                        vec![ParseSingleError::Unknown],
+                    ))
+                }
+            }
+        }
+        Err(_) => {
+            // should not happen
+            // According to our parsing strategy we should never produce an Err(_)
+            // IMPROVE this is an internal error => add log to suggest opening an bug ?
+            Err(FilterExpressionParseErrors::new(
+                input,
+                vec![ParseSingleError::Unknown],
+            ))
+        }
     }
 }
 
@@ -4,9 +4,19 @@
 //! Logic for parsing [filter expressions](https://nexte.st/book/filter-expressions) used by
 //! cargo-nextest.
 
+mod aliases;
+mod changed;
 mod compile;
 pub mod errors;
 mod expression;
 mod parsing;
+mod suggest;
+mod tags;
 
-pub use expression::{BinaryQuery, FilteringExpr, FilteringSet, NameMatcher, TestQuery};
+pub use aliases::FilterAliases;
+pub use changed::ChangedFiles;
+pub use expression::{
+    BinaryQuery, FilteringExpr, FilteringSet, NameMatcher, ParseContext, PathMatcher, PreviousRun,
+    RunStatus, TestQuery,
+};
+pub use tags::TestTags;
@@ -17,7 +17,7 @@ use guppy::graph::cargo::BuildPlatform;
 use miette::SourceSpan;
 use nom::{
     branch::alt,
-    bytes::complete::{is_not, tag, take_till},
+    bytes::complete::{is_not, tag, take_till, take_while1},
     character::complete::char,
     combinator::{eof, map, peek, recognize, verify},
     multi::{fold_many0, many0},
@@ -25,10 +25,11 @@ use nom::{
     Slice,
 };
 use nom_tracable::tracable_parser;
+use std::time::Duration;
 
 mod unicode_string;
 
-use crate::{errors::*, NameMatcher};
+use crate::{errors::*, suggest::did_you_mean, NameMatcher, PathMatcher, RunStatus};
 
 pub(crate) type Span<'a> = nom_locate::LocatedSpan<&'a str, State<'a>>;
 type IResult<'a, T> = nom::IResult<Span<'a>, T>;
@@ -39,20 +40,28 @@ impl<'a> ToSourceSpan for Span<'a> {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) enum SetDef {
     Package(NameMatcher, SourceSpan),
     Deps(NameMatcher, SourceSpan),
     Rdeps(NameMatcher, SourceSpan),
     Kind(NameMatcher, SourceSpan),
+    Feature(NameMatcher, SourceSpan),
     Binary(NameMatcher, SourceSpan),
+    BinaryPath(PathMatcher, SourceSpan),
     Platform(BuildPlatform, SourceSpan),
     Test(NameMatcher, SourceSpan),
+    RunStatus(RunStatus, SourceSpan),
+    SlowerThan(Duration, SourceSpan),
+    FasterThan(Duration, SourceSpan),
+    Alias(String, SourceSpan),
+    Tag(NameMatcher, SourceSpan),
+    Since(String, SourceSpan),
     All,
     None,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) enum Expr {
     Not(Box<Expr>),
     Union(Box<Expr>, Box<Expr>),
@@ -337,10 +346,10 @@ fn recover_unexpected_comma(input: Span) -> IResult<()> {
 
 fn nullary_set_def(
     name: &'static str,
-    make_set: fn() -> SetDef,
+    make_set: fn(SourceSpan) -> SetDef,
 ) -> impl FnMut(Span) -> IResult<Option<SetDef>> {
     move |i| {
-        let (i, _) = tag(name)(i)?;
+        let (i, name_span) = tag(name)(i)?;
         let (i, _) = expect_char('(', ParseSingleError::ExpectedOpenParenthesis)(i)?;
         let i = match recognize::<_, _, nom::error::Error<Span>, _>(take_till(|c| c == ')'))(i) {
             Ok((i, res)) => {
@@ -353,7 +362,7 @@ fn nullary_set_def(
             Err(_) => unreachable!(),
         };
         let (i, _) = expect_char(')', ParseSingleError::ExpectedCloseParenthesis)(i)?;
-        Ok((i, Some(make_set())))
+        Ok((i, Some(make_set(name_span.to_span()))))
     }
 }
 
@@ -409,19 +418,170 @@ fn platform_def(i: Span) -> IResult<Option<SetDef>> {
     ))
 }
 
+fn binary_path_def(i: Span) -> IResult<Option<SetDef>> {
+    let (i, _) = tag("binary_path")(i)?;
+    let (i, _) = expect_char('(', ParseSingleError::ExpectedOpenParenthesis)(i)?;
+    let start = i.location_offset();
+    let (i, res) = ws(parse_matcher_text)(i)?;
+    let end = i.location_offset();
+    let (i, _) = recover_unexpected_comma(i)?;
+    let (i, _) = expect_char(')', ParseSingleError::ExpectedCloseParenthesis)(i)?;
+
+    let span: SourceSpan = (start, end - start).into();
+    let matcher = match res.as_deref().map(|res| res.trim()) {
+        Some(res) => match PathMatcher::new(res) {
+            Ok(matcher) => Some(matcher),
+            Err(err) => {
+                i.extra.report_error(ParseSingleError::InvalidGlob {
+                    span,
+                    message: err.kind().to_string(),
+                });
+                None
+            }
+        },
+        None => {
+            // This was already reported above.
+            None
+        }
+    };
+    Ok((i, matcher.map(|matcher| SetDef::BinaryPath(matcher, span))))
+}
+
+fn duration_set_def(
+    name: &'static str,
+    make_set: fn(Duration, SourceSpan) -> SetDef,
+) -> impl FnMut(Span) -> IResult<Option<SetDef>> {
+    move |i| {
+        let (i, _) = tag(name)(i)?;
+        let (i, _) = expect_char('(', ParseSingleError::ExpectedOpenParenthesis)(i)?;
+        let start = i.location_offset();
+        // Try parsing the argument as a string for better error messages.
+        let (i, res) = ws(parse_matcher_text)(i)?;
+        let end = i.location_offset();
+        let (i, _) = recover_unexpected_comma(i)?;
+        let (i, _) = expect_char(')', ParseSingleError::ExpectedCloseParenthesis)(i)?;
+
+        let span: SourceSpan = (start, end - start).into();
+        let duration = match res.as_deref().map(|res| res.trim()) {
+            Some(res) => match humantime::parse_duration(res) {
+                Ok(duration) => Some(duration),
+                Err(_) => {
+                    i.extra
+                        .report_error(ParseSingleError::InvalidDurationArgument(span));
+                    None
+                }
+            },
+            None => {
+                // This was already reported above.
+                None
+            }
+        };
+        Ok((i, duration.map(|duration| make_set(duration, span))))
+    }
+}
+
+fn string_set_def(
+    name: &'static str,
+    make_set: fn(String, SourceSpan) -> SetDef,
+) -> impl FnMut(Span) -> IResult<Option<SetDef>> {
+    move |i| {
+        let (i, _) = tag(name)(i)?;
+        let (i, _) = expect_char('(', ParseSingleError::ExpectedOpenParenthesis)(i)?;
+        let start = i.location_offset();
+        let (i, res) = ws(parse_matcher_text)(i)?;
+        let end = i.location_offset();
+        let (i, _) = recover_unexpected_comma(i)?;
+        let (i, _) = expect_char(')', ParseSingleError::ExpectedCloseParenthesis)(i)?;
+
+        // The returned string will include leading and trailing whitespace. Whether it refers to
+        // something that exists, such as an alias or a git ref, is checked while compiling the
+        // expression.
+        let set = res.map(|arg| make_set(arg.trim().to_owned(), (start, end - start).into()));
+        Ok((i, set))
+    }
+}
+
+/// The names of all the predicates, as parsed by [`parse_set_def`].
+const PREDICATE_NAMES: &[&str] = &[
+    "package",
+    "deps",
+    "rdeps",
+    "kind",
+    "feature",
+    "binary_path",
+    "binary",
+    "test",
+    "tag",
+    "platform",
+    "failed",
+    "flaky",
+    "skipped",
+    "slower_than",
+    "faster_than",
+    "alias",
+    "since",
+    "all",
+    "none",
+];
+
+/// Parses a call to a predicate that doesn't exist, reporting an error with a suggestion for a
+/// predicate with a similar name.
+///
+/// Fails without consuming any input if the predicate exists, so that it can be parsed normally.
+fn unknown_set_def(input: Span) -> IResult<Option<SetDef>> {
+    let (i, name) = take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_')(input.clone())?;
+    let (i, _) = peek(ws(char('(')))(i)?;
+    let is_known = PREDICATE_NAMES.contains(name.fragment())
+        || matches!(*name.fragment(), "not" | "and" | "or");
+    if is_known {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Verify,
+        )));
+    }
+
+    let help = did_you_mean(name.fragment(), PREDICATE_NAMES.iter().copied(), |name| {
+        format!("{name}()")
+    });
+    i.extra.report_error(ParseSingleError::UnknownPredicate {
+        span: name.to_span(),
+        help,
+    });
+    let (i, _) = ws(char('('))(i)?;
+    let (i, _) = take_till(|c| c == ')')(i)?;
+    let (i, _) = expect_char(')', ParseSingleError::ExpectedCloseParenthesis)(i)?;
+    Ok((i, None))
+}
+
 #[tracable_parser]
 fn parse_set_def(input: Span) -> IResult<Option<SetDef>> {
-    ws(alt((
+    ws(alt((unknown_set_def, parse_known_set_def)))(input)
+}
+
+fn parse_known_set_def(input: Span) -> IResult<Option<SetDef>> {
+    alt((
         unary_set_def("package", NameMatcher::Equal, SetDef::Package),
         unary_set_def("deps", NameMatcher::Equal, SetDef::Deps),
         unary_set_def("rdeps", NameMatcher::Equal, SetDef::Rdeps),
         unary_set_def("kind", NameMatcher::Equal, SetDef::Kind),
+        unary_set_def("feature", NameMatcher::Equal, SetDef::Feature),
+        binary_path_def,
         unary_set_def("binary", NameMatcher::Equal, SetDef::Binary),
         unary_set_def("test", NameMatcher::Contains, SetDef::Test),
+        unary_set_def("tag", NameMatcher::Equal, SetDef::Tag),
         platform_def,
-        nullary_set_def("all", || SetDef::All),
-        nullary_set_def("none", || SetDef::None),
-    )))(input)
+        nullary_set_def("failed", |span| SetDef::RunStatus(RunStatus::Failed, span)),
+        nullary_set_def("flaky", |span| SetDef::RunStatus(RunStatus::Flaky, span)),
+        nullary_set_def("skipped", |span| {
+            SetDef::RunStatus(RunStatus::Skipped, span)
+        }),
+        duration_set_def("slower_than", SetDef::SlowerThan),
+        duration_set_def("faster_than", SetDef::FasterThan),
+        string_set_def("alias", SetDef::Alias),
+        string_set_def("since", SetDef::Since),
+        nullary_set_def("all", |_| SetDef::All),
+        nullary_set_def("none", |_| SetDef::None),
+    ))(input)
 }
 
 fn expect_expr<'a, P: FnMut(Span<'a>) -> IResult<'a, ParsedExpr>>(
@@ -710,6 +870,44 @@ mod tests {
 
         assert_eq!(SetDef::None, parse_set("none()"));
 
+        assert_eq!(
+            SetDef::RunStatus(RunStatus::Failed, (0, 6).into()),
+            parse_set("failed()")
+        );
+        assert_eq!(
+            SetDef::RunStatus(RunStatus::Flaky, (1, 5).into()),
+            parse_set(" flaky ( ) ")
+        );
+        assert_eq!(
+            SetDef::RunStatus(RunStatus::Skipped, (0, 7).into()),
+            parse_set("skipped()")
+        );
+
+        assert_eq!(
+            SetDef::BinaryPath(PathMatcher::new("**/vendor/**").unwrap(), (12, 13).into()),
+            parse_set("binary_path( **/vendor/**)")
+        );
+
+        assert_set_def!(
+            parse_set("slower_than(5s)"),
+            SlowerThan,
+            Duration::from_secs(5)
+        );
+        assert_set_def!(
+            parse_set("faster_than( 1m 500ms )"),
+            FasterThan,
+            Duration::from_millis(60_500)
+        );
+
+        assert_eq!(
+            SetDef::Alias("integration".to_owned(), (6, 13).into()),
+            parse_set("alias( integration )")
+        );
+        assert_eq!(
+            SetDef::Since("origin/main".to_owned(), (6, 11).into()),
+            parse_set("since(origin/main)")
+        );
+
         assert_set_def!(
             parse_set("package(something)"),
             Package,
@@ -730,6 +928,16 @@ mod tests {
             Test,
             NameMatcher::Contains("something".to_string())
         );
+        assert_set_def!(
+            parse_set("feature(postgres)"),
+            Feature,
+            NameMatcher::Equal("postgres".to_string())
+        );
+        assert_set_def!(
+            parse_set("tag(gpu)"),
+            Tag,
+            NameMatcher::Equal("gpu".to_string())
+        );
         assert_set_def!(parse_set("platform(host)"), Platform, BuildPlatform::Host);
         assert_set_def!(
             parse_set("platform(target)"),
@@ -941,6 +1149,69 @@ mod tests {
         assert_error!(error, InvalidPlatformArgument, 9, 8);
     }
 
+    #[test]
+    fn test_invalid_duration() {
+        let src = "slower_than(fast)";
+        let mut errors = parse_err(src);
+        assert_eq!(1, errors.len());
+        let error = errors.remove(0);
+        assert_error!(error, InvalidDurationArgument, 12, 4);
+    }
+
+    #[test]
+    fn test_unknown_predicate() {
+        let src = "packge(foo) | tests(bar)";
+        let errors = parse_err(src);
+        assert_eq!(
+            errors,
+            vec![
+                ParseSingleError::UnknownPredicate {
+                    span: (0, 6).into(),
+                    help: Some("did you mean `package()`?".to_owned()),
+                },
+                ParseSingleError::UnknownPredicate {
+                    span: (14, 5).into(),
+                    help: Some("did you mean `test()`?".to_owned()),
+                },
+            ]
+        );
+
+        let src = "frobnicate(foo)";
+        let errors = parse_err(src);
+        assert_eq!(
+            errors,
+            vec![ParseSingleError::UnknownPredicate {
+                span: (0, 10).into(),
+                help: None,
+            }]
+        );
+
+        // Names of predicates that exist are never reported as unknown.
+        for name in PREDICATE_NAMES {
+            let src = format!("{name}()");
+            let errors = parse_err(&src);
+            assert!(
+                !errors
+                    .iter()
+                    .any(|error| matches!(error, ParseSingleError::UnknownPredicate { .. })),
+                "{name} isn't reported as unknown: {errors:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_glob() {
+        let src = "binary_path(a[b)";
+        let mut errors = parse_err(src);
+        assert_eq!(1, errors.len());
+        let error = errors.remove(0);
+        assert!(matches!(
+            error,
+            ParseSingleError::InvalidGlob { span, message }
+                if span == (12, 3).into() && message == "unclosed character class; missing ']'"
+        ));
+    }
+
     #[test]
     fn test_missing_close_regex() {
         let src = "package(/aaa)";
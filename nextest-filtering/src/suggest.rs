@@ -0,0 +1,33 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+/// Returns the candidate that's most similar to `name`, if any of them is similar enough that
+/// `name` is likely to be a typo for it.
+///
+/// Ties are broken by picking the candidate that sorts first.
+pub(crate) fn closest_match<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .map(|candidate| (strsim::jaro_winkler(name, candidate), candidate))
+        .filter(|(confidence, _)| *confidence > 0.8)
+        .max_by(|(confidence_1, candidate_1), (confidence_2, candidate_2)| {
+            confidence_1
+                .partial_cmp(confidence_2)
+                .expect("confidences are never NaN")
+                .then_with(|| candidate_2.cmp(candidate_1))
+        })
+        .map(|(_, candidate)| candidate)
+}
+
+/// Returns a "did you mean" message for [`closest_match`].
+pub(crate) fn did_you_mean<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+    format: impl FnOnce(&str) -> String,
+) -> Option<String> {
+    closest_match(name, candidates)
+        .map(|candidate| format!("did you mean `{}`?", format(candidate)))
+}
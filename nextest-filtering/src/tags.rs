@@ -0,0 +1,80 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{expression::name_tags, FilteringExpr, FilteringSet, NameMatcher, TestQuery};
+use std::collections::BTreeSet;
+
+/// Tags assigned to tests, which the `tag()` predicate matches against.
+///
+/// Tests are tagged by rules, each of which assigns tags to the tests matching a filter
+/// expression, and optionally by their names.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TestTags {
+    name_prefix: Option<String>,
+    rules: Vec<TagRule>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct TagRule {
+    expr: FilteringExpr,
+    tags: BTreeSet<String>,
+}
+
+impl TestTags {
+    /// Creates a new `TestTags` with no rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tags tests by their names: each `::`-separated component of a test name that starts with
+    /// `prefix` tags the test with the rest of that component.
+    ///
+    /// For example, with the prefix `tag_`, the test `renderer::tag_gpu::draws` is tagged `gpu`.
+    pub fn set_name_prefix(&mut self, prefix: impl Into<String>) -> &mut Self {
+        self.name_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Tags tests that match `expr` with `tags`.
+    pub fn add_rule(
+        &mut self,
+        expr: FilteringExpr,
+        tags: impl IntoIterator<Item = impl Into<String>>,
+    ) -> &mut Self {
+        self.rules.push(TagRule {
+            expr,
+            tags: tags.into_iter().map(Into::into).collect(),
+        });
+        self
+    }
+
+    /// Returns the tags for the given test.
+    pub fn tags_for(&self, query: &TestQuery<'_>) -> BTreeSet<String> {
+        let mut tags: BTreeSet<_> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.expr.matches_test(query))
+            .flat_map(|rule| rule.tags.iter().cloned())
+            .collect();
+        if let Some(prefix) = &self.name_prefix {
+            tags.extend(name_tags(prefix, query.test_name).map(|tag| tag.to_owned()));
+        }
+        tags
+    }
+
+    /// Returns an expression that matches tests with a tag matching `matcher`.
+    pub(crate) fn matching(&self, matcher: &NameMatcher) -> FilteringExpr {
+        let rule_exprs = self
+            .rules
+            .iter()
+            .filter(|rule| rule.tags.iter().any(|tag| matcher.is_match(tag)))
+            .map(|rule| rule.expr.clone());
+        let name_expr = self.name_prefix.as_ref().map(|prefix| {
+            FilteringExpr::Set(FilteringSet::NameTag(prefix.clone(), matcher.clone()))
+        });
+        rule_exprs
+            .chain(name_expr)
+            .reduce(|expr_1, expr_2| FilteringExpr::Union(Box::new(expr_1), Box::new(expr_2)))
+            .unwrap_or(FilteringExpr::Set(FilteringSet::None))
+    }
+}
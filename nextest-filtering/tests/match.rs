@@ -1,11 +1,14 @@
 // Copyright (c) The nextest Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use camino::{Utf8Path, Utf8PathBuf};
 use guppy::{graph::cargo::BuildPlatform, PackageId};
 use nextest_filtering::{
-    errors::{FilterExpressionParseErrors, ParseSingleError},
-    BinaryQuery, FilteringExpr, TestQuery,
+    errors::{FilterAliasParseErrors, FilterExpressionParseErrors, ParseSingleError},
+    BinaryQuery, ChangedFiles, FilterAliases, FilteringExpr, FilteringSet, ParseContext,
+    PreviousRun, RunStatus, TestQuery, TestTags,
 };
+use std::time::Duration;
 use test_case::test_case;
 
 #[track_caller]
@@ -36,8 +39,11 @@ fn test_expr_package_contains() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
         test_name: "test_something"
@@ -45,8 +51,11 @@ fn test_expr_package_contains() {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_b,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
         test_name: "test_something"
@@ -54,8 +63,11 @@ fn test_expr_package_contains() {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_c,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
         test_name: "test_something"
@@ -74,8 +86,11 @@ fn test_expr_package_equal() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
         test_name: "test_something"
@@ -83,8 +98,11 @@ fn test_expr_package_equal() {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_b,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
         test_name: "test_something"
@@ -92,8 +110,11 @@ fn test_expr_package_equal() {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_c,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
         test_name: "test_something"
@@ -112,8 +133,11 @@ fn test_expr_package_regex() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
         test_name: "test_something"
@@ -121,8 +145,11 @@ fn test_expr_package_regex() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_b,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
         test_name: "test_something"
@@ -130,8 +157,11 @@ fn test_expr_package_regex() {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_c,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
         test_name: "test_something"
@@ -155,8 +185,11 @@ fn test_expr_deps() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -165,8 +198,11 @@ fn test_expr_deps() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_b,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -175,8 +211,11 @@ fn test_expr_deps() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_c,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -185,8 +224,11 @@ fn test_expr_deps() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_d,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -197,8 +239,11 @@ fn test_expr_deps() {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_e,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -207,8 +252,11 @@ fn test_expr_deps() {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_f,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -217,8 +265,11 @@ fn test_expr_deps() {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_g,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -243,8 +294,11 @@ fn test_expr_rdeps() {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -253,8 +307,11 @@ fn test_expr_rdeps() {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_b,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -263,8 +320,11 @@ fn test_expr_rdeps() {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_c,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -275,8 +335,11 @@ fn test_expr_rdeps() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_d,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -285,8 +348,11 @@ fn test_expr_rdeps() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_e,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -295,8 +361,11 @@ fn test_expr_rdeps() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_f,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -305,8 +374,11 @@ fn test_expr_rdeps() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_g,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -321,7 +393,7 @@ fn test_expr_with_no_matching_packages() {
         assert_eq!(errors.errors.len(), 1);
         assert!(matches!(
             errors.errors[0],
-            ParseSingleError::NoPackageMatch(_)
+            ParseSingleError::NoPackageMatch { .. }
         ));
     }
 
@@ -337,6 +409,24 @@ fn test_expr_with_no_matching_packages() {
 
     let errors = FilteringExpr::parse("deps(/does-not/)", &graph).unwrap_err();
     assert_error(&errors);
+
+    // Exact package names that are likely to be typos come with a suggestion.
+    let errors = FilteringExpr::parse("package(crate-a)", &graph).unwrap_err();
+    assert_eq!(
+        errors.errors,
+        vec![ParseSingleError::NoPackageMatch {
+            span: (8, 7).into(),
+            help: Some("did you mean `crate_a`?".to_owned()),
+        }]
+    );
+    let errors = FilteringExpr::parse("package(~crate-a)", &graph).unwrap_err();
+    assert_eq!(
+        errors.errors,
+        vec![ParseSingleError::NoPackageMatch {
+            span: (8, 8).into(),
+            help: None,
+        }]
+    );
 }
 
 #[test]
@@ -349,8 +439,11 @@ fn test_expr_kind() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -359,8 +452,11 @@ fn test_expr_kind() {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "test",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -369,8 +465,11 @@ fn test_expr_kind() {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib2",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -388,8 +487,11 @@ fn test_expr_binary() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -398,8 +500,11 @@ fn test_expr_binary() {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary2",
             kind: "test",
+            features: &[],
             binary_name: "my-binary2",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -408,8 +513,11 @@ fn test_expr_binary() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib2",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -417,6 +525,50 @@ fn test_expr_binary() {
     }));
 }
 
+#[test]
+fn test_expr_binary_path() {
+    let graph = load_graph();
+    let expr = FilteringExpr::parse(
+        "binary_path(**/vendor/**) | binary_path(/out/*-test)",
+        &graph,
+    )
+    .unwrap();
+    println!("{:?}", expr);
+
+    let pid_a = mk_pid('a');
+    let binary_query = |binary_path| BinaryQuery {
+        package_id: &pid_a,
+        binary_id: "my-binary",
+        kind: "lib",
+        features: &[],
+        binary_name: "my-binary",
+        binary_path: Utf8Path::new(binary_path),
+        platform: BuildPlatform::Target,
+    };
+    for (binary_path, matches) in [
+        ("/work/vendor/target/debug/deps/my_binary-1234", true),
+        ("/work/target/debug/deps/my_binary-1234", false),
+        ("/out/my-test", true),
+        // `*` doesn't match path separators.
+        ("/out/nested/my-test", false),
+    ] {
+        let binary_query = binary_query(binary_path);
+        assert_eq!(
+            expr.matches_binary(&binary_query),
+            Some(matches),
+            "{binary_path} matches binary"
+        );
+        assert_eq!(
+            expr.matches_test(&TestQuery {
+                binary_query,
+                test_name: "test_something",
+            }),
+            matches,
+            "{binary_path} matches test"
+        );
+    }
+}
+
 #[test]
 fn test_expr_platform() {
     let graph = load_graph();
@@ -427,8 +579,11 @@ fn test_expr_platform() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Host,
         },
 
@@ -437,8 +592,11 @@ fn test_expr_platform() {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -452,8 +610,11 @@ fn test_expr_platform() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -462,8 +623,11 @@ fn test_expr_platform() {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Host,
         },
 
@@ -481,8 +645,11 @@ fn test_expr_kind_partial() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "test",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -491,8 +658,11 @@ fn test_expr_kind_partial() {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -500,6 +670,51 @@ fn test_expr_kind_partial() {
     }));
 }
 
+#[test]
+fn test_expr_feature() {
+    let graph = load_graph();
+    let expr = FilteringExpr::parse("feature(postgres) & kind(test)", &graph).unwrap();
+    println!("{:?}", expr);
+
+    let pid_a = mk_pid('a');
+    let features = ["default".to_owned(), "postgres".to_owned()];
+    let binary_query = BinaryQuery {
+        package_id: &pid_a,
+        binary_id: "my-binary",
+        kind: "test",
+        features: &features,
+        binary_name: "my-binary",
+        binary_path: Utf8Path::new("/fake/binary"),
+        platform: BuildPlatform::Target,
+    };
+    assert_eq!(expr.matches_binary(&binary_query), Some(true));
+    assert!(expr.matches_test(&TestQuery {
+        binary_query,
+        test_name: "test_something"
+    }));
+
+    // Binaries built without the feature don't match, and don't need to be listed.
+    let binary_query = BinaryQuery {
+        features: &features[..1],
+        ..binary_query
+    };
+    assert_eq!(expr.matches_binary(&binary_query), Some(false));
+    assert!(!expr.matches_test(&TestQuery {
+        binary_query,
+        test_name: "test_something"
+    }));
+
+    // Feature names aren't matched partially by default.
+    let binary_query = BinaryQuery {
+        features: &features,
+        ..binary_query
+    };
+    let expr = FilteringExpr::parse("feature(postgre)", &graph).unwrap();
+    assert_eq!(expr.matches_binary(&binary_query), Some(false));
+    let expr = FilteringExpr::parse("feature(~postgre)", &graph).unwrap();
+    assert_eq!(expr.matches_binary(&binary_query), Some(true));
+}
+
 #[test]
 fn test_expr_test() {
     let graph = load_graph();
@@ -512,8 +727,11 @@ fn test_expr_test() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -522,8 +740,11 @@ fn test_expr_test() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_b,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -532,8 +753,11 @@ fn test_expr_test() {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -551,8 +775,11 @@ fn test_expr_test_not() {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -561,8 +788,11 @@ fn test_expr_test_not() {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -582,8 +812,11 @@ fn test_expr_test_union(input: &str) {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -592,8 +825,11 @@ fn test_expr_test_union(input: &str) {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -602,8 +838,11 @@ fn test_expr_test_union(input: &str) {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -622,8 +861,11 @@ fn test_expr_test_difference(input: &str) {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -632,8 +874,11 @@ fn test_expr_test_difference(input: &str) {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -642,8 +887,11 @@ fn test_expr_test_difference(input: &str) {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -661,8 +909,11 @@ fn test_expr_test_intersect(input: &str) {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -671,8 +922,11 @@ fn test_expr_test_intersect(input: &str) {
     assert!(!expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -681,8 +935,11 @@ fn test_expr_test_intersect(input: &str) {
     assert!(expr.matches_test(&TestQuery {
         binary_query: BinaryQuery {
             package_id: &pid_a,
+            binary_id: "my-binary",
             kind: "lib",
+            features: &[],
             binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         },
 
@@ -706,8 +963,11 @@ fn test_binary_query() {
     assert_eq!(
         expr.matches_binary(&BinaryQuery {
             package_id: &pid_a,
+            binary_id: "foo",
             kind: "lib",
+            features: &[],
             binary_name: "foo",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         }),
         Some(true)
@@ -716,8 +976,11 @@ fn test_binary_query() {
     assert_eq!(
         expr.matches_binary(&BinaryQuery {
             package_id: &pid_b,
+            binary_id: "bar",
             kind: "lib",
+            features: &[],
             binary_name: "bar",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Host,
         }),
         Some(true)
@@ -726,8 +989,11 @@ fn test_binary_query() {
     assert_eq!(
         expr.matches_binary(&BinaryQuery {
             package_id: &pid_a,
+            binary_id: "baz",
             kind: "bench",
+            features: &[],
             binary_name: "baz",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         }),
         Some(true)
@@ -736,8 +1002,11 @@ fn test_binary_query() {
     assert_eq!(
         expr.matches_binary(&BinaryQuery {
             package_id: &pid_a,
+            binary_id: "baz",
             kind: "lib",
+            features: &[],
             binary_name: "baz",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         }),
         None
@@ -747,8 +1016,11 @@ fn test_binary_query() {
     assert_eq!(
         expr.matches_binary(&BinaryQuery {
             package_id: &pid_a,
+            binary_id: "baz",
             kind: "bin",
+            features: &[],
             binary_name: "baz",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         }),
         Some(true)
@@ -757,10 +1029,465 @@ fn test_binary_query() {
     assert_eq!(
         expr.matches_binary(&BinaryQuery {
             package_id: &pid_b,
+            binary_id: "baz",
             kind: "lib",
+            features: &[],
             binary_name: "baz",
+            binary_path: Utf8Path::new("/fake/binary"),
             platform: BuildPlatform::Target,
         }),
         Some(false)
     );
 }
+
+#[test]
+fn test_expr_previous_run() {
+    let graph = load_graph();
+    let mut previous_run = PreviousRun::new();
+    previous_run.insert("crate_a", "test_failed", RunStatus::Failed);
+    previous_run.insert("crate_a", "test_flaky", RunStatus::Flaky);
+    previous_run.insert("crate_a", "test_passed", RunStatus::Passed);
+    previous_run.insert("crate_b", "test_failed", RunStatus::Skipped);
+
+    let expr = FilteringExpr::parse_with_previous_run("failed() | flaky()", &graph, &previous_run)
+        .unwrap();
+    println!("{:?}", expr);
+
+    let pid_a = mk_pid('a');
+    let pid_b = mk_pid('b');
+    let binary_query_a = BinaryQuery {
+        package_id: &pid_a,
+        binary_id: "crate_a",
+        kind: "lib",
+        features: &[],
+        binary_name: "crate_a",
+        binary_path: Utf8Path::new("/fake/binary"),
+        platform: BuildPlatform::Target,
+    };
+    let binary_query_b = BinaryQuery {
+        package_id: &pid_b,
+        binary_id: "crate_b",
+        kind: "lib",
+        features: &[],
+        binary_name: "crate_b",
+        binary_path: Utf8Path::new("/fake/binary"),
+        platform: BuildPlatform::Target,
+    };
+
+    for (test_name, matches) in [
+        ("test_failed", true),
+        ("test_flaky", true),
+        ("test_passed", false),
+        ("test_new", false),
+    ] {
+        assert_eq!(
+            expr.matches_test(&TestQuery {
+                binary_query: binary_query_a,
+                test_name,
+            }),
+            matches,
+            "crate_a {test_name} matches"
+        );
+    }
+    // The test with the same name in crate_b was skipped, not failed.
+    assert!(!expr.matches_test(&TestQuery {
+        binary_query: binary_query_b,
+        test_name: "test_failed",
+    }));
+
+    assert_eq!(expr.matches_binary(&binary_query_a), None);
+    assert_eq!(expr.matches_binary(&binary_query_b), Some(false));
+
+    let expr = FilteringExpr::parse_with_previous_run("skipped()", &graph, &previous_run).unwrap();
+    assert!(expr.matches_test(&TestQuery {
+        binary_query: binary_query_b,
+        test_name: "test_failed",
+    }));
+
+    // Without a previous run, these predicates are rejected.
+    let errors = FilteringExpr::parse("package(crate_a) & failed()", &graph).unwrap_err();
+    assert_eq!(errors.errors.len(), 1);
+    assert!(matches!(
+        errors.errors[0],
+        ParseSingleError::PreviousRunUnavailable(_)
+    ));
+}
+
+#[test]
+fn test_expr_durations() {
+    let graph = load_graph();
+    let mut previous_run = PreviousRun::new();
+    previous_run.insert_duration("crate_a", "test_quick", Duration::from_millis(20));
+    previous_run.insert_duration("crate_a", "test_medium", Duration::from_secs(2));
+    previous_run.insert_duration("crate_a", "test_slow", Duration::from_secs(30));
+
+    let pid_a = mk_pid('a');
+    let binary_query = BinaryQuery {
+        package_id: &pid_a,
+        binary_id: "crate_a",
+        kind: "lib",
+        features: &[],
+        binary_name: "crate_a",
+        binary_path: Utf8Path::new("/fake/binary"),
+        platform: BuildPlatform::Target,
+    };
+
+    let slow =
+        FilteringExpr::parse_with_previous_run("slower_than(5s)", &graph, &previous_run).unwrap();
+    let quick = FilteringExpr::parse_with_previous_run("faster_than(500ms)", &graph, &previous_run)
+        .unwrap();
+    println!("{:?} {:?}", slow, quick);
+
+    for (test_name, is_slow, is_quick) in [
+        ("test_quick", false, true),
+        ("test_medium", false, false),
+        ("test_slow", true, false),
+        // Tests without recorded durations match neither predicate.
+        ("test_new", false, false),
+    ] {
+        let query = TestQuery {
+            binary_query,
+            test_name,
+        };
+        assert_eq!(slow.matches_test(&query), is_slow, "{test_name} is slow");
+        assert_eq!(quick.matches_test(&query), is_quick, "{test_name} is quick");
+    }
+}
+
+fn make_aliases(
+    graph: &guppy::graph::PackageGraph,
+    aliases: &[(&str, &str)],
+) -> Result<FilterAliases, Vec<FilterAliasParseErrors>> {
+    FilterAliases::new(
+        graph,
+        aliases
+            .iter()
+            .map(|(name, expr)| (name.to_string(), expr.to_string())),
+    )
+}
+
+#[test]
+fn test_expr_aliases() {
+    let graph = load_graph();
+    let aliases = make_aliases(
+        &graph,
+        &[
+            // Aliases can refer to aliases defined after them.
+            ("crate-a-or-b", "alias(crate-a) | package(crate_b)"),
+            ("crate-a", "package(crate_a)"),
+            ("retried", "flaky()"),
+        ],
+    )
+    .unwrap();
+
+    let expr = FilteringExpr::parse_with_context(
+        "alias(crate-a-or-b) - test(ignored)",
+        &graph,
+        ParseContext::new().set_aliases(&aliases),
+    )
+    .unwrap();
+    println!("{:?}", expr);
+
+    let pid_a = mk_pid('a');
+    let pid_b = mk_pid('b');
+    let pid_c = mk_pid('c');
+    for (package_id, test_name, matches) in [
+        (&pid_a, "test_something", true),
+        (&pid_b, "test_something", true),
+        (&pid_c, "test_something", false),
+        (&pid_a, "test_ignored", false),
+    ] {
+        let query = TestQuery {
+            binary_query: BinaryQuery {
+                package_id,
+                binary_id: "my-binary",
+                kind: "lib",
+                features: &[],
+                binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
+                platform: BuildPlatform::Target,
+            },
+            test_name,
+        };
+        assert_eq!(expr.matches_test(&query), matches, "{query:?}");
+    }
+
+    // Undefined aliases are rejected.
+    let errors = FilteringExpr::parse_with_context(
+        "alias(crate-z)",
+        &graph,
+        ParseContext::new().set_aliases(&aliases),
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors.errors,
+        vec![ParseSingleError::UnknownAlias((6, 7).into())]
+    );
+
+    // Predicates based on previous runs are reported against the alias that uses them.
+    assert!(aliases.needs_previous_run("all() - alias(retried)"));
+    assert!(!FilteringExpr::needs_previous_run("all() - alias(retried)"));
+    let errors = FilteringExpr::parse_with_context(
+        "all() - alias(retried)",
+        &graph,
+        ParseContext::new().set_aliases(&aliases),
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors.errors,
+        vec![ParseSingleError::PreviousRunUnavailable((14, 7).into())]
+    );
+    let previous_run = PreviousRun::new();
+    FilteringExpr::parse_with_context(
+        "all() - alias(retried)",
+        &graph,
+        ParseContext::new()
+            .set_aliases(&aliases)
+            .set_previous_run(&previous_run),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_alias_errors() {
+    let graph = load_graph();
+
+    let errors = make_aliases(
+        &graph,
+        &[
+            ("a", "alias(b)"),
+            ("b", "package(crate_a) | alias(a)"),
+            ("self", "alias(self)"),
+            ("ok", "package(crate_a)"),
+        ],
+    )
+    .unwrap_err();
+    let errors: Vec<_> = errors
+        .iter()
+        .map(|error| (error.name.as_str(), error.parse_errors.errors.clone()))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            (
+                "a",
+                vec![ParseSingleError::AliasCycle {
+                    span: (6, 1).into(),
+                    cycle: "a -> b -> a".to_owned(),
+                }]
+            ),
+            (
+                "b",
+                vec![ParseSingleError::AliasCycle {
+                    span: (25, 1).into(),
+                    cycle: "b -> a -> b".to_owned(),
+                }]
+            ),
+            (
+                "self",
+                vec![ParseSingleError::AliasCycle {
+                    span: (6, 4).into(),
+                    cycle: "self -> self".to_owned(),
+                }]
+            ),
+        ]
+    );
+
+    let errors = make_aliases(
+        &graph,
+        &[
+            ("bad name", "all()"),
+            ("syntax", "package(crate_a"),
+            ("unknown", "alias(missing) | alias(syntax)"),
+            ("no-match", "package(crate_z)"),
+        ],
+    )
+    .unwrap_err();
+    let errors: Vec<_> = errors
+        .iter()
+        .map(|error| (error.name.as_str(), error.parse_errors.errors.clone()))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            (
+                "bad name",
+                vec![ParseSingleError::InvalidAliasName((0, 8).into())]
+            ),
+            (
+                "syntax",
+                vec![ParseSingleError::ExpectedCloseParenthesis((15, 0).into())]
+            ),
+            // Aliases that failed to parse still count as defined.
+            (
+                "unknown",
+                vec![ParseSingleError::UnknownAlias((6, 7).into())]
+            ),
+        ]
+    );
+
+    // Errors against the package graph are only checked once everything else is fine.
+    let errors = make_aliases(&graph, &[("no-match", "package(crate_z)")]).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].parse_errors.errors,
+        vec![ParseSingleError::NoPackageMatch {
+            span: (8, 7).into(),
+            help: Some("did you mean `crate_a`?".to_owned()),
+        }]
+    );
+}
+
+#[test]
+fn test_expr_tags() {
+    let graph = load_graph();
+    let mut tags = TestTags::new();
+    tags.add_rule(
+        FilteringExpr::parse("package(crate_a)", &graph).unwrap(),
+        ["gpu", "slow"],
+    );
+    tags.add_rule(
+        FilteringExpr::parse("test(=test_network)", &graph).unwrap(),
+        ["network"],
+    );
+    tags.set_name_prefix("tag_");
+
+    let gpu =
+        FilteringExpr::parse_with_context("tag(gpu)", &graph, ParseContext::new().set_tags(&tags))
+            .unwrap();
+    let network = FilteringExpr::parse_with_context(
+        "tag(network) - tag(=slow)",
+        &graph,
+        ParseContext::new().set_tags(&tags),
+    )
+    .unwrap();
+    println!("{:?} {:?}", gpu, network);
+
+    let pid_a = mk_pid('a');
+    let pid_b = mk_pid('b');
+    for (package_id, test_name, expected_tags, is_gpu, is_network) in [
+        (&pid_a, "test_something", &["gpu", "slow"][..], true, false),
+        (
+            &pid_a,
+            "test_network",
+            &["gpu", "network", "slow"][..],
+            true,
+            false,
+        ),
+        (&pid_b, "test_network", &["network"][..], false, true),
+        (
+            &pid_b,
+            "renderer::tag_gpu::draws",
+            &["gpu"][..],
+            true,
+            false,
+        ),
+        // Only the rest of a component after the prefix counts, and it must be non-empty.
+        (&pid_b, "tag_::gpu_tag_network", &[][..], false, false),
+    ] {
+        let query = TestQuery {
+            binary_query: BinaryQuery {
+                package_id,
+                binary_id: "my-binary",
+                kind: "lib",
+                features: &[],
+                binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
+                platform: BuildPlatform::Target,
+            },
+            test_name,
+        };
+        let expected_tags: Vec<_> = expected_tags.iter().map(|tag| tag.to_string()).collect();
+        assert_eq!(
+            tags.tags_for(&query).into_iter().collect::<Vec<_>>(),
+            expected_tags,
+            "tags for {query:?}"
+        );
+        assert_eq!(gpu.matches_test(&query), is_gpu, "{query:?} is gpu");
+        assert_eq!(
+            network.matches_test(&query),
+            is_network,
+            "{query:?} is network"
+        );
+    }
+
+    // tag() can only be used where tags are available.
+    let errors = FilteringExpr::parse("all() - tag(gpu)", &graph).unwrap_err();
+    assert_eq!(
+        errors.errors,
+        vec![ParseSingleError::TagsUnavailable((12, 3).into())]
+    );
+}
+
+#[derive(Debug)]
+struct FakeChangedFiles;
+
+impl ChangedFiles for FakeChangedFiles {
+    fn changed_files(&self, git_ref: &str) -> Result<Vec<Utf8PathBuf>, String> {
+        match git_ref {
+            "main" => Ok(vec![
+                "/home/fakeuser/tests-workspace/crate-c/src/lib.rs".into(),
+                "/home/fakeuser/tests-workspace/README.md".into(),
+            ]),
+            "HEAD" => Ok(vec![]),
+            _ => Err(format!("unknown git ref `{git_ref}`")),
+        }
+    }
+}
+
+#[test]
+fn test_expr_since() {
+    let graph = load_graph();
+    let mut cx = ParseContext::new();
+    cx.set_changed_files(&FakeChangedFiles);
+
+    // Packages that depend on changed packages, possibly transitively, are included too.
+    let expr = FilteringExpr::parse_with_context("since(main)", &graph, &cx).unwrap();
+    println!("{:?}", expr);
+    for (package, matches) in [
+        ('a', false),
+        ('b', false),
+        ('c', true),
+        ('d', true),
+        ('e', true),
+        ('f', true),
+        ('g', true),
+    ] {
+        let package_id = mk_pid(package);
+        let query = TestQuery {
+            binary_query: BinaryQuery {
+                package_id: &package_id,
+                binary_id: "my-binary",
+                kind: "lib",
+                features: &[],
+                binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
+                platform: BuildPlatform::Target,
+            },
+            test_name: "test_something",
+        };
+        assert_eq!(expr.matches_test(&query), matches, "{query:?}");
+    }
+
+    // No changes means no packages, which isn't an error.
+    let expr = FilteringExpr::parse_with_context("since(HEAD)", &graph, &cx).unwrap();
+    assert_eq!(
+        expr,
+        FilteringExpr::Set(FilteringSet::Packages(Default::default()))
+    );
+
+    let errors = FilteringExpr::parse_with_context("since(nope)", &graph, &cx).unwrap_err();
+    assert_eq!(
+        errors.errors,
+        vec![ParseSingleError::ChangedFilesError {
+            span: (6, 4).into(),
+            message: "unknown git ref `nope`".to_owned(),
+        }]
+    );
+
+    let errors = FilteringExpr::parse("since(main)", &graph).unwrap_err();
+    assert_eq!(
+        errors.errors,
+        vec![ParseSingleError::ChangedFilesUnavailable((6, 4).into())]
+    );
+}
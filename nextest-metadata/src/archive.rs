@@ -0,0 +1,96 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::BinaryListSummary;
+use camino::Utf8PathBuf;
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, collections::BTreeMap, fmt};
+
+/// A summary of the contents of a nextest archive.
+///
+/// Produced by `cargo nextest archive inspect --message-format json`.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ArchiveSummary {
+    /// The metadata about test binaries stored in the archive.
+    pub binary_list: BinaryListSummary,
+
+    /// The size of the archive file, in bytes.
+    pub archive_size: u64,
+
+    /// The total size of the files in the archive once extracted, in bytes.
+    pub total_size: u64,
+
+    /// For delta archives, the checksum of the baseline archive, of the form `sha256:<hex>`.
+    ///
+    /// This is `None` for full archives.
+    pub baseline_checksum: Option<String>,
+
+    /// The files in the archive, keyed by their path within the archive.
+    pub files: BTreeMap<Utf8PathBuf, ArchiveFileSummary>,
+}
+
+/// A file within a nextest archive.
+///
+/// Part of an [`ArchiveSummary`].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ArchiveFileSummary {
+    /// What the file is used for.
+    pub kind: ArchiveFileKind,
+
+    /// The size of the file, in bytes.
+    pub size: u64,
+}
+
+/// What a file within a nextest archive is used for.
+///
+/// Part of an [`ArchiveFileSummary`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct ArchiveFileKind(pub Cow<'static, str>);
+
+impl ArchiveFileKind {
+    /// Creates a new `ArchiveFileKind` from a string.
+    #[inline]
+    pub fn new(kind: impl Into<Cow<'static, str>>) -> Self {
+        Self(kind.into())
+    }
+
+    /// Creates a new `ArchiveFileKind` from a static string.
+    #[inline]
+    pub const fn new_const(kind: &'static str) -> Self {
+        Self(Cow::Borrowed(kind))
+    }
+
+    /// Returns the kind as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The "metadata" kind, used for metadata written out by nextest.
+    pub const METADATA: Self = Self::new_const("metadata");
+
+    /// The "test-binary" kind, used for test binaries.
+    pub const TEST_BINARY: Self = Self::new_const("test-binary");
+
+    /// The "non-test-binary" kind, used for non-test binaries and dynamic libraries.
+    pub const NON_TEST_BINARY: Self = Self::new_const("non-test-binary");
+
+    /// The "linked-path" kind, used for files within linked paths.
+    pub const LINKED_PATH: Self = Self::new_const("linked-path");
+
+    /// The "workspace" kind, used for files from the workspace included with `archive.include`.
+    pub const WORKSPACE: Self = Self::new_const("workspace");
+
+    /// The "other" kind, used for other files, e.g. custom target spec files.
+    pub const OTHER: Self = Self::new_const("other");
+}
+
+impl fmt::Display for ArchiveFileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
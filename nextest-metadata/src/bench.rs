@@ -0,0 +1,58 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A summary of the results of a benchmark run.
+///
+/// Produced by `cargo nextest bench --message-format json`.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BenchResultsSummary {
+    /// Results for benchmarks that were run and reported results, keyed by binary ID and then by
+    /// benchmark name.
+    pub benchmarks: BTreeMap<String, BTreeMap<String, BenchmarkSummary>>,
+}
+
+/// The results of a single benchmark, as measured by criterion.
+///
+/// Part of a [`BenchResultsSummary`].
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BenchmarkSummary {
+    /// The mean time per iteration.
+    pub mean: BenchEstimateSummary,
+
+    /// The median time per iteration.
+    pub median: BenchEstimateSummary,
+
+    /// The standard deviation of the time per iteration.
+    pub std_dev: BenchEstimateSummary,
+
+    /// The change in the mean time per iteration compared to the previous run, as a fraction (e.g.
+    /// `-0.05` for 5% faster).
+    ///
+    /// This is `None` if there were no results from a previous run to compare against.
+    pub mean_change: Option<BenchEstimateSummary>,
+}
+
+/// A statistical estimate, along with its confidence interval.
+///
+/// Times are in nanoseconds.
+///
+/// Part of a [`BenchmarkSummary`].
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BenchEstimateSummary {
+    /// The point estimate.
+    pub point_estimate: f64,
+
+    /// The lower bound of the confidence interval.
+    pub lower_bound: f64,
+
+    /// The upper bound of the confidence interval.
+    pub upper_bound: f64,
+}
@@ -0,0 +1,49 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use serde::{Deserialize, Serialize};
+
+/// A plan for splitting a test run across CI jobs, in the shape of a GitHub Actions matrix.
+///
+/// Produced by `cargo nextest ci plan`. Each entry in `include` describes one shard, so the summary
+/// can be passed to `fromJSON` in a workflow's `strategy.matrix`, or turned into parallel jobs on
+/// other CI systems.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CiMatrixSummary {
+    /// The shards in the plan, in order.
+    pub include: Vec<CiShardSummary>,
+}
+
+/// One shard in a [`CiMatrixSummary`].
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CiShardSummary {
+    /// The shard, counting up from 1.
+    pub shard: u64,
+
+    /// The total number of shards.
+    pub total_shards: u64,
+
+    /// The value to pass in to `--partition` to run the tests in this shard, if a shard plan was
+    /// written out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partition: Option<String>,
+
+    /// The number of tests assigned to this shard.
+    pub test_count: usize,
+
+    /// How long the tests in this shard are expected to take, in seconds.
+    pub estimated_duration_secs: f64,
+
+    /// The IDs of the tests in this shard, if requested.
+    ///
+    /// Each test ID is of the form `<binary-id> <test-name>`, one of which can be written out per
+    /// line to produce a file for `--test-list-file`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tests: Option<Vec<String>>,
+}
@@ -0,0 +1,25 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The tests added and removed between two test lists.
+///
+/// Produced by `cargo nextest list --diff <previous-list> --message-format json`. Renamed tests are
+/// reported as one removed test and one added test.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestListDiffSummary {
+    /// Tests that are in the current list but not in the previous one, keyed by binary ID.
+    ///
+    /// Binaries that didn't have any added tests are omitted.
+    pub added: BTreeMap<String, BTreeSet<String>>,
+
+    /// Tests that are in the previous list but not in the current one, keyed by binary ID.
+    ///
+    /// Binaries that didn't have any removed tests are omitted.
+    pub removed: BTreeMap<String, BTreeSet<String>>,
+}
@@ -25,6 +25,12 @@ impl NextestExitCode {
     /// Creating a test list produced an error.
     pub const TEST_LIST_CREATION_FAILED: i32 = 104;
 
+    /// Reading the results of benchmarks produced an error.
+    pub const BENCH_RESULTS_READ_FAILED: i32 = 105;
+
+    /// Tests were removed compared to a previous test list, and `--fail-on-removed` was passed in.
+    pub const TESTS_REMOVED: i32 = 106;
+
     /// Writing data to stdout or stderr produced an error.
     pub const WRITE_OUTPUT_ERROR: i32 = 110;
 
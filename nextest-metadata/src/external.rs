@@ -0,0 +1,71 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::BinaryListSummary;
+use camino::Utf8PathBuf;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A list of test binaries built outside of Cargo, for example by Buck2 or Bazel.
+///
+/// This is a [`BinaryListSummary`] along with the package information nextest would otherwise
+/// obtain from `cargo metadata`, and extra environment variables to run binaries with. Passed in to
+/// nextest with `--external-binaries <PATH>`, in which case Cargo is never invoked.
+///
+/// For more, see [Running externally built binaries](https://nexte.st/book/external-binaries) on
+/// the nextest site.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExternalBinaryListSummary {
+    /// The version of the format. This must be [`FORMAT_VERSION`](Self::FORMAT_VERSION).
+    pub format_version: u32,
+
+    /// The absolute path to the root of the workspace.
+    ///
+    /// Nextest's configuration is read from `.config/nextest.toml` within this directory.
+    pub workspace_root: Utf8PathBuf,
+
+    /// The packages test binaries are a part of, keyed by package ID.
+    pub packages: BTreeMap<String, ExternalPackageSummary>,
+
+    /// The test binaries and the metadata used to run them.
+    ///
+    /// Paths in the build metadata, such as linked paths, are relative to the target directory,
+    /// which must be an absolute path. Paths to binaries must be absolute.
+    #[serde(flatten)]
+    pub binary_list: BinaryListSummary,
+
+    /// Extra environment variables to run test binaries with, keyed by binary ID.
+    #[serde(default)]
+    pub env: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl ExternalBinaryListSummary {
+    /// The version of the format understood by this version of nextest.
+    pub const FORMAT_VERSION: u32 = 1;
+}
+
+/// A package that externally built test binaries are a part of.
+///
+/// Part of an [`ExternalBinaryListSummary`].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExternalPackageSummary {
+    /// The name of the package, as used by the `package()` filter expression.
+    pub name: String,
+
+    /// The version of the package. Defaults to `0.0.0`.
+    #[serde(default = "default_package_version")]
+    pub version: String,
+
+    /// The directory containing the package, relative to the workspace root.
+    ///
+    /// Tests in this package are run within this directory, and `CARGO_MANIFEST_DIR` is set to it.
+    pub path: Utf8PathBuf,
+}
+
+fn default_package_version() -> String {
+    "0.0.0".to_owned()
+}
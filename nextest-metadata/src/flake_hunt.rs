@@ -0,0 +1,85 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use serde::{Deserialize, Serialize};
+
+/// How often tests failed when they were run over and over under load.
+///
+/// Produced by `cargo nextest flake-hunt --message-format json`.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FlakeHuntSummary {
+    /// The rounds tests were run in, in the order they were run.
+    pub rounds: Vec<FlakeHuntRoundSummary>,
+
+    /// Tests that failed at least once, the ones that failed most often first.
+    pub failing: Vec<FlakeHuntTestSummary>,
+
+    /// The number of tests that passed every time they were run.
+    pub always_passed: usize,
+}
+
+/// One round of tests in a [`FlakeHuntSummary`].
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FlakeHuntRoundSummary {
+    /// The seed tests were shuffled with.
+    pub seed: u64,
+
+    /// The number of tests that were run at the same time.
+    pub test_threads: usize,
+
+    /// The number of tests that passed.
+    pub passed: usize,
+
+    /// The number of tests that failed.
+    pub failed: usize,
+}
+
+/// A test that failed at least once in a [`FlakeHuntSummary`].
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FlakeHuntTestSummary {
+    /// The binary ID of the test.
+    pub binary_id: String,
+
+    /// The name of the test.
+    pub name: String,
+
+    /// The number of times the test was run.
+    pub runs: usize,
+
+    /// The number of times the test failed.
+    pub failures: usize,
+
+    /// The ways the test failed, the most common first.
+    pub outputs: Vec<FlakeHuntFailureSummary>,
+}
+
+/// One way a test failed, along with the output of the first failure of this kind.
+///
+/// Part of a [`FlakeHuntTestSummary`].
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FlakeHuntFailureSummary {
+    /// The status of the failure as shown in nextest's output, for example `FAIL`, `SIGSEGV` or
+    /// `TIMEOUT`.
+    pub status: String,
+
+    /// The number of times the test failed this way.
+    pub count: usize,
+
+    /// The standard output of the first failure of this kind, with invalid UTF-8 replaced.
+    pub stdout: String,
+
+    /// The standard error of the first failure of this kind, with invalid UTF-8 replaced.
+    pub stderr: String,
+}
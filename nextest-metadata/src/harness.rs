@@ -0,0 +1,68 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::RustTestLocationSummary;
+use serde::{Deserialize, Serialize};
+
+/// The list of tests in a custom test harness, printed out by the harness while nextest lists
+/// tests.
+///
+/// Nextest sets the [`LIST_FORMAT_ENV`](Self::LIST_FORMAT_ENV) environment variable to
+/// [`LIST_FORMAT`](Self::LIST_FORMAT) while running test binaries with `--list --format terse`.
+/// Custom test harnesses can print out this summary as JSON instead of the terse format, for
+/// example to report which tests are ignored without being run a second time with `--ignored`.
+///
+/// For more, see [Custom test harnesses](https://nexte.st/book/custom-test-harnesses) on the
+/// nextest site.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct HarnessTestListSummary {
+    /// The version of the format. This must be [`FORMAT_VERSION`](Self::FORMAT_VERSION).
+    pub format_version: u32,
+
+    /// The tests in the harness.
+    pub tests: Vec<HarnessTestSummary>,
+}
+
+impl HarnessTestListSummary {
+    /// The environment variable nextest sets while listing tests.
+    pub const LIST_FORMAT_ENV: &'static str = "NEXTEST_LIST_FORMAT";
+
+    /// The value of [`LIST_FORMAT_ENV`](Self::LIST_FORMAT_ENV) nextest sets while listing tests.
+    pub const LIST_FORMAT: &'static str = "json";
+
+    /// The version of the format understood by this version of nextest.
+    pub const FORMAT_VERSION: u32 = 1;
+
+    /// Creates a new summary with the current format version.
+    pub fn new(tests: impl IntoIterator<Item = HarnessTestSummary>) -> Self {
+        Self {
+            format_version: Self::FORMAT_VERSION,
+            tests: tests.into_iter().collect(),
+        }
+    }
+}
+
+/// A test within a custom test harness.
+///
+/// Part of a [`HarnessTestListSummary`].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct HarnessTestSummary {
+    /// The name of the test, passed in to the harness as `<name> --exact --nocapture` to run it.
+    pub name: String,
+
+    /// Whether the test is ignored. Ignored tests are run with `--ignored` passed in as well.
+    #[serde(default)]
+    pub ignored: bool,
+
+    /// The reason the test is ignored, shown in nextest's list output and reports.
+    #[serde(default)]
+    pub ignore_reason: Option<String>,
+
+    /// Where the test is defined in source code.
+    #[serde(default)]
+    pub location: Option<RustTestLocationSummary>,
+}
@@ -6,6 +6,15 @@
 //!
 //! Implemented so far:
 //! * ✅ Listing tests with [`TestListSummary`]
+//! * ✅ Inspecting archives with [`ArchiveSummary`]
+//! * ✅ Benchmark results with [`BenchResultsSummary`]
+//! * ✅ CI shard plans with [`CiMatrixSummary`]
+//! * ✅ Differences between test lists with [`TestListDiffSummary`]
+//! * ✅ Tests that depend on the order tests are run in, with [`OrderDepsSummary`]
+//! * ✅ How often tests fail under load, with [`FlakeHuntSummary`]
+//! * ✅ Events that occur while tests are run, with [`RunEventSummary`]
+//! * ✅ Test lists printed out by custom test harnesses, with [`HarnessTestListSummary`]
+//! * ✅ Test binaries built outside of Cargo, with [`ExternalBinaryListSummary`]
 //! * ✅ Semantic exit codes with [`NextestExitCode`]
 //!
 //! # Examples
@@ -39,10 +48,28 @@
 //! page](https://nexte.st/book/stability#nextest-metadata) on the nextest site.
 #![warn(missing_docs)]
 
+mod archive;
+mod bench;
+mod ci;
+mod diff;
 mod errors;
 mod exit_codes;
+mod external;
+mod flake_hunt;
+mod harness;
+mod order_deps;
+mod run_events;
 mod test_list;
 
+pub use archive::*;
+pub use bench::*;
+pub use ci::*;
+pub use diff::*;
 pub use errors::*;
 pub use exit_codes::*;
+pub use external::*;
+pub use flake_hunt::*;
+pub use harness::*;
+pub use order_deps::*;
+pub use run_events::*;
 pub use test_list::*;
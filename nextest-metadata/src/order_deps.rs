@@ -0,0 +1,120 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use serde::{Deserialize, Serialize};
+
+/// Tests suspected of failing because of which other tests ran before them or alongside them.
+///
+/// Produced by `cargo nextest detect-order-deps --message-format json`.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct OrderDepsSummary {
+    /// The runs of the test suite that were compared, in the order they were run.
+    pub runs: Vec<OrderDepsRunSummary>,
+
+    /// Tests that failed in some runs and passed in others, along with the tests suspected of
+    /// making them fail.
+    pub inconsistent: Vec<InconsistentTestSummary>,
+
+    /// Tests that failed in every run they were run in.
+    ///
+    /// These tests are left out of the analysis, since their failures don't depend on the order
+    /// tests are run in.
+    pub always_failed: Vec<OrderDepsTestSummary>,
+}
+
+/// One run of the test suite in an [`OrderDepsSummary`].
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct OrderDepsRunSummary {
+    /// The seed tests were shuffled with. Pass this in to `cargo nextest run --shuffle-seed`,
+    /// along with the same filters, to start tests in the same order.
+    pub seed: u64,
+
+    /// The number of tests that were run at the same time.
+    pub test_threads: usize,
+
+    /// The number of tests that passed.
+    pub passed: usize,
+
+    /// The number of tests that failed.
+    pub failed: usize,
+}
+
+/// A test that failed in some runs and passed in others.
+///
+/// Part of an [`OrderDepsSummary`].
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct InconsistentTestSummary {
+    /// The test.
+    #[serde(flatten)]
+    pub test: OrderDepsTestSummary,
+
+    /// The number of runs the test was run in.
+    pub runs: usize,
+
+    /// The number of runs the test failed in.
+    pub failed_runs: usize,
+
+    /// Tests suspected of making this test fail, most suspicious first.
+    pub suspects: Vec<OrderDepSuspectSummary>,
+}
+
+/// A test suspected of making another test fail.
+///
+/// Part of an [`InconsistentTestSummary`].
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct OrderDepSuspectSummary {
+    /// The suspected test.
+    #[serde(flatten)]
+    pub test: OrderDepsTestSummary,
+
+    /// How the suspected test ran relative to the failing test.
+    pub relation: OrderDepRelation,
+
+    /// The number of runs the failing test failed in where the suspected test ran this way.
+    ///
+    /// This is always the total number of runs the failing test failed in.
+    pub failed_runs: usize,
+
+    /// The number of runs the failing test passed in where the suspected test ran this way.
+    pub passed_runs: usize,
+}
+
+/// How a suspected test ran relative to a failing test.
+///
+/// Part of an [`OrderDepSuspectSummary`].
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OrderDepRelation {
+    /// The suspected test finished before the failing test started.
+    RanBefore,
+
+    /// The suspected test was running at the same time as the failing test.
+    RanAlongside,
+}
+
+/// A test in an [`OrderDepsSummary`].
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct OrderDepsTestSummary {
+    /// The binary ID of the test.
+    pub binary_id: String,
+
+    /// The name of the test.
+    pub name: String,
+}
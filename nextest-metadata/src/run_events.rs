@@ -0,0 +1,834 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::MismatchReason;
+use camino::Utf8PathBuf;
+use serde::{Deserialize, Serialize};
+
+/// An event that occurred during a test run.
+///
+/// `cargo nextest run --message-format json` writes one of these to standard output per line, as
+/// the events occur. The kind of event is stored in the `"type"` field.
+///
+/// Durations are in seconds.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum RunEventSummary {
+    /// The test run started.
+    RunStarted(RunStartedEventSummary),
+
+    /// Tests were listed while the run was in progress, and are about to be run.
+    TestsAdded(TestsAddedEventSummary),
+
+    /// A test started running.
+    TestStarted(TestStartedEventSummary),
+
+    /// A test was slower than its slow timeout.
+    TestSlow(TestSlowEventSummary),
+
+    /// A test wrote output, with `--live-output`.
+    TestOutput(TestOutputEventSummary),
+
+    /// An attempt to run a test failed, and the test is being retried.
+    TestRetry(TestRetryEventSummary),
+
+    /// A test that failed was run again on its own, with `check-failures-in-isolation`.
+    TestIsolationChecked(TestAttemptEventSummary),
+
+    /// A test that passed was run again to check that its output is the same, with
+    /// `--check-determinism`.
+    TestDeterminismChecked(TestDeterminismCheckedEventSummary),
+
+    /// A test finished running.
+    TestFinished(TestFinishedEventSummary),
+
+    /// A test finished before the run was interrupted, and isn't run again now that the run is
+    /// resumed with `--resume`.
+    TestPreviouslyFinished(TestPreviouslyFinishedEventSummary),
+
+    /// A test was skipped.
+    TestSkipped(TestSkippedEventSummary),
+
+    /// A test wasn't started because the run time budget was used up.
+    TestNotRun(TestIdSummary),
+
+    /// The run is being canceled.
+    RunBeginCancel(RunBeginCancelEventSummary),
+
+    /// The test run finished.
+    RunFinished(RunFinishedEventSummary),
+}
+
+/// The identity of a test in a [`RunEventSummary`].
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestIdSummary {
+    /// The binary ID of the test.
+    pub binary_id: String,
+
+    /// The name of the test.
+    pub name: String,
+}
+
+/// A [`RunEventSummary::RunStarted`] event.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RunStartedEventSummary {
+    /// The unique ID of the run.
+    pub run_id: String,
+
+    /// The number of tests that will be run, not counting iterations.
+    ///
+    /// If `listing` is true, more tests are added to these with
+    /// [`RunEventSummary::TestsAdded`] events as the run progresses.
+    pub test_count: usize,
+
+    /// The number of times each test is run, set with `--iterations`, or `None` if tests are run
+    /// until one fails.
+    pub iterations: Option<usize>,
+
+    /// The seed tests were shuffled with, if they're started in a random order.
+    pub shuffle_seed: Option<u64>,
+
+    /// The seed the `NEXTEST_TEST_SEED` value given to each test was derived from.
+    pub seed: u64,
+
+    /// If this run resumes an interrupted one, the number of tests that finished before it was
+    /// interrupted.
+    pub previously_finished: Option<usize>,
+
+    /// Whether tests are started while test binaries are still being built and listed.
+    pub listing: bool,
+}
+
+/// A [`RunEventSummary::TestsAdded`] event.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestsAddedEventSummary {
+    /// The number of tests added that will be run, not counting iterations.
+    pub test_count: usize,
+
+    /// The binary IDs of the test binaries the tests were listed from.
+    pub binary_ids: Vec<String>,
+}
+
+/// A [`RunEventSummary::TestStarted`] event.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestStartedEventSummary {
+    /// The test that started.
+    #[serde(flatten)]
+    pub test: TestIdSummary,
+
+    /// The number of tests running, including this one.
+    pub running: usize,
+}
+
+/// A [`RunEventSummary::TestSlow`] event.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestSlowEventSummary {
+    /// The test that was slow.
+    #[serde(flatten)]
+    pub test: TestIdSummary,
+
+    /// How long the test has been running for.
+    pub elapsed: f64,
+}
+
+/// A [`RunEventSummary::TestOutput`] event.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestOutputEventSummary {
+    /// The test that wrote the output.
+    #[serde(flatten)]
+    pub test: TestIdSummary,
+
+    /// The stream the output was written to.
+    pub stream: OutputStreamSummary,
+
+    /// The output, made up of complete lines unless it's the end of the stream.
+    pub output: String,
+
+    /// The time since the test started at which the output was read, in seconds.
+    #[serde(default)]
+    pub elapsed: f64,
+}
+
+/// The stream a test wrote output to, in a [`TestOutputEventSummary`] or an
+/// [`OutputChunkSummary`].
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputStreamSummary {
+    /// Standard output.
+    Stdout,
+
+    /// Standard error.
+    Stderr,
+}
+
+/// A part of the output of a [`TestAttemptSummary`].
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct OutputChunkSummary {
+    /// The stream the output was written to.
+    pub stream: OutputStreamSummary,
+
+    /// The byte offset in the output of the stream that this part starts at.
+    pub start: usize,
+
+    /// The byte offset in the output of the stream that this part ends at, exclusive.
+    pub end: usize,
+
+    /// The time since the attempt started at which this part was read, in seconds.
+    #[serde(default)]
+    pub elapsed: f64,
+}
+
+/// A [`RunEventSummary::TestRetry`] event.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestRetryEventSummary {
+    /// The test that is being retried.
+    #[serde(flatten)]
+    pub test: TestIdSummary,
+
+    /// The attempt that failed.
+    pub attempt: TestAttemptSummary,
+
+    /// How long nextest waits before the next attempt.
+    pub delay: f64,
+
+    /// Whether the next attempt is deferred until every test has been run once.
+    pub deferred: bool,
+}
+
+/// A [`RunEventSummary::TestIsolationChecked`] event.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestAttemptEventSummary {
+    /// The test that was run.
+    #[serde(flatten)]
+    pub test: TestIdSummary,
+
+    /// The attempt.
+    pub attempt: TestAttemptSummary,
+}
+
+/// A [`RunEventSummary::TestDeterminismChecked`] event.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestDeterminismCheckedEventSummary {
+    /// The test that was run again.
+    #[serde(flatten)]
+    pub test: TestIdSummary,
+
+    /// The second run of the test.
+    pub attempt: TestAttemptSummary,
+
+    /// Whether the second run failed, or its output differed from the first run's.
+    pub nondeterministic: bool,
+}
+
+/// A [`RunEventSummary::TestFinished`] event.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestFinishedEventSummary {
+    /// The test that finished.
+    #[serde(flatten)]
+    pub test: TestIdSummary,
+
+    /// Whether the test passed, failed, or passed after failing at first.
+    pub status: TestFinishedStatus,
+
+    /// Whether the test is quarantined, in which case its failures don't fail the run.
+    pub quarantined: bool,
+
+    /// Every attempt to run the test, in the order they were run. The last attempt decides the
+    /// status of the test.
+    pub attempts: Vec<TestAttemptSummary>,
+}
+
+/// A [`RunEventSummary::TestPreviouslyFinished`] event.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestPreviouslyFinishedEventSummary {
+    /// The test that finished.
+    #[serde(flatten)]
+    pub test: TestIdSummary,
+
+    /// Whether the test passed, failed, or passed after failing at first.
+    pub status: TestFinishedStatus,
+
+    /// How long the last attempt of the test took.
+    pub time_taken: f64,
+}
+
+/// A [`RunEventSummary::TestSkipped`] event.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestSkippedEventSummary {
+    /// The test that was skipped.
+    #[serde(flatten)]
+    pub test: TestIdSummary,
+
+    /// The reason the test was skipped.
+    pub reason: MismatchReason,
+}
+
+/// A [`RunEventSummary::RunBeginCancel`] event.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RunBeginCancelEventSummary {
+    /// The number of tests still running.
+    pub running: usize,
+
+    /// The reason the run is being canceled.
+    pub reason: CancelReasonSummary,
+}
+
+/// A [`RunEventSummary::RunFinished`] event.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RunFinishedEventSummary {
+    /// The unique ID of the run.
+    pub run_id: String,
+
+    /// How long the run took.
+    pub elapsed: f64,
+
+    /// Whether the run is considered a success.
+    pub success: bool,
+
+    /// The number of tests that were expected to be run when the run started, counting each
+    /// iteration separately.
+    pub initial_run_count: usize,
+
+    /// The number of tests that finished running.
+    pub finished_count: usize,
+
+    /// The number of tests that passed, including flaky and leaky tests.
+    pub passed: usize,
+
+    /// The number of tests that passed after failing at first.
+    pub flaky: usize,
+
+    /// The number of tests that failed.
+    pub failed: usize,
+
+    /// The number of tests that timed out.
+    pub timed_out: usize,
+
+    /// The number of tests that passed, but left processes running after they exited.
+    pub leaky: usize,
+
+    /// The number of tests that couldn't be executed.
+    pub exec_failed: usize,
+
+    /// The number of tests that were skipped.
+    pub skipped: usize,
+
+    /// The number of quarantined tests that failed. These aren't counted in `failed`.
+    pub quarantined_failed: usize,
+
+    /// How many tests passed towards each pass rate required with `required-pass-rate`.
+    pub pass_rates: Vec<PassRateSummary>,
+}
+
+/// How many of the tests that count towards a required pass rate passed.
+///
+/// Part of a [`RunFinishedEventSummary`].
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PassRateSummary {
+    /// The test group whose tests count towards this pass rate, or `None` for the tests that
+    /// aren't in a test group with its own.
+    pub test_group: Option<String>,
+
+    /// The fraction of these tests, between 0 and 1, that must pass.
+    pub required: f64,
+
+    /// The number of these tests that passed.
+    pub passed: usize,
+
+    /// The number of these tests that failed.
+    pub failed: usize,
+}
+
+/// One attempt to run a test.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestAttemptSummary {
+    /// The attempt number, starting from 1.
+    pub attempt: usize,
+
+    /// The total number of attempts the test can be run for, equal to 1 + the number of retries.
+    pub total_attempts: usize,
+
+    /// The iteration this is an attempt of, starting from 1.
+    pub iteration: usize,
+
+    /// The result of the attempt.
+    pub result: TestAttemptResult,
+
+    /// The exit code of the test process, if it exited normally.
+    pub exit_code: Option<i32>,
+
+    /// The signal the test process was terminated by, on Unix.
+    pub signal: Option<i32>,
+
+    /// How long the attempt took.
+    pub time_taken: f64,
+
+    /// Whether the attempt was slower than the test's slow timeout.
+    pub slow: bool,
+
+    /// The value of `NEXTEST_TEST_SEED` the attempt was run with.
+    pub test_seed: u64,
+
+    /// The standard output of the attempt, with invalid UTF-8 replaced.
+    pub stdout: String,
+
+    /// The standard error of the attempt, with invalid UTF-8 replaced.
+    pub stderr: String,
+
+    /// The parts of `stdout` and `stderr`, in the order the attempt wrote them in.
+    ///
+    /// The order is approximate, since output written to both streams at around the same time
+    /// may be read out of order.
+    #[serde(default)]
+    pub output_chunks: Vec<OutputChunkSummary>,
+
+    /// The process IDs of processes the test left running after it exited.
+    pub leaked_pids: Vec<u32>,
+
+    /// The temporary directory the test was given, if it was kept after the test failed.
+    pub kept_tmpdir: Option<Utf8PathBuf>,
+}
+
+/// The result of a [`TestAttemptSummary`].
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum TestAttemptResult {
+    /// The test passed.
+    Pass,
+
+    /// The test passed, but left processes running after it exited.
+    Leak,
+
+    /// The test failed.
+    Fail,
+
+    /// The test passed, but left processes running after it exited, and tests that do are marked
+    /// as failed.
+    LeakFail,
+
+    /// The test couldn't be executed.
+    ExecFail,
+
+    /// The test was terminated for running for longer than its timeout.
+    Timeout,
+
+    /// The test was terminated for producing no output for longer than its no-output timeout.
+    Hang,
+
+    /// The test was stopped for using more memory than its memory limit.
+    MemoryLimitExceeded,
+
+    /// The test was expected to fail, and it failed.
+    ExpectedFailure,
+
+    /// The test was expected to fail, but it passed.
+    UnexpectedPass,
+
+    /// The test was expected to fail, but it passed, and tests that do are marked as failed.
+    UnexpectedPassFail,
+}
+
+/// The status of a test once it finished.
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TestFinishedStatus {
+    /// The test passed on the first attempt.
+    Passed,
+
+    /// The test failed at first, then passed on a retry.
+    Flaky,
+
+    /// The test failed on every attempt.
+    Failed,
+}
+
+/// The reason a run is being canceled, in a [`RunBeginCancelEventSummary`].
+///
+/// Introduced in cargo-nextest 0.9.35.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum CancelReasonSummary {
+    /// A test failed, and the run isn't continued after failures.
+    TestFailure,
+
+    /// Building or listing tests failed while tests were being run.
+    BuildFailure,
+
+    /// The run time budget was used up.
+    RunTimeBudget,
+
+    /// An error occurred while reporting results.
+    ReportError,
+
+    /// A termination signal was received.
+    Signal,
+
+    /// An interrupt signal was received.
+    Interrupt,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each line is the exact output for one event: changing any of these breaks programs that read
+    // `--message-format json`.
+    static ATTEMPT_JSON: &str = concat!(
+        r#"{"attempt":1,"total-attempts":2,"iteration":1,"result":"fail","exit-code":101,"#,
+        r#""signal":null,"time-taken":0.25,"slow":true,"test-seed":7,"stdout":"out\n","#,
+        r#""stderr":"err\n","output-chunks":[{"stream":"stdout","start":0,"end":4,"elapsed":0.0},"#,
+        r#"{"stream":"stderr","start":0,"end":4,"elapsed":0.125}],"leaked-pids":[1234],"#,
+        r#""kept-tmpdir":"/tmp/nextest-kept"}"#,
+    );
+
+    fn test_id() -> TestIdSummary {
+        TestIdSummary {
+            binary_id: "my-crate::my-test".to_owned(),
+            name: "tests::foo".to_owned(),
+        }
+    }
+
+    fn attempt() -> TestAttemptSummary {
+        TestAttemptSummary {
+            attempt: 1,
+            total_attempts: 2,
+            iteration: 1,
+            result: TestAttemptResult::Fail,
+            exit_code: Some(101),
+            signal: None,
+            time_taken: 0.25,
+            slow: true,
+            test_seed: 7,
+            stdout: "out\n".to_owned(),
+            stderr: "err\n".to_owned(),
+            output_chunks: vec![
+                OutputChunkSummary {
+                    stream: OutputStreamSummary::Stdout,
+                    start: 0,
+                    end: 4,
+                    elapsed: 0.0,
+                },
+                OutputChunkSummary {
+                    stream: OutputStreamSummary::Stderr,
+                    start: 0,
+                    end: 4,
+                    elapsed: 0.125,
+                },
+            ],
+            leaked_pids: vec![1234],
+            kept_tmpdir: Some("/tmp/nextest-kept".into()),
+        }
+    }
+
+    #[track_caller]
+    fn assert_json(event: RunEventSummary, expected: &str) {
+        assert_eq!(
+            serde_json::to_string(&event).expect("event serialized"),
+            expected,
+            "serialized event matches"
+        );
+        assert_eq!(
+            serde_json::from_str::<RunEventSummary>(expected).expect("event deserialized"),
+            event,
+            "deserialized event matches"
+        );
+    }
+
+    #[test]
+    fn test_run_events() {
+        assert_json(
+            RunEventSummary::RunStarted(RunStartedEventSummary {
+                run_id: "00000000-0000-0000-0000-000000000001".to_owned(),
+                test_count: 3,
+                iterations: Some(1),
+                shuffle_seed: None,
+                seed: 42,
+                previously_finished: Some(2),
+                listing: true,
+            }),
+            concat!(
+                r#"{"type":"run-started","run-id":"00000000-0000-0000-0000-000000000001","#,
+                r#""test-count":3,"iterations":1,"shuffle-seed":null,"seed":42,"#,
+                r#""previously-finished":2,"listing":true}"#,
+            ),
+        );
+        assert_json(
+            RunEventSummary::TestsAdded(TestsAddedEventSummary {
+                test_count: 2,
+                binary_ids: vec!["my-crate::my-test".to_owned()],
+            }),
+            r#"{"type":"tests-added","test-count":2,"binary-ids":["my-crate::my-test"]}"#,
+        );
+        assert_json(
+            RunEventSummary::TestStarted(TestStartedEventSummary {
+                test: test_id(),
+                running: 2,
+            }),
+            r#"{"type":"test-started","binary-id":"my-crate::my-test","name":"tests::foo","running":2}"#,
+        );
+        assert_json(
+            RunEventSummary::TestSlow(TestSlowEventSummary {
+                test: test_id(),
+                elapsed: 60.0,
+            }),
+            r#"{"type":"test-slow","binary-id":"my-crate::my-test","name":"tests::foo","elapsed":60.0}"#,
+        );
+        assert_json(
+            RunEventSummary::TestOutput(TestOutputEventSummary {
+                test: test_id(),
+                stream: OutputStreamSummary::Stderr,
+                output: "line\n".to_owned(),
+                elapsed: 0.5,
+            }),
+            concat!(
+                r#"{"type":"test-output","binary-id":"my-crate::my-test","name":"tests::foo","#,
+                r#""stream":"stderr","output":"line\n","elapsed":0.5}"#,
+            ),
+        );
+        assert_json(
+            RunEventSummary::TestRetry(TestRetryEventSummary {
+                test: test_id(),
+                attempt: attempt(),
+                delay: 1.5,
+                deferred: true,
+            }),
+            &format!(
+                r#"{{"type":"test-retry","binary-id":"my-crate::my-test","name":"tests::foo","attempt":{},"delay":1.5,"deferred":true}}"#,
+                ATTEMPT_JSON
+            ),
+        );
+        assert_json(
+            RunEventSummary::TestIsolationChecked(TestAttemptEventSummary {
+                test: test_id(),
+                attempt: attempt(),
+            }),
+            &format!(
+                r#"{{"type":"test-isolation-checked","binary-id":"my-crate::my-test","name":"tests::foo","attempt":{}}}"#,
+                ATTEMPT_JSON
+            ),
+        );
+        assert_json(
+            RunEventSummary::TestDeterminismChecked(TestDeterminismCheckedEventSummary {
+                test: test_id(),
+                attempt: attempt(),
+                nondeterministic: true,
+            }),
+            &format!(
+                r#"{{"type":"test-determinism-checked","binary-id":"my-crate::my-test","name":"tests::foo","attempt":{},"nondeterministic":true}}"#,
+                ATTEMPT_JSON
+            ),
+        );
+        assert_json(
+            RunEventSummary::TestFinished(TestFinishedEventSummary {
+                test: test_id(),
+                status: TestFinishedStatus::Flaky,
+                quarantined: false,
+                attempts: vec![attempt()],
+            }),
+            &format!(
+                r#"{{"type":"test-finished","binary-id":"my-crate::my-test","name":"tests::foo","status":"flaky","quarantined":false,"attempts":[{}]}}"#,
+                ATTEMPT_JSON
+            ),
+        );
+        assert_json(
+            RunEventSummary::TestPreviouslyFinished(TestPreviouslyFinishedEventSummary {
+                test: test_id(),
+                status: TestFinishedStatus::Failed,
+                time_taken: 0.25,
+            }),
+            concat!(
+                r#"{"type":"test-previously-finished","binary-id":"my-crate::my-test","#,
+                r#""name":"tests::foo","status":"failed","time-taken":0.25}"#,
+            ),
+        );
+        assert_json(
+            RunEventSummary::TestSkipped(TestSkippedEventSummary {
+                test: test_id(),
+                reason: MismatchReason::Ignored,
+            }),
+            r#"{"type":"test-skipped","binary-id":"my-crate::my-test","name":"tests::foo","reason":"ignored"}"#,
+        );
+        assert_json(
+            RunEventSummary::TestNotRun(test_id()),
+            r#"{"type":"test-not-run","binary-id":"my-crate::my-test","name":"tests::foo"}"#,
+        );
+        assert_json(
+            RunEventSummary::RunBeginCancel(RunBeginCancelEventSummary {
+                running: 1,
+                reason: CancelReasonSummary::RunTimeBudget,
+            }),
+            r#"{"type":"run-begin-cancel","running":1,"reason":"run-time-budget"}"#,
+        );
+        assert_json(
+            RunEventSummary::RunBeginCancel(RunBeginCancelEventSummary {
+                running: 0,
+                reason: CancelReasonSummary::BuildFailure,
+            }),
+            r#"{"type":"run-begin-cancel","running":0,"reason":"build-failure"}"#,
+        );
+        assert_json(
+            RunEventSummary::RunFinished(RunFinishedEventSummary {
+                run_id: "00000000-0000-0000-0000-000000000001".to_owned(),
+                elapsed: 12.5,
+                success: false,
+                initial_run_count: 3,
+                finished_count: 3,
+                passed: 2,
+                flaky: 1,
+                failed: 1,
+                timed_out: 0,
+                leaky: 0,
+                exec_failed: 0,
+                skipped: 4,
+                quarantined_failed: 0,
+                pass_rates: vec![PassRateSummary {
+                    test_group: Some("flaky-group".to_owned()),
+                    required: 0.9,
+                    passed: 1,
+                    failed: 1,
+                }],
+            }),
+            concat!(
+                r#"{"type":"run-finished","run-id":"00000000-0000-0000-0000-000000000001","#,
+                r#""elapsed":12.5,"success":false,"initial-run-count":3,"finished-count":3,"#,
+                r#""passed":2,"flaky":1,"failed":1,"timed-out":0,"leaky":0,"exec-failed":0,"#,
+                r#""skipped":4,"quarantined-failed":0,"pass-rates":[{"test-group":"flaky-group","#,
+                r#""required":0.9,"passed":1,"failed":1}]}"#,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_enum_values() {
+        let results = [
+            (TestAttemptResult::Pass, "pass"),
+            (TestAttemptResult::Leak, "leak"),
+            (TestAttemptResult::Fail, "fail"),
+            (TestAttemptResult::LeakFail, "leak-fail"),
+            (TestAttemptResult::ExecFail, "exec-fail"),
+            (TestAttemptResult::Timeout, "timeout"),
+            (TestAttemptResult::Hang, "hang"),
+            (
+                TestAttemptResult::MemoryLimitExceeded,
+                "memory-limit-exceeded",
+            ),
+            (TestAttemptResult::ExpectedFailure, "expected-failure"),
+            (TestAttemptResult::UnexpectedPass, "unexpected-pass"),
+            (
+                TestAttemptResult::UnexpectedPassFail,
+                "unexpected-pass-fail",
+            ),
+        ];
+        for (result, expected) in results {
+            assert_eq!(
+                serde_json::to_value(result).expect("result serialized"),
+                expected,
+                "{result:?} serialized"
+            );
+        }
+
+        let reasons = [
+            (CancelReasonSummary::TestFailure, "test-failure"),
+            (CancelReasonSummary::RunTimeBudget, "run-time-budget"),
+            (CancelReasonSummary::ReportError, "report-error"),
+            (CancelReasonSummary::Signal, "signal"),
+            (CancelReasonSummary::Interrupt, "interrupt"),
+        ];
+        for (reason, expected) in reasons {
+            assert_eq!(
+                serde_json::to_value(reason).expect("reason serialized"),
+                expected,
+                "{reason:?} serialized"
+            );
+        }
+    }
+
+    #[test]
+    fn test_output_without_timings() {
+        // Output from versions of nextest that didn't record when output was read can still be
+        // read.
+        let event: RunEventSummary = serde_json::from_str(
+            r#"{"type":"test-output","binary-id":"my-crate::my-test","name":"tests::foo","stream":"stdout","output":"line\n"}"#,
+        )
+        .expect("event deserialized");
+        assert_eq!(
+            event,
+            RunEventSummary::TestOutput(TestOutputEventSummary {
+                test: test_id(),
+                stream: OutputStreamSummary::Stdout,
+                output: "line\n".to_owned(),
+                elapsed: 0.0,
+            })
+        );
+
+        let mut attempt_json: serde_json::Value =
+            serde_json::from_str(ATTEMPT_JSON).expect("attempt deserialized");
+        let fields = attempt_json.as_object_mut().expect("attempt is an object");
+        fields.remove("output-chunks");
+        let attempt: TestAttemptSummary =
+            serde_json::from_value(attempt_json).expect("attempt deserialized");
+        assert_eq!(attempt.output_chunks, vec![]);
+    }
+}
@@ -195,6 +195,32 @@ pub struct RustTestBinarySummary {
     /// Platform for which this binary was built.
     /// (Proc-macro tests are built for the host.)
     pub build_platform: BuildPlatform,
+
+    /// For doctests, information about the doctest this binary was compiled from.
+    ///
+    /// Introduced in cargo-nextest 0.9.35. This is `None` for other kinds of test binaries.
+    #[serde(default)]
+    pub doctest: Option<RustDoctestSummary>,
+
+    /// The Cargo features the binary's package was built with.
+    ///
+    /// Introduced in cargo-nextest 0.9.35. This is empty for binaries built outside of Cargo.
+    #[serde(default)]
+    pub features: Vec<String>,
+}
+
+/// Information about a doctest compiled into its own binary.
+///
+/// Part of a [`RustTestBinarySummary`].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RustDoctestSummary {
+    /// The name of the doctest, as reported by rustdoc, e.g. `src/lib.rs - foo (line 10)`.
+    pub name: String,
+
+    /// True if the doctest is marked `should_panic`, in which case the binary is expected to exit
+    /// with a failure.
+    pub should_panic: bool,
 }
 
 /// Information about the kind of a Rust test binary.
@@ -237,6 +263,9 @@ impl RustTestBinaryKind {
 
     /// The "proc-macro" kind, used for tests within procedural macros.
     pub const PROC_MACRO: Self = Self::new_const("proc-macro");
+
+    /// The "doctest" kind, used for doctests compiled into their own binaries.
+    pub const DOCTEST: Self = Self::new_const("doctest");
 }
 
 impl fmt::Display for RustTestBinaryKind {
@@ -272,8 +301,101 @@ pub struct RustBuildMetaSummary {
     /// Linked paths, relative to the target directory.
     pub linked_paths: BTreeSet<Utf8PathBuf>,
 
+    /// The package IDs that requested each linked path, keyed by the linked path.
+    ///
+    /// Introduced in cargo-nextest 0.9.35. Older versions do not record this information, in
+    /// which case this map is empty.
+    #[serde(default)]
+    pub linked_path_packages: BTreeMap<Utf8PathBuf, BTreeSet<String>>,
+
     /// The target triple used while compiling the Rust artifacts
     pub target_triple: String,
+
+    /// The path to the custom target spec (JSON) file used while compiling the Rust artifacts, if
+    /// any.
+    ///
+    /// Introduced in cargo-nextest 0.9.35. Older versions do not support custom target spec files.
+    #[serde(default)]
+    pub target_spec_file: Option<Utf8PathBuf>,
+
+    /// The version of rustc used while compiling the Rust artifacts.
+    ///
+    /// Introduced in cargo-nextest 0.9.35. This is `None` for metadata produced by older versions,
+    /// or if the version couldn't be determined.
+    #[serde(default)]
+    pub rustc_version: Option<RustcVersionSummary>,
+
+    /// The Cargo feature selection used while compiling the Rust artifacts.
+    ///
+    /// Introduced in cargo-nextest 0.9.35. This is `None` for metadata produced by older versions.
+    #[serde(default)]
+    pub features: Option<FeatureSelectionSummary>,
+
+    /// The Cargo profile used while compiling the Rust artifacts.
+    ///
+    /// Introduced in cargo-nextest 0.9.35. This is `None` for metadata produced by older versions.
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+
+/// The version of rustc used to build Rust artifacts, as reported by `rustc -vV`.
+///
+/// This is part of [`RustBuildMetaSummary`].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RustcVersionSummary {
+    /// The release version of rustc, e.g. `1.63.0`.
+    pub release: String,
+
+    /// The commit hash rustc was built from, if known.
+    pub commit_hash: Option<String>,
+}
+
+impl fmt::Display for RustcVersionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.commit_hash {
+            Some(commit_hash) => write!(f, "{} ({})", self.release, commit_hash),
+            None => write!(f, "{}", self.release),
+        }
+    }
+}
+
+/// The Cargo feature selection used to build Rust artifacts.
+///
+/// This is part of [`RustBuildMetaSummary`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FeatureSelectionSummary {
+    /// The features passed in with `--features`.
+    pub features: BTreeSet<String>,
+
+    /// Whether `--all-features` was passed in.
+    pub all_features: bool,
+
+    /// Whether `--no-default-features` was passed in.
+    pub no_default_features: bool,
+}
+
+impl fmt::Display for FeatureSelectionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if self.all_features {
+            parts.push("--all-features".to_owned());
+        }
+        if self.no_default_features {
+            parts.push("--no-default-features".to_owned());
+        }
+        if !self.features.is_empty() {
+            let features: Vec<_> = self.features.iter().map(|s| s.as_str()).collect();
+            parts.push(format!("--features {}", features.join(",")));
+        }
+
+        if parts.is_empty() {
+            write!(f, "default features")
+        } else {
+            write!(f, "{}", parts.join(" "))
+        }
+    }
 }
 
 /// A non-test Rust binary. Used to set the correct environment
@@ -323,6 +445,20 @@ impl RustNonTestBinaryKind {
 
     /// The "bin-exe" kind, used for binary executables.
     pub const BIN_EXE: Self = Self::new_const("bin-exe");
+
+    /// The "bin-artifact" kind, used for binary executables built as [artifact
+    /// dependencies](https://doc.rust-lang.org/nightly/cargo/reference/unstable.html#artifact-dependencies)
+    /// of other packages.
+    ///
+    /// Introduced in cargo-nextest 0.9.35.
+    pub const BIN_ARTIFACT: Self = Self::new_const("bin-artifact");
+
+    /// The "cdylib" kind, used for C-compatible dynamic libraries (`.so` on Linux, `.dylib` on
+    /// macOS, `.dll` on Windows) built by workspace packages. Other files produced alongside them
+    /// use the "dylib" kind.
+    ///
+    /// Introduced in cargo-nextest 0.9.35.
+    pub const CDYLIB: Self = Self::new_const("cdylib");
 }
 
 impl fmt::Display for RustNonTestBinaryKind {
@@ -410,12 +546,54 @@ pub struct RustTestCaseSummary {
     /// Ignored tests, if run, are executed with the `--ignored` argument.
     pub ignored: bool,
 
+    /// The reason this test is ignored, as provided through `#[ignore = "reason"]`.
+    ///
+    /// Introduced in cargo-nextest 0.9.35. This is `None` if the test isn't ignored, no reason was
+    /// provided, or the test binary didn't report it.
+    #[serde(default)]
+    pub ignore_reason: Option<String>,
+
+    /// Where this test is defined in source code.
+    ///
+    /// Introduced in cargo-nextest 0.9.35. This is `None` if the test binary didn't report it.
+    #[serde(default)]
+    pub location: Option<RustTestLocationSummary>,
+
+    /// The tags assigned to this test, through the `[test-tags]` table in the config.
+    ///
+    /// Introduced in cargo-nextest 0.9.35.
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+
     /// Whether the test matches the provided test filter.
     ///
     /// Only tests that match the filter are run.
     pub filter_match: FilterMatch,
 }
 
+/// The location of a test in source code.
+///
+/// Part of a [`RustTestCaseSummary`].
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RustTestLocationSummary {
+    /// The path to the source file, as passed in to the compiler. For workspace packages, this is
+    /// typically relative to the workspace root.
+    pub path: Utf8PathBuf,
+
+    /// The line the test function is defined on, starting from 1.
+    pub line: u32,
+
+    /// The column the test function's name starts at, starting from 1.
+    pub column: u32,
+}
+
+impl fmt::Display for RustTestLocationSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.path, self.line, self.column)
+    }
+}
+
 /// An enum describing whether a test matches a filter.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case", tag = "status")]
@@ -453,6 +631,11 @@ pub enum MismatchReason {
 
     /// This test is in a different partition.
     Partition,
+
+    /// This test is not in the provided list of test IDs.
+    ///
+    /// Introduced in cargo-nextest 0.9.35.
+    TestIds,
 }
 
 impl fmt::Display for MismatchReason {
@@ -464,6 +647,7 @@ impl fmt::Display for MismatchReason {
                 write!(f, "does not match the provided expression filters")
             }
             MismatchReason::Partition => write!(f, "is in a different partition"),
+            MismatchReason::TestIds => write!(f, "is not in the provided list of test IDs"),
         }
     }
 }
@@ -0,0 +1,441 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Collecting the results of benchmarks written with [criterion](https://docs.rs/criterion).
+//!
+//! Benchmarks are run by [`TestRunner`](crate::runner::TestRunner) in bench mode, each one in its
+//! own process. Criterion writes out the results of each benchmark as JSON files within its output
+//! directory: `new/benchmark.json` identifies the benchmark, `new/estimates.json` contains the
+//! measurements, and `change/estimates.json` contains the change from the previous run (if any).
+
+use crate::{
+    errors::{BenchResultsError, WriteTestListError},
+    list::{OutputFormat, Styles, TestInstance},
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use nextest_metadata::{BenchEstimateSummary, BenchResultsSummary, BenchmarkSummary};
+use owo_colors::OwoColorize;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    io::{self, Write},
+};
+
+/// The environment variable criterion reads its output directory from.
+pub const CRITERION_HOME_ENV: &str = "CRITERION_HOME";
+
+/// Returns the directory criterion writes results to for benchmarks in `target_dir`.
+///
+/// This is the same as the directory used with `cargo bench`, so results from earlier runs of
+/// either command are compared against each other.
+pub fn criterion_home(target_dir: &Utf8Path) -> Utf8PathBuf {
+    target_dir.join("criterion")
+}
+
+/// Records the benchmarks that ran successfully, to collect their results once the run is done.
+#[derive(Clone, Debug, Default)]
+pub struct BenchResultsCollector {
+    // Benchmark names, keyed by binary ID.
+    benchmarks: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl BenchResultsCollector {
+    /// Creates a new, empty collector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a benchmark that ran successfully.
+    pub fn record(&mut self, test_instance: &TestInstance<'_>) {
+        self.benchmarks
+            .entry(test_instance.bin_info.binary_id.clone())
+            .or_default()
+            .insert(test_instance.name.to_owned());
+    }
+
+    /// Reads the results of the recorded benchmarks from `criterion_home`.
+    pub fn collect(self, criterion_home: &Utf8Path) -> Result<BenchResults, BenchResultsError> {
+        let result_dirs = find_result_dirs(criterion_home)?;
+
+        let mut summary = BenchResultsSummary::default();
+        let mut missing = Vec::new();
+        for (binary_id, names) in self.benchmarks {
+            for name in names {
+                match result_dirs.get(&name) {
+                    Some(dir) => {
+                        summary
+                            .benchmarks
+                            .entry(binary_id.clone())
+                            .or_default()
+                            .insert(name, read_benchmark(dir)?);
+                    }
+                    None => missing.push((binary_id.clone(), name)),
+                }
+            }
+        }
+
+        Ok(BenchResults { summary, missing })
+    }
+}
+
+/// The results of a benchmark run.
+///
+/// Created with [`BenchResultsCollector::collect`].
+#[derive(Clone, Debug)]
+pub struct BenchResults {
+    summary: BenchResultsSummary,
+    missing: Vec<(String, String)>,
+}
+
+impl BenchResults {
+    /// Returns the benchmarks that ran successfully but didn't write out any results, as (binary
+    /// ID, benchmark name) pairs.
+    ///
+    /// This is the case for benchmarks that don't use criterion.
+    pub fn missing(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.missing
+            .iter()
+            .map(|(binary_id, name)| (binary_id.as_str(), name.as_str()))
+    }
+
+    /// Returns a serializable summary of the results.
+    pub fn to_summary(&self) -> BenchResultsSummary {
+        self.summary.clone()
+    }
+
+    /// Outputs the results in the given format.
+    pub fn write(
+        &self,
+        output_format: OutputFormat,
+        writer: impl Write,
+        colorize: bool,
+    ) -> Result<(), WriteTestListError> {
+        match output_format {
+            OutputFormat::Human { .. } => self
+                .write_human(writer, colorize)
+                .map_err(WriteTestListError::Io),
+            OutputFormat::Serializable(format) => format
+                .to_writer(&self.summary, writer)
+                .map_err(WriteTestListError::Json),
+        }
+    }
+
+    fn write_human(&self, mut writer: impl Write, colorize: bool) -> io::Result<()> {
+        let mut styles = Styles::default();
+        if colorize {
+            styles.colorize();
+        }
+
+        for (binary_id, benchmarks) in &self.summary.benchmarks {
+            writeln!(writer, "{}:", binary_id.style(styles.binary_id))?;
+            for (name, benchmark) in benchmarks {
+                write!(
+                    writer,
+                    "    {}: {} {} [{} .. {}], {} {}, {} {}",
+                    name.style(styles.test_name),
+                    "mean".style(styles.field),
+                    format_time(benchmark.mean.point_estimate),
+                    format_time(benchmark.mean.lower_bound),
+                    format_time(benchmark.mean.upper_bound),
+                    "median".style(styles.field),
+                    format_time(benchmark.median.point_estimate),
+                    "std dev".style(styles.field),
+                    format_time(benchmark.std_dev.point_estimate),
+                )?;
+                if let Some(change) = &benchmark.mean_change {
+                    write!(
+                        writer,
+                        ", {} {:+.2}%",
+                        "change".style(styles.field),
+                        change.point_estimate * 100.0,
+                    )?;
+                }
+                writeln!(writer)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// ---
+// Criterion's output files
+// ---
+
+#[derive(Deserialize)]
+struct CriterionBenchmark {
+    full_id: String,
+}
+
+#[derive(Deserialize)]
+struct CriterionEstimates {
+    mean: CriterionEstimate,
+    median: CriterionEstimate,
+    std_dev: CriterionEstimate,
+}
+
+#[derive(Deserialize)]
+struct CriterionChangeEstimates {
+    mean: CriterionEstimate,
+}
+
+#[derive(Deserialize)]
+struct CriterionEstimate {
+    confidence_interval: CriterionConfidenceInterval,
+    point_estimate: f64,
+}
+
+#[derive(Deserialize)]
+struct CriterionConfidenceInterval {
+    lower_bound: f64,
+    upper_bound: f64,
+}
+
+impl From<CriterionEstimate> for BenchEstimateSummary {
+    fn from(estimate: CriterionEstimate) -> Self {
+        Self {
+            point_estimate: estimate.point_estimate,
+            lower_bound: estimate.confidence_interval.lower_bound,
+            upper_bound: estimate.confidence_interval.upper_bound,
+        }
+    }
+}
+
+/// Finds the directories criterion wrote results to, keyed by benchmark ID.
+///
+/// Criterion turns benchmark IDs into directory names in lossy ways, so the ID is read from each
+/// directory rather than the other way around.
+fn find_result_dirs(
+    criterion_home: &Utf8Path,
+) -> Result<HashMap<String, Utf8PathBuf>, BenchResultsError> {
+    let mut result_dirs = HashMap::new();
+    if !criterion_home.is_dir() {
+        return Ok(result_dirs);
+    }
+
+    let mut dirs = vec![criterion_home.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        let entries = dir
+            .read_dir_utf8()
+            .map_err(|error| BenchResultsError::ReadDir {
+                path: dir.clone(),
+                error,
+            })?;
+        for entry in entries {
+            let entry = entry.map_err(|error| BenchResultsError::ReadDir {
+                path: dir.clone(),
+                error,
+            })?;
+            if !entry.path().is_dir() {
+                continue;
+            }
+            // Results from earlier runs are stored alongside the new ones, and HTML reports are
+            // stored in their own directories.
+            match entry.file_name() {
+                "new" => {
+                    let benchmark_file = entry.path().join("benchmark.json");
+                    if benchmark_file.is_file() {
+                        let benchmark: CriterionBenchmark = read_json(&benchmark_file)?;
+                        result_dirs.insert(benchmark.full_id, dir.clone());
+                    }
+                }
+                "base" | "change" | "report" => {}
+                _ => dirs.push(entry.path().to_owned()),
+            }
+        }
+    }
+
+    Ok(result_dirs)
+}
+
+fn read_benchmark(dir: &Utf8Path) -> Result<BenchmarkSummary, BenchResultsError> {
+    let estimates: CriterionEstimates = read_json(&dir.join("new").join("estimates.json"))?;
+    let change_file = dir.join("change").join("estimates.json");
+    let mean_change = if change_file.is_file() {
+        let change: CriterionChangeEstimates = read_json(&change_file)?;
+        Some(change.mean.into())
+    } else {
+        None
+    };
+
+    Ok(BenchmarkSummary {
+        mean: estimates.mean.into(),
+        median: estimates.median.into(),
+        std_dev: estimates.std_dev.into(),
+        mean_change,
+    })
+}
+
+fn read_json<T: DeserializeOwned>(path: &Utf8Path) -> Result<T, BenchResultsError> {
+    let json = fs::read_to_string(path).map_err(|error| BenchResultsError::ReadFile {
+        path: path.to_owned(),
+        error,
+    })?;
+    serde_json::from_str(&json).map_err(|error| BenchResultsError::Deserialize {
+        path: path.to_owned(),
+        error,
+    })
+}
+
+/// Formats a time in nanoseconds with four significant digits, the same way criterion does.
+fn format_time(ns: f64) -> String {
+    fn short(n: f64) -> String {
+        if n < 10.0 {
+            format!("{:.4}", n)
+        } else if n < 100.0 {
+            format!("{:.3}", n)
+        } else {
+            format!("{:.2}", n)
+        }
+    }
+
+    if ns < 1.0e3 {
+        format!("{} ns", short(ns))
+    } else if ns < 1.0e6 {
+        format!("{} µs", short(ns / 1.0e3))
+    } else if ns < 1.0e9 {
+        format!("{} ms", short(ns / 1.0e6))
+    } else {
+        format!("{} s", short(ns / 1.0e9))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn estimate_json(point_estimate: f64) -> String {
+        format!(
+            r#"{{
+                "confidence_interval": {{
+                    "confidence_level": 0.95,
+                    "lower_bound": {},
+                    "upper_bound": {}
+                }},
+                "point_estimate": {},
+                "standard_error": 1.0
+            }}"#,
+            point_estimate - 1.0,
+            point_estimate + 1.0,
+            point_estimate,
+        )
+    }
+
+    fn write_results(dir: &Utf8Path, full_id: &str, mean: f64, change: Option<f64>) {
+        fs::create_dir_all(dir.join("new")).unwrap();
+        fs::write(
+            dir.join("new/benchmark.json"),
+            format!(
+                r#"{{"group_id": "g", "full_id": {}, "directory_name": "x"}}"#,
+                serde_json::to_string(full_id).unwrap(),
+            ),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("new/estimates.json"),
+            format!(
+                r#"{{"mean": {}, "median": {}, "median_abs_dev": {}, "slope": null, "std_dev": {}}}"#,
+                estimate_json(mean),
+                estimate_json(mean - 10.0),
+                estimate_json(5.0),
+                estimate_json(20.0),
+            ),
+        )
+        .unwrap();
+        if let Some(change) = change {
+            fs::create_dir_all(dir.join("change")).unwrap();
+            fs::write(
+                dir.join("change/estimates.json"),
+                format!(
+                    r#"{{"mean": {}, "median": {}}}"#,
+                    estimate_json(change),
+                    estimate_json(change),
+                ),
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_collect() {
+        let temp_dir = TempDir::new().expect("temp dir created");
+        let home: &Utf8Path = temp_dir.path().try_into().expect("temp dir is valid UTF-8");
+
+        // Criterion sanitizes directory names, so they don't match benchmark IDs.
+        write_results(&home.join("fib/20"), "fib/20", 1500.0, Some(-0.05));
+        write_results(&home.join("parse_ _json_"), "parse \"json\"", 100.0, None);
+        // Results from earlier runs and reports aren't picked up.
+        write_results(&home.join("fib/20/base"), "fib/20/base", 1.0, None);
+        fs::create_dir_all(home.join("report")).unwrap();
+
+        let mut collector = BenchResultsCollector::new();
+        collector.benchmarks.insert(
+            "my-bench".to_owned(),
+            ["fib/20", "parse \"json\"", "no-criterion"]
+                .into_iter()
+                .map(|name| name.to_owned())
+                .collect(),
+        );
+        let results = collector.collect(home).expect("results collected");
+
+        assert_eq!(
+            results.missing().collect::<Vec<_>>(),
+            vec![("my-bench", "no-criterion")],
+        );
+        let benchmarks = &results.to_summary().benchmarks["my-bench"];
+        assert_eq!(benchmarks.len(), 2);
+
+        let fib = &benchmarks["fib/20"];
+        assert_eq!(
+            fib.mean,
+            BenchEstimateSummary {
+                point_estimate: 1500.0,
+                lower_bound: 1499.0,
+                upper_bound: 1501.0,
+            }
+        );
+        assert_eq!(fib.median.point_estimate, 1490.0);
+        assert_eq!(fib.std_dev.point_estimate, 20.0);
+        assert_eq!(
+            fib.mean_change.map(|change| change.point_estimate),
+            Some(-0.05)
+        );
+
+        assert_eq!(benchmarks["parse \"json\""].mean_change, None);
+
+        let mut output = Vec::new();
+        results
+            .write(OutputFormat::Human { verbose: false }, &mut output, false)
+            .expect("results written");
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "my-bench:\n    \
+             fib/20: mean 1.5000 µs [1.4990 µs .. 1.5010 µs], median 1.4900 µs, \
+             std dev 20.000 ns, change -5.00%\n    \
+             parse \"json\": mean 100.00 ns [99.000 ns .. 101.00 ns], median 90.000 ns, \
+             std dev 20.000 ns\n",
+        );
+    }
+
+    #[test]
+    fn test_collect_missing_home() {
+        let temp_dir = TempDir::new().expect("temp dir created");
+        let home: &Utf8Path = temp_dir.path().try_into().expect("temp dir is valid UTF-8");
+
+        let mut collector = BenchResultsCollector::new();
+        collector
+            .benchmarks
+            .entry("my-bench".to_owned())
+            .or_default()
+            .insert("fib/20".to_owned());
+        let results = collector
+            .collect(&home.join("criterion"))
+            .expect("missing criterion home isn't an error");
+        assert_eq!(
+            results.missing().collect::<Vec<_>>(),
+            vec![("my-bench", "fib/20")],
+        );
+    }
+}
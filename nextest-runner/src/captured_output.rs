@@ -0,0 +1,499 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Limits on the output captured from tests, for the `captured-output` setting.
+//!
+//! Once a stream is longer than `max-size`, only its start and its end are kept, each half of
+//! `max-size`, since the start shows what the test was doing and the end is usually where failures
+//! are reported. With `store-full-output`, everything the test wrote to the stream is also written
+//! to a file in the store directory once it's cut down.
+
+use crate::{
+    config::CapturedOutputConfig,
+    runner::{OutputChunk, OutputStream},
+};
+use bytes::{Buf, BytesMut};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{
+    collections::VecDeque,
+    io,
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+use tokio::io::{AsyncWriteExt, BufWriter};
+
+/// The longest a test name can be in the name of its full output file.
+const MAX_NAME_LEN: usize = 64;
+
+/// Hands out buffers that cut down captured output to the configured size.
+#[derive(Debug)]
+pub(crate) struct OutputLimits {
+    max_size: u64,
+    full_output_dir: Option<Utf8PathBuf>,
+    next_id: AtomicUsize,
+}
+
+impl OutputLimits {
+    /// Creates limits from the profile's settings, removing any full output files written out by
+    /// earlier runs.
+    pub(crate) fn new(config: &CapturedOutputConfig) -> Self {
+        let full_output_dir = config.full_output_dir().map(Utf8Path::to_owned);
+        if let Some(dir) = &full_output_dir {
+            match std::fs::remove_dir_all(dir) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    log::warn!(
+                        target: "nextest-runner",
+                        "failed to remove output from earlier runs in `{dir}`: {err}",
+                    );
+                }
+            }
+        }
+        Self {
+            max_size: config.max_size(),
+            full_output_dir,
+            next_id: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns a buffer for what an attempt of the test `test_name` writes to `stream`.
+    pub(crate) fn buffer(&self, test_name: &str, stream: OutputStream) -> CapturedOutput {
+        let full_output_path = self.full_output_dir.as_ref().map(|dir| {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+            dir.join(file_name(id, test_name, stream))
+        });
+        CapturedOutput::new(
+            stream,
+            Some(Limit {
+                head_size: self.max_size / 2,
+                tail_size: (self.max_size - self.max_size / 2) as usize,
+                tail: BytesMut::new(),
+                truncated: 0,
+                full_output_path,
+                full_output: None,
+            }),
+        )
+    }
+}
+
+/// The output captured from a stream, which is cut down if it gets too long.
+///
+/// Each chunk of output is pushed along with a sequence number shared with the test's other
+/// stream, so that the order output was read in across both streams can be worked out, and the
+/// time since the test started that it was read at.
+#[derive(Debug)]
+pub(crate) struct CapturedOutput {
+    stream: OutputStream,
+    // All of the output if it isn't limited, and its start otherwise.
+    head: BytesMut,
+    // The length of everything written to the stream.
+    len: u64,
+    // The chunks that start within the head.
+    head_chunks: Vec<Chunk>,
+    // The chunks after those that are at least partly within the tail.
+    tail_chunks: VecDeque<Chunk>,
+    limit: Option<Limit>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Chunk {
+    seq: usize,
+    elapsed: Duration,
+    // The range of everything written to the stream that this chunk is.
+    start: u64,
+    end: u64,
+}
+
+#[derive(Debug)]
+struct Limit {
+    head_size: u64,
+    tail_size: usize,
+    tail: BytesMut,
+    // The number of bytes left out between the head and the tail.
+    truncated: u64,
+    full_output_path: Option<Utf8PathBuf>,
+    // Opened once output is first left out, if the full output is written out.
+    full_output: Option<BufWriter<tokio::fs::File>>,
+}
+
+impl CapturedOutput {
+    /// Returns a buffer that keeps all of the output written to `stream`.
+    pub(crate) fn unlimited(stream: OutputStream) -> Self {
+        Self::new(stream, None)
+    }
+
+    fn new(stream: OutputStream, limit: Option<Limit>) -> Self {
+        Self {
+            stream,
+            head: BytesMut::new(),
+            len: 0,
+            head_chunks: Vec::new(),
+            tail_chunks: VecDeque::new(),
+            limit,
+        }
+    }
+
+    /// Adds output that was just read, `elapsed` after the test started, as the chunk numbered
+    /// `seq` across the test's streams.
+    pub(crate) async fn push(&mut self, mut output: &[u8], seq: usize, elapsed: Duration) {
+        if output.is_empty() {
+            return;
+        }
+        let chunk = Chunk {
+            seq,
+            elapsed,
+            start: self.len,
+            end: self.len + output.len() as u64,
+        };
+        self.len = chunk.end;
+        let limit = match &mut self.limit {
+            Some(limit) => limit,
+            None => {
+                self.head.extend_from_slice(output);
+                self.head_chunks.push(chunk);
+                return;
+            }
+        };
+        match chunk.start < limit.head_size {
+            true => self.head_chunks.push(chunk),
+            false => self.tail_chunks.push_back(chunk),
+        }
+
+        let to_head = output
+            .len()
+            .min((limit.head_size - self.head.len() as u64) as usize);
+        self.head.extend_from_slice(&output[..to_head]);
+        output = &output[to_head..];
+        if output.is_empty() {
+            return;
+        }
+
+        let excess = (limit.tail.len() + output.len()).saturating_sub(limit.tail_size);
+        if excess > 0 && limit.truncated == 0 {
+            // Output is about to be left out for the first time, so start writing out the full
+            // output with what's been kept so far.
+            if let Some(path) = &limit.full_output_path {
+                let mut file = open_full_output(path).await;
+                if let Some(f) = &mut file {
+                    if let Err(err) = write_all(f, &[&self.head, &limit.tail]).await {
+                        warn_full_output(path, &err);
+                        file = None;
+                    }
+                }
+                limit.full_output = file;
+            }
+        }
+        if let Some(file) = &mut limit.full_output {
+            if let Err(err) = file.write_all(output).await {
+                let path = limit
+                    .full_output_path
+                    .as_ref()
+                    .expect("path is set with the file");
+                warn_full_output(path, &err);
+                limit.full_output = None;
+            }
+        }
+
+        limit.tail.extend_from_slice(output);
+        if excess > 0 {
+            limit.tail.advance(excess);
+            limit.truncated += excess as u64;
+            let tail_start = self.len - limit.tail.len() as u64;
+            while matches!(self.tail_chunks.front(), Some(chunk) if chunk.end <= tail_start) {
+                self.tail_chunks.pop_front();
+            }
+        }
+    }
+
+    /// Returns everything that was kept, with a marker saying how much was left out if anything
+    /// was.
+    pub(crate) async fn finish(self) -> FinishedOutput {
+        let mut output = self.head;
+        let head_len = output.len() as u64;
+        let mut marker = String::new();
+        let mut tail = BytesMut::new();
+        if let Some(limit) = self.limit {
+            if limit.truncated > 0 {
+                marker = format!(
+                    "\n--- nextest: {} bytes of output left out",
+                    limit.truncated
+                );
+                if let (Some(mut file), Some(path)) = (limit.full_output, &limit.full_output_path) {
+                    match file.flush().await {
+                        Ok(()) => marker.push_str(&format!(", full output in {path}")),
+                        Err(err) => warn_full_output(path, &err),
+                    }
+                }
+                marker.push_str(" ---\n");
+            }
+            tail = limit.tail;
+        }
+        output.extend_from_slice(marker.as_bytes());
+        // Where the tail starts, in everything written to the stream and in the output.
+        let tail_start = self.len - tail.len() as u64;
+        let tail_offset = output.len() as u64;
+        output.extend_from_slice(&tail);
+
+        // Work out which parts of the output each chunk ended up as. The marker is shown as part
+        // of the first chunk that was cut down.
+        let mut ranges = Vec::new();
+        let mut marker_added = marker.is_empty();
+        for chunk in self.head_chunks.iter().chain(&self.tail_chunks) {
+            if chunk.start < head_len {
+                ranges.push((
+                    *chunk,
+                    chunk.start as usize..chunk.end.min(head_len) as usize,
+                ));
+            }
+            if !marker_added && chunk.end > head_len {
+                ranges.push((*chunk, head_len as usize..tail_offset as usize));
+                marker_added = true;
+            }
+            if chunk.end > tail_start {
+                let start = chunk.start.max(tail_start) - tail_start + tail_offset;
+                let end = chunk.end - tail_start + tail_offset;
+                ranges.push((*chunk, start as usize..end as usize));
+            }
+        }
+
+        FinishedOutput {
+            stream: self.stream,
+            output,
+            ranges,
+        }
+    }
+}
+
+/// The output kept from a stream once the test is done writing to it.
+#[derive(Debug)]
+pub(crate) struct FinishedOutput {
+    stream: OutputStream,
+    pub(crate) output: BytesMut,
+    // The parts of the output each chunk ended up as, along with the chunk.
+    ranges: Vec<(Chunk, Range<usize>)>,
+}
+
+/// Returns the parts of the output kept from a test's standard output and standard error, in the
+/// order they were read in.
+///
+/// Consecutive parts of the same stream are combined if they were read at the same time, or if the
+/// later part just carries on a line without starting a new one. Either way, the time each line
+/// started being written at is kept.
+pub(crate) fn interleave(stdout: &FinishedOutput, stderr: &FinishedOutput) -> Vec<OutputChunk> {
+    let mut stdout_ranges = stdout.ranges.iter().peekable();
+    let mut stderr_ranges = stderr.ranges.iter().peekable();
+    let mut chunks: Vec<OutputChunk> = Vec::new();
+    loop {
+        let (finished, (chunk, range)) = match (stdout_ranges.peek(), stderr_ranges.peek()) {
+            (Some((stdout_chunk, _)), Some((stderr_chunk, _)))
+                if stdout_chunk.seq < stderr_chunk.seq =>
+            {
+                (stdout, stdout_ranges.next().expect("peeked"))
+            }
+            (_, Some(_)) => (stderr, stderr_ranges.next().expect("peeked")),
+            (Some(_), None) => (stdout, stdout_ranges.next().expect("peeked")),
+            (None, None) => break,
+        };
+        match chunks.last_mut() {
+            Some(last)
+                if last.stream == finished.stream
+                    && last.range.end == range.start
+                    && (last.elapsed == chunk.elapsed
+                        || continues_line(&finished.output, range.clone())) =>
+            {
+                last.range.end = range.end;
+            }
+            _ => chunks.push(OutputChunk {
+                stream: finished.stream,
+                range: range.clone(),
+                elapsed: chunk.elapsed,
+            }),
+        }
+    }
+    chunks
+}
+
+/// Returns true if no line starts within `range` of `output`.
+fn continues_line(output: &[u8], range: Range<usize>) -> bool {
+    range.start > 0
+        && output[range.start - 1] != b'\n'
+        && !output[range.start..range.end - 1].contains(&b'\n')
+}
+
+async fn open_full_output(path: &Utf8Path) -> Option<BufWriter<tokio::fs::File>> {
+    let res = async {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::File::create(path).await
+    }
+    .await;
+    match res {
+        Ok(file) => Some(BufWriter::new(file)),
+        Err(err) => {
+            warn_full_output(path, &err);
+            None
+        }
+    }
+}
+
+async fn write_all(file: &mut BufWriter<tokio::fs::File>, outputs: &[&BytesMut]) -> io::Result<()> {
+    for output in outputs {
+        file.write_all(output).await?;
+    }
+    Ok(())
+}
+
+fn warn_full_output(path: &Utf8Path, err: &io::Error) {
+    log::warn!(
+        target: "nextest-runner",
+        "failed to write full test output to `{path}`: {err}",
+    );
+}
+
+/// Returns the name of the file the full output of a stream is written to, which is made unique
+/// by `id`.
+///
+/// Characters that aren't allowed in file names on some platforms are replaced, and long names are
+/// truncated.
+fn file_name(id: usize, test_name: &str, stream: OutputStream) -> String {
+    let name: String = test_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(MAX_NAME_LEN)
+        .collect();
+    let extension = match stream {
+        OutputStream::Stdout => "stdout",
+        OutputStream::Stderr => "stderr",
+    };
+    format!("{id}-{name}.{extension}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limited(max_size: u64, full_output_dir: Option<Utf8PathBuf>) -> OutputLimits {
+        OutputLimits {
+            max_size,
+            full_output_dir,
+            next_id: AtomicUsize::new(0),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_head_and_tail() {
+        let limits = limited(10, None);
+        let mut output = limits.buffer("tests::a", OutputStream::Stdout);
+        output.push(b"0123", 0, Duration::ZERO).await;
+        output.push(b"4567", 1, Duration::ZERO).await;
+        assert_eq!(&output.finish().await.output[..], b"01234567");
+
+        let mut output = limits.buffer("tests::a", OutputStream::Stdout);
+        for (seq, chunk) in [&b"0123"[..], b"456789abc", b"def", b"ghijk"]
+            .into_iter()
+            .enumerate()
+        {
+            output.push(chunk, seq, Duration::ZERO).await;
+        }
+        let finished = output.finish().await;
+        assert_eq!(
+            &finished.output[..],
+            &b"01234\n--- nextest: 11 bytes of output left out ---\nghijk"[..]
+        );
+        // The marker is part of the first chunk that was cut down, and chunks that were left out
+        // entirely are dropped.
+        assert_eq!(
+            finished
+                .ranges
+                .iter()
+                .map(|(chunk, range)| (chunk.seq, range.clone()))
+                .collect::<Vec<_>>(),
+            vec![(0, 0..4), (1, 4..5), (1, 5..51), (3, 51..56)]
+        );
+
+        let mut output = CapturedOutput::unlimited(OutputStream::Stdout);
+        output.push(b"0123456789abcdef", 0, Duration::ZERO).await;
+        assert_eq!(&output.finish().await.output[..], b"0123456789abcdef");
+    }
+
+    #[tokio::test]
+    async fn test_full_output() {
+        let dir = tempfile::tempdir().expect("created temporary directory");
+        let dir = Utf8PathBuf::try_from(dir.path().to_owned()).expect("path is UTF-8");
+        let limits = limited(4, Some(dir.join("output")));
+
+        let mut output = limits.buffer("tests::a", OutputStream::Stderr);
+        output.push(b"012", 0, Duration::ZERO).await;
+        output.push(b"3456789", 1, Duration::ZERO).await;
+        let path = dir.join("output/0-tests__a.stderr");
+        assert_eq!(
+            String::from_utf8(output.finish().await.output.to_vec()).expect("output is UTF-8"),
+            format!("01\n--- nextest: 6 bytes of output left out, full output in {path} ---\n89"),
+        );
+        assert_eq!(
+            std::fs::read(&path).expect("full output was written"),
+            b"0123456789"
+        );
+
+        // Output that isn't cut down isn't written out.
+        let mut output = limits.buffer("tests::b", OutputStream::Stderr);
+        output.push(b"0123", 0, Duration::ZERO).await;
+        output.finish().await;
+        assert!(!dir.join("output/1-tests__b.stderr").exists());
+    }
+
+    #[tokio::test]
+    async fn test_interleave() {
+        let ms = Duration::from_millis;
+        let mut stdout = CapturedOutput::unlimited(OutputStream::Stdout);
+        let mut stderr = CapturedOutput::unlimited(OutputStream::Stderr);
+        stdout.push(b"a\n", 0, ms(1)).await;
+        stderr.push(b"b\n", 1, ms(1)).await;
+        stdout.push(b"c\n", 2, ms(2)).await;
+        stdout.push(b"d\n", 3, ms(2)).await;
+        stdout.push(b"e\n", 4, ms(3)).await;
+        stderr.push(b"f", 5, ms(3)).await;
+        stderr.push(b"g\n", 6, ms(4)).await;
+        stderr.push(b"h\n", 7, ms(5)).await;
+
+        let chunk = |stream, range, elapsed| OutputChunk {
+            stream,
+            range,
+            elapsed,
+        };
+        assert_eq!(
+            interleave(&stdout.finish().await, &stderr.finish().await),
+            vec![
+                chunk(OutputStream::Stdout, 0..2, ms(1)),
+                chunk(OutputStream::Stderr, 0..2, ms(1)),
+                chunk(OutputStream::Stdout, 2..6, ms(2)),
+                chunk(OutputStream::Stdout, 6..8, ms(3)),
+                // "g" carries on the line "f" started.
+                chunk(OutputStream::Stderr, 2..5, ms(3)),
+                chunk(OutputStream::Stderr, 5..7, ms(5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(
+            file_name(0, "tests::basic", OutputStream::Stdout),
+            "0-tests__basic.stdout"
+        );
+        let long_name = "a".repeat(100);
+        assert_eq!(
+            file_name(3, &long_name, OutputStream::Stderr).len(),
+            2 + MAX_NAME_LEN + ".stderr".len()
+        );
+    }
+}
@@ -18,10 +18,16 @@ use toml_edit::Item;
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TargetTriple {
     /// The target triple being built.
+    ///
+    /// For custom targets defined by a target spec file, this is the file stem of the spec file.
+    /// This matches the name of the output directory Cargo uses for the target.
     pub triple: String,
 
     /// The source the triple came from.
     pub source: TargetTripleSource,
+
+    /// The location where the target is defined.
+    pub location: TargetDefinitionLocation,
 }
 
 impl TargetTriple {
@@ -47,17 +53,68 @@ impl TargetTriple {
 
     /// Converts a `String` that was output by `TargetTriple::serialize` back to a target triple.
     /// This target triple is assumed to orginiate from a build-metadata config.
-    pub fn deserialize(target_triple: String) -> Option<TargetTriple> {
+    ///
+    /// `target_spec_file` is the path to the custom target spec file, if any, as recorded in the
+    /// build-metadata.
+    pub fn deserialize(
+        target_triple: String,
+        target_spec_file: Option<Utf8PathBuf>,
+    ) -> Option<TargetTriple> {
         if target_triple.is_empty() {
             None
         } else {
             Some(TargetTriple {
                 triple: target_triple,
                 source: TargetTripleSource::Metadata,
+                location: match target_spec_file {
+                    Some(path) => TargetDefinitionLocation::DirectFile(path),
+                    None => TargetDefinitionLocation::Builtin,
+                },
             })
         }
     }
 
+    /// Returns the path to the custom target spec file, if this target is defined by one.
+    pub fn target_spec_file(&self) -> Option<&Utf8Path> {
+        match &self.location {
+            TargetDefinitionLocation::Builtin => None,
+            TargetDefinitionLocation::DirectFile(path) => Some(path),
+        }
+    }
+
+    /// Returns the value to pass in to `--target` for Cargo and rustc.
+    ///
+    /// For custom targets, this is the path to the target spec file.
+    pub fn target_arg(&self) -> &str {
+        match &self.location {
+            TargetDefinitionLocation::Builtin => &self.triple,
+            TargetDefinitionLocation::DirectFile(path) => path.as_str(),
+        }
+    }
+
+    // Handles both target triples and paths to target spec files. Relative paths to target spec
+    // files are resolved against `base_dir`, similar to Cargo.
+    fn new(target: &str, source: TargetTripleSource, base_dir: &Utf8Path) -> Self {
+        let path = Utf8Path::new(target);
+        if path.extension() == Some("json") {
+            let triple = path
+                .file_stem()
+                .expect("paths with an extension have a file stem")
+                .to_owned();
+            Self {
+                triple,
+                source,
+                location: TargetDefinitionLocation::DirectFile(base_dir.join(path)),
+            }
+        } else {
+            Self {
+                triple: target.to_owned(),
+                source,
+                location: TargetDefinitionLocation::Builtin,
+            }
+        }
+    }
+
     /// Find the target triple being built.
     ///
     /// This does so by looking at, in order:
@@ -66,17 +123,18 @@ impl TargetTriple {
     /// 2. the CARGO_BUILD_TARGET env var
     /// 3. build.target in Cargo config files
     ///
-    /// Note that currently this only supports triples, not JSON files.
+    /// Both target triples and paths to custom target spec (JSON) files are supported.
     pub fn find(
         cargo_configs: &CargoConfigs,
         target_cli_option: Option<&str>,
     ) -> Result<Option<Self>, TargetTripleError> {
         // First, look at the CLI option passed in.
         if let Some(triple) = target_cli_option {
-            return Ok(Some(TargetTriple {
-                triple: triple.to_owned(),
-                source: TargetTripleSource::CliOption,
-            }));
+            return Ok(Some(Self::new(
+                triple,
+                TargetTripleSource::CliOption,
+                cargo_configs.cwd(),
+            )));
         }
 
         // Finally, look at the cargo configs.
@@ -86,15 +144,12 @@ impl TargetTriple {
     /// The environment variable used for target searches
     pub const CARGO_BUILD_TARGET_ENV: &'static str = "CARGO_BUILD_TARGET";
 
-    fn from_env() -> Result<Option<Self>, TargetTripleError> {
+    fn from_env(cwd: &Utf8Path) -> Result<Option<Self>, TargetTripleError> {
         if let Some(triple_val) = std::env::var_os(Self::CARGO_BUILD_TARGET_ENV) {
             let triple = triple_val
                 .into_string()
                 .map_err(|_osstr| TargetTripleError::InvalidEnvironmentVar)?;
-            Ok(Some(Self {
-                triple,
-                source: TargetTripleSource::Env,
-            }))
+            Ok(Some(Self::new(&triple, TargetTripleSource::Env, cwd)))
         } else {
             Ok(None)
         }
@@ -106,17 +161,27 @@ impl TargetTriple {
                 DiscoveredConfig::CliOption { config, source }
                 | DiscoveredConfig::File { config, source } => {
                     if let Some(triple) = &config.build.target {
-                        return Ok(Some(TargetTriple {
-                            triple: triple.to_owned(),
-                            source: TargetTripleSource::CargoConfig {
+                        // Paths in config files are relative to the parent of the directory the
+                        // file is in.
+                        let base_dir = match source {
+                            CargoConfigSource::CliOption => cargo_configs.cwd(),
+                            CargoConfigSource::File(path) => path
+                                .parent()
+                                .and_then(|dir| dir.parent())
+                                .unwrap_or_else(|| cargo_configs.cwd()),
+                        };
+                        return Ok(Some(Self::new(
+                            triple,
+                            TargetTripleSource::CargoConfig {
                                 source: source.clone(),
                             },
-                        }));
+                            base_dir,
+                        )));
                     }
                 }
                 DiscoveredConfig::Env => {
                     // Look at the CARGO_BUILD_TARGET env var.
-                    if let Some(triple) = Self::from_env()? {
+                    if let Some(triple) = Self::from_env(cargo_configs.cwd())? {
                         return Ok(Some(triple));
                     }
                 }
@@ -127,6 +192,18 @@ impl TargetTriple {
     }
 }
 
+/// The location where a target is defined.
+///
+/// This is the type of [`TargetTriple::location`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TargetDefinitionLocation {
+    /// The target is built into rustc.
+    Builtin,
+
+    /// The target is defined by a custom target spec (JSON) file at this path.
+    DirectFile(Utf8PathBuf),
+}
+
 /// The place where a target triple's configuration was picked up from.
 ///
 /// This is the type of [`TargetTriple::source`].
@@ -611,6 +688,7 @@ mod tests {
                 source: TargetTripleSource::CargoConfig {
                     source: CargoConfigSource::File(dir_path.join("foo/bar/.cargo/config.toml")),
                 },
+                location: TargetDefinitionLocation::Builtin,
             }),
         );
 
@@ -621,6 +699,7 @@ mod tests {
                 source: TargetTripleSource::CargoConfig {
                     source: CargoConfigSource::File(dir_path.join("foo/.cargo/config")),
                 },
+                location: TargetDefinitionLocation::Builtin,
             }),
         );
 
@@ -636,6 +715,7 @@ mod tests {
                 source: TargetTripleSource::CargoConfig {
                     source: CargoConfigSource::CliOption,
                 },
+                location: TargetDefinitionLocation::Builtin,
             })
         );
 
@@ -655,6 +735,7 @@ mod tests {
                 source: TargetTripleSource::CargoConfig {
                     source: CargoConfigSource::CliOption,
                 },
+                location: TargetDefinitionLocation::Builtin,
             })
         );
 
@@ -671,6 +752,7 @@ mod tests {
                 source: TargetTripleSource::CargoConfig {
                     source: CargoConfigSource::CliOption,
                 },
+                location: TargetDefinitionLocation::Builtin,
             })
         );
 
@@ -685,6 +767,7 @@ mod tests {
             Some(TargetTriple {
                 triple: "aarch64-pc-windows-msvc".into(),
                 source: TargetTripleSource::Env,
+                location: TargetDefinitionLocation::Builtin,
             })
         );
 
@@ -697,6 +780,7 @@ mod tests {
                 source: TargetTripleSource::CargoConfig {
                     source: CargoConfigSource::File(dir_foo_path.join("extra-config.toml")),
                 },
+                location: TargetDefinitionLocation::Builtin,
             })
         );
         assert_eq!(
@@ -709,6 +793,7 @@ mod tests {
             Some(TargetTriple {
                 triple: "aarch64-pc-windows-msvc".into(),
                 source: TargetTripleSource::Env,
+                location: TargetDefinitionLocation::Builtin,
             })
         );
         assert_eq!(
@@ -726,6 +811,7 @@ mod tests {
                 source: TargetTripleSource::CargoConfig {
                     source: CargoConfigSource::CliOption,
                 },
+                location: TargetDefinitionLocation::Builtin,
             })
         );
         assert_eq!(
@@ -743,12 +829,59 @@ mod tests {
                 source: TargetTripleSource::CargoConfig {
                     source: CargoConfigSource::CliOption,
                 },
+                location: TargetDefinitionLocation::Builtin,
             })
         );
 
         assert_eq!(find_target_triple(&[], None, &dir_path, &dir_path), None);
     }
 
+    #[test]
+    fn test_find_target_triple_spec_file() {
+        let dir = setup_temp_dir().unwrap();
+        let dir_path = Utf8PathBuf::try_from(dir.path().canonicalize().unwrap()).unwrap();
+        let dir_foo_path = dir_path.join("foo");
+        let dir_foo_bar_path = dir_foo_path.join("bar");
+        std::fs::write(
+            dir_foo_bar_path.join(".cargo/config.toml"),
+            "[build]\ntarget = \"targets/my-target.json\"\n",
+        )
+        .unwrap();
+
+        // Paths in config files are relative to the parent of the .cargo directory.
+        assert_eq!(
+            find_target_triple(&[], None, &dir_foo_bar_path, &dir_path),
+            Some(TargetTriple {
+                triple: "my-target".into(),
+                source: TargetTripleSource::CargoConfig {
+                    source: CargoConfigSource::File(dir_path.join("foo/bar/.cargo/config.toml")),
+                },
+                location: TargetDefinitionLocation::DirectFile(
+                    dir_foo_bar_path.join("targets/my-target.json")
+                ),
+            }),
+        );
+
+        // Paths passed in via --config key-value pairs are relative to the current directory.
+        assert_eq!(
+            find_target_triple(
+                &["build.target=\"other-target.json\""],
+                None,
+                &dir_foo_path,
+                &dir_path
+            ),
+            Some(TargetTriple {
+                triple: "other-target".into(),
+                source: TargetTripleSource::CargoConfig {
+                    source: CargoConfigSource::CliOption,
+                },
+                location: TargetDefinitionLocation::DirectFile(
+                    dir_foo_path.join("other-target.json")
+                ),
+            }),
+        );
+    }
+
     fn setup_temp_dir() -> Result<TempDir> {
         let dir = tempfile::Builder::new()
             .tempdir()
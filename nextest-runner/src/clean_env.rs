@@ -0,0 +1,118 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Clearing the environment tests are run in, for the `clean-env` setting.
+//!
+//! Tests don't inherit any environment variables from nextest, other than the ones that are always
+//! kept and the ones in `env-allowlist`. Variables that nextest sets for tests, such as `NEXTEST_*`,
+//! `CARGO_*` for the package and the dynamic library path, are still set.
+
+use std::{collections::HashSet, ffi::OsString, process::Command};
+
+/// Variables that are kept even with a clean environment, since programs commonly fail to start or
+/// find their tools without them.
+const ALWAYS_KEPT: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "TMPDIR",
+    "TMP",
+    "TEMP",
+    // Used by Cargo and rustup if tests run them.
+    "CARGO",
+    "CARGO_HOME",
+    "RUSTUP_HOME",
+    "RUSTUP_TOOLCHAIN",
+    // Needed by most programs on Windows.
+    "SYSTEMROOT",
+    "SYSTEMDRIVE",
+    "WINDIR",
+    "COMSPEC",
+    "PATHEXT",
+    "USERPROFILE",
+    "APPDATA",
+    "LOCALAPPDATA",
+    "PROGRAMDATA",
+];
+
+/// Removes inherited environment variables from test processes.
+#[derive(Debug)]
+pub(crate) struct EnvCleaner {
+    // The names of the variables in nextest's environment that tests don't inherit.
+    removed: Vec<OsString>,
+}
+
+impl EnvCleaner {
+    /// Creates a new cleaner, which keeps the variables that match a pattern in `allowlist`.
+    pub(crate) fn new(allowlist: &[String]) -> Self {
+        let removed = std::env::vars_os()
+            .map(|(name, _)| name)
+            .filter(|name| {
+                let name = match name.to_str() {
+                    Some(name) => name,
+                    // Variables with names that aren't valid UTF-8 can't be allowed.
+                    None => return true,
+                };
+                !ALWAYS_KEPT
+                    .iter()
+                    .copied()
+                    .chain(allowlist.iter().map(String::as_str))
+                    .any(|pattern| matches_pattern(pattern, name))
+            })
+            .collect();
+        Self { removed }
+    }
+
+    /// Keeps the process started by `cmd` from inheriting removed variables.
+    ///
+    /// Variables already set on `cmd` are left alone.
+    pub(crate) fn apply(&self, cmd: &mut Command) {
+        let set: HashSet<_> = cmd.get_envs().map(|(name, _)| name.to_owned()).collect();
+        for name in &self.removed {
+            if !set.contains(name.as_os_str()) {
+                cmd.env_remove(name);
+            }
+        }
+    }
+}
+
+/// Returns true if `name` matches `pattern`, which is either a variable name or a prefix followed
+/// by `*`.
+///
+/// Variable names are case-insensitive on Windows.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let (pattern, is_prefix) = match pattern.strip_suffix('*') {
+        Some(prefix) => (prefix, true),
+        None => (pattern, false),
+    };
+    let name = match is_prefix {
+        true => match name.get(..pattern.len()) {
+            Some(name) => name,
+            None => return false,
+        },
+        false => name,
+    };
+    if cfg!(windows) {
+        pattern.eq_ignore_ascii_case(name)
+    } else {
+        pattern == name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("PATH", "PATH"));
+        assert!(!matches_pattern("PATH", "PATHEXT"));
+        assert!(matches_pattern("RUST_*", "RUST_LOG"));
+        assert!(matches_pattern("RUST_*", "RUST_"));
+        assert!(!matches_pattern("RUST_*", "RUSTFLAGS"));
+        assert!(!matches_pattern("RUST_*", "RUST"));
+        assert!(matches_pattern("*", "ANYTHING"));
+        assert_eq!(matches_pattern("path", "PATH"), cfg!(windows));
+    }
+}
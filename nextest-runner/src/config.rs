@@ -9,13 +9,25 @@ use crate::{
         TestThreadsParseError, ToolConfigFileParseError,
     },
     reporter::{FinalStatusLevel, StatusLevel, TestOutputDisplay},
+    reuse_build::{
+        deserialize_size, ArchiveCompression, ArchiveDebuginfo, ArchiveInclude, PathRemap,
+    },
+    runner::ExecuteStatus,
 };
 use camino::{Utf8Path, Utf8PathBuf};
 use config::{builder::DefaultState, Config, ConfigBuilder, File, FileFormat, FileSourceFile};
 use guppy::graph::PackageGraph;
-use nextest_filtering::{FilteringExpr, TestQuery};
+use nextest_filtering::{
+    BinaryQuery, FilterAliases, FilteringExpr, ParseContext, TestQuery, TestTags,
+};
 use serde::{de::IntoDeserializer, Deserialize};
-use std::{collections::HashMap, fmt, num::NonZeroUsize, str::FromStr, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    num::NonZeroUsize,
+    str::FromStr,
+    time::Duration,
+};
 
 /// Overall configuration for nextest.
 ///
@@ -29,6 +41,8 @@ use std::{collections::HashMap, fmt, num::NonZeroUsize, str::FromStr, time::Dura
 pub struct NextestConfig {
     workspace_root: Utf8PathBuf,
     inner: NextestConfigImpl,
+    filterset_aliases: FilterAliases,
+    test_tags: TestTags,
     overrides: NextestOverridesImpl,
 }
 
@@ -71,11 +85,13 @@ impl NextestConfig {
     {
         let workspace_root = workspace_root.into();
         let tool_config_files_rev = tool_config_files.into_iter().rev();
-        let (inner, overrides) =
+        let (inner, filterset_aliases, test_tags, overrides) =
             Self::read_from_sources(graph, &workspace_root, config_file, tool_config_files_rev)?;
         Ok(Self {
             workspace_root,
             inner,
+            filterset_aliases,
+            test_tags,
             overrides,
         })
     }
@@ -92,7 +108,9 @@ impl NextestConfig {
         Self {
             workspace_root: workspace_root.into(),
             inner,
-            // The default config does not (cannot) have overrides.
+            // The default config does not (cannot) have aliases, tag rules or overrides.
+            filterset_aliases: FilterAliases::default(),
+            test_tags: TestTags::default(),
             overrides: NextestOverridesImpl::default(),
         }
     }
@@ -103,6 +121,36 @@ impl NextestConfig {
         self.make_profile(name.as_ref())
     }
 
+    /// Returns the filterset aliases defined in the `[filterset-aliases]` table, which filter
+    /// expressions can refer to with `alias(name)`.
+    pub fn filterset_aliases(&self) -> &FilterAliases {
+        &self.filterset_aliases
+    }
+
+    /// Returns the test tags defined in the `[test-tags]` table, which filter expressions can
+    /// match against with `tag(name)`.
+    pub fn test_tags(&self) -> &TestTags {
+        &self.test_tags
+    }
+
+    /// Returns true if binaries extracted from archives should be re-signed on macOS.
+    pub fn macos_resign(&self) -> bool {
+        self.inner.reuse_build.macos_resign
+    }
+
+    /// Returns the rules to remap paths with while reusing builds, in the order they should be
+    /// tried.
+    ///
+    /// Relative destinations are resolved against the workspace root.
+    pub fn path_remaps(&self) -> Vec<PathRemap> {
+        self.inner
+            .reuse_build
+            .path_remap
+            .iter()
+            .map(|remap| remap.relative_to(&self.workspace_root))
+            .collect()
+    }
+
     // ---
     // Helper methods
     // ---
@@ -112,24 +160,28 @@ impl NextestConfig {
         workspace_root: &Utf8Path,
         file: Option<&Utf8Path>,
         tool_config_files_rev: impl Iterator<Item = &'a ToolConfigFile>,
-    ) -> Result<(NextestConfigImpl, NextestOverridesImpl), ConfigParseError> {
+    ) -> Result<
+        (
+            NextestConfigImpl,
+            FilterAliases,
+            TestTags,
+            NextestOverridesImpl,
+        ),
+        ConfigParseError,
+    > {
         // First, get the default config.
         let mut composite_builder = Self::make_default_config();
 
-        // Overrides are handled additively.
-        // Note that they're stored in reverse order here, and are flipped over at the end.
-        let mut overrides_impl = NextestOverridesImpl::default();
+        // Each file is also deserialized on its own, lowest priority first, so that aliases, tag
+        // rules and overrides can be handled additively.
+        let mut individual_configs = Vec::new();
 
         // Next, merge in tool configs.
         for ToolConfigFile { config_file, tool } in tool_config_files_rev {
             let source = File::new(config_file.as_str(), FileFormat::Toml);
-            Self::deserialize_individual_config(
-                graph,
-                config_file,
-                Some(tool),
-                source.clone(),
-                &mut overrides_impl,
-            )?;
+            let this_config =
+                Self::deserialize_individual_config(config_file, Some(tool), source.clone())?;
+            individual_configs.push((config_file.clone(), Some(tool.as_str()), this_config));
 
             // This is the final, composite builder used at the end.
             composite_builder = composite_builder.add_source(source);
@@ -145,60 +197,240 @@ impl NextestConfig {
             }
         };
 
-        Self::deserialize_individual_config(
-            graph,
-            &config_file,
-            None,
-            source.clone(),
-            &mut overrides_impl,
-        )?;
+        let this_config = Self::deserialize_individual_config(&config_file, None, source.clone())?;
+        individual_configs.push((config_file.clone(), None, this_config));
 
         composite_builder = composite_builder.add_source(source);
 
         let config = Self::build_and_deserialize_config(&composite_builder)
             .map_err(|kind| ConfigParseError::new(config_file, None, kind))?;
 
+        Self::check_test_groups(&config, &individual_configs)?;
+        Self::check_resources(&config, &individual_configs)?;
+        let filterset_aliases = Self::compile_filterset_aliases(graph, &individual_configs)?;
+        let test_tags = Self::compile_test_tags(
+            graph,
+            &filterset_aliases,
+            config.test_tags.name_prefix.as_deref(),
+            &individual_configs,
+        )?;
+
+        // Overrides are handled additively.
+        // Note that they're stored in reverse order here, and are flipped over at the end.
+        let mut cx = ParseContext::new();
+        cx.set_aliases(&filterset_aliases).set_tags(&test_tags);
+        let mut overrides_impl = NextestOverridesImpl::default();
+        for (config_file, tool, this_config) in &individual_configs {
+            // Compile the overrides for this file.
+            let this_overrides = NextestOverridesImpl::new(graph, &cx, this_config)
+                .map_err(|kind| ConfigParseError::new(config_file, *tool, kind))?;
+
+            // Grab the overrides for this config. Add them in reversed order (we'll flip it around at the end).
+            overrides_impl
+                .default
+                .extend(this_overrides.default.into_iter().rev());
+            for (name, overrides) in this_overrides.other {
+                overrides_impl
+                    .other
+                    .entry(name)
+                    .or_default()
+                    .extend(overrides.into_iter().rev());
+            }
+        }
+
         // Reverse all the overrides at the end.
         overrides_impl.default.reverse();
         for override_ in overrides_impl.other.values_mut() {
             override_.reverse();
         }
 
-        Ok((config, overrides_impl))
+        Ok((config, filterset_aliases, test_tags, overrides_impl))
     }
 
     fn deserialize_individual_config(
-        graph: &PackageGraph,
         config_file: &Utf8Path,
         tool: Option<&str>,
         source: File<FileSourceFile, FileFormat>,
-        overrides_impl: &mut NextestOverridesImpl,
-    ) -> Result<(), ConfigParseError> {
-        // Try building default builder + this file to get good error attribution and handle
-        // overrides additively.
+    ) -> Result<NextestConfigImpl, ConfigParseError> {
+        // Try building default builder + this file to get good error attribution.
         let default_builder = Self::make_default_config();
         let this_builder = default_builder.add_source(source);
-        let this_config = Self::build_and_deserialize_config(&this_builder)
-            .map_err(|kind| ConfigParseError::new(config_file, tool, kind))?;
-        // Compile the overrides for this file.
-        let this_overrides = NextestOverridesImpl::new(graph, &this_config)
-            .map_err(|kind| ConfigParseError::new(config_file, tool, kind))?;
-
-        // Grab the overrides for this config. Add them in reversed order (we'll flip it around at the end).
-        overrides_impl
-            .default
-            .extend(this_overrides.default.into_iter().rev());
-        for (name, overrides) in this_overrides.other {
-            overrides_impl
-                .other
-                .entry(name)
-                .or_default()
-                .extend(overrides.into_iter().rev());
+        Self::build_and_deserialize_config(&this_builder)
+            .map_err(|kind| ConfigParseError::new(config_file, tool, kind))
+    }
+
+    fn check_test_groups(
+        config: &NextestConfigImpl,
+        individual_configs: &[(Utf8PathBuf, Option<&str>, NextestConfigImpl)],
+    ) -> Result<(), ConfigParseError> {
+        // Each individual config only has the test groups defined in its own file, but overrides
+        // may refer to groups defined in any file.
+        for (config_file, tool, this_config) in individual_configs {
+            let error = |kind| ConfigParseError::new(config_file, *tool, kind);
+            for (name, group) in &this_config.test_groups {
+                if group.max_threads == TestThreads::Count(0) {
+                    return Err(error(ConfigParseErrorKind::InvalidTestGroupThreads {
+                        name: name.clone(),
+                    }));
+                }
+            }
+
+            let profiles = std::iter::once(("default", &this_config.profiles.default.overrides))
+                .chain(
+                    this_config
+                        .profiles
+                        .other
+                        .iter()
+                        .map(|(name, profile)| (name.as_str(), &profile.overrides)),
+                );
+            for (profile_name, overrides) in profiles {
+                for source in overrides {
+                    match &source.data.test_group {
+                        Some(name) if !config.test_groups.contains_key(name) => {
+                            return Err(error(ConfigParseErrorKind::UnknownTestGroup {
+                                profile_name: profile_name.to_owned(),
+                                name: name.clone(),
+                                known_groups: config.test_groups.keys().cloned().collect(),
+                            }));
+                        }
+                        _ => {}
+                    }
+                }
+            }
         }
+        Ok(())
+    }
+
+    fn check_resources(
+        config: &NextestConfigImpl,
+        individual_configs: &[(Utf8PathBuf, Option<&str>, NextestConfigImpl)],
+    ) -> Result<(), ConfigParseError> {
+        // As with test groups, overrides may consume resources defined in any file.
+        for (config_file, tool, this_config) in individual_configs {
+            let error = |kind| ConfigParseError::new(config_file, *tool, kind);
+            for (name, &count) in &this_config.resources {
+                if !is_valid_resource_name(name) {
+                    return Err(error(ConfigParseErrorKind::InvalidResourceName {
+                        name: name.clone(),
+                    }));
+                }
+                if count == 0 {
+                    return Err(error(ConfigParseErrorKind::InvalidResourceCount {
+                        name: name.clone(),
+                    }));
+                }
+            }
 
+            let profiles = std::iter::once(("default", &this_config.profiles.default.overrides))
+                .chain(
+                    this_config
+                        .profiles
+                        .other
+                        .iter()
+                        .map(|(name, profile)| (name.as_str(), &profile.overrides)),
+                );
+            for (profile_name, overrides) in profiles {
+                let resources = overrides
+                    .iter()
+                    .flat_map(|source| source.data.resources.iter().flatten());
+                for (name, &requested) in resources {
+                    match config.resources.get(name) {
+                        Some(&available) if requested > available => {
+                            return Err(error(ConfigParseErrorKind::ResourceRequestTooLarge {
+                                profile_name: profile_name.to_owned(),
+                                name: name.clone(),
+                                requested,
+                                available,
+                            }));
+                        }
+                        Some(_) => {}
+                        None => {
+                            return Err(error(ConfigParseErrorKind::UnknownResource {
+                                profile_name: profile_name.to_owned(),
+                                name: name.clone(),
+                                known_resources: config.resources.keys().cloned().collect(),
+                            }));
+                        }
+                    }
+                }
+            }
+        }
         Ok(())
     }
 
+    fn compile_filterset_aliases(
+        graph: &PackageGraph,
+        individual_configs: &[(Utf8PathBuf, Option<&str>, NextestConfigImpl)],
+    ) -> Result<FilterAliases, ConfigParseError> {
+        // Aliases in higher priority files replace those with the same name in lower priority
+        // ones. Keep track of which file each alias came from to attribute errors to it.
+        let mut sources = BTreeMap::new();
+        for (index, (_, _, this_config)) in individual_configs.iter().enumerate() {
+            for (name, expr) in &this_config.filterset_aliases {
+                sources.insert(name.as_str(), (index, expr.as_str()));
+            }
+        }
+
+        let aliases = sources
+            .iter()
+            .map(|(name, (_, expr))| ((*name).to_owned(), (*expr).to_owned()));
+        FilterAliases::new(graph, aliases).map_err(|errors| {
+            // Report errors for the lowest priority file that has any, since aliases in higher
+            // priority files may depend on it.
+            let index = errors
+                .iter()
+                .map(|error| sources[error.name.as_str()].0)
+                .min()
+                .expect("at least one error was returned");
+            let errors = errors
+                .into_iter()
+                .filter(|error| sources[error.name.as_str()].0 == index)
+                .collect();
+            let (config_file, tool, _) = &individual_configs[index];
+            ConfigParseError::new(
+                config_file,
+                *tool,
+                ConfigParseErrorKind::FiltersetAliasError(errors),
+            )
+        })
+    }
+
+    fn compile_test_tags(
+        graph: &PackageGraph,
+        aliases: &FilterAliases,
+        name_prefix: Option<&str>,
+        individual_configs: &[(Utf8PathBuf, Option<&str>, NextestConfigImpl)],
+    ) -> Result<TestTags, ConfigParseError> {
+        let mut test_tags = TestTags::new();
+        if let Some(name_prefix) = name_prefix {
+            test_tags.set_name_prefix(name_prefix);
+        }
+
+        // Tag rules are handled additively. Their filters can't use tag() themselves.
+        let mut cx = ParseContext::new();
+        cx.set_aliases(aliases);
+        for (config_file, tool, this_config) in individual_configs {
+            let mut errors = vec![];
+            for rule in &this_config.test_tags.rules {
+                match FilteringExpr::parse_with_context(&rule.filter, graph, &cx) {
+                    Ok(expr) => {
+                        test_tags.add_rule(expr, &rule.tags);
+                    }
+                    Err(parse_errors) => errors.push(parse_errors),
+                }
+            }
+            if !errors.is_empty() {
+                return Err(ConfigParseError::new(
+                    config_file,
+                    *tool,
+                    ConfigParseErrorKind::TagRuleError(errors),
+                ));
+            }
+        }
+
+        Ok(test_tags)
+    }
+
     fn make_default_config() -> ConfigBuilder<DefaultState> {
         Config::builder().add_source(File::from_str(Self::DEFAULT_CONFIG, FileFormat::Toml))
     }
@@ -221,10 +453,14 @@ impl NextestConfig {
             .collect();
 
         Ok(NextestProfile {
+            name: name.to_owned(),
+            workspace_root: &self.workspace_root,
             store_dir,
             default_profile: &self.inner.profiles.default,
             custom_profile,
             overrides,
+            test_groups: &self.inner.test_groups,
+            resources: &self.inner.resources,
         })
     }
 
@@ -292,25 +528,87 @@ impl FromStr for ToolConfigFile {
 /// Returned by [`NextestConfig::profile`].
 #[derive(Clone, Debug)]
 pub struct NextestProfile<'cfg> {
+    name: String,
+    workspace_root: &'cfg Utf8Path,
     store_dir: Utf8PathBuf,
     default_profile: &'cfg DefaultProfileImpl,
     custom_profile: Option<&'cfg CustomProfileImpl>,
     overrides: Vec<&'cfg ProfileOverrideImpl>,
+    test_groups: &'cfg BTreeMap<String, TestGroupConfig>,
+    resources: &'cfg BTreeMap<String, usize>,
 }
 
 impl<'cfg> NextestProfile<'cfg> {
+    /// Returns the name of this profile.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the root of the workspace this profile is for.
+    pub fn workspace_root(&self) -> &'cfg Utf8Path {
+        self.workspace_root
+    }
+
     /// Returns the absolute profile-specific store directory.
     pub fn store_dir(&self) -> &Utf8Path {
         &self.store_dir
     }
 
-    /// Returns the retry count for this profile.
-    pub fn retries(&self) -> usize {
+    /// Returns the retry policy for this profile.
+    pub fn retries(&self) -> RetryPolicy {
         self.custom_profile
             .and_then(|profile| profile.retries)
             .unwrap_or(self.default_profile.retries)
     }
 
+    /// Returns the condition failed attempts must meet to be retried, if any.
+    pub fn retry_if(&self) -> Option<&'cfg RetryCondition> {
+        self.custom_profile
+            .and_then(|profile| profile.retry_if.as_ref())
+            .or(self.default_profile.retry_if.as_ref())
+    }
+
+    /// Returns when failed tests are retried for this profile.
+    pub fn retry_schedule(&self) -> RetrySchedule {
+        self.custom_profile
+            .and_then(|profile| profile.retry_schedule)
+            .unwrap_or(self.default_profile.retry_schedule)
+    }
+
+    /// Returns true if tests that fail are run again on their own at the end of the run, to tell
+    /// whether they only fail when run alongside other tests.
+    pub fn check_failures_in_isolation(&self) -> bool {
+        self.custom_profile
+            .and_then(|profile| profile.check_failures_in_isolation)
+            .unwrap_or(self.default_profile.check_failures_in_isolation)
+    }
+
+    /// Returns patterns for the parts of lines left out when the output of a test is compared
+    /// between two runs, with `--check-determinism`.
+    pub fn determinism_ignore(&self) -> &'cfg [regex::bytes::Regex] {
+        self.custom_profile
+            .and_then(|profile| profile.determinism_ignore.as_deref())
+            .unwrap_or(&self.default_profile.determinism_ignore)
+    }
+
+    /// Returns true if tests that are expected to fail fail the run if they pass.
+    pub fn strict_xfail(&self) -> bool {
+        self.custom_profile
+            .and_then(|profile| profile.strict_xfail)
+            .unwrap_or(self.default_profile.strict_xfail)
+    }
+
+    /// Returns the fraction of tests, between 0 and 1, that must pass for the run to succeed, if
+    /// failures are tolerated as long as enough tests pass.
+    ///
+    /// Tests in a test group with its own [required pass
+    /// rate](TestGroupConfig::required_pass_rate) count towards that instead.
+    pub fn required_pass_rate(&self) -> Option<f64> {
+        self.custom_profile
+            .and_then(|profile| profile.required_pass_rate)
+            .or(self.default_profile.required_pass_rate)
+    }
+
     /// Returns the number of threads to run against for this profile.
     pub fn test_threads(&self) -> TestThreads {
         self.custom_profile
@@ -333,6 +631,89 @@ impl<'cfg> NextestProfile<'cfg> {
             .unwrap_or(self.default_profile.leak_timeout)
     }
 
+    /// Returns how long a test that's being terminated is given to shut down before it's killed.
+    pub fn terminate_grace_period(&self) -> Duration {
+        self.custom_profile
+            .and_then(|profile| profile.terminate_grace_period)
+            .unwrap_or(self.default_profile.terminate_grace_period)
+    }
+
+    /// Returns the signals to send tests that are terminated after timing out, if they're
+    /// configured.
+    ///
+    /// If these aren't configured, tests are sent `SIGTERM` and given [`Self::terminate_grace_period`]
+    /// to exit.
+    pub fn terminate_signals(&self) -> Option<&'cfg [TerminateStep]> {
+        self.custom_profile
+            .and_then(|profile| profile.terminate_signals.as_deref())
+            .or(self.default_profile.terminate_signals.as_deref())
+    }
+
+    /// Returns how long a test can go without producing any output before it's considered hung
+    /// and terminated, if there's a limit.
+    pub fn no_output_timeout(&self) -> Option<Duration> {
+        self.custom_profile
+            .and_then(|profile| profile.no_output_timeout)
+            .or(self.default_profile.no_output_timeout)
+    }
+
+    /// Returns the most memory a test can use before it's stopped, in bytes, if there's a limit.
+    pub fn memory_limit(&self) -> Option<u64> {
+        self.custom_profile
+            .and_then(|profile| profile.memory_limit)
+            .or(self.default_profile.memory_limit)
+    }
+
+    /// Returns true if each test is run without network access, other than through loopback.
+    pub fn network_isolation(&self) -> bool {
+        self.custom_profile
+            .and_then(|profile| profile.network_isolation)
+            .unwrap_or(self.default_profile.network_isolation)
+    }
+
+    /// Returns true if tests only inherit the environment variables that are always kept and the
+    /// ones in [`Self::env_allowlist`].
+    pub fn clean_env(&self) -> bool {
+        self.custom_profile
+            .and_then(|profile| profile.clean_env)
+            .unwrap_or(self.default_profile.clean_env)
+    }
+
+    /// Returns the patterns for environment variables that tests inherit with a clean environment.
+    ///
+    /// Each pattern is either a variable name, or a prefix followed by `*`.
+    pub fn env_allowlist(&self) -> &'cfg [String] {
+        self.custom_profile
+            .and_then(|profile| profile.env_allowlist.as_deref())
+            .unwrap_or(&self.default_profile.env_allowlist)
+    }
+
+    /// Returns what tests are given as standard input.
+    ///
+    /// Relative paths to files are resolved against the workspace root.
+    pub fn stdin(&self) -> StdinMode {
+        self.custom_profile
+            .and_then(|profile| profile.stdin.as_ref())
+            .unwrap_or(&self.default_profile.stdin)
+            .relative_to(self.workspace_root)
+    }
+
+    /// Returns the nice value tests are run with, if they're not run with nextest's own.
+    ///
+    /// On Windows, the nice value is mapped to a priority class.
+    pub fn nice(&self) -> Option<i32> {
+        self.custom_profile
+            .and_then(|profile| profile.nice)
+            .or(self.default_profile.nice)
+    }
+
+    /// Returns true if each test is pinned to its own CPUs.
+    pub fn pin_cpus(&self) -> bool {
+        self.custom_profile
+            .and_then(|profile| profile.pin_cpus)
+            .unwrap_or(self.default_profile.pin_cpus)
+    }
+
     /// Returns the test status level.
     pub fn status_level(&self) -> StatusLevel {
         self.custom_profile
@@ -368,11 +749,69 @@ impl<'cfg> NextestProfile<'cfg> {
             .unwrap_or(self.default_profile.fail_fast)
     }
 
+    /// Returns the directory tests listed in test binaries are cached in, if the list cache is
+    /// enabled for this profile.
+    pub fn list_cache_dir(&self) -> Option<Utf8PathBuf> {
+        let list_cache = self
+            .custom_profile
+            .and_then(|profile| profile.list_cache)
+            .unwrap_or(self.default_profile.list_cache);
+        list_cache.then(|| self.store_dir.join("list-cache"))
+    }
+
+    /// Returns the order tests are started in for this profile.
+    pub fn test_order(&self) -> TestOrder {
+        self.custom_profile
+            .and_then(|profile| profile.test_order)
+            .unwrap_or(self.default_profile.test_order)
+    }
+
+    /// Returns what's done about processes that tests leave running after they exit.
+    pub fn leaked_processes(&self) -> LeakedProcessPolicy {
+        self.custom_profile
+            .and_then(|profile| profile.leaked_processes)
+            .unwrap_or(self.default_profile.leaked_processes)
+    }
+
+    /// Returns how this profile takes part in a GNU make jobserver.
+    pub fn jobserver(&self) -> JobserverMode {
+        self.custom_profile
+            .and_then(|profile| profile.jobserver)
+            .unwrap_or(self.default_profile.jobserver)
+    }
+
+    /// Returns the test groups that tests can be assigned to with overrides, keyed by name.
+    pub fn test_groups(&self) -> &'cfg BTreeMap<String, TestGroupConfig> {
+        self.test_groups
+    }
+
+    /// Returns the resources that tests can consume tokens of with overrides, along with the number
+    /// of tokens each one has.
+    pub fn resources(&self) -> &'cfg BTreeMap<String, usize> {
+        self.resources
+    }
+
     /// Returns override settings for individual tests.
     pub fn overrides_for(&self, query: &TestQuery<'_>) -> ProfileOverrides {
         let mut retries = None;
+        let mut retry_if = None;
         let mut slow_timeout = None;
         let mut leak_timeout = None;
+        let mut terminate_grace_period = None;
+        let mut terminate_signals = None;
+        let mut no_output_timeout = None;
+        let mut test_group = None;
+        let mut resources = None;
+        let mut threads_required = None;
+        let mut memory_limit = None;
+        let mut pin_cpus = None;
+        let mut nice = None;
+        let mut network_isolation = None;
+        let mut working_directory = None;
+        let mut stdin = None;
+        let mut expected_failure = None;
+        let mut quarantine = None;
+        let mut live_output = None;
 
         for &override_ in &self.overrides {
             if !override_.expr.matches_test(query) {
@@ -381,21 +820,109 @@ impl<'cfg> NextestProfile<'cfg> {
             if retries.is_none() && override_.data.retries.is_some() {
                 retries = override_.data.retries;
             }
+            if retry_if.is_none() && override_.data.retry_if.is_some() {
+                retry_if = override_.data.retry_if.clone();
+            }
             if slow_timeout.is_none() && override_.data.slow_timeout.is_some() {
                 slow_timeout = override_.data.slow_timeout;
             }
             if leak_timeout.is_none() && override_.data.leak_timeout.is_some() {
                 leak_timeout = override_.data.leak_timeout;
             }
+            if terminate_grace_period.is_none() && override_.data.terminate_grace_period.is_some() {
+                terminate_grace_period = override_.data.terminate_grace_period;
+            }
+            if terminate_signals.is_none() && override_.data.terminate_signals.is_some() {
+                terminate_signals = override_.data.terminate_signals.clone();
+            }
+            if no_output_timeout.is_none() && override_.data.no_output_timeout.is_some() {
+                no_output_timeout = override_.data.no_output_timeout;
+            }
+            if test_group.is_none() && override_.data.test_group.is_some() {
+                test_group = override_.data.test_group.clone();
+            }
+            if resources.is_none() && override_.data.resources.is_some() {
+                resources = override_.data.resources.clone();
+            }
+            if threads_required.is_none() && override_.data.threads_required.is_some() {
+                threads_required = override_.data.threads_required;
+            }
+            if memory_limit.is_none() && override_.data.memory_limit.is_some() {
+                memory_limit = override_.data.memory_limit;
+            }
+            if pin_cpus.is_none() && override_.data.pin_cpus.is_some() {
+                pin_cpus = override_.data.pin_cpus;
+            }
+            if nice.is_none() && override_.data.nice.is_some() {
+                nice = override_.data.nice;
+            }
+            if network_isolation.is_none() && override_.data.network_isolation.is_some() {
+                network_isolation = override_.data.network_isolation;
+            }
+            if working_directory.is_none() && override_.data.working_directory.is_some() {
+                working_directory = override_
+                    .data
+                    .working_directory
+                    .as_ref()
+                    .map(|dir| self.workspace_root.join(dir));
+            }
+            if stdin.is_none() && override_.data.stdin.is_some() {
+                stdin = override_
+                    .data
+                    .stdin
+                    .as_ref()
+                    .map(|stdin| stdin.relative_to(self.workspace_root));
+            }
+            if expected_failure.is_none() && override_.data.expected_failure.is_some() {
+                expected_failure = override_.data.expected_failure;
+            }
+            if quarantine.is_none() && override_.data.quarantine.is_some() {
+                quarantine = override_.data.quarantine;
+            }
+            if live_output.is_none() && override_.data.live_output.is_some() {
+                live_output = override_.data.live_output;
+            }
         }
 
         ProfileOverrides {
             retries,
+            retry_if,
             slow_timeout,
             leak_timeout,
+            terminate_grace_period,
+            terminate_signals,
+            no_output_timeout,
+            test_group,
+            resources,
+            threads_required,
+            memory_limit,
+            pin_cpus,
+            nice,
+            network_isolation,
+            working_directory,
+            stdin,
+            expected_failure,
+            quarantine,
+            live_output,
         }
     }
 
+    /// Returns true if the given binary should be run once as a single test, rather than being
+    /// queried for the tests it contains.
+    ///
+    /// Only overrides whose filters can be evaluated against the binary alone are considered: an
+    /// override that depends on test names never matches.
+    pub fn single_test_for(&self, query: &BinaryQuery<'_>) -> bool {
+        self.overrides
+            .iter()
+            .find(|override_| {
+                override_.data.single_test.is_some()
+                    && override_.expr.matches_binary(query) == Some(true)
+            })
+            .and_then(|override_| override_.data.single_test)
+            .unwrap_or(false)
+    }
+
     /// Returns the JUnit configuration for this profile.
     pub fn junit(&self) -> Option<NextestJunitConfig<'cfg>> {
         let path = self
@@ -406,13 +933,118 @@ impl<'cfg> NextestProfile<'cfg> {
 
         path.map(|path| {
             let path = self.store_dir.join(path);
-            let report_name = self
-                .custom_profile
-                .and_then(|profile| profile.junit.report_name.as_deref())
-                .unwrap_or(&self.default_profile.junit.report_name);
-            NextestJunitConfig { path, report_name }
+            let custom = self.custom_profile.map(|profile| &profile.junit);
+            let default = &self.default_profile.junit;
+            let report_name = custom
+                .and_then(|custom| custom.report_name.as_deref())
+                .unwrap_or(&default.report_name);
+            NextestJunitConfig {
+                path,
+                report_name,
+                store_success_output: custom
+                    .and_then(|custom| custom.store_success_output)
+                    .unwrap_or(default.store_success_output),
+                store_failure_output: custom
+                    .and_then(|custom| custom.store_failure_output)
+                    .unwrap_or(default.store_failure_output),
+                max_output_size: custom
+                    .and_then(|custom| custom.max_output_size)
+                    .or(default.max_output_size),
+            }
+        })
+    }
+
+    /// Returns the bounds to scale the number of tests running at a time within, if adaptive
+    /// concurrency is enabled for this profile.
+    pub fn adaptive_threads(&self) -> Option<AdaptiveThreads> {
+        let custom = self.custom_profile.map(|profile| &profile.adaptive_threads);
+        let default = &self.default_profile.adaptive_threads;
+        let enabled = custom
+            .and_then(|custom| custom.enabled)
+            .unwrap_or(default.enabled);
+        enabled.then(|| AdaptiveThreads {
+            min_threads: custom
+                .and_then(|custom| custom.min_threads)
+                .unwrap_or(default.min_threads)
+                .max(1),
+            max_load: custom
+                .and_then(|custom| custom.max_load)
+                .unwrap_or(default.max_load),
         })
     }
+
+    /// Returns the absolute path to write an HTML report of each run to, if one is written for this
+    /// profile.
+    pub fn html_report_path(&self) -> Option<Utf8PathBuf> {
+        let path = self
+            .custom_profile
+            .and_then(|profile| profile.html_report.path.as_deref())
+            .or(self.default_profile.html_report.path.as_deref());
+        path.map(|path| self.store_dir.join(path))
+    }
+
+    /// Returns the absolute path to write an SVG timeline of each run to, if one is written for
+    /// this profile.
+    pub fn timeline_path(&self) -> Option<Utf8PathBuf> {
+        let path = self
+            .custom_profile
+            .and_then(|profile| profile.timeline.path.as_deref())
+            .or(self.default_profile.timeline.path.as_deref());
+        path.map(|path| self.store_dir.join(path))
+    }
+
+    /// Returns the settings for per-test temporary directories, if they're enabled for this
+    /// profile.
+    pub fn test_tmpdir(&self) -> Option<TestTmpdirConfig> {
+        let custom = self.custom_profile.map(|profile| &profile.test_tmpdir);
+        let default = &self.default_profile.test_tmpdir;
+        let enabled = custom
+            .and_then(|custom| custom.enabled)
+            .unwrap_or(default.enabled);
+        enabled.then(|| TestTmpdirConfig {
+            keep_on_failure: custom
+                .and_then(|custom| custom.keep_on_failure)
+                .unwrap_or(default.keep_on_failure),
+        })
+    }
+
+    /// Returns the limits on the output captured from each test, if output is limited for this
+    /// profile.
+    pub fn captured_output(&self) -> Option<CapturedOutputConfig> {
+        let custom = self.custom_profile.map(|profile| &profile.captured_output);
+        let default = &self.default_profile.captured_output;
+        let max_size = custom
+            .and_then(|custom| custom.max_size)
+            .or(default.max_size)?;
+        let store_full_output = custom
+            .and_then(|custom| custom.store_full_output)
+            .unwrap_or(default.store_full_output);
+        Some(CapturedOutputConfig {
+            max_size,
+            full_output_dir: store_full_output.then(|| self.store_dir.join("output")),
+        })
+    }
+
+    /// Returns the compression to use while creating archives with this profile.
+    pub fn archive_compression(&self) -> ArchiveCompression {
+        self.custom_profile
+            .and_then(|profile| profile.archive.compression)
+            .unwrap_or(self.default_profile.archive.compression)
+    }
+
+    /// Returns what to do with debug info in binaries while creating archives with this profile.
+    pub fn archive_debuginfo(&self) -> ArchiveDebuginfo {
+        self.custom_profile
+            .and_then(|profile| profile.archive.debuginfo)
+            .unwrap_or(self.default_profile.archive.debuginfo)
+    }
+
+    /// Returns the files within the workspace to include in archives created with this profile.
+    pub fn archive_include(&self) -> &'cfg [ArchiveInclude] {
+        self.custom_profile
+            .and_then(|profile| profile.archive.include.as_deref())
+            .unwrap_or(&self.default_profile.archive.include)
+    }
 }
 
 /// Override settings for individual tests.
@@ -420,17 +1052,38 @@ impl<'cfg> NextestProfile<'cfg> {
 /// Returned by
 #[derive(Clone, Debug)]
 pub struct ProfileOverrides {
-    retries: Option<usize>,
+    retries: Option<RetryPolicy>,
+    retry_if: Option<RetryCondition>,
     slow_timeout: Option<SlowTimeout>,
     leak_timeout: Option<Duration>,
+    terminate_grace_period: Option<Duration>,
+    terminate_signals: Option<Vec<TerminateStep>>,
+    no_output_timeout: Option<Duration>,
+    test_group: Option<String>,
+    resources: Option<BTreeMap<String, usize>>,
+    threads_required: Option<ThreadsRequired>,
+    memory_limit: Option<u64>,
+    pin_cpus: Option<bool>,
+    nice: Option<i32>,
+    network_isolation: Option<bool>,
+    working_directory: Option<Utf8PathBuf>,
+    stdin: Option<StdinMode>,
+    expected_failure: Option<bool>,
+    quarantine: Option<bool>,
+    live_output: Option<bool>,
 }
 
 impl ProfileOverrides {
-    /// Returns the number of retries for this test.
-    pub fn retries(&self) -> Option<usize> {
+    /// Returns the retry policy for this test.
+    pub fn retries(&self) -> Option<RetryPolicy> {
         self.retries
     }
 
+    /// Returns the condition failed attempts of this test must meet to be retried.
+    pub fn retry_if(&self) -> Option<&RetryCondition> {
+        self.retry_if.as_ref()
+    }
+
     /// Returns the slow timeout for this test.
     pub fn slow_timeout(&self) -> Option<SlowTimeout> {
         self.slow_timeout
@@ -440,6 +1093,83 @@ impl ProfileOverrides {
     pub fn leak_timeout(&self) -> Option<Duration> {
         self.leak_timeout
     }
+
+    /// Returns how long this test is given to shut down when it's terminated.
+    pub fn terminate_grace_period(&self) -> Option<Duration> {
+        self.terminate_grace_period
+    }
+
+    /// Returns the signals to send this test if it's terminated after timing out.
+    pub fn terminate_signals(&self) -> Option<&[TerminateStep]> {
+        self.terminate_signals.as_deref()
+    }
+
+    /// Returns how long this test can go without producing any output before it's considered
+    /// hung.
+    pub fn no_output_timeout(&self) -> Option<Duration> {
+        self.no_output_timeout
+    }
+
+    /// Returns the name of the test group this test is in, if any.
+    pub fn test_group(&self) -> Option<&str> {
+        self.test_group.as_deref()
+    }
+
+    /// Returns the resources this test consumes, along with the number of tokens of each.
+    pub fn resources(&self) -> Option<&BTreeMap<String, usize>> {
+        self.resources.as_ref()
+    }
+
+    /// Returns the number of test threads this test counts as.
+    pub fn threads_required(&self) -> Option<ThreadsRequired> {
+        self.threads_required
+    }
+
+    /// Returns the most memory this test can use before it's stopped, in bytes.
+    pub fn memory_limit(&self) -> Option<u64> {
+        self.memory_limit
+    }
+
+    /// Returns true if this test is pinned to its own CPUs.
+    pub fn pin_cpus(&self) -> Option<bool> {
+        self.pin_cpus
+    }
+
+    /// Returns the nice value this test is run with.
+    pub fn nice(&self) -> Option<i32> {
+        self.nice
+    }
+
+    /// Returns true if this test is run without network access, other than through loopback.
+    pub fn network_isolation(&self) -> Option<bool> {
+        self.network_isolation
+    }
+
+    /// Returns the absolute path to the directory this test is run in.
+    pub fn working_directory(&self) -> Option<&Utf8Path> {
+        self.working_directory.as_deref()
+    }
+
+    /// Returns what this test is given as standard input.
+    pub fn stdin(&self) -> Option<&StdinMode> {
+        self.stdin.as_ref()
+    }
+
+    /// Returns true if this test is expected to fail.
+    pub fn expected_failure(&self) -> Option<bool> {
+        self.expected_failure
+    }
+
+    /// Returns true if this test is quarantined, so that its failures don't fail the run.
+    pub fn quarantine(&self) -> Option<bool> {
+        self.quarantine
+    }
+
+    /// Returns true if each line this test writes is shown as soon as it's written, while the test
+    /// is still captured and run in parallel with others.
+    pub fn live_output(&self) -> Option<bool> {
+        self.live_output
+    }
 }
 
 /// JUnit configuration for nextest, returned by a [`NextestProfile`].
@@ -447,6 +1177,9 @@ impl ProfileOverrides {
 pub struct NextestJunitConfig<'cfg> {
     path: Utf8PathBuf,
     report_name: &'cfg str,
+    store_success_output: bool,
+    store_failure_output: bool,
+    max_output_size: Option<u64>,
 }
 
 impl<'cfg> NextestJunitConfig<'cfg> {
@@ -459,22 +1192,72 @@ impl<'cfg> NextestJunitConfig<'cfg> {
     pub fn report_name(&self) -> &'cfg str {
         self.report_name
     }
+
+    /// Returns true if the standard output and standard error of passing tests are included in the
+    /// report.
+    pub fn store_success_output(&self) -> bool {
+        self.store_success_output
+    }
+
+    /// Returns true if the standard output and standard error of failing tests, and of failed
+    /// attempts of retried tests, are included in the report.
+    pub fn store_failure_output(&self) -> bool {
+        self.store_failure_output
+    }
+
+    /// Returns the most bytes of each of a test's standard output and standard error that are
+    /// included in the report, if limited.
+    pub fn max_output_size(&self) -> Option<u64> {
+        self.max_output_size
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 struct NextestConfigImpl {
     store: StoreConfigImpl,
+    reuse_build: ReuseBuildConfigImpl,
+    #[serde(default)]
+    filterset_aliases: BTreeMap<String, String>,
+    #[serde(default)]
+    test_tags: TestTagsImpl,
+    #[serde(default)]
+    test_groups: BTreeMap<String, TestGroupConfig>,
+    #[serde(default)]
+    resources: BTreeMap<String, usize>,
     #[serde(rename = "profile")]
     profiles: NextestProfilesImpl,
 }
 
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct TestTagsImpl {
+    #[serde(default)]
+    name_prefix: Option<String>,
+    #[serde(default)]
+    rules: Vec<TagRuleImpl>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct TagRuleImpl {
+    filter: String,
+    tags: Vec<String>,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 struct StoreConfigImpl {
     dir: Utf8PathBuf,
 }
 
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ReuseBuildConfigImpl {
+    path_remap: Vec<PathRemap>,
+    macos_resign: bool,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 struct NextestProfilesImpl {
@@ -508,7 +1291,17 @@ impl NextestProfilesImpl {
 #[serde(rename_all = "kebab-case")]
 struct DefaultProfileImpl {
     test_threads: TestThreads,
-    retries: usize,
+    #[serde(deserialize_with = "deserialize_retry_policy")]
+    retries: RetryPolicy,
+    #[serde(default)]
+    retry_if: Option<RetryCondition>,
+    retry_schedule: RetrySchedule,
+    check_failures_in_isolation: bool,
+    #[serde(deserialize_with = "deserialize_output_patterns")]
+    determinism_ignore: Vec<regex::bytes::Regex>,
+    strict_xfail: bool,
+    #[serde(default, deserialize_with = "deserialize_pass_rate")]
+    required_pass_rate: Option<f64>,
     status_level: StatusLevel,
     final_status_level: FinalStatusLevel,
     failure_output: TestOutputDisplay,
@@ -518,9 +1311,268 @@ struct DefaultProfileImpl {
     slow_timeout: SlowTimeout,
     #[serde(with = "humantime_serde")]
     leak_timeout: Duration,
+    #[serde(with = "humantime_serde")]
+    terminate_grace_period: Duration,
+    #[serde(default)]
+    terminate_signals: Option<Vec<TerminateStep>>,
+    #[serde(default, with = "humantime_serde::option")]
+    no_output_timeout: Option<Duration>,
+    leaked_processes: LeakedProcessPolicy,
+    list_cache: bool,
+    test_order: TestOrder,
+    jobserver: JobserverMode,
+    #[serde(default, deserialize_with = "deserialize_optional_size")]
+    memory_limit: Option<u64>,
+    pin_cpus: bool,
+    #[serde(default, deserialize_with = "deserialize_nice")]
+    nice: Option<i32>,
+    network_isolation: bool,
+    stdin: StdinMode,
+    clean_env: bool,
+    env_allowlist: Vec<String>,
     #[serde(default)]
     overrides: Vec<ProfileOverrideSource>,
     junit: DefaultJunitImpl,
+    html_report: DefaultHtmlReportImpl,
+    timeline: DefaultTimelineImpl,
+    archive: DefaultArchiveImpl,
+    adaptive_threads: DefaultAdaptiveThreadsImpl,
+    test_tmpdir: DefaultTestTmpdirImpl,
+    captured_output: DefaultCapturedOutputImpl,
+}
+
+fn is_valid_resource_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// A named group of tests that can't run more than a certain number at a time.
+///
+/// Tests are assigned to groups with overrides. Returned by [`NextestProfile::test_groups`].
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestGroupConfig {
+    max_threads: TestThreads,
+    #[serde(default, deserialize_with = "deserialize_pass_rate")]
+    required_pass_rate: Option<f64>,
+}
+
+impl TestGroupConfig {
+    /// Returns the maximum number of tests in this group that can run at the same time.
+    pub fn max_threads(&self) -> TestThreads {
+        self.max_threads
+    }
+
+    /// Returns the fraction of tests in this group, between 0 and 1, that must pass for the run
+    /// to succeed, if it has its own.
+    pub fn required_pass_rate(&self) -> Option<f64> {
+        self.required_pass_rate
+    }
+}
+
+/// Settings for scaling the number of tests running at a time with system load and memory
+/// pressure.
+///
+/// The number of tests running at a time is kept between [`Self::min_threads`] and the number of
+/// test threads. Returned by [`NextestProfile::adaptive_threads`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveThreads {
+    min_threads: usize,
+    max_load: f64,
+}
+
+impl AdaptiveThreads {
+    /// Returns the fewest tests to keep running at a time, however loaded the system is.
+    pub fn min_threads(&self) -> usize {
+        self.min_threads
+    }
+
+    /// Returns the load average per logical CPU above which fewer tests are run at a time.
+    pub fn max_load(&self) -> f64 {
+        self.max_load
+    }
+}
+
+/// Settings for giving each test its own temporary directory.
+///
+/// Returned by [`NextestProfile::test_tmpdir`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TestTmpdirConfig {
+    keep_on_failure: bool,
+}
+
+impl TestTmpdirConfig {
+    /// Returns true if the temporary directories of failed tests are kept after the run.
+    pub fn keep_on_failure(&self) -> bool {
+        self.keep_on_failure
+    }
+}
+
+/// Limits on the output captured from each test.
+///
+/// Returned by [`NextestProfile::captured_output`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CapturedOutputConfig {
+    max_size: u64,
+    full_output_dir: Option<Utf8PathBuf>,
+}
+
+impl CapturedOutputConfig {
+    /// Returns the most output kept from each of a test's standard output and standard error, in
+    /// bytes.
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    /// Returns the directory the full output of tests whose output was cut down is written to, if
+    /// it's written out.
+    pub fn full_output_dir(&self) -> Option<&Utf8Path> {
+        self.full_output_dir.as_deref()
+    }
+}
+
+/// Type for the test-order config key.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TestOrder {
+    /// Start tests in the order they're listed in.
+    Listing,
+
+    /// Start the tests that took the longest the last time they were run first, followed by tests
+    /// that haven't been timed in the order they're listed in.
+    SlowestFirst,
+}
+
+/// Type for the retry-schedule config key.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RetrySchedule {
+    /// Retry failed tests as soon as they fail.
+    Immediate,
+
+    /// Retry failed tests once every test has been run once, with as many tests running at a time
+    /// as in the rest of the run.
+    Deferred,
+
+    /// Retry failed tests once every test has been run once, one test at a time.
+    DeferredSerial,
+}
+
+/// Type for the jobserver config key.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum JobserverMode {
+    /// Don't take part in a jobserver.
+    Off,
+
+    /// Take a token from an inherited jobserver for each running test, if a jobserver was
+    /// inherited.
+    Inherit,
+
+    /// Like [`Self::Inherit`], but if no jobserver was inherited, act as a jobserver with one
+    /// token per test thread. Test processes and the tools they run can take tokens from it too.
+    Provide,
+}
+
+/// A step in terminating a test that timed out: a signal to send, and how long to wait for the
+/// test to exit afterwards.
+///
+/// Returned by [`NextestProfile::terminate_signals`].
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct TerminateStep {
+    signal: TerminateSignal,
+    #[serde(with = "humantime_serde")]
+    wait: Duration,
+}
+
+impl TerminateStep {
+    /// Returns the signal to send.
+    pub fn signal(&self) -> TerminateSignal {
+        self.signal
+    }
+
+    /// Returns how long to wait for the test to exit before moving on to the next step.
+    pub fn wait(&self) -> Duration {
+        self.wait
+    }
+}
+
+/// A signal that can be sent to tests that are terminated, through `terminate-signals`.
+///
+/// Signals can be written with or without the `SIG` prefix. `SIGKILL` isn't included, since it's
+/// always sent once every other signal has been.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+pub enum TerminateSignal {
+    /// `SIGHUP`.
+    #[serde(rename = "SIGHUP", alias = "HUP")]
+    Hup,
+    /// `SIGINT`.
+    #[serde(rename = "SIGINT", alias = "INT")]
+    Int,
+    /// `SIGQUIT`.
+    #[serde(rename = "SIGQUIT", alias = "QUIT")]
+    Quit,
+    /// `SIGABRT`.
+    #[serde(rename = "SIGABRT", alias = "ABRT")]
+    Abrt,
+    /// `SIGUSR1`.
+    #[serde(rename = "SIGUSR1", alias = "USR1")]
+    Usr1,
+    /// `SIGUSR2`.
+    #[serde(rename = "SIGUSR2", alias = "USR2")]
+    Usr2,
+    /// `SIGALRM`.
+    #[serde(rename = "SIGALRM", alias = "ALRM")]
+    Alrm,
+    /// `SIGTERM`.
+    #[serde(rename = "SIGTERM", alias = "TERM")]
+    Term,
+}
+
+/// Type for the stdin config key.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum StdinMode {
+    /// Give tests an empty standard input, like `/dev/null`.
+    Null,
+
+    /// Give tests nextest's own standard input.
+    Inherit,
+
+    /// Give tests the contents of a file as standard input.
+    File(Utf8PathBuf),
+
+    /// Give tests a fixed string as standard input.
+    String(String),
+}
+
+impl StdinMode {
+    fn relative_to(&self, workspace_root: &Utf8Path) -> Self {
+        match self {
+            Self::File(path) => Self::File(workspace_root.join(path)),
+            other => other.clone(),
+        }
+    }
+}
+
+/// Type for the leaked-processes config key.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LeakedProcessPolicy {
+    /// Don't look for processes left running by tests.
+    Ignore,
+
+    /// Report processes left running by tests.
+    Warn,
+
+    /// Report processes left running by tests, and kill them.
+    Kill,
+
+    /// Report processes left running by tests, and mark tests that passed as failed.
+    Fail,
 }
 
 /// Type for the test-threads config key.
@@ -598,55 +1650,216 @@ impl<'de> Deserialize<'de> for TestThreads {
     }
 }
 
-/// Type for the slow-timeout config key.
+/// Type for the threads-required override key.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ThreadsRequired {
+    /// The test counts as a specified number of test threads.
+    Count(usize),
+
+    /// The test counts as a number of test threads equal to the logical CPU count.
+    NumCpus,
+
+    /// The test counts as all test threads, so it runs on its own.
+    NumTestThreads,
+}
+
+impl ThreadsRequired {
+    /// Gets the actual number of test threads the test counts as, given the number of test
+    /// threads.
+    ///
+    /// This is always at least 1, and never more than `test_threads`.
+    pub fn compute(self, test_threads: usize) -> usize {
+        let threads = match self {
+            Self::Count(threads) => threads,
+            Self::NumCpus => num_cpus::get(),
+            Self::NumTestThreads => test_threads,
+        };
+        threads.clamp(1, test_threads.max(1))
+    }
+}
+
+impl<'de> Deserialize<'de> for ThreadsRequired {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct V;
+
+        impl<'de2> serde::de::Visitor<'de2> for V {
+            type Value = ThreadsRequired;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(
+                    formatter,
+                    "a positive integer or the string \"num-cpus\" or \"num-test-threads\""
+                )
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                match v {
+                    "num-cpus" => Ok(ThreadsRequired::NumCpus),
+                    "num-test-threads" => Ok(ThreadsRequired::NumTestThreads),
+                    _ => Err(serde::de::Error::invalid_value(
+                        serde::de::Unexpected::Str(v),
+                        &self,
+                    )),
+                }
+            }
+
+            // Note that TOML uses i64, not u64.
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                if v > 0 {
+                    Ok(ThreadsRequired::Count(v as usize))
+                } else {
+                    Err(serde::de::Error::invalid_value(
+                        serde::de::Unexpected::Signed(v),
+                        &self,
+                    ))
+                }
+            }
+        }
+
+        deserializer.deserialize_any(V)
+    }
+}
+
+/// Type for the retries config key.
+///
+/// In configuration, this is either a number of retries, which are attempted right away, or a table
+/// with a `backoff` key.
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "kebab-case")]
-pub struct SlowTimeout {
-    #[serde(with = "humantime_serde")]
-    pub(crate) period: Duration,
-    #[serde(default)]
-    pub(crate) terminate_after: Option<NonZeroUsize>,
+#[serde(tag = "backoff", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum RetryPolicy {
+    /// Each retry is attempted after the same delay.
+    #[serde(rename_all = "kebab-case")]
+    Fixed {
+        /// The number of times a failing test is retried.
+        count: usize,
+
+        /// The delay before each retry.
+        #[serde(default, with = "humantime_serde")]
+        delay: Duration,
+
+        /// Whether delays are randomized, to between half of and the full delay.
+        #[serde(default)]
+        jitter: bool,
+
+        /// The longest time retries of a test can take, counted from when its first attempt
+        /// finished. A retry isn't attempted if its delay would take it past this.
+        #[serde(default, with = "humantime_serde::option")]
+        max_retry_time: Option<Duration>,
+    },
+
+    /// The delay before each retry is twice the delay before the one before it.
+    #[serde(rename_all = "kebab-case")]
+    Exponential {
+        /// The number of times a failing test is retried.
+        count: usize,
+
+        /// The delay before the first retry.
+        #[serde(with = "humantime_serde")]
+        delay: Duration,
+
+        /// Whether delays are randomized, to between half of and the full delay.
+        #[serde(default)]
+        jitter: bool,
+
+        /// The longest delay before a retry, if any.
+        #[serde(default, with = "humantime_serde::option")]
+        max_delay: Option<Duration>,
+
+        /// The longest time retries of a test can take, counted from when its first attempt
+        /// finished. A retry isn't attempted if its delay would take it past this.
+        #[serde(default, with = "humantime_serde::option")]
+        max_retry_time: Option<Duration>,
+    },
 }
 
-fn require_deserialize_slow_timeout<'de, D>(deserializer: D) -> Result<SlowTimeout, D::Error>
-where
-    D: serde::Deserializer<'de>,
-{
-    match deserialize_slow_timeout(deserializer) {
-        Ok(None) => Err(serde::de::Error::missing_field("field missing or null")),
-        Err(e) => Err(e),
-        Ok(Some(st)) => Ok(st),
+impl RetryPolicy {
+    /// Creates a policy that retries failing tests `count` times, right away.
+    pub fn new_without_delay(count: usize) -> Self {
+        Self::Fixed {
+            count,
+            delay: Duration::ZERO,
+            jitter: false,
+            max_retry_time: None,
+        }
+    }
+
+    /// Returns the number of times a failing test is retried.
+    pub fn count(&self) -> usize {
+        match self {
+            Self::Fixed { count, .. } | Self::Exponential { count, .. } => *count,
+        }
+    }
+
+    /// Returns the delay before retry number `retry`, counting from 1, before jitter is applied.
+    pub fn delay(&self, retry: usize) -> Duration {
+        match *self {
+            Self::Fixed { delay, .. } => delay,
+            Self::Exponential {
+                delay, max_delay, ..
+            } => {
+                let exponent = u32::try_from(retry.saturating_sub(1)).unwrap_or(u32::MAX);
+                let delay = delay.saturating_mul(2u32.saturating_pow(exponent));
+                match max_delay {
+                    Some(max_delay) => delay.min(max_delay),
+                    None => delay,
+                }
+            }
+        }
+    }
+
+    /// Returns true if delays are randomized.
+    pub fn jitter(&self) -> bool {
+        match self {
+            Self::Fixed { jitter, .. } | Self::Exponential { jitter, .. } => *jitter,
+        }
+    }
+
+    /// Returns the longest time retries of a test can take, if any.
+    pub fn max_retry_time(&self) -> Option<Duration> {
+        match self {
+            Self::Fixed { max_retry_time, .. } | Self::Exponential { max_retry_time, .. } => {
+                *max_retry_time
+            }
+        }
     }
 }
 
-fn deserialize_slow_timeout<'de, D>(deserializer: D) -> Result<Option<SlowTimeout>, D::Error>
+fn deserialize_retry_policy<'de, D>(deserializer: D) -> Result<RetryPolicy, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
     struct V;
 
     impl<'de2> serde::de::Visitor<'de2> for V {
-        type Value = Option<SlowTimeout>;
+        type Value = RetryPolicy;
 
         fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
             write!(
                 formatter,
-                "a table ({{ period = \"60s\", terminate-after = 2 }}) or a string (\"60s\")"
+                "a table ({{ backoff = \"fixed\", count = 2, delay = \"1s\" }}) or a number of retries"
             )
         }
 
-        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        // Note that TOML uses i64, not u64.
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
         where
             E: serde::de::Error,
         {
-            if v.is_empty() {
-                Ok(None)
-            } else {
-                let period = humantime_serde::deserialize(v.into_deserializer())?;
-                Ok(Some(SlowTimeout {
-                    period,
-                    terminate_after: None,
-                }))
+            match usize::try_from(v) {
+                Ok(count) => Ok(RetryPolicy::new_without_delay(count)),
+                Err(_) => Err(serde::de::Error::invalid_value(
+                    serde::de::Unexpected::Signed(v),
+                    &self,
+                )),
             }
         }
 
@@ -654,81 +1867,448 @@ where
         where
             A: serde::de::MapAccess<'de2>,
         {
-            SlowTimeout::deserialize(serde::de::value::MapAccessDeserializer::new(map)).map(Some)
+            RetryPolicy::deserialize(serde::de::value::MapAccessDeserializer::new(map))
         }
     }
 
     deserializer.deserialize_any(V)
 }
 
-#[derive(Clone, Debug, Deserialize)]
-#[serde(rename_all = "kebab-case")]
-struct DefaultJunitImpl {
-    #[serde(default)]
-    path: Option<Utf8PathBuf>,
-    report_name: String,
+fn deserialize_optional_retry_policy<'de, D>(
+    deserializer: D,
+) -> Result<Option<RetryPolicy>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    deserialize_retry_policy(deserializer).map(Some)
 }
 
+/// Type for the retry-if config key.
+///
+/// A failed attempt is retried if it matches any of the conditions set.
 #[derive(Clone, Debug, Deserialize)]
-#[serde(rename_all = "kebab-case")]
-struct CustomProfileImpl {
-    #[serde(default)]
-    retries: Option<usize>,
-    #[serde(default)]
-    test_threads: Option<TestThreads>,
-    #[serde(default)]
-    status_level: Option<StatusLevel>,
-    #[serde(default)]
-    final_status_level: Option<FinalStatusLevel>,
-    #[serde(default)]
-    failure_output: Option<TestOutputDisplay>,
-    #[serde(default)]
-    success_output: Option<TestOutputDisplay>,
-    #[serde(default)]
-    fail_fast: Option<bool>,
-    #[serde(default, deserialize_with = "deserialize_slow_timeout")]
-    slow_timeout: Option<SlowTimeout>,
-    #[serde(default, with = "humantime_serde::option")]
-    leak_timeout: Option<Duration>,
-    #[serde(default)]
-    overrides: Vec<ProfileOverrideSource>,
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RetryCondition {
+    // Matched against standard output and standard error.
+    #[serde(default, deserialize_with = "deserialize_output_patterns")]
+    output: Vec<regex::bytes::Regex>,
     #[serde(default)]
-    junit: JunitImpl,
+    exit_codes: Vec<i32>,
+    // Signal names without the SIG prefix.
+    #[serde(default, deserialize_with = "deserialize_signal_names")]
+    signals: Vec<&'static str>,
 }
 
-/// Pre-compiled form of profile overrides.
-#[derive(Clone, Debug, Deserialize)]
-#[serde(rename_all = "kebab-case")]
-struct ProfileOverrideSource {
-    /// The filter expression to match against.
-    filter: String,
-    /// Overrides.
-    #[serde(flatten)]
-    data: ProfileOverrideData,
-}
+impl RetryCondition {
+    /// Returns true if the failed attempt `status` should be retried.
+    pub fn matches(&self, status: &ExecuteStatus) -> bool {
+        if status
+            .exit_code
+            .map_or(false, |code| self.exit_codes.contains(&code))
+        {
+            return true;
+        }
+        #[cfg(unix)]
+        {
+            use crate::runner::{AbortStatus, ExecutionResult};
 
-#[derive(Clone, Debug, Deserialize)]
-#[serde(rename_all = "kebab-case")]
-struct ProfileOverrideData {
-    #[serde(default)]
-    retries: Option<usize>,
-    #[serde(default, deserialize_with = "deserialize_slow_timeout")]
-    slow_timeout: Option<SlowTimeout>,
-    #[serde(default)]
-    leak_timeout: Option<Duration>,
+            if let ExecutionResult::Fail {
+                abort_status: Some(AbortStatus::UnixSignal(signal)),
+                ..
+            } = status.result
+            {
+                let name = crate::helpers::signal_str(signal);
+                if name.map_or(false, |name| self.signals.contains(&name)) {
+                    return true;
+                }
+            }
+        }
+        self.output
+            .iter()
+            .any(|regex| regex.is_match(&status.stdout) || regex.is_match(&status.stderr))
+    }
 }
 
-#[derive(Clone, Debug, Default)]
-struct NextestOverridesImpl {
-    default: Vec<ProfileOverrideImpl>,
-    other: HashMap<String, Vec<ProfileOverrideImpl>>,
+fn deserialize_output_patterns<'de, D>(
+    deserializer: D,
+) -> Result<Vec<regex::bytes::Regex>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|pattern| regex::bytes::Regex::new(pattern).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+fn deserialize_optional_output_patterns<'de, D>(
+    deserializer: D,
+) -> Result<Option<Vec<regex::bytes::Regex>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    deserialize_output_patterns(deserializer).map(Some)
+}
+
+fn deserialize_signal_names<'de, D>(deserializer: D) -> Result<Vec<&'static str>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|name| {
+            let bare_name = name.strip_prefix("SIG").unwrap_or(name);
+            crate::helpers::SIGNALS
+                .iter()
+                .find(|(_, signal_name)| *signal_name == bare_name)
+                .map(|(_, signal_name)| *signal_name)
+                .ok_or_else(|| {
+                    serde::de::Error::invalid_value(
+                        serde::de::Unexpected::Str(name),
+                        &"a signal name such as \"SIGKILL\"",
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Type for the slow-timeout config key.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct SlowTimeout {
+    #[serde(with = "humantime_serde")]
+    pub(crate) period: Duration,
+    #[serde(default)]
+    pub(crate) terminate_after: Option<NonZeroUsize>,
+}
+
+fn require_deserialize_slow_timeout<'de, D>(deserializer: D) -> Result<SlowTimeout, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match deserialize_slow_timeout(deserializer) {
+        Ok(None) => Err(serde::de::Error::missing_field("field missing or null")),
+        Err(e) => Err(e),
+        Ok(Some(st)) => Ok(st),
+    }
+}
+
+fn deserialize_optional_size<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    deserialize_size(deserializer).map(Some)
+}
+
+fn deserialize_nice<'de, D>(deserializer: D) -> Result<Option<i32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let nice = i32::deserialize(deserializer)?;
+    if (-20..=19).contains(&nice) {
+        Ok(Some(nice))
+    } else {
+        Err(serde::de::Error::invalid_value(
+            serde::de::Unexpected::Signed(nice.into()),
+            &"a nice value between -20 and 19",
+        ))
+    }
+}
+
+/// Deserializes a pass rate, given either as a fraction between 0 and 1 or as a percentage string
+/// such as "98%".
+fn deserialize_pass_rate<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct V;
+
+    impl<'de2> serde::de::Visitor<'de2> for V {
+        type Value = f64;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(
+                formatter,
+                "a fraction between 0 and 1, or a percentage such as \"98%\""
+            )
+        }
+
+        fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            if (0.0..=1.0).contains(&v) {
+                Ok(v)
+            } else {
+                Err(serde::de::Error::invalid_value(
+                    serde::de::Unexpected::Float(v),
+                    &self,
+                ))
+            }
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            match v {
+                0 | 1 => Ok(v as f64),
+                _ => Err(serde::de::Error::invalid_value(
+                    serde::de::Unexpected::Signed(v),
+                    &self,
+                )),
+            }
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            let percent = v
+                .strip_suffix('%')
+                .and_then(|percent| percent.trim().parse::<f64>().ok())
+                .filter(|percent| (0.0..=100.0).contains(percent));
+            match percent {
+                Some(percent) => Ok(percent / 100.0),
+                None => Err(serde::de::Error::invalid_value(
+                    serde::de::Unexpected::Str(v),
+                    &self,
+                )),
+            }
+        }
+    }
+
+    deserializer.deserialize_any(V).map(Some)
+}
+
+fn deserialize_slow_timeout<'de, D>(deserializer: D) -> Result<Option<SlowTimeout>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct V;
+
+    impl<'de2> serde::de::Visitor<'de2> for V {
+        type Value = Option<SlowTimeout>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(
+                formatter,
+                "a table ({{ period = \"60s\", terminate-after = 2 }}) or a string (\"60s\")"
+            )
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            if v.is_empty() {
+                Ok(None)
+            } else {
+                let period = humantime_serde::deserialize(v.into_deserializer())?;
+                Ok(Some(SlowTimeout {
+                    period,
+                    terminate_after: None,
+                }))
+            }
+        }
+
+        fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+        where
+            A: serde::de::MapAccess<'de2>,
+        {
+            SlowTimeout::deserialize(serde::de::value::MapAccessDeserializer::new(map)).map(Some)
+        }
+    }
+
+    deserializer.deserialize_any(V)
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct DefaultJunitImpl {
+    #[serde(default)]
+    path: Option<Utf8PathBuf>,
+    report_name: String,
+    store_success_output: bool,
+    store_failure_output: bool,
+    #[serde(default, deserialize_with = "deserialize_optional_size")]
+    max_output_size: Option<u64>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct DefaultHtmlReportImpl {
+    #[serde(default)]
+    path: Option<Utf8PathBuf>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct DefaultTimelineImpl {
+    #[serde(default)]
+    path: Option<Utf8PathBuf>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct DefaultArchiveImpl {
+    compression: ArchiveCompression,
+    debuginfo: ArchiveDebuginfo,
+    #[serde(default)]
+    include: Vec<ArchiveInclude>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct CustomProfileImpl {
+    #[serde(default, deserialize_with = "deserialize_optional_retry_policy")]
+    retries: Option<RetryPolicy>,
+    #[serde(default)]
+    retry_if: Option<RetryCondition>,
+    #[serde(default)]
+    retry_schedule: Option<RetrySchedule>,
+    #[serde(default)]
+    check_failures_in_isolation: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_optional_output_patterns")]
+    determinism_ignore: Option<Vec<regex::bytes::Regex>>,
+    #[serde(default)]
+    strict_xfail: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_pass_rate")]
+    required_pass_rate: Option<f64>,
+    #[serde(default)]
+    test_threads: Option<TestThreads>,
+    #[serde(default)]
+    status_level: Option<StatusLevel>,
+    #[serde(default)]
+    final_status_level: Option<FinalStatusLevel>,
+    #[serde(default)]
+    failure_output: Option<TestOutputDisplay>,
+    #[serde(default)]
+    success_output: Option<TestOutputDisplay>,
+    #[serde(default)]
+    fail_fast: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_slow_timeout")]
+    slow_timeout: Option<SlowTimeout>,
+    #[serde(default, with = "humantime_serde::option")]
+    leak_timeout: Option<Duration>,
+    #[serde(default, with = "humantime_serde::option")]
+    terminate_grace_period: Option<Duration>,
+    #[serde(default)]
+    terminate_signals: Option<Vec<TerminateStep>>,
+    #[serde(default, with = "humantime_serde::option")]
+    no_output_timeout: Option<Duration>,
+    #[serde(default)]
+    leaked_processes: Option<LeakedProcessPolicy>,
+    #[serde(default)]
+    list_cache: Option<bool>,
+    #[serde(default)]
+    test_order: Option<TestOrder>,
+    #[serde(default)]
+    jobserver: Option<JobserverMode>,
+    #[serde(default, deserialize_with = "deserialize_optional_size")]
+    memory_limit: Option<u64>,
+    #[serde(default)]
+    pin_cpus: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_nice")]
+    nice: Option<i32>,
+    #[serde(default)]
+    network_isolation: Option<bool>,
+    #[serde(default)]
+    stdin: Option<StdinMode>,
+    #[serde(default)]
+    clean_env: Option<bool>,
+    #[serde(default)]
+    env_allowlist: Option<Vec<String>>,
+    #[serde(default)]
+    overrides: Vec<ProfileOverrideSource>,
+    #[serde(default)]
+    junit: JunitImpl,
+    #[serde(default)]
+    html_report: HtmlReportImpl,
+    #[serde(default)]
+    timeline: TimelineImpl,
+    #[serde(default)]
+    archive: ArchiveImpl,
+    #[serde(default)]
+    adaptive_threads: AdaptiveThreadsImpl,
+    #[serde(default)]
+    test_tmpdir: TestTmpdirImpl,
+    #[serde(default)]
+    captured_output: CapturedOutputImpl,
+}
+
+/// Pre-compiled form of profile overrides.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ProfileOverrideSource {
+    /// The filter expression to match against.
+    filter: String,
+    /// Overrides.
+    #[serde(flatten)]
+    data: ProfileOverrideData,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ProfileOverrideData {
+    #[serde(default, deserialize_with = "deserialize_optional_retry_policy")]
+    retries: Option<RetryPolicy>,
+    #[serde(default)]
+    retry_if: Option<RetryCondition>,
+    #[serde(default, deserialize_with = "deserialize_slow_timeout")]
+    slow_timeout: Option<SlowTimeout>,
+    #[serde(default)]
+    leak_timeout: Option<Duration>,
+    #[serde(default, with = "humantime_serde::option")]
+    terminate_grace_period: Option<Duration>,
+    #[serde(default)]
+    terminate_signals: Option<Vec<TerminateStep>>,
+    #[serde(default, with = "humantime_serde::option")]
+    no_output_timeout: Option<Duration>,
+    #[serde(default)]
+    single_test: Option<bool>,
+    #[serde(default)]
+    test_group: Option<String>,
+    #[serde(default)]
+    resources: Option<BTreeMap<String, usize>>,
+    #[serde(default)]
+    threads_required: Option<ThreadsRequired>,
+    #[serde(default, deserialize_with = "deserialize_optional_size")]
+    memory_limit: Option<u64>,
+    #[serde(default)]
+    pin_cpus: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_nice")]
+    nice: Option<i32>,
+    #[serde(default)]
+    network_isolation: Option<bool>,
+    #[serde(default)]
+    working_directory: Option<Utf8PathBuf>,
+    #[serde(default)]
+    stdin: Option<StdinMode>,
+    #[serde(default)]
+    expected_failure: Option<bool>,
+    #[serde(default)]
+    quarantine: Option<bool>,
+    #[serde(default)]
+    live_output: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct NextestOverridesImpl {
+    default: Vec<ProfileOverrideImpl>,
+    other: HashMap<String, Vec<ProfileOverrideImpl>>,
 }
 
 impl NextestOverridesImpl {
-    fn new(graph: &PackageGraph, config: &NextestConfigImpl) -> Result<Self, ConfigParseErrorKind> {
+    fn new(
+        graph: &PackageGraph,
+        cx: &ParseContext<'_>,
+        config: &NextestConfigImpl,
+    ) -> Result<Self, ConfigParseErrorKind> {
         let mut errors = vec![];
         let default = Self::compile_overrides(
             graph,
+            cx,
             "default",
             &config.profiles.default.overrides,
             &mut errors,
@@ -740,7 +2320,13 @@ impl NextestOverridesImpl {
             .map(|(profile_name, profile)| {
                 (
                     profile_name.clone(),
-                    Self::compile_overrides(graph, profile_name, &profile.overrides, &mut errors),
+                    Self::compile_overrides(
+                        graph,
+                        cx,
+                        profile_name,
+                        &profile.overrides,
+                        &mut errors,
+                    ),
                 )
             })
             .collect();
@@ -754,13 +2340,14 @@ impl NextestOverridesImpl {
 
     fn compile_overrides(
         graph: &PackageGraph,
+        cx: &ParseContext<'_>,
         profile_name: &str,
         overrides: &[ProfileOverrideSource],
         errors: &mut Vec<ConfigParseOverrideError>,
     ) -> Vec<ProfileOverrideImpl> {
         overrides
             .iter()
-            .filter_map(|source| ProfileOverrideImpl::new(graph, profile_name, source, errors))
+            .filter_map(|source| ProfileOverrideImpl::new(graph, cx, profile_name, source, errors))
             .collect()
     }
 }
@@ -774,11 +2361,12 @@ struct ProfileOverrideImpl {
 impl ProfileOverrideImpl {
     fn new(
         graph: &PackageGraph,
+        cx: &ParseContext<'_>,
         profile_name: &str,
         source: &ProfileOverrideSource,
         errors: &mut Vec<ConfigParseOverrideError>,
     ) -> Option<Self> {
-        match FilteringExpr::parse(&source.filter, graph) {
+        match FilteringExpr::parse_with_context(&source.filter, graph, cx) {
             Ok(expr) => Some(Self {
                 expr,
                 data: source.data.clone(),
@@ -800,24 +2388,123 @@ struct JunitImpl {
     #[serde(default)]
     path: Option<Utf8PathBuf>,
     report_name: Option<String>,
+    #[serde(default)]
+    store_success_output: Option<bool>,
+    #[serde(default)]
+    store_failure_output: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_optional_size")]
+    max_output_size: Option<u64>,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use guppy::{graph::cargo::BuildPlatform, MetadataCommand};
-    use indoc::indoc;
-    use nextest_filtering::BinaryQuery;
-    use std::{io::Write, path::PathBuf, process::Command};
-    use tempfile::tempdir;
-    use test_case::test_case;
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct HtmlReportImpl {
+    #[serde(default)]
+    path: Option<Utf8PathBuf>,
+}
 
-    #[test]
-    fn default_config_is_valid() {
-        let default_config = NextestConfig::default_config("foo");
-        default_config
-            .profile(NextestConfig::DEFAULT_PROFILE)
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct TimelineImpl {
+    #[serde(default)]
+    path: Option<Utf8PathBuf>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ArchiveImpl {
+    #[serde(default)]
+    compression: Option<ArchiveCompression>,
+    #[serde(default)]
+    debuginfo: Option<ArchiveDebuginfo>,
+    #[serde(default)]
+    include: Option<Vec<ArchiveInclude>>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct DefaultAdaptiveThreadsImpl {
+    enabled: bool,
+    min_threads: usize,
+    max_load: f64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct AdaptiveThreadsImpl {
+    #[serde(default)]
+    enabled: Option<bool>,
+    #[serde(default)]
+    min_threads: Option<usize>,
+    #[serde(default)]
+    max_load: Option<f64>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct DefaultTestTmpdirImpl {
+    enabled: bool,
+    keep_on_failure: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct TestTmpdirImpl {
+    #[serde(default)]
+    enabled: Option<bool>,
+    #[serde(default)]
+    keep_on_failure: Option<bool>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct DefaultCapturedOutputImpl {
+    #[serde(default, deserialize_with = "deserialize_optional_size")]
+    max_size: Option<u64>,
+    store_full_output: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct CapturedOutputImpl {
+    #[serde(default, deserialize_with = "deserialize_optional_size")]
+    max_size: Option<u64>,
+    #[serde(default)]
+    store_full_output: Option<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::ExecutionResult;
+    use guppy::{graph::cargo::BuildPlatform, MetadataCommand};
+    use indoc::indoc;
+    use maplit::btreemap;
+    use nextest_filtering::BinaryQuery;
+    use std::{io::Write, path::PathBuf, process::Command};
+    use tempfile::tempdir;
+    use test_case::test_case;
+
+    #[test]
+    fn default_config_is_valid() {
+        let default_config = NextestConfig::default_config("foo");
+        let profile = default_config
+            .profile(NextestConfig::DEFAULT_PROFILE)
             .expect("default profile should exist");
+        assert_eq!(
+            profile.archive_compression(),
+            ArchiveCompression::default(),
+            "default archive compression should match ArchiveCompression::default()",
+        );
+        assert_eq!(
+            profile.archive_debuginfo(),
+            ArchiveDebuginfo::default(),
+            "default archive debuginfo should match ArchiveDebuginfo::default()",
+        );
+        assert!(
+            default_config.macos_resign(),
+            "extracted binaries are re-signed on macOS by default"
+        );
     }
 
     #[test_case(
@@ -1025,115 +2712,1281 @@ mod tests {
         "#},
         Some(3)
 
-        ; "no overrides match my_test exactly"
-    )]
+        ; "no overrides match my_test exactly"
+    )]
+
+    fn overrides_retries(config_contents: &str, retries: Option<usize>) {
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
+
+        let graph = temp_workspace(workspace_path, config_contents);
+        let package_id = graph.workspace().iter().next().unwrap().id();
+
+        let config =
+            NextestConfig::from_sources(graph.workspace().root(), &graph, None, []).unwrap();
+        let query = TestQuery {
+            binary_query: BinaryQuery {
+                package_id,
+                binary_id: "my-package",
+                kind: "lib",
+                features: &[],
+                binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
+                platform: BuildPlatform::Target,
+            },
+            test_name: "my_test",
+        };
+        let overrides_for = config
+            .profile("ci")
+            .expect("ci profile is defined")
+            .overrides_for(&query);
+        assert_eq!(
+            overrides_for.retries().map(|retries| retries.count()),
+            retries,
+            "actual retries don't match expected retries"
+        );
+    }
+
+    #[test_case(
+        indoc! {r#"
+            [[profile.default.overrides]]
+            filter = "binary(my-binary)"
+            single-test = true
+        "#},
+        true
+
+        ; "binary matches"
+    )]
+    #[test_case(
+        indoc! {r#"
+            [[profile.default.overrides]]
+            filter = "binary(other-binary)"
+            single-test = true
+        "#},
+        false
+
+        ; "binary does not match"
+    )]
+    #[test_case(
+        indoc! {r#"
+            [[profile.default.overrides]]
+            filter = "binary(my-binary) & test(my_test)"
+            single-test = true
+        "#},
+        false
+
+        ; "filter depends on test names"
+    )]
+    #[test_case(
+        indoc! {r#"
+            [[profile.default.overrides]]
+            filter = "binary(my-binary)"
+            single-test = false
+
+            [[profile.default.overrides]]
+            filter = "all()"
+            single-test = true
+        "#},
+        false
+
+        ; "first matching override wins"
+    )]
+    fn overrides_single_test(config_contents: &str, single_test: bool) {
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
+
+        let graph = temp_workspace(workspace_path, config_contents);
+        let package_id = graph.workspace().iter().next().unwrap().id();
+
+        let config =
+            NextestConfig::from_sources(graph.workspace().root(), &graph, None, []).unwrap();
+        let query = BinaryQuery {
+            package_id,
+            binary_id: "my-package::my-binary",
+            kind: "test",
+            features: &[],
+            binary_name: "my-binary",
+            binary_path: Utf8Path::new("/fake/binary"),
+            platform: BuildPlatform::Target,
+        };
+        assert_eq!(
+            config
+                .profile(NextestConfig::DEFAULT_PROFILE)
+                .expect("default profile is defined")
+                .single_test_for(&query),
+            single_test,
+            "actual single-test doesn't match expected single-test"
+        );
+    }
+
+    #[test]
+    fn parse_tool_config_file() {
+        cfg_if::cfg_if! {
+            if #[cfg(windows)] {
+                let valid = ["tool:C:\\foo\\bar", "tool:\\\\?\\C:\\foo\\bar"];
+                let invalid = ["C:\\foo\\bar", "tool:\\foo\\bar", "tool:", ":/foo/bar"];
+            } else {
+                let valid = ["tool:/foo/bar"];
+                let invalid = ["/foo/bar", "tool:", ":/foo/bar", "tool:foo/bar"];
+            }
+        }
+
+        for valid_input in valid {
+            valid_input.parse::<ToolConfigFile>().unwrap_or_else(|err| {
+                panic!("valid input {valid_input} should parse correctly: {err}")
+            });
+        }
+
+        for invalid_input in invalid {
+            invalid_input
+                .parse::<ToolConfigFile>()
+                .expect_err(&format!("invalid input {invalid_input} should error out"));
+        }
+    }
+
+    #[test]
+    fn lowpri_config() {
+        let config_contents = r#"
+        [profile.default]
+        retries = 3
+
+        [[profile.default.overrides]]
+        filter = 'test(test_foo)'
+        retries = 20
+        "#;
+
+        let lowpri1_config_contents = r#"
+        [profile.default]
+        retries = 4
+
+        [[profile.default.overrides]]
+        filter = 'test(test_bar)'
+        retries = 21
+
+        [profile.lowpri]
+        retries = 12
+
+        [[profile.lowpri.overrides]]
+        filter = 'test(test_baz)'
+        retries = 22
+        "#;
+
+        let lowpri2_config_contents = r#"
+        [profile.default]
+        retries = 5
+
+        [[profile.default.overrides]]
+        filter = 'test(test_)'
+        retries = 23
+
+        [profile.lowpri]
+        retries = 16
+
+        [[profile.lowpri.overrides]]
+        filter = 'test(test_ba)'
+        retries = 24
+
+        [[profile.lowpri.overrides]]
+        filter = 'test(test_)'
+        retries = 25
+
+        [profile.lowpri2]
+        retries = 18
+
+        [[profile.lowpri2.overrides]]
+        filter = 'all()'
+        retries = 26
+        "#;
+
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
+
+        let graph = temp_workspace(workspace_path, config_contents);
+        let workspace_root = graph.workspace().root();
+        let lowpri1_path = workspace_root.join(".config/lowpri1.toml");
+        let lowpri2_path = workspace_root.join(".config/lowpri2.toml");
+        std::fs::write(&lowpri1_path, lowpri1_config_contents).unwrap();
+        std::fs::write(&lowpri2_path, lowpri2_config_contents).unwrap();
+
+        let config = NextestConfig::from_sources(
+            workspace_root,
+            &graph,
+            None,
+            &[
+                ToolConfigFile {
+                    tool: "lowpri1".to_owned(),
+                    config_file: lowpri1_path,
+                },
+                ToolConfigFile {
+                    tool: "lowpri2".to_owned(),
+                    config_file: lowpri2_path,
+                },
+            ],
+        )
+        .expect("parsing config failed");
+
+        let default_profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile is present");
+        // This is present in .config/nextest.toml and is the highest priority
+        assert_eq!(default_profile.retries().count(), 3);
+
+        let package_id = graph.workspace().iter().next().unwrap().id();
+
+        let test_foo_query = TestQuery {
+            binary_query: BinaryQuery {
+                package_id,
+                binary_id: "my-package",
+                kind: "lib",
+                features: &[],
+                binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
+                platform: BuildPlatform::Target,
+            },
+            test_name: "test_foo",
+        };
+        let test_bar_query = TestQuery {
+            binary_query: BinaryQuery {
+                package_id,
+                binary_id: "my-package",
+                kind: "lib",
+                features: &[],
+                binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
+                platform: BuildPlatform::Target,
+            },
+            test_name: "test_bar",
+        };
+        let test_baz_query = TestQuery {
+            binary_query: BinaryQuery {
+                package_id,
+                binary_id: "my-package",
+                kind: "lib",
+                features: &[],
+                binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
+                platform: BuildPlatform::Target,
+            },
+            test_name: "test_baz",
+        };
+
+        assert_eq!(
+            default_profile.overrides_for(&test_foo_query).retries(),
+            Some(RetryPolicy::new_without_delay(20)),
+            "retries for test_foo/default profile"
+        );
+        assert_eq!(
+            default_profile.overrides_for(&test_bar_query).retries(),
+            Some(RetryPolicy::new_without_delay(21)),
+            "retries for test_bar/default profile"
+        );
+        assert_eq!(
+            default_profile.overrides_for(&test_baz_query).retries(),
+            Some(RetryPolicy::new_without_delay(23)),
+            "retries for test_baz/default profile"
+        );
+
+        let lowpri_profile = config.profile("lowpri").expect("lowpri profile is present");
+        assert_eq!(lowpri_profile.retries().count(), 12);
+        assert_eq!(
+            lowpri_profile.overrides_for(&test_foo_query).retries(),
+            Some(RetryPolicy::new_without_delay(25)),
+            "retries for test_foo/default profile"
+        );
+        assert_eq!(
+            lowpri_profile.overrides_for(&test_bar_query).retries(),
+            Some(RetryPolicy::new_without_delay(24)),
+            "retries for test_bar/default profile"
+        );
+        assert_eq!(
+            lowpri_profile.overrides_for(&test_baz_query).retries(),
+            Some(RetryPolicy::new_without_delay(22)),
+            "retries for test_baz/default profile"
+        );
+
+        let lowpri2_profile = config
+            .profile("lowpri2")
+            .expect("lowpri2 profile is present");
+        assert_eq!(lowpri2_profile.retries().count(), 18);
+        assert_eq!(
+            lowpri2_profile.overrides_for(&test_foo_query).retries(),
+            Some(RetryPolicy::new_without_delay(26)),
+            "retries for test_foo/default profile"
+        );
+        assert_eq!(
+            lowpri2_profile.overrides_for(&test_bar_query).retries(),
+            Some(RetryPolicy::new_without_delay(26)),
+            "retries for test_bar/default profile"
+        );
+        assert_eq!(
+            lowpri2_profile.overrides_for(&test_baz_query).retries(),
+            Some(RetryPolicy::new_without_delay(26)),
+            "retries for test_baz/default profile"
+        );
+    }
+
+    #[test]
+    fn path_remap_config() {
+        let config_contents = r#"
+        [reuse-build]
+        path-remap = ["/build/vendor=../vendor", "/build=build"]
+        "#;
+
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
+
+        let graph = temp_workspace(workspace_path, config_contents);
+        let workspace_root = graph.workspace().root();
+        let config = NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect("config is valid");
+
+        assert_eq!(
+            config.path_remaps(),
+            vec![
+                PathRemap::new("/build/vendor", workspace_root.join("../vendor")),
+                PathRemap::new("/build", workspace_root.join("build")),
+            ],
+            "path remaps are in order, with relative paths resolved against the workspace root"
+        );
+
+        assert_eq!(
+            NextestConfig::default_config(workspace_root).path_remaps(),
+            vec![],
+            "default config has no path remaps"
+        );
+    }
+
+    #[test]
+    fn filterset_aliases() {
+        let config_contents = r#"
+        [filterset-aliases]
+        my-tests = "alias(base) & test(my_)"
+
+        [[profile.default.overrides]]
+        filter = "alias(my-tests)"
+        retries = 3
+        "#;
+
+        let tool_config_contents = r#"
+        [filterset-aliases]
+        base = "kind(lib)"
+        unused = "none()"
+        "#;
+
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
+
+        let graph = temp_workspace(workspace_path, config_contents);
+        let workspace_root = graph.workspace().root();
+        let tool_path = workspace_root.join(".config/tool.toml");
+        std::fs::write(&tool_path, tool_config_contents).unwrap();
+
+        // Aliases in the main config can refer to aliases defined by tools.
+        let tool_config_files = [ToolConfigFile {
+            tool: "my-tool".to_owned(),
+            config_file: tool_path.clone(),
+        }];
+        let config = NextestConfig::from_sources(workspace_root, &graph, None, &tool_config_files)
+            .expect("parsing config failed");
+
+        let package_id = graph.workspace().iter().next().unwrap().id();
+        let make_query = |kind, test_name| TestQuery {
+            binary_query: BinaryQuery {
+                package_id,
+                binary_id: "my-package",
+                kind,
+                features: &[],
+                binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
+                platform: BuildPlatform::Target,
+            },
+            test_name,
+        };
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile is present");
+        assert_eq!(
+            profile
+                .overrides_for(&make_query("lib", "my_test"))
+                .retries(),
+            Some(RetryPolicy::new_without_delay(3))
+        );
+        assert_eq!(
+            profile
+                .overrides_for(&make_query("test", "my_test"))
+                .retries(),
+            None
+        );
+
+        // Errors in aliases are attributed to the file the alias is defined in.
+        std::fs::write(&tool_path, "[filterset-aliases]\nbase = \"alias(base)\"\n").unwrap();
+        let err = NextestConfig::from_sources(workspace_root, &graph, None, &tool_config_files)
+            .expect_err("alias cycle is an error");
+        assert_eq!(err.config_file(), tool_path);
+        match err.kind() {
+            ConfigParseErrorKind::FiltersetAliasError(errors) => {
+                assert_eq!(errors.len(), 1, "one alias has errors: {errors:?}");
+                assert_eq!(errors[0].name, "base");
+            }
+            other => panic!("expected alias error, found {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_groups() {
+        let config_contents = r#"
+        [[profile.default.overrides]]
+        filter = "test(db_)"
+        test-group = "database"
+
+        [profile.other]
+        [[profile.other.overrides]]
+        filter = "test(db_)"
+        test-group = "serial"
+        "#;
+
+        let tool_config_contents = r#"
+        [test-groups]
+        database = { max-threads = 2 }
+        serial = { max-threads = 1 }
+        "#;
+
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
+
+        let graph = temp_workspace(workspace_path, config_contents);
+        let workspace_root = graph.workspace().root();
+        let tool_path = workspace_root.join(".config/tool.toml");
+        std::fs::write(&tool_path, tool_config_contents).unwrap();
+
+        // Overrides in the main config can refer to test groups defined by tools.
+        let tool_config_files = [ToolConfigFile {
+            tool: "my-tool".to_owned(),
+            config_file: tool_path.clone(),
+        }];
+        let config = NextestConfig::from_sources(workspace_root, &graph, None, &tool_config_files)
+            .expect("parsing config failed");
+
+        let package_id = graph.workspace().iter().next().unwrap().id();
+        let make_query = |test_name| TestQuery {
+            binary_query: BinaryQuery {
+                package_id,
+                binary_id: "my-package",
+                kind: "lib",
+                features: &[],
+                binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
+                platform: BuildPlatform::Target,
+            },
+            test_name,
+        };
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile is present");
+        assert_eq!(
+            profile.test_groups()["database"].max_threads(),
+            TestThreads::Count(2)
+        );
+        assert_eq!(
+            profile.overrides_for(&make_query("db_test")).test_group(),
+            Some("database")
+        );
+        assert_eq!(
+            profile
+                .overrides_for(&make_query("other_test"))
+                .test_group(),
+            None
+        );
+        let other_profile = config.profile("other").expect("other profile is present");
+        assert_eq!(
+            other_profile
+                .overrides_for(&make_query("db_test"))
+                .test_group(),
+            Some("serial"),
+            "overrides for this profile take precedence"
+        );
+
+        // Unknown test groups are errors.
+        std::fs::write(
+            &tool_path,
+            "[test-groups]\ndatabase = { max-threads = 2 }\n",
+        )
+        .unwrap();
+        let err = NextestConfig::from_sources(workspace_root, &graph, None, &tool_config_files)
+            .expect_err("unknown test group is an error");
+        assert_eq!(
+            err.config_file(),
+            workspace_root.join(".config/nextest.toml")
+        );
+        match err.kind() {
+            ConfigParseErrorKind::UnknownTestGroup {
+                profile_name,
+                name,
+                known_groups,
+            } => {
+                assert_eq!(profile_name, "other");
+                assert_eq!(name, "serial");
+                assert_eq!(known_groups, &["database".to_owned()]);
+            }
+            other => panic!("expected unknown test group error, found {other:?}"),
+        }
+
+        // So are test groups that can't run any tests.
+        std::fs::write(
+            &tool_path,
+            "[test-groups]\ndatabase = { max-threads = 0 }\nserial = { max-threads = 1 }\n",
+        )
+        .unwrap();
+        let err = NextestConfig::from_sources(workspace_root, &graph, None, &tool_config_files)
+            .expect_err("max-threads = 0 is an error");
+        assert_eq!(err.config_file(), tool_path);
+        assert!(
+            matches!(
+                err.kind(),
+                ConfigParseErrorKind::InvalidTestGroupThreads { name } if name == "database"
+            ),
+            "expected invalid test group error, found {:?}",
+            err.kind()
+        );
+    }
+
+    #[test]
+    fn resources() {
+        let config_contents = r#"
+        [resources]
+        gpu = 2
+        license-seat = 1
+
+        [[profile.default.overrides]]
+        filter = "test(gpu_)"
+        resources = { gpu = 1, license-seat = 1 }
+
+        [[profile.default.overrides]]
+        filter = "test(_gpus)"
+        resources = { gpu = 2 }
+        "#;
+
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
+
+        let graph = temp_workspace(workspace_path, config_contents);
+        let workspace_root = graph.workspace().root();
+        let config = NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect("parsing config failed");
+
+        let package_id = graph.workspace().iter().next().unwrap().id();
+        let make_query = |test_name| TestQuery {
+            binary_query: BinaryQuery {
+                package_id,
+                binary_id: "my-package",
+                kind: "lib",
+                features: &[],
+                binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
+                platform: BuildPlatform::Target,
+            },
+            test_name,
+        };
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile is present");
+        assert_eq!(
+            profile.resources(),
+            &btreemap! { "gpu".to_owned() => 2, "license-seat".to_owned() => 1 }
+        );
+        assert_eq!(
+            profile.overrides_for(&make_query("gpu_test")).resources(),
+            Some(&btreemap! { "gpu".to_owned() => 1, "license-seat".to_owned() => 1 })
+        );
+        assert_eq!(
+            profile
+                .overrides_for(&make_query("gpu_test_two_gpus"))
+                .resources(),
+            Some(&btreemap! { "gpu".to_owned() => 1, "license-seat".to_owned() => 1 }),
+            "the first matching override wins"
+        );
+        assert_eq!(
+            profile.overrides_for(&make_query("other_test")).resources(),
+            None
+        );
+
+        let invalid = [
+            ("[resources]\n\"my gpu\" = 1\n", "invalid name"),
+            ("[resources]\ngpu = 0\n", "no tokens"),
+            (
+                "[resources]\ngpu = 1\n[[profile.default.overrides]]\n\
+                 filter = \"all()\"\nresources = { gpu = 2 }\n",
+                "too many tokens requested",
+            ),
+            (
+                "[[profile.default.overrides]]\nfilter = \"all()\"\nresources = { gpu = 1 }\n",
+                "unknown resource",
+            ),
+        ];
+        let config_path = workspace_root.join(".config/nextest.toml");
+        for (config_contents, description) in invalid {
+            std::fs::write(&config_path, config_contents).unwrap();
+            let err = NextestConfig::from_sources(workspace_root, &graph, None, &[])
+                .expect_err("config is invalid");
+            let matches = match err.kind() {
+                ConfigParseErrorKind::InvalidResourceName { name } => name == "my gpu",
+                ConfigParseErrorKind::InvalidResourceCount { name } => name == "gpu",
+                ConfigParseErrorKind::ResourceRequestTooLarge {
+                    requested,
+                    available,
+                    ..
+                } => (*requested, *available) == (2, 1),
+                ConfigParseErrorKind::UnknownResource { name, .. } => name == "gpu",
+                _ => false,
+            };
+            assert!(matches, "{description}: unexpected error {:?}", err.kind());
+        }
+    }
+
+    #[test]
+    fn threads_required() {
+        let config_contents = r#"
+        [[profile.default.overrides]]
+        filter = "test(heavy_)"
+        threads-required = 4
+
+        [[profile.default.overrides]]
+        filter = "test(machine_)"
+        threads-required = "num-test-threads"
+
+        [[profile.default.overrides]]
+        filter = "test(cpus_)"
+        threads-required = "num-cpus"
+        "#;
+
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
+
+        let graph = temp_workspace(workspace_path, config_contents);
+        let workspace_root = graph.workspace().root();
+        let config = NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect("parsing config failed");
+
+        let package_id = graph.workspace().iter().next().unwrap().id();
+        let make_query = |test_name| TestQuery {
+            binary_query: BinaryQuery {
+                package_id,
+                binary_id: "my-package",
+                kind: "lib",
+                features: &[],
+                binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
+                platform: BuildPlatform::Target,
+            },
+            test_name,
+        };
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile is present");
+        let threads_required = |test_name| {
+            profile
+                .overrides_for(&make_query(test_name))
+                .threads_required()
+        };
+        assert_eq!(
+            threads_required("heavy_test"),
+            Some(ThreadsRequired::Count(4))
+        );
+        assert_eq!(
+            threads_required("machine_test"),
+            Some(ThreadsRequired::NumTestThreads)
+        );
+        assert_eq!(
+            threads_required("cpus_test"),
+            Some(ThreadsRequired::NumCpus)
+        );
+        assert_eq!(threads_required("other_test"), None);
+
+        // The number of threads is clamped to the number of test threads.
+        assert_eq!(ThreadsRequired::Count(4).compute(8), 4);
+        assert_eq!(ThreadsRequired::Count(4).compute(2), 2);
+        assert_eq!(ThreadsRequired::NumTestThreads.compute(6), 6);
+
+        let config_path = workspace_root.join(".config/nextest.toml");
+        std::fs::write(
+            &config_path,
+            "[[profile.default.overrides]]\nfilter = \"all()\"\nthreads-required = 0\n",
+        )
+        .unwrap();
+        NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect_err("threads-required must be positive");
+    }
+
+    #[test]
+    fn memory_limit() {
+        let config_contents = r#"
+        [profile.default]
+        memory-limit = "4GiB"
+
+        [[profile.default.overrides]]
+        filter = "test(big_)"
+        memory-limit = "8GB"
+
+        [[profile.default.overrides]]
+        filter = "test(small_)"
+        memory-limit = 1048576
+
+        [profile.unlimited]
+        "#;
+
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
+
+        let graph = temp_workspace(workspace_path, config_contents);
+        let workspace_root = graph.workspace().root();
+        let config = NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect("parsing config failed");
+
+        let package_id = graph.workspace().iter().next().unwrap().id();
+        let make_query = |test_name| TestQuery {
+            binary_query: BinaryQuery {
+                package_id,
+                binary_id: "my-package",
+                kind: "lib",
+                features: &[],
+                binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
+                platform: BuildPlatform::Target,
+            },
+            test_name,
+        };
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile is present");
+        assert_eq!(profile.memory_limit(), Some(4 * 1024 * 1024 * 1024));
+        let memory_limit = |test_name| profile.overrides_for(&make_query(test_name)).memory_limit();
+        assert_eq!(memory_limit("big_test"), Some(8_000_000_000));
+        assert_eq!(memory_limit("small_test"), Some(1024 * 1024));
+        assert_eq!(memory_limit("other_test"), None);
+
+        // Custom profiles inherit the limit from the default profile.
+        let profile = config.profile("unlimited").expect("profile is present");
+        assert_eq!(profile.memory_limit(), Some(4 * 1024 * 1024 * 1024));
+
+        let config_path = workspace_root.join(".config/nextest.toml");
+        std::fs::write(&config_path, "[profile.default]\nmemory-limit = \"lots\"\n").unwrap();
+        NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect_err("memory-limit must be a size");
+    }
+
+    #[test]
+    fn pin_cpus() {
+        let config_contents = r#"
+        [[profile.default.overrides]]
+        filter = "test(bench_)"
+        pin-cpus = true
+
+        [profile.pinned]
+        pin-cpus = true
+
+        [[profile.pinned.overrides]]
+        filter = "test(unpinned_)"
+        pin-cpus = false
+        "#;
+
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
+
+        let graph = temp_workspace(workspace_path, config_contents);
+        let workspace_root = graph.workspace().root();
+        let config = NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect("parsing config failed");
+
+        let package_id = graph.workspace().iter().next().unwrap().id();
+        let make_query = |test_name| TestQuery {
+            binary_query: BinaryQuery {
+                package_id,
+                binary_id: "my-package",
+                kind: "lib",
+                features: &[],
+                binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
+                platform: BuildPlatform::Target,
+            },
+            test_name,
+        };
+
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile is present");
+        assert!(!profile.pin_cpus());
+        let pin_cpus = |test_name| profile.overrides_for(&make_query(test_name)).pin_cpus();
+        assert_eq!(pin_cpus("bench_test"), Some(true));
+        assert_eq!(pin_cpus("other_test"), None);
+
+        let profile = config.profile("pinned").expect("profile is present");
+        assert!(profile.pin_cpus());
+        let pin_cpus = |test_name| profile.overrides_for(&make_query(test_name)).pin_cpus();
+        assert_eq!(pin_cpus("unpinned_test"), Some(false));
+        assert_eq!(pin_cpus("bench_test"), Some(true));
+    }
+
+    #[test]
+    fn expected_failure_and_quarantine() {
+        let config_contents = r#"
+        [[profile.default.overrides]]
+        filter = "test(known_bug_)"
+        expected-failure = true
+
+        [[profile.default.overrides]]
+        filter = "test(flaky_)"
+        quarantine = true
+
+        [[profile.default.overrides]]
+        filter = "test(serve_)"
+        live-output = true
+
+        [profile.ci]
+        strict-xfail = true
+        "#;
+
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
+
+        let graph = temp_workspace(workspace_path, config_contents);
+        let workspace_root = graph.workspace().root();
+        let config = NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect("parsing config failed");
+
+        let package_id = graph.workspace().iter().next().unwrap().id();
+        let make_query = |test_name| TestQuery {
+            binary_query: BinaryQuery {
+                package_id,
+                binary_id: "my-package",
+                kind: "lib",
+                features: &[],
+                binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
+                platform: BuildPlatform::Target,
+            },
+            test_name,
+        };
+
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile is present");
+        assert!(!profile.strict_xfail());
+        let expected_failure = |test_name| {
+            profile
+                .overrides_for(&make_query(test_name))
+                .expected_failure()
+        };
+        assert_eq!(expected_failure("known_bug_overflow"), Some(true));
+        assert_eq!(expected_failure("other_test"), None);
+        let quarantine = |test_name| profile.overrides_for(&make_query(test_name)).quarantine();
+        assert_eq!(quarantine("flaky_network"), Some(true));
+        assert_eq!(quarantine("known_bug_overflow"), None);
+        let live_output = |test_name| profile.overrides_for(&make_query(test_name)).live_output();
+        assert_eq!(live_output("serve_http"), Some(true));
+        assert_eq!(live_output("flaky_network"), None);
+
+        let profile = config.profile("ci").expect("profile is present");
+        assert!(profile.strict_xfail());
+    }
+
+    #[test]
+    fn required_pass_rate() {
+        let config_contents = r#"
+        [test-groups]
+        fuzz = { max-threads = 4, required-pass-rate = "95%" }
+        serial = { max-threads = 1 }
+
+        [profile.nightly]
+        required-pass-rate = 0.98
+        "#;
+
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
+
+        let graph = temp_workspace(workspace_path, config_contents);
+        let workspace_root = graph.workspace().root();
+        let config = NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect("parsing config failed");
+
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile is present");
+        assert_eq!(profile.required_pass_rate(), None);
+        assert_eq!(
+            profile.test_groups()["fuzz"].required_pass_rate(),
+            Some(0.95)
+        );
+        assert_eq!(profile.test_groups()["serial"].required_pass_rate(), None);
+
+        let profile = config.profile("nightly").expect("profile is present");
+        assert_eq!(profile.required_pass_rate(), Some(0.98));
+
+        for invalid in ["1.5", r#""98""#, r#""120%""#] {
+            let config_contents = format!("[profile.default]\nrequired-pass-rate = {invalid}\n");
+            let workspace_dir = tempdir().unwrap();
+            let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
+            let graph = temp_workspace(workspace_path, &config_contents);
+            let workspace_root = graph.workspace().root();
+            NextestConfig::from_sources(workspace_root, &graph, None, &[])
+                .expect_err(&format!("required-pass-rate = {invalid} is invalid"));
+        }
+    }
+
+    #[test]
+    fn nice() {
+        let config_contents = r#"
+        [profile.default]
+        nice = 10
+
+        [[profile.default.overrides]]
+        filter = "test(latency_)"
+        nice = -5
+
+        [profile.ci]
+        nice = 0
+        "#;
+
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
+
+        let graph = temp_workspace(workspace_path, config_contents);
+        let workspace_root = graph.workspace().root();
+        let config = NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect("parsing config failed");
+
+        let package_id = graph.workspace().iter().next().unwrap().id();
+        let query = TestQuery {
+            binary_query: BinaryQuery {
+                package_id,
+                binary_id: "my-package",
+                kind: "lib",
+                features: &[],
+                binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
+                platform: BuildPlatform::Target,
+            },
+            test_name: "latency_test",
+        };
+
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile is present");
+        assert_eq!(profile.nice(), Some(10));
+        assert_eq!(profile.overrides_for(&query).nice(), Some(-5));
+        let profile = config.profile("ci").expect("profile is present");
+        assert_eq!(profile.nice(), Some(0));
+
+        let config_path = workspace_root.join(".config/nextest.toml");
+        std::fs::write(&config_path, "[profile.default]\nnice = 20\n").unwrap();
+        NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect_err("nice values are at most 19");
+    }
+
+    #[test]
+    fn terminate_signals() {
+        let config_contents = r#"
+        [profile.default]
+        terminate-signals = [
+            { signal = "SIGUSR1", wait = "2s" },
+            { signal = "TERM", wait = "10s" },
+        ]
+
+        [[profile.default.overrides]]
+        filter = "test(quick_)"
+        terminate-signals = []
+        "#;
+
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
+
+        let graph = temp_workspace(workspace_path, config_contents);
+        let workspace_root = graph.workspace().root();
+        let config = NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect("parsing config failed");
+
+        let package_id = graph.workspace().iter().next().unwrap().id();
+        let query = TestQuery {
+            binary_query: BinaryQuery {
+                package_id,
+                binary_id: "my-package",
+                kind: "lib",
+                features: &[],
+                binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
+                platform: BuildPlatform::Target,
+            },
+            test_name: "quick_test",
+        };
+
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile is present");
+        let steps = profile.terminate_signals().expect("signals are configured");
+        assert_eq!(
+            steps
+                .iter()
+                .map(|step| (step.signal(), step.wait()))
+                .collect::<Vec<_>>(),
+            vec![
+                (TerminateSignal::Usr1, Duration::from_secs(2)),
+                (TerminateSignal::Term, Duration::from_secs(10)),
+            ]
+        );
+        assert_eq!(
+            profile.overrides_for(&query).terminate_signals(),
+            Some(&[][..])
+        );
+
+        let config_path = workspace_root.join(".config/nextest.toml");
+        std::fs::write(
+            &config_path,
+            "[profile.default]\nterminate-signals = [{ signal = \"SIGKILL\", wait = \"1s\" }]\n",
+        )
+        .unwrap();
+        NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect_err("SIGKILL can't be configured");
+    }
+
+    #[test]
+    fn retry_policy() {
+        let config_contents = r#"
+        [profile.default]
+        retries = { backoff = "exponential", count = 4, delay = "1s", max-delay = "3s", jitter = true }
+
+        [[profile.default.overrides]]
+        filter = "test(e2e_)"
+        retries = { backoff = "fixed", count = 2, delay = "500ms", max-retry-time = "1m" }
+        "#;
+
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
+
+        let graph = temp_workspace(workspace_path, config_contents);
+        let workspace_root = graph.workspace().root();
+        let config = NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect("parsing config failed");
+
+        let package_id = graph.workspace().iter().next().unwrap().id();
+        let query = TestQuery {
+            binary_query: BinaryQuery {
+                package_id,
+                binary_id: "my-package",
+                kind: "lib",
+                features: &[],
+                binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
+                platform: BuildPlatform::Target,
+            },
+            test_name: "e2e_test",
+        };
+
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile is present");
+        let retries = profile.retries();
+        assert_eq!(retries.count(), 4);
+        assert!(retries.jitter());
+        assert_eq!(
+            (1..=4)
+                .map(|retry| retries.delay(retry))
+                .collect::<Vec<_>>(),
+            vec![
+                Duration::from_secs(1),
+                Duration::from_secs(2),
+                Duration::from_secs(3),
+                Duration::from_secs(3),
+            ]
+        );
+
+        let retries = profile
+            .overrides_for(&query)
+            .retries()
+            .expect("retries are overridden");
+        assert_eq!(
+            retries,
+            RetryPolicy::Fixed {
+                count: 2,
+                delay: Duration::from_millis(500),
+                jitter: false,
+                max_retry_time: Some(Duration::from_secs(60)),
+            }
+        );
+        assert_eq!(retries.delay(2), Duration::from_millis(500));
+
+        // Exponential backoff needs a delay to start from.
+        let config_path = workspace_root.join(".config/nextest.toml");
+        std::fs::write(
+            &config_path,
+            "[profile.default]\nretries = { backoff = \"exponential\", count = 2 }\n",
+        )
+        .unwrap();
+        NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect_err("exponential backoff without a delay is rejected");
+    }
+
+    #[test]
+    fn retry_if() {
+        let config_contents = r#"
+        [profile.default]
+        retry-if = { exit-codes = [101], signals = ["SIGKILL", "SEGV"] }
+
+        [[profile.default.overrides]]
+        filter = "test(e2e_)"
+        retry-if = { output = ["connection (refused|reset)"] }
+        "#;
 
-    fn overrides_retries(config_contents: &str, retries: Option<usize>) {
         let workspace_dir = tempdir().unwrap();
         let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
 
         let graph = temp_workspace(workspace_path, config_contents);
-        let package_id = graph.workspace().iter().next().unwrap().id();
+        let workspace_root = graph.workspace().root();
+        let config = NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect("parsing config failed");
 
-        let config =
-            NextestConfig::from_sources(graph.workspace().root(), &graph, None, []).unwrap();
+        let package_id = graph.workspace().iter().next().unwrap().id();
         let query = TestQuery {
             binary_query: BinaryQuery {
                 package_id,
+                binary_id: "my-package",
                 kind: "lib",
+                features: &[],
                 binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
                 platform: BuildPlatform::Target,
             },
-            test_name: "my_test",
+            test_name: "e2e_test",
         };
-        let overrides_for = config
-            .profile("ci")
-            .expect("ci profile is defined")
-            .overrides_for(&query);
-        assert_eq!(
-            overrides_for.retries(),
-            retries,
-            "actual retries don't match expected retries"
-        );
+        let make_status = |exit_code, stderr: &'static str| ExecuteStatus {
+            attempt: 1,
+            total_attempts: 2,
+            iteration: 1,
+            total_iterations: Some(1),
+            stdout: bytes::Bytes::new(),
+            stderr: bytes::Bytes::from(stderr),
+            output_chunks: vec![],
+            result: ExecutionResult::Fail {
+                abort_status: None,
+                leaked: false,
+            },
+            exit_code,
+            leaked_processes: vec![],
+            kept_tmpdir: None,
+            test_seed: 0,
+            start_time: std::time::SystemTime::now(),
+            time_taken: Duration::from_secs(1),
+            is_slow: false,
+        };
+
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile is present");
+        let retry_if = profile.retry_if().expect("retry-if is set");
+        assert!(retry_if.matches(&make_status(Some(101), "")));
+        assert!(!retry_if.matches(&make_status(Some(1), "connection refused")));
+
+        let overrides = profile.overrides_for(&query);
+        let retry_if = overrides.retry_if().expect("retry-if is overridden");
+        assert!(retry_if.matches(&make_status(Some(1), "error: connection reset by peer")));
+        assert!(!retry_if.matches(&make_status(Some(101), "assertion failed")));
+
+        let config_path = workspace_root.join(".config/nextest.toml");
+        std::fs::write(
+            &config_path,
+            "[profile.default]\nretry-if = { signals = [\"SIGFOO\"] }\n",
+        )
+        .unwrap();
+        NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect_err("unknown signals are rejected");
+        std::fs::write(
+            &config_path,
+            "[profile.default]\nretry-if = { output = [\"(unclosed\"] }\n",
+        )
+        .unwrap();
+        NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect_err("invalid regexes are rejected");
     }
 
     #[test]
-    fn parse_tool_config_file() {
-        cfg_if::cfg_if! {
-            if #[cfg(windows)] {
-                let valid = ["tool:C:\\foo\\bar", "tool:\\\\?\\C:\\foo\\bar"];
-                let invalid = ["C:\\foo\\bar", "tool:\\foo\\bar", "tool:", ":/foo/bar"];
-            } else {
-                let valid = ["tool:/foo/bar"];
-                let invalid = ["/foo/bar", "tool:", ":/foo/bar", "tool:foo/bar"];
-            }
-        }
+    fn retry_schedule() {
+        let config_contents = r#"
+        [profile.ci]
+        retry-schedule = "deferred-serial"
+        check-failures-in-isolation = true
+        "#;
 
-        for valid_input in valid {
-            valid_input.parse::<ToolConfigFile>().unwrap_or_else(|err| {
-                panic!("valid input {valid_input} should parse correctly: {err}")
-            });
-        }
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
 
-        for invalid_input in invalid {
-            invalid_input
-                .parse::<ToolConfigFile>()
-                .expect_err(&format!("invalid input {invalid_input} should error out"));
-        }
+        let graph = temp_workspace(workspace_path, config_contents);
+        let workspace_root = graph.workspace().root();
+        let config = NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect("parsing config failed");
+
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile is present");
+        assert_eq!(profile.retry_schedule(), RetrySchedule::Immediate);
+        assert!(!profile.check_failures_in_isolation());
+        let profile = config.profile("ci").expect("profile is present");
+        assert_eq!(profile.retry_schedule(), RetrySchedule::DeferredSerial);
+        assert!(profile.check_failures_in_isolation());
+
+        let config_path = workspace_root.join(".config/nextest.toml");
+        std::fs::write(
+            &config_path,
+            "[profile.default]\nretry-schedule = \"later\"\n",
+        )
+        .unwrap();
+        NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect_err("unknown retry schedules are rejected");
     }
 
     #[test]
-    fn lowpri_config() {
+    fn determinism_ignore() {
         let config_contents = r#"
-        [profile.default]
-        retries = 3
-
-        [[profile.default.overrides]]
-        filter = 'test(test_foo)'
-        retries = 20
+        [profile.ci]
+        determinism-ignore = ['\d+ms', 'pid \d+']
         "#;
 
-        let lowpri1_config_contents = r#"
-        [profile.default]
-        retries = 4
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
 
-        [[profile.default.overrides]]
-        filter = 'test(test_bar)'
-        retries = 21
+        let graph = temp_workspace(workspace_path, config_contents);
+        let workspace_root = graph.workspace().root();
+        let config = NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect("parsing config failed");
 
-        [profile.lowpri]
-        retries = 12
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile is present");
+        assert!(profile.determinism_ignore().is_empty());
+        let profile = config.profile("ci").expect("profile is present");
+        let patterns: Vec<_> = profile
+            .determinism_ignore()
+            .iter()
+            .map(|regex| regex.as_str())
+            .collect();
+        assert_eq!(patterns, vec!["\\d+ms", "pid \\d+"]);
 
-        [[profile.lowpri.overrides]]
-        filter = 'test(test_baz)'
-        retries = 22
-        "#;
+        let config_path = workspace_root.join(".config/nextest.toml");
+        std::fs::write(
+            &config_path,
+            "[profile.default]\ndeterminism-ignore = [\"(unclosed\"]\n",
+        )
+        .unwrap();
+        NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect_err("invalid regexes are rejected");
+    }
 
-        let lowpri2_config_contents = r#"
+    #[test]
+    fn no_output_timeout() {
+        let config_contents = r#"
         [profile.default]
-        retries = 5
+        no-output-timeout = "60s"
 
         [[profile.default.overrides]]
-        filter = 'test(test_)'
-        retries = 23
-
-        [profile.lowpri]
-        retries = 16
-
-        [[profile.lowpri.overrides]]
-        filter = 'test(test_ba)'
-        retries = 24
-
-        [[profile.lowpri.overrides]]
-        filter = 'test(test_)'
-        retries = 25
-
-        [profile.lowpri2]
-        retries = 18
-
-        [[profile.lowpri2.overrides]]
-        filter = 'all()'
-        retries = 26
+        filter = "test(quiet_)"
+        no-output-timeout = "10m"
         "#;
 
         let workspace_dir = tempdir().unwrap();
@@ -1141,117 +3994,256 @@ mod tests {
 
         let graph = temp_workspace(workspace_path, config_contents);
         let workspace_root = graph.workspace().root();
-        let lowpri1_path = workspace_root.join(".config/lowpri1.toml");
-        let lowpri2_path = workspace_root.join(".config/lowpri2.toml");
-        std::fs::write(&lowpri1_path, lowpri1_config_contents).unwrap();
-        std::fs::write(&lowpri2_path, lowpri2_config_contents).unwrap();
-
-        let config = NextestConfig::from_sources(
-            workspace_root,
-            &graph,
-            None,
-            &[
-                ToolConfigFile {
-                    tool: "lowpri1".to_owned(),
-                    config_file: lowpri1_path,
-                },
-                ToolConfigFile {
-                    tool: "lowpri2".to_owned(),
-                    config_file: lowpri2_path,
-                },
-            ],
-        )
-        .expect("parsing config failed");
-
-        let default_profile = config
-            .profile(NextestConfig::DEFAULT_PROFILE)
-            .expect("default profile is present");
-        // This is present in .config/nextest.toml and is the highest priority
-        assert_eq!(default_profile.retries(), 3);
+        let config = NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect("parsing config failed");
 
         let package_id = graph.workspace().iter().next().unwrap().id();
-
-        let test_foo_query = TestQuery {
+        let make_query = |test_name| TestQuery {
             binary_query: BinaryQuery {
                 package_id,
+                binary_id: "my-package",
                 kind: "lib",
+                features: &[],
                 binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
                 platform: BuildPlatform::Target,
             },
-            test_name: "test_foo",
+            test_name,
         };
-        let test_bar_query = TestQuery {
+
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile is present");
+        assert_eq!(profile.no_output_timeout(), Some(Duration::from_secs(60)));
+        assert_eq!(
+            profile
+                .overrides_for(&make_query("quiet_test"))
+                .no_output_timeout(),
+            Some(Duration::from_secs(600))
+        );
+        assert_eq!(
+            profile
+                .overrides_for(&make_query("other_test"))
+                .no_output_timeout(),
+            None
+        );
+    }
+
+    #[test]
+    fn working_directory() {
+        let config_contents = r#"
+        [[profile.default.overrides]]
+        filter = "test(fixture_)"
+        working-directory = "fixtures/project"
+
+        [[profile.default.overrides]]
+        filter = "test(abs_)"
+        working-directory = "/abs/dir"
+        "#;
+
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
+
+        let graph = temp_workspace(workspace_path, config_contents);
+        let workspace_root = graph.workspace().root();
+        let config = NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect("parsing config failed");
+
+        let package_id = graph.workspace().iter().next().unwrap().id();
+        let make_query = |test_name| TestQuery {
             binary_query: BinaryQuery {
                 package_id,
+                binary_id: "my-package",
                 kind: "lib",
+                features: &[],
                 binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
                 platform: BuildPlatform::Target,
             },
-            test_name: "test_bar",
+            test_name,
         };
-        let test_baz_query = TestQuery {
+
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile is present");
+        assert_eq!(
+            profile
+                .overrides_for(&make_query("fixture_test"))
+                .working_directory(),
+            Some(workspace_root.join("fixtures/project").as_path()),
+            "relative paths are resolved against the workspace root"
+        );
+        assert_eq!(
+            profile
+                .overrides_for(&make_query("abs_test"))
+                .working_directory(),
+            Some(Utf8Path::new("/abs/dir"))
+        );
+        assert_eq!(
+            profile
+                .overrides_for(&make_query("other_test"))
+                .working_directory(),
+            None
+        );
+    }
+
+    #[test]
+    fn stdin() {
+        let config_contents = r#"
+        [profile.default]
+        stdin = { string = "y\n" }
+
+        [[profile.default.overrides]]
+        filter = "test(file_)"
+        stdin = { file = "fixtures/input.txt" }
+
+        [[profile.default.overrides]]
+        filter = "test(interactive_)"
+        stdin = "inherit"
+
+        [profile.ci]
+        stdin = "null"
+        "#;
+
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
+
+        let graph = temp_workspace(workspace_path, config_contents);
+        let workspace_root = graph.workspace().root();
+        let config = NextestConfig::from_sources(workspace_root, &graph, None, &[])
+            .expect("parsing config failed");
+
+        let package_id = graph.workspace().iter().next().unwrap().id();
+        let make_query = |test_name| TestQuery {
             binary_query: BinaryQuery {
                 package_id,
+                binary_id: "my-package",
                 kind: "lib",
+                features: &[],
                 binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
                 platform: BuildPlatform::Target,
             },
-            test_name: "test_baz",
+            test_name,
         };
 
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile is present");
+        assert_eq!(profile.stdin(), StdinMode::String("y\n".to_owned()));
         assert_eq!(
-            default_profile.overrides_for(&test_foo_query).retries(),
-            Some(20),
-            "retries for test_foo/default profile"
+            profile.overrides_for(&make_query("file_test")).stdin(),
+            Some(&StdinMode::File(workspace_root.join("fixtures/input.txt"))),
+            "relative paths are resolved against the workspace root"
         );
         assert_eq!(
-            default_profile.overrides_for(&test_bar_query).retries(),
-            Some(21),
-            "retries for test_bar/default profile"
+            profile
+                .overrides_for(&make_query("interactive_test"))
+                .stdin(),
+            Some(&StdinMode::Inherit)
         );
         assert_eq!(
-            default_profile.overrides_for(&test_baz_query).retries(),
-            Some(23),
-            "retries for test_baz/default profile"
+            profile.overrides_for(&make_query("other_test")).stdin(),
+            None
         );
+        let profile = config.profile("ci").expect("profile is present");
+        assert_eq!(profile.stdin(), StdinMode::Null);
+    }
 
-        let lowpri_profile = config.profile("lowpri").expect("lowpri profile is present");
-        assert_eq!(lowpri_profile.retries(), 12);
-        assert_eq!(
-            lowpri_profile.overrides_for(&test_foo_query).retries(),
-            Some(25),
-            "retries for test_foo/default profile"
-        );
-        assert_eq!(
-            lowpri_profile.overrides_for(&test_bar_query).retries(),
-            Some(24),
-            "retries for test_bar/default profile"
-        );
-        assert_eq!(
-            lowpri_profile.overrides_for(&test_baz_query).retries(),
-            Some(22),
-            "retries for test_baz/default profile"
-        );
+    #[test]
+    fn test_tags() {
+        let config_contents = r#"
+        [test-tags]
+        name-prefix = "tag_"
 
-        let lowpri2_profile = config
-            .profile("lowpri2")
-            .expect("lowpri2 profile is present");
-        assert_eq!(lowpri2_profile.retries(), 18);
+        [[test-tags.rules]]
+        filter = "test(gpu_)"
+        tags = ["gpu"]
+
+        [[profile.default.overrides]]
+        filter = "tag(slow)"
+        retries = 2
+        "#;
+
+        let tool_config_contents = r#"
+        [[test-tags.rules]]
+        filter = "kind(test)"
+        tags = ["integration", "slow"]
+        "#;
+
+        let workspace_dir = tempdir().unwrap();
+        let workspace_path: &Utf8Path = workspace_dir.path().try_into().unwrap();
+
+        let graph = temp_workspace(workspace_path, config_contents);
+        let workspace_root = graph.workspace().root();
+        let tool_path = workspace_root.join(".config/tool.toml");
+        std::fs::write(&tool_path, tool_config_contents).unwrap();
+
+        // Tag rules from all files apply.
+        let tool_config_files = [ToolConfigFile {
+            tool: "my-tool".to_owned(),
+            config_file: tool_path.clone(),
+        }];
+        let config = NextestConfig::from_sources(workspace_root, &graph, None, &tool_config_files)
+            .expect("parsing config failed");
+
+        let package_id = graph.workspace().iter().next().unwrap().id();
+        let make_query = |kind, test_name| TestQuery {
+            binary_query: BinaryQuery {
+                package_id,
+                binary_id: "my-package",
+                kind,
+                features: &[],
+                binary_name: "my-binary",
+                binary_path: Utf8Path::new("/fake/binary"),
+                platform: BuildPlatform::Target,
+            },
+            test_name,
+        };
+        let tags_for = |kind, test_name| -> Vec<String> {
+            config
+                .test_tags()
+                .tags_for(&make_query(kind, test_name))
+                .into_iter()
+                .collect()
+        };
+        assert_eq!(tags_for("lib", "gpu_draw"), vec!["gpu"]);
         assert_eq!(
-            lowpri2_profile.overrides_for(&test_foo_query).retries(),
-            Some(26),
-            "retries for test_foo/default profile"
+            tags_for("test", "tag_network::fetch"),
+            vec!["integration", "network", "slow"]
         );
+
+        // Overrides can match against tags.
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile is present");
         assert_eq!(
-            lowpri2_profile.overrides_for(&test_bar_query).retries(),
-            Some(26),
-            "retries for test_bar/default profile"
+            profile
+                .overrides_for(&make_query("test", "fetch"))
+                .retries(),
+            Some(RetryPolicy::new_without_delay(2))
         );
         assert_eq!(
-            lowpri2_profile.overrides_for(&test_baz_query).retries(),
-            Some(26),
-            "retries for test_baz/default profile"
+            profile.overrides_for(&make_query("lib", "fetch")).retries(),
+            None
         );
+
+        // Tag rules can't match against tags themselves.
+        std::fs::write(
+            &tool_path,
+            "[[test-tags.rules]]\nfilter = \"tag(gpu)\"\ntags = [\"graphics\"]\n",
+        )
+        .unwrap();
+        let err = NextestConfig::from_sources(workspace_root, &graph, None, &tool_config_files)
+            .expect_err("tag() in a tag rule is an error");
+        assert_eq!(err.config_file(), tool_path);
+        match err.kind() {
+            ConfigParseErrorKind::TagRuleError(errors) => {
+                assert_eq!(errors.len(), 1, "one rule has errors: {errors:?}");
+                assert_eq!(errors[0].input, "tag(gpu)");
+            }
+            other => panic!("expected tag rule error, found {other:?}"),
+        }
     }
 
     fn temp_workspace(temp_dir: &Utf8Path, config_contents: &str) -> PackageGraph {
@@ -0,0 +1,227 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Pinning test processes to CPUs, for the `pin-cpus` setting.
+//!
+//! On Linux, the CPU affinity of each test process is set with `sched_setaffinity` before it runs.
+//! On Windows, the affinity is set on the job object each test is assigned to by the runner. On
+//! other platforms, pinning isn't supported and tests are run unpinned.
+
+use std::{fmt, process::Command, sync::Mutex};
+
+/// Hands out CPUs for tests to be pinned to.
+///
+/// CPUs are handed out round-robin over the CPUs nextest can run on. A CPU that's free is always
+/// preferred, so tests get dedicated CPUs as long as there are enough of them.
+#[derive(Debug)]
+pub(crate) struct CpuPinner {
+    cpus: Vec<usize>,
+    state: Mutex<PinnerState>,
+}
+
+#[derive(Debug)]
+struct PinnerState {
+    // The index into `cpus` to start looking for CPUs from next.
+    next: usize,
+    // The number of tests pinned to each CPU, indexed like `cpus`.
+    uses: Vec<usize>,
+}
+
+impl CpuPinner {
+    /// Creates a new pinner over the CPUs this process can run on.
+    pub(crate) fn new() -> Self {
+        Self::with_cpus(imp::available_cpus())
+    }
+
+    fn with_cpus(cpus: Vec<usize>) -> Self {
+        let uses = vec![0; cpus.len()];
+        Self {
+            cpus,
+            state: Mutex::new(PinnerState { next: 0, uses }),
+        }
+    }
+
+    /// Hands out `count` CPUs, or every CPU if there are fewer than that.
+    ///
+    /// Returns `None` if the CPUs this process can run on aren't known.
+    pub(crate) fn pin(&self, count: usize) -> Option<PinnedCpus<'_>> {
+        let len = self.cpus.len();
+        if len == 0 {
+            return None;
+        }
+        let count = count.clamp(1, len);
+
+        let mut state = self.state.lock().expect("pinner lock isn't poisoned");
+        // Prefer CPUs fewer tests are pinned to, then CPUs in round-robin order from `next`.
+        let mut order: Vec<_> = (0..len).map(|offset| (state.next + offset) % len).collect();
+        order.sort_by_key(|&index| state.uses[index]);
+        let mut indexes = order[..count].to_vec();
+        // Carry on from the last CPU handed out in round-robin order.
+        let last = indexes
+            .iter()
+            .copied()
+            .max_by_key(|&index| (index + len - state.next) % len)
+            .expect("at least one CPU is handed out");
+        state.next = (last + 1) % len;
+        for &index in &indexes {
+            state.uses[index] += 1;
+        }
+        indexes.sort_unstable();
+
+        Some(PinnedCpus {
+            pinner: self,
+            indexes,
+        })
+    }
+}
+
+/// CPUs handed out to a test, which are given back when this is dropped.
+#[derive(Debug)]
+pub(crate) struct PinnedCpus<'a> {
+    pinner: &'a CpuPinner,
+    // Indexes into the pinner's `cpus`.
+    indexes: Vec<usize>,
+}
+
+impl<'a> PinnedCpus<'a> {
+    /// Returns the CPUs handed out, in ascending order.
+    pub(crate) fn cpus(&self) -> impl Iterator<Item = usize> + '_ {
+        self.indexes.iter().map(|&index| self.pinner.cpus[index])
+    }
+
+    /// Pins the process started by `cmd` to these CPUs.
+    pub(crate) fn apply(&self, cmd: &mut Command) {
+        imp::set_affinity(cmd, self.cpus());
+    }
+}
+
+impl<'a> fmt::Display for PinnedCpus<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, cpu) in self.cpus().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{cpu}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Drop for PinnedCpus<'a> {
+    fn drop(&mut self) {
+        let mut state = self
+            .pinner
+            .state
+            .lock()
+            .expect("pinner lock isn't poisoned");
+        for &index in &self.indexes {
+            state.uses[index] -= 1;
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::*;
+    use std::os::unix::process::CommandExt;
+
+    const CPU_SETSIZE: usize = libc::CPU_SETSIZE as usize;
+
+    pub(super) fn available_cpus() -> Vec<usize> {
+        // SAFETY: cpu_set_t is a plain bitmask, and sched_getaffinity is passed its size.
+        unsafe {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+                return vec![];
+            }
+            (0..CPU_SETSIZE)
+                .filter(|&cpu| libc::CPU_ISSET(cpu, &set))
+                .collect()
+        }
+    }
+
+    pub(super) fn set_affinity(cmd: &mut Command, cpus: impl Iterator<Item = usize>) {
+        // SAFETY: cpu_set_t is a plain bitmask, and CPUs are only set if they fit in it.
+        let set = unsafe {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            libc::CPU_ZERO(&mut set);
+            for cpu in cpus.filter(|&cpu| cpu < CPU_SETSIZE) {
+                libc::CPU_SET(cpu, &mut set);
+            }
+            set
+        };
+        // SAFETY: sched_setaffinity is async-signal-safe, and the closure doesn't allocate.
+        unsafe {
+            cmd.pre_exec(move || {
+                if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) == 0 {
+                    Ok(())
+                } else {
+                    Err(std::io::Error::last_os_error())
+                }
+            });
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::*;
+
+    pub(super) fn available_cpus() -> Vec<usize> {
+        // Job object affinities are masks over the CPUs in one processor group.
+        let bits = usize::BITS as usize;
+        (0..num_cpus::get().min(bits)).collect()
+    }
+
+    pub(super) fn set_affinity(_cmd: &mut Command, _cpus: impl Iterator<Item = usize>) {
+        // The runner sets the affinity on the job object the test is assigned to.
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod imp {
+    use super::*;
+
+    pub(super) fn available_cpus() -> Vec<usize> {
+        // Pinning isn't supported on this platform.
+        vec![]
+    }
+
+    pub(super) fn set_affinity(_cmd: &mut Command, _cpus: impl Iterator<Item = usize>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pin_round_robin() {
+        let pinner = CpuPinner::with_cpus(vec![0, 2, 4, 6]);
+        let cpus = |pinned: &PinnedCpus<'_>| pinned.cpus().collect::<Vec<_>>();
+
+        let a = pinner.pin(1).expect("CPUs are known");
+        let b = pinner.pin(2).expect("CPUs are known");
+        assert_eq!(cpus(&a), vec![0]);
+        assert_eq!(cpus(&b), vec![2, 4]);
+        assert_eq!(b.to_string(), "2,4");
+
+        // Free CPUs are preferred over ones tests are pinned to, wrapping around.
+        drop(a);
+        let c = pinner.pin(2).expect("CPUs are known");
+        assert_eq!(cpus(&c), vec![0, 6]);
+
+        // With no free CPUs left, the least used ones are shared, continuing round-robin.
+        let d = pinner.pin(1).expect("CPUs are known");
+        assert_eq!(cpus(&d), vec![2]);
+
+        // Asking for more CPUs than there are hands out all of them.
+        let e = pinner.pin(8).expect("CPUs are known");
+        assert_eq!(cpus(&e), vec![0, 2, 4, 6]);
+    }
+
+    #[test]
+    fn pin_unknown_cpus() {
+        let pinner = CpuPinner::with_cpus(vec![]);
+        assert!(pinner.pin(1).is_none());
+    }
+}
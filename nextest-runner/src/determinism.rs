@@ -0,0 +1,115 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Checking that tests produce the same output every time they're run, for `--check-determinism`.
+//!
+//! Tests that pass are run a second time, and their standard output and standard error are compared
+//! line by line with the first run's. Parts of lines that match the `determinism-ignore` patterns,
+//! such as timestamps, are left out of the comparison, as are the times libtest reports tests took.
+
+use crate::runner::{ExecuteStatus, LineDifference, OutputDifference};
+use once_cell::sync::Lazy;
+use regex::bytes::Regex;
+use std::borrow::Cow;
+
+/// The time libtest reports tests took, at the end of its summary line.
+static LIBTEST_TIME: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"; finished in \d+(\.\d+)?s$").expect("regex is valid"));
+
+/// Compares the first run of a test that passed with its second run, and returns how they differed,
+/// if they did.
+pub(crate) fn compare_runs(
+    first: &ExecuteStatus,
+    second: &ExecuteStatus,
+    ignore: &[Regex],
+) -> Option<OutputDifference> {
+    if !second.result.is_success() {
+        return Some(OutputDifference::Failed);
+    }
+    if let Some(difference) = first_difference(&first.stdout, &second.stdout, ignore) {
+        return Some(OutputDifference::Stdout(difference));
+    }
+    first_difference(&first.stderr, &second.stderr, ignore).map(OutputDifference::Stderr)
+}
+
+/// Returns the first line that differs between `first` and `second`.
+fn first_difference(first: &[u8], second: &[u8], ignore: &[Regex]) -> Option<LineDifference> {
+    let mut first_lines = first.split(|&b| b == b'\n');
+    let mut second_lines = second.split(|&b| b == b'\n');
+    let mut line = 1;
+    loop {
+        let (first_line, second_line) = match (first_lines.next(), second_lines.next()) {
+            (None, None) => return None,
+            lines => lines,
+        };
+        if first_line.map(|line| normalize(line, ignore))
+            != second_line.map(|line| normalize(line, ignore))
+        {
+            let lossy = |line: Option<&[u8]>| {
+                line.map(|line| String::from_utf8_lossy(line).trim_end().to_owned())
+            };
+            return Some(LineDifference {
+                line,
+                first: lossy(first_line),
+                second: lossy(second_line),
+            });
+        }
+        line += 1;
+    }
+}
+
+/// Returns `line` with the parts that aren't compared removed.
+fn normalize<'a>(line: &'a [u8], ignore: &[Regex]) -> Cow<'a, [u8]> {
+    // Output written on Windows ends lines with \r\n.
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let mut line = Cow::Borrowed(line);
+    for regex in std::iter::once(&*LIBTEST_TIME).chain(ignore) {
+        let replaced = match regex.replace_all(&line, &b""[..]) {
+            Cow::Owned(replaced) => replaced,
+            // Nothing matched.
+            Cow::Borrowed(_) => continue,
+        };
+        line = Cow::Owned(replaced);
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_difference() {
+        let ignore = [Regex::new(r"pid \d+").unwrap()];
+        let first = b"running 1 test\nstarted pid 4242\ntest foo ... ok\n\n\
+                      test result: ok. 1 passed; 0 failed; finished in 0.01s\n";
+        let second = b"running 1 test\r\nstarted pid 5151\ntest foo ... ok\n\n\
+                       test result: ok. 1 passed; 0 failed; finished in 1.52s\n";
+        assert_eq!(first_difference(first, second, &ignore), None);
+
+        assert_eq!(
+            first_difference(b"a\nb 1\nc\n", b"a\nb 2\nc\n", &ignore),
+            Some(LineDifference {
+                line: 2,
+                first: Some("b 1".to_owned()),
+                second: Some("b 2".to_owned()),
+            })
+        );
+        assert_eq!(
+            first_difference(b"a\n", b"a\nextra\n", &[]),
+            Some(LineDifference {
+                line: 2,
+                first: Some("".to_owned()),
+                second: Some("extra".to_owned()),
+            })
+        );
+        assert_eq!(
+            first_difference(b"a", b"a\n", &[]),
+            Some(LineDifference {
+                line: 2,
+                first: None,
+                second: Some("".to_owned()),
+            })
+        );
+    }
+}
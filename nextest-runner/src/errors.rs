@@ -15,9 +15,10 @@ use crate::{
 use camino::{FromPathBufError, Utf8Path, Utf8PathBuf};
 use config::ConfigError;
 use itertools::Itertools;
-use nextest_filtering::errors::FilterExpressionParseErrors;
+use nextest_filtering::errors::{FilterAliasParseErrors, FilterExpressionParseErrors};
 use std::{borrow::Cow, env::JoinPathsError, fmt, process::ExitStatus};
 use thiserror::Error;
+use uuid::Uuid;
 
 /// An error that occurred while parsing the config.
 #[derive(Debug, Error)]
@@ -79,6 +80,90 @@ pub enum ConfigParseErrorKind {
     /// Errors occurred while parsing overrides.
     #[error("error parsing overrides (destructure this variant for more details)")]
     OverrideError(Vec<ConfigParseOverrideError>),
+    /// Errors occurred while parsing filterset aliases.
+    #[error("error parsing filterset aliases (destructure this variant for more details)")]
+    FiltersetAliasError(Vec<FilterAliasParseErrors>),
+    /// Errors occurred while parsing the filter expressions of test tag rules.
+    #[error("error parsing test tag rules (destructure this variant for more details)")]
+    TagRuleError(Vec<FilterExpressionParseErrors>),
+    /// A test group was defined with `max-threads` set to 0.
+    #[error("test group `{name}` must have max-threads set to at least 1")]
+    InvalidTestGroupThreads {
+        /// The name of the test group.
+        name: String,
+    },
+    /// An override assigned tests to a test group that isn't defined.
+    #[error(
+        "for profile `{profile_name}`, an override refers to unknown test group `{name}` \
+         (known groups: {})",
+        known_names_str(known_groups)
+    )]
+    UnknownTestGroup {
+        /// The name of the profile under which the override was found.
+        profile_name: String,
+
+        /// The name of the unknown test group.
+        name: String,
+
+        /// The test groups that are defined.
+        known_groups: Vec<String>,
+    },
+    /// A resource was defined with a name that can't be used in environment variables.
+    #[error(
+        "resource name `{name}` is invalid: names can only contain letters, numbers, `-` and `_`"
+    )]
+    InvalidResourceName {
+        /// The name of the resource.
+        name: String,
+    },
+    /// A resource was defined with no tokens.
+    #[error("resource `{name}` must have at least 1 token")]
+    InvalidResourceCount {
+        /// The name of the resource.
+        name: String,
+    },
+    /// An override declared that tests consume a resource that isn't defined.
+    #[error(
+        "for profile `{profile_name}`, an override refers to unknown resource `{name}` \
+         (known resources: {})",
+        known_names_str(known_resources)
+    )]
+    UnknownResource {
+        /// The name of the profile under which the override was found.
+        profile_name: String,
+
+        /// The name of the unknown resource.
+        name: String,
+
+        /// The resources that are defined.
+        known_resources: Vec<String>,
+    },
+    /// An override declared that tests consume more tokens of a resource than there are.
+    #[error(
+        "for profile `{profile_name}`, an override consumes {requested} tokens of resource \
+         `{name}`, but only {available} are available"
+    )]
+    ResourceRequestTooLarge {
+        /// The name of the profile under which the override was found.
+        profile_name: String,
+
+        /// The name of the resource.
+        name: String,
+
+        /// The number of tokens the override consumes.
+        requested: usize,
+
+        /// The number of tokens the resource has.
+        available: usize,
+    },
+}
+
+fn known_names_str(known_names: &[String]) -> String {
+    if known_names.is_empty() {
+        "(none)".to_owned()
+    } else {
+        known_names.join(", ")
+    }
 }
 
 /// An error that occurred while parsing config overrides.
@@ -258,6 +343,127 @@ impl fmt::Display for PartitionerBuilderParseError {
     }
 }
 
+/// An error that occurs while bisecting with a [`GitBisect`](crate::git::GitBisect).
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum GitBisectError {
+    /// `git` couldn't be run.
+    #[error("error running `{command}`")]
+    Exec {
+        /// The command that was run.
+        command: String,
+
+        /// The error that occurred.
+        #[source]
+        error: std::io::Error,
+    },
+
+    /// `git` exited with an error.
+    #[error("`{command}` failed: {stderr}")]
+    Failed {
+        /// The command that was run.
+        command: String,
+
+        /// The standard error of the command, with invalid UTF-8 replaced.
+        stderr: String,
+    },
+
+    /// The working tree has uncommitted changes.
+    #[error(
+        "the working tree has uncommitted changes, which would be carried over to each commit \
+         tested (commit or stash them before bisecting)"
+    )]
+    UncommittedChanges,
+}
+
+/// An error that occurs while reading a [`ShardPlan`](crate::partition::ShardPlan).
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ShardPlanError {
+    /// An error occurred while reading the plan.
+    #[error("error reading shard plan from `{path}`")]
+    Read {
+        /// The file that couldn't be read.
+        path: Utf8PathBuf,
+
+        /// The error that occurred.
+        #[source]
+        error: std::io::Error,
+    },
+
+    /// An error occurred while deserializing the plan.
+    #[error("error deserializing shard plan from `{path}`")]
+    Deserialize {
+        /// The file that couldn't be deserialized.
+        path: Utf8PathBuf,
+
+        /// The error that occurred.
+        #[source]
+        error: serde_json::Error,
+    },
+
+    /// The plan has no shards.
+    #[error("shard plan `{path}` must have at least 1 shard")]
+    NoShards {
+        /// The file the plan was read from.
+        path: Utf8PathBuf,
+    },
+
+    /// A test or filterset was assigned to a shard that isn't in the plan.
+    #[error(
+        "in shard plan `{path}`, `{entry}` is assigned to shard {shard}, \
+         but shards must be between 1 and {total_shards}"
+    )]
+    InvalidShard {
+        /// The file the plan was read from.
+        path: Utf8PathBuf,
+
+        /// The test ID or filterset.
+        entry: String,
+
+        /// The shard it was assigned to.
+        shard: u64,
+
+        /// The number of shards in the plan.
+        total_shards: u64,
+    },
+
+    /// A test ID wasn't of the form `<binary-id> <test-name>`.
+    #[error(
+        "in shard plan `{path}`, test ID `{test_id}` isn't of the form `<binary-id> <test-name>`"
+    )]
+    InvalidTestId {
+        /// The file the plan was read from.
+        path: Utf8PathBuf,
+
+        /// The invalid test ID.
+        test_id: String,
+    },
+
+    /// Filtersets in the plan failed to parse.
+    #[error("failed to parse filtersets in shard plan `{path}`")]
+    FilterParse {
+        /// The file the plan was read from.
+        path: Utf8PathBuf,
+
+        /// The errors that occurred.
+        errors: Vec<FilterExpressionParseErrors>,
+    },
+
+    /// The shard passed in isn't in the plan.
+    #[error("shard {shard} isn't in shard plan `{path}`, which has {total_shards} shards")]
+    ShardOutOfRange {
+        /// The file the plan was read from.
+        path: Utf8PathBuf,
+
+        /// The shard passed in.
+        shard: u64,
+
+        /// The number of shards in the plan.
+        total_shards: u64,
+    },
+}
+
 /// An error occurred in [`PathMapper::new`](crate::reuse_build::PathMapper::new).
 #[derive(Debug, Error)]
 pub enum PathMapperConstructError {
@@ -332,6 +538,9 @@ pub enum PathMapperConstructKind {
 
     /// The target directory.
     TargetDir,
+
+    /// The destination of a path remap rule.
+    PathRemap,
 }
 
 impl fmt::Display for PathMapperConstructKind {
@@ -339,6 +548,7 @@ impl fmt::Display for PathMapperConstructKind {
         match self {
             Self::WorkspaceRoot => write!(f, "remapped workspace root"),
             Self::TargetDir => write!(f, "remapped target directory"),
+            Self::PathRemap => write!(f, "path remap destination"),
         }
     }
 }
@@ -364,6 +574,38 @@ pub enum FromMessagesError {
         /// The name of the malformed target within the package.
         binary_name: String,
     },
+
+    /// A line in the output of `cargo test --doc` couldn't be parsed.
+    #[error("for package `{package_name}`, error parsing doctest output line `{line}`: {reason}")]
+    DoctestParse {
+        /// The name of the package doctests were being collected for.
+        package_name: String,
+        /// The line that couldn't be parsed.
+        line: String,
+        /// The reason parsing failed.
+        reason: String,
+    },
+
+    /// A doctest failed to compile.
+    #[error("doctest `{test_name}` failed to compile:\n{output}")]
+    DoctestCompileFailed {
+        /// The name of the doctest.
+        test_name: String,
+        /// The output produced by rustdoc.
+        output: String,
+    },
+
+    /// An error occurred while reading the source file for a doctest.
+    #[error("error reading source file `{path}` for doctest `{test_name}`")]
+    DoctestSourceRead {
+        /// The name of the doctest.
+        test_name: String,
+        /// The path to the source file.
+        path: Utf8PathBuf,
+        /// The underlying error.
+        #[source]
+        error: std::io::Error,
+    },
 }
 
 /// An error that occurs while parsing test list output.
@@ -534,6 +776,44 @@ pub enum WriteTestListError {
     Json(#[source] serde_json::Error),
 }
 
+/// An error that occurs while reading the results of benchmarks.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum BenchResultsError {
+    /// An error occurred while reading a directory criterion writes results to.
+    #[error("error reading benchmark results directory `{path}`")]
+    ReadDir {
+        /// The directory that couldn't be read.
+        path: Utf8PathBuf,
+
+        /// The error that occurred.
+        #[source]
+        error: std::io::Error,
+    },
+
+    /// An error occurred while reading a file written out by criterion.
+    #[error("error reading benchmark results file `{path}`")]
+    ReadFile {
+        /// The file that couldn't be read.
+        path: Utf8PathBuf,
+
+        /// The error that occurred.
+        #[source]
+        error: std::io::Error,
+    },
+
+    /// An error occurred while deserializing a file written out by criterion.
+    #[error("error deserializing benchmark results file `{path}`")]
+    Deserialize {
+        /// The file that couldn't be deserialized.
+        path: Utf8PathBuf,
+
+        /// The error that occurred.
+        #[source]
+        error: serde_json::Error,
+    },
+}
+
 /// An error occurred while configuring handles.
 ///
 /// Only relevant on Windows.
@@ -556,6 +836,10 @@ pub enum TestRunnerBuildError {
     /// An error occurred while setting up signals.
     #[error("error setting up signals")]
     SignalHandlerSetupError(#[from] SignalHandlerSetupError),
+
+    /// An error occurred while setting up a jobserver.
+    #[error("error setting up jobserver")]
+    JobserverSetup(#[source] std::io::Error),
 }
 
 /// Represents an unknown archive format.
@@ -578,6 +862,60 @@ fn supported_extensions() -> String {
         .join(", ")
 }
 
+/// An error that occurs while parsing an
+/// [`ArchiveCompression`](crate::reuse_build::ArchiveCompression) from a string.
+#[derive(Clone, Debug, Error)]
+#[error("invalid archive compression `{input}`: {message}")]
+pub struct InvalidArchiveCompression {
+    input: String,
+    message: &'static str,
+}
+
+impl InvalidArchiveCompression {
+    pub(crate) fn new(input: impl Into<String>, message: &'static str) -> Self {
+        Self {
+            input: input.into(),
+            message,
+        }
+    }
+}
+
+/// An error that occurs while parsing a [`PathRemap`](crate::reuse_build::PathRemap) from a
+/// string.
+#[derive(Clone, Debug, Error)]
+#[error("invalid path remap `{input}`: {message}")]
+pub struct InvalidPathRemap {
+    input: String,
+    message: &'static str,
+}
+
+impl InvalidPathRemap {
+    pub(crate) fn new(input: impl Into<String>, message: &'static str) -> Self {
+        Self {
+            input: input.into(),
+            message,
+        }
+    }
+}
+
+/// An error that occurs while parsing an
+/// [`ArchiveChecksum`](crate::reuse_build::ArchiveChecksum) from a string.
+#[derive(Clone, Debug, Error)]
+#[error("invalid archive checksum `{input}`: {message}")]
+pub struct InvalidArchiveChecksum {
+    input: String,
+    message: &'static str,
+}
+
+impl InvalidArchiveChecksum {
+    pub(crate) fn new(input: impl Into<String>, message: &'static str) -> Self {
+        Self {
+            input: input.into(),
+            message,
+        }
+    }
+}
+
 /// An error that occurs while archiving data.
 #[derive(Debug, Error)]
 #[non_exhaustive]
@@ -611,10 +949,80 @@ pub enum ArchiveCreateError {
         error: std::io::Error,
     },
 
+    /// The files matched by an `archive.include` entry were larger than its `max-size`.
+    #[error(
+        "files matched by archive include `{glob}` are {size} bytes in total, \
+         which is larger than max-size ({max_size} bytes)"
+    )]
+    IncludeTooLarge {
+        /// The glob for the include.
+        glob: String,
+
+        /// The total size of the matched files, in bytes.
+        size: u64,
+
+        /// The configured maximum size, in bytes.
+        max_size: u64,
+    },
+
+    /// An error occurred while reading the baseline archive for a delta archive.
+    #[error("error reading baseline archive `{path}`")]
+    BaselineRead {
+        /// The path to the baseline archive.
+        path: Utf8PathBuf,
+
+        /// The error that occurred.
+        #[source]
+        error: Box<ArchiveReadError>,
+    },
+
+    /// The baseline archive is itself a delta archive.
+    #[error("baseline archive `{0}` is a delta archive: baselines must be full archives")]
+    BaselineIsDelta(Utf8PathBuf),
+
+    /// An error occurred while creating a temporary directory to strip debug info in.
+    #[error("error creating temporary directory to strip debug info in")]
+    DebuginfoTempDirCreate(#[source] std::io::Error),
+
+    /// An error occurred while running `objcopy` to strip debug info.
+    #[error("error running `{objcopy}` to strip debug info (is binutils installed?)")]
+    ObjcopyExec {
+        /// The objcopy program that was run.
+        objcopy: String,
+
+        /// The error that occurred.
+        #[source]
+        error: std::io::Error,
+    },
+
+    /// `objcopy` failed while stripping debug info.
+    #[error("stripping debug info from `{path}` failed with {status}:\n{stderr}")]
+    ObjcopyFailed {
+        /// The file debug info was being stripped from.
+        path: Utf8PathBuf,
+
+        /// The exit status of objcopy.
+        status: ExitStatus,
+
+        /// The standard error output of objcopy.
+        stderr: String,
+    },
+
     /// An error occurred while writing data to the output file.
     #[error("error writing to archive")]
     OutputArchiveIo(#[source] std::io::Error),
 
+    /// An error occurred while writing the debuginfo archive.
+    #[error("error writing to debuginfo archive `{path}`")]
+    DebuginfoArchiveIo {
+        /// The path to the debuginfo archive.
+        path: Utf8PathBuf,
+
+        /// The error that occurred.
+        #[source]
+        error: std::io::Error,
+    },
+
     /// An error occurred in the reporter.
     #[error("error reporting archive status")]
     ReporterIo(#[source] std::io::Error),
@@ -642,10 +1050,14 @@ pub enum ArchiveReadError {
     #[error("path in archive `{}` wasn't valid UTF-8", String::from_utf8_lossy(.0))]
     NonUtf8Path(Vec<u8>),
 
-    /// A file path within the archive didn't begin with "target/".
-    #[error("path in archive `{0}` doesn't start with `target/`")]
+    /// A file path within the archive didn't begin with "target/" or "workspace/".
+    #[error("path in archive `{0}` doesn't start with `target/` or `workspace/`")]
     NoTargetPrefix(Utf8PathBuf),
 
+    /// An entry for a file in the workspace wasn't a regular file.
+    #[error("workspace path in archive `{0}` isn't a regular file")]
+    WorkspaceEntryNotFile(Utf8PathBuf),
+
     /// A file path within the archive had an invalid component within it.
     #[error("path in archive `{path}` contains an invalid component `{component}`")]
     InvalidComponent {
@@ -680,6 +1092,10 @@ pub enum ArchiveReadError {
         actual: u32,
     },
 
+    /// The format of an archive fetched from a URL couldn't be determined.
+    #[error("unknown archive format")]
+    UnknownFormat(#[source] UnknownArchiveFormat),
+
     /// A metadata file wasn't found.
     #[error("metadata file `{0}` not found in archive")]
     MetadataFileNotFound(&'static Utf8Path),
@@ -732,10 +1148,41 @@ pub enum ArchiveExtractError {
     #[error("destination `{0}` already exists")]
     DestinationExists(Utf8PathBuf),
 
+    /// An error occurred while fetching the archive from a URL.
+    #[error("error fetching archive")]
+    Fetch(#[source] ArchiveFetchError),
+
     /// An error occurred while reading the archive.
     #[error("error reading archive")]
     Read(#[source] ArchiveReadError),
 
+    /// The checksum of the archive didn't match the expected checksum.
+    ///
+    /// Files might have already been extracted to the destination directory.
+    #[error("archive checksum mismatch: expected `{expected}`, actual `{actual}`")]
+    ChecksumMismatch {
+        /// The expected checksum.
+        expected: String,
+
+        /// The actual checksum.
+        actual: String,
+    },
+
+    /// The baseline archive passed in isn't the one the delta archive was created against.
+    ///
+    /// Files might have already been extracted to the destination directory.
+    #[error(
+        "baseline archive doesn't match the one the delta archive was created against: \
+         expected checksum `{expected}`, actual `{actual}`"
+    )]
+    BaselineMismatch {
+        /// The checksum of the baseline recorded in the delta archive.
+        expected: String,
+
+        /// The actual checksum of the baseline archive.
+        actual: String,
+    },
+
     /// An error occurred while writing out a file to the destination directory.
     #[error("error writing file `{path}` to disk")]
     WriteFile {
@@ -752,6 +1199,134 @@ pub enum ArchiveExtractError {
     ReporterIo(std::io::Error),
 }
 
+/// An error that occurs while reading a list of test binaries built outside of Cargo.
+///
+/// Returned by [`ReuseBuildInfo::from_external`](crate::reuse_build::ReuseBuildInfo::from_external).
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ExternalBinariesError {
+    /// The list has a format version this version of nextest doesn't support.
+    #[error(
+        "external binary list has format version {format_version}, but this version of nextest \
+         only supports version {supported}"
+    )]
+    UnsupportedFormatVersion {
+        /// The format version in the list.
+        format_version: u32,
+
+        /// The format version supported by this version of nextest.
+        supported: u32,
+    },
+
+    /// A test binary is part of a package that isn't in the list of packages.
+    #[error(
+        "test binary `{binary_id}` is part of package `{package_id}`, which isn't in `packages`"
+    )]
+    UnknownPackage {
+        /// The binary ID.
+        binary_id: String,
+
+        /// The package ID that isn't in the list of packages.
+        package_id: String,
+    },
+
+    /// Environment variables were specified for a test binary that isn't in the list.
+    #[error("environment variables specified for `{binary_id}`, which isn't in `rust-binaries`")]
+    UnknownBinary {
+        /// The binary ID.
+        binary_id: String,
+    },
+
+    /// The package graph couldn't be constructed from the list of packages.
+    #[error("error constructing package graph from `packages`")]
+    PackageGraph(#[source] guppy::Error),
+}
+
+/// An error that occurs while fetching an archive from a URL.
+///
+/// Returned as part of [`ArchiveExtractError`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ArchiveFetchError {
+    /// The URL was invalid.
+    #[error("invalid archive URL `{url}`: {message}")]
+    InvalidUrl {
+        /// The URL.
+        url: String,
+
+        /// A message describing why the URL is invalid.
+        message: String,
+    },
+
+    /// An error occurred while making the request.
+    #[error("error fetching `{url}`")]
+    Request {
+        /// The URL that was fetched.
+        url: String,
+
+        /// The error that occurred.
+        #[source]
+        error: reqwest::Error,
+    },
+}
+
+/// An error that occurs while reading the outcome of the last test run from a
+/// [`RunStore`](crate::run_store::RunStore).
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum RunStoreReadError {
+    /// An error occurred while reading the record of the last run.
+    #[error("error reading last run from `{path}`")]
+    Read {
+        /// The file that couldn't be read.
+        path: Utf8PathBuf,
+
+        /// The error that occurred.
+        #[source]
+        error: std::io::Error,
+    },
+
+    /// An error occurred while deserializing the record of the last run.
+    #[error("error deserializing last run from `{path}`")]
+    Deserialize {
+        /// The file that couldn't be deserialized.
+        path: Utf8PathBuf,
+
+        /// The error that occurred.
+        #[source]
+        error: serde_json::Error,
+    },
+
+    /// The last run was recorded in a format version this version of nextest doesn't support.
+    #[error(
+        "last run in `{path}` has format version {format_version}, \
+         but only version {supported} is supported"
+    )]
+    UnsupportedFormatVersion {
+        /// The file the last run was recorded in.
+        path: Utf8PathBuf,
+
+        /// The format version of the record.
+        format_version: u32,
+
+        /// The supported format version.
+        supported: u32,
+    },
+
+    /// No checkpoint was found for the run being resumed.
+    #[error(
+        "no checkpoint found for run {run_id} at `{path}` \
+         (runs that finished can't be resumed, and checkpoints are kept separately for each profile)"
+    )]
+    CheckpointNotFound {
+        /// The ID of the run.
+        run_id: Uuid,
+
+        /// The path the checkpoint was expected at.
+        path: Utf8PathBuf,
+    },
+}
+
 /// An error that occurs while writing an event.
 #[derive(Debug, Error)]
 #[non_exhaustive]
@@ -953,6 +1528,17 @@ pub enum TargetRunnerError {
         error: target_spec::errors::TripleParseError,
     },
 
+    /// Failed to parse a custom target spec file.
+    #[error("failed to parse target spec file `{path}`")]
+    TargetSpecFileParse {
+        /// The path to the target spec file.
+        path: Utf8PathBuf,
+
+        /// The error that occurred parsing the file.
+        #[source]
+        error: serde_json::Error,
+    },
+
     /// Error looking up Cargo configs.
     #[error("error discovering Cargo configs")]
     CargoConfigSearchError(
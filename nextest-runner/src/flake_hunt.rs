@@ -0,0 +1,409 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Running tests over and over under adverse conditions to find flaky tests.
+//!
+//! Tests are run in rounds, each in a different shuffled order and with more tests running at a
+//! time than usual. Optionally, [`LoadGenerator`] keeps CPUs and disks busy while they run. Counting
+//! how often each test fails, and keeping the output of one failure of each kind, points at the
+//! tests that are flaky and at why.
+
+use crate::{
+    errors::WriteTestListError,
+    helpers::write_test_name,
+    list::{OutputFormat, Styles, TestInstance},
+    reporter::status_str,
+    runner::{ExecuteStatus, ExecutionStatuses},
+};
+use nextest_metadata::{
+    FlakeHuntFailureSummary, FlakeHuntRoundSummary, FlakeHuntSummary, FlakeHuntTestSummary,
+};
+use owo_colors::OwoColorize;
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    fs::File,
+    io::{self, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+};
+
+/// The most lines of each output stream shown for a failure in human-readable output.
+const MAX_HUMAN_OUTPUT_LINES: usize = 10;
+
+/// The size of each write made by I/O load threads.
+const IO_LOAD_CHUNK: usize = 1024 * 1024;
+
+/// The size files written by I/O load threads are truncated at.
+const IO_LOAD_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Records how tests did in each round.
+#[derive(Clone, Debug, Default)]
+pub struct FlakeHuntCollector {
+    rounds: Vec<FlakeHuntRoundSummary>,
+    // Keyed by (binary ID, name).
+    tests: BTreeMap<(String, String), TestRecord>,
+    // The number of tests that passed and failed in the round that's in progress.
+    current: (usize, usize),
+}
+
+#[derive(Clone, Debug, Default)]
+struct TestRecord {
+    runs: usize,
+    // Keyed by status, in the order each kind of failure first happened in.
+    failures: Vec<FlakeHuntFailureSummary>,
+}
+
+impl FlakeHuntCollector {
+    /// Creates a new, empty collector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a test that finished in the current round.
+    pub fn record(&mut self, test_instance: &TestInstance<'_>, run_statuses: &ExecutionStatuses) {
+        self.record_status(
+            &test_instance.bin_info.binary_id,
+            test_instance.name,
+            run_statuses.last_status(),
+        );
+    }
+
+    fn record_status(&mut self, binary_id: &str, name: &str, status: &ExecuteStatus) {
+        let record = self
+            .tests
+            .entry((binary_id.to_owned(), name.to_owned()))
+            .or_default();
+        record.runs += 1;
+        if status.result.is_success() {
+            self.current.0 += 1;
+            return;
+        }
+        self.current.1 += 1;
+
+        let status_str = status_str(status.result);
+        match record
+            .failures
+            .iter_mut()
+            .find(|failure| failure.status == status_str)
+        {
+            Some(failure) => failure.count += 1,
+            None => record.failures.push(FlakeHuntFailureSummary {
+                status: status_str.into_owned(),
+                count: 1,
+                stdout: String::from_utf8_lossy(&status.stdout).into_owned(),
+                stderr: String::from_utf8_lossy(&status.stderr).into_owned(),
+            }),
+        }
+    }
+
+    /// Finishes the current round, in which tests were shuffled with `seed` and `test_threads` tests
+    /// were run at a time.
+    pub fn finish_round(&mut self, seed: u64, test_threads: usize) {
+        let (passed, failed) = std::mem::take(&mut self.current);
+        self.rounds.push(FlakeHuntRoundSummary {
+            seed,
+            test_threads,
+            passed,
+            failed,
+        });
+    }
+
+    /// Returns the results of the finished rounds.
+    pub fn finish(self) -> FlakeHunt {
+        let mut always_passed = 0;
+        let mut failing = Vec::new();
+        for ((binary_id, name), mut record) in self.tests {
+            if record.failures.is_empty() {
+                always_passed += 1;
+                continue;
+            }
+            // This sort is stable, so kinds of failures that are as common as each other stay in
+            // the order they first happened in.
+            record
+                .failures
+                .sort_by_key(|failure| Reverse(failure.count));
+            failing.push(FlakeHuntTestSummary {
+                binary_id,
+                name,
+                runs: record.runs,
+                failures: record.failures.iter().map(|failure| failure.count).sum(),
+                outputs: record.failures,
+            });
+        }
+        // Tests that failed a greater share of the time come first: compare failures / runs
+        // without dividing.
+        failing.sort_by(|a, b| {
+            (b.failures * a.runs)
+                .cmp(&(a.failures * b.runs))
+                .then_with(|| (&a.binary_id, &a.name).cmp(&(&b.binary_id, &b.name)))
+        });
+
+        FlakeHunt {
+            summary: FlakeHuntSummary {
+                rounds: self.rounds,
+                failing,
+                always_passed,
+            },
+        }
+    }
+}
+
+/// How often tests failed in a flake hunt, and how.
+///
+/// Created with [`FlakeHuntCollector::finish`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FlakeHunt {
+    summary: FlakeHuntSummary,
+}
+
+impl FlakeHunt {
+    /// Returns true if any tests failed.
+    pub fn has_failures(&self) -> bool {
+        !self.summary.failing.is_empty()
+    }
+
+    /// Returns a serializable summary of the results.
+    pub fn to_summary(&self) -> FlakeHuntSummary {
+        self.summary.clone()
+    }
+
+    /// Outputs the results in the given format.
+    pub fn write(
+        &self,
+        output_format: OutputFormat,
+        writer: impl Write,
+        colorize: bool,
+    ) -> Result<(), WriteTestListError> {
+        match output_format {
+            OutputFormat::Human { verbose } => self
+                .write_human(writer, verbose, colorize)
+                .map_err(WriteTestListError::Io),
+            OutputFormat::Serializable(format) => format
+                .to_writer(&self.summary, writer)
+                .map_err(WriteTestListError::Json),
+        }
+    }
+
+    fn write_human(&self, mut writer: impl Write, verbose: bool, colorize: bool) -> io::Result<()> {
+        let mut styles = Styles::default();
+        if colorize {
+            styles.colorize();
+        }
+
+        for test in &self.summary.failing {
+            write!(writer, "{} ", test.binary_id.style(styles.binary_id))?;
+            write_test_name(&test.name, &styles, &mut writer)?;
+            writeln!(
+                writer,
+                ": failed {}/{} runs ({:.1}%)",
+                test.failures,
+                test.runs,
+                test.failures as f64 * 100.0 / test.runs as f64,
+            )?;
+            for failure in &test.outputs {
+                writeln!(
+                    writer,
+                    "    {} in {} runs, first output:",
+                    failure.status.style(styles.field),
+                    failure.count,
+                )?;
+                write_output("stdout", &failure.stdout, verbose, &mut writer)?;
+                write_output("stderr", &failure.stderr, verbose, &mut writer)?;
+            }
+        }
+
+        let runs: usize = self
+            .summary
+            .rounds
+            .iter()
+            .map(|round| round.passed + round.failed)
+            .sum();
+        writeln!(
+            writer,
+            "{} rounds, {} test runs: {} tests failed at least once, {} tests passed every time",
+            self.summary.rounds.len(),
+            runs,
+            self.summary.failing.len(),
+            self.summary.always_passed,
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Writes out the last lines of an output stream, or all of them if `verbose` is set.
+fn write_output(name: &str, output: &str, verbose: bool, mut writer: impl Write) -> io::Result<()> {
+    let lines: Vec<_> = output.lines().collect();
+    if lines.is_empty() {
+        return Ok(());
+    }
+    let shown = match verbose {
+        true => lines.len(),
+        false => lines.len().min(MAX_HUMAN_OUTPUT_LINES),
+    };
+    match shown < lines.len() {
+        true => writeln!(
+            writer,
+            "      --- {name} (last {shown} of {} lines, use --verbose to see all) ---",
+            lines.len()
+        )?,
+        false => writeln!(writer, "      --- {name} ---")?,
+    }
+    for line in &lines[lines.len() - shown..] {
+        writeln!(writer, "      {line}")?;
+    }
+    Ok(())
+}
+
+/// Keeps CPUs and disks busy until it's dropped, to make failures that depend on timing more
+/// likely.
+#[derive(Debug)]
+pub struct LoadGenerator {
+    stop: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+    // The files written by I/O load threads.
+    io_files: Vec<PathBuf>,
+}
+
+impl LoadGenerator {
+    /// Starts `cpu_threads` threads that spin, and `io_threads` threads that write files in the
+    /// system temporary directory and flush them to disk.
+    pub fn start(cpu_threads: usize, io_threads: usize) -> io::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut generator = Self {
+            stop,
+            threads: Vec::with_capacity(cpu_threads + io_threads),
+            io_files: Vec::with_capacity(io_threads),
+        };
+
+        for _ in 0..cpu_threads {
+            let stop = generator.stop.clone();
+            generator.threads.push(std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    std::hint::spin_loop();
+                }
+            }));
+        }
+        for index in 0..io_threads {
+            let path = std::env::temp_dir()
+                .join(format!("nextest-io-load-{}-{index}", std::process::id()));
+            let file = File::create(&path)?;
+            generator.io_files.push(path);
+            let stop = generator.stop.clone();
+            generator.threads.push(std::thread::spawn(move || {
+                let _ = write_until_stopped(file, &stop);
+            }));
+        }
+
+        Ok(generator)
+    }
+}
+
+fn write_until_stopped(mut file: File, stop: &AtomicBool) -> io::Result<()> {
+    let chunk = vec![0xa5; IO_LOAD_CHUNK];
+    let mut written = 0;
+    while !stop.load(Ordering::Relaxed) {
+        if written >= IO_LOAD_FILE_SIZE {
+            file.set_len(0)?;
+            io::Seek::rewind(&mut file)?;
+            written = 0;
+        }
+        file.write_all(&chunk)?;
+        file.sync_data()?;
+        written += IO_LOAD_CHUNK as u64;
+    }
+    Ok(())
+}
+
+impl Drop for LoadGenerator {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+        for path in &self.io_files {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::ExecutionResult;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_finish() {
+        let status = |result, stdout: &str| ExecuteStatus {
+            attempt: 1,
+            total_attempts: 1,
+            iteration: 1,
+            total_iterations: Some(1),
+            stdout: stdout.to_owned().into(),
+            stderr: Default::default(),
+            output_chunks: vec![],
+            result,
+            exit_code: None,
+            leaked_processes: vec![],
+            kept_tmpdir: None,
+            test_seed: 0,
+            start_time: SystemTime::UNIX_EPOCH,
+            time_taken: Duration::from_millis(10),
+            is_slow: false,
+        };
+        let fail = ExecutionResult::Fail {
+            abort_status: None,
+            leaked: false,
+        };
+
+        let mut collector = FlakeHuntCollector::new();
+        for round in 0..4 {
+            let (often, rarely) = match round {
+                0 => (fail, ExecutionResult::Timeout),
+                1 => (ExecutionResult::Timeout, ExecutionResult::Pass),
+                2 => (fail, ExecutionResult::Pass),
+                _ => (ExecutionResult::Pass, ExecutionResult::Pass),
+            };
+            collector.record_status("crate", "often", &status(often, &format!("round {round}")));
+            collector.record_status("crate", "rarely", &status(rarely, "timed out"));
+            collector.record_status("crate", "stable", &status(ExecutionResult::Pass, ""));
+            collector.finish_round(round, 8);
+        }
+
+        let summary = collector.finish().to_summary();
+        assert_eq!(summary.rounds.len(), 4);
+        assert_eq!((summary.rounds[0].passed, summary.rounds[0].failed), (1, 2));
+        assert_eq!(summary.always_passed, 1);
+
+        let failing: Vec<_> = summary
+            .failing
+            .iter()
+            .map(|test| {
+                let outputs: Vec<_> = test
+                    .outputs
+                    .iter()
+                    .map(|output| (output.status.as_str(), output.count, output.stdout.as_str()))
+                    .collect();
+                (test.name.as_str(), test.failures, test.runs, outputs)
+            })
+            .collect();
+        assert_eq!(
+            failing,
+            vec![
+                (
+                    "often",
+                    3,
+                    4,
+                    vec![("FAIL", 2, "round 0"), ("TIMEOUT", 1, "round 1")]
+                ),
+                ("rarely", 1, 4, vec![("TIMEOUT", 1, "timed out")]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,447 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Running git, to find files changed for the `since()` filter expression predicate, and to bisect
+//! for `cargo nextest bisect`.
+
+use crate::errors::GitBisectError;
+use camino::{Utf8Path, Utf8PathBuf};
+use nextest_filtering::ChangedFiles;
+use std::{
+    collections::HashMap,
+    fmt,
+    process::{Command, Output},
+    sync::Mutex,
+};
+
+/// Finds the files changed since git refs by running `git` within a workspace.
+///
+/// Changes are computed against the merge base of the ref and `HEAD`, so for a branch like
+/// `origin/main`, only changes made since the current branch diverged from it are included.
+/// Uncommitted changes and untracked files count as changed too.
+#[derive(Debug)]
+pub struct GitChangedFiles {
+    workspace_root: Utf8PathBuf,
+    cache: Mutex<HashMap<String, Result<Vec<Utf8PathBuf>, String>>>,
+}
+
+impl GitChangedFiles {
+    /// Creates a new `GitChangedFiles` for the git repository containing the workspace root.
+    ///
+    /// `git` is only run once changed files are looked up.
+    pub fn new(workspace_root: impl Into<Utf8PathBuf>) -> Self {
+        Self {
+            workspace_root: workspace_root.into(),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn compute(&self, git_ref: &str) -> Result<Vec<Utf8PathBuf>, String> {
+        if git_ref.starts_with('-') {
+            return Err("git refs can't start with `-`".to_owned());
+        }
+
+        // Paths printed out by git are relative to the top level of the repository, which might
+        // be above the workspace root. The top level is printed out with symlinks resolved, so
+        // paths within the workspace are joined to the workspace root instead, to match the paths
+        // Cargo uses.
+        let toplevel = run_git(&self.workspace_root, &["rev-parse", "--show-toplevel"])?;
+        let toplevel = Utf8PathBuf::from(toplevel.trim_end());
+        let prefix = run_git(&self.workspace_root, &["rev-parse", "--show-prefix"])?;
+        let prefix = prefix.trim_end();
+        let merge_base = run_git(&toplevel, &["merge-base", git_ref, "HEAD"])?;
+
+        let changed = run_git(
+            &toplevel,
+            &["diff", "--name-only", "-z", merge_base.trim_end()],
+        )?;
+        let untracked = run_git(
+            &toplevel,
+            &["ls-files", "--others", "--exclude-standard", "-z"],
+        )?;
+
+        let mut files: Vec<_> = changed
+            .split('\0')
+            .chain(untracked.split('\0'))
+            .filter(|path| !path.is_empty())
+            .map(|path| match path.strip_prefix(prefix) {
+                Some(path) => self.workspace_root.join(path),
+                None => toplevel.join(path),
+            })
+            .collect();
+        files.sort_unstable();
+        files.dedup();
+        Ok(files)
+    }
+}
+
+impl ChangedFiles for GitChangedFiles {
+    fn changed_files(&self, git_ref: &str) -> Result<Vec<Utf8PathBuf>, String> {
+        let mut cache = self.cache.lock().expect("lock isn't poisoned");
+        cache
+            .entry(git_ref.to_owned())
+            .or_insert_with(|| self.compute(git_ref))
+            .clone()
+    }
+}
+
+/// A `git bisect` session within a workspace, which is reset when this is dropped.
+#[derive(Debug)]
+pub struct GitBisect {
+    workspace_root: Utf8PathBuf,
+    active: bool,
+}
+
+/// Where a [`GitBisect`] is at after marking a commit.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BisectState {
+    /// Another commit has been checked out to be tested.
+    Testing,
+
+    /// The first bad commit has been found.
+    FirstBad(String),
+
+    /// Only commits that were skipped are left, and the first bad commit is one of these.
+    OnlySkipped(Vec<String>),
+}
+
+/// How a commit is marked in a [`GitBisect`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BisectVerdict {
+    /// The tests passed.
+    Good,
+
+    /// The tests failed.
+    Bad,
+
+    /// The tests couldn't be built or run.
+    Skip,
+}
+
+impl BisectVerdict {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Good => "good",
+            Self::Bad => "bad",
+            Self::Skip => "skip",
+        }
+    }
+}
+
+impl fmt::Display for BisectVerdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl GitBisect {
+    /// Starts bisecting between `bad` and `good` in the git repository containing the workspace
+    /// root, and checks out the first commit to test.
+    ///
+    /// Returns an error if tracked files have uncommitted changes, since they would be carried over
+    /// to every commit tested.
+    pub fn start(
+        workspace_root: impl Into<Utf8PathBuf>,
+        bad: &str,
+        good: &[String],
+    ) -> Result<(Self, BisectState), GitBisectError> {
+        let mut bisect = Self {
+            workspace_root: workspace_root.into(),
+            active: false,
+        };
+        // Untracked files are fine: older commits often don't ignore the target directory.
+        let status = bisect.git(&["status", "--porcelain", "--untracked-files=no"])?;
+        if !status.trim().is_empty() {
+            return Err(GitBisectError::UncommittedChanges);
+        }
+
+        let mut args = vec!["bisect", "start", bad];
+        args.extend(good.iter().map(|rev| rev.as_str()));
+        // Everything before this is a revision rather than a path.
+        args.push("--");
+        let state = bisect.bisect(&args)?;
+        bisect.active = true;
+        Ok((bisect, state))
+    }
+
+    /// Returns the full hash of the commit that's checked out.
+    pub fn head(&self) -> Result<String, GitBisectError> {
+        Ok(self.git(&["rev-parse", "HEAD"])?.trim_end().to_owned())
+    }
+
+    /// Returns the abbreviated hash and subject of `commit`.
+    pub fn describe(&self, commit: &str) -> Result<String, GitBisectError> {
+        Ok(self
+            .git(&["log", "-1", "--format=%h %s", commit])?
+            .trim_end()
+            .to_owned())
+    }
+
+    /// Marks the commit that's checked out, and checks out the next one to test if there are any
+    /// left.
+    pub fn mark(&mut self, verdict: BisectVerdict) -> Result<BisectState, GitBisectError> {
+        self.bisect(&["bisect", verdict.as_str()])
+    }
+
+    /// Ends the bisection, checking out what was checked out before it started.
+    pub fn reset(mut self) -> Result<(), GitBisectError> {
+        self.active = false;
+        self.git(&["bisect", "reset"])?;
+        Ok(())
+    }
+
+    fn bisect(&self, args: &[&str]) -> Result<BisectState, GitBisectError> {
+        let output = self.run(args)?;
+        // git exits with an error if only skipped commits are left, so look at the output first.
+        if let Some(state) = parse_bisect_output(&String::from_utf8_lossy(&output.stdout)) {
+            return Ok(state);
+        }
+        if !output.status.success() {
+            return Err(bisect_failed(args, &output));
+        }
+        Ok(BisectState::Testing)
+    }
+
+    fn git(&self, args: &[&str]) -> Result<String, GitBisectError> {
+        let output = self.run(args)?;
+        if !output.status.success() {
+            return Err(bisect_failed(args, &output));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn run(&self, args: &[&str]) -> Result<Output, GitBisectError> {
+        Command::new("git")
+            .args(args)
+            .current_dir(&self.workspace_root)
+            // The output is parsed, so make sure it isn't translated.
+            .env("LC_ALL", "C")
+            .output()
+            .map_err(|error| GitBisectError::Exec {
+                command: format!("git {}", args.join(" ")),
+                error,
+            })
+    }
+}
+
+impl Drop for GitBisect {
+    fn drop(&mut self) {
+        // Don't leave the repository partway through a bisection if testing a commit failed or was
+        // interrupted.
+        if self.active {
+            if let Err(err) = self.git(&["bisect", "reset"]) {
+                log::warn!("{err}: run `git bisect reset` to end the bisection");
+            }
+        }
+    }
+}
+
+fn bisect_failed(args: &[&str], output: &Output) -> GitBisectError {
+    GitBisectError::Failed {
+        command: format!("git {}", args.join(" ")),
+        stderr: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+    }
+}
+
+/// Looks for the end of a bisection in the output of `git bisect`.
+fn parse_bisect_output(stdout: &str) -> Option<BisectState> {
+    let mut lines = stdout.lines();
+    while let Some(line) = lines.next() {
+        if let Some(commit) = line.strip_suffix(" is the first bad commit") {
+            return Some(BisectState::FirstBad(commit.to_owned()));
+        }
+        if line == "The first bad commit could be any of:" {
+            let commits = lines
+                .take_while(|line| !line.is_empty() && !line.contains(' '))
+                .map(|line| line.to_owned())
+                .collect();
+            return Some(BisectState::OnlySkipped(commits));
+        }
+    }
+    None
+}
+
+/// Returns the commit checked out in the git repository containing `dir`, if it's in one.
+pub(crate) fn head_commit(dir: &Utf8Path) -> Option<String> {
+    let stdout = run_git(dir, &["rev-parse", "HEAD"]).ok()?;
+    Some(stdout.trim().to_owned())
+}
+
+fn run_git(dir: &Utf8Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|error| format!("error running `git`: {error}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim(),
+        ));
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|_| format!("`git {}` produced non-UTF-8 output", args.join(" ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_git_changed_files() {
+        let temp_dir = TempDir::new().expect("temp dir created");
+        let repo: &Utf8Path = temp_dir.path().try_into().expect("temp dir is valid UTF-8");
+        let git = |args: &[&str]| {
+            run_git(
+                repo,
+                &[
+                    &["-c", "user.name=test", "-c", "user.email=test@example.com"],
+                    args,
+                ]
+                .concat(),
+            )
+            .expect("git command succeeded")
+        };
+        let write = |path: &str| {
+            let path = repo.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "contents").unwrap();
+        };
+
+        git(&["init", "--quiet"]);
+        write("crate-a/src/lib.rs");
+        write("crate-b/src/lib.rs");
+        git(&["add", "."]);
+        git(&["commit", "--quiet", "-m", "initial"]);
+        git(&["branch", "base"]);
+
+        // Committed, uncommitted and untracked changes are all included.
+        write("crate-a/src/committed.rs");
+        git(&["add", "."]);
+        git(&["commit", "--quiet", "-m", "second"]);
+        std::fs::write(repo.join("crate-b/src/lib.rs"), "changed").unwrap();
+        write("untracked.txt");
+
+        let changed_files = GitChangedFiles::new(repo);
+        assert_eq!(
+            changed_files
+                .changed_files("base")
+                .expect("base is a valid ref"),
+            vec![
+                repo.join("crate-a/src/committed.rs"),
+                repo.join("crate-b/src/lib.rs"),
+                repo.join("untracked.txt"),
+            ]
+        );
+
+        // Workspaces can be within a larger repository.
+        let changed_files = GitChangedFiles::new(repo.join("crate-a"));
+        let toplevel = Utf8PathBuf::from(git(&["rev-parse", "--show-toplevel"]).trim_end());
+        let mut expected = vec![
+            toplevel.join("crate-b/src/lib.rs"),
+            toplevel.join("untracked.txt"),
+            repo.join("crate-a/src/committed.rs"),
+        ];
+        expected.sort_unstable();
+        assert_eq!(
+            changed_files
+                .changed_files("base")
+                .expect("base is a valid ref"),
+            expected
+        );
+
+        let error = changed_files
+            .changed_files("missing")
+            .expect_err("missing isn't a valid ref");
+        assert!(
+            error.starts_with("`git merge-base missing HEAD` failed"),
+            "error is about the ref: {error}"
+        );
+        changed_files
+            .changed_files("--help")
+            .expect_err("refs can't be options");
+    }
+
+    #[test]
+    fn test_git_bisect() {
+        let temp_dir = TempDir::new().expect("temp dir created");
+        let repo: &Utf8Path = temp_dir.path().try_into().expect("temp dir is valid UTF-8");
+        let git = |args: &[&str]| {
+            run_git(
+                repo,
+                &[
+                    &["-c", "user.name=test", "-c", "user.email=test@example.com"],
+                    args,
+                ]
+                .concat(),
+            )
+            .expect("git command succeeded")
+        };
+
+        git(&["init", "--quiet"]);
+        // The file contains the number of the commit, and "breaks" from commit 5 onwards.
+        let mut commits = vec![];
+        for i in 1..=8 {
+            std::fs::write(repo.join("file"), i.to_string()).unwrap();
+            git(&["add", "."]);
+            git(&["commit", "--quiet", "-m", &format!("commit {i}")]);
+            commits.push(git(&["rev-parse", "HEAD"]).trim_end().to_owned());
+        }
+
+        std::fs::write(repo.join("file"), "changed").unwrap();
+        let error = GitBisect::start(repo, "HEAD", &[commits[0].clone()])
+            .expect_err("uncommitted changes aren't allowed");
+        assert!(matches!(error, GitBisectError::UncommittedChanges));
+        git(&["checkout", "--quiet", "file"]);
+
+        let (mut bisect, mut state) =
+            GitBisect::start(repo, "HEAD", &[commits[0].clone()]).expect("bisection started");
+        let mut tested = 0;
+        while state == BisectState::Testing {
+            let contents = std::fs::read_to_string(repo.join("file")).unwrap();
+            let verdict = if contents.parse::<usize>().unwrap() >= 5 {
+                BisectVerdict::Bad
+            } else {
+                BisectVerdict::Good
+            };
+            state = bisect.mark(verdict).expect("commit marked");
+            tested += 1;
+        }
+        assert_eq!(state, BisectState::FirstBad(commits[4].clone()));
+        assert!(tested <= 3, "bisection took {tested} steps");
+        assert_eq!(
+            bisect.describe(&commits[4]).unwrap(),
+            format!("{} commit 5", &commits[4][..7])
+        );
+
+        // Dropping the bisection resets it.
+        drop(bisect);
+        assert_eq!(git(&["rev-parse", "HEAD"]).trim_end(), commits[7]);
+    }
+
+    #[test]
+    fn test_parse_bisect_output() {
+        assert_eq!(
+            parse_bisect_output(
+                "Bisecting: 2 revisions left to test after this (roughly 1 step)\n\
+                 [2b689855a7565b45fb198c8fe7fc5ea6c86364b5] Fix the parser\n"
+            ),
+            None
+        );
+        assert_eq!(
+            parse_bisect_output(
+                "There are only 'skip'ped commits left to test.\n\
+                 The first bad commit could be any of:\n\
+                 ee3367f7150fcad0acba599c3f6fe9993f63ee3d\n\
+                 00440bb4fdc5d52d962c66dea5d4bd74c25bceb5\n\
+                 We cannot bisect more!\n"
+            ),
+            Some(BisectState::OnlySkipped(vec![
+                "ee3367f7150fcad0acba599c3f6fe9993f63ee3d".to_owned(),
+                "00440bb4fdc5d52d962c66dea5d4bd74c25bceb5".to_owned(),
+            ]))
+        );
+    }
+}
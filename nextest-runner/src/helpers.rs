@@ -145,26 +145,32 @@ pub(crate) fn extract_abort_status(exit_status: ExitStatus) -> Option<AbortStatu
     }
 }
 
+/// Signal numbers along with their names, without the `SIG` prefix.
+///
+/// These signal numbers are the same on at least Linux, macOS and FreeBSD.
+pub(crate) const SIGNALS: &[(i32, &str)] = &[
+    (1, "HUP"),
+    (2, "INT"),
+    (5, "TRAP"),
+    (6, "ABRT"),
+    (8, "FPE"),
+    (9, "KILL"),
+    (11, "SEGV"),
+    (13, "PIPE"),
+    (14, "ALRM"),
+    (15, "TERM"),
+    (24, "XCPU"),
+    (25, "XFSZ"),
+    (26, "VTALRM"),
+    (27, "PROF"),
+];
+
 #[cfg(unix)]
 pub(crate) fn signal_str(signal: i32) -> Option<&'static str> {
-    // These signal numbers are the same on at least Linux, macOS and FreeBSD.
-    match signal {
-        1 => Some("HUP"),
-        2 => Some("INT"),
-        5 => Some("TRAP"),
-        6 => Some("ABRT"),
-        8 => Some("FPE"),
-        9 => Some("KILL"),
-        11 => Some("SEGV"),
-        13 => Some("PIPE"),
-        14 => Some("ALRM"),
-        15 => Some("TERM"),
-        24 => Some("XCPU"),
-        25 => Some("XFSZ"),
-        26 => Some("VTALRM"),
-        27 => Some("PROF"),
-        _ => None,
-    }
+    SIGNALS
+        .iter()
+        .find(|(number, _)| *number == signal)
+        .map(|(_, name)| *name)
 }
 
 #[cfg(windows)]
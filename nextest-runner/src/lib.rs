@@ -45,18 +45,35 @@
 //! 6. The test reporter sees events and prints them to stderr (and aggregates them if necessary
 //!    based on configs).
 
+pub mod bench;
+mod captured_output;
 pub mod cargo_config;
+mod clean_env;
 pub mod config;
+mod cpu_affinity;
+mod determinism;
 pub mod errors;
+pub mod flake_hunt;
+pub mod git;
 mod helpers;
 pub mod list;
+mod memory_limit;
+mod network_isolation;
+pub mod order_deps;
 pub mod partition;
+#[cfg(unix)]
+mod process_tree;
 pub mod reporter;
 pub mod reuse_build;
+pub mod run_store;
 pub mod runner;
 pub mod signal;
 mod stopwatch;
+mod system_load;
 pub mod target_runner;
 pub mod test_filter;
+#[cfg(test)]
+mod test_helpers;
+mod test_tmpdir;
 #[cfg(feature = "self-update")]
 pub mod update;
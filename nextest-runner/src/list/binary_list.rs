@@ -11,11 +11,11 @@ use camino::{Utf8Path, Utf8PathBuf};
 use cargo_metadata::{Artifact, BuildScript, Message, PackageId};
 use guppy::graph::PackageGraph;
 use nextest_metadata::{
-    BinaryListSummary, BuildPlatform, RustNonTestBinaryKind, RustNonTestBinarySummary,
-    RustTestBinaryKind, RustTestBinarySummary,
+    BinaryListSummary, BuildPlatform, RustDoctestSummary, RustNonTestBinaryKind,
+    RustNonTestBinarySummary, RustTestBinaryKind, RustTestBinarySummary,
 };
 use owo_colors::OwoColorize;
-use std::{fmt::Write as _, io, io::Write};
+use std::{collections::BTreeMap, fmt::Write as _, io, io::Write, ops::ControlFlow};
 
 /// A Rust test binary built by Cargo.
 #[derive(Clone, Debug)]
@@ -33,6 +33,13 @@ pub struct RustTestBinary {
     /// Platform for which this binary was built.
     /// (Proc-macro tests are built for the host.)
     pub build_platform: BuildPlatform,
+    /// For doctests, information about the doctest this binary was compiled from.
+    pub doctest: Option<RustDoctestSummary>,
+    /// The Cargo features the package was built with.
+    pub features: Vec<String>,
+    /// Extra environment variables to run this binary with. Only set for binaries built outside
+    /// of Cargo.
+    pub env: BTreeMap<String, String>,
 }
 
 /// The list of Rust test binaries built by Cargo.
@@ -51,12 +58,40 @@ impl BinaryList {
         reader: impl io::BufRead,
         graph: &PackageGraph,
         target_triple: Option<TargetTriple>,
+    ) -> Result<Self, FromMessagesError> {
+        Self::from_messages_with(reader, graph, target_triple, |_| ControlFlow::Continue(()))
+    }
+
+    /// Parses Cargo messages from the given `BufRead` and returns a list of test binaries, calling
+    /// `on_test_binary` as soon as each test binary is built.
+    ///
+    /// The list passed in to `on_test_binary` contains just the newly built binary, along with the
+    /// build metadata gathered so far. This can be used to start listing tests in a binary while
+    /// the rest of the build is still in progress.
+    ///
+    /// If `on_test_binary` returns [`ControlFlow::Break`], no more messages are read, and the test
+    /// binaries built so far are returned.
+    pub fn from_messages_with(
+        reader: impl io::BufRead,
+        graph: &PackageGraph,
+        target_triple: Option<TargetTriple>,
+        mut on_test_binary: impl FnMut(BinaryList) -> ControlFlow<()>,
     ) -> Result<Self, FromMessagesError> {
         let mut state = BinaryListBuildState::new(graph, target_triple);
 
-        for message in Message::parse_stream(reader) {
+        'messages: for message in Message::parse_stream(reader) {
             let message = message.map_err(FromMessagesError::ReadMessages)?;
+            let binary_count = state.rust_binaries.len();
             state.process_message(message)?;
+            for binary in &state.rust_binaries[binary_count..] {
+                let flow = on_test_binary(BinaryList {
+                    rust_build_meta: state.rust_build_meta.clone(),
+                    rust_binaries: vec![binary.clone()],
+                });
+                if flow.is_break() {
+                    break 'messages;
+                }
+            }
         }
 
         Ok(state.finish())
@@ -74,6 +109,9 @@ impl BinaryList {
                 kind: bin.kind,
                 id: bin.binary_id,
                 build_platform: bin.build_platform,
+                doctest: bin.doctest,
+                features: bin.features,
+                env: BTreeMap::new(),
             })
             .collect();
         Self {
@@ -82,6 +120,67 @@ impl BinaryList {
         }
     }
 
+    /// Splits this list up by target triple, for builds with more than one `--target`.
+    ///
+    /// Cargo puts the artifacts for each target triple in a subdirectory of the target directory
+    /// named after the triple. Artifacts outside of these subdirectories are built for the host
+    /// platform.
+    ///
+    /// Returns a list of the test binaries built for the host platform (e.g. proc-macro tests),
+    /// which has no target triple, along with one list per target triple. Host base output
+    /// directories, linked paths and non-test binaries are included in every list.
+    pub fn split_by_target(
+        &self,
+        target_triples: &[TargetTriple],
+    ) -> (BinaryList, Vec<BinaryList>) {
+        let target_directory = &self.rust_build_meta.target_directory;
+        // Returns the index of the target triple the artifact at this path was built for, or None
+        // if it was built for the host platform.
+        let target_index = |path: &Utf8Path| {
+            let rel_path = path.strip_prefix(target_directory).unwrap_or(path);
+            let first = rel_path.components().next()?.as_str();
+            target_triples
+                .iter()
+                .position(|triple| triple.triple == first)
+        };
+
+        // Returns the list for the given target triple index, or for the host platform if it's
+        // None.
+        let split = |index: Option<usize>| {
+            let is_included = |target: Option<usize>| target.is_none() || target == index;
+
+            let mut rust_build_meta = self.rust_build_meta.clone();
+            rust_build_meta.target_triple = index.map(|index| target_triples[index].clone());
+            rust_build_meta
+                .base_output_directories
+                .retain(|dir| is_included(target_index(dir)));
+            rust_build_meta
+                .linked_paths
+                .retain(|path, _| is_included(target_index(path)));
+            for binaries in rust_build_meta.non_test_binaries.values_mut() {
+                binaries.retain(|binary| is_included(target_index(&binary.path)));
+            }
+            rust_build_meta
+                .non_test_binaries
+                .retain(|_, binaries| !binaries.is_empty());
+
+            let rust_binaries = self
+                .rust_binaries
+                .iter()
+                .filter(|binary| target_index(&binary.path) == index)
+                .cloned()
+                .collect();
+
+            BinaryList {
+                rust_build_meta,
+                rust_binaries,
+            }
+        };
+
+        let target_lists = (0..target_triples.len()).map(|index| split(Some(index)));
+        (split(None), target_lists.collect())
+    }
+
     /// Outputs this list to the given writer.
     pub fn write(
         &self,
@@ -111,6 +210,8 @@ impl BinaryList {
                     binary_path: bin.path.clone(),
                     binary_id: bin.id.clone(),
                     build_platform: bin.build_platform,
+                    doctest: bin.doctest.clone(),
+                    features: bin.features.clone(),
                 };
                 (bin.id.clone(), summary)
             })
@@ -188,6 +289,15 @@ impl<'g> BinaryListBuildState<'g> {
     }
 
     fn process_artifact(&mut self, artifact: Artifact) -> Result<(), FromMessagesError> {
+        if self.is_build_std_artifact(&artifact) {
+            // With -Z build-std, the standard library is built like any other dependency. Its
+            // output directories must be part of the dynamic library path even if no test
+            // binaries are built into them.
+            for filename in &artifact.filenames {
+                self.detect_base_output_dir(filename);
+            }
+        }
+
         if let Some(path) = artifact.executable {
             self.detect_base_output_dir(&path);
 
@@ -255,15 +365,25 @@ impl<'g> BinaryListBuildState<'g> {
                     name,
                     id,
                     build_platform: platform,
+                    doctest: None,
+                    features: artifact.features,
+                    env: BTreeMap::new(),
                 });
             } else if artifact.target.kind.iter().any(|x| x == "bin") {
                 // This is a non-test binary -- add it to the map.
                 // Error case here implies that the returned path wasn't in the target directory -- ignore it
                 // since it shouldn't happen in normal use.
                 if let Ok(rel_path) = path.strip_prefix(&self.rust_build_meta.target_directory) {
+                    // Binaries built as artifact dependencies are exposed to the packages that
+                    // depend on them, not to the package they're a part of.
+                    let kind = if is_artifact_dependency_path(rel_path) {
+                        RustNonTestBinaryKind::BIN_ARTIFACT
+                    } else {
+                        RustNonTestBinaryKind::BIN_EXE
+                    };
                     let non_test_binary = RustNonTestBinarySummary {
                         name: artifact.target.name,
-                        kind: RustNonTestBinaryKind::BIN_EXE,
+                        kind,
                         path: convert_rel_path_to_forward_slash(rel_path),
                     };
 
@@ -276,12 +396,25 @@ impl<'g> BinaryListBuildState<'g> {
             }
         } else if artifact.target.kind.iter().any(|x| x.contains("dylib")) {
             // Also look for and grab dynamic libraries to store in archives.
+            //
+            // cdylibs built by workspace packages are also exposed to tests, so that they can be
+            // loaded at runtime.
+            let is_workspace_cdylib = artifact.target.kind.iter().any(|x| x == "cdylib")
+                && self
+                    .graph
+                    .metadata(&guppy::PackageId::new(artifact.package_id.repr.clone()))
+                    .map_or(false, |package| package.in_workspace());
             for filename in artifact.filenames {
                 if let Ok(rel_path) = filename.strip_prefix(&self.rust_build_meta.target_directory)
                 {
+                    let kind = if is_workspace_cdylib && is_dynamic_library_path(rel_path) {
+                        RustNonTestBinaryKind::CDYLIB
+                    } else {
+                        RustNonTestBinaryKind::DYLIB
+                    };
                     let non_test_binary = RustNonTestBinarySummary {
                         name: artifact.target.name.clone(),
-                        kind: RustNonTestBinaryKind::DYLIB,
+                        kind,
                         path: convert_rel_path_to_forward_slash(rel_path),
                     };
                     self.rust_build_meta
@@ -296,6 +429,16 @@ impl<'g> BinaryListBuildState<'g> {
         Ok(())
     }
 
+    /// Returns true if this artifact is part of the standard library, built with -Z build-std.
+    ///
+    /// Standard library crates are not part of the output of `cargo metadata`, so they're missing
+    /// from the package graph.
+    fn is_build_std_artifact(&self, artifact: &Artifact) -> bool {
+        self.graph
+            .metadata(&guppy::PackageId::new(artifact.package_id.repr.clone()))
+            .is_err()
+    }
+
     /// Look for paths that contain "deps" in their second-to-last component,
     /// and are descendants of the target directory.
     /// The paths without "deps" are base output directories.
@@ -360,12 +503,39 @@ impl<'g> BinaryListBuildState<'g> {
     }
 }
 
+/// Returns true if this path has the extension used for dynamic libraries on any platform.
+///
+/// While cross-compiling, the target platform might use a different extension from the host.
+fn is_dynamic_library_path(path: &Utf8Path) -> bool {
+    matches!(path.extension(), Some("so" | "dylib" | "dll"))
+}
+
+/// Returns true if this path, relative to the target directory, is in the directory Cargo puts
+/// artifact dependencies in.
+///
+/// These paths are of the form `<profile>/deps/artifact/<unit-dir>/bin/<name>`.
+fn is_artifact_dependency_path(rel_path: &Utf8Path) -> bool {
+    let mut components = rel_path.components().map(|component| component.as_str());
+    while let Some(component) = components.next() {
+        if component == "deps" {
+            return components.next() == Some("artifact");
+        }
+    }
+    false
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{cargo_config::TargetTripleSource, list::SerializableFormat};
+    use crate::{
+        cargo_config::{TargetDefinitionLocation, TargetTripleSource},
+        list::SerializableFormat,
+        reuse_build::PathMapper,
+    };
+    use guppy::CargoMetadata;
     use indoc::indoc;
     use maplit::btreeset;
+    use once_cell::sync::Lazy;
     use pretty_assertions::assert_eq;
 
     #[test]
@@ -378,6 +548,9 @@ mod tests {
             kind: RustTestBinaryKind::LIB,
             name: "fake-binary".to_owned(),
             build_platform: BuildPlatform::Target,
+            doctest: None,
+            features: vec![],
+            env: BTreeMap::new(),
         };
         let fake_macro_test = RustTestBinary {
             id: "fake-macro::proc-macro/fake-macro".to_owned(),
@@ -387,11 +560,15 @@ mod tests {
             kind: RustTestBinaryKind::PROC_MACRO,
             name: "fake-macro".to_owned(),
             build_platform: BuildPlatform::Host,
+            doctest: None,
+            features: vec![],
+            env: BTreeMap::new(),
         };
 
         let fake_triple = TargetTriple {
             triple: "fake-triple".to_owned(),
             source: TargetTripleSource::CliOption,
+            location: TargetDefinitionLocation::Builtin,
         };
         let mut rust_build_meta = RustBuildMeta::new("/fake/target", Some(fake_triple));
         rust_build_meta
@@ -417,6 +594,10 @@ mod tests {
                 },
             },
         );
+        rust_build_meta.linked_paths.insert(
+            "my-profile/build/my-package/out".into(),
+            btreeset! { "my-package-id".to_owned() },
+        );
 
         let binary_list = BinaryList {
             rust_build_meta,
@@ -462,8 +643,19 @@ mod tests {
                 }
               ]
             },
-            "linked-paths": [],
-            "target-triple": "fake-triple"
+            "linked-paths": [
+              "my-profile/build/my-package/out"
+            ],
+            "linked-path-packages": {
+              "my-profile/build/my-package/out": [
+                "my-package-id"
+              ]
+            },
+            "target-triple": "fake-triple",
+            "target-spec-file": null,
+            "rustc-version": null,
+            "features": null,
+            "profile": null
           },
           "rust-binaries": {
             "fake-macro::proc-macro/fake-macro": {
@@ -472,7 +664,9 @@ mod tests {
               "package-id": "fake-macro 0.1.0 (path+file:///Users/fakeuser/project/fake-macro)",
               "kind": "proc-macro",
               "binary-path": "/fake/macro",
-              "build-platform": "host"
+              "build-platform": "host",
+              "doctest": null,
+              "features": []
             },
             "fake-package::bin/fake-binary": {
               "binary-id": "fake-package::bin/fake-binary",
@@ -480,7 +674,9 @@ mod tests {
               "package-id": "fake-package 0.1.0 (path+file:///Users/fakeuser/project/fake-package)",
               "kind": "lib",
               "binary-path": "/fake/binary",
-              "build-platform": "target"
+              "build-platform": "target",
+              "doctest": null,
+              "features": []
             }
           }
         }"#};
@@ -503,5 +699,371 @@ mod tests {
                 .expect("json-pretty succeeded"),
             EXPECTED_JSON_PRETTY
         );
+
+        // Linked paths and the packages that requested them should roundtrip through the summary.
+        let roundtrip = BinaryList::from_summary(binary_list.to_summary());
+        assert_eq!(
+            roundtrip.rust_build_meta.linked_paths, binary_list.rust_build_meta.linked_paths,
+            "linked paths roundtrip through summary"
+        );
+    }
+
+    #[test]
+    fn test_from_messages_with() {
+        let messages = [
+            artifact_message(
+                METADATA_HELPER_ID,
+                "metadata-helper",
+                &["lib"],
+                &[],
+                Some("debug/deps/metadata_helper-0123456789abcdef"),
+                true,
+            ),
+            artifact_message(
+                METADATA_BASE_ID,
+                "metadata-base",
+                &["lib"],
+                &["debug/deps/libmetadata_base-0123456789abcdef.rlib"],
+                None,
+                false,
+            ),
+            artifact_message(
+                METADATA_BASE_ID,
+                "metadata-base",
+                &["lib"],
+                &[],
+                Some("x86_64-unknown-linux-gnu/debug/deps/metadata_base-0123456789abcdef"),
+                true,
+            ),
+        ]
+        .join("\n");
+
+        let mut built = Vec::new();
+        let binary_list = BinaryList::from_messages_with(
+            io::Cursor::new(messages.as_bytes()),
+            &PACKAGE_GRAPH_FIXTURE,
+            None,
+            |binary_list| {
+                built.push(binary_list);
+                ControlFlow::Continue(())
+            },
+        )
+        .expect("messages are valid");
+
+        let ids = |list: &BinaryList| -> Vec<String> {
+            list.rust_binaries
+                .iter()
+                .map(|bin| bin.id.clone())
+                .collect()
+        };
+        assert_eq!(
+            ids(&binary_list),
+            vec!["metadata-base", "metadata-helper"],
+            "all test binaries are returned"
+        );
+        assert_eq!(built.len(), 2, "called once per test binary");
+        assert_eq!(ids(&built[0]), vec!["metadata-helper"]);
+        assert_eq!(
+            built[0].rust_build_meta.base_output_directories,
+            btreeset! { "debug".into() },
+            "build metadata gathered so far is passed in"
+        );
+        assert_eq!(ids(&built[1]), vec!["metadata-base"]);
+        assert_eq!(
+            built[1].rust_build_meta.base_output_directories,
+            btreeset! { "debug".into(), "x86_64-unknown-linux-gnu/debug".into() },
+        );
+
+        let mut built_count = 0;
+        let binary_list = BinaryList::from_messages_with(
+            io::Cursor::new(messages.as_bytes()),
+            &PACKAGE_GRAPH_FIXTURE,
+            None,
+            |_| {
+                built_count += 1;
+                ControlFlow::Break(())
+            },
+        )
+        .expect("messages are valid");
+        assert_eq!(built_count, 1, "no more messages are read after a break");
+        assert_eq!(
+            ids(&binary_list),
+            vec!["metadata-helper"],
+            "test binaries built before the break are returned"
+        );
+    }
+
+    #[test]
+    fn test_from_messages_build_std() {
+        // With -Z build-std, the standard library is built from the rust-src component, so its
+        // packages aren't in the package graph.
+        static CORE_ID: &str = "core 0.0.0 (path+file:///home/fakeuser/.rustup/toolchains/nightly-x86_64-unknown-linux-gnu/lib/rustlib/src/rust/library/core)";
+        static STD_ID: &str = "std 0.0.0 (path+file:///home/fakeuser/.rustup/toolchains/nightly-x86_64-unknown-linux-gnu/lib/rustlib/src/rust/library/std)";
+
+        let messages = [
+            artifact_message(
+                CORE_ID,
+                "core",
+                &["lib"],
+                &["x86_64-unknown-linux-gnu/debug/deps/libcore-0123456789abcdef.rlib"],
+                None,
+                false,
+            ),
+            artifact_message(
+                STD_ID,
+                "std",
+                &["rlib", "dylib"],
+                &[
+                    "x86_64-unknown-linux-gnu/debug/deps/libstd-0123456789abcdef.rlib",
+                    "x86_64-unknown-linux-gnu/debug/deps/libstd-0123456789abcdef.so",
+                ],
+                None,
+                false,
+            ),
+            artifact_message(
+                METADATA_BASE_ID,
+                "metadata-base",
+                &["lib"],
+                &[],
+                Some("debug/deps/metadata_base-0123456789abcdef"),
+                true,
+            ),
+        ]
+        .join("\n");
+
+        let binary_list =
+            BinaryList::from_messages(io::Cursor::new(messages), &PACKAGE_GRAPH_FIXTURE, None)
+                .expect("messages are valid");
+        let rust_build_meta = &binary_list.rust_build_meta;
+
+        assert_eq!(
+            rust_build_meta.base_output_directories,
+            btreeset! { "debug".into(), "x86_64-unknown-linux-gnu/debug".into() },
+            "standard library output directories are detected"
+        );
+        let target_dir = Utf8Path::new(FIXTURE_TARGET_DIR);
+        assert_eq!(
+            rust_build_meta.map_paths(&PathMapper::noop()).dylib_paths(),
+            vec![
+                target_dir.join("debug/deps"),
+                target_dir.join("debug"),
+                target_dir.join("x86_64-unknown-linux-gnu/debug/deps"),
+                target_dir.join("x86_64-unknown-linux-gnu/debug"),
+            ],
+            "standard library output directories are on the dynamic library path"
+        );
+
+        // The standard library dylib is recorded as a non-test binary, so it's archived along with
+        // the test binaries.
+        assert_eq!(
+            rust_build_meta.non_test_binaries.get(STD_ID),
+            Some(&btreeset! {
+                RustNonTestBinarySummary {
+                    name: "std".into(),
+                    kind: RustNonTestBinaryKind::DYLIB,
+                    path: "x86_64-unknown-linux-gnu/debug/deps/libstd-0123456789abcdef.rlib".into(),
+                },
+                RustNonTestBinarySummary {
+                    name: "std".into(),
+                    kind: RustNonTestBinaryKind::DYLIB,
+                    path: "x86_64-unknown-linux-gnu/debug/deps/libstd-0123456789abcdef.so".into(),
+                },
+            }),
+        );
+        assert!(
+            !rust_build_meta.non_test_binaries.contains_key(CORE_ID),
+            "rlib-only crates aren't archived"
+        );
+    }
+
+    #[test]
+    fn test_is_artifact_dependency_path() {
+        assert!(is_artifact_dependency_path(Utf8Path::new(
+            "debug/deps/artifact/my-bin-0123456789abcdef/bin/my-bin"
+        )));
+        assert!(is_artifact_dependency_path(Utf8Path::new(
+            "x86_64-unknown-linux-gnu/debug/deps/artifact/my-bin-0123456789abcdef/bin/my-bin"
+        )));
+        assert!(!is_artifact_dependency_path(Utf8Path::new("debug/my-bin")));
+        assert!(!is_artifact_dependency_path(Utf8Path::new(
+            "debug/deps/my-bin-0123456789abcdef"
+        )));
+    }
+
+    #[test]
+    fn test_is_dynamic_library_path() {
+        assert!(is_dynamic_library_path(Utf8Path::new(
+            "debug/libmy_cdylib.so"
+        )));
+        assert!(is_dynamic_library_path(Utf8Path::new(
+            "debug/libmy_cdylib.dylib"
+        )));
+        assert!(is_dynamic_library_path(Utf8Path::new(
+            "x86_64-pc-windows-msvc/debug/my_cdylib.dll"
+        )));
+        assert!(!is_dynamic_library_path(Utf8Path::new(
+            "x86_64-pc-windows-msvc/debug/my_cdylib.dll.lib"
+        )));
+        assert!(!is_dynamic_library_path(Utf8Path::new(
+            "x86_64-pc-windows-msvc/debug/my_cdylib.pdb"
+        )));
+        assert!(!is_dynamic_library_path(Utf8Path::new(
+            "debug/libmy_lib.rlib"
+        )));
+    }
+
+    #[test]
+    fn test_split_by_target() {
+        let triple_a = TargetTriple {
+            triple: "triple-a".to_owned(),
+            source: TargetTripleSource::CliOption,
+            location: TargetDefinitionLocation::Builtin,
+        };
+        let triple_b = TargetTriple {
+            triple: "triple-b".to_owned(),
+            source: TargetTripleSource::CliOption,
+            location: TargetDefinitionLocation::Builtin,
+        };
+        let make_binary = |id: &str, path: &str, build_platform| RustTestBinary {
+            id: id.to_owned(),
+            path: path.into(),
+            package_id: "my-package-id".to_owned(),
+            kind: RustTestBinaryKind::LIB,
+            name: id.to_owned(),
+            build_platform,
+            doctest: None,
+            features: vec![],
+            env: BTreeMap::new(),
+        };
+
+        let mut rust_build_meta = RustBuildMeta::new("/fake/target", None);
+        rust_build_meta.base_output_directories = btreeset! {
+            "debug".into(),
+            "triple-a/debug".into(),
+            "triple-b/debug".into(),
+        };
+        rust_build_meta.linked_paths.insert(
+            "triple-b/debug/build/my-package/out".into(),
+            btreeset! { "my-package-id".to_owned() },
+        );
+        rust_build_meta.non_test_binaries.insert(
+            "my-package-id".into(),
+            btreeset! {
+                RustNonTestBinarySummary {
+                    name: "my-name".into(),
+                    kind: RustNonTestBinaryKind::BIN_EXE,
+                    path: "triple-a/debug/my-name".into(),
+                },
+            },
+        );
+        let binary_list = BinaryList {
+            rust_build_meta,
+            rust_binaries: vec![
+                make_binary(
+                    "a",
+                    "/fake/target/triple-a/debug/deps/a",
+                    BuildPlatform::Target,
+                ),
+                make_binary(
+                    "b",
+                    "/fake/target/triple-b/debug/deps/b",
+                    BuildPlatform::Target,
+                ),
+                make_binary("host", "/fake/target/debug/deps/host", BuildPlatform::Host),
+            ],
+        };
+
+        let (host, split) = binary_list.split_by_target(&[triple_a.clone(), triple_b.clone()]);
+        let ids = |list: &BinaryList| -> Vec<String> {
+            list.rust_binaries
+                .iter()
+                .map(|bin| bin.id.clone())
+                .collect()
+        };
+
+        assert_eq!(host.rust_build_meta.target_triple, None);
+        assert_eq!(
+            ids(&host),
+            vec!["host"],
+            "host binaries are only in the host list"
+        );
+        assert_eq!(
+            host.rust_build_meta.base_output_directories,
+            btreeset! { "debug".into() },
+        );
+        assert!(host.rust_build_meta.linked_paths.is_empty());
+        assert!(host.rust_build_meta.non_test_binaries.is_empty());
+
+        assert_eq!(split.len(), 2, "one list per target triple");
+        assert_eq!(split[0].rust_build_meta.target_triple, Some(triple_a));
+        assert_eq!(ids(&split[0]), vec!["a"]);
+        assert_eq!(
+            split[0].rust_build_meta.base_output_directories,
+            btreeset! { "debug".into(), "triple-a/debug".into() },
+        );
+        assert!(split[0].rust_build_meta.linked_paths.is_empty());
+        assert_eq!(split[0].rust_build_meta.non_test_binaries.len(), 1);
+
+        assert_eq!(split[1].rust_build_meta.target_triple, Some(triple_b));
+        assert_eq!(ids(&split[1]), vec!["b"]);
+        assert_eq!(
+            split[1].rust_build_meta.base_output_directories,
+            btreeset! { "debug".into(), "triple-b/debug".into() },
+        );
+        assert_eq!(split[1].rust_build_meta.linked_paths.len(), 1);
+        assert!(split[1].rust_build_meta.non_test_binaries.is_empty());
+    }
+
+    static PACKAGE_GRAPH_FIXTURE: Lazy<PackageGraph> = Lazy::new(|| {
+        static FIXTURE_JSON: &str = include_str!("../../../fixtures/cargo-metadata.json");
+        let metadata = CargoMetadata::parse_json(FIXTURE_JSON).expect("fixture is valid JSON");
+        metadata
+            .build_graph()
+            .expect("fixture is valid PackageGraph")
+    });
+
+    static FIXTURE_TARGET_DIR: &str =
+        "/Users/fakeuser/local/testcrates/metadata/metadata-base/target";
+    static METADATA_HELPER_ID: &str = "metadata-helper 0.1.0 (path+file:///Users/fakeuser/local/testcrates/metadata/metadata-helper)";
+    static METADATA_BASE_ID: &str =
+        "metadata-base 0.1.0 (path+file:///Users/fakeuser/local/testcrates/metadata/metadata-base)";
+
+    /// Returns a `compiler-artifact` message, with paths relative to the fixture's target
+    /// directory.
+    fn artifact_message(
+        package_id: &str,
+        name: &str,
+        kind: &[&str],
+        filenames: &[&str],
+        executable: Option<&str>,
+        test: bool,
+    ) -> String {
+        let abs_path = |path: &str| format!("{FIXTURE_TARGET_DIR}/{path}");
+        serde_json::json!({
+            "reason": "compiler-artifact",
+            "package_id": package_id,
+            "manifest_path": "/fake/Cargo.toml",
+            "target": {
+                "kind": kind,
+                "crate_types": kind,
+                "name": name,
+                "src_path": "/fake/src/lib.rs",
+                "edition": "2021",
+                "doctest": false,
+                "test": true,
+            },
+            "profile": {
+                "opt_level": "0",
+                "debuginfo": 2,
+                "debug_assertions": true,
+                "overflow_checks": true,
+                "test": test,
+            },
+            "features": [],
+            "filenames": filenames.iter().map(|path| abs_path(path)).collect::<Vec<_>>(),
+            "executable": executable.map(abs_path),
+            "fresh": false,
+        })
+        .to_string()
     }
 }
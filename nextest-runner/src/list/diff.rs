@@ -0,0 +1,261 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    errors::WriteTestListError,
+    list::{OutputFormat, Styles},
+};
+use nextest_metadata::{RustTestSuiteStatusSummary, TestListDiffSummary, TestListSummary};
+use owo_colors::OwoColorize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, Write},
+};
+
+/// The tests added and removed between a previous test list and the current one.
+///
+/// Binaries that were skipped in either list (for example because they didn't match filter
+/// expressions) aren't compared, since their tests weren't listed.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TestListDiff {
+    summary: TestListDiffSummary,
+}
+
+impl TestListDiff {
+    /// Compares a previous test list with the current one.
+    pub fn new(previous: &TestListSummary, current: &TestListSummary) -> Self {
+        let previous_tests = listed_tests(previous);
+        let current_tests = listed_tests(current);
+        let skipped = skipped_binaries(previous)
+            .chain(skipped_binaries(current))
+            .collect::<BTreeSet<_>>();
+
+        let mut summary = TestListDiffSummary::default();
+        for (binary_id, tests) in &current_tests {
+            if skipped.contains(binary_id) {
+                continue;
+            }
+            let added: BTreeSet<_> = match previous_tests.get(binary_id) {
+                Some(previous) => tests
+                    .difference(previous)
+                    .map(|&name| name.to_owned())
+                    .collect(),
+                None => tests.iter().map(|&name| name.to_owned()).collect(),
+            };
+            if !added.is_empty() {
+                summary.added.insert((*binary_id).to_owned(), added);
+            }
+        }
+        for (binary_id, tests) in &previous_tests {
+            if skipped.contains(binary_id) {
+                continue;
+            }
+            let removed: BTreeSet<_> = match current_tests.get(binary_id) {
+                Some(current) => tests
+                    .difference(current)
+                    .map(|&name| name.to_owned())
+                    .collect(),
+                None => tests.iter().map(|&name| name.to_owned()).collect(),
+            };
+            if !removed.is_empty() {
+                summary.removed.insert((*binary_id).to_owned(), removed);
+            }
+        }
+
+        Self { summary }
+    }
+
+    /// Returns true if no tests were added or removed.
+    pub fn is_empty(&self) -> bool {
+        self.summary.added.is_empty() && self.summary.removed.is_empty()
+    }
+
+    /// Returns true if any tests were removed.
+    pub fn has_removed(&self) -> bool {
+        !self.summary.removed.is_empty()
+    }
+
+    /// Returns a serializable summary of the differences.
+    pub fn to_summary(&self) -> TestListDiffSummary {
+        self.summary.clone()
+    }
+
+    /// Outputs the differences in the given format.
+    pub fn write(
+        &self,
+        output_format: OutputFormat,
+        writer: impl Write,
+        colorize: bool,
+    ) -> Result<(), WriteTestListError> {
+        match output_format {
+            OutputFormat::Human { .. } => self
+                .write_human(writer, colorize)
+                .map_err(WriteTestListError::Io),
+            OutputFormat::Serializable(format) => format
+                .to_writer(&self.summary, writer)
+                .map_err(WriteTestListError::Json),
+        }
+    }
+
+    fn write_human(&self, mut writer: impl Write, colorize: bool) -> io::Result<()> {
+        let mut styles = Styles::default();
+        if colorize {
+            styles.colorize();
+        }
+
+        if self.is_empty() {
+            return writeln!(writer, "(no tests added or removed)");
+        }
+
+        let binary_ids: BTreeSet<_> = self
+            .summary
+            .added
+            .keys()
+            .chain(self.summary.removed.keys())
+            .collect();
+        for binary_id in binary_ids {
+            writeln!(writer, "{}:", binary_id.style(styles.binary_id))?;
+            for name in self.summary.removed.get(binary_id).into_iter().flatten() {
+                writeln!(writer, "    {} {}", "-".style(styles.removed), name)?;
+            }
+            for name in self.summary.added.get(binary_id).into_iter().flatten() {
+                writeln!(writer, "    {} {}", "+".style(styles.added), name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn listed_tests(summary: &TestListSummary) -> BTreeMap<&str, BTreeSet<&str>> {
+    summary
+        .rust_suites
+        .iter()
+        .map(|(binary_id, suite)| {
+            let tests = suite.test_cases.keys().map(|name| name.as_str()).collect();
+            (binary_id.as_str(), tests)
+        })
+        .collect()
+}
+
+fn skipped_binaries(summary: &TestListSummary) -> impl Iterator<Item = &str> {
+    summary
+        .rust_suites
+        .iter()
+        .filter(|(_, suite)| suite.status == RustTestSuiteStatusSummary::SKIPPED)
+        .map(|(binary_id, _)| binary_id.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::{btreemap, btreeset};
+
+    #[test]
+    fn test_list_diff() {
+        let previous = parse_summary(
+            r#"
+            "fake-package::unchanged": ["tests::a", "tests::b"],
+            "fake-package::changed": ["tests::a", "tests::old_name"],
+            "fake-package::removed": ["tests::a"],
+            "fake-package::skipped-now": ["tests::a"]
+            "#,
+            &[],
+        );
+        let current = parse_summary(
+            r#"
+            "fake-package::unchanged": ["tests::b", "tests::a"],
+            "fake-package::changed": ["tests::a", "tests::new_name"],
+            "fake-package::added": ["tests::a"],
+            "fake-package::skipped-now": []
+            "#,
+            &["fake-package::skipped-now"],
+        );
+
+        let diff = TestListDiff::new(&previous, &current);
+        assert!(diff.has_removed(), "tests were removed");
+        assert_eq!(
+            diff.to_summary(),
+            TestListDiffSummary {
+                added: btreemap! {
+                    "fake-package::added".to_owned() => btreeset! { "tests::a".to_owned() },
+                    "fake-package::changed".to_owned() => btreeset! { "tests::new_name".to_owned() },
+                },
+                removed: btreemap! {
+                    "fake-package::changed".to_owned() => btreeset! { "tests::old_name".to_owned() },
+                    "fake-package::removed".to_owned() => btreeset! { "tests::a".to_owned() },
+                },
+            }
+        );
+
+        let mut output = Vec::new();
+        diff.write(OutputFormat::Human { verbose: false }, &mut output, false)
+            .expect("writing to a Vec succeeds");
+        assert_eq!(
+            String::from_utf8(output).expect("output is valid UTF-8"),
+            indoc::indoc! {"
+                fake-package::added:
+                    + tests::a
+                fake-package::changed:
+                    - tests::old_name
+                    + tests::new_name
+                fake-package::removed:
+                    - tests::a
+            "}
+        );
+
+        let same = TestListDiff::new(&previous, &previous);
+        assert!(same.is_empty(), "a list has no differences with itself");
+    }
+
+    /// Builds a test list summary out of `"binary-id": [test names]` pairs.
+    fn parse_summary(suites: &str, skipped: &[&str]) -> TestListSummary {
+        let suites: BTreeMap<String, Vec<String>> =
+            serde_json::from_str(&format!("{{{}}}", suites)).expect("valid suites JSON");
+        let rust_suites: serde_json::Map<_, _> = suites
+            .into_iter()
+            .map(|(binary_id, tests)| {
+                let status = if skipped.contains(&binary_id.as_str()) {
+                    "skipped"
+                } else {
+                    "listed"
+                };
+                let test_cases: serde_json::Map<_, _> = tests
+                    .into_iter()
+                    .map(|name| {
+                        let test_case = serde_json::json!({
+                            "ignored": false,
+                            "filter-match": { "status": "matches" },
+                        });
+                        (name, test_case)
+                    })
+                    .collect();
+                let suite = serde_json::json!({
+                    "package-name": "fake-package",
+                    "binary-id": binary_id,
+                    "binary-name": "fake-binary",
+                    "package-id": "fake-package 0.1.0 (path+file:///fake)",
+                    "kind": "test",
+                    "binary-path": "/fake/binary",
+                    "build-platform": "target",
+                    "cwd": "/fake",
+                    "status": status,
+                    "testcases": test_cases,
+                });
+                (binary_id, suite)
+            })
+            .collect();
+
+        serde_json::from_value(serde_json::json!({
+            "rust-build-meta": {
+                "target-directory": "/fake/target",
+                "base-output-directories": [],
+                "non-test-binaries": {},
+                "linked-paths": [],
+                "target-triple": "x86_64-unknown-linux-gnu",
+            },
+            "test-count": 0,
+            "rust-suites": rust_suites,
+        }))
+        .expect("valid test list summary")
+    }
+}
@@ -0,0 +1,367 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Support for doctests compiled into their own binaries.
+//!
+//! rustdoc doesn't produce libtest binaries for doctests. Instead, with the unstable
+//! `--persist-doctests <dir> --no-run` options, it compiles each doctest into a separate binary
+//! within `<dir>`. Each of these binaries runs a single doctest and takes no arguments.
+
+use crate::{
+    errors::FromMessagesError,
+    list::{BinaryList, RustTestBinary},
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use guppy::graph::PackageMetadata;
+use nextest_metadata::{BuildPlatform, RustDoctestSummary, RustTestBinaryKind};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+impl BinaryList {
+    /// Adds doctests compiled for `package` to this list.
+    ///
+    /// `rustdoc_output` is the standard output of `cargo test --doc`, run with
+    /// `--persist-doctests <persist_dir> --no-run` passed in to rustdoc and `--format json` passed
+    /// in to the test harness. Paths to source files in the output are relative to
+    /// `workspace_root`.
+    ///
+    /// Doctests marked `ignore`, `no_run` or `compile_fail` aren't added, since they can't be run.
+    pub fn add_doctests(
+        &mut self,
+        package: PackageMetadata<'_>,
+        workspace_root: &Utf8Path,
+        persist_dir: &Utf8Path,
+        rustdoc_output: &str,
+    ) -> Result<(), FromMessagesError> {
+        let mut test_names = Vec::new();
+        for line in rustdoc_output.lines() {
+            let parse_error = |reason: String| FromMessagesError::DoctestParse {
+                package_name: package.name().to_owned(),
+                line: line.to_owned(),
+                reason,
+            };
+
+            if !line.starts_with('{') {
+                continue;
+            }
+            let event: LibtestEvent =
+                serde_json::from_str(line).map_err(|err| parse_error(err.to_string()))?;
+            if event.kind != "test" {
+                continue;
+            }
+            match (event.event.as_str(), event.name) {
+                ("ok", Some(name)) => test_names.push(name),
+                ("failed", Some(test_name)) => {
+                    return Err(FromMessagesError::DoctestCompileFailed {
+                        test_name,
+                        output: event.stdout.unwrap_or_default(),
+                    });
+                }
+                (_, Some(_)) => {}
+                (_, None) => return Err(parse_error("test event without a name".to_owned())),
+            }
+        }
+
+        // rustdoc names the directory for each doctest after the file and line it's defined at,
+        // plus a counter for doctests defined at the same line (e.g. by macros). Doctests are
+        // reported in sorted order, which might not match the order rustdoc collected them in, but
+        // doctests sharing a line are rare enough that this is acceptable.
+        test_names.sort_unstable();
+        let mut counters = HashMap::new();
+
+        // Doctests are built against the package's library, with the same features as the
+        // package's other test binaries.
+        let features = self
+            .rust_binaries
+            .iter()
+            .find(|bin| bin.package_id == package.id().repr())
+            .map_or_else(Vec::new, |bin| bin.features.clone());
+        for test_name in test_names {
+            let (file, line) =
+                parse_doctest_name(&test_name).ok_or_else(|| FromMessagesError::DoctestParse {
+                    package_name: package.name().to_owned(),
+                    line: test_name.clone(),
+                    reason: "doctest name is not of the form `<file> - <item> (line <n>)`"
+                        .to_owned(),
+                })?;
+            let folder_name: String = file
+                .chars()
+                .map(|c| {
+                    if matches!(c, '\\' | '/' | '.') {
+                        '_'
+                    } else {
+                        c
+                    }
+                })
+                .collect();
+            let counter = counters.entry((folder_name.clone(), line)).or_insert(0);
+            let dir_name = format!("{folder_name}_{line}_{counter}");
+            *counter += 1;
+
+            // compile_fail doctests don't produce a binary.
+            let path = persist_dir
+                .join(&dir_name)
+                .join(format!("rust_out{}", std::env::consts::EXE_SUFFIX));
+            if !path.is_file() {
+                continue;
+            }
+
+            let source_path = workspace_root.join(file);
+            let source = std::fs::read_to_string(&source_path).map_err(|error| {
+                FromMessagesError::DoctestSourceRead {
+                    test_name: test_name.clone(),
+                    path: source_path.clone(),
+                    error,
+                }
+            })?;
+            let attributes = source
+                .lines()
+                .nth(line.saturating_sub(1))
+                .map_or(DoctestAttributes::default(), DoctestAttributes::parse);
+            if attributes.no_run {
+                continue;
+            }
+
+            self.rust_binaries.push(RustTestBinary {
+                id: format!(
+                    "{}::{}/{dir_name}",
+                    package.name(),
+                    RustTestBinaryKind::DOCTEST
+                ),
+                path,
+                package_id: package.id().repr().to_owned(),
+                kind: RustTestBinaryKind::DOCTEST,
+                name: dir_name,
+                build_platform: BuildPlatform::Target,
+                doctest: Some(RustDoctestSummary {
+                    name: test_name,
+                    should_panic: attributes.should_panic,
+                }),
+                features: features.clone(),
+                env: BTreeMap::new(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct LibtestEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    event: String,
+    name: Option<String>,
+    stdout: Option<String>,
+}
+
+/// Parses a doctest name of the form `<file> - <item> (line <n>)` into the file and line.
+fn parse_doctest_name(name: &str) -> Option<(&str, usize)> {
+    let (file, _) = name.split_once(" - ")?;
+    let (_, line) = name.rsplit_once("(line ")?;
+    let line = line.strip_suffix(')')?.parse().ok()?;
+    Some((file, line))
+}
+
+/// Attributes of a doctest, parsed from the code fence it starts with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct DoctestAttributes {
+    should_panic: bool,
+    no_run: bool,
+}
+
+impl DoctestAttributes {
+    fn parse(fence_line: &str) -> Self {
+        let mut attributes = Self::default();
+        let info = match fence_line.find("```").or_else(|| fence_line.find("~~~")) {
+            Some(index) => fence_line[index..].trim_start_matches(['`', '~']),
+            None => return attributes,
+        };
+        for token in info.split(|c: char| c == ',' || c == '{' || c == '}' || c.is_whitespace()) {
+            match token {
+                "should_panic" => attributes.should_panic = true,
+                "no_run" => attributes.no_run = true,
+                _ => {}
+            }
+        }
+        attributes
+    }
+}
+
+/// Returns the directory doctests for `package` are persisted to, within `target_dir`.
+pub fn doctest_persist_dir(target_dir: &Utf8Path, package: PackageMetadata<'_>) -> Utf8PathBuf {
+    target_dir
+        .join("nextest")
+        .join("doctests")
+        .join(package.name())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::list::RustBuildMeta;
+    use guppy::{graph::PackageGraph, CargoMetadata, PackageId};
+    use once_cell::sync::Lazy;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    static PACKAGE_GRAPH_FIXTURE: Lazy<PackageGraph> = Lazy::new(|| {
+        static FIXTURE_JSON: &str = include_str!("../../../fixtures/cargo-metadata.json");
+        CargoMetadata::parse_json(FIXTURE_JSON)
+            .expect("fixture is valid JSON")
+            .build_graph()
+            .expect("fixture is valid PackageGraph")
+    });
+    static PACKAGE_ID: &str = "metadata-helper 0.1.0 (path+file:///Users/fakeuser/local/testcrates/metadata/metadata-helper)";
+
+    #[test]
+    fn test_add_doctests() {
+        let temp_dir = TempDir::new().expect("temp dir created");
+        let temp_dir: &Utf8Path = temp_dir.path().try_into().expect("temp dir is valid UTF-8");
+        let workspace_root = temp_dir.join("workspace");
+        let persist_dir = temp_dir.join("persist");
+        std::fs::create_dir_all(workspace_root.join("src")).unwrap();
+        std::fs::write(
+            workspace_root.join("src/lib.rs"),
+            "/// ```\n\
+             /// assert!(true);\n\
+             /// ```\n\
+             ///\n\
+             /// ```should_panic\n\
+             /// panic!();\n\
+             /// ```\n\
+             ///\n\
+             /// ```rust,no_run\n\
+             /// loop {}\n\
+             /// ```\n\
+             ///\n\
+             /// ```compile_fail\n\
+             /// let x: u32 = \"a\";\n\
+             /// ```\n\
+             pub fn foo() {}\n",
+        )
+        .unwrap();
+        for dir in ["src_lib_rs_1_0", "src_lib_rs_5_0", "src_lib_rs_9_0"] {
+            let dir = persist_dir.join(dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(
+                dir.join(format!("rust_out{}", std::env::consts::EXE_SUFFIX)),
+                "",
+            )
+            .unwrap();
+        }
+        std::fs::create_dir_all(persist_dir.join("src_lib_rs_13_0")).unwrap();
+
+        let rustdoc_output = r#"{ "type": "suite", "event": "started", "test_count": 5 }
+{ "type": "test", "event": "started", "name": "src/lib.rs - foo (line 1)" }
+{ "type": "test", "name": "src/lib.rs - foo (line 1)", "event": "ok" }
+{ "type": "test", "name": "src/lib.rs - foo (line 13)", "event": "ok" }
+{ "type": "test", "name": "src/lib.rs - foo (line 5)", "event": "ok" }
+{ "type": "test", "name": "src/lib.rs - foo (line 9)", "event": "ok" }
+{ "type": "test", "name": "src/lib.rs - foo (line 17)", "event": "ignored" }
+{ "type": "suite", "event": "ok", "passed": 4, "failed": 0, "ignored": 1 }
+"#;
+
+        let package = PACKAGE_GRAPH_FIXTURE
+            .metadata(&PackageId::new(PACKAGE_ID))
+            .expect("package ID is valid");
+        let mut binary_list = BinaryList {
+            rust_build_meta: RustBuildMeta::new(temp_dir, None),
+            rust_binaries: vec![],
+        };
+        binary_list
+            .add_doctests(package, &workspace_root, &persist_dir, rustdoc_output)
+            .expect("doctests added");
+
+        let doctests: Vec<_> = binary_list
+            .rust_binaries
+            .iter()
+            .map(|binary| {
+                let doctest = binary.doctest.as_ref().expect("binary is a doctest");
+                (
+                    binary.id.as_str(),
+                    doctest.name.as_str(),
+                    doctest.should_panic,
+                )
+            })
+            .collect();
+        assert_eq!(
+            doctests,
+            vec![
+                (
+                    "metadata-helper::doctest/src_lib_rs_1_0",
+                    "src/lib.rs - foo (line 1)",
+                    false
+                ),
+                (
+                    "metadata-helper::doctest/src_lib_rs_5_0",
+                    "src/lib.rs - foo (line 5)",
+                    true
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_add_doctests_compile_failed() {
+        let package = PACKAGE_GRAPH_FIXTURE
+            .metadata(&PackageId::new(PACKAGE_ID))
+            .expect("package ID is valid");
+        let mut binary_list = BinaryList {
+            rust_build_meta: RustBuildMeta::new("/fake/target", None),
+            rust_binaries: vec![],
+        };
+        let rustdoc_output = r#"{ "type": "test", "name": "src/lib.rs - foo (line 1)", "event": "failed", "stdout": "error[E0308]: mismatched types\n" }"#;
+        let err = binary_list
+            .add_doctests(
+                package,
+                "/fake".as_ref(),
+                "/fake/persist".as_ref(),
+                rustdoc_output,
+            )
+            .expect_err("doctest failed to compile");
+        assert_eq!(
+            err.to_string(),
+            "doctest `src/lib.rs - foo (line 1)` failed to compile:\nerror[E0308]: mismatched types\n",
+        );
+    }
+
+    #[test]
+    fn test_parse_doctest_name() {
+        assert_eq!(
+            parse_doctest_name("src/lib.rs - foo::bar (line 12)"),
+            Some(("src/lib.rs", 12)),
+        );
+        assert_eq!(
+            parse_doctest_name("src/lib.rs - (line 1)"),
+            Some(("src/lib.rs", 1)),
+        );
+        assert_eq!(
+            parse_doctest_name("src/lib.rs - Foo<'a> (line 3)"),
+            Some(("src/lib.rs", 3)),
+        );
+        assert_eq!(parse_doctest_name("tests::foo"), None);
+    }
+
+    #[test]
+    fn test_parse_doctest_attributes() {
+        let cases = [
+            ("/// ```", false, false),
+            ("    /// ```should_panic", true, false),
+            ("//! ```rust,no_run", false, true),
+            ("/// ~~~ should_panic no_run", true, true),
+            ("/// ```rust, should_panic", true, false),
+            ("not a fence", false, false),
+        ];
+        for (line, should_panic, no_run) in cases {
+            assert_eq!(
+                DoctestAttributes::parse(line),
+                DoctestAttributes {
+                    should_panic,
+                    no_run
+                },
+                "attributes for `{line}` parsed correctly",
+            );
+        }
+    }
+}
@@ -6,13 +6,18 @@
 //! The main data structures in this module are:
 //! * [`TestList`] for test instances
 //! * [`BinaryList`] for test binaries
+//! * [`TestListDiff`] for comparing test lists
 
 mod binary_list;
+mod diff;
+mod doctest;
 mod output_format;
 mod rust_build_meta;
 mod test_list;
 
 pub use binary_list::*;
+pub use diff::*;
+pub use doctest::*;
 pub use output_format::*;
 pub use rust_build_meta::*;
 pub use test_list::*;
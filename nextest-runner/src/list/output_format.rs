@@ -54,6 +54,8 @@ pub(crate) struct Styles {
     pub(crate) test_name: Style,
     pub(crate) module_path: Style,
     pub(crate) field: Style,
+    pub(crate) added: Style,
+    pub(crate) removed: Style,
 }
 
 impl Styles {
@@ -62,5 +64,7 @@ impl Styles {
         self.test_name = Style::new().blue().bold();
         self.field = Style::new().yellow().bold();
         self.module_path = Style::new().cyan();
+        self.added = Style::new().green().bold();
+        self.removed = Style::new().red().bold();
     }
 }
@@ -2,13 +2,16 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
-    cargo_config::TargetTriple,
+    cargo_config::{TargetDefinitionLocation, TargetTriple},
     helpers::convert_rel_path_to_main_sep,
     list::{BinaryListState, TestListState},
     reuse_build::PathMapper,
 };
-use camino::Utf8PathBuf;
-use nextest_metadata::{RustBuildMetaSummary, RustNonTestBinarySummary};
+use camino::{Utf8Path, Utf8PathBuf};
+use nextest_metadata::{
+    FeatureSelectionSummary, RustBuildMetaSummary, RustNonTestBinaryKind, RustNonTestBinarySummary,
+    RustcVersionSummary,
+};
 use std::{
     collections::{BTreeMap, BTreeSet},
     marker::PhantomData,
@@ -31,14 +34,27 @@ pub struct RustBuildMeta<State> {
     /// added to the dynamic library path.
     ///
     /// The values are the package IDs of the libraries that requested the linked paths.
-    ///
-    /// Note that the serialized metadata only has the paths for now, not the libraries that
-    /// requested them. We might consider adding a new field with metadata about that.
     pub linked_paths: BTreeMap<Utf8PathBuf, BTreeSet<String>>,
 
     /// The target triple used while compiling the artifacts
     pub target_triple: Option<TargetTriple>,
 
+    /// The library directory within the rustc sysroot for the target platform, as reported by
+    /// `rustc --print target-libdir`. This directory is added to the dynamic library path.
+    ///
+    /// This is specific to the machine tests are run on, so it is not part of the serialized
+    /// metadata.
+    pub sysroot_libdir: Option<Utf8PathBuf>,
+
+    /// The version of rustc used while compiling the artifacts, if known.
+    pub rustc_version: Option<RustcVersionSummary>,
+
+    /// The Cargo feature selection used while compiling the artifacts, if known.
+    pub features: Option<FeatureSelectionSummary>,
+
+    /// The Cargo profile used while compiling the artifacts, if known.
+    pub profile: Option<String>,
+
     state: PhantomData<State>,
 }
 
@@ -55,6 +71,10 @@ impl RustBuildMeta<BinaryListState> {
             linked_paths: BTreeMap::new(),
             state: PhantomData,
             target_triple,
+            sysroot_libdir: None,
+            rustc_version: None,
+            features: None,
+            profile: None,
         }
     }
 
@@ -70,11 +90,31 @@ impl RustBuildMeta<BinaryListState> {
             non_test_binaries: self.non_test_binaries.clone(),
             linked_paths: self.linked_paths.clone(),
             state: PhantomData,
-            target_triple: self.target_triple.clone(),
+            target_triple: self
+                .target_triple
+                .as_ref()
+                .map(|triple| map_target_triple(triple, path_mapper)),
+            sysroot_libdir: self.sysroot_libdir.clone(),
+            rustc_version: self.rustc_version.clone(),
+            features: self.features.clone(),
+            profile: self.profile.clone(),
         }
     }
 }
 
+// Custom target spec files are stored at the root of the target directory within archives.
+fn map_target_triple(triple: &TargetTriple, path_mapper: &PathMapper) -> TargetTriple {
+    let mut triple = triple.clone();
+    if let (TargetDefinitionLocation::DirectFile(path), Some(new_target_dir)) =
+        (&mut triple.location, path_mapper.new_target_dir())
+    {
+        if let Some(file_name) = path.file_name() {
+            *path = new_target_dir.join(file_name);
+        }
+    }
+    triple
+}
+
 impl RustBuildMeta<TestListState> {
     /// Empty metadata for tests.
     #[cfg(test)]
@@ -86,6 +126,10 @@ impl RustBuildMeta<TestListState> {
             linked_paths: BTreeMap::new(),
             state: PhantomData,
             target_triple: None,
+            sysroot_libdir: None,
+            rustc_version: None,
+            features: None,
+            profile: None,
         }
     }
 
@@ -95,20 +139,23 @@ impl RustBuildMeta<TestListState> {
     ///
     /// These paths are prepended to the dynamic library environment variable for the current
     /// platform (e.g. `LD_LIBRARY_PATH` on non-Apple Unix platforms).
+    ///
+    /// On Windows, where `PATH` is used to find DLLs, this also includes the directories that
+    /// contain dynamic libraries built as part of the workspace.
+    ///
+    /// Some of these paths might not exist on disk. Whether each path exists is checked each time
+    /// a test process is set up, not here.
     pub fn dylib_paths(&self) -> Vec<Utf8PathBuf> {
-        // FIXME/HELP WANTED: get the rustc sysroot library path here.
-        // See https://github.com/nextest-rs/nextest/issues/267.
+        self.dylib_paths_impl(cfg!(windows))
+    }
 
-        // Cargo puts linked paths before base output directories.
-        self.linked_paths
+    fn dylib_paths_impl(&self, include_dll_dirs: bool) -> Vec<Utf8PathBuf> {
+        // Cargo puts linked paths before base output directories, and the sysroot library
+        // directory at the end.
+        let mut paths: Vec<_> = self
+            .linked_paths
             .keys()
-            .filter_map(|rel_path| {
-                let join_path = self
-                    .target_directory
-                    .join(convert_rel_path_to_main_sep(rel_path));
-                // Only add the directory to the path if it exists on disk.
-                join_path.exists().then(|| join_path)
-            })
+            .map(|rel_path| self.abs_linked_path(rel_path))
             .chain(self.base_output_directories.iter().flat_map(|base_output| {
                 let abs_base = self
                     .target_directory
@@ -117,13 +164,80 @@ impl RustBuildMeta<TestListState> {
                 // This is the order paths are added in by Cargo.
                 [with_deps, abs_base]
             }))
-            .collect()
+            .collect();
+
+        if include_dll_dirs {
+            // DLLs are looked up through PATH on Windows, so tests that depend on DLLs produced by
+            // the build (e.g. cdylibs copied into target/<profile>/examples) can't be started
+            // unless the directories they're in are part of it.
+            let dll_dirs: BTreeSet<_> = self
+                .non_test_binaries
+                .values()
+                .flatten()
+                .filter(|binary| {
+                    binary.kind == RustNonTestBinaryKind::DYLIB
+                        || binary.kind == RustNonTestBinaryKind::CDYLIB
+                })
+                .filter_map(|binary| {
+                    let abs_path = self
+                        .target_directory
+                        .join(convert_rel_path_to_main_sep(&binary.path));
+                    abs_path.parent().map(|parent| parent.to_path_buf())
+                })
+                .collect();
+            for dll_dir in dll_dirs {
+                if !paths.contains(&dll_dir) {
+                    paths.push(dll_dir);
+                }
+            }
+        }
+
+        paths.extend(self.sysroot_libdir.iter().cloned());
+        paths
+    }
+
+    /// Warns about linked paths that don't currently exist on disk.
+    pub(crate) fn warn_missing_linked_paths(&self) {
+        for (rel_path, package_ids) in &self.linked_paths {
+            let abs_path = self.abs_linked_path(rel_path);
+            if abs_path.exists() {
+                continue;
+            }
+
+            let mut s = String::new();
+            for package_id in package_ids {
+                s.push_str("  - ");
+                s.push_str(package_id);
+                s.push('\n');
+            }
+            if s.is_empty() {
+                // This happens with metadata produced by older versions of nextest, which didn't
+                // record the packages that requested each linked path.
+                log::warn!(
+                    target: "nextest-runner",
+                    "linked path `{abs_path}` doesn't exist, skipping it unless it's created \
+                     before tests are run",
+                );
+            } else {
+                log::warn!(
+                    target: "nextest-runner",
+                    "these crates link against `{abs_path}` which doesn't exist, skipping it \
+                     unless it's created before tests are run:\n{s}",
+                );
+            }
+        }
+    }
+
+    fn abs_linked_path(&self, rel_path: &Utf8Path) -> Utf8PathBuf {
+        self.target_directory
+            .join(convert_rel_path_to_main_sep(rel_path))
     }
 }
 
 impl<State> RustBuildMeta<State> {
     /// Creates a `RustBuildMeta` from a serializable summary.
     pub fn from_summary(summary: RustBuildMetaSummary) -> Self {
+        let mut linked_path_packages = summary.linked_path_packages;
         Self {
             target_directory: summary.target_directory,
             base_output_directories: summary.base_output_directories,
@@ -131,10 +245,24 @@ impl<State> RustBuildMeta<State> {
             linked_paths: summary
                 .linked_paths
                 .into_iter()
-                .map(|linked_path| (linked_path, BTreeSet::new()))
+                .map(|linked_path| {
+                    // Metadata produced by older versions of nextest doesn't have the packages
+                    // that requested each linked path.
+                    let packages = linked_path_packages
+                        .remove(&linked_path)
+                        .unwrap_or_default();
+                    (linked_path, packages)
+                })
                 .collect(),
             state: PhantomData,
-            target_triple: TargetTriple::deserialize(summary.target_triple),
+            target_triple: TargetTriple::deserialize(
+                summary.target_triple,
+                summary.target_spec_file,
+            ),
+            sysroot_libdir: None,
+            rustc_version: summary.rustc_version,
+            features: summary.features,
+            profile: summary.profile,
         }
     }
 
@@ -145,7 +273,147 @@ impl<State> RustBuildMeta<State> {
             base_output_directories: self.base_output_directories.clone(),
             non_test_binaries: self.non_test_binaries.clone(),
             linked_paths: self.linked_paths.keys().cloned().collect(),
+            linked_path_packages: self.linked_paths.clone(),
             target_triple: TargetTriple::serialize(self.target_triple.as_ref()),
+            target_spec_file: self
+                .target_triple
+                .as_ref()
+                .and_then(|triple| triple.target_spec_file())
+                .map(|path| path.to_owned()),
+            rustc_version: self.rustc_version.clone(),
+            features: self.features.clone(),
+            profile: self.profile.clone(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::{btreemap, btreeset};
+
+    #[test]
+    fn test_dylib_paths_sysroot_libdir() {
+        let sysroot_dir = tempfile::tempdir().expect("created temp dir");
+        let sysroot_libdir: Utf8PathBuf = sysroot_dir
+            .path()
+            .to_path_buf()
+            .try_into()
+            .expect("temp dir is valid UTF-8");
+
+        let mut rust_build_meta = RustBuildMeta::new("/fake/target", None);
+        rust_build_meta
+            .base_output_directories
+            .insert("debug".into());
+        let mut rust_build_meta = rust_build_meta.map_paths(&PathMapper::noop());
+        rust_build_meta.sysroot_libdir = Some(sysroot_libdir.clone());
+
+        let target_dir = Utf8PathBuf::from("/fake/target");
+        assert_eq!(
+            rust_build_meta.dylib_paths(),
+            vec![
+                target_dir.join("debug").join("deps"),
+                target_dir.join("debug"),
+                sysroot_libdir,
+            ],
+            "sysroot libdir is added after base output directories"
+        );
+    }
+
+    #[test]
+    fn test_dylib_paths_dll_dirs() {
+        let mut rust_build_meta = RustBuildMeta::new("/fake/target", None);
+        rust_build_meta
+            .base_output_directories
+            .insert("debug".into());
+        rust_build_meta.non_test_binaries.insert(
+            "my-package-id".to_owned(),
+            btreeset! {
+                RustNonTestBinarySummary {
+                    name: "my-cdylib".to_owned(),
+                    kind: RustNonTestBinaryKind::DYLIB,
+                    path: "debug/my_cdylib.dll".into(),
+                },
+                RustNonTestBinarySummary {
+                    name: "my-example-cdylib".to_owned(),
+                    kind: RustNonTestBinaryKind::DYLIB,
+                    path: "debug/examples/my_example_cdylib.dll".into(),
+                },
+                RustNonTestBinarySummary {
+                    name: "my-workspace-cdylib".to_owned(),
+                    kind: RustNonTestBinaryKind::CDYLIB,
+                    path: "debug/cdylib-dir/my_workspace_cdylib.dll".into(),
+                },
+                RustNonTestBinarySummary {
+                    name: "my-bin".to_owned(),
+                    kind: RustNonTestBinaryKind::BIN_EXE,
+                    path: "debug/bin-dir/my-bin.exe".into(),
+                },
+            },
+        );
+        let rust_build_meta = rust_build_meta.map_paths(&PathMapper::noop());
+
+        let target_dir = Utf8PathBuf::from("/fake/target");
+        let base_paths = vec![
+            target_dir.join("debug").join("deps"),
+            target_dir.join("debug"),
+        ];
+        assert_eq!(
+            rust_build_meta.dylib_paths_impl(false),
+            base_paths,
+            "DLL directories aren't included"
+        );
+
+        let mut with_dll_dirs = base_paths;
+        with_dll_dirs.push(target_dir.join("debug").join("cdylib-dir"));
+        with_dll_dirs.push(target_dir.join("debug").join("examples"));
+        assert_eq!(
+            rust_build_meta.dylib_paths_impl(true),
+            with_dll_dirs,
+            "directories with DLLs are added once, and directories with only executables are skipped"
+        );
+    }
+
+    #[test]
+    fn test_from_summary_without_linked_path_packages() {
+        // Metadata produced by nextest 0.9.34 and below doesn't have linked-path-packages.
+        let summary: RustBuildMetaSummary = serde_json::from_str(
+            r#"{
+                "target-directory": "/fake/target",
+                "base-output-directories": ["debug"],
+                "non-test-binaries": {},
+                "linked-paths": ["debug/build/foo/out"],
+                "target-triple": ""
+            }"#,
+        )
+        .expect("summary without linked-path-packages deserializes");
+        let rust_build_meta = RustBuildMeta::<BinaryListState>::from_summary(summary);
+        assert_eq!(
+            rust_build_meta.linked_paths,
+            btreemap! { "debug/build/foo/out".into() => BTreeSet::new() },
+        );
+
+        let summary: RustBuildMetaSummary = serde_json::from_str(
+            r#"{
+                "target-directory": "/fake/target",
+                "base-output-directories": ["debug"],
+                "non-test-binaries": {},
+                "linked-paths": ["debug/build/foo/out"],
+                "linked-path-packages": {
+                    "debug/build/foo/out": ["foo 0.1.0 (path+file:///fake/foo)"]
+                },
+                "target-triple": ""
+            }"#,
+        )
+        .expect("summary with linked-path-packages deserializes");
+        let rust_build_meta = RustBuildMeta::<BinaryListState>::from_summary(summary);
+        assert_eq!(
+            rust_build_meta.linked_paths,
+            btreemap! {
+                "debug/build/foo/out".into() => btreeset! {
+                    "foo 0.1.0 (path+file:///fake/foo)".to_owned()
+                },
+            },
+        );
+    }
+}
@@ -3,9 +3,10 @@
 
 use crate::{
     errors::{CreateTestListError, FromMessagesError, WriteTestListError},
-    helpers::{dylib_path, dylib_path_envvar, write_test_name},
+    helpers::{convert_build_platform, dylib_path, dylib_path_envvar, write_test_name},
     list::{BinaryList, OutputFormat, RustBuildMeta, Styles, TestListState},
     reuse_build::PathMapper,
+    run_store::TestTimings,
     target_runner::{PlatformRunner, TargetRunner},
     test_filter::TestFilterBuilder,
 };
@@ -15,21 +16,28 @@ use guppy::{
     graph::{PackageGraph, PackageMetadata},
     PackageId,
 };
+use nextest_filtering::BinaryQuery;
 use nextest_metadata::{
-    BuildPlatform, RustNonTestBinaryKind, RustTestBinaryKind, RustTestBinarySummary,
-    RustTestCaseSummary, RustTestSuiteStatusSummary, RustTestSuiteSummary, TestListSummary,
+    BuildPlatform, HarnessTestListSummary, RustDoctestSummary, RustNonTestBinaryKind,
+    RustTestBinaryKind, RustTestBinarySummary, RustTestCaseSummary, RustTestLocationSummary,
+    RustTestSuiteStatusSummary, RustTestSuiteSummary, TestListSummary,
 };
 use once_cell::sync::{Lazy, OnceCell};
 use owo_colors::OwoColorize;
+use serde::{Deserialize, Serialize};
 use std::{
+    cmp::Reverse,
     collections::{BTreeMap, BTreeSet, HashMap},
     ffi::{OsStr, OsString},
+    hash::{Hash, Hasher},
     io,
     io::Write,
     path::PathBuf,
     sync::Arc,
+    time::SystemTime,
 };
-use tokio::runtime::Runtime;
+use tokio::{runtime::Runtime, sync::Semaphore, task::JoinHandle};
+use twox_hash::XxHash64;
 
 /// A Rust test binary built by Cargo. This artifact hasn't been run yet so there's no information
 /// about the tests within it.
@@ -56,11 +64,32 @@ pub struct RustTestArtifact<'g> {
     /// Non-test binaries to be exposed to this artifact at runtime (name, path).
     pub non_test_binaries: BTreeSet<(String, Utf8PathBuf)>,
 
+    /// Binaries from artifact dependencies to be exposed to this artifact at runtime
+    /// (environment variable name, path).
+    pub artifact_binaries: BTreeSet<(String, Utf8PathBuf)>,
+
+    /// cdylibs built by workspace packages, to be exposed to this artifact at runtime
+    /// (name, path).
+    pub cdylibs: BTreeSet<(String, Utf8PathBuf)>,
+
     /// The working directory that this test should be executed in.
     pub cwd: Utf8PathBuf,
 
     /// The platform for which this test artifact was built.
     pub build_platform: BuildPlatform,
+
+    /// For doctests, information about the doctest this artifact was compiled from.
+    pub doctest: Option<RustDoctestSummary>,
+
+    /// The Cargo features the package was built with, as matched by the `feature()` predicate.
+    pub features: Vec<String>,
+
+    /// If true, this binary is run once as a single test named after the binary, rather than being
+    /// queried for the tests it contains. Set through the `single-test` override.
+    pub single_test: bool,
+
+    /// Extra environment variables to run this artifact with.
+    pub env: BTreeMap<String, String>,
 }
 
 impl<'g> RustTestArtifact<'g> {
@@ -74,6 +103,19 @@ impl<'g> RustTestArtifact<'g> {
     ) -> Result<Vec<Self>, FromMessagesError> {
         let mut binaries = vec![];
 
+        // cdylibs built by workspace packages are exposed to all test binaries, so that they can
+        // be loaded at runtime.
+        let cdylibs: BTreeSet<_> = rust_build_meta
+            .non_test_binaries
+            .values()
+            .flatten()
+            .filter(|binary| binary.kind == RustNonTestBinaryKind::CDYLIB)
+            .map(|binary| {
+                let abs_path = rust_build_meta.target_directory.join(&binary.path);
+                (binary.name.clone(), abs_path)
+            })
+            .collect();
+
         for binary in &binary_list.rust_binaries {
             if platform_filter.is_some() && platform_filter != Some(binary.build_platform) {
                 continue;
@@ -124,6 +166,32 @@ impl<'g> RustTestArtifact<'g> {
                 BTreeSet::new()
             };
 
+            // Binaries built as artifact dependencies are exposed to all the test binaries in
+            // packages that depend on them.
+            let mut artifact_binaries = BTreeSet::new();
+            for link in package.direct_links() {
+                let binaries = match rust_build_meta.non_test_binaries.get(link.to().id().repr()) {
+                    Some(binaries) => binaries,
+                    None => continue,
+                };
+                let var_prefix = format!(
+                    "CARGO_BIN_FILE_{}",
+                    link.dep_name().to_ascii_uppercase().replace('-', "_")
+                );
+                for binary in binaries {
+                    if binary.kind != RustNonTestBinaryKind::BIN_ARTIFACT {
+                        continue;
+                    }
+                    let abs_path = rust_build_meta.target_directory.join(&binary.path);
+                    // Cargo also sets the variable without the binary name if the binary has
+                    // the same name as the package.
+                    if binary.name == link.to().name() {
+                        artifact_binaries.insert((var_prefix.clone(), abs_path.clone()));
+                    }
+                    artifact_binaries.insert((format!("{}_{}", var_prefix, binary.name), abs_path));
+                }
+            }
+
             binaries.push(RustTestArtifact {
                 binary_id: binary.id.clone(),
                 package,
@@ -132,13 +200,32 @@ impl<'g> RustTestArtifact<'g> {
                 kind: binary.kind.clone(),
                 cwd,
                 non_test_binaries,
+                artifact_binaries,
+                cdylibs: cdylibs.clone(),
                 build_platform: binary.build_platform,
+                doctest: binary.doctest.clone(),
+                features: binary.features.clone(),
+                single_test: false,
+                env: binary.env.clone(),
             })
         }
 
         Ok(binaries)
     }
 
+    /// Returns a [`BinaryQuery`] for this artifact, used to evaluate filter expressions against it.
+    pub fn to_binary_query(&self) -> BinaryQuery<'_> {
+        BinaryQuery {
+            package_id: self.package.id(),
+            binary_id: &self.binary_id,
+            kind: self.kind.as_str(),
+            features: &self.features,
+            binary_name: &self.binary_name,
+            binary_path: &self.binary_path,
+            platform: convert_build_platform(self.build_platform),
+        }
+    }
+
     // ---
     // Helper methods
     // ---
@@ -150,8 +237,14 @@ impl<'g> RustTestArtifact<'g> {
             binary_name,
             kind,
             non_test_binaries,
+            artifact_binaries,
+            cdylibs,
             cwd,
             build_platform,
+            doctest,
+            features,
+            single_test,
+            env,
         } = self;
         (
             binary_path,
@@ -161,8 +254,15 @@ impl<'g> RustTestArtifact<'g> {
                 binary_name,
                 kind,
                 non_test_binaries,
+                artifact_binaries,
+                cdylibs,
                 cwd,
                 build_platform,
+                doctest,
+                features,
+                single_test,
+                env,
+                target_triple: None,
                 status,
             },
         )
@@ -174,8 +274,9 @@ impl<'g> RustTestArtifact<'g> {
 pub struct TestList<'g> {
     test_count: usize,
     rust_build_meta: RustBuildMeta<TestListState>,
+    // Build metadata for each target triple, if this list was merged from lists for several targets.
+    target_build_metas: BTreeMap<String, RustBuildMeta<TestListState>>,
     rust_suites: BTreeMap<Utf8PathBuf, RustTestSuite<'g>>,
-    updated_dylib_path: OsString,
     // Computed on first access.
     skip_count: OnceCell<usize>,
 }
@@ -193,49 +294,53 @@ impl<'g> TestList<'g> {
         I: IntoIterator<Item = RustTestArtifact<'g>>,
         I::IntoIter: Send,
     {
-        let updated_dylib_path = Self::create_dylib_path(&rust_build_meta)?;
-        log::debug!(
-            "updated {}: {}",
-            dylib_path_envvar(),
-            updated_dylib_path.to_string_lossy(),
-        );
-
-        let runtime = Runtime::new().map_err(CreateTestListError::TokioRuntimeCreate)?;
-
-        let stream = futures::stream::iter(test_artifacts.into_iter()).map(|test_binary| {
-            async {
-                if filter.should_obtain_test_list_from_binary(&test_binary) {
-                    // Run the binary to obtain the test list.
-                    let (non_ignored, ignored) =
-                        test_binary.exec(&updated_dylib_path, runner).await?;
-                    let (bin, info) = Self::process_output(
-                        test_binary,
-                        filter,
-                        non_ignored.as_str(),
-                        ignored.as_str(),
-                    )?;
-                    Ok::<_, CreateTestListError>((bin, info))
-                } else {
-                    // Skipped means no tests, so test_count doesn't need to be modified.
-                    Ok(Self::process_skipped(test_binary))
-                }
-            }
-        });
-        let fut = stream.buffer_unordered(list_threads).try_collect();
+        Self::new_with_prefetcher(
+            test_artifacts,
+            rust_build_meta,
+            filter,
+            runner,
+            &mut TestListPrefetcher::new(list_threads)?,
+        )
+    }
 
-        let rust_suites: BTreeMap<_, _> = runtime.block_on(fut)?;
-        let test_count = rust_suites
-            .values()
-            .map(|suite| suite.status.test_count())
-            .sum();
+    /// Creates a new test list, reusing the output of binaries listed ahead of time by
+    /// `prefetcher`.
+    ///
+    /// Binaries that weren't listed ahead of time, or were listed differently from how they'd be
+    /// listed now, are listed the same way as with [`TestList::new`], with as many binaries listed
+    /// at a time as `prefetcher` lists. The same prefetcher can be used to create several test
+    /// lists, for example one per target triple.
+    pub fn new_with_prefetcher<I>(
+        test_artifacts: I,
+        rust_build_meta: RustBuildMeta<TestListState>,
+        filter: &TestFilterBuilder,
+        runner: &TargetRunner,
+        prefetcher: &mut TestListPrefetcher,
+    ) -> Result<Self, CreateTestListError>
+    where
+        I: IntoIterator<Item = RustTestArtifact<'g>>,
+        I::IntoIter: Send,
+    {
+        let fut = prefetcher.test_list_future(test_artifacts, rust_build_meta, filter, runner);
+        prefetcher.runtime.block_on(fut)
+    }
 
-        Ok(Self {
-            rust_suites,
+    /// Creates a test list with no tests in it, to be run while the tests in it are still being
+    /// listed.
+    ///
+    /// The test lists passed in to [`TestRunner::try_execute_while_listing`] afterwards are run
+    /// along with this one. `rust_build_meta` is used to report on the run as a whole, for example
+    /// the target triple in JUnit reports.
+    ///
+    /// [`TestRunner::try_execute_while_listing`]: crate::runner::TestRunner::try_execute_while_listing
+    pub fn new_empty(rust_build_meta: RustBuildMeta<TestListState>) -> Self {
+        Self {
+            test_count: 0,
             rust_build_meta,
-            updated_dylib_path,
-            test_count,
+            target_build_metas: BTreeMap::new(),
+            rust_suites: BTreeMap::new(),
             skip_count: OnceCell::new(),
-        })
+        }
     }
 
     /// Creates a new test list with the given binary names and outputs.
@@ -249,17 +354,23 @@ impl<'g> TestList<'g> {
     ) -> Result<Self, CreateTestListError> {
         let mut test_count = 0;
 
-        let updated_dylib_path = Self::create_dylib_path(&rust_build_meta)?;
+        // This is called for its side effects: checking that the dylib paths can be joined.
+        Self::create_dylib_path(&rust_build_meta)?;
 
         let test_artifacts = test_bin_outputs
             .into_iter()
             .map(|(test_binary, non_ignored, ignored)| {
-                if filter.should_obtain_test_list_from_binary(&test_binary) {
+                if test_binary.doctest.is_some() || test_binary.single_test {
+                    let (bin, info) = Self::process_single_test(test_binary, filter);
+                    test_count += info.status.test_count();
+                    Ok((bin, info))
+                } else if filter.should_obtain_test_list_from_binary(&test_binary) {
                     let (bin, info) = Self::process_output(
                         test_binary,
                         filter,
                         non_ignored.as_ref(),
                         ignored.as_ref(),
+                        &HashMap::new(),
                     )?;
                     test_count += info.status.test_count();
                     Ok((bin, info))
@@ -273,12 +384,45 @@ impl<'g> TestList<'g> {
         Ok(Self {
             rust_suites: test_artifacts,
             rust_build_meta,
-            updated_dylib_path,
+            target_build_metas: BTreeMap::new(),
             test_count,
             skip_count: OnceCell::new(),
         })
     }
 
+    /// Merges test lists for several target triples into one, for test runs across more than one
+    /// target.
+    ///
+    /// Each test list must have been created with the build metadata for a single target triple,
+    /// or for the host platform, e.g. from the lists returned by [`BinaryList::split_by_target`].
+    /// The target triple is recorded in each test suite built for the target platform, and is
+    /// appended to its binary ID as `@<triple>` so that binary IDs stay unique. Test suites built
+    /// for the host platform are left as they are.
+    ///
+    /// The build metadata of the first list is returned by [`Self::rust_build_meta`], and is used
+    /// for test suites without a target triple. Pass in the host platform's list first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `test_lists` is empty.
+    pub fn merge(test_lists: impl IntoIterator<Item = TestList<'g>>) -> Self {
+        let mut test_lists = test_lists.into_iter();
+        let mut merged = test_lists
+            .next()
+            .expect("at least one test list must be passed in")
+            .with_target_triple();
+
+        for test_list in test_lists {
+            let test_list = test_list.with_target_triple();
+            merged.test_count += test_list.test_count;
+            merged
+                .target_build_metas
+                .extend(test_list.target_build_metas);
+            merged.rust_suites.extend(test_list.rust_suites);
+        }
+        merged
+    }
+
     /// Returns the total number of tests across all binaries.
     pub fn test_count(&self) -> usize {
         self.test_count
@@ -316,8 +460,19 @@ impl<'g> TestList<'g> {
     }
 
     /// Returns the updated dynamic library path used for tests.
-    pub fn updated_dylib_path(&self) -> &OsStr {
-        &self.updated_dylib_path
+    ///
+    /// Directories are only included if they exist on disk at the time this is called, so the
+    /// result might change if directories are created or removed after the test list is built.
+    pub fn updated_dylib_path(&self) -> OsString {
+        Self::join_existing_dylib_paths(&self.rust_build_meta.dylib_paths())
+    }
+
+    /// Returns the updated dynamic library path used for tests in the given suite.
+    ///
+    /// This is different from [`Self::updated_dylib_path`] if this list was merged from test lists
+    /// for several target triples.
+    pub fn updated_dylib_path_for(&self, test_suite: &RustTestSuite<'_>) -> OsString {
+        Self::join_existing_dylib_paths(&self.rust_build_meta_for(test_suite).dylib_paths())
     }
 
     /// Constructs a serializble summary for this test list.
@@ -336,6 +491,8 @@ impl<'g> TestList<'g> {
                         binary_path: binary_path.clone(),
                         binary_id: info.binary_id.clone(),
                         build_platform: info.build_platform,
+                        doctest: info.doctest.clone(),
+                        features: info.features.clone(),
                     },
                     cwd: info.cwd.clone(),
                     status,
@@ -386,6 +543,16 @@ impl<'g> TestList<'g> {
         })
     }
 
+    /// Returns the list of tests, ordered by how long they took the last time they were run.
+    ///
+    /// The slowest tests come first, followed by untimed tests in the order they're listed in.
+    pub fn tests_slowest_first(&self, timings: &TestTimings) -> Vec<TestInstance<'_>> {
+        let mut tests: Vec<_> = self.iter_tests().collect();
+        // The sort is stable, so tests that took as long as each other stay in listing order.
+        tests.sort_by_key(|test| Reverse(timings.get(&test.bin_info.binary_id, test.name)));
+        tests
+    }
+
     /// Outputs this list as a string with the given format.
     pub fn to_string(&self, output_format: OutputFormat) -> Result<String, WriteTestListError> {
         // Ugh this sucks. String really should have an io::Write impl that errors on non-UTF8 text.
@@ -398,30 +565,83 @@ impl<'g> TestList<'g> {
     // Helper methods
     // ---
 
+    // Records this list's target triple in its target platform suites and build metadata map, for
+    // merging.
+    fn with_target_triple(mut self) -> Self {
+        let triple = match &self.rust_build_meta.target_triple {
+            Some(triple) => triple.triple.clone(),
+            None => return self,
+        };
+        for suite in self.rust_suites.values_mut() {
+            if suite.build_platform == BuildPlatform::Target {
+                suite.binary_id = format!("{}@{}", suite.binary_id, triple);
+                suite.target_triple = Some(triple.clone());
+            }
+        }
+        self.target_build_metas
+            .insert(triple, self.rust_build_meta.clone());
+        self
+    }
+
+    fn rust_build_meta_for(&self, test_suite: &RustTestSuite<'_>) -> &RustBuildMeta<TestListState> {
+        test_suite
+            .target_triple
+            .as_ref()
+            .and_then(|triple| self.target_build_metas.get(triple))
+            .unwrap_or(&self.rust_build_meta)
+    }
+
     // Empty list for tests.
     #[cfg(test)]
     pub(crate) fn empty() -> Self {
+        Self::new_empty(RustBuildMeta::empty())
+    }
+
+    // List with these test suites, keyed by binary path, for tests.
+    #[cfg(test)]
+    pub(crate) fn with_suites(
+        rust_suites: impl IntoIterator<Item = (Utf8PathBuf, RustTestSuite<'g>)>,
+    ) -> Self {
+        let rust_suites: BTreeMap<_, _> = rust_suites.into_iter().collect();
+        let test_count = rust_suites
+            .values()
+            .map(|suite| suite.status.test_count())
+            .sum();
         Self {
-            test_count: 0,
-            rust_build_meta: RustBuildMeta::empty(),
-            updated_dylib_path: OsString::new(),
-            rust_suites: BTreeMap::new(),
-            skip_count: OnceCell::new(),
+            test_count,
+            rust_suites,
+            ..Self::empty()
         }
     }
 
     pub(crate) fn create_dylib_path(
         rust_build_meta: &RustBuildMeta<TestListState>,
     ) -> Result<OsString, CreateTestListError> {
+        let new_paths = rust_build_meta.dylib_paths();
+        // Check that all the paths can be joined, including ones that don't exist yet. This means
+        // that joining paths later (once some of them might have been created) can't fail.
+        if let Err(error) = std::env::join_paths(&new_paths) {
+            return Err(CreateTestListError::dylib_join_paths(new_paths, error));
+        }
+        rust_build_meta.warn_missing_linked_paths();
+
+        Ok(Self::join_existing_dylib_paths(&new_paths))
+    }
+
+    fn join_existing_dylib_paths(new_paths: &[Utf8PathBuf]) -> OsString {
         let dylib_path = dylib_path();
         let dylib_path_is_empty = dylib_path.is_empty();
-        let new_paths = rust_build_meta.dylib_paths();
 
         let mut updated_dylib_path: Vec<PathBuf> =
             Vec::with_capacity(dylib_path.len() + new_paths.len());
         updated_dylib_path.extend(
             new_paths
                 .iter()
+                // Only add directories to the path if they exist on disk. This is checked each time
+                // the path is created rather than once while building the test list, because
+                // directories like linked paths might be created after that (e.g. if an archive
+                // is extracted on another machine).
+                .filter(|path| path.exists())
                 .map(|path| path.clone().into_std_path_buf()),
         );
         updated_dylib_path.extend(dylib_path);
@@ -441,7 +661,7 @@ impl<'g> TestList<'g> {
         }
 
         std::env::join_paths(updated_dylib_path)
-            .map_err(move |error| CreateTestListError::dylib_join_paths(new_paths, error))
+            .expect("dylib paths were checked while creating the test list")
     }
 
     fn process_output(
@@ -449,24 +669,74 @@ impl<'g> TestList<'g> {
         filter: &TestFilterBuilder,
         non_ignored: impl AsRef<str>,
         ignored: impl AsRef<str>,
+        test_details: &HashMap<String, TestDetails>,
     ) -> Result<(Utf8PathBuf, RustTestSuite<'g>), CreateTestListError> {
+        let harness_list = Self::parse_harness_json(&test_binary.binary_id, non_ignored.as_ref())?;
+        let harness_details: HashMap<_, _>;
+        let test_details = match &harness_list {
+            Some(harness_list) => {
+                harness_details = harness_list
+                    .tests
+                    .iter()
+                    .map(|test| {
+                        let details = TestDetails {
+                            ignore_reason: test.ignore_reason.clone(),
+                            location: test.location.clone(),
+                        };
+                        (test.name.clone(), details)
+                    })
+                    .collect();
+                &harness_details
+            }
+            None => test_details,
+        };
+        let (non_ignored, ignored) = match &harness_list {
+            Some(harness_list) => {
+                // Split the JSON list up the same way as libtest's output: all tests, then just the
+                // ignored ones.
+                let mut non_ignored: Vec<_> = harness_list
+                    .tests
+                    .iter()
+                    .map(|test| test.name.as_str())
+                    .collect();
+                non_ignored.sort_unstable();
+                let mut ignored: Vec<_> = harness_list
+                    .tests
+                    .iter()
+                    .filter(|test| test.ignored)
+                    .map(|test| test.name.as_str())
+                    .collect();
+                ignored.sort_unstable();
+                (non_ignored, ignored)
+            }
+            None => (
+                Self::parse(&test_binary.binary_id, non_ignored.as_ref())?,
+                Self::parse(&test_binary.binary_id, ignored.as_ref())?,
+            ),
+        };
+
         let mut test_cases = BTreeMap::new();
 
         // Treat ignored and non-ignored as separate sets of single filters, so that partitioning
         // based on one doesn't affect the other.
         let mut non_ignored_filter = filter.build();
-        for test_name in Self::parse(&test_binary.binary_id, non_ignored.as_ref())? {
+        for test_name in non_ignored {
             test_cases.insert(
                 test_name.into(),
                 RustTestCaseSummary {
                     ignored: false,
+                    ignore_reason: None,
+                    location: test_details
+                        .get(test_name)
+                        .and_then(|details| details.location.clone()),
+                    tags: filter.test_tags_for(&test_binary, test_name),
                     filter_match: non_ignored_filter.filter_match(&test_binary, test_name, false),
                 },
             );
         }
 
         let mut ignored_filter = filter.build();
-        for test_name in Self::parse(&test_binary.binary_id, ignored.as_ref())? {
+        for test_name in ignored {
             // Note that libtest prints out:
             // * just ignored tests if --ignored is passed in
             // * all tests, both ignored and non-ignored, if --ignored is not passed in
@@ -475,6 +745,13 @@ impl<'g> TestList<'g> {
                 test_name.into(),
                 RustTestCaseSummary {
                     ignored: true,
+                    ignore_reason: test_details
+                        .get(test_name)
+                        .and_then(|details| details.ignore_reason.clone()),
+                    location: test_details
+                        .get(test_name)
+                        .and_then(|details| details.location.clone()),
+                    tags: filter.test_tags_for(&test_binary, test_name),
                     filter_match: ignored_filter.filter_match(&test_binary, test_name, true),
                 },
             );
@@ -483,10 +760,116 @@ impl<'g> TestList<'g> {
         Ok(test_binary.into_test_suite(RustTestSuiteStatus::Listed { test_cases }))
     }
 
+    // Each doctest binary runs a single doctest and isn't a libtest binary, so it can't be listed.
+    // Binaries marked as single tests are treated the same way, and are named after the binary.
+    fn process_single_test(
+        test_binary: RustTestArtifact<'g>,
+        filter: &TestFilterBuilder,
+    ) -> (Utf8PathBuf, RustTestSuite<'g>) {
+        let test_name = match &test_binary.doctest {
+            Some(doctest) => doctest.name.clone(),
+            None => test_binary.binary_name.clone(),
+        };
+        let filter_match = filter.build().filter_match(&test_binary, &test_name, false);
+        let tags = filter.test_tags_for(&test_binary, &test_name);
+        let mut test_cases = BTreeMap::new();
+        test_cases.insert(
+            test_name,
+            RustTestCaseSummary {
+                ignored: false,
+                ignore_reason: None,
+                location: None,
+                tags,
+                filter_match,
+            },
+        );
+
+        test_binary.into_test_suite(RustTestSuiteStatus::Listed { test_cases })
+    }
+
     fn process_skipped(test_binary: RustTestArtifact<'g>) -> (Utf8PathBuf, RustTestSuite<'g>) {
         test_binary.into_test_suite(RustTestSuiteStatus::Skipped)
     }
 
+    fn is_harness_json(list_output: &str) -> bool {
+        // The terse format has a "<test name>: test" line for each test, and test names don't start
+        // with "{" in practice.
+        list_output.trim_start().starts_with('{')
+    }
+
+    /// Parses the JSON test list printed out by custom test harnesses that implement nextest's list
+    /// format, or returns `None` if `list_output` isn't in that format.
+    fn parse_harness_json(
+        binary_id: &str,
+        list_output: &str,
+    ) -> Result<Option<HarnessTestListSummary>, CreateTestListError> {
+        if !Self::is_harness_json(list_output) {
+            return Ok(None);
+        }
+
+        let harness_list: HarnessTestListSummary =
+            serde_json::from_str(list_output).map_err(|err| {
+                CreateTestListError::parse_line(
+                    binary_id,
+                    format!("error parsing JSON test list: {err}"),
+                    list_output,
+                )
+            })?;
+        if harness_list.format_version != HarnessTestListSummary::FORMAT_VERSION {
+            return Err(CreateTestListError::parse_line(
+                binary_id,
+                format!(
+                    "JSON test list has format version {}, but only version {} is supported",
+                    harness_list.format_version,
+                    HarnessTestListSummary::FORMAT_VERSION,
+                ),
+                list_output,
+            ));
+        }
+        Ok(Some(harness_list))
+    }
+
+    /// Parses the details of tests out of libtest's (unstable) JSON list format, which has a line
+    /// like this for each test:
+    ///
+    /// ```text
+    /// { "type": "test", "event": "discovered", "name": "foo", "ignore": true, "ignore_message": "reason", "source_path": "src/lib.rs", "start_line": 3, "start_col": 4, ... }
+    /// ```
+    ///
+    /// Lines that can't be parsed, or that don't carry any details, are skipped.
+    fn parse_libtest_json_list(list_output: &str) -> HashMap<String, TestDetails> {
+        #[derive(Deserialize)]
+        struct LibtestListLine {
+            #[serde(rename = "type")]
+            ty: String,
+            name: String,
+            #[serde(default)]
+            ignore_message: String,
+            source_path: Option<Utf8PathBuf>,
+            start_line: Option<u32>,
+            start_col: Option<u32>,
+        }
+
+        list_output
+            .lines()
+            .filter_map(|line| serde_json::from_str::<LibtestListLine>(line).ok())
+            .filter(|line| line.ty == "test")
+            .filter_map(|line| {
+                let location = match (line.source_path, line.start_line, line.start_col) {
+                    (Some(path), Some(line), Some(column)) => {
+                        Some(RustTestLocationSummary { path, line, column })
+                    }
+                    _ => None,
+                };
+                let details = TestDetails {
+                    ignore_reason: (!line.ignore_message.is_empty()).then(|| line.ignore_message),
+                    location,
+                };
+                (details != TestDetails::default()).then(|| (line.name, details))
+            })
+            .collect()
+    }
+
     /// Parses the output of --list --format terse and returns a sorted list.
     fn parse<'a>(
         binary_id: &'a str,
@@ -563,11 +946,19 @@ impl<'g> TestList<'g> {
                             match (verbose, info.filter_match.is_match()) {
                                 (_, true) => {
                                     write_test_name(name, &styles, &mut indented)?;
+                                    if let Some(reason) = &info.ignore_reason {
+                                        write!(indented, " (ignored: {})", reason)?;
+                                    }
                                     writeln!(indented)?;
                                 }
                                 (true, false) => {
                                     write_test_name(name, &styles, &mut indented)?;
-                                    writeln!(indented, " (skipped)")?;
+                                    match &info.ignore_reason {
+                                        Some(reason) => {
+                                            writeln!(indented, " (skipped, ignored: {})", reason)?
+                                        }
+                                        None => writeln!(indented, " (skipped)")?,
+                                    }
                                 }
                                 (false, false) => {
                                     // Skip printing this test entirely if it isn't a match.
@@ -615,6 +1006,29 @@ pub struct RustTestSuite<'g> {
     /// Non-test binaries corresponding to this test suite (name, path).
     pub non_test_binaries: BTreeSet<(String, Utf8PathBuf)>,
 
+    /// Binaries from artifact dependencies corresponding to this test suite
+    /// (environment variable name, path).
+    pub artifact_binaries: BTreeSet<(String, Utf8PathBuf)>,
+
+    /// cdylibs built by workspace packages, exposed to this test suite (name, path).
+    pub cdylibs: BTreeSet<(String, Utf8PathBuf)>,
+
+    /// For doctests, information about the doctest this test suite was compiled from.
+    pub doctest: Option<RustDoctestSummary>,
+
+    /// The Cargo features the package was built with.
+    pub features: Vec<String>,
+
+    /// If true, the binary is run once as a single test. Set through the `single-test` override.
+    pub single_test: bool,
+
+    /// Extra environment variables to run this test suite with.
+    pub env: BTreeMap<String, String>,
+
+    /// The target triple of the build this test suite is a part of, if tests are being run for more
+    /// than one target. Set by [`TestList::merge`].
+    pub target_triple: Option<String>,
+
     /// Test suite status and test case names.
     pub status: RustTestSuiteStatus,
 }
@@ -625,7 +1039,55 @@ impl<'g> RustTestArtifact<'g> {
         &self,
         dylib_path: &OsStr,
         runner: &TargetRunner,
-    ) -> Result<(String, String), CreateTestListError> {
+        cache: Option<&ListCache>,
+    ) -> Result<ListOutput, CreateTestListError> {
+        self.list_commands(dylib_path, runner)?.run(cache).await
+    }
+
+    /// Uses the output of listing this binary ahead of time if it was listed the same way, and
+    /// lists it again otherwise.
+    async fn exec_prefetched(
+        &self,
+        prefetched: PrefetchedList,
+        dylib_path: &OsStr,
+        runner: &TargetRunner,
+        cache: Option<&ListCache>,
+    ) -> Result<ListOutput, CreateTestListError> {
+        let commands = self.list_commands(dylib_path, runner)?;
+        if prefetched.inputs == commands.inputs {
+            match prefetched.handle.await {
+                Ok(Ok(output)) => return Ok(output),
+                Ok(Err(err)) => {
+                    log::debug!(
+                        "for {}, listing tests ahead of time failed, listing again: {}",
+                        self.binary_id,
+                        err
+                    );
+                }
+                Err(err) => {
+                    log::debug!(
+                        "for {}, listing tests ahead of time panicked, listing again: {}",
+                        self.binary_id,
+                        err
+                    );
+                }
+            }
+        } else {
+            log::debug!(
+                "for {}, binary was listed ahead of time with different inputs, listing again",
+                self.binary_id
+            );
+            prefetched.handle.abort();
+        }
+        commands.run(cache).await
+    }
+
+    /// Returns the commands to run to list the tests in this binary.
+    fn list_commands(
+        &self,
+        dylib_path: &OsStr,
+        runner: &TargetRunner,
+    ) -> Result<ListCommands, CreateTestListError> {
         // This error situation has been known to happen with reused builds. It produces
         // a really terrible and confusing "file not found" message if allowed to prceed.
         if !self.cwd.is_dir() {
@@ -636,24 +1098,46 @@ impl<'g> RustTestArtifact<'g> {
         }
         let platform_runner = runner.for_build_platform(self.build_platform);
 
-        let non_ignored = self.exec_single(false, dylib_path, platform_runner);
-        let ignored = self.exec_single(true, dylib_path, platform_runner);
+        let non_ignored = self.list_command(&["--format", "terse"], dylib_path, platform_runner);
+        let inputs = ListInputs {
+            program: non_ignored.program.clone(),
+            argv: non_ignored.argv.clone(),
+            cwd: self.cwd.clone(),
+            non_test_binaries: self.non_test_binaries.clone(),
+            artifact_binaries: self.artifact_binaries.clone(),
+            cdylibs: self.cdylibs.clone(),
+            env: self.env.clone(),
+        };
 
-        let (non_ignored_out, ignored_out) = futures::future::join(non_ignored, ignored).await;
-        Ok((non_ignored_out?, ignored_out?))
+        Ok(ListCommands {
+            binary_id: self.binary_id.clone(),
+            binary_path: self.binary_path.clone(),
+            inputs,
+            non_ignored,
+            ignored: self.list_command(
+                &["--format", "terse", "--ignored"],
+                dylib_path,
+                platform_runner,
+            ),
+            details: self.list_command(
+                &["-Zunstable-options", "--format", "json"],
+                dylib_path,
+                platform_runner,
+            ),
+        })
     }
 
-    async fn exec_single(
+    fn list_command(
         &self,
-        ignored: bool,
+        list_args: &[&'static str],
         dylib_path: &OsStr,
         runner: Option<&PlatformRunner>,
-    ) -> Result<String, CreateTestListError> {
+    ) -> ListCommand {
         let mut argv = Vec::new();
 
         let program: String = if let Some(runner) = runner {
-            argv.extend(runner.args());
-            argv.push(self.binary_path.as_str());
+            argv.extend(runner.args().map(|arg| arg.to_owned()));
+            argv.push(self.binary_path.to_string());
             runner.binary().into()
         } else {
             debug_assert!(
@@ -664,39 +1148,168 @@ impl<'g> RustTestArtifact<'g> {
             self.binary_path.clone().into()
         };
 
-        argv.extend(["--list", "--format", "terse"]);
-        if ignored {
-            argv.push("--ignored");
-        }
+        argv.push("--list".to_owned());
+        argv.extend(list_args.iter().map(|&arg| arg.to_owned()));
 
-        let cmd = make_test_command(
+        let mut cmd = make_test_command(
             program.clone(),
             &argv,
             &self.cwd,
             &self.package,
             dylib_path,
             &self.non_test_binaries,
+            &self.artifact_binaries,
+            &self.cdylibs,
         );
+        cmd.envs(&self.env);
+        // Custom test harnesses can print out a JSON test list instead.
+        cmd.env(
+            HarnessTestListSummary::LIST_FORMAT_ENV,
+            HarnessTestListSummary::LIST_FORMAT,
+        );
+        if list_args.contains(&"-Zunstable-options") {
+            // This lets test binaries built by stable compilers accept unstable options.
+            cmd.env("RUSTC_BOOTSTRAP", "1");
+        }
+
+        ListCommand {
+            binary_id: self.binary_id.clone(),
+            program,
+            argv,
+            cmd,
+        }
+    }
+}
+
+/// The output of listing a test binary: the non-ignored and ignored tests in the terse format,
+/// and details about the tests that aren't part of that format.
+type ListOutput = (String, String, HashMap<String, TestDetails>);
+
+/// The commands used to list the tests in a test binary.
+#[derive(Debug)]
+struct ListCommands {
+    binary_id: String,
+    binary_path: Utf8PathBuf,
+    inputs: ListInputs,
+    non_ignored: ListCommand,
+    ignored: ListCommand,
+    details: ListCommand,
+}
+
+impl ListCommands {
+    /// Runs these commands, or returns their output from an earlier run if it's in the cache.
+    async fn run(self, cache: Option<&ListCache>) -> Result<ListOutput, CreateTestListError> {
+        let cache_key = cache.and_then(|_| {
+            ListCacheKey::new(&self.binary_path, &self.inputs)
+                .map_err(|err| {
+                    log::debug!(
+                        "for {}, unable to read binary metadata for the list cache: {}",
+                        self.binary_id,
+                        err
+                    );
+                })
+                .ok()
+        });
+        if let (Some(cache), Some(cache_key)) = (cache, &cache_key) {
+            if let Some(output) = cache.get(cache_key) {
+                log::debug!("for {}, using tests from the list cache", self.binary_id);
+                return Ok(output);
+            }
+        }
+
+        let binary_id = self.binary_id.clone();
+        let output = self.run_uncached().await?;
+        if let (Some(cache), Some(cache_key)) = (cache, cache_key) {
+            if let Err(err) = cache.put(cache_key, &output) {
+                log::debug!(
+                    "for {}, unable to write to the list cache: {}",
+                    binary_id,
+                    err
+                );
+            }
+        }
+        Ok(output)
+    }
+
+    async fn run_uncached(self) -> Result<ListOutput, CreateTestListError> {
+        let (non_ignored_out, ignored_out) =
+            futures::future::join(self.non_ignored.run(), self.ignored.run()).await;
+        let (non_ignored_out, ignored_out) = (non_ignored_out?, ignored_out?);
+
+        // libtest only reports ignore reasons and source locations through its unstable JSON list
+        // format, so query for them separately, and only for libtest binaries that have tests. This
+        // is best effort: binaries that don't support the format simply don't have these details.
+        let has_tests = non_ignored_out.lines().any(|line| line.ends_with(": test"));
+        let test_details = if has_tests && !TestList::is_harness_json(&non_ignored_out) {
+            match self.details.run().await {
+                Ok(output) => TestList::parse_libtest_json_list(&output),
+                Err(err) => {
+                    log::debug!(
+                        "for {}, unable to obtain test details: {}",
+                        self.binary_id,
+                        err
+                    );
+                    HashMap::new()
+                }
+            }
+        } else {
+            HashMap::new()
+        };
+
+        Ok((non_ignored_out, ignored_out, test_details))
+    }
+}
+
+/// What a test binary is listed with, other than the dynamic library path.
+///
+/// Binaries listed while a build is still in progress are listed with a dynamic library path
+/// that's missing output directories only produced later in the build, which binaries that have
+/// already been built can't depend on. Everything else must match for the output to be reused.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct ListInputs {
+    program: String,
+    argv: Vec<String>,
+    cwd: Utf8PathBuf,
+    non_test_binaries: BTreeSet<(String, Utf8PathBuf)>,
+    artifact_binaries: BTreeSet<(String, Utf8PathBuf)>,
+    cdylibs: BTreeSet<(String, Utf8PathBuf)>,
+    env: BTreeMap<String, String>,
+}
+
+/// A single command run to list the tests in a test binary.
+#[derive(Debug)]
+struct ListCommand {
+    binary_id: String,
+    program: String,
+    argv: Vec<String>,
+    cmd: std::process::Command,
+}
+
+impl ListCommand {
+    async fn run(self) -> Result<String, CreateTestListError> {
+        let Self {
+            binary_id,
+            program,
+            argv,
+            cmd,
+        } = self;
         let mut cmd = tokio::process::Command::from(cmd);
         match cmd.output().await {
             Ok(output) => {
                 if output.status.success() {
                     String::from_utf8(output.stdout).map_err(|err| {
                         CreateTestListError::CommandNonUtf8 {
-                            binary_id: self.binary_id.clone(),
-                            command: std::iter::once(program)
-                                .chain(argv.iter().map(|&s| s.to_owned()))
-                                .collect(),
+                            binary_id,
+                            command: std::iter::once(program).chain(argv).collect(),
                             stdout: err.into_bytes(),
                             stderr: output.stderr,
                         }
                     })
                 } else {
                     Err(CreateTestListError::CommandFail {
-                        binary_id: self.binary_id.clone(),
-                        command: std::iter::once(program)
-                            .chain(argv.iter().map(|&s| s.to_owned()))
-                            .collect(),
+                        binary_id,
+                        command: std::iter::once(program).chain(argv).collect(),
                         exit_status: output.status,
                         stdout: output.stdout,
                         stderr: output.stderr,
@@ -704,16 +1317,334 @@ impl<'g> RustTestArtifact<'g> {
                 }
             }
             Err(error) => Err(CreateTestListError::CommandExecFail {
-                binary_id: self.binary_id.clone(),
-                command: std::iter::once(program)
-                    .chain(argv.iter().map(|&s| s.to_owned()))
-                    .collect(),
+                binary_id,
+                command: std::iter::once(program).chain(argv).collect(),
                 error,
             }),
         }
     }
 }
 
+/// Lists the tests in test binaries ahead of time, for example while the rest of the build is
+/// still in progress.
+///
+/// Passed in to [`TestList::new_with_prefetcher`], which reuses the output for binaries that would
+/// have been listed the same way. If a cache directory is set, binaries that haven't changed since
+/// they were listed in an earlier run aren't listed again.
+#[derive(Debug)]
+pub struct TestListPrefetcher {
+    runtime: Runtime,
+    list_threads: usize,
+    semaphore: Arc<Semaphore>,
+    prefetched: HashMap<Utf8PathBuf, PrefetchedList>,
+    cache: Option<ListCache>,
+}
+
+impl TestListPrefetcher {
+    /// Creates a new prefetcher that lists up to `list_threads` binaries at a time.
+    pub fn new(list_threads: usize) -> Result<Self, CreateTestListError> {
+        let runtime = Runtime::new().map_err(CreateTestListError::TokioRuntimeCreate)?;
+        Ok(Self {
+            runtime,
+            list_threads,
+            semaphore: Arc::new(Semaphore::new(list_threads)),
+            prefetched: HashMap::new(),
+            cache: None,
+        })
+    }
+
+    /// Caches the tests listed in each binary in this directory, keyed on the binary's size and
+    /// modification time along with everything else it's listed with.
+    ///
+    /// This should be set before any binaries are prefetched.
+    pub fn set_cache_dir(&mut self, cache_dir: impl Into<Utf8PathBuf>) -> &mut Self {
+        self.cache = Some(ListCache {
+            dir: cache_dir.into(),
+        });
+        self
+    }
+
+    /// Starts listing the tests in this artifact in the background.
+    ///
+    /// Artifacts that [`TestList::new`] wouldn't run to obtain a test list, for example because
+    /// they're skipped by the filter, aren't listed. Errors are reported once the test list is
+    /// created, if the artifact is still a part of it.
+    pub fn prefetch(
+        &mut self,
+        test_artifact: &RustTestArtifact<'_>,
+        rust_build_meta: &RustBuildMeta<TestListState>,
+        filter: &TestFilterBuilder,
+        runner: &TargetRunner,
+    ) {
+        if test_artifact.doctest.is_some()
+            || test_artifact.single_test
+            || !filter.should_obtain_test_list_from_binary(test_artifact)
+        {
+            return;
+        }
+
+        let commands = match TestList::create_dylib_path(rust_build_meta)
+            .and_then(|dylib_path| test_artifact.list_commands(&dylib_path, runner))
+        {
+            Ok(commands) => commands,
+            Err(err) => {
+                log::debug!(
+                    "for {}, not listing tests ahead of time: {}",
+                    test_artifact.binary_id,
+                    err
+                );
+                return;
+            }
+        };
+
+        let inputs = commands.inputs.clone();
+        let semaphore = self.semaphore.clone();
+        let cache = self.cache.clone();
+        let handle = self.runtime.spawn(async move {
+            let _permit = semaphore
+                .acquire_owned()
+                .await
+                .expect("semaphore is never closed");
+            commands.run(cache.as_ref()).await
+        });
+        self.prefetched.insert(
+            test_artifact.binary_path.clone(),
+            PrefetchedList { inputs, handle },
+        );
+    }
+
+    /// Returns a future that creates a test list, reusing the output of binaries listed ahead of
+    /// time.
+    ///
+    /// This works the same way as [`TestList::new_with_prefetcher`], except that binaries are only
+    /// listed once the future is awaited, so that test lists can be created while tests from
+    /// other lists are running. Binaries that weren't listed ahead of time are listed on the Tokio
+    /// runtime the future is run on.
+    pub fn test_list_future<'f, 'g: 'f, I>(
+        &mut self,
+        test_artifacts: I,
+        rust_build_meta: RustBuildMeta<TestListState>,
+        filter: &'f TestFilterBuilder,
+        runner: &'f TargetRunner,
+    ) -> impl Future<Output = Result<TestList<'g>, CreateTestListError>> + 'f
+    where
+        I: IntoIterator<Item = RustTestArtifact<'g>>,
+    {
+        let test_artifacts: Vec<_> = test_artifacts
+            .into_iter()
+            .map(|test_binary| {
+                let prefetched = self.prefetched.remove(&test_binary.binary_path);
+                (test_binary, prefetched)
+            })
+            .collect();
+        let list_threads = self.list_threads;
+        let cache = self.cache.clone();
+
+        async move {
+            let updated_dylib_path = TestList::create_dylib_path(&rust_build_meta)?;
+            log::debug!(
+                "updated {}: {}",
+                dylib_path_envvar(),
+                updated_dylib_path.to_string_lossy(),
+            );
+
+            let updated_dylib_path = &updated_dylib_path;
+            let cache = cache.as_ref();
+            // The futures are collected before they're awaited, so that the closure creating them
+            // isn't held across an await point. (Otherwise, the compiler can't tell that the
+            // returned future is Send.)
+            let list_futures: Vec<_> = test_artifacts
+                .into_iter()
+                .map(|(test_binary, prefetched)| async move {
+                    if test_binary.doctest.is_some() || test_binary.single_test {
+                        Ok(TestList::process_single_test(test_binary, filter))
+                    } else if filter.should_obtain_test_list_from_binary(&test_binary) {
+                        // Run the binary to obtain the test list.
+                        let (non_ignored, ignored, test_details) = match prefetched {
+                            Some(prefetched) => {
+                                test_binary
+                                    .exec_prefetched(prefetched, updated_dylib_path, runner, cache)
+                                    .await?
+                            }
+                            None => test_binary.exec(updated_dylib_path, runner, cache).await?,
+                        };
+                        let (bin, info) = TestList::process_output(
+                            test_binary,
+                            filter,
+                            non_ignored.as_str(),
+                            ignored.as_str(),
+                            &test_details,
+                        )?;
+                        Ok::<_, CreateTestListError>((bin, info))
+                    } else {
+                        // Skipped means no tests, so test_count doesn't need to be modified.
+                        Ok(TestList::process_skipped(test_binary))
+                    }
+                })
+                .collect();
+            let rust_suites: BTreeMap<_, _> = futures::stream::iter(list_futures)
+                .buffer_unordered(list_threads)
+                .try_collect()
+                .await?;
+            let test_count = rust_suites
+                .values()
+                .map(|suite| suite.status.test_count())
+                .sum();
+
+            Ok(TestList {
+                rust_suites,
+                rust_build_meta,
+                target_build_metas: BTreeMap::new(),
+                test_count,
+                skip_count: OnceCell::new(),
+            })
+        }
+    }
+}
+
+/// Test lists that are added one at a time while tests are being run, for example as test binaries
+/// are built.
+///
+/// Lists are added through a shared reference and are never moved afterwards, so tests from lists
+/// added earlier can run while more lists are added. Passed in to
+/// [`TestRunner::try_execute_while_listing`].
+///
+/// [`TestRunner::try_execute_while_listing`]: crate::runner::TestRunner::try_execute_while_listing
+#[derive(Debug, Default)]
+pub struct TestListBatches<'g> {
+    first: OnceCell<Box<TestListBatch<'g>>>,
+}
+
+impl<'g> TestListBatches<'g> {
+    /// Creates a new, empty set of test lists.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a test list after the ones already added, and returns a reference to it.
+    pub fn push(&self, test_list: TestList<'g>) -> &TestList<'g> {
+        let mut batch = Box::new(TestListBatch {
+            test_list,
+            next: OnceCell::new(),
+        });
+        let mut cell = &self.first;
+        loop {
+            match cell.get() {
+                Some(existing) => cell = &existing.next,
+                // If another list was added at the same time, try again after it.
+                None => match cell.set(batch) {
+                    Ok(()) => return &cell.get().expect("batch was just set").test_list,
+                    Err(unset) => batch = unset,
+                },
+            }
+        }
+    }
+
+    /// Iterates over the test lists added so far, in the order they were added in.
+    pub fn iter(&self) -> impl Iterator<Item = &TestList<'g>> + '_ {
+        std::iter::successors(self.first.get(), |batch| batch.next.get())
+            .map(|batch| &batch.test_list)
+    }
+}
+
+#[derive(Debug)]
+struct TestListBatch<'g> {
+    test_list: TestList<'g>,
+    next: OnceCell<Box<TestListBatch<'g>>>,
+}
+
+/// A test binary that's being listed ahead of time.
+#[derive(Debug)]
+struct PrefetchedList {
+    inputs: ListInputs,
+    handle: JoinHandle<Result<ListOutput, CreateTestListError>>,
+}
+
+/// A cache of the tests listed in test binaries, stored as one JSON file per binary path.
+#[derive(Clone, Debug)]
+struct ListCache {
+    dir: Utf8PathBuf,
+}
+
+impl ListCache {
+    /// The version of the cache format. Entries with a different version are ignored.
+    const FORMAT_VERSION: u32 = 1;
+
+    fn get(&self, key: &ListCacheKey) -> Option<ListOutput> {
+        let contents = std::fs::read_to_string(self.entry_path(&key.binary_path)).ok()?;
+        let entry: ListCacheEntry = serde_json::from_str(&contents).ok()?;
+        (entry.format_version == Self::FORMAT_VERSION && &entry.key == key)
+            .then(|| (entry.non_ignored, entry.ignored, entry.test_details))
+    }
+
+    fn put(&self, key: ListCacheKey, output: &ListOutput) -> io::Result<()> {
+        let path = self.entry_path(&key.binary_path);
+        let (non_ignored, ignored, test_details) = output.clone();
+        let entry = ListCacheEntry {
+            format_version: Self::FORMAT_VERSION,
+            key,
+            non_ignored,
+            ignored,
+            test_details,
+        };
+
+        // Write the entry to a temporary file first so that concurrent runs never see a partially
+        // written entry.
+        std::fs::create_dir_all(&self.dir)?;
+        let mut file = tempfile::NamedTempFile::new_in(&self.dir)?;
+        serde_json::to_writer(&mut file, &entry)?;
+        file.persist(path).map_err(|err| err.error)?;
+        Ok(())
+    }
+
+    fn entry_path(&self, binary_path: &Utf8Path) -> Utf8PathBuf {
+        let mut hasher = XxHash64::default();
+        binary_path.hash(&mut hasher);
+        self.dir.join(format!("{:016x}.json", hasher.finish()))
+    }
+}
+
+/// What a cached test list is keyed on.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct ListCacheKey {
+    binary_path: Utf8PathBuf,
+    binary_len: u64,
+    binary_modified: SystemTime,
+    inputs: ListInputs,
+}
+
+impl ListCacheKey {
+    fn new(binary_path: &Utf8Path, inputs: &ListInputs) -> io::Result<Self> {
+        let metadata = std::fs::metadata(binary_path)?;
+        Ok(Self {
+            binary_path: binary_path.to_owned(),
+            binary_len: metadata.len(),
+            binary_modified: metadata.modified()?,
+            inputs: inputs.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct ListCacheEntry {
+    format_version: u32,
+    key: ListCacheKey,
+    non_ignored: String,
+    ignored: String,
+    test_details: HashMap<String, TestDetails>,
+}
+
+/// Details about a test that aren't part of the terse list format, obtained from libtest's JSON
+/// list format or a custom test harness's JSON list.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct TestDetails {
+    ignore_reason: Option<String>,
+    location: Option<RustTestLocationSummary>,
+}
+
 /// Serializable information about the status of and test cases within a test suite.
 ///
 /// Part of a [`RustTestSuiteSummary`].
@@ -811,7 +1742,10 @@ impl<'a> TestInstance<'a> {
         test_list: &TestList<'_>,
         target_runner: &TargetRunner,
     ) -> std::process::Command {
-        let platform_runner = target_runner.for_build_platform(self.bin_info.build_platform);
+        let platform_runner = target_runner.for_build_platform_and_triple(
+            self.bin_info.build_platform,
+            self.bin_info.target_triple.as_deref(),
+        );
         // TODO: non-rust tests
 
         let mut args = Vec::new();
@@ -825,23 +1759,32 @@ impl<'a> TestInstance<'a> {
             None => self.binary.to_owned().into(),
         };
 
-        args.extend(["--exact", self.name, "--nocapture"]);
-        if self.test_info.ignored {
-            args.push("--ignored");
+        // Doctest binaries and binaries marked as single tests are run as a whole, and aren't
+        // passed in any arguments.
+        if self.bin_info.doctest.is_none() && !self.bin_info.single_test {
+            args.extend(["--exact", self.name, "--nocapture"]);
+            if self.test_info.ignored {
+                args.push("--ignored");
+            }
         }
 
-        make_test_command(
+        let mut cmd = make_test_command(
             program,
             args,
             &self.bin_info.cwd,
             &self.bin_info.package,
-            test_list.updated_dylib_path(),
+            &test_list.updated_dylib_path_for(self.bin_info),
             &self.bin_info.non_test_binaries,
-        )
+            &self.bin_info.artifact_binaries,
+            &self.bin_info.cdylibs,
+        );
+        cmd.envs(&self.bin_info.env);
+        cmd
     }
 }
 
 /// Create a duct Expression for a test binary with the given arguments, using the specified [`PackageMetadata`].
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn make_test_command(
     program: String,
     args: impl IntoIterator<Item = impl AsRef<OsStr>>,
@@ -849,6 +1792,8 @@ pub(crate) fn make_test_command(
     package: &PackageMetadata<'_>,
     dylib_path: &OsStr,
     non_test_binaries: &BTreeSet<(String, Utf8PathBuf)>,
+    artifact_binaries: &BTreeSet<(String, Utf8PathBuf)>,
+    cdylibs: &BTreeSet<(String, Utf8PathBuf)>,
 ) -> std::process::Command {
     // This is a workaround for a macOS SIP issue:
     // https://github.com/nextest-rs/nextest/pull/84
@@ -952,6 +1897,17 @@ pub(crate) fn make_test_command(
         cmd.env(format!("NEXTEST_BIN_EXE_{}", name), &path);
     }
 
+    // Cargo exposes paths to binaries from artifact dependencies at compile time through
+    // CARGO_BIN_FILE_* variables. Set them at runtime as well, so that relocated paths work.
+    for (var, path) in artifact_binaries {
+        cmd.env(var, path);
+    }
+
+    // Expose paths to cdylibs built by the workspace, so that tests can dlopen them.
+    for (name, path) in cdylibs {
+        cmd.env(format!("NEXTEST_CDYLIB_{}", name), path);
+    }
+
     cmd
 }
 
@@ -959,18 +1915,18 @@ pub(crate) fn make_test_command(
 mod tests {
     use super::*;
     use crate::{
-        cargo_config::{TargetTriple, TargetTripleSource},
+        cargo_config::{TargetDefinitionLocation, TargetTriple, TargetTripleSource},
         list::SerializableFormat,
         test_filter::RunIgnored,
     };
     use guppy::CargoMetadata;
     use indoc::indoc;
-    use maplit::btreemap;
+    use maplit::{btreemap, btreeset, hashmap};
     use nextest_filtering::FilteringExpr;
     use nextest_metadata::{FilterMatch, MismatchReason};
     use once_cell::sync::Lazy;
     use pretty_assertions::assert_eq;
-    use std::iter;
+    use std::{iter, time::Duration};
 
     #[test]
     fn test_parse_test_list() {
@@ -1005,7 +1961,13 @@ mod tests {
             binary_id: fake_binary_id.clone(),
             kind: RustTestBinaryKind::LIB,
             non_test_binaries: BTreeSet::new(),
+            artifact_binaries: BTreeSet::new(),
+            cdylibs: BTreeSet::new(),
             build_platform: BuildPlatform::Target,
+            doctest: None,
+            features: vec![],
+            single_test: false,
+            env: BTreeMap::new(),
         };
 
         let skipped_binary_name = "skipped-binary".to_owned();
@@ -1018,12 +1980,19 @@ mod tests {
             binary_id: skipped_binary_id.clone(),
             kind: RustTestBinaryKind::PROC_MACRO,
             non_test_binaries: BTreeSet::new(),
+            artifact_binaries: BTreeSet::new(),
+            cdylibs: BTreeSet::new(),
             build_platform: BuildPlatform::Host,
+            doctest: None,
+            features: vec![],
+            single_test: false,
+            env: BTreeMap::new(),
         };
 
         let fake_triple = TargetTriple {
             triple: "fake-triple".to_owned(),
             source: TargetTripleSource::CliOption,
+            location: TargetDefinitionLocation::Builtin,
         };
         let rust_build_meta =
             RustBuildMeta::new("/fake", Some(fake_triple)).map_paths(&PathMapper::noop());
@@ -1048,26 +2017,44 @@ mod tests {
                         test_cases: btreemap! {
                             "tests::foo::test_bar".to_owned() => RustTestCaseSummary {
                                 ignored: false,
+                                ignore_reason: None,
+                                location: None,
+                                tags: BTreeSet::new(),
                                 filter_match: FilterMatch::Matches,
                             },
                             "tests::baz::test_quux".to_owned() => RustTestCaseSummary {
                                 ignored: false,
+                                ignore_reason: None,
+                                location: None,
+                                tags: BTreeSet::new(),
                                 filter_match: FilterMatch::Matches,
                             },
                             "benches::bench_foo".to_owned() => RustTestCaseSummary {
                                 ignored: false,
+                                ignore_reason: None,
+                                location: None,
+                                tags: BTreeSet::new(),
                                 filter_match: FilterMatch::Matches,
                             },
                             "tests::ignored::test_bar".to_owned() => RustTestCaseSummary {
                                 ignored: true,
+                                ignore_reason: None,
+                                location: None,
+                                tags: BTreeSet::new(),
                                 filter_match: FilterMatch::Mismatch { reason: MismatchReason::Ignored },
                             },
                             "tests::baz::test_ignored".to_owned() => RustTestCaseSummary {
                                 ignored: true,
+                                ignore_reason: None,
+                                location: None,
+                                tags: BTreeSet::new(),
                                 filter_match: FilterMatch::Mismatch { reason: MismatchReason::Ignored },
                             },
                             "benches::ignored_bench_foo".to_owned() => RustTestCaseSummary {
                                 ignored: true,
+                                ignore_reason: None,
+                                location: None,
+                                tags: BTreeSet::new(),
                                 filter_match: FilterMatch::Mismatch { reason: MismatchReason::Ignored },
                             },
                         },
@@ -1079,6 +2066,13 @@ mod tests {
                     binary_id: fake_binary_id,
                     kind: RustTestBinaryKind::LIB,
                     non_test_binaries: BTreeSet::new(),
+                    artifact_binaries: BTreeSet::new(),
+                    cdylibs: BTreeSet::new(),
+                    doctest: None,
+                    features: vec![],
+                    single_test: false,
+                    env: BTreeMap::new(),
+                    target_triple: None,
                 },
                 "/fake/skipped-binary".into() => RustTestSuite {
                     status: RustTestSuiteStatus::Skipped,
@@ -1089,6 +2083,13 @@ mod tests {
                     binary_id: skipped_binary_id,
                     kind: RustTestBinaryKind::PROC_MACRO,
                     non_test_binaries: BTreeSet::new(),
+                    artifact_binaries: BTreeSet::new(),
+                    cdylibs: BTreeSet::new(),
+                    doctest: None,
+                    features: vec![],
+                    single_test: false,
+                    env: BTreeMap::new(),
+                    target_triple: None,
                 },
             }
         );
@@ -1124,7 +2125,12 @@ mod tests {
                 "base-output-directories": [],
                 "non-test-binaries": {},
                 "linked-paths": [],
-                "target-triple": "fake-triple"
+                "linked-path-packages": {},
+                "target-triple": "fake-triple",
+                "target-spec-file": null,
+                "rustc-version": null,
+                "features": null,
+                "profile": null
               },
               "test-count": 6,
               "rust-suites": {
@@ -1136,17 +2142,25 @@ mod tests {
                   "kind": "lib",
                   "binary-path": "/fake/binary",
                   "build-platform": "target",
+                  "doctest": null,
+                  "features": [],
                   "cwd": "/fake/cwd",
                   "status": "listed",
                   "testcases": {
                     "benches::bench_foo": {
                       "ignored": false,
+                      "ignore-reason": null,
+                      "location": null,
+                      "tags": [],
                       "filter-match": {
                         "status": "matches"
                       }
                     },
                     "benches::ignored_bench_foo": {
                       "ignored": true,
+                      "ignore-reason": null,
+                      "location": null,
+                      "tags": [],
                       "filter-match": {
                         "status": "mismatch",
                         "reason": "ignored"
@@ -1154,6 +2168,9 @@ mod tests {
                     },
                     "tests::baz::test_ignored": {
                       "ignored": true,
+                      "ignore-reason": null,
+                      "location": null,
+                      "tags": [],
                       "filter-match": {
                         "status": "mismatch",
                         "reason": "ignored"
@@ -1161,18 +2178,27 @@ mod tests {
                     },
                     "tests::baz::test_quux": {
                       "ignored": false,
+                      "ignore-reason": null,
+                      "location": null,
+                      "tags": [],
                       "filter-match": {
                         "status": "matches"
                       }
                     },
                     "tests::foo::test_bar": {
                       "ignored": false,
+                      "ignore-reason": null,
+                      "location": null,
+                      "tags": [],
                       "filter-match": {
                         "status": "matches"
                       }
                     },
                     "tests::ignored::test_bar": {
                       "ignored": true,
+                      "ignore-reason": null,
+                      "location": null,
+                      "tags": [],
                       "filter-match": {
                         "status": "mismatch",
                         "reason": "ignored"
@@ -1188,6 +2214,8 @@ mod tests {
                   "kind": "proc-macro",
                   "binary-path": "/fake/skipped-binary",
                   "build-platform": "host",
+                  "doctest": null,
+                  "features": [],
                   "cwd": "/fake/cwd",
                   "status": "skipped",
                   "testcases": {}
@@ -1221,6 +2249,604 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_harness_json_list() {
+        // The output of the --ignored invocation is discarded for JSON lists.
+        let non_ignored_output = indoc! {r#"
+            {
+                "format-version": 1,
+                "tests": [
+                    {"name": "data::foo.txt", "location": {"path": "tests/data.rs", "line": 4, "column": 1}},
+                    {"name": "data::bar.txt", "ignored": true, "ignore-reason": "too large"},
+                    {"name": "data::baz.txt", "ignored": false}
+                ]
+            }
+        "#};
+        let ignored_output = "should-not-be-parsed";
+
+        let test_filter = TestFilterBuilder::new(
+            RunIgnored::Default,
+            None,
+            iter::empty::<String>(),
+            Vec::new(),
+        );
+        let test_binary = RustTestArtifact {
+            binary_path: "/fake/harness".into(),
+            cwd: "/fake/cwd".into(),
+            package: package_metadata(),
+            binary_name: "harness".to_owned(),
+            binary_id: "fake-package::harness".to_owned(),
+            kind: RustTestBinaryKind::TEST,
+            non_test_binaries: BTreeSet::new(),
+            artifact_binaries: BTreeSet::new(),
+            cdylibs: BTreeSet::new(),
+            build_platform: BuildPlatform::Target,
+            doctest: None,
+            features: vec![],
+            single_test: false,
+            env: BTreeMap::new(),
+        };
+        let rust_build_meta = RustBuildMeta::new("/fake", None).map_paths(&PathMapper::noop());
+
+        let test_list = TestList::new_with_outputs(
+            [(test_binary.clone(), non_ignored_output, ignored_output)],
+            rust_build_meta.clone(),
+            &test_filter,
+        )
+        .expect("valid output");
+        let suite = &test_list.rust_suites[Utf8Path::new("/fake/harness")];
+        assert_eq!(
+            suite.status,
+            RustTestSuiteStatus::Listed {
+                test_cases: btreemap! {
+                    "data::foo.txt".to_owned() => RustTestCaseSummary {
+                        ignored: false,
+                        ignore_reason: None,
+                        location: Some(RustTestLocationSummary {
+                            path: "tests/data.rs".into(),
+                            line: 4,
+                            column: 1,
+                        }),
+                        tags: BTreeSet::new(),
+                        filter_match: FilterMatch::Matches,
+                    },
+                    "data::bar.txt".to_owned() => RustTestCaseSummary {
+                        ignored: true,
+                        ignore_reason: Some("too large".to_owned()),
+                        location: None,
+                        tags: BTreeSet::new(),
+                        filter_match: FilterMatch::Mismatch { reason: MismatchReason::Ignored },
+                    },
+                    "data::baz.txt".to_owned() => RustTestCaseSummary {
+                        ignored: false,
+                        ignore_reason: None,
+                        location: None,
+                        tags: BTreeSet::new(),
+                        filter_match: FilterMatch::Matches,
+                    },
+                },
+            }
+        );
+
+        let unsupported_output = r#"{"format-version": 2, "tests": []}"#;
+        let error = TestList::new_with_outputs(
+            [(test_binary, unsupported_output, "")],
+            rust_build_meta,
+            &test_filter,
+        )
+        .expect_err("unsupported format version");
+        assert!(
+            error
+                .to_string()
+                .contains("JSON test list has format version 2"),
+            "error is about the format version: {error}"
+        );
+    }
+
+    #[test]
+    fn test_parse_libtest_json_list() {
+        let list_output = indoc! {r#"
+            { "type": "suite", "event": "discovery" }
+            { "type": "test", "event": "discovered", "name": "tests::plain", "ignore": true, "ignore_message": "" }
+            { "type": "test", "event": "discovered", "name": "tests::network", "ignore": true, "ignore_message": "needs network", "source_path": "src/lib.rs", "start_line": 12, "start_col": 8, "end_line": 12, "end_col": 15 }
+            { "type": "test", "event": "discovered", "name": "tests::normal", "ignore": false, "ignore_message": "", "source_path": "src/lib.rs", "start_line": 20, "start_col": 8, "end_line": 20, "end_col": 14 }
+            not json
+            { "type": "suite", "event": "completed", "tests": 3, "benchmarks": 0, "total": 3, "ignored": 2 }
+        "#};
+
+        assert_eq!(
+            TestList::parse_libtest_json_list(list_output),
+            hashmap! {
+                "tests::network".to_owned() => TestDetails {
+                    ignore_reason: Some("needs network".to_owned()),
+                    location: Some(RustTestLocationSummary {
+                        path: "src/lib.rs".into(),
+                        line: 12,
+                        column: 8,
+                    }),
+                },
+                "tests::normal".to_owned() => TestDetails {
+                    ignore_reason: None,
+                    location: Some(RustTestLocationSummary {
+                        path: "src/lib.rs".into(),
+                        line: 20,
+                        column: 8,
+                    }),
+                },
+            },
+        );
+    }
+
+    #[test]
+    fn test_list_cache() {
+        let temp_dir = tempfile::tempdir().expect("created temp dir");
+        let temp_dir = Utf8Path::from_path(temp_dir.path()).expect("temp dir is valid UTF-8");
+        let binary_path = temp_dir.join("fake-binary");
+        std::fs::write(&binary_path, "binary contents").expect("wrote fake binary");
+
+        let cache = ListCache {
+            dir: temp_dir.join("list-cache"),
+        };
+        let inputs = ListInputs {
+            program: binary_path.to_string(),
+            argv: vec![
+                "--list".to_owned(),
+                "--format".to_owned(),
+                "terse".to_owned(),
+            ],
+            cwd: "/fake/cwd".into(),
+            non_test_binaries: BTreeSet::new(),
+            artifact_binaries: BTreeSet::new(),
+            cdylibs: BTreeSet::new(),
+            env: BTreeMap::new(),
+        };
+        let key = ListCacheKey::new(&binary_path, &inputs).expect("fake binary exists");
+        assert_eq!(cache.get(&key), None, "cache starts out empty");
+
+        let output = (
+            "tests::foo: test\n".to_owned(),
+            String::new(),
+            hashmap! {
+                "tests::foo".to_owned() => TestDetails {
+                    ignore_reason: None,
+                    location: Some(RustTestLocationSummary {
+                        path: "src/lib.rs".into(),
+                        line: 3,
+                        column: 8,
+                    }),
+                },
+            },
+        );
+        cache.put(key.clone(), &output).expect("wrote cache entry");
+        assert_eq!(cache.get(&key), Some(output), "cached output is returned");
+
+        let other_inputs = ListInputs {
+            cwd: "/fake/other-cwd".into(),
+            ..inputs.clone()
+        };
+        let other_key = ListCacheKey::new(&binary_path, &other_inputs).expect("fake binary exists");
+        assert_eq!(
+            cache.get(&other_key),
+            None,
+            "different inputs miss the cache"
+        );
+
+        std::fs::write(&binary_path, "rebuilt binary contents").expect("wrote fake binary");
+        let rebuilt_key = ListCacheKey::new(&binary_path, &inputs).expect("fake binary exists");
+        assert_eq!(
+            cache.get(&rebuilt_key),
+            None,
+            "rebuilt binaries miss the cache"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_prefetched_lists() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::tempdir().expect("created temp dir");
+        let temp_dir = Utf8Path::from_path(temp_dir.path()).expect("temp dir is valid UTF-8");
+        let name_path = temp_dir.join("name");
+        let log_path = temp_dir.join("log");
+        // This lists a single test named after the contents of the name file, suffixed with
+        // $SUFFIX, and logs each time it's run.
+        let binary_path = temp_dir.join("fake-binary");
+        std::fs::write(
+            &binary_path,
+            format!(
+                indoc! {r#"
+                    #!/bin/sh
+                    name=$(cat "{name_path}")
+                    echo "$@" >> "{log_path}"
+                    case "$*" in
+                        *--ignored*|*json*) ;;
+                        *) echo "tests::$name$SUFFIX: test" ;;
+                    esac
+                "#},
+                name_path = name_path,
+                log_path = log_path,
+            ),
+        )
+        .expect("wrote fake binary");
+        std::fs::set_permissions(&binary_path, std::fs::Permissions::from_mode(0o755))
+            .expect("made fake binary executable");
+
+        let test_binary = RustTestArtifact {
+            binary_path: binary_path.clone(),
+            cwd: temp_dir.to_path_buf(),
+            package: package_metadata(),
+            binary_name: "fake-binary".to_owned(),
+            binary_id: "fake-package::fake-binary".to_owned(),
+            kind: RustTestBinaryKind::LIB,
+            non_test_binaries: BTreeSet::new(),
+            artifact_binaries: BTreeSet::new(),
+            cdylibs: BTreeSet::new(),
+            build_platform: BuildPlatform::Target,
+            doctest: None,
+            features: vec![],
+            single_test: false,
+            env: BTreeMap::new(),
+        };
+        let rust_build_meta = RustBuildMeta::new(temp_dir, None).map_paths(&PathMapper::noop());
+        let filter = TestFilterBuilder::any(RunIgnored::Default);
+        let runner = TargetRunner::empty();
+
+        // Prefetches the fake binary, waits for it to be listed, then creates a test list with
+        // `listed_binary`, returning the names of the tests in it.
+        let list_after_prefetch = |listed_binary: RustTestArtifact<'static>| {
+            std::fs::write(&name_path, "prefetched").expect("wrote name");
+            let _ = std::fs::remove_file(&log_path);
+            let mut prefetcher = TestListPrefetcher::new(1).expect("created prefetcher");
+            prefetcher.prefetch(&test_binary, &rust_build_meta, &filter, &runner);
+            // The details are listed last, after the tests have been listed.
+            while !std::fs::read_to_string(&log_path).map_or(false, |log| log.contains("json")) {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+
+            std::fs::write(&name_path, "relisted").expect("wrote name");
+            let test_list = TestList::new_with_prefetcher(
+                [listed_binary],
+                rust_build_meta.clone(),
+                &filter,
+                &runner,
+                &mut prefetcher,
+            )
+            .expect("test list created");
+            test_list
+                .iter_tests()
+                .map(|test| test.name.to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            list_after_prefetch(test_binary.clone()),
+            vec!["tests::prefetched".to_owned()],
+            "binary listed the same way reuses the prefetched output"
+        );
+        let changed_binary = RustTestArtifact {
+            env: btreemap! { "SUFFIX".to_owned() => "_changed".to_owned() },
+            ..test_binary.clone()
+        };
+        assert_eq!(
+            list_after_prefetch(changed_binary),
+            vec!["tests::relisted_changed".to_owned()],
+            "binary listed with a different environment is listed again"
+        );
+    }
+
+    #[test]
+    fn test_merge_test_lists() {
+        let test_filter = TestFilterBuilder::any(RunIgnored::Default);
+        let make_test_list = |triple: &str| {
+            let test_binary = RustTestArtifact {
+                binary_path: format!("/fake/target/{triple}/debug/deps/fake-binary").into(),
+                cwd: "/fake/cwd".into(),
+                package: package_metadata(),
+                binary_name: "fake-binary".to_owned(),
+                binary_id: "fake-package::fake-binary".to_owned(),
+                kind: RustTestBinaryKind::LIB,
+                non_test_binaries: BTreeSet::new(),
+                artifact_binaries: BTreeSet::new(),
+                cdylibs: BTreeSet::new(),
+                build_platform: BuildPlatform::Target,
+                doctest: None,
+                features: vec![],
+                single_test: false,
+                env: BTreeMap::new(),
+            };
+            let mut rust_build_meta = RustBuildMeta::new(
+                "/fake/target",
+                Some(TargetTriple {
+                    triple: triple.to_owned(),
+                    source: TargetTripleSource::CliOption,
+                    location: TargetDefinitionLocation::Builtin,
+                }),
+            );
+            rust_build_meta
+                .base_output_directories
+                .insert(format!("{triple}/debug").into());
+            TestList::new_with_outputs(
+                [(test_binary, "tests::foo: test\n", "")],
+                rust_build_meta.map_paths(&PathMapper::noop()),
+                &test_filter,
+            )
+            .expect("valid output")
+        };
+
+        let test_list = TestList::merge([make_test_list("triple-a"), make_test_list("triple-b")]);
+        assert_eq!(test_list.test_count(), 2);
+        assert_eq!(test_list.binary_count(), 2);
+
+        for triple in ["triple-a", "triple-b"] {
+            let suite = test_list
+                .get(format!("/fake/target/{triple}/debug/deps/fake-binary"))
+                .expect("suite is present in merged list");
+            assert_eq!(suite.target_triple.as_deref(), Some(triple));
+            assert_eq!(
+                suite.binary_id,
+                format!("fake-package::fake-binary@{triple}"),
+                "binary ID is suffixed with the target triple"
+            );
+            assert_eq!(
+                test_list.rust_build_meta_for(suite).dylib_paths(),
+                vec![
+                    Utf8PathBuf::from(format!("/fake/target/{triple}/debug/deps")),
+                    Utf8PathBuf::from(format!("/fake/target/{triple}/debug")),
+                ],
+                "build metadata for the suite's target triple is used"
+            );
+        }
+    }
+
+    #[test]
+    fn test_merge_host_and_target_lists() {
+        let test_filter = TestFilterBuilder::any(RunIgnored::Default);
+        let make_artifact = |path: &str, build_platform| RustTestArtifact {
+            binary_path: path.into(),
+            cwd: "/fake/cwd".into(),
+            package: package_metadata(),
+            binary_name: "fake-binary".to_owned(),
+            binary_id: format!("fake-package::{}", build_platform),
+            kind: match build_platform {
+                BuildPlatform::Host => RustTestBinaryKind::PROC_MACRO,
+                BuildPlatform::Target => RustTestBinaryKind::LIB,
+            },
+            non_test_binaries: BTreeSet::new(),
+            artifact_binaries: BTreeSet::new(),
+            cdylibs: BTreeSet::new(),
+            build_platform,
+            doctest: None,
+            features: vec![],
+            single_test: false,
+            env: BTreeMap::new(),
+        };
+        let make_test_list = |triple: Option<&str>, artifacts: Vec<RustTestArtifact<'static>>| {
+            let target_triple = triple.map(|triple| TargetTriple {
+                triple: triple.to_owned(),
+                source: TargetTripleSource::CliOption,
+                location: TargetDefinitionLocation::Builtin,
+            });
+            let mut rust_build_meta = RustBuildMeta::new("/fake/target", target_triple);
+            rust_build_meta.base_output_directories.insert(
+                match triple {
+                    Some(triple) => format!("{triple}/debug"),
+                    None => "debug".to_owned(),
+                }
+                .into(),
+            );
+            TestList::new_with_outputs(
+                artifacts
+                    .into_iter()
+                    .map(|artifact| (artifact, "tests::foo: test\n", "")),
+                rust_build_meta.map_paths(&PathMapper::noop()),
+                &test_filter,
+            )
+            .expect("valid output")
+        };
+
+        // The lists for a build for two target triples, as split up by
+        // `BinaryList::split_by_target`.
+        let test_list = TestList::merge([
+            make_test_list(
+                None,
+                vec![make_artifact(
+                    "/fake/target/debug/deps/fake-binary",
+                    BuildPlatform::Host,
+                )],
+            ),
+            make_test_list(
+                Some("triple-a"),
+                vec![make_artifact(
+                    "/fake/target/triple-a/debug/deps/fake-binary",
+                    BuildPlatform::Target,
+                )],
+            ),
+            make_test_list(
+                Some("triple-b"),
+                vec![make_artifact(
+                    "/fake/target/triple-b/debug/deps/fake-binary",
+                    BuildPlatform::Target,
+                )],
+            ),
+        ]);
+        assert_eq!(test_list.test_count(), 3, "host tests are only listed once");
+        assert_eq!(
+            test_list
+                .iter()
+                .map(|(_, suite)| suite.binary_id.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "fake-package::host",
+                "fake-package::target@triple-a",
+                "fake-package::target@triple-b",
+            ],
+        );
+
+        let host_suite = test_list
+            .get("/fake/target/debug/deps/fake-binary")
+            .expect("host suite is present in merged list");
+        assert_eq!(host_suite.target_triple, None);
+        let host_build_meta = test_list.rust_build_meta_for(host_suite);
+        assert_eq!(host_build_meta.target_triple, None);
+        assert_eq!(
+            host_build_meta.dylib_paths(),
+            vec![
+                Utf8PathBuf::from("/fake/target/debug/deps"),
+                Utf8PathBuf::from("/fake/target/debug"),
+            ],
+            "build metadata for the host platform is used"
+        );
+
+        for triple in ["triple-a", "triple-b"] {
+            let suite = test_list
+                .get(format!("/fake/target/{triple}/debug/deps/fake-binary"))
+                .expect("target suite is present in merged list");
+            assert_eq!(suite.target_triple.as_deref(), Some(triple));
+            assert_eq!(
+                test_list
+                    .rust_build_meta_for(suite)
+                    .target_triple
+                    .as_ref()
+                    .map(|triple| triple.triple.as_str()),
+                Some(triple),
+            );
+        }
+    }
+
+    #[test]
+    fn test_list_batches() {
+        let make_test_list = |target_dir: &str| {
+            TestList::new_empty(RustBuildMeta::new(target_dir, None).map_paths(&PathMapper::noop()))
+        };
+        let target_dir =
+            |test_list: &TestList<'_>| test_list.rust_build_meta().target_directory.clone();
+
+        let batches = TestListBatches::new();
+        assert_eq!(batches.iter().count(), 0, "no lists added yet");
+
+        let first = batches.push(make_test_list("/fake/first"));
+        assert_eq!(
+            target_dir(first),
+            "/fake/first",
+            "the list added is returned"
+        );
+        let second = batches.push(make_test_list("/fake/second"));
+        assert_eq!(target_dir(second), "/fake/second");
+        // Lists added earlier can still be used after more are added.
+        assert_eq!(target_dir(first), "/fake/first");
+
+        let target_dirs: Vec<_> = batches.iter().map(target_dir).collect();
+        assert_eq!(
+            target_dirs,
+            vec!["/fake/first", "/fake/second"],
+            "lists are iterated over in the order they were added in"
+        );
+    }
+
+    #[test]
+    fn test_tests_slowest_first() {
+        let test_filter = TestFilterBuilder::any(RunIgnored::Default);
+        let test_binary = RustTestArtifact {
+            binary_path: "/fake/binary".into(),
+            cwd: "/fake/cwd".into(),
+            package: package_metadata(),
+            binary_name: "fake-binary".to_owned(),
+            binary_id: "fake-package::fake-binary".to_owned(),
+            kind: RustTestBinaryKind::LIB,
+            non_test_binaries: BTreeSet::new(),
+            artifact_binaries: BTreeSet::new(),
+            cdylibs: BTreeSet::new(),
+            build_platform: BuildPlatform::Target,
+            doctest: None,
+            features: vec![],
+            single_test: false,
+            env: BTreeMap::new(),
+        };
+        let output = indoc! {"
+            fast: test
+            medium_1: test
+            medium_2: test
+            slow: test
+            untimed_1: test
+            untimed_2: test
+        "};
+        let test_list = TestList::new_with_outputs(
+            [(test_binary, output, "")],
+            RustBuildMeta::new("/fake", None).map_paths(&PathMapper::noop()),
+            &test_filter,
+        )
+        .expect("valid output");
+
+        let mut timings = TestTimings::default();
+        for (test_name, secs) in [("fast", 1), ("medium_2", 5), ("medium_1", 5), ("slow", 30)] {
+            timings.insert(
+                "fake-package::fake-binary",
+                test_name,
+                Duration::from_secs(secs),
+            );
+        }
+        // A timing for another binary doesn't apply.
+        timings.insert("other-package", "untimed_2", Duration::from_secs(60));
+
+        let order: Vec<_> = test_list
+            .tests_slowest_first(&timings)
+            .into_iter()
+            .map(|test| test.name)
+            .collect();
+        assert_eq!(
+            order,
+            [
+                "slow",
+                "medium_1",
+                "medium_2",
+                "fast",
+                "untimed_1",
+                "untimed_2"
+            ],
+            "slowest tests come first, and ties and untimed tests are in listing order"
+        );
+    }
+
+    #[test]
+    fn test_updated_dylib_path_checks_existence() {
+        let target_dir = tempfile::tempdir().expect("created temp dir");
+        let target_dir: Utf8PathBuf = target_dir
+            .path()
+            .to_path_buf()
+            .try_into()
+            .expect("temp dir is valid UTF-8");
+
+        let mut rust_build_meta = RustBuildMeta::new(&target_dir, None);
+        rust_build_meta.linked_paths.insert(
+            "debug/build/foo/out".into(),
+            btreeset! { "foo-package-id".to_owned() },
+        );
+        let rust_build_meta = rust_build_meta.map_paths(&PathMapper::noop());
+        let test_list = TestList::new_with_outputs(
+            iter::empty::<(RustTestArtifact<'_>, &str, &str)>(),
+            rust_build_meta,
+            &TestFilterBuilder::any(RunIgnored::Default),
+        )
+        .expect("test list created even though the linked path doesn't exist");
+
+        let linked_path = target_dir.join("debug/build/foo/out");
+        let contains_linked_path = |dylib_path: OsString| {
+            std::env::split_paths(&dylib_path).any(|path| path == linked_path)
+        };
+        assert!(
+            !contains_linked_path(test_list.updated_dylib_path()),
+            "linked path that doesn't exist is skipped"
+        );
+
+        // Creating the directory after the test list is built should cause it to be picked up.
+        std::fs::create_dir_all(&linked_path).expect("created linked path");
+        assert!(
+            contains_linked_path(test_list.updated_dylib_path()),
+            "linked path created after building the test list is included"
+        );
+    }
+
     static PACKAGE_GRAPH_FIXTURE: Lazy<PackageGraph> = Lazy::new(|| {
         static FIXTURE_JSON: &str = include_str!("../../../fixtures/cargo-metadata.json");
         let metadata = CargoMetadata::parse_json(FIXTURE_JSON).expect("fixture is valid JSON");
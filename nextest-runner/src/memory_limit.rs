@@ -0,0 +1,341 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Limiting how much memory tests use, for the `memory-limit` setting.
+//!
+//! On Linux, each test is run in its own cgroup with a memory limit if nextest can create cgroups
+//! with the memory controller enabled. Otherwise, on Unix, the address space of each test process
+//! is limited with `setrlimit`. On Windows, tests are assigned to job objects with a memory limit
+//! by the runner.
+
+use std::process::Command;
+
+/// Sets up memory limits for test processes.
+#[derive(Debug)]
+pub(crate) struct MemoryLimiter {
+    imp: imp::Limiter,
+}
+
+impl MemoryLimiter {
+    /// Creates a new limiter, using `name_prefix` for any cgroups it creates.
+    pub(crate) fn new(name_prefix: String) -> Self {
+        Self {
+            imp: imp::Limiter::new(name_prefix),
+        }
+    }
+
+    /// Limits how much memory the process started by `cmd` can use, in bytes.
+    ///
+    /// If the limit can't be enforced through a cgroup, it falls back to limiting each process.
+    pub(crate) fn apply(&self, cmd: &mut Command, limit: u64) -> TestMemoryLimit {
+        TestMemoryLimit {
+            imp: self.imp.apply(cmd, limit),
+        }
+    }
+}
+
+/// A memory limit set up for a test.
+///
+/// Any cgroup created for the test is removed when this is dropped.
+#[derive(Debug)]
+pub(crate) struct TestMemoryLimit {
+    imp: imp::TestLimit,
+}
+
+impl TestMemoryLimit {
+    /// Returns true if a test that failed was stopped because it went over its memory limit.
+    ///
+    /// Tests killed by the out-of-memory killer in their cgroup are always caught. Otherwise, the
+    /// limit makes allocations fail, which is caught through the message Rust's default allocation
+    /// error handler prints out before aborting.
+    pub(crate) fn exceeded(&self, stderr: &[u8]) -> bool {
+        self.imp.oom_killed() || alloc_failed(stderr)
+    }
+}
+
+fn alloc_failed(stderr: &[u8]) -> bool {
+    stderr.split(|&b| b == b'\n').any(|line| {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        line.starts_with(b"memory allocation of ") && line.ends_with(b" bytes failed")
+    })
+}
+
+/// Limits the address space of the process started by `cmd`.
+#[cfg(unix)]
+fn set_address_space_limit(cmd: &mut Command, limit: u64) {
+    use std::os::unix::process::CommandExt;
+
+    let rlimit = libc::rlimit {
+        rlim_cur: limit as libc::rlim_t,
+        rlim_max: limit as libc::rlim_t,
+    };
+    // SAFETY: setrlimit is async-signal-safe, and the closure doesn't allocate.
+    unsafe {
+        cmd.pre_exec(move || {
+            if libc::setrlimit(libc::RLIMIT_AS, &rlimit) == 0 {
+                Ok(())
+            } else {
+                Err(std::io::Error::last_os_error())
+            }
+        });
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::*;
+    use std::{
+        ffi::CString,
+        io,
+        os::unix::{ffi::OsStrExt, process::CommandExt},
+        path::{Path, PathBuf},
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+    #[derive(Debug)]
+    pub(super) struct Limiter {
+        // The cgroup to create test cgroups in, if any.
+        cgroup_parent: Option<PathBuf>,
+        name_prefix: String,
+        next_id: AtomicUsize,
+    }
+
+    impl Limiter {
+        pub(super) fn new(name_prefix: String) -> Self {
+            let cgroup_parent = find_cgroup_parent();
+            match &cgroup_parent {
+                Some(parent) => log::debug!(
+                    target: "nextest-runner",
+                    "limiting memory through cgroups in `{}`",
+                    parent.display(),
+                ),
+                None => log::debug!(
+                    target: "nextest-runner",
+                    "no cgroup with the memory controller available, limiting memory through rlimits",
+                ),
+            }
+            Self {
+                cgroup_parent,
+                name_prefix,
+                next_id: AtomicUsize::new(0),
+            }
+        }
+
+        pub(super) fn apply(&self, cmd: &mut Command, limit: u64) -> TestLimit {
+            if let Some(parent) = &self.cgroup_parent {
+                let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                let name = format!("{}-{id}", self.name_prefix);
+                match TestCgroup::create(parent, &name, limit) {
+                    Ok(cgroup) => {
+                        cgroup.add_on_exec(cmd);
+                        return TestLimit {
+                            cgroup: Some(cgroup),
+                        };
+                    }
+                    Err(err) => log::debug!(
+                        target: "nextest-runner",
+                        "failed to create cgroup `{name}`, limiting memory through rlimits: {err}",
+                    ),
+                }
+            }
+            set_address_space_limit(cmd, limit);
+            TestLimit { cgroup: None }
+        }
+    }
+
+    #[derive(Debug)]
+    pub(super) struct TestLimit {
+        cgroup: Option<TestCgroup>,
+    }
+
+    impl TestLimit {
+        pub(super) fn oom_killed(&self) -> bool {
+            self.cgroup
+                .as_ref()
+                .map_or(false, |cgroup| cgroup.oom_killed())
+        }
+    }
+
+    /// Returns nextest's own cgroup or its parent, whichever has the memory controller enabled for
+    /// the cgroups within it.
+    ///
+    /// In a delegated cgroup, nextest typically runs in a leaf, so test cgroups are created next to
+    /// it.
+    fn find_cgroup_parent() -> Option<PathBuf> {
+        let cgroups = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+        // Only cgroup v2, which is listed with the hierarchy ID 0, is supported.
+        let path = cgroups.lines().find_map(|line| line.strip_prefix("0::"))?;
+        let own = Path::new(CGROUP_ROOT).join(path.trim_start_matches('/'));
+        let candidates = std::iter::once(own.as_path()).chain(own.parent());
+        for dir in candidates.filter(|dir| dir.starts_with(CGROUP_ROOT)) {
+            let subtree_control = match std::fs::read_to_string(dir.join("cgroup.subtree_control"))
+            {
+                Ok(subtree_control) => subtree_control,
+                Err(_) => continue,
+            };
+            if subtree_control
+                .split_whitespace()
+                .any(|controller| controller == "memory")
+            {
+                return Some(dir.to_owned());
+            }
+        }
+        None
+    }
+
+    #[derive(Debug)]
+    struct TestCgroup {
+        dir: PathBuf,
+    }
+
+    impl TestCgroup {
+        fn create(parent: &Path, name: &str, limit: u64) -> io::Result<Self> {
+            let dir = parent.join(name);
+            std::fs::create_dir(&dir)?;
+            // From here on, the cgroup is removed on failure by the drop impl.
+            let cgroup = Self { dir };
+            std::fs::write(cgroup.dir.join("memory.max"), limit.to_string())?;
+            // These are best-effort: swap might not be accounted for, and older kernels don't
+            // support killing every process in the cgroup together.
+            let _ = std::fs::write(cgroup.dir.join("memory.swap.max"), "0");
+            let _ = std::fs::write(cgroup.dir.join("memory.oom.group"), "1");
+            Ok(cgroup)
+        }
+
+        /// Moves the process started by `cmd` into this cgroup before it runs the test.
+        fn add_on_exec(&self, cmd: &mut Command) {
+            let procs = CString::new(self.dir.join("cgroup.procs").as_os_str().as_bytes())
+                .expect("cgroup paths don't contain nul bytes");
+            // SAFETY: open, write and close are async-signal-safe, and the closure doesn't
+            // allocate.
+            unsafe {
+                cmd.pre_exec(move || {
+                    let fd = libc::open(procs.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+                    if fd < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    // Writing 0 moves the writing process.
+                    let written = libc::write(fd, b"0".as_ptr().cast(), 1);
+                    let err = io::Error::last_os_error();
+                    libc::close(fd);
+                    if written == 1 {
+                        Ok(())
+                    } else {
+                        Err(err)
+                    }
+                });
+            }
+        }
+
+        fn oom_killed(&self) -> bool {
+            std::fs::read_to_string(self.dir.join("memory.events"))
+                .map_or(false, |events| parse_oom_kills(&events) > 0)
+        }
+    }
+
+    impl Drop for TestCgroup {
+        fn drop(&mut self) {
+            // This fails if processes started by the test are still running.
+            if let Err(err) = std::fs::remove_dir(&self.dir) {
+                log::debug!(
+                    target: "nextest-runner",
+                    "failed to remove cgroup `{}`: {err}",
+                    self.dir.display(),
+                );
+            }
+        }
+    }
+
+    /// Returns the number of processes killed by the out-of-memory killer, from the contents of a
+    /// `memory.events` file.
+    pub(super) fn parse_oom_kills(events: &str) -> u64 {
+        events
+            .lines()
+            .find_map(|line| line.strip_prefix("oom_kill "))
+            .and_then(|count| count.trim().parse().ok())
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+mod imp {
+    use super::*;
+
+    #[derive(Debug)]
+    pub(super) struct Limiter(());
+
+    impl Limiter {
+        pub(super) fn new(_name_prefix: String) -> Self {
+            Self(())
+        }
+
+        pub(super) fn apply(&self, cmd: &mut Command, limit: u64) -> TestLimit {
+            set_address_space_limit(cmd, limit);
+            TestLimit(())
+        }
+    }
+
+    #[derive(Debug)]
+    pub(super) struct TestLimit(());
+
+    impl TestLimit {
+        pub(super) fn oom_killed(&self) -> bool {
+            false
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use super::*;
+
+    #[derive(Debug)]
+    pub(super) struct Limiter(());
+
+    impl Limiter {
+        pub(super) fn new(_name_prefix: String) -> Self {
+            Self(())
+        }
+
+        pub(super) fn apply(&self, _cmd: &mut Command, _limit: u64) -> TestLimit {
+            // On Windows, the runner sets the limit on the job object the test is assigned to.
+            TestLimit(())
+        }
+    }
+
+    #[derive(Debug)]
+    pub(super) struct TestLimit(());
+
+    impl TestLimit {
+        pub(super) fn oom_killed(&self) -> bool {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alloc_failed() {
+        assert!(alloc_failed(
+            b"running 1 test\nmemory allocation of 1073741824 bytes failed\n"
+        ));
+        assert!(alloc_failed(b"memory allocation of 64 bytes failed\r\n"));
+        assert!(!alloc_failed(b"test result: FAILED\n"));
+        assert!(!alloc_failed(
+            b"assertion failed: memory allocation of 3 bytes failed to fit\n"
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_oom_kills() {
+        let events = "low 0\nhigh 0\nmax 12\noom 1\noom_kill 2\noom_group_kill 1\n";
+        assert_eq!(imp::parse_oom_kills(events), 2);
+        assert_eq!(imp::parse_oom_kills("low 0\n"), 0);
+    }
+}
@@ -0,0 +1,203 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Running tests without network access, for the `network-isolation` setting.
+//!
+//! On Linux, each test process is moved into a new network namespace before it runs. The namespace
+//! only has a loopback interface, so tests can still talk to servers they start themselves, but
+//! can't reach anything else, and ports they bind don't clash with ports bound by other tests.
+//!
+//! Creating a network namespace needs `CAP_SYS_ADMIN`. Without it, the test is also moved into a
+//! new user namespace, which unprivileged processes can create on most distributions. The test's
+//! user and group IDs are mapped to themselves inside the user namespace, so files it creates are
+//! owned as usual.
+
+use std::{io, process::Command};
+
+/// Runs the process started by `cmd` in a new network namespace with only a loopback interface.
+///
+/// Returns false if network isolation isn't supported on this platform.
+pub(crate) fn isolate_network(cmd: &mut Command) -> bool {
+    imp::isolate_network(cmd)
+}
+
+/// Returns true if `err`, returned while starting a process isolated with [`isolate_network`],
+/// may have been caused by being unable to create the network namespace.
+pub(crate) fn is_isolation_error(err: &io::Error) -> bool {
+    imp::is_isolation_error(err)
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::*;
+    use std::os::unix::process::CommandExt;
+
+    const IFNAMSIZ: usize = 16;
+
+    /// The parts of `struct ifreq` used to get and set interface flags.
+    ///
+    /// The union in `struct ifreq` is at most 24 bytes long, so this is at least as large as it.
+    #[repr(C)]
+    struct IfreqFlags {
+        name: [libc::c_char; IFNAMSIZ],
+        flags: libc::c_short,
+        _pad: [u8; 22],
+    }
+
+    pub(super) fn isolate_network(cmd: &mut Command) -> bool {
+        // SAFETY: geteuid and getegid are always safe to call.
+        let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+        // The maps are formatted here, since the closure can't allocate.
+        let uid_map = format!("{uid} {uid} 1");
+        let gid_map = format!("{gid} {gid} 1");
+
+        // SAFETY: unshare, open, write, close, socket and ioctl are async-signal-safe, and the
+        // closure doesn't allocate.
+        unsafe {
+            cmd.pre_exec(move || {
+                if libc::unshare(libc::CLONE_NEWNET) != 0 {
+                    // Without the privileges to create a network namespace, create it inside a new
+                    // user namespace, mapping the current user and group to themselves.
+                    check(libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET))?;
+                    // setgroups must be denied before an unprivileged process can write gid_map.
+                    write_file(b"/proc/self/setgroups\0", b"deny")?;
+                    write_file(b"/proc/self/uid_map\0", uid_map.as_bytes())?;
+                    write_file(b"/proc/self/gid_map\0", gid_map.as_bytes())?;
+                }
+                bring_up_loopback()
+            });
+        }
+        true
+    }
+
+    pub(super) fn is_isolation_error(err: &io::Error) -> bool {
+        // These are the errors unshare and writing the ID maps fail with, for example if user
+        // namespaces are disabled or too many of them exist.
+        matches!(
+            err.raw_os_error(),
+            Some(libc::EPERM | libc::EINVAL | libc::ENOSPC | libc::EUSERS)
+        )
+    }
+
+    fn check(ret: libc::c_int) -> io::Result<()> {
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    /// Writes `contents` to the file at the nul-terminated `path`.
+    unsafe fn write_file(path: &[u8], contents: &[u8]) -> io::Result<()> {
+        let fd = libc::open(path.as_ptr().cast(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let written = libc::write(fd, contents.as_ptr().cast(), contents.len());
+        let err = io::Error::last_os_error();
+        libc::close(fd);
+        if written == contents.len() as isize {
+            Ok(())
+        } else {
+            Err(err)
+        }
+    }
+
+    /// Sets the loopback interface in the current network namespace up, which new namespaces
+    /// start out with down.
+    unsafe fn bring_up_loopback() -> io::Result<()> {
+        let fd = libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut ifreq = IfreqFlags {
+            name: [0; IFNAMSIZ],
+            flags: 0,
+            _pad: [0; 22],
+        };
+        for (dest, &src) in ifreq.name.iter_mut().zip(b"lo") {
+            *dest = src as libc::c_char;
+        }
+        let mut res = libc::ioctl(fd, libc::SIOCGIFFLAGS as _, &mut ifreq);
+        if res == 0 {
+            ifreq.flags |= libc::IFF_UP as libc::c_short;
+            res = libc::ioctl(fd, libc::SIOCSIFFLAGS as _, &ifreq);
+        }
+        let err = io::Error::last_os_error();
+        libc::close(fd);
+        if res == 0 {
+            Ok(())
+        } else {
+            Err(err)
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use super::*;
+
+    pub(super) fn isolate_network(_cmd: &mut Command) -> bool {
+        false
+    }
+
+    pub(super) fn is_isolation_error(_err: &io::Error) -> bool {
+        false
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::{
+        net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+        time::Duration,
+    };
+
+    const CHILD_ENV: &str = "__NEXTEST_NETWORK_ISOLATION_CHILD";
+
+    #[test]
+    fn isolated_network() {
+        if std::env::var_os(CHILD_ENV).is_some() {
+            // This is the test binary run again within the network namespace.
+            let listener =
+                TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).expect("bound to loopback address");
+            TcpStream::connect(listener.local_addr().expect("listener has an address"))
+                .expect("connected to loopback address");
+            let err = TcpStream::connect_timeout(
+                &SocketAddr::from(([192, 0, 2, 1], 80)),
+                Duration::from_secs(5),
+            )
+            .expect_err("connecting outside the namespace fails");
+            assert_eq!(
+                err.raw_os_error(),
+                Some(libc::ENETUNREACH),
+                "only loopback is reachable, so the connection fails right away: {err}"
+            );
+            return;
+        }
+
+        let mut cmd = Command::new(std::env::current_exe().expect("current exe is known"));
+        cmd.args([
+            "--exact",
+            "network_isolation::tests::isolated_network",
+            "--nocapture",
+        ])
+        .env(CHILD_ENV, "1");
+        assert!(isolate_network(&mut cmd), "supported on Linux");
+        let output = match cmd.output() {
+            Ok(output) => output,
+            Err(err) if err.raw_os_error() == Some(libc::EPERM) => {
+                eprintln!("skipping test, unable to create a network namespace: {err}");
+                return;
+            }
+            Err(err) => panic!("failed to run test binary in a network namespace: {err}"),
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            output.status.success() && stdout.contains("1 passed"),
+            "checks within the network namespace passed\n--- stdout:\n{stdout}\n--- stderr:\n{}",
+            String::from_utf8_lossy(&output.stderr),
+        );
+    }
+}
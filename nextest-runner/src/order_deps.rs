@@ -0,0 +1,410 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Detecting tests that fail depending on which other tests run before them or alongside them.
+//!
+//! The tests are run several times, each time in a different shuffled order, and with different
+//! numbers of tests running at once. For each test that fails in some runs and passes in others,
+//! the tests that finished before it started and the tests that ran alongside it are compared
+//! between the runs it failed in and the runs it passed in. A test that ran before or alongside it
+//! in every run it failed in, but in at most half of the runs it passed in, is reported as a
+//! suspect.
+
+use crate::{
+    errors::WriteTestListError,
+    helpers::write_test_name,
+    list::{OutputFormat, Styles, TestInstance},
+    runner::ExecutionStatuses,
+};
+use nextest_metadata::{
+    InconsistentTestSummary, OrderDepRelation, OrderDepSuspectSummary, OrderDepsRunSummary,
+    OrderDepsSummary, OrderDepsTestSummary,
+};
+use owo_colors::OwoColorize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::{self, Write},
+    time::SystemTime,
+};
+
+/// The most suspects listed for each test in human-readable output.
+const MAX_HUMAN_SUSPECTS: usize = 10;
+
+/// Records how tests did in each run, to look for order dependencies once the runs are done.
+#[derive(Clone, Debug, Default)]
+pub struct OrderDepsCollector {
+    runs: Vec<RecordedRun>,
+    // The tests that finished in the run that's in progress.
+    current: Vec<RecordedTest>,
+}
+
+#[derive(Clone, Debug)]
+struct RecordedRun {
+    seed: u64,
+    test_threads: usize,
+    tests: Vec<RecordedTest>,
+}
+
+#[derive(Clone, Debug)]
+struct RecordedTest {
+    binary_id: String,
+    name: String,
+    start: SystemTime,
+    end: SystemTime,
+    passed: bool,
+}
+
+impl RecordedTest {
+    fn has_relation(&self, other: &RecordedTest, relation: OrderDepRelation) -> bool {
+        match relation {
+            OrderDepRelation::RanBefore => self.end <= other.start,
+            OrderDepRelation::RanAlongside => self.start < other.end && other.start < self.end,
+        }
+    }
+}
+
+impl OrderDepsCollector {
+    /// Creates a new, empty collector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a test that finished in the current run.
+    pub fn record(&mut self, test_instance: &TestInstance<'_>, run_statuses: &ExecutionStatuses) {
+        let first_status = run_statuses
+            .iter()
+            .next()
+            .expect("at least one attempt was run");
+        let last_status = run_statuses.last_status();
+        self.record_test(
+            &test_instance.bin_info.binary_id,
+            test_instance.name,
+            first_status.start_time,
+            last_status.start_time + last_status.time_taken,
+            last_status.result.is_success(),
+        );
+    }
+
+    fn record_test(
+        &mut self,
+        binary_id: &str,
+        name: &str,
+        start: SystemTime,
+        end: SystemTime,
+        passed: bool,
+    ) {
+        self.current.push(RecordedTest {
+            binary_id: binary_id.to_owned(),
+            name: name.to_owned(),
+            start,
+            end,
+            passed,
+        });
+    }
+
+    /// Finishes the current run, in which tests were shuffled with `seed` and `test_threads` tests
+    /// were run at a time.
+    pub fn finish_run(&mut self, seed: u64, test_threads: usize) {
+        self.runs.push(RecordedRun {
+            seed,
+            test_threads,
+            tests: std::mem::take(&mut self.current),
+        });
+    }
+
+    /// Compares the finished runs.
+    pub fn analyze(&self) -> OrderDeps {
+        let runs = self
+            .runs
+            .iter()
+            .map(|run| OrderDepsRunSummary {
+                seed: run.seed,
+                test_threads: run.test_threads,
+                passed: run.tests.iter().filter(|test| test.passed).count(),
+                failed: run.tests.iter().filter(|test| !test.passed).count(),
+            })
+            .collect();
+
+        // The tests in each run, keyed by (binary ID, name).
+        let indexes: Vec<HashMap<(&str, &str), &RecordedTest>> = self
+            .runs
+            .iter()
+            .map(|run| {
+                run.tests
+                    .iter()
+                    .map(|test| ((test.binary_id.as_str(), test.name.as_str()), test))
+                    .collect()
+            })
+            .collect();
+        let all_tests: BTreeSet<(&str, &str)> = indexes
+            .iter()
+            .flat_map(|index| index.keys().copied())
+            .collect();
+
+        let mut inconsistent = Vec::new();
+        let mut always_failed = Vec::new();
+        for &key in &all_tests {
+            let (failed, passed): (Vec<_>, Vec<_>) = indexes
+                .iter()
+                .filter_map(|index| Some((index, *index.get(&key)?)))
+                .partition(|(_, test)| !test.passed);
+            if failed.is_empty() {
+                continue;
+            }
+            if passed.is_empty() {
+                always_failed.push(test_summary(key));
+                continue;
+            }
+
+            let mut suspects = Vec::new();
+            for &other in all_tests.iter().filter(|&&other| other != key) {
+                for relation in [OrderDepRelation::RanBefore, OrderDepRelation::RanAlongside] {
+                    let count = |runs: &[(&HashMap<_, &RecordedTest>, &RecordedTest)]| {
+                        runs.iter()
+                            .filter(|(index, test)| {
+                                index
+                                    .get(&other)
+                                    .map_or(false, |other| other.has_relation(test, relation))
+                            })
+                            .count()
+                    };
+                    let failed_runs = count(&failed);
+                    let passed_runs = count(&passed);
+                    if failed_runs == failed.len() && passed_runs * 2 <= passed.len() {
+                        suspects.push(OrderDepSuspectSummary {
+                            test: test_summary(other),
+                            relation,
+                            failed_runs,
+                            passed_runs,
+                        });
+                    }
+                }
+            }
+            // Tests that ran this way in fewer of the passing runs are more suspicious.
+            suspects.sort_by(|a, b| {
+                (a.passed_runs, a.relation, &a.test).cmp(&(b.passed_runs, b.relation, &b.test))
+            });
+
+            inconsistent.push(InconsistentTestSummary {
+                test: test_summary(key),
+                runs: failed.len() + passed.len(),
+                failed_runs: failed.len(),
+                suspects,
+            });
+        }
+
+        OrderDeps {
+            summary: OrderDepsSummary {
+                runs,
+                inconsistent,
+                always_failed,
+            },
+        }
+    }
+}
+
+fn test_summary((binary_id, name): (&str, &str)) -> OrderDepsTestSummary {
+    OrderDepsTestSummary {
+        binary_id: binary_id.to_owned(),
+        name: name.to_owned(),
+    }
+}
+
+/// Tests that failed in some runs and passed in others, and the tests suspected of making them
+/// fail.
+///
+/// Created with [`OrderDepsCollector::analyze`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct OrderDeps {
+    summary: OrderDepsSummary,
+}
+
+impl OrderDeps {
+    /// Returns true if any tests failed in some runs and passed in others.
+    pub fn has_inconsistent(&self) -> bool {
+        !self.summary.inconsistent.is_empty()
+    }
+
+    /// Returns a serializable summary of the results.
+    pub fn to_summary(&self) -> OrderDepsSummary {
+        self.summary.clone()
+    }
+
+    /// Outputs the results in the given format.
+    pub fn write(
+        &self,
+        output_format: OutputFormat,
+        writer: impl Write,
+        colorize: bool,
+    ) -> Result<(), WriteTestListError> {
+        match output_format {
+            OutputFormat::Human { verbose } => self
+                .write_human(writer, verbose, colorize)
+                .map_err(WriteTestListError::Io),
+            OutputFormat::Serializable(format) => format
+                .to_writer(&self.summary, writer)
+                .map_err(WriteTestListError::Json),
+        }
+    }
+
+    fn write_human(&self, mut writer: impl Write, verbose: bool, colorize: bool) -> io::Result<()> {
+        let mut styles = Styles::default();
+        if colorize {
+            styles.colorize();
+        }
+
+        if self.summary.inconsistent.is_empty() {
+            writeln!(
+                writer,
+                "no tests failed in some of the {} runs and passed in others",
+                self.summary.runs.len()
+            )?;
+        }
+        for test in &self.summary.inconsistent {
+            write_test(&test.test, &styles, &mut writer)?;
+            writeln!(
+                writer,
+                ": failed in {}/{} runs",
+                test.failed_runs, test.runs
+            )?;
+            if test.suspects.is_empty() {
+                writeln!(writer, "    (no suspects found)")?;
+            }
+            let shown = match verbose {
+                true => test.suspects.len(),
+                false => test.suspects.len().min(MAX_HUMAN_SUSPECTS),
+            };
+            for suspect in &test.suspects[..shown] {
+                let relation = match suspect.relation {
+                    OrderDepRelation::RanBefore => "ran before it",
+                    OrderDepRelation::RanAlongside => "ran alongside it",
+                };
+                write!(writer, "    ")?;
+                write_test(&suspect.test, &styles, &mut writer)?;
+                writeln!(
+                    writer,
+                    " {} in {}/{} failing runs and {}/{} passing runs",
+                    relation.style(styles.field),
+                    suspect.failed_runs,
+                    test.failed_runs,
+                    suspect.passed_runs,
+                    test.runs - test.failed_runs,
+                )?;
+            }
+            if shown < test.suspects.len() {
+                writeln!(
+                    writer,
+                    "    ... and {} more (use --verbose to see all suspects)",
+                    test.suspects.len() - shown
+                )?;
+            }
+        }
+
+        if !self.summary.always_failed.is_empty() {
+            writeln!(
+                writer,
+                "{} tests failed in every run and were left out",
+                self.summary.always_failed.len()
+            )?;
+            if verbose {
+                for test in &self.summary.always_failed {
+                    write!(writer, "    ")?;
+                    write_test(test, &styles, &mut writer)?;
+                    writeln!(writer)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn write_test(
+    test: &OrderDepsTestSummary,
+    styles: &Styles,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    write!(writer, "{} ", test.binary_id.style(styles.binary_id))?;
+    write_test_name(&test.name, styles, writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_analyze() {
+        let at = |secs: u64| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        let mut collector = OrderDepsCollector::new();
+
+        // victim and flaky fail after polluter has run and alongside noisy. They also run
+        // alongside each other, but do in the run they pass in too.
+        collector.record_test("crate", "polluter", at(0), at(1), true);
+        collector.record_test("crate", "victim", at(1), at(2), false);
+        collector.record_test("crate", "noisy", at(0), at(2), true);
+        collector.record_test("crate", "flaky", at(1), at(3), false);
+        collector.record_test("crate", "broken", at(3), at(4), false);
+        collector.finish_run(1, 2);
+
+        collector.record_test("crate", "victim", at(0), at(1), true);
+        collector.record_test("crate", "polluter", at(1), at(2), true);
+        collector.record_test("crate", "flaky", at(0), at(1), true);
+        collector.record_test("crate", "noisy", at(1), at(2), true);
+        collector.record_test("crate", "broken", at(2), at(3), false);
+        collector.finish_run(2, 2);
+
+        let summary = collector.analyze().to_summary();
+        assert_eq!(
+            summary.runs,
+            vec![
+                OrderDepsRunSummary {
+                    seed: 1,
+                    test_threads: 2,
+                    passed: 2,
+                    failed: 3,
+                },
+                OrderDepsRunSummary {
+                    seed: 2,
+                    test_threads: 2,
+                    passed: 4,
+                    failed: 1,
+                },
+            ]
+        );
+        let test = |name: &str| test_summary(("crate", name));
+        assert_eq!(summary.always_failed, vec![test("broken")]);
+
+        let suspects: Vec<_> = summary
+            .inconsistent
+            .iter()
+            .map(|inconsistent| {
+                let suspects: Vec<_> = inconsistent
+                    .suspects
+                    .iter()
+                    .map(|suspect| (suspect.test.name.as_str(), suspect.relation))
+                    .collect();
+                (inconsistent.test.name.as_str(), suspects)
+            })
+            .collect();
+        assert_eq!(
+            suspects,
+            vec![
+                (
+                    "flaky",
+                    vec![
+                        ("polluter", OrderDepRelation::RanBefore),
+                        ("noisy", OrderDepRelation::RanAlongside),
+                    ]
+                ),
+                (
+                    "victim",
+                    vec![
+                        ("polluter", OrderDepRelation::RanBefore),
+                        ("noisy", OrderDepRelation::RanAlongside),
+                    ]
+                ),
+            ]
+        );
+    }
+}
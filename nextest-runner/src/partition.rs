@@ -3,15 +3,29 @@
 
 //! Support for partitioning test runs across several machines.
 //!
-//! At the moment this only supports simple hash-based and count-based sharding. In the future it
-//! could potentially be made smarter: e.g. using data to pick different sets of binaries and tests
-//! to run, with an aim to minimize total build and test times.
+//! This supports simple hash-based and count-based sharding, stable sharding which moves as few
+//! tests as possible when the number of shards changes, timed sharding which uses recorded test
+//! durations to balance how long each shard takes, and explicit shard plans. In the future it could
+//! potentially be made smarter: e.g. using data to pick different sets of binaries to build, with
+//! an aim to minimize total build and test times.
 
-use crate::errors::PartitionerBuilderParseError;
+use crate::{
+    errors::{PartitionerBuilderParseError, ShardPlanError},
+    run_store::TestTimings,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use guppy::graph::PackageGraph;
+use nextest_filtering::{FilteringExpr, ParseContext, TestQuery};
+use serde::{Deserialize, Serialize};
 use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap},
     fmt,
     hash::{Hash, Hasher},
+    io,
     str::FromStr,
+    sync::Arc,
+    time::Duration,
 };
 use twox_hash::XxHash64;
 
@@ -39,18 +53,135 @@ pub enum PartitionerBuilder {
         /// The total number of shards.
         total_shards: u64,
     },
+
+    /// Partition based on rendezvous hashing.
+    ///
+    /// Like hash-based partitioning, which shard a test is in doesn't depend on any other tests.
+    /// Additionally, when the number of shards changes from N to N + 1, only the tests that move to
+    /// the new shard change shards.
+    Stable {
+        /// The shard this is in, counting up from 1.
+        shard: u64,
+
+        /// The total number of shards.
+        total_shards: u64,
+    },
+
+    /// Partition based on recorded test durations, so that each shard takes roughly the same
+    /// amount of time to run.
+    ///
+    /// Tests are assigned to shards with [`Self::set_timings`]. Tests without recorded durations
+    /// are partitioned by hashing.
+    Timed {
+        /// The shard this is in, counting up from 1.
+        shard: u64,
+
+        /// The total number of shards.
+        total_shards: u64,
+
+        /// The shards timed tests are assigned to.
+        assignments: TimedAssignments,
+    },
+
+    /// Partition based on a shard plan, which assigns tests to shards explicitly.
+    ///
+    /// The plan is read from `path`, and must be set with [`Self::set_plan`] before building
+    /// partitioners.
+    Plan {
+        /// The shard this is in, counting up from 1.
+        shard: u64,
+
+        /// The path to read the plan from.
+        path: Utf8PathBuf,
+
+        /// The plan, once it's been read.
+        plan: Option<Arc<ShardPlan>>,
+    },
+}
+
+/// The shards that tests with recorded durations are assigned to, for timed partitioning.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TimedAssignments {
+    // binary ID -> test name -> shard, counting up from 0
+    shards: Arc<BTreeMap<String, BTreeMap<String, u64>>>,
+}
+
+impl TimedAssignments {
+    /// Assigns timed tests to shards, longest test first, each to the shard with the least total
+    /// duration so far.
+    ///
+    /// The assignments only depend on the timings, so every shard computes the same assignments
+    /// from the same timings.
+    fn new(timings: &TestTimings, total_shards: u64) -> Self {
+        Self {
+            shards: Arc::new(assign_by_duration(timings.iter().collect(), total_shards)),
+        }
+    }
+
+    fn get(&self, binary_id: &str, test_name: &str) -> Option<u64> {
+        self.shards.get(binary_id)?.get(test_name).copied()
+    }
 }
 
 /// Represents an individual partitioner, typically scoped to a test binary.
 pub trait Partitioner: fmt::Debug {
-    /// Returns true if the given test name matches the partition.
-    fn test_matches(&mut self, test_name: &str) -> bool;
+    /// Returns true if the given test matches the partition.
+    fn test_matches(&mut self, query: &TestQuery<'_>) -> bool;
 }
 
 impl PartitionerBuilder {
+    /// Returns true if this partitioner uses recorded test durations.
+    pub fn needs_timings(&self) -> bool {
+        matches!(self, PartitionerBuilder::Timed { .. })
+    }
+
+    /// Assigns tests to shards based on how long they took to run, for timed partitioning.
+    ///
+    /// This does nothing for other kinds of partitioning.
+    pub fn set_timings(&mut self, timings: &TestTimings) -> &mut Self {
+        if let PartitionerBuilder::Timed {
+            total_shards,
+            assignments,
+            ..
+        } = self
+        {
+            *assignments = TimedAssignments::new(timings, *total_shards);
+        }
+        self
+    }
+
+    /// Returns the path to read the shard plan from, for partitioning based on a plan.
+    pub fn plan_path(&self) -> Option<&Utf8Path> {
+        match self {
+            PartitionerBuilder::Plan { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Sets the shard plan to partition tests with, for partitioning based on a plan.
+    ///
+    /// Returns an error if the shard this is in isn't in the plan. This does nothing for other
+    /// kinds of partitioning.
+    pub fn set_plan(&mut self, shard_plan: ShardPlan) -> Result<&mut Self, ShardPlanError> {
+        if let PartitionerBuilder::Plan { shard, path, plan } = self {
+            if *shard > shard_plan.total_shards {
+                return Err(ShardPlanError::ShardOutOfRange {
+                    path: path.clone(),
+                    shard: *shard,
+                    total_shards: shard_plan.total_shards,
+                });
+            }
+            *plan = Some(Arc::new(shard_plan));
+        }
+        Ok(self)
+    }
+
     /// Creates a new `Partitioner` from this `PartitionerBuilder`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this partitions based on a plan that hasn't been set with [`Self::set_plan`].
     pub fn build(&self) -> Box<dyn Partitioner> {
-        // Note we don't use test_binary at the moment but might in the future.
         match self {
             PartitionerBuilder::Count {
                 shard,
@@ -60,6 +191,25 @@ impl PartitionerBuilder {
                 shard,
                 total_shards,
             } => Box::new(HashPartitioner::new(*shard, *total_shards)),
+            PartitionerBuilder::Stable {
+                shard,
+                total_shards,
+            } => Box::new(StablePartitioner::new(*shard, *total_shards)),
+            PartitionerBuilder::Timed {
+                shard,
+                total_shards,
+                assignments,
+            } => Box::new(TimedPartitioner::new(
+                *shard,
+                *total_shards,
+                assignments.clone(),
+            )),
+            PartitionerBuilder::Plan { shard, plan, .. } => {
+                let plan = plan
+                    .clone()
+                    .expect("shard plan is set before building partitioners");
+                Box::new(PlanPartitioner::new(*shard, plan))
+            }
         }
     }
 }
@@ -83,11 +233,55 @@ impl FromStr for PartitionerBuilder {
                 shard,
                 total_shards,
             })
+        } else if let Some(input) = s.strip_prefix("stable:") {
+            let (shard, total_shards) = parse_shards(input, "stable:M/N")?;
+
+            Ok(PartitionerBuilder::Stable {
+                shard,
+                total_shards,
+            })
+        } else if let Some(input) = s.strip_prefix("plan:") {
+            // The path comes last since it can contain colons itself.
+            let (shard_str, path) = input.split_once(':').ok_or_else(|| {
+                PartitionerBuilderParseError::new(
+                    Some("plan:M:PATH"),
+                    format!("expected input '{}' to be in the format M:PATH", input),
+                )
+            })?;
+            let shard: u64 = shard_str.parse().map_err(|err| {
+                PartitionerBuilderParseError::new(
+                    Some("plan:M:PATH"),
+                    format!("failed to parse shard '{}' as u64: {}", shard_str, err),
+                )
+            })?;
+            if shard == 0 || path.is_empty() {
+                return Err(PartitionerBuilderParseError::new(
+                    Some("plan:M:PATH"),
+                    format!(
+                        "expected input '{}' to have a shard of at least 1 and a path",
+                        input
+                    ),
+                ));
+            }
+
+            Ok(PartitionerBuilder::Plan {
+                shard,
+                path: path.into(),
+                plan: None,
+            })
+        } else if let Some(input) = s.strip_prefix("timed:") {
+            let (shard, total_shards) = parse_shards(input, "timed:M/N")?;
+
+            Ok(PartitionerBuilder::Timed {
+                shard,
+                total_shards,
+                assignments: TimedAssignments::default(),
+            })
         } else {
             Err(PartitionerBuilderParseError::new(
                 None,
                 format!(
-                    "partition input '{}' must begin with \"hash:\" or \"count:\"",
+                    "partition input '{}' must begin with \"hash:\", \"count:\", \"stable:\", \"timed:\" or \"plan:\"",
                     s
                 ),
             ))
@@ -160,7 +354,7 @@ impl CountPartitioner {
 }
 
 impl Partitioner for CountPartitioner {
-    fn test_matches(&mut self, _test_name: &str) -> bool {
+    fn test_matches(&mut self, _query: &TestQuery<'_>) -> bool {
         let matches = self.curr == self.shard_minus_one;
         self.curr = (self.curr + 1) % self.total_shards;
         matches
@@ -184,16 +378,398 @@ impl HashPartitioner {
 }
 
 impl Partitioner for HashPartitioner {
-    fn test_matches(&mut self, test_name: &str) -> bool {
+    fn test_matches(&mut self, query: &TestQuery<'_>) -> bool {
         let mut hasher = XxHash64::default();
-        test_name.hash(&mut hasher);
+        query.test_name.hash(&mut hasher);
         hasher.finish() % self.total_shards == self.shard_minus_one
     }
 }
 
+#[derive(Clone, Debug)]
+struct StablePartitioner {
+    shard_minus_one: u64,
+    total_shards: u64,
+}
+
+impl StablePartitioner {
+    fn new(shard: u64, total_shards: u64) -> Self {
+        let shard_minus_one = shard - 1;
+        Self {
+            shard_minus_one,
+            total_shards,
+        }
+    }
+}
+
+impl Partitioner for StablePartitioner {
+    fn test_matches(&mut self, query: &TestQuery<'_>) -> bool {
+        // Each test goes to the shard with the highest hash of the test and the shard. Adding a
+        // shard only moves the tests for which the new shard has the highest hash.
+        let weight = |shard: u64| {
+            let mut hasher = XxHash64::default();
+            (query.binary_query.binary_id, query.test_name, shard).hash(&mut hasher);
+            hasher.finish()
+        };
+        let chosen = (0..self.total_shards)
+            .max_by_key(|&shard| (weight(shard), Reverse(shard)))
+            .expect("there's at least one shard");
+        chosen == self.shard_minus_one
+    }
+}
+
+#[derive(Clone, Debug)]
+struct TimedPartitioner {
+    shard_minus_one: u64,
+    assignments: TimedAssignments,
+    hash_partitioner: HashPartitioner,
+}
+
+impl TimedPartitioner {
+    fn new(shard: u64, total_shards: u64, assignments: TimedAssignments) -> Self {
+        Self {
+            shard_minus_one: shard - 1,
+            assignments,
+            hash_partitioner: HashPartitioner::new(shard, total_shards),
+        }
+    }
+}
+
+impl Partitioner for TimedPartitioner {
+    fn test_matches(&mut self, query: &TestQuery<'_>) -> bool {
+        match self
+            .assignments
+            .get(query.binary_query.binary_id, query.test_name)
+        {
+            Some(shard) => shard == self.shard_minus_one,
+            None => self.hash_partitioner.test_matches(query),
+        }
+    }
+}
+
+/// A plan that assigns tests to shards, for `--partition plan:M:PATH`.
+///
+/// Plans are JSON files that assign tests to shards either by test ID (`<binary-id> <test-name>`)
+/// or through filtersets. Tests listed by ID take precedence, then the first matching filterset.
+/// Tests that aren't in the plan at all are partitioned by hashing.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ShardPlan {
+    total_shards: u64,
+    filtersets: Vec<PlannedFilterset>,
+    // binary ID -> test name -> shard, counting up from 1
+    tests: BTreeMap<String, BTreeMap<String, u64>>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct PlannedFilterset {
+    input: String,
+    expr: FilteringExpr,
+    shard: u64,
+}
+
+impl ShardPlan {
+    /// Creates a plan which assigns the given tests to shards based on how long they took to run,
+    /// so that each shard takes roughly the same amount of time.
+    ///
+    /// Tests without a duration are assumed to take as long as the average test with one.
+    pub fn from_durations<'a>(
+        tests: impl IntoIterator<Item = (&'a str, &'a str, Option<Duration>)>,
+        total_shards: u64,
+    ) -> Self {
+        Self::from_durations_with_estimates(tests, total_shards).0
+    }
+
+    /// Like [`Self::from_durations`], but also returns the tests assigned to each shard along with
+    /// how long each shard is expected to take.
+    ///
+    /// Shards that don't have any tests assigned to them are included, so there's always one
+    /// estimate per shard.
+    pub fn from_durations_with_estimates<'a>(
+        tests: impl IntoIterator<Item = (&'a str, &'a str, Option<Duration>)>,
+        total_shards: u64,
+    ) -> (Self, Vec<ShardEstimate>) {
+        let tests: Vec<_> = tests.into_iter().collect();
+        let durations: Vec<_> = tests
+            .iter()
+            .filter_map(|(_, _, duration)| *duration)
+            .collect();
+        let average = match durations.len() {
+            0 => Duration::from_secs(1),
+            len => durations.iter().sum::<Duration>() / len as u32,
+        };
+        let tests: Vec<_> = tests
+            .into_iter()
+            .map(|(binary_id, test_name, duration)| {
+                (binary_id, test_name, duration.unwrap_or(average))
+            })
+            .collect();
+
+        let mut shards = assign_by_duration(tests.clone(), total_shards);
+        for tests in shards.values_mut() {
+            for shard in tests.values_mut() {
+                *shard += 1;
+            }
+        }
+
+        let mut estimates: Vec<_> = (1..=total_shards)
+            .map(|shard| ShardEstimate {
+                shard,
+                tests: Vec::new(),
+                estimated_duration: Duration::ZERO,
+            })
+            .collect();
+        for (binary_id, test_name, duration) in tests {
+            let shard = shards[binary_id][test_name];
+            let estimate = &mut estimates[(shard - 1) as usize];
+            estimate.tests.push(format!("{binary_id} {test_name}"));
+            estimate.estimated_duration += duration;
+        }
+        for estimate in &mut estimates {
+            estimate.tests.sort_unstable();
+        }
+
+        let plan = Self {
+            total_shards,
+            filtersets: Vec::new(),
+            tests: shards,
+        };
+        (plan, estimates)
+    }
+
+    /// Reads a plan from a JSON file, parsing its filtersets with the given context.
+    pub fn read_from(
+        path: &Utf8Path,
+        graph: &PackageGraph,
+        cx: &ParseContext<'_>,
+    ) -> Result<Self, ShardPlanError> {
+        let contents = std::fs::read_to_string(path).map_err(|error| ShardPlanError::Read {
+            path: path.to_owned(),
+            error,
+        })?;
+        let summary: ShardPlanSummary =
+            serde_json::from_str(&contents).map_err(|error| ShardPlanError::Deserialize {
+                path: path.to_owned(),
+                error,
+            })?;
+
+        let total_shards = summary.total_shards;
+        if total_shards == 0 {
+            return Err(ShardPlanError::NoShards {
+                path: path.to_owned(),
+            });
+        }
+        let check_shard = |entry: &str, shard: u64| {
+            if (1..=total_shards).contains(&shard) {
+                Ok(())
+            } else {
+                Err(ShardPlanError::InvalidShard {
+                    path: path.to_owned(),
+                    entry: entry.to_owned(),
+                    shard,
+                    total_shards,
+                })
+            }
+        };
+
+        let mut tests: BTreeMap<_, BTreeMap<_, _>> = BTreeMap::new();
+        for (test_id, shard) in summary.tests {
+            check_shard(&test_id, shard)?;
+            // Binary IDs can't contain spaces, but test names (e.g. for doctests) can.
+            let (binary_id, test_name) = match test_id.split_once(' ') {
+                Some(split) => split,
+                None => {
+                    return Err(ShardPlanError::InvalidTestId {
+                        path: path.to_owned(),
+                        test_id,
+                    })
+                }
+            };
+            tests
+                .entry(binary_id.to_owned())
+                .or_default()
+                .insert(test_name.to_owned(), shard);
+        }
+
+        let mut filtersets = Vec::with_capacity(summary.filtersets.len());
+        let mut parse_errors = Vec::new();
+        for filterset in summary.filtersets {
+            check_shard(&filterset.filter, filterset.shard)?;
+            match FilteringExpr::parse_with_context(&filterset.filter, graph, cx) {
+                Ok(expr) => filtersets.push(PlannedFilterset {
+                    input: filterset.filter,
+                    expr,
+                    shard: filterset.shard,
+                }),
+                Err(errors) => parse_errors.push(errors),
+            }
+        }
+        if !parse_errors.is_empty() {
+            return Err(ShardPlanError::FilterParse {
+                path: path.to_owned(),
+                errors: parse_errors,
+            });
+        }
+
+        Ok(Self {
+            total_shards,
+            filtersets,
+            tests,
+        })
+    }
+
+    /// Returns the number of shards in this plan.
+    pub fn total_shards(&self) -> u64 {
+        self.total_shards
+    }
+
+    /// Writes this plan out as JSON.
+    pub fn write(&self, writer: impl io::Write) -> Result<(), serde_json::Error> {
+        let summary = ShardPlanSummary {
+            total_shards: self.total_shards,
+            filtersets: self
+                .filtersets
+                .iter()
+                .map(|filterset| FiltersetSummary {
+                    filter: filterset.input.clone(),
+                    shard: filterset.shard,
+                })
+                .collect(),
+            tests: self
+                .tests
+                .iter()
+                .flat_map(|(binary_id, tests)| {
+                    tests
+                        .iter()
+                        .map(move |(test_name, shard)| (format!("{binary_id} {test_name}"), *shard))
+                })
+                .collect(),
+        };
+        serde_json::to_writer_pretty(writer, &summary)
+    }
+
+    /// Returns the shard the plan assigns this test to, counting up from 1.
+    fn shard_for(&self, query: &TestQuery<'_>) -> Option<u64> {
+        if let Some(shard) = self
+            .tests
+            .get(query.binary_query.binary_id)
+            .and_then(|tests| tests.get(query.test_name))
+        {
+            return Some(*shard);
+        }
+        self.filtersets
+            .iter()
+            .find(|filterset| filterset.expr.matches_test(query))
+            .map(|filterset| filterset.shard)
+    }
+}
+
+/// The tests a [`ShardPlan`] assigns to a shard, and how long they're expected to take.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ShardEstimate {
+    shard: u64,
+    tests: Vec<String>,
+    estimated_duration: Duration,
+}
+
+impl ShardEstimate {
+    /// Returns the shard this estimate is for, counting up from 1.
+    pub fn shard(&self) -> u64 {
+        self.shard
+    }
+
+    /// Returns the IDs (`<binary-id> <test-name>`) of the tests assigned to this shard, in sorted
+    /// order.
+    pub fn tests(&self) -> &[String] {
+        &self.tests
+    }
+
+    /// Returns the sum of the durations of the tests assigned to this shard.
+    pub fn estimated_duration(&self) -> Duration {
+        self.estimated_duration
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct ShardPlanSummary {
+    total_shards: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    filtersets: Vec<FiltersetSummary>,
+    // test ID -> shard
+    #[serde(default)]
+    tests: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct FiltersetSummary {
+    filter: String,
+    shard: u64,
+}
+
+#[derive(Clone, Debug)]
+struct PlanPartitioner {
+    shard: u64,
+    plan: Arc<ShardPlan>,
+    hash_partitioner: HashPartitioner,
+}
+
+impl PlanPartitioner {
+    fn new(shard: u64, plan: Arc<ShardPlan>) -> Self {
+        let hash_partitioner = HashPartitioner::new(shard, plan.total_shards);
+        Self {
+            shard,
+            plan,
+            hash_partitioner,
+        }
+    }
+}
+
+impl Partitioner for PlanPartitioner {
+    fn test_matches(&mut self, query: &TestQuery<'_>) -> bool {
+        match self.plan.shard_for(query) {
+            Some(shard) => shard == self.shard,
+            None => self.hash_partitioner.test_matches(query),
+        }
+    }
+}
+
+/// Assigns tests to shards, longest test first, each to the shard with the least total duration
+/// so far. Shards count up from 0.
+fn assign_by_duration(
+    mut tests: Vec<(&str, &str, Duration)>,
+    total_shards: u64,
+) -> BTreeMap<String, BTreeMap<String, u64>> {
+    tests.sort_by(
+        |(binary_id_1, test_name_1, duration_1), (binary_id_2, test_name_2, duration_2)| {
+            duration_2
+                .cmp(duration_1)
+                .then_with(|| (binary_id_1, test_name_1).cmp(&(binary_id_2, test_name_2)))
+        },
+    );
+
+    // There's no need to keep track of more shards than there are tests.
+    let mut loads: BinaryHeap<_> = (0..total_shards.min(tests.len() as u64))
+        .map(|shard| Reverse((Duration::ZERO, shard)))
+        .collect();
+    let mut shards: BTreeMap<_, BTreeMap<_, _>> = BTreeMap::new();
+    for (binary_id, test_name, duration) in tests {
+        let Reverse((load, shard)) = loads.pop().expect("there's at least one shard");
+        shards
+            .entry(binary_id.to_owned())
+            .or_default()
+            .insert(test_name.to_owned(), shard);
+        loads.push(Reverse((load + duration, shard)));
+    }
+    shards
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use guppy::{graph::cargo::BuildPlatform, CargoMetadata, PackageId};
+    use nextest_filtering::BinaryQuery;
+    use once_cell::sync::Lazy;
+    use tempfile::TempDir;
 
     #[test]
     fn partitioner_builder_from_str() {
@@ -219,6 +795,29 @@ mod tests {
                     total_shards: 200,
                 },
             ),
+            (
+                "stable:3/4",
+                PartitionerBuilder::Stable {
+                    shard: 3,
+                    total_shards: 4,
+                },
+            ),
+            (
+                "plan:2:C:\\plans\\plan.json",
+                PartitionerBuilder::Plan {
+                    shard: 2,
+                    path: "C:\\plans\\plan.json".into(),
+                    plan: None,
+                },
+            ),
+            (
+                "timed:2/3",
+                PartitionerBuilder::Timed {
+                    shard: 2,
+                    total_shards: 3,
+                    assignments: TimedAssignments::default(),
+                },
+            ),
         ];
 
         let failures = vec![
@@ -232,6 +831,14 @@ mod tests {
             "hash:m/2",
             "hash:1/n",
             "hash:1/2/3",
+            "stable:5/4",
+            "timed:0/2",
+            "timed:1",
+            "plan:",
+            "plan:1",
+            "plan:1:",
+            "plan:0:plan.json",
+            "plan:m:plan.json",
         ];
 
         for (input, output) in successes {
@@ -251,4 +858,282 @@ mod tests {
                 .expect_err(&format!("expected input '{}' to fail", input));
         }
     }
+
+    #[test]
+    fn stable_partitioner() {
+        let test_names: Vec<_> = (0..200).map(|i| format!("test_{i}")).collect();
+        let shards_for = |total_shards| {
+            let mut partitioners: Vec<_> = (1..=total_shards)
+                .map(|shard| {
+                    PartitionerBuilder::Stable {
+                        shard,
+                        total_shards,
+                    }
+                    .build()
+                })
+                .collect();
+            test_names
+                .iter()
+                .map(|test_name| {
+                    let mut shards = (0..total_shards).filter(|&shard| {
+                        test_matches(&mut *partitioners[shard as usize], "binary", test_name)
+                    });
+                    let shard = shards.next().expect("test is in a shard");
+                    assert_eq!(shards.next(), None, "{test_name} is in exactly one shard");
+                    shard
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Going from 4 to 5 shards only moves tests to the new shard.
+        let before = shards_for(4);
+        let after = shards_for(5);
+        for ((test_name, before), after) in test_names.iter().zip(before).zip(after) {
+            assert!(
+                before == after || after == 4,
+                "{test_name} moved from shard {before} to {after}"
+            );
+        }
+    }
+
+    #[test]
+    fn timed_partitioner() {
+        let mut timings = TestTimings::default();
+        for (test_name, secs) in [("a", 10), ("b", 7), ("c", 5), ("d", 4), ("e", 2), ("f", 1)] {
+            timings.insert("binary", test_name, Duration::from_secs(secs));
+        }
+
+        let shard_tests = |shard| {
+            let mut builder = PartitionerBuilder::Timed {
+                shard,
+                total_shards: 2,
+                assignments: TimedAssignments::default(),
+            };
+            builder.set_timings(&timings);
+            let mut partitioner = builder.build();
+            ["a", "b", "c", "d", "e", "f"]
+                .into_iter()
+                .filter(|test_name| test_matches(&mut *partitioner, "binary", test_name))
+                .collect::<Vec<_>>()
+        };
+        // The shards take 15 and 14 seconds in total.
+        assert_eq!(shard_tests(1), vec!["a", "d", "f"]);
+        assert_eq!(shard_tests(2), vec!["b", "c", "e"]);
+
+        // Untimed tests are partitioned by hashing, so each of them is in exactly one shard.
+        let mut partitioners: Vec<_> = (1..=3)
+            .map(|shard| {
+                let mut builder = PartitionerBuilder::Timed {
+                    shard,
+                    total_shards: 3,
+                    assignments: TimedAssignments::default(),
+                };
+                builder.set_timings(&timings);
+                builder.build()
+            })
+            .collect();
+        for test_name in ["untimed_1", "untimed_2", "untimed_3"] {
+            let mut matches = 0;
+            for partitioner in &mut partitioners {
+                if test_matches(&mut **partitioner, "other-binary", test_name) {
+                    matches += 1;
+                }
+            }
+            assert_eq!(matches, 1, "{test_name} is in exactly one shard");
+        }
+    }
+
+    #[test]
+    fn plan_partitioner() {
+        let temp_dir = TempDir::new().expect("temp dir created");
+        let temp_dir: &Utf8Path = temp_dir.path().try_into().expect("temp dir is valid UTF-8");
+        let path = temp_dir.join("plan.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "total-shards": 3,
+                "filtersets": [{ "filter": "test(/^slow_/)", "shard": 3 }],
+                "tests": { "binary slow_pinned": 1, "binary fast": 2 }
+            }"#,
+        )
+        .unwrap();
+        let plan = ShardPlan::read_from(&path, &PACKAGE_GRAPH_FIXTURE, &ParseContext::new())
+            .expect("plan is valid");
+
+        let mut partitioners: Vec<_> = (1..=3)
+            .map(|shard| {
+                let mut builder = PartitionerBuilder::Plan {
+                    shard,
+                    path: path.clone(),
+                    plan: None,
+                };
+                builder
+                    .set_plan(plan.clone())
+                    .expect("shard is in the plan");
+                builder.build()
+            })
+            .collect();
+        let mut shards_for = |test_name| {
+            (1..=3)
+                .filter(|&shard| test_matches(&mut *partitioners[shard - 1], "binary", test_name))
+                .collect::<Vec<_>>()
+        };
+        // Test IDs take precedence over filtersets.
+        assert_eq!(shards_for("slow_pinned"), vec![1]);
+        assert_eq!(shards_for("fast"), vec![2]);
+        assert_eq!(shards_for("slow_other"), vec![3]);
+        // Tests that aren't in the plan are hashed.
+        assert_eq!(shards_for("unplanned").len(), 1);
+
+        let mut builder = PartitionerBuilder::Plan {
+            shard: 4,
+            path: path.clone(),
+            plan: None,
+        };
+        let err = builder
+            .set_plan(plan)
+            .expect_err("shard 4 isn't in the plan");
+        assert!(
+            matches!(
+                err,
+                ShardPlanError::ShardOutOfRange {
+                    shard: 4,
+                    total_shards: 3,
+                    ..
+                }
+            ),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn plan_round_trip() {
+        let plan = ShardPlan::from_durations(
+            [
+                ("binary", "a", Some(Duration::from_secs(10))),
+                ("binary", "b", Some(Duration::from_secs(6))),
+                ("binary", "c", Some(Duration::from_secs(2))),
+                ("other-binary", "doctest name", None),
+            ],
+            2,
+        );
+        let mut json = Vec::new();
+        plan.write(&mut json).expect("plan written");
+
+        // The untimed test is assumed to take the average of 6 seconds.
+        let summary: ShardPlanSummary = serde_json::from_slice(&json).expect("valid JSON");
+        let expected: BTreeMap<_, _> = [
+            ("binary a", 1),
+            ("binary b", 2),
+            ("binary c", 1),
+            ("other-binary doctest name", 2),
+        ]
+        .into_iter()
+        .map(|(test_id, shard)| (test_id.to_owned(), shard))
+        .collect();
+        assert_eq!(summary.tests, expected);
+
+        let temp_dir = TempDir::new().expect("temp dir created");
+        let temp_dir: &Utf8Path = temp_dir.path().try_into().expect("temp dir is valid UTF-8");
+        let path = temp_dir.join("plan.json");
+        std::fs::write(&path, &json).unwrap();
+        let read = ShardPlan::read_from(&path, &PACKAGE_GRAPH_FIXTURE, &ParseContext::new())
+            .expect("plan is valid");
+        assert_eq!(read, plan);
+    }
+
+    #[test]
+    fn plan_estimates() {
+        let (plan, estimates) = ShardPlan::from_durations_with_estimates(
+            [
+                ("binary", "a", Some(Duration::from_secs(10))),
+                ("binary", "b", Some(Duration::from_secs(6))),
+                ("binary", "c", Some(Duration::from_secs(2))),
+                ("other-binary", "doctest name", None),
+            ],
+            3,
+        );
+        assert_eq!(plan.total_shards(), 3);
+        let estimates: Vec<_> = estimates
+            .iter()
+            .map(|estimate| {
+                (
+                    estimate.shard(),
+                    estimate.tests().to_vec(),
+                    estimate.estimated_duration().as_secs(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            estimates,
+            vec![
+                (1, vec!["binary a".to_owned()], 10),
+                (2, vec!["binary b".to_owned(), "binary c".to_owned()], 8),
+                (3, vec!["other-binary doctest name".to_owned()], 6),
+            ]
+        );
+
+        // Shards without any tests still get an estimate.
+        let (_, estimates) = ShardPlan::from_durations_with_estimates([("binary", "a", None)], 2);
+        assert_eq!(estimates.len(), 2);
+        assert!(estimates[1].tests().is_empty());
+        assert_eq!(estimates[1].estimated_duration(), Duration::ZERO);
+    }
+
+    #[test]
+    fn plan_errors() {
+        let temp_dir = TempDir::new().expect("temp dir created");
+        let temp_dir: &Utf8Path = temp_dir.path().try_into().expect("temp dir is valid UTF-8");
+        let path = temp_dir.join("plan.json");
+        let read_err = |contents: &str| {
+            std::fs::write(&path, contents).unwrap();
+            ShardPlan::read_from(&path, &PACKAGE_GRAPH_FIXTURE, &ParseContext::new())
+                .expect_err("plan is invalid")
+        };
+
+        assert!(matches!(
+            read_err(r#"{ "total-shards": 0 }"#),
+            ShardPlanError::NoShards { .. }
+        ));
+        assert!(matches!(
+            read_err(r#"{ "total-shards": 2, "tests": { "binary test": 3 } }"#),
+            ShardPlanError::InvalidShard { shard: 3, .. }
+        ));
+        assert!(matches!(
+            read_err(r#"{ "total-shards": 2, "tests": { "binary-only": 1 } }"#),
+            ShardPlanError::InvalidTestId { .. }
+        ));
+        assert!(matches!(
+            read_err(r#"{ "total-shards": 2, "filtersets": [{ "filter": "test(", "shard": 1 }] }"#),
+            ShardPlanError::FilterParse { .. }
+        ));
+        assert!(matches!(
+            read_err(r#"{ "tests": {} }"#),
+            ShardPlanError::Deserialize { .. }
+        ));
+    }
+
+    fn test_matches(partitioner: &mut dyn Partitioner, binary_id: &str, test_name: &str) -> bool {
+        let package_id = PackageId::new("fake-package");
+        partitioner.test_matches(&TestQuery {
+            binary_query: BinaryQuery {
+                package_id: &package_id,
+                binary_id,
+                binary_name: binary_id,
+                binary_path: Utf8Path::new("/fake/binary"),
+                kind: "lib",
+                features: &[],
+                platform: BuildPlatform::Target,
+            },
+            test_name,
+        })
+    }
+
+    static PACKAGE_GRAPH_FIXTURE: Lazy<PackageGraph> = Lazy::new(|| {
+        static FIXTURE_JSON: &str = include_str!("../../fixtures/cargo-metadata.json");
+        let metadata = CargoMetadata::parse_json(FIXTURE_JSON).expect("fixture is valid JSON");
+        metadata
+            .build_graph()
+            .expect("fixture is valid PackageGraph")
+    });
 }
@@ -0,0 +1,359 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Finding processes started by tests.
+//!
+//! Tests are run in their own process groups, which signals are sent to on termination. Processes
+//! started by a test can move out of its process group, for example by calling `setsid`, so on
+//! Linux nextest also looks for these through `/proc` and signals them individually.
+//!
+//! Processes still in a test's process group after it exits are reported as leaked.
+
+use crate::runner::LeakedProcess;
+use std::fmt::Write;
+
+/// Processes descended from a test process that aren't in its process group.
+#[derive(Debug)]
+pub(crate) struct EscapedProcesses {
+    processes: Vec<ProcessId>,
+}
+
+/// A process ID along with when the process started, so that a reused ID isn't mistaken for it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+struct ProcessId {
+    pid: i32,
+    start_time: u64,
+}
+
+impl EscapedProcesses {
+    /// Finds descendants of the process `pid`, which leads its own process group, that have left
+    /// that group.
+    pub(crate) fn find(pid: i32) -> Self {
+        Self {
+            processes: imp::escaped_descendants(pid),
+        }
+    }
+
+    /// Sends `signal` to each process that's still running.
+    pub(crate) fn signal(&self, signal: i32) {
+        for process in &self.processes {
+            if imp::is_running(process) {
+                // SAFETY: kill is safe to call with any arguments.
+                unsafe {
+                    libc::kill(process.pid, signal);
+                }
+            }
+        }
+    }
+
+    /// Returns true if any of the processes are still running.
+    pub(crate) fn any_running(&self) -> bool {
+        self.processes.iter().any(imp::is_running)
+    }
+}
+
+/// Returns the processes still running in the process group `pgid`, sorted by process ID.
+pub(crate) fn group_processes(pgid: i32) -> Vec<LeakedProcess> {
+    // Signal 0 only checks whether any process in the group exists, which is much cheaper than
+    // looking for the processes.
+    // SAFETY: kill is safe to call with any arguments.
+    if unsafe { libc::kill(-pgid, 0) } != 0 {
+        return vec![];
+    }
+    let mut processes = imp::group_processes(pgid);
+    processes.sort_by_key(|process| process.pid);
+    processes
+}
+
+/// Describes what the processes in the process group `pgid` are doing, to help diagnose tests
+/// that hang.
+///
+/// Each process is listed with its command line. On Linux, the threads of each process are listed
+/// too, along with their states and the kernel functions they're waiting in.
+pub(crate) fn describe_group(pgid: i32) -> String {
+    let mut description = String::new();
+    for process in group_processes(pgid) {
+        let _ = writeln!(
+            description,
+            "process {}: {}",
+            process.pid,
+            process.command.as_deref().unwrap_or("<unknown command>"),
+        );
+        for thread in imp::threads(process.pid as i32) {
+            let _ = write!(
+                description,
+                "  thread {} ({}): {}",
+                thread.tid,
+                thread.name,
+                state_name(thread.state),
+            );
+            match &thread.wchan {
+                Some(wchan) => {
+                    let _ = writeln!(description, ", waiting in {wchan}");
+                }
+                None => description.push('\n'),
+            }
+        }
+    }
+    description
+}
+
+/// A thread of a process, as described by [`describe_group`].
+#[derive(Clone, Debug)]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+struct ThreadInfo {
+    tid: i32,
+    name: String,
+    state: char,
+    // The kernel function the thread is waiting in, if any.
+    wchan: Option<String>,
+}
+
+/// Returns a description of a process state from `/proc/<pid>/stat`.
+fn state_name(state: char) -> String {
+    let name = match state {
+        'R' => "running",
+        'S' => "sleeping",
+        'D' => "waiting uninterruptibly",
+        'T' => "stopped",
+        't' => "stopped by a tracer",
+        'Z' => "zombie",
+        'X' => "dead",
+        'I' => "idle",
+        _ => return format!("state {state}"),
+    };
+    name.to_owned()
+}
+
+/// The fields of `/proc/<pid>/stat` that are used here.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+struct ProcStat {
+    pid: i32,
+    state: char,
+    ppid: i32,
+    pgrp: i32,
+    start_time: u64,
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_stat(stat: &str) -> Option<ProcStat> {
+    // The command name is in parentheses and can contain spaces and parentheses itself, so split
+    // on the last closing parenthesis.
+    let (pid, rest) = stat.rsplit_once(')')?;
+    let pid = pid.split_whitespace().next()?.parse().ok()?;
+    let mut fields = rest.split_whitespace();
+    let state = fields.next()?.chars().next()?;
+    let ppid = fields.next()?.parse().ok()?;
+    let pgrp = fields.next()?.parse().ok()?;
+    // The start time is field 22 of the file, and the process group is field 5.
+    let start_time = fields.nth(22 - 5 - 1)?.parse().ok()?;
+    Some(ProcStat {
+        pid,
+        state,
+        ppid,
+        pgrp,
+        start_time,
+    })
+}
+
+/// Parses the output of `ps -A -o pid= -o pgid= -o args=` into the processes in group `pgid`.
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+fn parse_ps(output: &str, pgid: i32) -> Vec<LeakedProcess> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.trim_start().splitn(2, char::is_whitespace);
+            let pid = fields.next()?.parse().ok()?;
+            let mut rest = fields.next()?.trim_start().splitn(2, char::is_whitespace);
+            let process_pgid: i32 = rest.next()?.parse().ok()?;
+            let command = rest
+                .next()
+                .map(str::trim)
+                .filter(|command| !command.is_empty());
+            (process_pgid == pgid).then(|| LeakedProcess {
+                pid,
+                command: command.map(str::to_owned),
+            })
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn read_stat(pid: i32) -> Option<ProcStat> {
+        let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+        parse_stat(&stat)
+    }
+
+    /// Reads the stat files of every process.
+    fn all_stats() -> Vec<ProcStat> {
+        let entries = match std::fs::read_dir("/proc") {
+            Ok(entries) => entries,
+            Err(_) => return vec![],
+        };
+        entries
+            .flatten()
+            .filter_map(|entry| {
+                let pid = entry.file_name().to_str()?.parse().ok()?;
+                read_stat(pid)
+            })
+            .collect()
+    }
+
+    fn read_cmdline(pid: i32) -> Option<String> {
+        let cmdline = std::fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+        let args: Vec<_> = cmdline
+            .split(|&b| b == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(String::from_utf8_lossy)
+            .collect();
+        (!args.is_empty()).then(|| args.join(" "))
+    }
+
+    pub(super) fn group_processes(pgid: i32) -> Vec<LeakedProcess> {
+        all_stats()
+            .into_iter()
+            .filter(|stat| stat.pgrp == pgid && stat.state != 'Z')
+            .map(|stat| LeakedProcess {
+                pid: stat.pid as u32,
+                command: read_cmdline(stat.pid),
+            })
+            .collect()
+    }
+
+    pub(super) fn threads(pid: i32) -> Vec<ThreadInfo> {
+        let entries = match std::fs::read_dir(format!("/proc/{pid}/task")) {
+            Ok(entries) => entries,
+            Err(_) => return vec![],
+        };
+        let mut threads: Vec<_> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let tid: i32 = entry.file_name().to_str()?.parse().ok()?;
+                let dir = entry.path();
+                let stat = parse_stat(&std::fs::read_to_string(dir.join("stat")).ok()?)?;
+                let name = std::fs::read_to_string(dir.join("comm")).unwrap_or_default();
+                // wchan is "0" for threads that aren't waiting in the kernel, and can't be read
+                // without privileges on some systems.
+                let wchan = std::fs::read_to_string(dir.join("wchan"))
+                    .ok()
+                    .filter(|wchan| !wchan.is_empty() && wchan != "0");
+                Some(ThreadInfo {
+                    tid,
+                    name: name.trim_end().to_owned(),
+                    state: stat.state,
+                    wchan,
+                })
+            })
+            .collect();
+        threads.sort_by_key(|thread| thread.tid);
+        threads
+    }
+
+    pub(super) fn escaped_descendants(pid: i32) -> Vec<ProcessId> {
+        let mut children: HashMap<i32, Vec<ProcStat>> = HashMap::new();
+        for stat in all_stats() {
+            children.entry(stat.ppid).or_default().push(stat);
+        }
+
+        let mut escaped = vec![];
+        let mut stack = vec![pid];
+        while let Some(parent) = stack.pop() {
+            for stat in children.get(&parent).into_iter().flatten() {
+                if stat.pgrp != pid {
+                    escaped.push(ProcessId {
+                        pid: stat.pid,
+                        start_time: stat.start_time,
+                    });
+                }
+                stack.push(stat.pid);
+            }
+        }
+        escaped
+    }
+
+    pub(super) fn is_running(process: &ProcessId) -> bool {
+        read_stat(process.pid).map_or(false, |stat| {
+            stat.start_time == process.start_time && stat.state != 'Z'
+        })
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use super::*;
+
+    pub(super) fn group_processes(pgid: i32) -> Vec<LeakedProcess> {
+        let output = std::process::Command::new("ps")
+            .args(["-A", "-o", "pid=", "-o", "pgid=", "-o", "args="])
+            .stdin(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .output();
+        match output {
+            Ok(output) if output.status.success() => {
+                parse_ps(&String::from_utf8_lossy(&output.stdout), pgid)
+            }
+            _ => vec![],
+        }
+    }
+
+    pub(super) fn threads(_pid: i32) -> Vec<ThreadInfo> {
+        // Threads aren't listed on other platforms.
+        vec![]
+    }
+
+    pub(super) fn escaped_descendants(_pid: i32) -> Vec<ProcessId> {
+        // Only the process group is signaled on other platforms.
+        vec![]
+    }
+
+    pub(super) fn is_running(_process: &ProcessId) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stat() {
+        let stat = "4242 (my (odd) test) S 4200 4200 4200 0 -1 4194560 100 0 0 0 1 2 0 0 20 0 1 0 \
+                    98765 1000000 200 18446744073709551615 1 1 0 0 0 0 0 0 0 0 0 0 17 3 0 0 0 0 0\n";
+        assert_eq!(
+            parse_stat(stat),
+            Some(ProcStat {
+                pid: 4242,
+                state: 'S',
+                ppid: 4200,
+                pgrp: 4200,
+                start_time: 98765,
+            })
+        );
+        assert_eq!(parse_stat("4242 (truncated) S 1"), None);
+    }
+
+    #[test]
+    fn test_parse_ps() {
+        let output =
+            "    1     1 /sbin/launchd\n  501   500 sleep 15\n  502   500 \n  503   503 bash\n";
+        assert_eq!(
+            parse_ps(output, 500),
+            vec![
+                LeakedProcess {
+                    pid: 501,
+                    command: Some("sleep 15".to_owned()),
+                },
+                LeakedProcess {
+                    pid: 502,
+                    command: None,
+                },
+            ]
+        );
+    }
+}
@@ -6,6 +6,19 @@
 //! The main structure in this module is [`TestReporter`].
 
 mod aggregator;
+mod breakdown;
+mod dashboard;
+mod eta;
+mod github;
+mod html;
+mod json;
+mod libtest;
+mod markdown;
+mod sarif;
+mod signature;
+mod tap;
+mod teamcity;
+mod timeline;
 pub use aggregator::heuristic_extract_description;
 use uuid::Uuid;
 
@@ -14,27 +27,48 @@ use crate::{
     errors::{StatusLevelParseError, TestOutputDisplayParseError, WriteEventError},
     helpers::write_test_name,
     list::{TestInstance, TestList},
-    reporter::aggregator::EventAggregator,
+    reporter::{
+        aggregator::EventAggregator,
+        breakdown::Breakdown,
+        dashboard::Dashboard,
+        eta::{format_eta, EtaEstimator},
+        github::GithubActionsReporter,
+        json::JsonReporter,
+        libtest::LibtestReporter,
+        markdown::MarkdownSummary,
+        sarif::SarifReporter,
+        signature::FailureSignature,
+        tap::TapReporter,
+        teamcity::TeamcityReporter,
+    },
+    run_store::{terminated_by_cancel, RunStore, TestTimings},
     runner::{
         AbortStatus, ExecuteStatus, ExecutionDescription, ExecutionResult, ExecutionStatuses,
-        RunStats,
+        LineDifference, OutputDifference, OutputStream, PassRateCheck, RunStats,
     },
 };
+use bytes::Bytes;
+use camino::Utf8PathBuf;
 use debug_ignore::DebugIgnore;
 use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use nextest_filtering::RunStatus;
 use nextest_metadata::MismatchReason;
 use owo_colors::{OwoColorize, Style};
 use serde::Deserialize;
 use std::{
     borrow::Cow,
     cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
     fmt::{self, Write as _},
     io,
     io::{BufWriter, Write},
     str::FromStr,
-    time::{Duration, SystemTime},
+    time::{Duration, Instant, SystemTime},
 };
 
+/// The number of tests listed for each way tests failed in the final summary, at most.
+const MAX_GROUP_TESTS_SHOWN: usize = 5;
+
 /// When to display test output in the reporter.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -103,6 +137,45 @@ impl fmt::Display for TestOutputDisplay {
     }
 }
 
+/// The format of the messages the reporter writes to standard output while tests are running.
+///
+/// Whichever format is chosen, nextest's own human-readable output is written to standard error.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum MessageFormat {
+    /// Nothing is written to standard output.
+    Human,
+
+    /// Each test event is written as a JSON line as soon as it occurs, in the format described by
+    /// [`RunEventSummary`](nextest_metadata::RunEventSummary).
+    Json,
+
+    /// Test events are written as JSON lines in the format libtest produces with `--format json`,
+    /// grouped into a suite for each test binary.
+    LibtestJson,
+
+    /// Test results are written in the Test Anything Protocol, version 14, as each test finishes.
+    Tap,
+
+    /// GitHub Actions workflow commands are written for tests that fail or are flaky, so that they
+    /// show up as annotations on the lines they failed at.
+    GithubActions,
+
+    /// TeamCity service messages are written as tests start and finish, with a flow ID for each
+    /// test.
+    Teamcity,
+
+    /// A SARIF log with a result for each test that fails or is flaky is written once the run
+    /// finishes, for code scanning services to ingest.
+    Sarif,
+}
+
+impl Default for MessageFormat {
+    fn default() -> Self {
+        Self::Human
+    }
+}
+
 /// Status level to show in the reporter output.
 ///
 /// Status levels are incremental: each level causes all the statuses listed above it to be output. For example,
@@ -233,11 +306,18 @@ pub enum ReporterStderr<'a> {
 #[derive(Debug, Default)]
 pub struct TestReporterBuilder {
     no_capture: bool,
+    live_output: bool,
     failure_output: Option<TestOutputDisplay>,
     success_output: Option<TestOutputDisplay>,
     status_level: Option<StatusLevel>,
     final_status_level: Option<FinalStatusLevel>,
     verbose: bool,
+    message_format: MessageFormat,
+    summary_markdown: Option<Utf8PathBuf>,
+    dashboard: bool,
+    no_group_failures: bool,
+    breakdown: bool,
+    output_timestamps: bool,
 }
 
 impl TestReporterBuilder {
@@ -250,6 +330,16 @@ impl TestReporterBuilder {
         self
     }
 
+    /// Sets live-output mode.
+    ///
+    /// In this mode, each line a test writes is shown as soon as it's written, tagged with the name
+    /// of the test. `failure_output` and `success_output` will be ignored, since test output has
+    /// already been shown.
+    pub fn set_live_output(&mut self, live_output: bool) -> &mut Self {
+        self.live_output = live_output;
+        self
+    }
+
     /// Sets the conditions under which test failures are output.
     pub fn set_failure_output(&mut self, failure_output: TestOutputDisplay) -> &mut Self {
         self.failure_output = Some(failure_output);
@@ -279,6 +369,51 @@ impl TestReporterBuilder {
         self.verbose = verbose;
         self
     }
+
+    /// Sets the format of the messages written to standard output.
+    pub fn set_message_format(&mut self, message_format: MessageFormat) -> &mut Self {
+        self.message_format = message_format;
+        self
+    }
+
+    /// Sets a file to append a Markdown summary of the run to, once it finishes.
+    pub fn set_summary_markdown(&mut self, path: impl Into<Utf8PathBuf>) -> &mut Self {
+        self.summary_markdown = Some(path.into());
+        self
+    }
+
+    /// Sets whether to show a full-screen dashboard while tests run, in place of the progress bar.
+    ///
+    /// The dashboard is only shown if standard error is a terminal. It's not shown in no-capture
+    /// mode, since tests write to the terminal directly then.
+    pub fn set_dashboard(&mut self, dashboard: bool) -> &mut Self {
+        self.dashboard = dashboard;
+        self
+    }
+
+    /// Sets whether to list each failing test separately in the final summary, rather than
+    /// grouping tests that failed the same way.
+    ///
+    /// By default, tests that panicked at the same place with the same message are grouped, and
+    /// if failure output is shown at the end of the run, it's only shown for one test per group.
+    pub fn set_no_group_failures(&mut self, no_group_failures: bool) -> &mut Self {
+        self.no_group_failures = no_group_failures;
+        self
+    }
+
+    /// Sets whether to write out a table of how tests did in each package and test binary after
+    /// the summary.
+    pub fn set_breakdown(&mut self, breakdown: bool) -> &mut Self {
+        self.breakdown = breakdown;
+        self
+    }
+
+    /// Sets whether to prefix each line of test output that's shown with the time since the test
+    /// started that it was written at.
+    pub fn set_output_timestamps(&mut self, output_timestamps: bool) -> &mut Self {
+        self.output_timestamps = output_timestamps;
+        self
+    }
 }
 
 impl TestReporterBuilder {
@@ -290,11 +425,7 @@ impl TestReporterBuilder {
         output: ReporterStderr<'a>,
     ) -> TestReporter<'a> {
         let styles = Box::new(Styles::default());
-        let binary_id_width = test_list
-            .iter()
-            .filter_map(|(_, info)| (info.status.test_count() > 0).then(|| info.binary_id.len()))
-            .max()
-            .unwrap_or_default();
+        let binary_id_width = binary_id_width(test_list);
         let aggregator = EventAggregator::new(profile);
 
         let status_level = self.status_level.unwrap_or_else(|| profile.status_level());
@@ -308,14 +439,14 @@ impl TestReporterBuilder {
             .unwrap_or_else(|| profile.final_status_level());
 
         // failure_output and success_output are meaningless if the runner isn't capturing any
-        // output.
-        let failure_output = match self.no_capture {
+        // output, or if it's shown as tests write it.
+        let failure_output = match self.no_capture || self.live_output {
             true => TestOutputDisplay::Never,
             false => self
                 .failure_output
                 .unwrap_or_else(|| profile.failure_output()),
         };
-        let success_output = match self.no_capture {
+        let success_output = match self.no_capture || self.live_output {
             true => TestOutputDisplay::Never,
             false => self
                 .success_output
@@ -323,6 +454,11 @@ impl TestReporterBuilder {
         };
 
         let stderr = match (output, self.no_capture) {
+            (ReporterStderr::Terminal, false)
+                if self.dashboard && console::Term::stderr().is_term() =>
+            {
+                ReporterStderrImpl::Dashboard(Dashboard::new())
+            }
             (ReporterStderr::Terminal, false) => {
                 let progress_bar = ProgressBar::new(test_list.test_count() as u64);
                 // Emulate Cargo's style.
@@ -350,7 +486,10 @@ impl TestReporterBuilder {
                 progress_bar.set_draw_target(ProgressDrawTarget::stderr_nohz());
                 // Enable a steady tick 10 times a second.
                 progress_bar.enable_steady_tick(100);
-                ReporterStderrImpl::TerminalWithBar(progress_bar)
+                ReporterStderrImpl::TerminalWithBar {
+                    progress_bar,
+                    eta: Box::new(EtaEstimator::new(read_eta_timings(profile))),
+                }
             }
             (ReporterStderr::Terminal, true) => {
                 // Do not use a progress bar if --no-capture is passed in. This is required since we
@@ -377,16 +516,53 @@ impl TestReporterBuilder {
                 styles,
                 cancel_status: None,
                 final_outputs: DebugIgnore(vec![]),
+                not_run: DebugIgnore(vec![]),
+                quarantined: DebugIgnore(vec![]),
+                nondeterministic: DebugIgnore(vec![]),
+                iterations: DebugIgnore(BTreeMap::new()),
+                until_failure: false,
+                first_failure: None,
+                shuffle_seed: None,
+                seed: 0,
+                resumable: false,
+                terminated: 0,
+                previously_failed: DebugIgnore(vec![]),
+                group_failures: !self.no_group_failures,
+                failure_signatures: DebugIgnore(vec![]),
+                breakdown: self.breakdown.then(Breakdown::default),
+                live_output_styles: DebugIgnore(HashMap::new()),
+                output_timestamps: self.output_timestamps,
+                listed: None,
             },
             stderr,
+            stdout_reporter: match self.message_format {
+                MessageFormat::Human => None,
+                MessageFormat::Json => Some(StdoutReporter::Json(JsonReporter::stdout())),
+                MessageFormat::LibtestJson => {
+                    Some(StdoutReporter::Libtest(LibtestReporter::stdout()))
+                }
+                MessageFormat::Tap => Some(StdoutReporter::Tap(TapReporter::stdout())),
+                MessageFormat::GithubActions => Some(StdoutReporter::GithubActions(
+                    GithubActionsReporter::stdout(),
+                )),
+                MessageFormat::Teamcity => {
+                    Some(StdoutReporter::Teamcity(TeamcityReporter::stdout()))
+                }
+                MessageFormat::Sarif => Some(StdoutReporter::Sarif(SarifReporter::stdout())),
+            },
+            summary_markdown: self.summary_markdown.clone().map(MarkdownSummary::new),
             metadata_reporter: aggregator,
         }
     }
 }
 
 enum ReporterStderrImpl<'a> {
-    TerminalWithBar(ProgressBar),
+    TerminalWithBar {
+        progress_bar: ProgressBar,
+        eta: Box<EtaEstimator>,
+    },
     TerminalWithoutBar,
+    Dashboard(Dashboard),
     Buffer(&'a mut Vec<u8>),
 }
 
@@ -394,13 +570,28 @@ enum ReporterStderrImpl<'a> {
 pub struct TestReporter<'a> {
     inner: TestReporterImpl<'a>,
     stderr: ReporterStderrImpl<'a>,
+    stdout_reporter: Option<StdoutReporter<'a>>,
+    summary_markdown: Option<MarkdownSummary>,
     metadata_reporter: EventAggregator<'a>,
 }
 
+/// Writes test events to standard output, for `--message-format`.
+enum StdoutReporter<'a> {
+    Json(JsonReporter<io::Stdout>),
+    Libtest(LibtestReporter<'a, io::Stdout>),
+    Tap(TapReporter<io::Stdout>),
+    GithubActions(GithubActionsReporter<io::Stdout>),
+    Teamcity(TeamcityReporter<io::Stdout>),
+    Sarif(SarifReporter<io::Stdout>),
+}
+
 impl<'a> TestReporter<'a> {
     /// Colorizes output.
     pub fn colorize(&mut self) {
         self.inner.styles.colorize();
+        if let ReporterStderrImpl::Dashboard(dashboard) = &mut self.stderr {
+            dashboard.colorize();
+        }
     }
 
     /// Report a test event.
@@ -408,6 +599,21 @@ impl<'a> TestReporter<'a> {
         self.write_event(event)
     }
 
+    /// Returns true if the tests that failed were asked to be run again once the run finishes,
+    /// from the dashboard.
+    ///
+    /// The tests can be run again by resuming the run with the [`ResumedRun`] returned by
+    /// [`FailureRetry::into_resumed_run`].
+    ///
+    /// [`ResumedRun`]: crate::run_store::ResumedRun
+    /// [`FailureRetry::into_resumed_run`]: crate::run_store::FailureRetry::into_resumed_run
+    pub fn retry_failures_requested(&self) -> bool {
+        match &self.stderr {
+            ReporterStderrImpl::Dashboard(dashboard) => dashboard.retry_failures_requested(),
+            _ => false,
+        }
+    }
+
     // ---
     // Helper methods
     // ---
@@ -415,7 +621,7 @@ impl<'a> TestReporter<'a> {
     /// Report this test event to the given writer.
     fn write_event(&mut self, event: TestEvent<'a>) -> Result<(), WriteEventError> {
         match &mut self.stderr {
-            ReporterStderrImpl::TerminalWithBar(progress_bar) => {
+            ReporterStderrImpl::TerminalWithBar { progress_bar, eta } => {
                 // Write to a string that will be printed as a log line.
                 let mut buf: Vec<u8> = Vec::new();
                 self.inner
@@ -424,7 +630,8 @@ impl<'a> TestReporter<'a> {
                 let s = String::from_utf8_lossy(&buf);
                 progress_bar.println(&s);
 
-                update_progress_bar(&event, &self.inner.styles, progress_bar);
+                eta.write_event(&event, Instant::now());
+                update_progress_bar(&event, &self.inner.styles, progress_bar, eta);
             }
             ReporterStderrImpl::TerminalWithoutBar => {
                 // Write to a buffered stderr.
@@ -434,18 +641,65 @@ impl<'a> TestReporter<'a> {
                     .map_err(WriteEventError::Io)?;
                 writer.flush().map_err(WriteEventError::Io)?;
             }
+            ReporterStderrImpl::Dashboard(dashboard) => {
+                // The regular output is written out once the dashboard is closed.
+                self.inner
+                    .write_event_impl(&event, dashboard.held_back())
+                    .map_err(WriteEventError::Io)?;
+                dashboard.write_event(&event, &self.inner.styles);
+            }
             ReporterStderrImpl::Buffer(buf) => {
                 self.inner
                     .write_event_impl(&event, buf)
                     .map_err(WriteEventError::Io)?;
             }
         }
+        match &mut self.stdout_reporter {
+            Some(StdoutReporter::Json(reporter)) => reporter.write_event(&event)?,
+            Some(StdoutReporter::Libtest(reporter)) => reporter.write_event(&event)?,
+            Some(StdoutReporter::Tap(reporter)) => reporter.write_event(&event)?,
+            Some(StdoutReporter::GithubActions(reporter)) => reporter.write_event(&event)?,
+            Some(StdoutReporter::Teamcity(reporter)) => reporter.write_event(&event)?,
+            Some(StdoutReporter::Sarif(reporter)) => reporter.write_event(&event)?,
+            None => {}
+        }
+        if let Some(summary_markdown) = &mut self.summary_markdown {
+            summary_markdown.write_event(&event)?;
+        }
         self.metadata_reporter.write_event(event)?;
         Ok(())
     }
 }
 
-fn update_progress_bar<'a>(event: &TestEvent<'a>, styles: &Styles, progress_bar: &mut ProgressBar) {
+/// Reads the timings to estimate how long the run has left with, if there are any.
+fn read_eta_timings(profile: &NextestProfile<'_>) -> Option<TestTimings> {
+    match RunStore::new(profile).read_timings() {
+        Ok(timings) => timings,
+        Err(err) => {
+            log::debug!(
+                target: "nextest-runner",
+                "failed to read test timings, estimating time left from this run only: {err}",
+            );
+            None
+        }
+    }
+}
+
+/// Returns the width to align the binary IDs of tests in this list to.
+fn binary_id_width(test_list: &TestList) -> usize {
+    test_list
+        .iter()
+        .filter_map(|(_, info)| (info.status.test_count() > 0).then(|| info.binary_id.len()))
+        .max()
+        .unwrap_or_default()
+}
+
+fn update_progress_bar<'a>(
+    event: &TestEvent<'a>,
+    styles: &Styles,
+    progress_bar: &mut ProgressBar,
+    eta: &EtaEstimator,
+) {
     match event {
         TestEvent::TestStarted {
             current_stats,
@@ -461,7 +715,24 @@ fn update_progress_bar<'a>(event: &TestEvent<'a>, styles: &Styles, progress_bar:
         } => {
             let running_state = RunningState::new(*cancel_state, current_stats);
             progress_bar.set_prefix(running_state.progress_bar_prefix(styles));
-            progress_bar.set_message(progress_bar_msg(current_stats, *running, styles));
+            let mut msg = progress_bar_msg(current_stats, *running, styles);
+            if cancel_state.is_none() {
+                let estimate = eta.estimate(Instant::now());
+                match (estimate.remaining, estimate.throughput) {
+                    (Some(remaining), Some(throughput)) => {
+                        let _ = write!(
+                            msg,
+                            " (ETA {}, {throughput:.1} tests/s)",
+                            format_eta(remaining).style(styles.count),
+                        );
+                    }
+                    (Some(remaining), None) => {
+                        let _ = write!(msg, " (ETA {})", format_eta(remaining).style(styles.count));
+                    }
+                    (None, _) => {}
+                }
+            }
+            progress_bar.set_message(msg);
             // If there are skipped tests, the initial run count will be lower than when constructed
             // in ProgressBar::new.
             progress_bar.set_length(current_stats.initial_run_count as u64);
@@ -513,6 +784,11 @@ fn progress_bar_msg(current_stats: &RunStats, running: usize, styles: &Styles) -
     s
 }
 
+/// Formats a fraction between 0 and 1 as a percentage, with at most two decimal places.
+fn format_percent(fraction: f64) -> String {
+    format!("{}%", (fraction * 10000.0).round() / 100.0)
+}
+
 fn write_summary_str(run_stats: &RunStats, styles: &Styles, out: &mut String) -> fmt::Result {
     write!(
         out,
@@ -521,8 +797,14 @@ fn write_summary_str(run_stats: &RunStats, styles: &Styles, out: &mut String) ->
         "passed".style(styles.pass)
     )?;
 
-    if run_stats.passed_slow > 0 || run_stats.flaky > 0 || run_stats.leaky > 0 {
-        let mut text = Vec::with_capacity(3);
+    if run_stats.passed_slow > 0
+        || run_stats.flaky > 0
+        || run_stats.leaky > 0
+        || run_stats.xfailed > 0
+        || run_stats.xpassed > 0
+        || run_stats.nondeterministic > 0
+    {
+        let mut text = Vec::with_capacity(6);
         if run_stats.passed_slow > 0 {
             text.push(format!(
                 "{} {}",
@@ -544,6 +826,27 @@ fn write_summary_str(run_stats: &RunStats, styles: &Styles, out: &mut String) ->
                 "leaky".style(styles.skip),
             ));
         }
+        if run_stats.xfailed > 0 {
+            text.push(format!(
+                "{} {}",
+                run_stats.xfailed.style(styles.count),
+                "xfailed".style(styles.skip),
+            ));
+        }
+        if run_stats.xpassed > 0 {
+            text.push(format!(
+                "{} {}",
+                run_stats.xpassed.style(styles.count),
+                "xpassed".style(styles.skip),
+            ));
+        }
+        if run_stats.nondeterministic > 0 {
+            text.push(format!(
+                "{} {}",
+                run_stats.nondeterministic.style(styles.count),
+                "nondeterministic".style(styles.fail),
+            ));
+        }
         write!(out, " ({})", text.join(", "))?;
     }
     write!(out, ", ")?;
@@ -575,6 +878,15 @@ fn write_summary_str(run_stats: &RunStats, styles: &Styles, out: &mut String) ->
         )?;
     }
 
+    if run_stats.quarantined_failed > 0 {
+        write!(
+            out,
+            "{} {}, ",
+            run_stats.quarantined_failed.style(styles.count),
+            "quarantined failed".style(styles.skip),
+        )?;
+    }
+
     write!(
         out,
         "{} {}",
@@ -610,6 +922,57 @@ struct TestReporterImpl<'a> {
     styles: Box<Styles>,
     cancel_status: Option<CancelReason>,
     final_outputs: DebugIgnore<Vec<(TestInstance<'a>, FinalOutput)>>,
+    // Tests that weren't started because the run time budget was used up.
+    not_run: DebugIgnore<Vec<TestInstance<'a>>>,
+    // The last attempts of quarantined tests.
+    quarantined: DebugIgnore<Vec<(TestInstance<'a>, ExecuteStatus)>>,
+    // Tests that behaved differently when they were run again, with --check-determinism.
+    nondeterministic: DebugIgnore<Vec<(TestInstance<'a>, OutputDifference)>>,
+    // How each test did in each of its iterations, with --iterations.
+    iterations: DebugIgnore<BTreeMap<(&'a str, &'a str), IterationStats<'a>>>,
+    // Set if tests are run until one fails.
+    until_failure: bool,
+    // The test that failed first along with the iteration it failed in, with --until-failure.
+    first_failure: Option<(TestInstance<'a>, usize)>,
+    // The seed tests were shuffled with, which is printed after the summary.
+    shuffle_seed: Option<u64>,
+    // The seed each test's NEXTEST_TEST_SEED was derived from, which is printed after the summary
+    // if any tests failed.
+    seed: u64,
+    // Set unless tests are run several times, in which case the run can't be resumed.
+    resumable: bool,
+    // The number of tests that failed because they were terminated when the run was canceled.
+    terminated: usize,
+    // Tests that failed before the run was resumed, along with how long they took.
+    previously_failed: DebugIgnore<Vec<(TestInstance<'a>, Duration)>>,
+    // Set unless --no-group-failures is passed in.
+    group_failures: bool,
+    // What each test that failed in this run failed with, if it could be worked out.
+    failure_signatures: DebugIgnore<Vec<(TestInstance<'a>, FailureSignature)>>,
+    // Set if --breakdown is passed in.
+    breakdown: Option<Breakdown<'a>>,
+    // The index into Styles::live_output for each test that's written output in live-output mode,
+    // handed out in turn.
+    live_output_styles: DebugIgnore<HashMap<(&'a str, &'a str), usize>>,
+    // Set if --output-timestamps is passed in.
+    output_timestamps: bool,
+    // The number of tests listed so far, if tests are run while they're being listed.
+    listed: Option<ListedCounts>,
+}
+
+// Running totals of the tests listed while tests are being run.
+#[derive(Debug, Default)]
+struct ListedCounts {
+    run_count: usize,
+    skip_count: usize,
+    binary_count: usize,
+}
+
+// The number of iterations of a test that finished, and how many of them passed.
+struct IterationStats<'a> {
+    test_instance: TestInstance<'a>,
+    passed: usize,
+    finished: usize,
 }
 
 impl<'a> TestReporterImpl<'a> {
@@ -618,25 +981,123 @@ impl<'a> TestReporterImpl<'a> {
         event: &TestEvent<'a>,
         writer: &mut impl Write,
     ) -> io::Result<()> {
+        if let Some(breakdown) = &mut self.breakdown {
+            breakdown.write_event(event);
+        }
+
         match event {
-            TestEvent::RunStarted { test_list, .. } => {
+            TestEvent::RunStarted {
+                test_list,
+                run_id,
+                iterations,
+                until_failure,
+                shuffle_seed,
+                seed,
+                previously_finished,
+                listing,
+            } => {
+                self.until_failure = *until_failure;
+                self.shuffle_seed = *shuffle_seed;
+                self.seed = *seed;
+                self.resumable = *iterations == Some(1) && !until_failure;
                 write!(writer, "{:>12} ", "Starting".style(self.styles.pass))?;
 
                 let count_style = self.styles.count;
 
-                write!(
-                    writer,
-                    "{} tests across {} binaries",
-                    test_list.run_count().style(count_style),
-                    test_list.binary_count().style(count_style),
-                )?;
+                if *listing {
+                    // The number of tests is reported as binaries are listed.
+                    self.listed = Some(ListedCounts::default());
+                    write!(writer, "tests as binaries are listed")?;
+                } else {
+                    write!(
+                        writer,
+                        "{} tests across {} binaries",
+                        test_list.run_count().style(count_style),
+                        test_list.binary_count().style(count_style),
+                    )?;
+                }
+                match (iterations, until_failure) {
+                    (None, _) => write!(writer, ", until a test fails")?,
+                    (Some(iterations), true) => write!(
+                        writer,
+                        ", up to {} iterations until a test fails",
+                        iterations.style(count_style)
+                    )?,
+                    (Some(iterations), false) if *iterations > 1 => write!(
+                        writer,
+                        ", {} iterations each",
+                        iterations.style(count_style)
+                    )?,
+                    (Some(_), false) => {}
+                }
 
                 let skip_count = test_list.skip_count();
-                if skip_count > 0 {
+                if skip_count > 0 && !listing {
                     write!(writer, " ({} skipped)", skip_count.style(count_style))?;
                 }
 
                 writeln!(writer)?;
+
+                if let Some(previously_finished) = previously_finished {
+                    writeln!(
+                        writer,
+                        "{:>12} run {}: {} tests already finished",
+                        "Resuming".style(self.styles.pass),
+                        run_id.style(count_style),
+                        previously_finished.style(count_style),
+                    )?;
+                }
+            }
+            TestEvent::TestsAdded { test_list } => {
+                self.binary_id_width = self.binary_id_width.max(binary_id_width(test_list));
+                let listed = self.listed.get_or_insert_with(ListedCounts::default);
+                listed.run_count += test_list.run_count();
+                listed.skip_count += test_list.skip_count();
+                listed.binary_count += test_list.binary_count();
+
+                if test_list.run_count() > 0 && self.status_level >= StatusLevel::Pass {
+                    let count_style = self.styles.count;
+                    write!(
+                        writer,
+                        "{:>12} {} tests from ",
+                        "Adding".style(self.styles.pass),
+                        test_list.run_count().style(count_style),
+                    )?;
+                    let mut binary_ids = test_list.iter().map(|(_, info)| &info.binary_id);
+                    match (binary_ids.next(), binary_ids.next()) {
+                        (Some(binary_id), None) => write!(
+                            writer,
+                            "{}",
+                            binary_id.style(self.styles.list_styles.binary_id)
+                        )?,
+                        _ => write!(
+                            writer,
+                            "{} binaries",
+                            test_list.binary_count().style(count_style),
+                        )?,
+                    }
+                    write!(
+                        writer,
+                        " ({} tests across {} binaries so far",
+                        listed.run_count.style(count_style),
+                        listed.binary_count.style(count_style),
+                    )?;
+                    if listed.skip_count > 0 {
+                        write!(writer, ", {} skipped", listed.skip_count.style(count_style))?;
+                    }
+                    writeln!(writer, ")")?;
+                }
+            }
+            TestEvent::TestPreviouslyFinished {
+                test_instance,
+                status,
+                time_taken,
+                ..
+            } => {
+                // These are listed after the summary, since their output wasn't kept.
+                if *status == RunStatus::Failed {
+                    self.previously_failed.push((*test_instance, *time_taken));
+                }
             }
             TestEvent::TestStarted { test_instance, .. } => {
                 // In no-capture mode, print out a test start event.
@@ -662,9 +1123,42 @@ impl<'a> TestReporterImpl<'a> {
                     writeln!(writer)?;
                 }
             }
+            TestEvent::TestOutput {
+                test_instance,
+                output,
+                elapsed,
+                ..
+            } => {
+                let next = self.live_output_styles.len();
+                let style = self.styles.live_output[*self
+                    .live_output_styles
+                    .entry(test_instance.sort_key())
+                    .or_insert(next)
+                    % self.styles.live_output.len()];
+                let tag = format!(
+                    "[{} {}]",
+                    test_instance.bin_info.binary_id, test_instance.name
+                );
+                for line in output.split_inclusive(|&b| b == b'\n') {
+                    write!(writer, "{}", tag.style(style))?;
+                    // Don't leave trailing whitespace after the tag on blank lines.
+                    if line != b"\n" || self.output_timestamps {
+                        write!(writer, " ")?;
+                    }
+                    if self.output_timestamps {
+                        self.write_output_timestamp(*elapsed, line, writer)?;
+                    }
+                    self.write_test_output(line, writer)?;
+                    if !line.ends_with(b"\n") {
+                        writeln!(writer)?;
+                    }
+                }
+            }
             TestEvent::TestRetry {
                 test_instance,
                 run_status,
+                delay,
+                deferred,
             } => {
                 if self.status_level >= StatusLevel::Retry {
                     let retry_string =
@@ -676,6 +1170,11 @@ impl<'a> TestReporterImpl<'a> {
 
                     // Print the name of the test.
                     self.write_instance(*test_instance, writer)?;
+                    if *deferred {
+                        write!(writer, " (retrying at the end of the run)")?;
+                    } else if !delay.is_zero() {
+                        write!(writer, " (retrying in {:.3}s)", delay.as_secs_f64())?;
+                    }
                     writeln!(writer)?;
 
                     // This test is guaranteed to have failed.
@@ -690,17 +1189,92 @@ impl<'a> TestReporterImpl<'a> {
                     // The final output doesn't show retries.
                 }
             }
+            TestEvent::TestIsolationChecked {
+                test_instance,
+                run_status,
+            } => {
+                if self.status_level >= StatusLevel::Fail {
+                    let (status, style, verdict) = match run_status.result.is_success() {
+                        true => (
+                            "ALONE PASS".to_owned(),
+                            self.styles.pass,
+                            "only fails alongside other tests",
+                        ),
+                        false => (
+                            format!("ALONE {}", short_status_str(run_status.result)),
+                            self.styles.fail,
+                            "fails in isolation",
+                        ),
+                    };
+                    write!(writer, "{:>12} ", status.style(style))?;
+                    self.write_duration(run_status.time_taken, writer)?;
+                    self.write_instance(*test_instance, writer)?;
+                    writeln!(writer, " ({verdict})")?;
+                }
+            }
+            TestEvent::TestDeterminismChecked {
+                test_instance,
+                run_status,
+                difference: Some(difference),
+            } => {
+                if self.status_level >= StatusLevel::Fail {
+                    write!(writer, "{:>12} ", "NONDETERM".style(self.styles.fail))?;
+                    self.write_duration(run_status.time_taken, writer)?;
+                    self.write_instance(*test_instance, writer)?;
+                    self.write_output_difference(difference, writer)?;
+                    if *difference == OutputDifference::Failed && self.failure_output.is_immediate()
+                    {
+                        self.write_stdout_stderr(test_instance, run_status, false, writer)?;
+                    }
+                }
+                self.nondeterministic
+                    .push((*test_instance, difference.clone()));
+            }
+            TestEvent::TestDeterminismChecked {
+                difference: None, ..
+            } => {}
             TestEvent::TestFinished {
                 test_instance,
                 run_statuses,
+                quarantined,
+                cancel_state,
                 ..
             } => {
                 let describe = run_statuses.describe();
                 let last_status = run_statuses.last_status();
-                let test_output_display = match last_status.result.is_success() {
-                    true => self.success_output,
-                    false => self.failure_output,
-                };
+                if terminated_by_cancel(run_statuses, *cancel_state) {
+                    self.terminated += 1;
+                }
+                if *quarantined {
+                    // These are listed after the summary, so that they can be tracked easily.
+                    self.quarantined.push((*test_instance, last_status.clone()));
+                }
+                if self.until_failure
+                    && self.first_failure.is_none()
+                    && !*quarantined
+                    && !last_status.result.is_success()
+                {
+                    self.first_failure = Some((*test_instance, last_status.iteration));
+                }
+                if self.group_failures && !*quarantined && !last_status.result.is_success() {
+                    if let Some(signature) = FailureSignature::new(last_status) {
+                        self.failure_signatures.push((*test_instance, signature));
+                    }
+                }
+                if last_status.total_iterations != Some(1) {
+                    let stats =
+                        self.iterations
+                            .entry(test_instance.sort_key())
+                            .or_insert(IterationStats {
+                                test_instance: *test_instance,
+                                passed: 0,
+                                finished: 0,
+                            });
+                    stats.passed += usize::from(last_status.result.is_success());
+                    stats.finished += 1;
+                }
+                let test_output_display =
+                    self.output_display(test_instance, last_status.result.is_success());
 
                 if self.status_level >= describe.status_level() {
                     self.write_status_line(*test_instance, describe, writer)?;
@@ -735,12 +1309,18 @@ impl<'a> TestReporterImpl<'a> {
                         .push((*test_instance, FinalOutput::Skipped(*reason)));
                 }
             }
+            TestEvent::TestNotRun { test_instance } => {
+                // These are listed after the summary, so that they can be found easily.
+                self.not_run.push(*test_instance);
+            }
             TestEvent::RunBeginCancel { running, reason } => {
                 self.cancel_status = self.cancel_status.max(Some(*reason));
 
                 write!(writer, "{:>12} ", "Canceling".style(self.styles.fail))?;
                 let reason_str = match reason {
                     CancelReason::TestFailure => "test failure",
+                    CancelReason::BuildFailure => "build failure",
+                    CancelReason::RunTimeBudget => "run time budget",
                     CancelReason::ReportError => "error",
                     CancelReason::Signal => "signal",
                     CancelReason::Interrupt => "interrupt",
@@ -756,9 +1336,10 @@ impl<'a> TestReporterImpl<'a> {
 
             TestEvent::RunFinished {
                 start_time: _start_time,
+                run_id,
                 elapsed,
                 run_stats,
-                ..
+                pass_rates,
             } => {
                 let summary_style = if run_stats.any_failed() {
                     self.styles.fail
@@ -796,6 +1377,188 @@ impl<'a> TestReporterImpl<'a> {
                 let _ = write_summary_str(run_stats, &self.styles, &mut summary_str);
                 writeln!(writer, " tests run: {summary_str}")?;
 
+                for check in pass_rates {
+                    let style = match check.is_met() {
+                        true => self.styles.pass,
+                        false => self.styles.fail,
+                    };
+                    write!(
+                        writer,
+                        "{:>12} {} of {} tests",
+                        "Pass rate".style(style),
+                        format_percent(check.pass_rate()).style(style),
+                        (check.passed + check.failed).style(self.styles.count),
+                    )?;
+                    match &check.test_group {
+                        Some(test_group) => write!(writer, " in group `{test_group}`")?,
+                        None if pass_rates.len() > 1 => write!(writer, " outside groups")?,
+                        None => {}
+                    }
+                    writeln!(
+                        writer,
+                        " passed, at least {} required",
+                        format_percent(check.required),
+                    )?;
+                }
+
+                if let Some(seed) = self.shuffle_seed {
+                    writeln!(
+                        writer,
+                        "{:>12} with seed {}: pass in `--shuffle-seed {}` to run tests in this order",
+                        "Shuffled".style(self.styles.pass),
+                        seed.style(self.styles.count),
+                        seed,
+                    )?;
+                }
+                if run_stats.any_failed() {
+                    writeln!(
+                        writer,
+                        "{:>12} with seed {}: pass in `--seed {}` to give tests the same \
+                         NEXTEST_TEST_SEED values",
+                        "Seeded".style(self.styles.pass),
+                        self.seed.style(self.styles.count),
+                        self.seed,
+                    )?;
+                }
+
+                // Say why the rest of the tests weren't run if the run was cut short by failures.
+                if self.until_failure {
+                    self.write_until_failure_line(writer)?;
+                } else if self.cancel_status == Some(CancelReason::TestFailure)
+                    && run_stats.finished_count < run_stats.initial_run_count
+                {
+                    let failed_count = run_stats.failed_count();
+                    writeln!(
+                        writer,
+                        "{:>12} run after {} failed {}: {} tests not run",
+                        "Aborted".style(self.styles.fail),
+                        failed_count.style(self.styles.count),
+                        if failed_count == 1 { "test" } else { "tests" },
+                        (run_stats.initial_run_count - run_stats.finished_count)
+                            .style(self.styles.count),
+                    )?;
+                }
+                let unfinished =
+                    run_stats.initial_run_count - run_stats.finished_count + self.terminated;
+                if self.resumable && unfinished > 0 {
+                    writeln!(
+                        writer,
+                        "{:>12} with `--resume {}` to run the {} tests that didn't finish",
+                        "Resume".style(self.styles.skip),
+                        run_id,
+                        unfinished.style(self.styles.count),
+                    )?;
+                }
+
+                if !self.previously_failed.is_empty() {
+                    self.previously_failed
+                        .sort_by_key(|(test_instance, _)| test_instance.sort_key());
+                    writeln!(
+                        writer,
+                        "{:>12} {} tests failed before the run was resumed",
+                        "RESUMED".style(self.styles.fail),
+                        self.previously_failed.len().style(self.styles.count),
+                    )?;
+                    for (test_instance, time_taken) in &*self.previously_failed {
+                        write!(writer, "{:>12} ", "FAIL".style(self.styles.fail))?;
+                        self.write_duration(*time_taken, writer)?;
+                        self.write_instance(*test_instance, writer)?;
+                        writeln!(writer)?;
+                    }
+                }
+
+                if !self.not_run.is_empty() {
+                    self.not_run
+                        .sort_by_key(|test_instance| test_instance.sort_key());
+                    writeln!(
+                        writer,
+                        "{:>12} {} tests not run due to the run time budget",
+                        "NOT RUN".style(self.styles.skip),
+                        self.not_run.len().style(self.styles.count),
+                    )?;
+                    for test_instance in &*self.not_run {
+                        write!(
+                            writer,
+                            "{:>12} [         ] ",
+                            "NOT RUN".style(self.styles.skip)
+                        )?;
+                        self.write_instance(*test_instance, writer)?;
+                        writeln!(writer)?;
+                    }
+                }
+
+                if !self.quarantined.is_empty() {
+                    self.quarantined
+                        .sort_by_key(|(test_instance, _)| test_instance.sort_key());
+                    writeln!(
+                        writer,
+                        "{:>12} {} quarantined tests run: {} {}, {} {}",
+                        "QUARANTINE".style(self.styles.skip),
+                        run_stats.quarantined.style(self.styles.count),
+                        (run_stats.quarantined - run_stats.quarantined_failed)
+                            .style(self.styles.count),
+                        "passed".style(self.styles.pass),
+                        run_stats.quarantined_failed.style(self.styles.count),
+                        "failed".style(self.styles.fail),
+                    )?;
+                    for (test_instance, last_status) in &*self.quarantined {
+                        let style = match last_status.result.is_success() {
+                            true => self.styles.pass,
+                            false => self.styles.fail,
+                        };
+                        write!(
+                            writer,
+                            "{:>12} ",
+                            status_str(last_status.result).style(style)
+                        )?;
+                        self.write_duration(last_status.time_taken, writer)?;
+                        self.write_instance(*test_instance, writer)?;
+                        writeln!(writer)?;
+                    }
+                }
+
+                if !self.nondeterministic.is_empty() {
+                    self.nondeterministic
+                        .sort_by_key(|(test_instance, _)| test_instance.sort_key());
+                    writeln!(
+                        writer,
+                        "{:>12} {} tests behaved differently when run again",
+                        "NONDETERM".style(self.styles.fail),
+                        self.nondeterministic.len().style(self.styles.count),
+                    )?;
+                    for (test_instance, difference) in &*self.nondeterministic {
+                        write!(
+                            writer,
+                            "{:>12} [         ] ",
+                            "NONDETERM".style(self.styles.fail)
+                        )?;
+                        self.write_instance(*test_instance, writer)?;
+                        self.write_output_difference(difference, writer)?;
+                    }
+                }
+
+                if !self.iterations.is_empty() {
+                    self.write_iteration_stats(writer)?;
+                }
+
+                if let Some(breakdown) = &self.breakdown {
+                    breakdown.write(&self.styles, writer)?;
+                }
+
+                // Tests whose failure output is left out, since another test failed the same way.
+                let mut same_failure = HashSet::new();
+                let groups = self.failure_groups();
+                if groups.iter().any(|group| group.len() > 1) {
+                    self.write_failure_groups(&groups, writer)?;
+                    for group in &groups {
+                        same_failure.extend(
+                            group[1..]
+                                .iter()
+                                .map(|test_instance| test_instance.sort_key()),
+                        );
+                    }
+                }
+
                 // Don't print out final outputs if canceled due to Ctrl-C.
                 if self.cancel_status < Some(CancelReason::Signal) {
                     // Sort the final outputs for a friendlier experience.
@@ -816,10 +1579,8 @@ impl<'a> TestReporterImpl<'a> {
                             }
                             FinalOutput::Executed(run_statuses) => {
                                 let last_status = run_statuses.last_status();
-                                let test_output_display = match last_status.result.is_success() {
-                                    true => self.success_output,
-                                    false => self.failure_output,
-                                };
+                                let test_output_display = self
+                                    .output_display(test_instance, last_status.result.is_success());
 
                                 if self.final_status_level >= final_status_level {
                                     self.write_final_status_line(
@@ -831,7 +1592,9 @@ impl<'a> TestReporterImpl<'a> {
                                 // This was previously gated on "if self.status_level >= StatusLevel::Fail"
                                 // but that seems incorrect -- the test output display and status level
                                 // controls are independent of each other.
-                                if test_output_display.is_final() {
+                                if test_output_display.is_final()
+                                    && !same_failure.contains(&test_instance.sort_key())
+                                {
                                     self.write_stdout_stderr(
                                         test_instance,
                                         last_status,
@@ -849,6 +1612,35 @@ impl<'a> TestReporterImpl<'a> {
         Ok(())
     }
 
+    fn write_output_difference(
+        &self,
+        difference: &OutputDifference,
+        writer: &mut impl Write,
+    ) -> io::Result<()> {
+        let (
+            stream,
+            LineDifference {
+                line,
+                first,
+                second,
+            },
+        ) = match difference {
+            OutputDifference::Failed => return writeln!(writer, " (failed when run again)"),
+            OutputDifference::Stdout(line) => ("stdout", line),
+            OutputDifference::Stderr(line) => ("stderr", line),
+        };
+        writeln!(writer, " ({stream} differs at line {line})")?;
+        for (run, line) in [("first run: ", first), ("second run:", second)] {
+            writeln!(
+                writer,
+                "{:>12}   {run} {}",
+                "",
+                line.as_deref().unwrap_or("(end of output)"),
+            )?;
+        }
+        Ok(())
+    }
+
     fn write_skip_line(
         &self,
         test_instance: TestInstance<'a>,
@@ -859,6 +1651,9 @@ impl<'a> TestReporterImpl<'a> {
         write!(writer, "[         ] ")?;
 
         self.write_instance(test_instance, writer)?;
+        if let Some(reason) = &test_instance.test_info.ignore_reason {
+            write!(writer, " (ignored: {})", reason)?;
+        }
         writeln!(writer)?;
 
         Ok(())
@@ -872,13 +1667,20 @@ impl<'a> TestReporterImpl<'a> {
     ) -> io::Result<()> {
         let last_status = describe.last_status();
         match describe {
-            ExecutionDescription::Success { .. } => {
-                if last_status.result == ExecutionResult::Leak {
-                    write!(writer, "{:>12} ", "LEAK".style(self.styles.skip))?;
-                } else {
+            ExecutionDescription::Success { .. } => match last_status.result {
+                ExecutionResult::Leak
+                | ExecutionResult::ExpectedFailure
+                | ExecutionResult::UnexpectedPass => {
+                    write!(
+                        writer,
+                        "{:>12} ",
+                        status_str(last_status.result).style(self.styles.skip)
+                    )?;
+                }
+                _ => {
                     write!(writer, "{:>12} ", "PASS".style(self.styles.pass))?;
                 }
-            }
+            },
             ExecutionDescription::Flaky { .. } => {
                 // Use the skip color to also represent a flaky test.
                 write!(
@@ -911,6 +1713,7 @@ impl<'a> TestReporterImpl<'a> {
 
         // Print the name of the test.
         self.write_instance(test_instance, writer)?;
+        self.write_iteration(last_status, writer)?;
         writeln!(writer)?;
 
         // On Windows, also print out the exception if available.
@@ -923,6 +1726,32 @@ impl<'a> TestReporterImpl<'a> {
             self.write_windows_message_line(nt_status, writer)?;
         }
 
+        self.write_leaked_processes(last_status, writer)?;
+        if let Some(kept_tmpdir) = &last_status.kept_tmpdir {
+            write!(writer, "{:>12} ", "TMPDIR".style(self.styles.skip))?;
+            writeln!(writer, "kept at {}", kept_tmpdir.display())?;
+        }
+
+        Ok(())
+    }
+
+    fn write_leaked_processes(
+        &self,
+        run_status: &ExecuteStatus,
+        writer: &mut impl Write,
+    ) -> io::Result<()> {
+        for process in &run_status.leaked_processes {
+            write!(writer, "{:>12} ", "PROCESS".style(self.styles.skip))?;
+            write!(
+                writer,
+                "pid {} left running",
+                process.pid.style(self.styles.count)
+            )?;
+            match &process.command {
+                Some(command) => writeln!(writer, ": {}", command)?,
+                None => writeln!(writer)?,
+            }
+        }
         Ok(())
     }
 
@@ -986,6 +1815,7 @@ impl<'a> TestReporterImpl<'a> {
 
         // Print the name of the test.
         self.write_instance(test_instance, writer)?;
+        self.write_iteration(last_status, writer)?;
         writeln!(writer)?;
 
         // On Windows, also print out the exception if available.
@@ -1001,6 +1831,175 @@ impl<'a> TestReporterImpl<'a> {
         Ok(())
     }
 
+    fn write_iteration(
+        &self,
+        run_status: &ExecuteStatus,
+        writer: &mut impl Write,
+    ) -> io::Result<()> {
+        match run_status.total_iterations {
+            Some(1) => {}
+            Some(total_iterations) => write!(
+                writer,
+                " (iteration {}/{})",
+                run_status.iteration, total_iterations
+            )?,
+            None => write!(writer, " (iteration {})", run_status.iteration)?,
+        }
+        Ok(())
+    }
+
+    /// Writes out why the run stopped, with --until-failure.
+    fn write_until_failure_line(&self, writer: &mut impl Write) -> io::Result<()> {
+        match self.first_failure {
+            Some((test_instance, iteration)) => {
+                write!(
+                    writer,
+                    "{:>12} on iteration {} after ",
+                    "Stopped".style(self.styles.fail),
+                    iteration.style(self.styles.count),
+                )?;
+                self.write_instance(test_instance, writer)?;
+                writeln!(writer, " failed")
+            }
+            None => {
+                let iterations = self
+                    .iterations
+                    .values()
+                    .map(|stats| stats.finished)
+                    .max()
+                    .unwrap_or(0);
+                writeln!(
+                    writer,
+                    "{:>12} after {} iterations with no failures",
+                    "Stopped".style(self.styles.pass),
+                    iterations.style(self.styles.count),
+                )
+            }
+        }
+    }
+
+    /// Returns when to display the output of a test that finished.
+    fn output_display(&self, test_instance: &TestInstance<'a>, success: bool) -> TestOutputDisplay {
+        // The output of tests run in live-output mode has already been shown.
+        if self
+            .live_output_styles
+            .contains_key(&test_instance.sort_key())
+        {
+            return TestOutputDisplay::Never;
+        }
+        match success {
+            true => self.success_output,
+            false => self.failure_output,
+        }
+    }
+
+    /// Groups the tests that failed by what they failed with, with the largest groups first. The
+    /// first test in each group is the one its output is shown for.
+    fn failure_groups(&self) -> Vec<Vec<TestInstance<'a>>> {
+        let mut groups: BTreeMap<&str, Vec<TestInstance<'a>>> = BTreeMap::new();
+        for (test_instance, signature) in &*self.failure_signatures {
+            groups
+                .entry(&signature.key)
+                .or_default()
+                .push(*test_instance);
+        }
+        let mut groups: Vec<_> = groups.into_values().collect();
+        for group in &mut groups {
+            group.sort_by_key(|test_instance| test_instance.sort_key());
+            // With --iterations, a test can fail the same way more than once.
+            group.dedup_by_key(|test_instance| test_instance.sort_key());
+        }
+        groups.sort_by_key(|group| (Reverse(group.len()), group[0].sort_key()));
+        groups
+    }
+
+    /// Writes out the ways tests failed, along with the tests that failed each way.
+    fn write_failure_groups(
+        &self,
+        groups: &[Vec<TestInstance<'a>>],
+        writer: &mut impl Write,
+    ) -> io::Result<()> {
+        writeln!(
+            writer,
+            "{:>12} {} unique failure signatures across {} tests",
+            "SIGNATURES".style(self.styles.fail),
+            groups.len().style(self.styles.count),
+            groups
+                .iter()
+                .map(Vec::len)
+                .sum::<usize>()
+                .style(self.styles.count),
+        )?;
+        for group in groups {
+            let signature = self
+                .failure_signatures
+                .iter()
+                .find(|(test_instance, _)| test_instance.sort_key() == group[0].sort_key())
+                .map(|(_, signature)| signature)
+                .expect("each group has a signature");
+            writeln!(
+                writer,
+                "{:>12} {} {}:",
+                "FAIL".style(self.styles.fail),
+                group.len().style(self.styles.count),
+                if group.len() == 1 { "test" } else { "tests" },
+            )?;
+            for line in signature.message.lines() {
+                writeln!(writer, "{:>12} {}", "", line.style(self.styles.fail_output))?;
+            }
+            for (i, test_instance) in group.iter().enumerate() {
+                if i == MAX_GROUP_TESTS_SHOWN && group.len() > MAX_GROUP_TESTS_SHOWN + 1 {
+                    writeln!(
+                        writer,
+                        "{:>12} ... and {} more tests",
+                        "",
+                        (group.len() - i).style(self.styles.count),
+                    )?;
+                    break;
+                }
+                write!(writer, "{:>12} ", "")?;
+                self.write_instance(*test_instance, writer)?;
+                writeln!(writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes out the pass rate of each test that failed in any of its iterations.
+    fn write_iteration_stats(&self, writer: &mut impl Write) -> io::Result<()> {
+        let failing: Vec<_> = self
+            .iterations
+            .values()
+            .filter(|stats| stats.passed < stats.finished)
+            .collect();
+        writeln!(
+            writer,
+            "{:>12} {} tests run repeatedly: {} {}, {} {}",
+            "ITERATIONS".style(self.styles.skip),
+            self.iterations.len().style(self.styles.count),
+            (self.iterations.len() - failing.len()).style(self.styles.count),
+            "passed every iteration".style(self.styles.pass),
+            failing.len().style(self.styles.count),
+            "failed at least once".style(self.styles.fail),
+        )?;
+        for stats in failing {
+            let (status, style) = match stats.passed {
+                0 => ("FAIL", self.styles.fail),
+                _ => ("FLAKY", self.styles.skip),
+            };
+            write!(writer, "{:>12} ", status.style(style))?;
+            let pass_rate = stats.passed as f64 * 100.0 / stats.finished as f64;
+            write!(writer, "[{:>7.1}%] ", pass_rate)?;
+            self.write_instance(stats.test_instance, writer)?;
+            writeln!(
+                writer,
+                " ({}/{} iterations passed)",
+                stats.passed, stats.finished
+            )?;
+        }
+        Ok(())
+    }
+
     fn write_instance(
         &self,
         instance: TestInstance<'a>,
@@ -1068,6 +2067,36 @@ impl<'a> TestReporterImpl<'a> {
             (self.styles.fail, self.styles.fail_output)
         };
 
+        if !is_retry && !run_status.result.is_success() {
+            if let Some(location) = &test_instance.test_info.location {
+                write!(writer, "\n{}", "--- ".style(header_style))?;
+                let out_len = self.write_attempt(run_status, header_style, writer)?;
+                // The width is to align test instances.
+                write!(
+                    writer,
+                    "{:width$}",
+                    "LOCATION:".style(header_style),
+                    width = (21 - out_len)
+                )?;
+                self.write_instance(*test_instance, writer)?;
+                writeln!(writer, "{}", " ---".style(header_style))?;
+                writeln!(writer, "{}", location)?;
+            }
+
+            write!(writer, "\n{}", "--- ".style(header_style))?;
+            let out_len = self.write_attempt(run_status, header_style, writer)?;
+            // The width is to align test instances.
+            write!(
+                writer,
+                "{:width$}",
+                "SEED:".style(header_style),
+                width = (21 - out_len)
+            )?;
+            self.write_instance(*test_instance, writer)?;
+            writeln!(writer, "{}", " ---".style(header_style))?;
+            writeln!(writer, "NEXTEST_TEST_SEED={}", run_status.test_seed)?;
+        }
+
         if !run_status.stdout.is_empty() {
             write!(writer, "\n{}", "--- ".style(header_style))?;
             let out_len = self.write_attempt(run_status, header_style, writer)?;
@@ -1081,7 +2110,7 @@ impl<'a> TestReporterImpl<'a> {
             self.write_instance(*test_instance, writer)?;
             writeln!(writer, "{}", " ---".style(header_style))?;
 
-            self.write_test_output(&run_status.stdout, writer)?;
+            self.write_captured_output(run_status, OutputStream::Stdout, writer)?;
         }
 
         if !run_status.stderr.is_empty() {
@@ -1097,12 +2126,66 @@ impl<'a> TestReporterImpl<'a> {
             self.write_instance(*test_instance, writer)?;
             writeln!(writer, "{}", " ---".style(header_style))?;
 
-            self.write_test_output(&run_status.stderr, writer)?;
+            self.write_captured_output(run_status, OutputStream::Stderr, writer)?;
         }
 
         writeln!(writer)
     }
 
+    /// Writes out what a test wrote to `stream`, with timestamps if --output-timestamps is passed
+    /// in.
+    fn write_captured_output(
+        &self,
+        run_status: &ExecuteStatus,
+        stream: OutputStream,
+        writer: &mut impl Write,
+    ) -> io::Result<()> {
+        let output = match stream {
+            OutputStream::Stdout => &run_status.stdout,
+            OutputStream::Stderr => &run_status.stderr,
+        };
+        let chunks = run_status
+            .output_chunks
+            .iter()
+            .filter(|chunk| chunk.stream == stream);
+        // Output that isn't made up of chunks, such as the error for a test that couldn't be
+        // started, has no times to show.
+        if !self.output_timestamps
+            || chunks.clone().map(|chunk| chunk.range.len()).sum::<usize>() != output.len()
+        {
+            return self.write_test_output(output, writer);
+        }
+
+        // Each line is shown with the time its first byte was read at.
+        let mut at_line_start = true;
+        for chunk in chunks {
+            for line in output[chunk.range.clone()].split_inclusive(|&b| b == b'\n') {
+                if at_line_start {
+                    self.write_output_timestamp(chunk.elapsed, line, writer)?;
+                }
+                self.write_test_output(line, writer)?;
+                at_line_start = line.ends_with(b"\n");
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes out the time a line of output was written at, before the line.
+    fn write_output_timestamp(
+        &self,
+        elapsed: Duration,
+        line: &[u8],
+        writer: &mut impl Write,
+    ) -> io::Result<()> {
+        let timestamp = format!("[+{:>7.3}s]", elapsed.as_secs_f64());
+        write!(writer, "{}", timestamp.style(self.styles.count))?;
+        // Don't leave trailing whitespace after the timestamp on blank lines.
+        if line != b"\n" {
+            write!(writer, " ")?;
+        }
+        Ok(())
+    }
+
     fn write_test_output(&self, output: &[u8], writer: &mut impl Write) -> io::Result<()> {
         if self.styles.is_colorized {
             const RESET_COLOR: &[u8] = b"\x1b[0m";
@@ -1152,7 +2235,7 @@ impl<'a> fmt::Debug for TestReporter<'a> {
     }
 }
 
-fn status_str(result: ExecutionResult) -> Cow<'static, str> {
+pub(crate) fn status_str(result: ExecutionResult) -> Cow<'static, str> {
     // Max 12 characters here.
     match result {
         #[cfg(unix)]
@@ -1180,10 +2263,15 @@ fn status_str(result: ExecutionResult) -> Cow<'static, str> {
             abort_status: None,
             leaked: false,
         } => "FAIL".into(),
-        ExecutionResult::ExecFail => "XFAIL".into(),
+        ExecutionResult::ExecFail => "EXEC-FAIL".into(),
         ExecutionResult::Pass => "PASS".into(),
         ExecutionResult::Leak => "LEAK".into(),
         ExecutionResult::Timeout => "TIMEOUT".into(),
+        ExecutionResult::Hang => "HANG".into(),
+        ExecutionResult::MemoryLimitExceeded => "MEMLIMIT".into(),
+        ExecutionResult::LeakFail => "LEAK-FAIL".into(),
+        ExecutionResult::ExpectedFailure => "XFAIL".into(),
+        ExecutionResult::UnexpectedPass | ExecutionResult::UnexpectedPassFail => "XPASS".into(),
     }
 }
 
@@ -1211,10 +2299,15 @@ fn short_status_str(result: ExecutionResult) -> Cow<'static, str> {
             abort_status: None,
             leaked: _,
         } => "FAIL".into(),
-        ExecutionResult::ExecFail => "XFAIL".into(),
+        ExecutionResult::ExecFail => "EXEC".into(),
         ExecutionResult::Pass => "PASS".into(),
         ExecutionResult::Leak => "LEAK".into(),
         ExecutionResult::Timeout => "TMT".into(),
+        ExecutionResult::Hang => "HANG".into(),
+        ExecutionResult::MemoryLimitExceeded => "MEM".into(),
+        ExecutionResult::LeakFail => "LKFAIL".into(),
+        ExecutionResult::ExpectedFailure => "XFAIL".into(),
+        ExecutionResult::UnexpectedPass | ExecutionResult::UnexpectedPassFail => "XPASS".into(),
     }
 }
 
@@ -1227,11 +2320,43 @@ pub enum TestEvent<'a> {
     RunStarted {
         /// The list of tests that will be run.
         ///
-        /// The methods on the test list indicate the number of tests that will be run.
+        /// The methods on the test list indicate the number of tests that will be run. If
+        /// `listing` is set, more tests are added to these as the run progresses.
         test_list: &'a TestList<'a>,
 
         /// The UUID for this run.
         run_id: Uuid,
+
+        /// The number of times each test is run, set with `--iterations`.
+        ///
+        /// This is `None` if tests are run until one fails, with no limit on the number of
+        /// iterations.
+        iterations: Option<usize>,
+
+        /// Whether tests are run until one fails, with `--until-failure`.
+        until_failure: bool,
+
+        /// The seed tests were shuffled with, if they're started in a random order.
+        shuffle_seed: Option<u64>,
+
+        /// The seed that the `NEXTEST_TEST_SEED` value given to each test was derived from.
+        seed: u64,
+
+        /// If this run resumes an interrupted one with `--resume`, the number of tests that
+        /// finished before it was interrupted.
+        previously_finished: Option<usize>,
+
+        /// Whether more tests are listed while the run is in progress, with each list of them
+        /// reported as a [`TestsAdded`](Self::TestsAdded) event.
+        listing: bool,
+    },
+
+    /// Tests were listed while the run was in progress, and are about to be run.
+    ///
+    /// Only produced by [`TestRunner::try_execute_while_listing`](crate::runner::TestRunner::try_execute_while_listing).
+    TestsAdded {
+        /// The list of tests that were added.
+        test_list: &'a TestList<'a>,
     },
 
     // TODO: add events for BinaryStarted and BinaryFinished? May want a slightly different way to
@@ -1261,6 +2386,24 @@ pub enum TestEvent<'a> {
         elapsed: Duration,
     },
 
+    /// A test wrote output, in live-output mode.
+    ///
+    /// The output is made up of complete lines, except for output at the end of a stream that
+    /// isn't followed by a newline.
+    TestOutput {
+        /// The test instance that wrote the output.
+        test_instance: TestInstance<'a>,
+
+        /// Whether the output was written to standard output or standard error.
+        stream: OutputStream,
+
+        /// The output.
+        output: Bytes,
+
+        /// The time since the test started at which the output was read.
+        elapsed: Duration,
+    },
+
     /// A test failed and is being retried.
     ///
     /// This event does not occur on the final run of a failing test.
@@ -1270,6 +2413,40 @@ pub enum TestEvent<'a> {
 
         /// The status of this attempt to run the test. Will never be success.
         run_status: ExecuteStatus,
+
+        /// How long nextest waits before the next attempt, as set by the retry policy.
+        delay: Duration,
+
+        /// Whether the next attempt is deferred until every test has been run once, as set by
+        /// the `retry-schedule` configuration.
+        deferred: bool,
+    },
+
+    /// A test that failed was run again on its own, once every other test had finished.
+    ///
+    /// This event only occurs if `check-failures-in-isolation` is enabled.
+    TestIsolationChecked {
+        /// The test instance that was run again.
+        test_instance: TestInstance<'a>,
+
+        /// The status of the run on its own. If this is a success, the test only fails when it's
+        /// run alongside other tests.
+        run_status: ExecuteStatus,
+    },
+
+    /// A test that passed was run again to check that its output is the same.
+    ///
+    /// This event only occurs with `--check-determinism`, before the test's
+    /// [`TestFinished`](Self::TestFinished) event.
+    TestDeterminismChecked {
+        /// The test instance that was run again.
+        test_instance: TestInstance<'a>,
+
+        /// The status of the second run.
+        run_status: ExecuteStatus,
+
+        /// How the second run differed from the first, or None if its output was the same.
+        difference: Option<OutputDifference>,
     },
 
     /// A test finished running.
@@ -1280,6 +2457,9 @@ pub enum TestEvent<'a> {
         /// Information about all the runs for this test.
         run_statuses: ExecutionStatuses,
 
+        /// Whether this test is quarantined, in which case its failures don't fail the run.
+        quarantined: bool,
+
         /// Current statistics for number of tests so far.
         current_stats: RunStats,
 
@@ -1290,6 +2470,23 @@ pub enum TestEvent<'a> {
         cancel_state: Option<CancelReason>,
     },
 
+    /// A test finished before the run was interrupted, and isn't run again now that it's resumed.
+    ///
+    /// This event only occurs with `--resume`, right after [`RunStarted`](Self::RunStarted).
+    TestPreviouslyFinished {
+        /// The test instance that finished.
+        test_instance: TestInstance<'a>,
+
+        /// Whether the test passed, failed or was flaky.
+        status: RunStatus,
+
+        /// How long the last attempt of the test took.
+        time_taken: Duration,
+
+        /// Current statistics for number of tests so far.
+        current_stats: RunStats,
+    },
+
     /// A test was skipped.
     TestSkipped {
         /// The test instance that was skipped.
@@ -1299,6 +2496,15 @@ pub enum TestEvent<'a> {
         reason: MismatchReason,
     },
 
+    /// A test wasn't started because the run time budget was used up.
+    ///
+    /// This event is sent for every test that wasn't started once the run is canceled for this
+    /// reason, including ones that wouldn't have finished within the budget.
+    TestNotRun {
+        /// The test instance that wasn't started.
+        test_instance: TestInstance<'a>,
+    },
+
     /// A cancellation notice was received.
     RunBeginCancel {
         /// The number of tests still running.
@@ -1321,6 +2527,10 @@ pub enum TestEvent<'a> {
 
         /// Statistics for the run.
         run_stats: RunStats,
+
+        /// How many tests passed towards each pass rate required with `required-pass-rate`, or
+        /// empty if none are.
+        pass_rates: Vec<PassRateCheck>,
     },
 }
 
@@ -1328,9 +2538,16 @@ pub enum TestEvent<'a> {
 /// The reason why a test run is being cancelled.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
 pub enum CancelReason {
-    /// A test failed and --no-fail-fast wasn't specified.
+    /// A test failed and --no-fail-fast wasn't specified, or as many tests as set with --max-fail
+    /// failed.
     TestFailure,
 
+    /// Building or listing tests failed while tests were being run.
+    BuildFailure,
+
+    /// The run time budget set with --max-run-time was used up.
+    RunTimeBudget,
+
     /// An error occurred while reporting results.
     ReportError,
 
@@ -1352,6 +2569,8 @@ struct Styles {
     retry_output: Style,
     fail_output: Style,
     skip: Style,
+    // Tags for the output of tests in live-output mode, which are given different colors.
+    live_output: [Style; 6],
     list_styles: crate::list::Styles,
 }
 
@@ -1366,6 +2585,14 @@ impl Styles {
         self.retry_output = Style::new().magenta();
         self.fail_output = Style::new().magenta();
         self.skip = Style::new().yellow().bold();
+        self.live_output = [
+            Style::new().cyan(),
+            Style::new().magenta(),
+            Style::new().yellow(),
+            Style::new().blue(),
+            Style::new().green(),
+            Style::new().bright_red(),
+        ];
         self.list_styles.colorize();
     }
 }
@@ -1373,7 +2600,13 @@ impl Styles {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::NextestConfig;
+    use crate::{
+        config::NextestConfig,
+        list::RustTestSuiteStatus,
+        runner::OutputChunk,
+        test_helpers::{fake_suite, fake_test_case},
+    };
+    use nextest_metadata::{BuildPlatform, FilterMatch, MismatchReason};
 
     #[test]
     fn no_capture_settings() {
@@ -1408,4 +2641,122 @@ mod tests {
             "status level is pass, overriding other settings"
         );
     }
+
+    #[test]
+    fn tests_added() {
+        let suite = |binary_id: &str, test_names: &[&str]| {
+            let mut suite = fake_suite(binary_id, BuildPlatform::Target);
+            let mut skipped = fake_test_case();
+            skipped.filter_match = FilterMatch::Mismatch {
+                reason: MismatchReason::String,
+            };
+            let test_cases = test_names
+                .iter()
+                .map(|name| {
+                    let test_case = match *name {
+                        "skipped" => skipped.clone(),
+                        _ => fake_test_case(),
+                    };
+                    ((*name).to_owned(), test_case)
+                })
+                .collect();
+            suite.status = RustTestSuiteStatus::Listed { test_cases };
+            (format!("/fake/{binary_id}").into(), suite)
+        };
+        let first = TestList::with_suites([suite("first", &["a", "b", "skipped"])]);
+        let second =
+            TestList::with_suites([suite("second", &["a"]), suite("third", &["a", "b", "c"])]);
+        let only_skipped = TestList::with_suites([suite("fourth", &["skipped"])]);
+
+        let config = NextestConfig::default_config("/fake/dir");
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+        let initial = TestList::empty();
+        let mut buf: Vec<u8> = Vec::new();
+        let mut reporter = TestReporterBuilder::default().build(
+            &initial,
+            &profile,
+            ReporterStderr::Buffer(&mut buf),
+        );
+        let events = [
+            TestEvent::RunStarted {
+                test_list: &initial,
+                run_id: Uuid::new_v4(),
+                iterations: Some(1),
+                until_failure: false,
+                shuffle_seed: None,
+                seed: 0,
+                previously_finished: None,
+                listing: true,
+            },
+            TestEvent::TestsAdded { test_list: &first },
+            TestEvent::TestsAdded {
+                test_list: &only_skipped,
+            },
+            TestEvent::TestsAdded { test_list: &second },
+        ];
+        for event in events {
+            reporter
+                .report_event(event)
+                .expect("writing to a buffer succeeds");
+        }
+        drop(reporter);
+
+        let output = String::from_utf8(buf).expect("output is UTF-8");
+        let lines: Vec<_> = output.lines().map(str::trim_start).collect();
+        assert!(
+            lines[0].starts_with("Starting tests as binaries are listed"),
+            "test counts aren't in the header: {output}"
+        );
+        assert_eq!(
+            &lines[1..],
+            [
+                "Adding 2 tests from first (2 tests across 1 binaries so far, 1 skipped)",
+                // Lists without any tests to run aren't reported, but their counts are.
+                "Adding 4 tests from 2 binaries (6 tests across 4 binaries so far, 2 skipped)",
+            ],
+        );
+    }
+
+    #[test]
+    fn output_timestamps() {
+        let mut builder = TestReporterBuilder::default();
+        builder.set_output_timestamps(true);
+        let test_list = TestList::empty();
+        let config = NextestConfig::default_config("/fake/dir");
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+        let mut buf: Vec<u8> = Vec::new();
+        let reporter = builder.build(&test_list, &profile, ReporterStderr::Buffer(&mut buf));
+
+        let chunk = |range, millis| OutputChunk {
+            stream: OutputStream::Stdout,
+            range,
+            elapsed: Duration::from_millis(millis),
+        };
+        let status = ExecuteStatus {
+            attempt: 1,
+            total_attempts: 1,
+            iteration: 1,
+            total_iterations: Some(1),
+            stdout: Bytes::from_static(b"starting\n\nconnect"),
+            stderr: Bytes::new(),
+            output_chunks: vec![chunk(0..10, 5), chunk(10..17, 1250)],
+            result: ExecutionResult::Pass,
+            exit_code: Some(0),
+            leaked_processes: vec![],
+            kept_tmpdir: None,
+            test_seed: 0,
+            start_time: std::time::SystemTime::UNIX_EPOCH,
+            time_taken: Duration::from_secs(2),
+            is_slow: false,
+        };
+        let mut out = Vec::new();
+        reporter
+            .inner
+            .write_captured_output(&status, OutputStream::Stdout, &mut out)
+            .expect("writing to a Vec succeeds");
+        assert_eq!(
+            String::from_utf8(out).expect("output is UTF-8"),
+            "[+  0.005s] starting\n[+  0.005s]\n[+  1.250s] connect"
+        );
+    }
 }
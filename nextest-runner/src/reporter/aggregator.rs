@@ -6,17 +6,24 @@
 #[cfg(any(unix, windows))]
 use crate::runner::AbortStatus;
 use crate::{
+    cargo_config::TargetTriple,
     config::{NextestJunitConfig, NextestProfile},
     errors::WriteEventError,
-    list::TestInstance,
-    reporter::TestEvent,
+    git::head_commit,
+    list::{RustTestSuite, TestInstance},
+    reporter::{html::HtmlReport, timeline::Timeline, TestEvent},
+    run_store::RunRecorder,
     runner::{ExecuteStatus, ExecutionDescription, ExecutionResult},
 };
 use camino::Utf8PathBuf;
 use chrono::{DateTime, FixedOffset, Utc};
 use debug_ignore::DebugIgnore;
+use nextest_filtering::RunStatus;
+use nextest_metadata::{BuildPlatform, MismatchReason, RustTestCaseSummary};
 use once_cell::sync::Lazy;
-use quick_junit::{NonSuccessKind, Output, Report, TestCase, TestCaseStatus, TestRerun, TestSuite};
+use quick_junit::{
+    NonSuccessKind, Output, Property, Report, TestCase, TestCaseStatus, TestRerun, TestSuite,
+};
 use regex::{Regex, RegexBuilder};
 use std::{borrow::Cow, collections::HashMap, fs::File, time::SystemTime};
 
@@ -27,17 +34,34 @@ pub(crate) struct EventAggregator<'cfg> {
     // TODO: log information in a JSONable report (converting that to XML later) instead of directly
     // writing it to XML
     junit: Option<MetadataJunit<'cfg>>,
+    html_report: Option<HtmlReport>,
+    timeline: Option<Timeline<'cfg>>,
+    run_recorder: RunRecorder<'cfg>,
 }
 
 impl<'cfg> EventAggregator<'cfg> {
     pub(crate) fn new(profile: &NextestProfile<'cfg>) -> Self {
         Self {
             store_dir: profile.store_dir().to_owned(),
-            junit: profile.junit().map(MetadataJunit::new),
+            junit: profile
+                .junit()
+                .map(|config| MetadataJunit::new(config, profile)),
+            html_report: profile.html_report_path().map(HtmlReport::new),
+            timeline: profile
+                .timeline_path()
+                .map(|path| Timeline::new(path, profile)),
+            run_recorder: RunRecorder::new(profile),
         }
     }
 
     pub(crate) fn write_event(&mut self, event: TestEvent<'cfg>) -> Result<(), WriteEventError> {
+        self.run_recorder.write_event(&event)?;
+        if let Some(html_report) = &mut self.html_report {
+            html_report.write_event(&event)?;
+        }
+        if let Some(timeline) = &mut self.timeline {
+            timeline.write_event(&event)?;
+        }
         if let Some(junit) = &mut self.junit {
             junit.write_event(event)?;
         }
@@ -48,28 +72,52 @@ impl<'cfg> EventAggregator<'cfg> {
 #[derive(Clone, Debug)]
 struct MetadataJunit<'cfg> {
     config: NextestJunitConfig<'cfg>,
+    // Properties of the run as a whole, recorded in each test suite.
+    run_properties: Vec<Property>,
+    // The target triple tests are built for, unless a suite records its own.
+    target_triple: String,
     test_suites: DebugIgnore<HashMap<&'cfg str, TestSuite>>,
 }
 
 impl<'cfg> MetadataJunit<'cfg> {
-    fn new(config: NextestJunitConfig<'cfg>) -> Self {
+    fn new(config: NextestJunitConfig<'cfg>, profile: &NextestProfile<'cfg>) -> Self {
+        let mut run_properties = vec![Property::new("profile", profile.name())];
+        if let Some(commit) = head_commit(profile.workspace_root()) {
+            run_properties.push(Property::new("git-sha", commit));
+        }
         Self {
             config,
+            run_properties,
+            target_triple: String::new(),
             test_suites: DebugIgnore(HashMap::new()),
         }
     }
 
     pub(crate) fn write_event(&mut self, event: TestEvent<'cfg>) -> Result<(), WriteEventError> {
         match event {
-            TestEvent::RunStarted { .. } => {}
+            TestEvent::RunStarted {
+                test_list, run_id, ..
+            } => {
+                // Downstream systems can correlate reports with the run and the commit tested
+                // through these.
+                self.run_properties
+                    .insert(0, Property::new("run-id", run_id.to_string()));
+                self.target_triple =
+                    TargetTriple::serialize(test_list.rust_build_meta().target_triple.as_ref());
+            }
+            TestEvent::TestsAdded { .. } => {}
             TestEvent::TestStarted { .. } => {}
             TestEvent::TestSlow { .. } => {}
+            TestEvent::TestOutput { .. } => {}
             TestEvent::TestRetry { .. } => {
                 // Retries are recorded in TestFinished.
             }
+            TestEvent::TestIsolationChecked { .. } => {}
+            TestEvent::TestDeterminismChecked { .. } => {}
             TestEvent::TestFinished {
                 test_instance,
                 run_statuses,
+                quarantined,
                 ..
             } => {
                 fn kind_ty(run_status: &ExecuteStatus) -> (NonSuccessKind, Cow<'static, str>) {
@@ -99,6 +147,21 @@ impl<'cfg> MetadataJunit<'cfg> {
                         ExecutionResult::Timeout => {
                             (NonSuccessKind::Failure, "test timeout".into())
                         }
+                        ExecutionResult::Hang => (
+                            NonSuccessKind::Failure,
+                            "test produced no output for too long".into(),
+                        ),
+                        ExecutionResult::MemoryLimitExceeded => {
+                            (NonSuccessKind::Failure, "test exceeded memory limit".into())
+                        }
+                        ExecutionResult::LeakFail => (
+                            NonSuccessKind::Failure,
+                            "test left processes running".into(),
+                        ),
+                        ExecutionResult::UnexpectedPassFail => (
+                            NonSuccessKind::Failure,
+                            "test passed but was expected to fail".into(),
+                        ),
                         ExecutionResult::ExecFail => {
                             (NonSuccessKind::Error, "execution failure".into())
                         }
@@ -106,15 +169,24 @@ impl<'cfg> MetadataJunit<'cfg> {
                             NonSuccessKind::Error,
                             "test passed but leaked handles".into(),
                         ),
-                        ExecutionResult::Pass => {
+                        ExecutionResult::Pass
+                        | ExecutionResult::ExpectedFailure
+                        | ExecutionResult::UnexpectedPass => {
                             unreachable!("this is a failure status")
                         }
                     }
                 }
 
-                let testsuite = self.testsuite_for(test_instance);
-
                 let (mut testcase_status, main_status, reruns) = match run_statuses.describe() {
+                    // Expected failures are reported as skipped, which is how JUnit reports from
+                    // other tools with expected failures represent them.
+                    ExecutionDescription::Success { single_status }
+                        if single_status.result == ExecutionResult::ExpectedFailure =>
+                    {
+                        let mut testcase_status = TestCaseStatus::skipped();
+                        testcase_status.set_message("expected failure");
+                        (testcase_status, single_status, &[][..])
+                    }
                     ExecutionDescription::Success { single_status } => {
                         (TestCaseStatus::success(), single_status, &[][..])
                     }
@@ -142,14 +214,23 @@ impl<'cfg> MetadataJunit<'cfg> {
 
                     let mut test_rerun = TestRerun::new(kind);
                     if let Some(description) = stack_trace {
-                        test_rerun.set_description(description);
+                        // Surefire reports the first line of a failure as its message, and the
+                        // details within a stackTrace element.
+                        if let Some(message) = description.lines().next() {
+                            test_rerun.set_message(message);
+                        }
+                        test_rerun.set_stack_trace(description);
                     }
                     test_rerun
                         .set_timestamp(to_datetime(rerun.start_time))
                         .set_time(rerun.time_taken)
-                        .set_type(ty)
-                        .set_system_out(stdout)
-                        .set_system_err(stderr);
+                        .set_type(ty);
+                    if self.config.store_failure_output() {
+                        let max_size = self.config.max_output_size();
+                        test_rerun
+                            .set_system_out(truncate_output(&rerun.stdout, max_size))
+                            .set_system_err(truncate_output(&rerun.stderr, max_size));
+                    }
                     // TODO: also publish time? it won't be standard JUnit (but maybe that's ok?)
                     testcase_status.add_rerun(test_rerun);
                 }
@@ -158,9 +239,34 @@ impl<'cfg> MetadataJunit<'cfg> {
                 testcase
                     .set_classname(&test_instance.bin_info.binary_id)
                     .set_timestamp(to_datetime(main_status.start_time))
-                    .set_time(main_status.time_taken);
+                    .set_time(main_status.time_taken)
+                    .add_properties(tag_properties(test_instance.test_info));
+                match main_status.result {
+                    ExecutionResult::ExpectedFailure => {
+                        testcase.add_property(Property::new("outcome", "xfail"));
+                    }
+                    ExecutionResult::UnexpectedPass | ExecutionResult::UnexpectedPassFail => {
+                        testcase.add_property(Property::new("outcome", "xpass"));
+                    }
+                    _ => {}
+                }
+                if quarantined {
+                    // Failures of quarantined tests are reported as usual, so that tools can tell
+                    // when they pass again.
+                    testcase.add_property(Property::new("quarantined", "true"));
+                }
+                if main_status.total_iterations != Some(1) {
+                    // Each iteration of a test is reported as a separate test case.
+                    testcase.add_property(Property::new(
+                        "iteration",
+                        main_status.iteration.to_string(),
+                    ));
+                }
+                testcase.add_property(Property::new(
+                    "test-seed",
+                    main_status.test_seed.to_string(),
+                ));
 
-                // TODO: also provide stdout and stderr for passing tests?
                 // TODO: allure seems to want the output to be in a format where text files are
                 // written out to disk:
                 // https://github.com/allure-framework/allure2/blob/master/plugins/junit-xml-plugin/src/main/java/io/qameta/allure/junitxml/JunitXmlPlugin.java#L192-L196
@@ -173,17 +279,82 @@ impl<'cfg> MetadataJunit<'cfg> {
                     if let Some(description) = description {
                         testcase.status.set_description(description);
                     }
-
+                }
+                let store_output = if main_status.result.is_success() {
+                    self.config.store_success_output()
+                } else {
+                    self.config.store_failure_output()
+                };
+                if store_output {
+                    let max_size = self.config.max_output_size();
                     testcase
-                        .set_system_out_lossy(&main_status.stdout)
-                        .set_system_err_lossy(&main_status.stderr);
+                        .set_system_out(truncate_output(&main_status.stdout, max_size))
+                        .set_system_err(truncate_output(&main_status.stderr, max_size));
+                }
+
+                self.testsuite_for(test_instance).add_test_case(testcase);
+            }
+            TestEvent::TestSkipped {
+                test_instance,
+                reason: MismatchReason::Ignored,
+            } => {
+                // Ignored tests are skipped in every run, so reporting them doesn't cause issues
+                // with aggregating runs.
+                let mut testcase_status = TestCaseStatus::skipped();
+                if let Some(reason) = &test_instance.test_info.ignore_reason {
+                    testcase_status.set_message(reason);
                 }
+                let mut testcase = TestCase::new(test_instance.name, testcase_status);
+                testcase
+                    .set_classname(&test_instance.bin_info.binary_id)
+                    .add_properties(tag_properties(test_instance.test_info));
 
-                testsuite.add_test_case(testcase);
+                self.testsuite_for(test_instance).add_test_case(testcase);
+            }
+            TestEvent::TestPreviouslyFinished {
+                test_instance,
+                status,
+                time_taken,
+                ..
+            } => {
+                // The output of tests that finished before the run was interrupted isn't kept, so
+                // only their outcome is reported.
+                let testcase_status = match status {
+                    RunStatus::Passed | RunStatus::Flaky => TestCaseStatus::success(),
+                    RunStatus::Failed => {
+                        let mut testcase_status =
+                            TestCaseStatus::non_success(NonSuccessKind::Failure);
+                        testcase_status
+                            .set_type("test failure")
+                            .set_message("failed before the run was resumed");
+                        testcase_status
+                    }
+                    RunStatus::Skipped => TestCaseStatus::skipped(),
+                };
+                let mut testcase = TestCase::new(test_instance.name, testcase_status);
+                testcase
+                    .set_classname(&test_instance.bin_info.binary_id)
+                    .set_time(time_taken)
+                    .add_properties(tag_properties(test_instance.test_info))
+                    .add_property(Property::new("resumed", "true"));
+
+                self.testsuite_for(test_instance).add_test_case(testcase);
+            }
+            TestEvent::TestNotRun { test_instance } => {
+                // Tests that weren't run because the run time budget was used up are reported so
+                // that they aren't silently missing from the report.
+                let mut testcase_status = TestCaseStatus::skipped();
+                testcase_status.set_message("not run: the run time budget was used up");
+                let mut testcase = TestCase::new(test_instance.name, testcase_status);
+                testcase
+                    .set_classname(&test_instance.bin_info.binary_id)
+                    .add_properties(tag_properties(test_instance.test_info));
+
+                self.testsuite_for(test_instance).add_test_case(testcase);
             }
             TestEvent::TestSkipped { .. } => {
-                // TODO: report skipped tests? causes issues if we want to aggregate runs across
-                // skipped and non-skipped tests. Probably needs to be made configurable.
+                // TODO: report other skipped tests? causes issues if we want to aggregate runs
+                // across skipped and non-skipped tests. Probably needs to be made configurable.
 
                 // let testsuite = self.testsuite_for(test_instance);
                 //
@@ -232,13 +403,63 @@ impl<'cfg> MetadataJunit<'cfg> {
     }
 
     fn testsuite_for(&mut self, test_instance: TestInstance<'cfg>) -> &mut TestSuite {
+        let run_properties = &self.run_properties;
+        let target_triple = &self.target_triple;
         self.test_suites
             .entry(&test_instance.bin_info.binary_id)
-            .or_insert_with(|| TestSuite::new(&test_instance.bin_info.binary_id))
+            .or_insert_with(|| {
+                let mut testsuite = TestSuite::new(&test_instance.bin_info.binary_id);
+                testsuite.add_properties(run_properties.iter().cloned());
+                if let Some(target_triple) =
+                    suite_target_triple(test_instance.bin_info, target_triple)
+                {
+                    testsuite.add_property(Property::new("target-triple", target_triple));
+                }
+                testsuite
+            })
+    }
+}
+
+/// Returns the target triple a test suite is built for, or `None` if the suite is built for the
+/// host or the triple isn't known.
+fn suite_target_triple(suite: &RustTestSuite<'_>, target_triple: &str) -> Option<String> {
+    match (suite.build_platform, &suite.target_triple) {
+        // Proc-macro tests and the like are built for the host, not the target.
+        (BuildPlatform::Host, _) => None,
+        (BuildPlatform::Target, Some(triple)) => Some(triple.clone()),
+        (BuildPlatform::Target, None) => {
+            (!target_triple.is_empty()).then(|| target_triple.to_owned())
+        }
+    }
+}
+
+/// Each tag is reported as a separate `tag` property, so that tools can slice results by tag.
+fn tag_properties(test_info: &RustTestCaseSummary) -> impl Iterator<Item = Property> + '_ {
+    test_info
+        .tags
+        .iter()
+        .map(|tag| Property::new("tag", tag.as_str()))
+}
+
+/// Converts output to a string, keeping only its last `max_size` bytes if it's longer than that.
+fn truncate_output(output: &[u8], max_size: Option<u64>) -> Cow<'_, str> {
+    let max_size = match max_size {
+        Some(max_size) if (output.len() as u64) > max_size => max_size as usize,
+        _ => return String::from_utf8_lossy(output),
+    };
+    let mut start = output.len() - max_size;
+    // Don't start in the middle of a UTF-8 sequence.
+    while start < output.len() && (output[start] & 0b1100_0000) == 0b1000_0000 {
+        start += 1;
     }
+    Cow::Owned(format!(
+        "[... {} bytes truncated ...]\n{}",
+        start,
+        String::from_utf8_lossy(&output[start..])
+    ))
 }
 
-fn to_datetime(system_time: SystemTime) -> DateTime<FixedOffset> {
+pub(super) fn to_datetime(system_time: SystemTime) -> DateTime<FixedOffset> {
     // Serialize using UTC.
     let datetime = DateTime::<Utc>::from(system_time);
     datetime.into()
@@ -337,6 +558,231 @@ fn heuristic_stack_trace(stderr: &str) -> Option<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        config::NextestConfig,
+        list::{RustTestSuiteStatus, TestList},
+        runner::{ExecutionStatuses, RunStats},
+    };
+    use bytes::Bytes;
+    use camino::Utf8Path;
+    use guppy::{graph::PackageGraph, CargoMetadata, PackageId};
+    use indoc::indoc;
+    use nextest_metadata::{FilterMatch, RustTestBinaryKind};
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        process::Command,
+        time::Duration,
+    };
+    use uuid::Uuid;
+
+    #[test]
+    fn test_junit_report() {
+        let temp_dir = tempfile::tempdir().expect("created temp dir");
+        let workspace_root: &Utf8Path =
+            temp_dir.path().try_into().expect("temp dir is valid UTF-8");
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .current_dir(workspace_root)
+                .status()
+                .expect("git ran");
+            assert!(status.success(), "git {args:?} succeeded");
+        };
+        std::fs::create_dir(workspace_root.join(".config")).expect("created config dir");
+        std::fs::write(
+            workspace_root.join(".config/nextest.toml"),
+            indoc! {r#"
+                [profile.default.junit]
+                path = "junit.xml"
+            "#},
+        )
+        .expect("wrote config");
+        git(&["init", "--quiet"]);
+        git(&["add", "."]);
+        git(&["commit", "--quiet", "-m", "initial"]);
+        let commit = head_commit(workspace_root).expect("workspace has a commit");
+
+        let graph = package_graph();
+        let config =
+            NextestConfig::from_sources(workspace_root, &graph, None, []).expect("config is valid");
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+        let mut junit = MetadataJunit::new(profile.junit().expect("junit is configured"), &profile);
+
+        let package = graph
+            .metadata(&PackageId::new(METADATA_BASE_ID))
+            .expect("package ID is valid");
+        let test_case = RustTestCaseSummary {
+            ignored: false,
+            ignore_reason: None,
+            location: None,
+            tags: BTreeSet::new(),
+            filter_match: FilterMatch::Matches,
+        };
+        let suite = |binary_id: &str, kind, build_platform| RustTestSuite {
+            binary_id: binary_id.to_owned(),
+            package,
+            binary_name: binary_id.to_owned(),
+            kind,
+            cwd: workspace_root.to_path_buf(),
+            build_platform,
+            non_test_binaries: BTreeSet::new(),
+            artifact_binaries: BTreeSet::new(),
+            cdylibs: BTreeSet::new(),
+            doctest: None,
+            features: vec![],
+            single_test: false,
+            env: BTreeMap::new(),
+            target_triple: None,
+            status: RustTestSuiteStatus::Skipped,
+        };
+        let target_suite = suite(
+            "metadata-base",
+            RustTestBinaryKind::LIB,
+            BuildPlatform::Target,
+        );
+        let host_suite = suite(
+            "metadata-base::proc-macro/macros",
+            RustTestBinaryKind::PROC_MACRO,
+            BuildPlatform::Host,
+        );
+
+        let failure_stderr =
+            "thread 'tests::flaky' panicked at 'assertion failed', src/lib.rs:3:5\n\
+             note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace\n";
+        let status = |attempt, result| ExecuteStatus {
+            attempt,
+            total_attempts: 2,
+            iteration: 1,
+            total_iterations: Some(1),
+            stdout: Bytes::new(),
+            stderr: match result {
+                ExecutionResult::Pass => Bytes::new(),
+                _ => Bytes::from_static(failure_stderr.as_bytes()),
+            },
+            output_chunks: vec![],
+            result,
+            exit_code: None,
+            leaked_processes: vec![],
+            kept_tmpdir: None,
+            test_seed: 0,
+            start_time: SystemTime::UNIX_EPOCH,
+            time_taken: Duration::from_millis(100),
+            is_slow: false,
+        };
+        let fail = ExecutionResult::Fail {
+            abort_status: None,
+            leaked: false,
+        };
+        let finished = |test_instance, statuses| TestEvent::TestFinished {
+            test_instance,
+            run_statuses: ExecutionStatuses::new(statuses),
+            quarantined: false,
+            current_stats: RunStats::default(),
+            running: 0,
+            cancel_state: None,
+        };
+
+        let test_list = TestList::empty();
+        let run_id = Uuid::new_v4();
+        let events = [
+            TestEvent::RunStarted {
+                test_list: &test_list,
+                run_id,
+                iterations: Some(1),
+                until_failure: false,
+                shuffle_seed: None,
+                seed: 0,
+                previously_finished: None,
+                listing: false,
+            },
+            finished(
+                TestInstance::new("tests::flaky", "/fake/binary", &target_suite, &test_case),
+                vec![status(1, fail), status(2, ExecutionResult::Pass)],
+            ),
+            finished(
+                TestInstance::new("tests::failing", "/fake/macros", &host_suite, &test_case),
+                vec![status(1, fail), status(2, fail)],
+            ),
+            TestEvent::RunFinished {
+                run_id,
+                start_time: SystemTime::UNIX_EPOCH,
+                elapsed: Duration::from_secs(1),
+                run_stats: RunStats::default(),
+                pass_rates: vec![],
+            },
+        ];
+        for event in events {
+            junit.write_event(event).expect("event written");
+        }
+
+        let report = std::fs::read_to_string(profile.store_dir().join("junit.xml"))
+            .expect("report was written");
+        let target_triple = TargetTriple::serialize(None);
+        for (binary_id, target_triple) in [
+            ("metadata-base", Some(&target_triple)),
+            ("metadata-base::proc-macro/macros", None),
+        ] {
+            let suite_start = report
+                .find(&format!("<testsuite name=\"{binary_id}\""))
+                .unwrap_or_else(|| panic!("suite {binary_id} is in the report"));
+            let suite_report = &report[suite_start..];
+            let properties = &suite_report[..suite_report
+                .find("</properties>")
+                .expect("suite has properties")];
+            assert!(properties.contains(&format!(r#"<property name="run-id" value="{run_id}"/>"#)));
+            assert!(properties.contains(r#"<property name="profile" value="default"/>"#));
+            assert!(properties.contains(&format!(r#"<property name="git-sha" value="{commit}"/>"#)));
+            match target_triple {
+                Some(target_triple) => assert!(
+                    properties.contains(&format!(
+                        r#"<property name="target-triple" value="{target_triple}"/>"#
+                    )),
+                    "suite {binary_id} has a target triple"
+                ),
+                None => assert!(
+                    !properties.contains("target-triple"),
+                    "host suite {binary_id} has no target triple"
+                ),
+            }
+        }
+
+        // Reruns carry the first line of the failure as their message, and all of it as their
+        // stack trace.
+        let message = "thread &apos;tests::flaky&apos; panicked at &apos;assertion failed&apos;, src/lib.rs:3:5";
+        for element in ["flakyFailure", "rerunFailure"] {
+            let start = report
+                .find(&format!("<{element} "))
+                .unwrap_or_else(|| panic!("{element} is in the report"));
+            let end = report[start..]
+                .find(&format!("</{element}>"))
+                .unwrap_or_else(|| panic!("{element} is closed"));
+            let rerun = &report[start..start + end];
+            assert!(
+                rerun.contains(&format!(r#"message="{message}""#)),
+                "{element} has the first line as its message: {rerun}"
+            );
+            assert!(
+                rerun.contains(&format!(
+                    "<stackTrace>{message}\nnote: run with `RUST_BACKTRACE=1` environment variable to display a backtrace</stackTrace>"
+                )),
+                "{element} has the full description as its stack trace: {rerun}"
+            );
+        }
+    }
+
+    static METADATA_BASE_ID: &str =
+        "metadata-base 0.1.0 (path+file:///Users/fakeuser/local/testcrates/metadata/metadata-base)";
+
+    fn package_graph() -> PackageGraph {
+        static FIXTURE_JSON: &str = include_str!("../../../fixtures/cargo-metadata.json");
+        let metadata = CargoMetadata::parse_json(FIXTURE_JSON).expect("fixture is valid JSON");
+        metadata
+            .build_graph()
+            .expect("fixture is valid PackageGraph")
+    }
 
     #[test]
     fn test_heuristic_extract_description() {
@@ -394,4 +840,23 @@ note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace"#,
             assert_eq!(heuristic_stack_trace(*input).as_deref(), Some(*output));
         }
     }
+
+    #[test]
+    fn test_truncate_output() {
+        assert_eq!(truncate_output(b"short", Some(5)), "short");
+        assert_eq!(truncate_output(b"no limit", None), "no limit");
+        assert_eq!(
+            truncate_output(b"first line\nlast line", Some(9)),
+            "[... 11 bytes truncated ...]\nlast line"
+        );
+        // "\u{e9}" is 2 bytes long, so starting 2 bytes from the end would split it.
+        assert_eq!(
+            truncate_output("caf\u{e9}!".as_bytes(), Some(2)),
+            "[... 5 bytes truncated ...]\n!"
+        );
+        assert_eq!(
+            truncate_output("caf\u{e9}!".as_bytes(), Some(3)),
+            "[... 3 bytes truncated ...]\n\u{e9}!"
+        );
+    }
 }
@@ -0,0 +1,209 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Breaks the results of a run down by package and test binary, for `--breakdown`.
+//!
+//! In a large workspace, the table written out after the summary shows which packages and test
+//! binaries had failures at a glance.
+
+use crate::{
+    list::TestInstance,
+    reporter::{Styles, TestEvent},
+};
+use nextest_filtering::RunStatus;
+use owo_colors::OwoColorize;
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    time::Duration,
+};
+
+const NAME_HEADING: &str = "package / binary";
+
+/// Counts of how tests did, by package and then by test binary.
+#[derive(Debug, Default)]
+pub(super) struct Breakdown<'a> {
+    packages: BTreeMap<&'a str, BTreeMap<&'a str, BreakdownCounts>>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct BreakdownCounts {
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+    // The time taken by every attempt of every test.
+    time_taken: Duration,
+}
+
+impl BreakdownCounts {
+    fn add(&mut self, other: &Self) {
+        self.passed += other.passed;
+        self.failed += other.failed;
+        self.skipped += other.skipped;
+        self.time_taken += other.time_taken;
+    }
+}
+
+impl<'a> Breakdown<'a> {
+    pub(super) fn write_event(&mut self, event: &TestEvent<'a>) {
+        match event {
+            TestEvent::TestFinished {
+                test_instance,
+                run_statuses,
+                ..
+            } => {
+                let counts = self.counts(test_instance);
+                match run_statuses.last_status().result.is_success() {
+                    true => counts.passed += 1,
+                    false => counts.failed += 1,
+                }
+                counts.time_taken += run_statuses.iter().map(|status| status.time_taken).sum();
+            }
+            TestEvent::TestPreviouslyFinished {
+                test_instance,
+                status,
+                time_taken,
+                ..
+            } => {
+                let counts = self.counts(test_instance);
+                match status {
+                    RunStatus::Passed | RunStatus::Flaky => counts.passed += 1,
+                    RunStatus::Failed => counts.failed += 1,
+                    RunStatus::Skipped => counts.skipped += 1,
+                }
+                counts.time_taken += *time_taken;
+            }
+            TestEvent::TestSkipped { test_instance, .. } => {
+                self.counts(test_instance).skipped += 1;
+            }
+            _ => {}
+        }
+    }
+
+    fn counts(&mut self, test_instance: &TestInstance<'a>) -> &mut BreakdownCounts {
+        self.packages
+            .entry(test_instance.bin_info.package.name())
+            .or_default()
+            .entry(&test_instance.bin_info.binary_id)
+            .or_default()
+    }
+
+    /// Writes out a row for each package, followed by a row for each of its test binaries.
+    pub(super) fn write(&self, styles: &Styles, writer: &mut impl Write) -> io::Result<()> {
+        if self.packages.is_empty() {
+            return Ok(());
+        }
+
+        let name_width = self
+            .packages
+            .iter()
+            .flat_map(|(package, binaries)| {
+                std::iter::once(package.len()).chain(binaries.keys().map(|id| id.len() + 2))
+            })
+            .chain(std::iter::once(NAME_HEADING.len()))
+            .max()
+            .unwrap_or_default();
+
+        writeln!(
+            writer,
+            "{:>12} {} packages, {} test binaries",
+            "BREAKDOWN".style(styles.skip),
+            self.packages.len().style(styles.count),
+            self.packages
+                .values()
+                .map(BTreeMap::len)
+                .sum::<usize>()
+                .style(styles.count),
+        )?;
+        writeln!(
+            writer,
+            "{:>12} {:<name_width$}  {:>7}  {:>7}  {:>7}  {:>10}",
+            "", NAME_HEADING, "passed", "failed", "skipped", "time",
+        )?;
+        for (package, binaries) in &self.packages {
+            let mut total = BreakdownCounts::default();
+            for counts in binaries.values() {
+                total.add(counts);
+            }
+            let style = match total.failed {
+                0 => styles.pass,
+                _ => styles.fail,
+            };
+            write!(writer, "{:>12} {:<name_width$}", "", package.style(style))?;
+            write_counts(&total, styles, writer)?;
+
+            for (binary_id, counts) in binaries {
+                write!(
+                    writer,
+                    "{:>12}   {:<width$}",
+                    "",
+                    binary_id.style(styles.list_styles.binary_id),
+                    width = name_width - 2,
+                )?;
+                write_counts(counts, styles, writer)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_counts(
+    counts: &BreakdownCounts,
+    styles: &Styles,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    let failed_style = match counts.failed {
+        0 => styles.count,
+        _ => styles.fail,
+    };
+    writeln!(
+        writer,
+        "  {:>7}  {:>7}  {:>7}  {:>9.3}s",
+        counts.passed.style(styles.count),
+        counts.failed.style(failed_style),
+        counts.skipped.style(styles.count),
+        counts.time_taken.as_secs_f64(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write() {
+        let mut breakdown = Breakdown::default();
+        let binaries = breakdown.packages.entry("my-crate").or_default();
+        binaries.insert(
+            "my-crate",
+            BreakdownCounts {
+                passed: 8,
+                failed: 1,
+                skipped: 0,
+                time_taken: Duration::from_millis(2000),
+            },
+        );
+        binaries.insert(
+            "my-crate::integration",
+            BreakdownCounts {
+                passed: 4,
+                failed: 0,
+                skipped: 2,
+                time_taken: Duration::from_millis(1021),
+            },
+        );
+
+        let mut out = Vec::new();
+        breakdown
+            .write(&Styles::default(), &mut out)
+            .expect("writing to a Vec succeeds");
+        assert_eq!(
+            String::from_utf8(out).expect("output is UTF-8"),
+            "   BREAKDOWN 1 packages, 2 test binaries\n\
+            \x20            package / binary          passed   failed  skipped        time\n\
+            \x20            my-crate                      12        1        2      3.021s\n\
+            \x20              my-crate                     8        1        0      2.000s\n\
+            \x20              my-crate::integration        4        0        2      1.021s\n"
+        );
+    }
+}
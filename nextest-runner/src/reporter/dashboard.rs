@@ -0,0 +1,899 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A full-screen dashboard shown while tests run, for `--dashboard`.
+//!
+//! The dashboard takes over the terminal's alternate screen and shows the tests that are running,
+//! how many tests passed and failed so far, and the output of each test that failed. The regular
+//! output is held back while the dashboard is shown, and written out once the run finishes so that
+//! it's still there to scroll back through.
+
+use crate::{
+    reporter::{progress_bar_msg, status_str, RunningState, Styles, TestEvent},
+    runner::{ExecuteStatus, ExecutionDescription, RunStats},
+};
+use owo_colors::{OwoColorize, Style};
+use std::{
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// How often the dashboard is redrawn, so that elapsed times stay current.
+const TICK: Duration = Duration::from_millis(100);
+
+/// Shows the state of a run on the terminal's alternate screen.
+pub(crate) struct Dashboard {
+    state: Arc<Mutex<DashboardState>>,
+    stop: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+    held_back: Vec<u8>,
+    active: bool,
+}
+
+impl Dashboard {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(DashboardState::new())),
+            stop: Arc::new(AtomicBool::new(false)),
+            threads: Vec::new(),
+            held_back: Vec::new(),
+            active: false,
+        }
+    }
+
+    /// The regular output, written out once the dashboard is closed.
+    pub(crate) fn held_back(&mut self) -> &mut Vec<u8> {
+        &mut self.held_back
+    }
+
+    /// Returns true if failed tests were asked to be run again once the run finishes.
+    pub(crate) fn retry_failures_requested(&self) -> bool {
+        self.state
+            .lock()
+            .expect("lock isn't poisoned")
+            .retry_failures
+    }
+
+    pub(crate) fn colorize(&mut self) {
+        self.state
+            .lock()
+            .expect("lock isn't poisoned")
+            .styles
+            .colorize();
+    }
+
+    pub(crate) fn write_event(&mut self, event: &TestEvent<'_>, styles: &Styles) {
+        if let TestEvent::RunStarted { .. } = event {
+            self.start();
+        }
+        self.state
+            .lock()
+            .expect("lock isn't poisoned")
+            .update(event, styles);
+        if let TestEvent::RunFinished { .. } = event {
+            self.finish();
+        }
+    }
+
+    fn start(&mut self) {
+        if self.active {
+            return;
+        }
+        self.active = true;
+        // Switch to the alternate screen and hide the cursor.
+        let _ = write_stderr(b"\x1b[?1049h\x1b[?25l");
+
+        let state = self.state.clone();
+        let stop = self.stop.clone();
+        self.threads.push(std::thread::spawn(move || {
+            while !stop.load(Ordering::Acquire) {
+                let screen = {
+                    let mut state = state.lock().expect("lock isn't poisoned");
+                    let (height, width) = console::Term::stderr().size();
+                    state.render(Instant::now(), width as usize, height as usize)
+                };
+                let _ = write_stderr(screen.as_bytes());
+                std::thread::sleep(TICK);
+            }
+        }));
+
+        #[cfg(unix)]
+        {
+            let state = self.state.clone();
+            let stop = self.stop.clone();
+            // If the terminal can't be read from, the dashboard is still shown, and Ctrl-C still
+            // cancels the run.
+            if let Ok(mut terminal) = input::RawTerminal::new() {
+                self.threads.push(std::thread::spawn(move || {
+                    while !stop.load(Ordering::Acquire) {
+                        let keys = match terminal.read_keys(TICK) {
+                            Ok(keys) => keys,
+                            Err(_) => break,
+                        };
+                        let mut state = state.lock().expect("lock isn't poisoned");
+                        for key in keys {
+                            if state.handle_key(key) == KeyAction::Cancel {
+                                input::request_cancel();
+                            }
+                        }
+                    }
+                    // Dropping the terminal here restores its settings.
+                }));
+            }
+        }
+    }
+
+    /// Closes the dashboard, and writes out the output held back while it was shown.
+    fn finish(&mut self) {
+        if !self.active {
+            return;
+        }
+        self.active = false;
+        self.stop.store(true, Ordering::Release);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+        // Show the cursor and switch back to the main screen.
+        let _ = write_stderr(b"\x1b[?25h\x1b[?1049l");
+        let _ = write_stderr(&self.held_back);
+        self.held_back.clear();
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        // The run may end early with an error, in which case the terminal still needs to be
+        // restored.
+        self.finish();
+    }
+}
+
+fn write_stderr(bytes: &[u8]) -> io::Result<()> {
+    let stderr = io::stderr();
+    let mut stderr = stderr.lock();
+    stderr.write_all(bytes)?;
+    stderr.flush()
+}
+
+#[derive(Debug)]
+struct DashboardState {
+    started: Instant,
+    prefix: String,
+    message: String,
+    finished: usize,
+    total: usize,
+    running: Vec<RunningTest>,
+    failures: Vec<FailureEntry>,
+    filter: String,
+    editing_filter: bool,
+    // The number of lines the failure log is scrolled up by, from the bottom.
+    scroll: usize,
+    // Whether to run failed tests again once the run finishes.
+    retry_failures: bool,
+    styles: DashboardStyles,
+}
+
+#[derive(Debug)]
+struct RunningTest {
+    name: String,
+    attempt: usize,
+    started: Instant,
+}
+
+#[derive(Debug)]
+struct FailureEntry {
+    name: String,
+    header: String,
+    lines: Vec<String>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum Key {
+    Char(char),
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Backspace,
+    Escape,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum KeyAction {
+    None,
+    Cancel,
+}
+
+impl DashboardState {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            prefix: String::new(),
+            message: String::new(),
+            finished: 0,
+            total: 0,
+            running: Vec::new(),
+            failures: Vec::new(),
+            filter: String::new(),
+            editing_filter: false,
+            scroll: 0,
+            retry_failures: false,
+            styles: DashboardStyles::default(),
+        }
+    }
+
+    fn update(&mut self, event: &TestEvent<'_>, styles: &Styles) {
+        match event {
+            TestEvent::RunStarted { test_list, .. } => {
+                let current_stats = RunStats::default();
+                self.started = Instant::now();
+                self.set_status(
+                    RunningState::Running(&current_stats).progress_bar_prefix(styles),
+                    progress_bar_msg(&current_stats, 0, styles),
+                    0,
+                    test_list.run_count(),
+                );
+            }
+            TestEvent::TestsAdded { test_list } => {
+                // The total is brought up to date with the number of iterations once tests start.
+                self.total += test_list.run_count();
+            }
+            TestEvent::TestStarted {
+                test_instance,
+                current_stats,
+                running,
+                cancel_state,
+            } => {
+                self.running.push(RunningTest {
+                    name: format!(
+                        "{} {}",
+                        test_instance.bin_info.binary_id, test_instance.name
+                    ),
+                    attempt: 1,
+                    started: Instant::now(),
+                });
+                self.set_status(
+                    RunningState::new(*cancel_state, current_stats).progress_bar_prefix(styles),
+                    progress_bar_msg(current_stats, *running, styles),
+                    current_stats.finished_count,
+                    current_stats.initial_run_count,
+                );
+            }
+            TestEvent::TestRetry {
+                test_instance,
+                run_status,
+                ..
+            } => {
+                let name = format!(
+                    "{} {}",
+                    test_instance.bin_info.binary_id, test_instance.name
+                );
+                if let Some(test) = self.running.iter_mut().find(|test| test.name == name) {
+                    test.attempt = run_status.attempt + 1;
+                    test.started = Instant::now();
+                }
+            }
+            TestEvent::TestFinished {
+                test_instance,
+                run_statuses,
+                quarantined,
+                current_stats,
+                running,
+                cancel_state,
+            } => {
+                let name = format!(
+                    "{} {}",
+                    test_instance.bin_info.binary_id, test_instance.name
+                );
+                self.running.retain(|test| test.name != name);
+                if let ExecutionDescription::Failure { last_status, .. } = run_statuses.describe() {
+                    let mut header = format!(
+                        "{} [{:.3}s] {name}",
+                        status_str(last_status.result),
+                        last_status.time_taken.as_secs_f64()
+                    );
+                    if *quarantined {
+                        header.push_str(" (quarantined)");
+                    }
+                    self.failures.push(FailureEntry {
+                        name,
+                        header,
+                        lines: output_lines(last_status),
+                    });
+                }
+                self.set_status(
+                    RunningState::new(*cancel_state, current_stats).progress_bar_prefix(styles),
+                    progress_bar_msg(current_stats, *running, styles),
+                    current_stats.finished_count,
+                    current_stats.initial_run_count,
+                );
+            }
+            TestEvent::RunBeginCancel { reason, .. } => {
+                self.prefix = RunningState::Canceling(*reason).progress_bar_prefix(styles);
+            }
+            _ => {}
+        }
+    }
+
+    fn set_status(&mut self, prefix: String, message: String, finished: usize, total: usize) {
+        self.prefix = prefix;
+        self.message = message;
+        self.finished = finished;
+        self.total = total;
+    }
+
+    fn handle_key(&mut self, key: Key) -> KeyAction {
+        if self.editing_filter {
+            match key {
+                Key::Char(c) => self.filter.push(c),
+                Key::Backspace => {
+                    self.filter.pop();
+                }
+                Key::Enter => self.editing_filter = false,
+                Key::Escape => {
+                    self.filter.clear();
+                    self.editing_filter = false;
+                }
+                _ => {}
+            }
+            self.scroll = 0;
+            return KeyAction::None;
+        }
+
+        match key {
+            Key::Char('q') => return KeyAction::Cancel,
+            Key::Char('r') => self.retry_failures = !self.retry_failures,
+            Key::Char('/') => self.editing_filter = true,
+            Key::Escape => {
+                self.filter.clear();
+                self.scroll = 0;
+            }
+            Key::Up | Key::Char('k') => self.scroll += 1,
+            Key::Down | Key::Char('j') => self.scroll = self.scroll.saturating_sub(1),
+            Key::PageUp => self.scroll += 10,
+            Key::PageDown => self.scroll = self.scroll.saturating_sub(10),
+            Key::Home | Key::Char('g') => self.scroll = usize::MAX,
+            Key::End | Key::Char('G') => self.scroll = 0,
+            _ => {}
+        }
+        KeyAction::None
+    }
+
+    fn matches_filter(&self, name: &str) -> bool {
+        name.contains(self.filter.as_str())
+    }
+
+    /// Renders the dashboard as a string that redraws the whole screen.
+    fn render(&mut self, now: Instant, width: usize, height: usize) -> String {
+        let mut lines = Vec::with_capacity(height);
+        lines.push(format!(
+            "{} [{}] {}/{}: {}",
+            self.prefix,
+            format_elapsed(now.saturating_duration_since(self.started)),
+            self.finished,
+            self.total,
+            self.message
+        ));
+        lines.push(progress_bar(self.finished, self.total, width));
+        lines.push(String::new());
+
+        let mut running: Vec<_> = self
+            .running
+            .iter()
+            .filter(|test| self.matches_filter(&test.name))
+            .collect();
+        // Show the tests that have been running for the longest first.
+        running.sort_by_key(|test| test.started);
+        lines.push(
+            format!("Running tests ({})", running.len())
+                .style(self.styles.heading)
+                .to_string(),
+        );
+        // Leave at least as much room for the failure log as for the running tests.
+        let running_rows = (height.saturating_sub(8) / 2).max(1);
+        let shown = if running.len() > running_rows {
+            running_rows - 1
+        } else {
+            running.len()
+        };
+        for test in &running[..shown] {
+            let mut line = format!(
+                "{:>9.1}s  {}",
+                now.saturating_duration_since(test.started).as_secs_f64(),
+                test.name
+            );
+            if test.attempt > 1 {
+                line.push_str(&format!(" (attempt {})", test.attempt));
+            }
+            lines.push(line);
+        }
+        if shown < running.len() {
+            lines.push(format!(
+                "{:>11}  ... and {} more",
+                "",
+                running.len() - shown
+            ));
+        }
+        lines.push(String::new());
+
+        let failures: Vec<_> = self
+            .failures
+            .iter()
+            .filter(|entry| self.matches_filter(&entry.name))
+            .collect();
+        let mut heading = format!("Failures ({})", failures.len());
+        if self.retry_failures {
+            heading.push_str(", run again once the run finishes");
+        }
+        lines.push(heading.style(self.styles.heading).to_string());
+        let mut log = Vec::new();
+        for entry in failures {
+            log.push(entry.header.style(self.styles.fail).to_string());
+            log.extend(entry.lines.iter().map(|line| format!("  {line}")));
+        }
+        // Fill the rest of the screen with the failure log, leaving a line for the help text.
+        let log_rows = height.saturating_sub(lines.len() + 1);
+        self.scroll = self.scroll.min(log.len().saturating_sub(log_rows));
+        let end = log.len() - self.scroll;
+        let start = end.saturating_sub(log_rows);
+        lines.extend(log.drain(start..end));
+
+        while lines.len() + 1 < height {
+            lines.push(String::new());
+        }
+        lines.truncate(height.saturating_sub(1));
+        let help = if self.editing_filter {
+            format!("filter: {}_", self.filter)
+        } else if !self.filter.is_empty() {
+            format!(
+                "filter: {}  (/ to change, esc to clear, q to cancel the run)",
+                self.filter
+            )
+        } else {
+            "q cancel run · r retry failures · ↑/↓ pgup/pgdn scroll failures · / filter by name"
+                .to_owned()
+        };
+        lines.push(help.style(self.styles.help).to_string());
+
+        let mut screen = String::from("\x1b[H");
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                screen.push_str("\r\n");
+            }
+            screen.push_str(&console::truncate_str(line, width, ""));
+            // Clear the rest of the line, since the screen isn't cleared between frames.
+            screen.push_str("\x1b[K");
+        }
+        screen.push_str("\x1b[J");
+        screen
+    }
+}
+
+#[derive(Debug, Default)]
+struct DashboardStyles {
+    heading: Style,
+    fail: Style,
+    help: Style,
+}
+
+impl DashboardStyles {
+    fn colorize(&mut self) {
+        self.heading = Style::new().bold();
+        self.fail = Style::new().red().bold();
+        self.help = Style::new().dimmed();
+    }
+}
+
+fn output_lines(status: &ExecuteStatus) -> Vec<String> {
+    let mut output = String::from_utf8_lossy(&status.stdout).into_owned();
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push('\n');
+    }
+    output.push_str(&String::from_utf8_lossy(&status.stderr));
+    // Tabs and carriage returns would throw off the layout of the screen.
+    output
+        .trim_end()
+        .lines()
+        .map(|line| line.replace('\t', "    ").replace('\r', ""))
+        .collect()
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn progress_bar(finished: usize, total: usize, width: usize) -> String {
+    let inner = width.saturating_sub(2);
+    let filled = (inner * finished.min(total))
+        .checked_div(total)
+        .unwrap_or_default();
+    let mut bar = String::with_capacity(width);
+    bar.push('[');
+    bar.extend(std::iter::repeat('=').take(filled));
+    if filled < inner {
+        bar.push('>');
+        bar.extend(std::iter::repeat(' ').take(inner - filled - 1));
+    }
+    bar.push(']');
+    bar
+}
+
+/// Reads keys from the terminal the run was started from, without waiting for a newline.
+#[cfg(unix)]
+mod input {
+    use super::Key;
+    use std::{
+        fs::File,
+        io::{self, Read},
+        os::unix::io::AsRawFd,
+        time::Duration,
+    };
+
+    pub(super) struct RawTerminal {
+        tty: File,
+        original: libc::termios,
+    }
+
+    impl RawTerminal {
+        pub(super) fn new() -> io::Result<Self> {
+            let tty = File::open("/dev/tty")?;
+            let fd = tty.as_raw_fd();
+            let mut original = std::mem::MaybeUninit::<libc::termios>::uninit();
+            // SAFETY: tcgetattr fills out the termios struct if it succeeds.
+            let original = unsafe {
+                if libc::tcgetattr(fd, original.as_mut_ptr()) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                original.assume_init()
+            };
+            let mut raw = original;
+            // Turn off line buffering and echoing, but leave signals on so that Ctrl-C still
+            // interrupts the run like it otherwise would.
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+            raw.c_cc[libc::VMIN] = 1;
+            raw.c_cc[libc::VTIME] = 0;
+            // SAFETY: raw is a valid termios struct.
+            if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self { tty, original })
+        }
+
+        /// Waits up to `timeout` for keys to be pressed.
+        pub(super) fn read_keys(&mut self, timeout: Duration) -> io::Result<Vec<Key>> {
+            let mut pollfd = libc::pollfd {
+                fd: self.tty.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // SAFETY: pollfd is a valid array of one pollfd struct.
+            let ready = unsafe { libc::poll(&mut pollfd, 1, timeout.as_millis() as libc::c_int) };
+            if ready < 0 {
+                let error = io::Error::last_os_error();
+                return match error.kind() {
+                    io::ErrorKind::Interrupted => Ok(Vec::new()),
+                    _ => Err(error),
+                };
+            }
+            if ready == 0 {
+                return Ok(Vec::new());
+            }
+            let mut buf = [0; 64];
+            let n = self.tty.read(&mut buf)?;
+            Ok(parse_keys(&buf[..n]))
+        }
+    }
+
+    impl Drop for RawTerminal {
+        fn drop(&mut self) {
+            // SAFETY: original is the termios struct read out in new().
+            unsafe {
+                libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSANOW, &self.original);
+            }
+        }
+    }
+
+    /// Cancels the run the same way Ctrl-C does.
+    pub(super) fn request_cancel() {
+        // SAFETY: raising a signal is always safe, and nextest handles SIGINT.
+        unsafe {
+            libc::raise(libc::SIGINT);
+        }
+    }
+
+    pub(super) fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+        let mut keys = Vec::new();
+        let text = String::from_utf8_lossy(bytes);
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            let key = match c {
+                '\x1b' => {
+                    // A lone escape is the escape key, and otherwise it starts an escape sequence.
+                    if chars.peek() != Some(&'[') {
+                        keys.push(Key::Escape);
+                        continue;
+                    }
+                    chars.next();
+                    let mut sequence = String::new();
+                    for c in chars.by_ref() {
+                        sequence.push(c);
+                        if c.is_ascii_alphabetic() || c == '~' {
+                            break;
+                        }
+                    }
+                    match sequence.as_str() {
+                        "A" => Key::Up,
+                        "B" => Key::Down,
+                        "H" | "1~" => Key::Home,
+                        "F" | "4~" => Key::End,
+                        "5~" => Key::PageUp,
+                        "6~" => Key::PageDown,
+                        _ => continue,
+                    }
+                }
+                '\r' | '\n' => Key::Enter,
+                '\x7f' | '\x08' => Key::Backspace,
+                c if c.is_control() => continue,
+                c => Key::Char(c),
+            };
+            keys.push(key);
+        }
+        keys
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_parse_keys() {
+            assert_eq!(
+                parse_keys(b"/ab\x7f\r\x1b[A\x1b[6~\x1b[Cq\x1b"),
+                vec![
+                    Key::Char('/'),
+                    Key::Char('a'),
+                    Key::Char('b'),
+                    Key::Backspace,
+                    Key::Enter,
+                    Key::Up,
+                    Key::PageDown,
+                    Key::Char('q'),
+                    Key::Escape,
+                ]
+            );
+        }
+
+        #[test]
+        fn test_parse_key_sequences() {
+            // Terminals send Home and End in one of two ways.
+            assert_eq!(
+                parse_keys(b"\x1b[H\x1b[1~\x1b[F\x1b[4~\x1b[5~\x1b[B"),
+                vec![
+                    Key::Home,
+                    Key::Home,
+                    Key::End,
+                    Key::End,
+                    Key::PageUp,
+                    Key::Down,
+                ]
+            );
+            // Unknown sequences, such as for function keys, are skipped as a whole.
+            assert_eq!(parse_keys(b"\x1b[15~\x1b[1;5Cr"), vec![Key::Char('r')]);
+            // Escape followed by something other than `[` is the escape key on its own.
+            assert_eq!(parse_keys(b"\x1bg"), vec![Key::Escape, Key::Char('g')]);
+            assert_eq!(
+                parse_keys("\n\x08\x03é".as_bytes()),
+                vec![Key::Enter, Key::Backspace, Key::Char('é')],
+                "newlines are Enter, other control characters are skipped, and text is UTF-8"
+            );
+            assert_eq!(parse_keys(b""), vec![]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_scroll_and_filter() {
+        let mut state = DashboardState::new();
+        let now = state.started + Duration::from_secs(75);
+        state.set_status("Running".to_owned(), "1 running".to_owned(), 2, 4);
+        state.running.push(RunningTest {
+            name: "my-crate slow".to_owned(),
+            attempt: 2,
+            started: state.started,
+        });
+        for name in ["my-crate tests::a", "my-crate tests::b"] {
+            state.failures.push(FailureEntry {
+                name: name.to_owned(),
+                header: format!("FAIL [1.000s] {name}"),
+                lines: (1..=5).map(|i| format!("line {i}")).collect(),
+            });
+        }
+
+        let screen = state.render(now, 40, 14);
+        let lines: Vec<_> = screen.split("\x1b[K").collect();
+        assert_eq!(lines[0], "\x1b[HRunning [00:01:15] 2/4: 1 running");
+        assert_eq!(lines[1], "\r\n[===================>                  ]");
+        assert_eq!(lines[4], "\r\n     75.0s  my-crate slow (attempt 2)");
+        // The log follows the end of the output by default.
+        assert_eq!(lines[7], "\r\nFAIL [1.000s] my-crate tests::b");
+        assert_eq!(lines[12], "\r\n  line 5");
+
+        // Scrolling up is limited to the start of the log.
+        for _ in 0..20 {
+            state.handle_key(Key::Up);
+        }
+        let screen = state.render(now, 40, 14);
+        let lines: Vec<_> = screen.split("\x1b[K").collect();
+        assert_eq!(lines[7], "\r\nFAIL [1.000s] my-crate tests::a");
+        assert_eq!(state.scroll, 6);
+
+        for key in [Key::Char('/'), Key::Char(':'), Key::Char('a'), Key::Enter] {
+            assert_eq!(state.handle_key(key), KeyAction::None);
+        }
+        let screen = state.render(now, 40, 14);
+        assert!(screen.contains("Running tests (0)"), "screen: {screen:?}");
+        assert!(screen.contains("Failures (1)"), "screen: {screen:?}");
+        assert!(!screen.contains("tests::b"), "screen: {screen:?}");
+        assert_eq!(state.handle_key(Key::Char('q')), KeyAction::Cancel);
+    }
+
+    #[test]
+    fn test_filter_keys() {
+        let mut state = DashboardState::new();
+        state.scroll = 3;
+        for key in [
+            Key::Char('/'),
+            Key::Char('q'),
+            Key::Char('x'),
+            Key::Char('r'),
+        ] {
+            assert_eq!(
+                state.handle_key(key),
+                KeyAction::None,
+                "keys are typed into the filter while it's being edited"
+            );
+        }
+        assert!(state.editing_filter);
+        assert_eq!(state.filter, "qxr");
+        assert!(!state.retry_failures);
+        assert_eq!(state.scroll, 0, "editing the filter scrolls to the end");
+
+        state.handle_key(Key::Backspace);
+        assert_eq!(state.filter, "qx");
+        state.handle_key(Key::Up);
+        assert_eq!(state.scroll, 0, "arrow keys don't scroll while editing");
+        state.handle_key(Key::Enter);
+        assert!(!state.editing_filter);
+        assert_eq!(state.filter, "qx", "enter keeps the filter");
+
+        // Editing the filter again adds to it, and escape clears it.
+        state.handle_key(Key::Char('/'));
+        state.handle_key(Key::Char('y'));
+        assert_eq!(state.filter, "qxy");
+        state.handle_key(Key::Escape);
+        assert!(!state.editing_filter);
+        assert_eq!(state.filter, "");
+
+        // Outside of editing, escape clears the filter too.
+        state.filter = "abc".to_owned();
+        state.scroll = 5;
+        assert_eq!(state.handle_key(Key::Escape), KeyAction::None);
+        assert_eq!(state.filter, "");
+        assert_eq!(state.scroll, 0);
+        // Backspace on an empty filter does nothing.
+        state.handle_key(Key::Char('/'));
+        state.handle_key(Key::Backspace);
+        assert_eq!(state.filter, "");
+    }
+
+    #[test]
+    fn test_scroll_keys() {
+        let mut state = DashboardState::new();
+        state.handle_key(Key::Down);
+        state.handle_key(Key::PageDown);
+        assert_eq!(
+            state.scroll, 0,
+            "scrolling down stops at the end of the log"
+        );
+        state.handle_key(Key::Up);
+        state.handle_key(Key::Char('k'));
+        assert_eq!(state.scroll, 2);
+        state.handle_key(Key::PageUp);
+        assert_eq!(state.scroll, 12);
+        state.handle_key(Key::Char('j'));
+        assert_eq!(state.scroll, 11);
+        state.handle_key(Key::PageDown);
+        state.handle_key(Key::PageDown);
+        assert_eq!(state.scroll, 0);
+        state.handle_key(Key::End);
+        assert_eq!(state.scroll, 0);
+
+        // Scrolling to the start is clamped to the length of the log once the screen is drawn.
+        state.failures.push(FailureEntry {
+            name: "my-crate tests::a".to_owned(),
+            header: "FAIL [1.000s] my-crate tests::a".to_owned(),
+            lines: (1..=20).map(|i| format!("line {i}")).collect(),
+        });
+        state.handle_key(Key::Home);
+        assert_eq!(state.scroll, usize::MAX);
+        let now = state.started;
+        let screen = state.render(now, 40, 20);
+        // 21 lines of log, with 13 of the 20 lines left to show them in.
+        assert_eq!(state.scroll, 8);
+        assert!(
+            screen.contains("FAIL [1.000s] my-crate tests::a"),
+            "screen: {screen:?}"
+        );
+        assert!(screen.contains("line 12"), "screen: {screen:?}");
+        assert!(!screen.contains("line 13"), "screen: {screen:?}");
+        state.handle_key(Key::Char('G'));
+        state.render(now, 40, 20);
+        assert_eq!(state.scroll, 0);
+
+        // A screen too small to show any of the log doesn't scroll it.
+        state.handle_key(Key::Char('g'));
+        state.render(now, 40, 3);
+        assert_eq!(state.scroll, 21);
+    }
+
+    #[test]
+    fn test_retry_failures_key() {
+        let mut state = DashboardState::new();
+        assert_eq!(state.handle_key(Key::Char('r')), KeyAction::None);
+        assert!(state.retry_failures);
+        let screen = state.render(state.started, 80, 12);
+        assert!(
+            screen.contains("Failures (0), run again once the run finishes"),
+            "screen: {screen:?}"
+        );
+        // Pressing r again takes the request back.
+        state.handle_key(Key::Char('r'));
+        assert!(!state.retry_failures);
+        let screen = state.render(state.started, 80, 12);
+        assert!(screen.contains("Failures (0)\x1b[K"), "screen: {screen:?}");
+    }
+
+    #[test]
+    fn test_matches_filter() {
+        let mut state = DashboardState::new();
+        assert!(
+            state.matches_filter("my-crate tests::a"),
+            "an empty filter matches everything"
+        );
+        state.filter = "tests::".to_owned();
+        assert!(state.matches_filter("my-crate tests::a"));
+        state.filter = "crate t".to_owned();
+        assert!(
+            state.matches_filter("my-crate tests::a"),
+            "the filter matches across the binary ID and test name"
+        );
+        state.filter = "Tests".to_owned();
+        assert!(
+            !state.matches_filter("my-crate tests::a"),
+            "the filter is case-sensitive"
+        );
+        state.filter = "tests::b".to_owned();
+        assert!(!state.matches_filter("my-crate tests::a"));
+    }
+
+    #[test]
+    fn test_progress_bar() {
+        assert_eq!(progress_bar(0, 4, 10), "[>       ]");
+        assert_eq!(progress_bar(2, 4, 10), "[====>   ]");
+        assert_eq!(progress_bar(4, 4, 10), "[========]");
+        assert_eq!(progress_bar(0, 0, 4), "[> ]");
+    }
+}
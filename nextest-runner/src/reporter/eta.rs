@@ -0,0 +1,368 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Estimates how much longer a run will take, for the progress bar.
+//!
+//! Each test is expected to take as long as it did the last time it was run, according to the
+//! timings recorded in the run store, and tests that haven't been timed are expected to take as
+//! long as the average test. As tests finish, expectations are scaled by how much faster or slower
+//! tests run this time around, and the work left is divided by the number of tests that have
+//! effectively been running at a time.
+
+use crate::{list::TestList, reporter::TestEvent, run_store::TestTimings};
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+
+/// How much faster or slower than their timings tests are assumed to be running, at most.
+const MAX_SCALE: f64 = 4.0;
+
+/// How long a run has to go on for before the number of tests finished per second is shown.
+const THROUGHPUT_AFTER: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub(crate) struct EtaEstimator {
+    timings: Option<TestTimings>,
+    started: Option<Instant>,
+    // (binary ID, test name) -> how long the test took the last time it was run.
+    expected: HashMap<(String, String), Option<Duration>>,
+    // (binary ID, test name) -> the number of times the test is still to be started.
+    pending: HashMap<(String, String), usize>,
+    // Expected durations of the runs still to be started, with how many runs took as long. Tests
+    // that haven't been timed are counted in pending_untimed instead.
+    pending_timed: BTreeMap<Duration, usize>,
+    pending_untimed: usize,
+    // (binary ID, test name) -> when the current attempt started.
+    running: HashMap<(String, String), Instant>,
+    // Across tests that finished: how long they took, and how long the ones that had been timed
+    // took along with how long they were expected to take.
+    finished_time: Duration,
+    finished_count: usize,
+    timed_actual: Duration,
+    timed_expected: Duration,
+    // The number of times each test is run, counting tests run until one fails once.
+    runs: usize,
+    // The total timing of tests in the run that have been timed, and how many there are.
+    timed_total: Duration,
+    timed_count: u32,
+    // The average timing of tests in the run that have been timed.
+    average_timing: Option<Duration>,
+}
+
+/// An estimate of how the run is going.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Estimate {
+    /// How much longer the run is expected to take, if it can be estimated yet.
+    pub(crate) remaining: Option<Duration>,
+
+    /// The number of tests finished per second so far, once the run has gone on for long enough
+    /// for it to be meaningful.
+    pub(crate) throughput: Option<f64>,
+}
+
+impl EtaEstimator {
+    pub(crate) fn new(timings: Option<TestTimings>) -> Self {
+        Self {
+            timings,
+            started: None,
+            expected: HashMap::new(),
+            pending: HashMap::new(),
+            pending_timed: BTreeMap::new(),
+            pending_untimed: 0,
+            running: HashMap::new(),
+            finished_time: Duration::ZERO,
+            finished_count: 0,
+            timed_actual: Duration::ZERO,
+            timed_expected: Duration::ZERO,
+            runs: 1,
+            timed_total: Duration::ZERO,
+            timed_count: 0,
+            average_timing: None,
+        }
+    }
+
+    pub(crate) fn write_event(&mut self, event: &TestEvent<'_>, now: Instant) {
+        match event {
+            TestEvent::RunStarted {
+                test_list,
+                iterations,
+                ..
+            } => {
+                self.started = Some(now);
+                // With --until-failure and no limit, tests are run as many times as it takes for
+                // one to fail, so count each of them once.
+                self.runs = iterations.unwrap_or(1);
+                self.add_tests(test_list);
+            }
+            TestEvent::TestsAdded { test_list } => self.add_tests(test_list),
+            TestEvent::TestStarted { test_instance, .. } => {
+                let key = (
+                    test_instance.bin_info.binary_id.clone(),
+                    test_instance.name.to_owned(),
+                );
+                if let Some(runs) = self.pending.get_mut(&key) {
+                    if *runs > 0 {
+                        *runs -= 1;
+                        let expected = self.expected.get(&key).copied().flatten();
+                        self.remove_pending(expected);
+                    }
+                }
+                self.running.insert(key, now);
+            }
+            TestEvent::TestRetry {
+                test_instance,
+                delay,
+                ..
+            } => {
+                let key = (
+                    test_instance.bin_info.binary_id.clone(),
+                    test_instance.name.to_owned(),
+                );
+                if let Some(started) = self.running.get_mut(&key) {
+                    *started = now + *delay;
+                }
+            }
+            TestEvent::TestFinished {
+                test_instance,
+                run_statuses,
+                ..
+            } => {
+                let key = (
+                    test_instance.bin_info.binary_id.clone(),
+                    test_instance.name.to_owned(),
+                );
+                self.running.remove(&key);
+                let time_taken = run_statuses.last_status().time_taken;
+                self.finished_time += time_taken;
+                self.finished_count += 1;
+                if let Some(expected) = self.expected.get(&key).copied().flatten() {
+                    self.timed_actual += time_taken;
+                    self.timed_expected += expected;
+                }
+            }
+            TestEvent::TestPreviouslyFinished { test_instance, .. }
+            | TestEvent::TestNotRun { test_instance } => {
+                // These tests won't be started in this run.
+                let key = (
+                    test_instance.bin_info.binary_id.clone(),
+                    test_instance.name.to_owned(),
+                );
+                if let Some(runs) = self.pending.remove(&key) {
+                    let expected = self.expected.get(&key).copied().flatten();
+                    for _ in 0..runs {
+                        self.remove_pending(expected);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn add_tests(&mut self, test_list: &TestList<'_>) {
+        let runs = self.runs;
+        for test_instance in test_list.iter_tests() {
+            if !test_instance.test_info.filter_match.is_match() {
+                continue;
+            }
+            let key = (
+                test_instance.bin_info.binary_id.clone(),
+                test_instance.name.to_owned(),
+            );
+            let expected = self
+                .timings
+                .as_ref()
+                .and_then(|timings| timings.get(&key.0, &key.1));
+            if let Some(expected) = expected {
+                self.timed_total += expected;
+                self.timed_count += 1;
+            }
+            self.add_pending(expected, runs);
+            self.expected.insert(key.clone(), expected);
+            self.pending.insert(key, runs);
+        }
+        self.average_timing = (self.timed_count > 0).then(|| self.timed_total / self.timed_count);
+    }
+
+    fn add_pending(&mut self, expected: Option<Duration>, runs: usize) {
+        match expected {
+            Some(expected) => *self.pending_timed.entry(expected).or_default() += runs,
+            None => self.pending_untimed += runs,
+        }
+    }
+
+    fn remove_pending(&mut self, expected: Option<Duration>) {
+        match expected {
+            Some(expected) => {
+                if let Some(count) = self.pending_timed.get_mut(&expected) {
+                    *count -= 1;
+                    if *count == 0 {
+                        self.pending_timed.remove(&expected);
+                    }
+                }
+            }
+            None => self.pending_untimed = self.pending_untimed.saturating_sub(1),
+        }
+    }
+
+    /// Estimates how much longer the run will take, as of `now`.
+    pub(crate) fn estimate(&self, now: Instant) -> Estimate {
+        let elapsed = match self.started {
+            Some(started) => now.saturating_duration_since(started),
+            None => {
+                return Estimate {
+                    remaining: None,
+                    throughput: None,
+                }
+            }
+        };
+        let throughput = (elapsed >= THROUGHPUT_AFTER)
+            .then(|| self.finished_count as f64 / elapsed.as_secs_f64());
+        Estimate {
+            remaining: self.remaining(now, elapsed),
+            throughput,
+        }
+    }
+
+    fn remaining(&self, now: Instant, elapsed: Duration) -> Option<Duration> {
+        // Tests may be running faster or slower than when they were timed, for example if the
+        // machine is busier.
+        let scale = match self.timed_expected.as_secs_f64() {
+            expected if expected > 0.0 => {
+                (self.timed_actual.as_secs_f64() / expected).clamp(1.0 / MAX_SCALE, MAX_SCALE)
+            }
+            _ => 1.0,
+        };
+        let untimed = match (self.average_timing, self.finished_count) {
+            (Some(average), _) => average.as_secs_f64() * scale,
+            (None, 0) => {
+                // Nothing is known about how long the tests left take.
+                if self.pending_untimed > 0 || !self.running.is_empty() {
+                    return None;
+                }
+                0.0
+            }
+            (None, count) => self.finished_time.as_secs_f64() / count as f64,
+        };
+        let expected = |key: &(String, String)| match self.expected.get(key).copied().flatten() {
+            Some(expected) => expected.as_secs_f64() * scale,
+            None => untimed,
+        };
+
+        let mut work = self
+            .pending_timed
+            .iter()
+            .map(|(expected, count)| expected.as_secs_f64() * scale * *count as f64)
+            .sum::<f64>()
+            + untimed * self.pending_untimed as f64;
+        // The run can't finish before the longest test left does.
+        let mut longest = self
+            .pending_timed
+            .keys()
+            .next_back()
+            .map_or(0.0, |expected| expected.as_secs_f64() * scale);
+        if self.pending_untimed > 0 {
+            longest = longest.max(untimed);
+        }
+        let mut running_time = 0.0;
+        for (key, started) in &self.running {
+            let so_far = now.saturating_duration_since(*started).as_secs_f64();
+            running_time += so_far;
+            let left = (expected(key) - so_far).max(0.0);
+            work += left;
+            longest = longest.max(left);
+        }
+
+        // The number of tests that have effectively been running at a time, which is lower than
+        // the number of test threads if tests are held up by test groups or the like.
+        let busy = self.finished_time.as_secs_f64() + running_time;
+        let parallelism = match elapsed.as_secs_f64() {
+            secs if secs > 0.0 && busy > 0.0 => (busy / secs).max(1.0),
+            _ => (self.running.len() as f64).max(1.0),
+        };
+        Some(Duration::from_secs_f64((work / parallelism).max(longest)))
+    }
+}
+
+/// Formats an estimate of time left, rounded to the second.
+pub(crate) fn format_eta(remaining: Duration) -> String {
+    let secs = remaining.as_secs() + u64::from(remaining.subsec_millis() >= 500);
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs / 60 % 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(test_name: &str) -> (String, String) {
+        ("my-crate".to_owned(), test_name.to_owned())
+    }
+
+    #[test]
+    fn test_remaining() {
+        let start = Instant::now();
+        let mut estimator = EtaEstimator::new(None);
+        estimator.started = Some(start);
+        for (test_name, expected) in [("a", Some(10)), ("b", Some(2)), ("c", Some(4)), ("d", None)]
+        {
+            let expected = expected.map(Duration::from_secs);
+            estimator.add_pending(expected, 1);
+            estimator.expected.insert(key(test_name), expected);
+            estimator.pending.insert(key(test_name), 1);
+        }
+        estimator.average_timing = Some(Duration::from_secs(4));
+
+        // Nothing has run yet: 10 + 2 + 4 + 4 seconds of work on one thread.
+        assert_eq!(
+            estimator.remaining(start, Duration::ZERO),
+            Some(Duration::from_secs(20))
+        );
+
+        // a and b start together, and b finishes after 4 seconds, twice as long as expected.
+        for test_name in ["a", "b"] {
+            estimator.remove_pending(estimator.expected[&key(test_name)]);
+            estimator.running.insert(key(test_name), start);
+        }
+        let now = start + Duration::from_secs(4);
+        estimator.running.remove(&key("b"));
+        estimator.finished_time = Duration::from_secs(4);
+        estimator.finished_count = 1;
+        estimator.timed_actual = Duration::from_secs(4);
+        estimator.timed_expected = Duration::from_secs(2);
+        // Two tests have been running at a time. Left: a has 20 - 4 seconds, and c and d have 8
+        // seconds each, for 32 seconds of work. But a alone has 16 seconds left.
+        assert_eq!(
+            estimator.remaining(now, Duration::from_secs(4)),
+            Some(Duration::from_secs(16))
+        );
+    }
+
+    #[test]
+    fn test_remaining_untimed() {
+        let start = Instant::now();
+        let mut estimator = EtaEstimator::new(None);
+        estimator.started = Some(start);
+        estimator.add_pending(None, 3);
+        assert_eq!(estimator.remaining(start, Duration::ZERO), None);
+
+        estimator.remove_pending(None);
+        estimator.finished_time = Duration::from_secs(3);
+        estimator.finished_count = 1;
+        assert_eq!(
+            estimator.remaining(start + Duration::from_secs(3), Duration::from_secs(3)),
+            Some(Duration::from_secs(6))
+        );
+    }
+
+    #[test]
+    fn test_format_eta() {
+        assert_eq!(format_eta(Duration::from_millis(400)), "0s");
+        assert_eq!(format_eta(Duration::from_millis(59_600)), "1m 00s");
+        assert_eq!(format_eta(Duration::from_secs(754)), "12m 34s");
+        assert_eq!(format_eta(Duration::from_secs(7384)), "2h 03m");
+    }
+}
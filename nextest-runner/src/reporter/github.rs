@@ -0,0 +1,289 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Writes GitHub Actions workflow commands for failing tests, for `--message-format
+//! github-actions`.
+//!
+//! GitHub Actions turns each `::error` command into an annotation, shown inline on the diff of a
+//! pull request. Annotations point at the place the test panicked at if it's in the workspace, and
+//! at the definition of the test otherwise.
+
+use crate::{
+    errors::WriteEventError,
+    list::TestInstance,
+    reporter::{heuristic_extract_description, status_str, TestEvent},
+    runner::{ExecuteStatus, ExecutionDescription},
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+};
+
+/// The place a test panicked at, in both the format used since Rust 1.73
+/// (`panicked at src/lib.rs:4:5:`) and the one used before it (`panicked at 'message',
+/// src/lib.rs:4:5`). Newer versions of Rust also print the ID of the thread after its name.
+static PANIC_LOCATION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?m)^thread '[^']*'(?: \(\d+\))? panicked at (?:'.*', )?([^'\r\n]+?):(\d+):(\d+):?\r?$",
+    )
+    .expect("regex is valid")
+});
+
+/// Writes an annotation for each test that fails or is flaky.
+pub(crate) struct GithubActionsReporter<W> {
+    writer: W,
+}
+
+impl GithubActionsReporter<io::Stdout> {
+    /// Creates a new reporter that writes to standard output.
+    pub(crate) fn stdout() -> Self {
+        Self {
+            writer: io::stdout(),
+        }
+    }
+}
+
+impl<W: Write> GithubActionsReporter<W> {
+    pub(crate) fn write_event(&mut self, event: &TestEvent<'_>) -> Result<(), WriteEventError> {
+        let annotation = match event {
+            TestEvent::TestFinished {
+                test_instance,
+                run_statuses,
+                quarantined,
+                ..
+            } => match run_statuses.describe() {
+                ExecutionDescription::Success { .. } => return Ok(()),
+                ExecutionDescription::Flaky { prior_statuses, .. } => Annotation::new(
+                    "warning",
+                    test_instance,
+                    &prior_statuses[0],
+                    format!(
+                        "Test passed after {} failed attempts. First failure:\n{}",
+                        prior_statuses.len(),
+                        failure_message(&prior_statuses[0]),
+                    ),
+                ),
+                ExecutionDescription::Failure { last_status, .. } => Annotation::new(
+                    // Failures of quarantined tests don't fail the run.
+                    if *quarantined { "warning" } else { "error" },
+                    test_instance,
+                    last_status,
+                    failure_message(last_status),
+                ),
+            },
+            _ => return Ok(()),
+        };
+
+        self.writer
+            .write_all(annotation.to_string().as_bytes())
+            .map_err(WriteEventError::Io)?;
+        self.writer.flush().map_err(WriteEventError::Io)
+    }
+}
+
+struct Annotation {
+    command: &'static str,
+    location: Option<SourceLocation>,
+    title: String,
+    message: String,
+}
+
+/// A place in a source file, relative to the workspace root.
+#[derive(Debug, PartialEq)]
+pub(super) struct SourceLocation {
+    pub(super) file: String,
+    pub(super) line: u32,
+    pub(super) column: u32,
+}
+
+impl Annotation {
+    fn new(
+        command: &'static str,
+        test_instance: &TestInstance<'_>,
+        status: &ExecuteStatus,
+        message: String,
+    ) -> Self {
+        Self {
+            command,
+            location: test_location(test_instance, status),
+            title: format!(
+                "{} {} {}",
+                status_str(status.result),
+                test_instance.bin_info.binary_id,
+                test_instance.name
+            ),
+            message,
+        }
+    }
+}
+
+impl std::fmt::Display for Annotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "::{} ", self.command)?;
+        if let Some(location) = &self.location {
+            write!(
+                f,
+                "file={},line={},col={},",
+                escape_property(&location.file),
+                location.line,
+                location.column
+            )?;
+        }
+        writeln!(
+            f,
+            "title={}::{}",
+            escape_property(&self.title),
+            escape_data(&self.message)
+        )
+    }
+}
+
+/// Returns the place a test panicked at if it's within the workspace, and otherwise the place the
+/// test is defined at, if known.
+pub(super) fn test_location(
+    test_instance: &TestInstance<'_>,
+    status: &ExecuteStatus,
+) -> Option<SourceLocation> {
+    let stderr = String::from_utf8_lossy(&status.stderr);
+    panic_location(&stderr).or_else(|| {
+        let location = test_instance.test_info.location.as_ref()?;
+        Some(SourceLocation {
+            file: location.path.as_str().replace('\\', "/"),
+            line: location.line,
+            column: location.column,
+        })
+    })
+}
+
+/// Returns the description of a failure: the panic message if there is one, and otherwise the
+/// status of the test.
+pub(super) fn failure_message(status: &ExecuteStatus) -> String {
+    let stdout = String::from_utf8_lossy(&status.stdout);
+    let stderr = String::from_utf8_lossy(&status.stderr);
+    if let Some(location) = PANIC_LOCATION.find(&stderr) {
+        let panic = &stderr[location.start()..];
+        // Leave out the backtrace, which makes for annotations that are too long to be useful.
+        let end = ["\nstack backtrace:", "\nnote: run with `RUST_BACKTRACE"]
+            .iter()
+            .filter_map(|marker| panic.find(marker))
+            .min()
+            .unwrap_or(panic.len());
+        return panic[..end].trim_end().to_owned();
+    }
+    heuristic_extract_description(status.result, &stdout, &stderr)
+        .unwrap_or_else(|| format!("Test failed with status {}", status_str(status.result)))
+}
+
+/// Returns the file, line and column a test panicked at, if it's a relative path within the
+/// workspace.
+fn panic_location(stderr: &str) -> Option<SourceLocation> {
+    let captures = PANIC_LOCATION.captures(stderr)?;
+    let file = captures[1].replace('\\', "/");
+    // Absolute paths point outside the workspace, for example into the standard library or a
+    // registry dependency.
+    let is_absolute = file.starts_with('/') || file.get(1..3) == Some(":/");
+    if is_absolute {
+        return None;
+    }
+    Some(SourceLocation {
+        file,
+        line: captures[2].parse().ok()?,
+        column: captures[3].parse().ok()?,
+    })
+}
+
+/// Escapes the message of a workflow command.
+fn escape_data(s: &str) -> Cow<'_, str> {
+    if !s.contains(['%', '\r', '\n']) {
+        return Cow::Borrowed(s);
+    }
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '%' => escaped.push_str("%25"),
+            '\r' => escaped.push_str("%0D"),
+            '\n' => escaped.push_str("%0A"),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Escapes a property of a workflow command.
+fn escape_property(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in escape_data(s).chars() {
+        match c {
+            ':' => escaped.push_str("%3A"),
+            ',' => escaped.push_str("%2C"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panic_location() {
+        let loc = |file: &str, line, column| {
+            Some(SourceLocation {
+                file: file.to_owned(),
+                line,
+                column,
+            })
+        };
+        let tests: &[(&str, Option<SourceLocation>)] = &[
+            (
+                "thread 'tests::foo' panicked at src/lib.rs:67:5:\nassertion failed\n",
+                loc("src/lib.rs", 67, 5),
+            ),
+            (
+                "thread 'tests::bar' (3885) panicked at tests/it.rs:2:9:\nfail\n",
+                loc("tests/it.rs", 2, 9),
+            ),
+            (
+                "Error: oops\nthread 'main' panicked at 'foo', crates\\my-crate\\src\\lib.rs:1:9\n",
+                loc("crates/my-crate/src/lib.rs", 1, 9),
+            ),
+            (
+                "thread 'foo' panicked at /rustc/fe5b13d6/library/test/src/lib.rs:186:5:\n",
+                None,
+            ),
+            ("thread 'foo' panicked at 'foo', src/lib.rs:1\n", None),
+            ("test foo ... FAILED\n", None),
+        ];
+        for (stderr, expected) in tests {
+            assert_eq!(&panic_location(stderr), expected, "for {stderr:?}");
+        }
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape_data("50% done\r\nnext"), "50%25 done%0D%0Anext");
+        assert_eq!(escape_property("a: b, c"), "a%3A b%2C c");
+    }
+
+    #[test]
+    fn test_annotation_display() {
+        let annotation = Annotation {
+            command: "error",
+            location: Some(SourceLocation {
+                file: "src/lib.rs".to_owned(),
+                line: 4,
+                column: 5,
+            }),
+            title: "FAIL my-crate tests::foo".to_owned(),
+            message: "thread 'tests::foo' panicked at src/lib.rs:4:5:\nboom".to_owned(),
+        };
+        assert_eq!(
+            annotation.to_string(),
+            "::error file=src/lib.rs,line=4,col=5,title=FAIL my-crate tests%3A%3Afoo::\
+             thread 'tests::foo' panicked at src/lib.rs:4:5:%0Aboom\n"
+        );
+    }
+}
@@ -0,0 +1,564 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Writes a self-contained HTML report of a run, configured with `html-report.path`.
+//!
+//! The report is a single file with its styles and scripts inline, so that it can be attached to
+//! CI runs as an artifact and opened in any browser. It's written out once the run finishes.
+
+use crate::{
+    errors::WriteEventError,
+    list::TestInstance,
+    reporter::{aggregator::to_datetime, status_str, TestEvent},
+    runner::{ExecuteStatus, ExecutionDescription, RunStats},
+};
+use camino::Utf8PathBuf;
+use chrono::SecondsFormat;
+use nextest_filtering::RunStatus;
+use nextest_metadata::MismatchReason;
+use std::{borrow::Cow, fmt::Write as _, time::Duration};
+
+/// Collects the results of tests as they finish, and writes them out as an HTML report at the end
+/// of the run.
+#[derive(Clone, Debug)]
+pub(crate) struct HtmlReport {
+    path: Utf8PathBuf,
+    iterations: Option<usize>,
+    shuffle_seed: Option<u64>,
+    seed: u64,
+    tests: Vec<TestRow>,
+}
+
+impl HtmlReport {
+    pub(crate) fn new(path: Utf8PathBuf) -> Self {
+        Self {
+            path,
+            iterations: Some(1),
+            shuffle_seed: None,
+            seed: 0,
+            tests: Vec::new(),
+        }
+    }
+
+    pub(crate) fn write_event(&mut self, event: &TestEvent<'_>) -> Result<(), WriteEventError> {
+        match event {
+            TestEvent::RunStarted {
+                iterations,
+                shuffle_seed,
+                seed,
+                ..
+            } => {
+                self.iterations = *iterations;
+                self.shuffle_seed = *shuffle_seed;
+                self.seed = *seed;
+            }
+            TestEvent::TestFinished {
+                test_instance,
+                run_statuses,
+                quarantined,
+                ..
+            } => {
+                let last_status = run_statuses.last_status();
+                let (status, label) = match run_statuses.describe() {
+                    ExecutionDescription::Success { .. } => (RowStatus::Passed, "PASS".into()),
+                    ExecutionDescription::Flaky { .. } => (RowStatus::Flaky, "FLAKY".into()),
+                    ExecutionDescription::Failure { .. } => {
+                        (RowStatus::Failed, status_str(last_status.result))
+                    }
+                };
+                self.tests.push(TestRow {
+                    note: quarantined
+                        .then(|| "quarantined: failures don't fail the run".to_owned()),
+                    attempts: run_statuses.iter().map(AttemptRow::new).collect(),
+                    time_taken: Some(last_status.time_taken),
+                    ..TestRow::new(test_instance, status, label)
+                });
+            }
+            TestEvent::TestPreviouslyFinished {
+                test_instance,
+                status,
+                time_taken,
+                ..
+            } => {
+                let (status, label) = match status {
+                    RunStatus::Flaky => (RowStatus::Flaky, "FLAKY"),
+                    RunStatus::Failed => (RowStatus::Failed, "FAIL"),
+                    RunStatus::Passed | RunStatus::Skipped => (RowStatus::Passed, "PASS"),
+                };
+                self.tests.push(TestRow {
+                    note: Some("finished before the run was resumed".to_owned()),
+                    time_taken: Some(*time_taken),
+                    ..TestRow::new(test_instance, status, label.into())
+                });
+            }
+            TestEvent::TestSkipped {
+                test_instance,
+                reason: MismatchReason::Ignored,
+            } => {
+                // Tests skipped because they don't match filters aren't part of the run, so leave
+                // them out of the report.
+                self.tests.push(TestRow {
+                    note: test_instance.test_info.ignore_reason.clone(),
+                    ..TestRow::new(test_instance, RowStatus::Skipped, "SKIP".into())
+                });
+            }
+            TestEvent::TestNotRun { test_instance } => {
+                self.tests.push(TestRow {
+                    note: Some("the run time budget was used up".to_owned()),
+                    ..TestRow::new(test_instance, RowStatus::NotRun, "NOT RUN".into())
+                });
+            }
+            TestEvent::RunFinished {
+                run_id,
+                start_time,
+                elapsed,
+                run_stats,
+                ..
+            } => {
+                let metadata = RunMetadata {
+                    run_id: run_id.to_string(),
+                    start_time: to_datetime(*start_time).to_rfc3339_opts(SecondsFormat::Secs, true),
+                    elapsed: *elapsed,
+                    run_stats: *run_stats,
+                    iterations: self.iterations,
+                    shuffle_seed: self.shuffle_seed,
+                    seed: self.seed,
+                };
+                self.write(&metadata)?;
+            }
+            TestEvent::TestsAdded { .. }
+            | TestEvent::TestStarted { .. }
+            | TestEvent::TestSlow { .. }
+            | TestEvent::TestOutput { .. }
+            | TestEvent::TestRetry { .. }
+            | TestEvent::TestIsolationChecked { .. }
+            | TestEvent::TestDeterminismChecked { .. }
+            | TestEvent::TestSkipped { .. }
+            | TestEvent::RunBeginCancel { .. } => {}
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, metadata: &RunMetadata) -> Result<(), WriteEventError> {
+        // Show failures first, since they're what people usually open the report for.
+        self.tests.sort_by(|a, b| {
+            (a.status, &a.binary_id, &a.name).cmp(&(b.status, &b.binary_id, &b.name))
+        });
+        let html = render(metadata, &self.tests);
+
+        let dir = self.path.parent().expect("report path must have a parent");
+        std::fs::create_dir_all(dir).map_err(|error| WriteEventError::Fs {
+            file: dir.to_path_buf(),
+            error,
+        })?;
+        std::fs::write(&self.path, html).map_err(|error| WriteEventError::Fs {
+            file: self.path.clone(),
+            error,
+        })
+    }
+}
+
+struct RunMetadata {
+    run_id: String,
+    start_time: String,
+    elapsed: Duration,
+    run_stats: RunStats,
+    iterations: Option<usize>,
+    shuffle_seed: Option<u64>,
+    seed: u64,
+}
+
+/// The status a test is filtered by in the report, in the order tests are shown in.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum RowStatus {
+    Failed,
+    Flaky,
+    Passed,
+    Skipped,
+    NotRun,
+}
+
+impl RowStatus {
+    const ALL: [Self; 5] = [
+        Self::Failed,
+        Self::Flaky,
+        Self::Passed,
+        Self::Skipped,
+        Self::NotRun,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Failed => "failed",
+            Self::Flaky => "flaky",
+            Self::Passed => "passed",
+            Self::Skipped => "skipped",
+            Self::NotRun => "not-run",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct TestRow {
+    binary_id: String,
+    name: String,
+    status: RowStatus,
+    label: Cow<'static, str>,
+    note: Option<String>,
+    time_taken: Option<Duration>,
+    attempts: Vec<AttemptRow>,
+}
+
+impl TestRow {
+    fn new(test_instance: &TestInstance<'_>, status: RowStatus, label: Cow<'static, str>) -> Self {
+        Self {
+            binary_id: test_instance.bin_info.binary_id.clone(),
+            name: test_instance.name.to_owned(),
+            status,
+            label,
+            note: None,
+            time_taken: None,
+            attempts: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct AttemptRow {
+    attempt: usize,
+    total_attempts: usize,
+    iteration: usize,
+    label: Cow<'static, str>,
+    time_taken: Duration,
+    stdout: String,
+    stderr: String,
+}
+
+impl AttemptRow {
+    fn new(status: &ExecuteStatus) -> Self {
+        Self {
+            attempt: status.attempt,
+            total_attempts: status.total_attempts,
+            iteration: status.iteration,
+            label: status_str(status.result),
+            time_taken: status.time_taken,
+            stdout: String::from_utf8_lossy(&status.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&status.stderr).into_owned(),
+        }
+    }
+}
+
+fn render(metadata: &RunMetadata, tests: &[TestRow]) -> String {
+    let stats = &metadata.run_stats;
+    let result = if stats.is_success() {
+        "passed"
+    } else {
+        "failed"
+    };
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>\n\
+         <html lang=\"en\">\n\
+         <head>\n\
+         <meta charset=\"utf-8\">\n\
+         <title>nextest run {run_id}</title>\n\
+         <style>{STYLE}</style>\n\
+         </head>\n\
+         <body>\n\
+         <h1>nextest run <span class=\"{result}\">{result}</span></h1>\n\
+         <table class=\"metadata\">\n",
+        run_id = escape(&metadata.run_id),
+    );
+
+    let mut summary = format!(
+        "{} of {} run: {} passed",
+        stats.finished_count, stats.initial_run_count, stats.passed
+    );
+    for (count, kind) in [
+        (stats.flaky, "flaky"),
+        (stats.failed, "failed"),
+        (stats.timed_out, "timed out"),
+        (stats.leaky, "leaky"),
+        (stats.exec_failed, "exec failed"),
+        (stats.quarantined_failed, "quarantined failures"),
+        (stats.skipped, "skipped"),
+    ] {
+        if count > 0 {
+            let _ = write!(summary, ", {count} {kind}");
+        }
+    }
+    let mut metadata_rows = vec![
+        ("Run ID", metadata.run_id.clone()),
+        ("Started", metadata.start_time.clone()),
+        ("Duration", format_duration(metadata.elapsed)),
+        ("Tests", summary),
+        ("Seed", metadata.seed.to_string()),
+    ];
+    if let Some(shuffle_seed) = metadata.shuffle_seed {
+        metadata_rows.push(("Shuffle seed", shuffle_seed.to_string()));
+    }
+    match metadata.iterations {
+        Some(1) => {}
+        Some(iterations) => metadata_rows.push(("Iterations", iterations.to_string())),
+        None => metadata_rows.push(("Iterations", "until failure".to_owned())),
+    }
+    for (name, value) in metadata_rows {
+        let _ = writeln!(out, "<tr><th>{name}</th><td>{}</td></tr>", escape(&value));
+    }
+
+    out.push_str(
+        "</table>\n\
+         <p class=\"controls\">\n\
+         <input id=\"filter\" type=\"search\" placeholder=\"Filter tests\">\n\
+         <select id=\"status\">\n\
+         <option value=\"\">All statuses</option>\n",
+    );
+    for status in RowStatus::ALL {
+        let count = tests.iter().filter(|test| test.status == status).count();
+        if count > 0 {
+            let _ = writeln!(
+                out,
+                "<option value=\"{status}\">{status} ({count})</option>",
+                status = status.as_str()
+            );
+        }
+    }
+    out.push_str(
+        "</select>\n\
+         </p>\n\
+         <table id=\"tests\">\n\
+         <thead><tr>\
+         <th data-type=\"number\">Status</th>\
+         <th>Binary</th>\
+         <th>Test</th>\
+         <th data-type=\"number\">Duration</th>\
+         <th data-type=\"number\">Attempts</th>\
+         </tr></thead>\n\
+         <tbody>\n",
+    );
+    for test in tests {
+        render_test(&mut out, test);
+    }
+    let _ = write!(
+        out,
+        "</tbody>\n\
+         </table>\n\
+         <script>{SCRIPT}</script>\n\
+         </body>\n\
+         </html>\n"
+    );
+    out
+}
+
+fn render_test(out: &mut String, test: &TestRow) {
+    let status = test.status.as_str();
+    let _ = write!(
+        out,
+        "<tr data-status=\"{status}\" data-search=\"{search}\">\
+         <td class=\"{status}\" data-value=\"{rank}\">{label}</td>\
+         <td>{binary_id}</td>\
+         <td>",
+        search = escape(&format!("{} {}", test.binary_id, test.name).to_lowercase()),
+        rank = test.status as usize,
+        label = escape(&test.label),
+        binary_id = escape(&test.binary_id),
+    );
+
+    let name = escape(&test.name);
+    if test.attempts.is_empty() && test.note.is_none() {
+        out.push_str(&name);
+    } else {
+        let _ = write!(out, "<details><summary>{name}</summary>");
+        if let Some(note) = &test.note {
+            let _ = write!(out, "<p class=\"note\">{}</p>", escape(note));
+        }
+        for attempt in &test.attempts {
+            render_attempt(out, attempt);
+        }
+        out.push_str("</details>");
+    }
+
+    match test.time_taken {
+        Some(time_taken) => {
+            let _ = write!(
+                out,
+                "</td><td data-value=\"{}\">{}</td>",
+                time_taken.as_secs_f64(),
+                format_duration(time_taken)
+            );
+        }
+        None => out.push_str("</td><td data-value=\"-1\"></td>"),
+    }
+    let _ = writeln!(out, "<td>{}</td></tr>", test.attempts.len());
+}
+
+fn render_attempt(out: &mut String, attempt: &AttemptRow) {
+    let _ = write!(out, "<div class=\"attempt\"><h4>");
+    if attempt.total_attempts > 1 {
+        let _ = write!(
+            out,
+            "Attempt {} of {}: ",
+            attempt.attempt, attempt.total_attempts
+        );
+    }
+    if attempt.iteration > 1 {
+        let _ = write!(out, "iteration {}: ", attempt.iteration);
+    }
+    let _ = write!(
+        out,
+        "{} after {}</h4>",
+        escape(&attempt.label),
+        format_duration(attempt.time_taken)
+    );
+    if attempt.stdout.is_empty() && attempt.stderr.is_empty() {
+        out.push_str("<p class=\"note\">No output was captured.</p>");
+    }
+    for (name, output) in [("stdout", &attempt.stdout), ("stderr", &attempt.stderr)] {
+        if !output.is_empty() {
+            let _ = write!(out, "<h5>{name}</h5><pre>{}</pre>", escape(output));
+        }
+    }
+    out.push_str("</div>");
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.3}s", duration.as_secs_f64())
+}
+
+/// Escapes text for use in HTML, both as element content and within quoted attributes.
+pub(super) fn escape(s: &str) -> Cow<'_, str> {
+    if !s.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(s);
+    }
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+static STYLE: &str = "
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; }
+th, td { padding: 0.3em 0.8em; text-align: left; vertical-align: top; }
+table.metadata th { color: #666; font-weight: normal; }
+#tests { width: 100%; margin-top: 1em; }
+#tests thead th { cursor: pointer; user-select: none; border-bottom: 2px solid #ccc; }
+#tests thead th[data-order=asc]::after { content: ' \\25b2'; }
+#tests thead th[data-order=desc]::after { content: ' \\25bc'; }
+#tests tbody tr { border-bottom: 1px solid #eee; }
+.controls input { width: 30em; }
+.passed { color: #1a7f37; font-weight: bold; }
+.flaky { color: #9a6700; font-weight: bold; }
+.failed { color: #cf222e; font-weight: bold; }
+.skipped, .not-run { color: #666; font-weight: bold; }
+.note { color: #666; font-style: italic; }
+details summary { cursor: pointer; }
+.attempt { margin: 0.5em 0 1em 1em; }
+.attempt h4, .attempt h5 { margin: 0.5em 0 0.2em; }
+pre { background: #f6f8fa; padding: 0.5em; overflow-x: auto; max-height: 40em; margin: 0; }
+";
+
+static SCRIPT: &str = "
+const table = document.getElementById('tests');
+const rows = Array.from(table.tBodies[0].rows);
+const filter = document.getElementById('filter');
+const status = document.getElementById('status');
+function applyFilter() {
+  const text = filter.value.toLowerCase();
+  for (const row of rows) {
+    row.hidden = (status.value && row.dataset.status !== status.value)
+      || !row.dataset.search.includes(text);
+  }
+}
+filter.addEventListener('input', applyFilter);
+status.addEventListener('change', applyFilter);
+const headers = Array.from(table.tHead.rows[0].cells);
+headers.forEach((header, index) => {
+  header.addEventListener('click', () => {
+    const ascending = header.dataset.order !== 'asc';
+    headers.forEach((other) => delete other.dataset.order);
+    header.dataset.order = ascending ? 'asc' : 'desc';
+    const numeric = header.dataset.type === 'number';
+    const key = (row) => {
+      const cell = row.cells[index];
+      const value = cell.dataset.value ?? cell.textContent;
+      return numeric ? parseFloat(value) : value;
+    };
+    rows.sort((a, b) => {
+      const order = numeric ? key(a) - key(b) : key(a).localeCompare(key(b));
+      return ascending ? order : -order;
+    });
+    table.tBodies[0].append(...rows);
+  });
+});
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("<b>\"Tom\" & 'Jerry'</b>"),
+            "&lt;b&gt;&quot;Tom&quot; &amp; &#39;Jerry&#39;&lt;/b&gt;"
+        );
+        assert!(matches!(escape("tests::foo"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_render_test() {
+        let test = TestRow {
+            binary_id: "my-crate".to_owned(),
+            name: "tests::<foo>".to_owned(),
+            status: RowStatus::Flaky,
+            label: "FLAKY".into(),
+            note: None,
+            time_taken: Some(Duration::from_millis(1500)),
+            attempts: vec![
+                AttemptRow {
+                    attempt: 1,
+                    total_attempts: 2,
+                    iteration: 1,
+                    label: "FAIL".into(),
+                    time_taken: Duration::from_millis(250),
+                    stdout: String::new(),
+                    stderr: "a < b".to_owned(),
+                },
+                AttemptRow {
+                    attempt: 2,
+                    total_attempts: 2,
+                    iteration: 1,
+                    label: "PASS".into(),
+                    time_taken: Duration::from_millis(1500),
+                    stdout: String::new(),
+                    stderr: String::new(),
+                },
+            ],
+        };
+        let mut out = String::new();
+        render_test(&mut out, &test);
+        assert_eq!(
+            out,
+            "<tr data-status=\"flaky\" data-search=\"my-crate tests::&lt;foo&gt;\">\
+             <td class=\"flaky\" data-value=\"1\">FLAKY</td>\
+             <td>my-crate</td>\
+             <td><details><summary>tests::&lt;foo&gt;</summary>\
+             <div class=\"attempt\"><h4>Attempt 1 of 2: FAIL after 0.250s</h4>\
+             <h5>stderr</h5><pre>a &lt; b</pre></div>\
+             <div class=\"attempt\"><h4>Attempt 2 of 2: PASS after 1.500s</h4>\
+             <p class=\"note\">No output was captured.</p></div>\
+             </details></td>\
+             <td data-value=\"1.5\">1.500s</td><td>2</td></tr>\n"
+        );
+    }
+}
@@ -0,0 +1,499 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Writes test events as JSON lines, for `--message-format json`.
+//!
+//! The format of each line is described by [`RunEventSummary`] in the nextest-metadata crate.
+
+use crate::{
+    errors::WriteEventError,
+    list::TestInstance,
+    reporter::{CancelReason, TestEvent},
+    runner::{
+        ExecuteStatus, ExecutionDescription, ExecutionResult, ExecutionStatuses, OutputStream,
+    },
+};
+use camino::Utf8PathBuf;
+use nextest_filtering::RunStatus;
+use nextest_metadata::{
+    CancelReasonSummary, OutputChunkSummary, OutputStreamSummary, PassRateSummary,
+    RunBeginCancelEventSummary, RunEventSummary, RunFinishedEventSummary, RunStartedEventSummary,
+    TestAttemptEventSummary, TestAttemptResult, TestAttemptSummary,
+    TestDeterminismCheckedEventSummary, TestFinishedEventSummary, TestFinishedStatus,
+    TestIdSummary, TestOutputEventSummary, TestPreviouslyFinishedEventSummary,
+    TestRetryEventSummary, TestSkippedEventSummary, TestSlowEventSummary, TestStartedEventSummary,
+    TestsAddedEventSummary,
+};
+use std::io::{self, Write};
+
+/// Writes a JSON line for each test event as it occurs.
+pub(crate) struct JsonReporter<W> {
+    writer: W,
+}
+
+impl JsonReporter<io::Stdout> {
+    /// Creates a new reporter that writes to standard output.
+    pub(crate) fn stdout() -> Self {
+        Self {
+            writer: io::stdout(),
+        }
+    }
+}
+
+impl<W: Write> JsonReporter<W> {
+    pub(crate) fn write_event(&mut self, event: &TestEvent<'_>) -> Result<(), WriteEventError> {
+        let mut line =
+            serde_json::to_vec(&event_summary(event)).expect("serializing to a Vec doesn't fail");
+        line.push(b'\n');
+        // Write out each line as soon as it's produced, so that programs reading the output can
+        // follow along with the run.
+        self.writer.write_all(&line).map_err(WriteEventError::Io)?;
+        self.writer.flush().map_err(WriteEventError::Io)
+    }
+}
+
+fn event_summary(event: &TestEvent<'_>) -> RunEventSummary {
+    match event {
+        TestEvent::RunStarted {
+            test_list,
+            run_id,
+            iterations,
+            shuffle_seed,
+            seed,
+            previously_finished,
+            listing,
+            ..
+        } => RunEventSummary::RunStarted(RunStartedEventSummary {
+            run_id: run_id.to_string(),
+            test_count: test_list.run_count(),
+            iterations: *iterations,
+            shuffle_seed: *shuffle_seed,
+            seed: *seed,
+            previously_finished: *previously_finished,
+            listing: *listing,
+        }),
+        TestEvent::TestsAdded { test_list } => {
+            RunEventSummary::TestsAdded(TestsAddedEventSummary {
+                test_count: test_list.run_count(),
+                binary_ids: test_list
+                    .iter()
+                    .map(|(_, info)| info.binary_id.clone())
+                    .collect(),
+            })
+        }
+        TestEvent::TestStarted {
+            test_instance,
+            running,
+            ..
+        } => RunEventSummary::TestStarted(TestStartedEventSummary {
+            test: test_id(test_instance),
+            running: *running,
+        }),
+        TestEvent::TestSlow {
+            test_instance,
+            elapsed,
+        } => RunEventSummary::TestSlow(TestSlowEventSummary {
+            test: test_id(test_instance),
+            elapsed: elapsed.as_secs_f64(),
+        }),
+        TestEvent::TestOutput {
+            test_instance,
+            stream,
+            output,
+            elapsed,
+        } => RunEventSummary::TestOutput(TestOutputEventSummary {
+            test: test_id(test_instance),
+            stream: output_stream(*stream),
+            output: String::from_utf8_lossy(output).into_owned(),
+            elapsed: elapsed.as_secs_f64(),
+        }),
+        TestEvent::TestRetry {
+            test_instance,
+            run_status,
+            delay,
+            deferred,
+        } => RunEventSummary::TestRetry(TestRetryEventSummary {
+            test: test_id(test_instance),
+            attempt: attempt_summary(run_status),
+            delay: delay.as_secs_f64(),
+            deferred: *deferred,
+        }),
+        TestEvent::TestIsolationChecked {
+            test_instance,
+            run_status,
+        } => RunEventSummary::TestIsolationChecked(TestAttemptEventSummary {
+            test: test_id(test_instance),
+            attempt: attempt_summary(run_status),
+        }),
+        TestEvent::TestDeterminismChecked {
+            test_instance,
+            run_status,
+            difference,
+        } => RunEventSummary::TestDeterminismChecked(TestDeterminismCheckedEventSummary {
+            test: test_id(test_instance),
+            attempt: attempt_summary(run_status),
+            nondeterministic: difference.is_some(),
+        }),
+        TestEvent::TestFinished {
+            test_instance,
+            run_statuses,
+            quarantined,
+            ..
+        } => RunEventSummary::TestFinished(TestFinishedEventSummary {
+            test: test_id(test_instance),
+            status: finished_status(run_statuses),
+            quarantined: *quarantined,
+            attempts: run_statuses.iter().map(attempt_summary).collect(),
+        }),
+        TestEvent::TestPreviouslyFinished {
+            test_instance,
+            status,
+            time_taken,
+            ..
+        } => RunEventSummary::TestPreviouslyFinished(TestPreviouslyFinishedEventSummary {
+            test: test_id(test_instance),
+            status: match status {
+                RunStatus::Flaky => TestFinishedStatus::Flaky,
+                RunStatus::Failed => TestFinishedStatus::Failed,
+                RunStatus::Passed | RunStatus::Skipped => TestFinishedStatus::Passed,
+            },
+            time_taken: time_taken.as_secs_f64(),
+        }),
+        TestEvent::TestSkipped {
+            test_instance,
+            reason,
+        } => RunEventSummary::TestSkipped(TestSkippedEventSummary {
+            test: test_id(test_instance),
+            reason: *reason,
+        }),
+        TestEvent::TestNotRun { test_instance } => {
+            RunEventSummary::TestNotRun(test_id(test_instance))
+        }
+        TestEvent::RunBeginCancel { running, reason } => {
+            RunEventSummary::RunBeginCancel(RunBeginCancelEventSummary {
+                running: *running,
+                reason: match reason {
+                    CancelReason::TestFailure => CancelReasonSummary::TestFailure,
+                    CancelReason::BuildFailure => CancelReasonSummary::BuildFailure,
+                    CancelReason::RunTimeBudget => CancelReasonSummary::RunTimeBudget,
+                    CancelReason::ReportError => CancelReasonSummary::ReportError,
+                    CancelReason::Signal => CancelReasonSummary::Signal,
+                    CancelReason::Interrupt => CancelReasonSummary::Interrupt,
+                },
+            })
+        }
+        TestEvent::RunFinished {
+            run_id,
+            elapsed,
+            run_stats,
+            pass_rates,
+            ..
+        } => RunEventSummary::RunFinished(RunFinishedEventSummary {
+            run_id: run_id.to_string(),
+            elapsed: elapsed.as_secs_f64(),
+            success: run_stats.is_success(),
+            initial_run_count: run_stats.initial_run_count,
+            finished_count: run_stats.finished_count,
+            passed: run_stats.passed,
+            flaky: run_stats.flaky,
+            failed: run_stats.failed,
+            timed_out: run_stats.timed_out,
+            leaky: run_stats.leaky,
+            exec_failed: run_stats.exec_failed,
+            skipped: run_stats.skipped,
+            quarantined_failed: run_stats.quarantined_failed,
+            pass_rates: pass_rates
+                .iter()
+                .map(|check| PassRateSummary {
+                    test_group: check.test_group.clone(),
+                    required: check.required,
+                    passed: check.passed,
+                    failed: check.failed,
+                })
+                .collect(),
+        }),
+    }
+}
+
+fn test_id(test_instance: &TestInstance<'_>) -> TestIdSummary {
+    TestIdSummary {
+        binary_id: test_instance.bin_info.binary_id.clone(),
+        name: test_instance.name.to_owned(),
+    }
+}
+
+fn finished_status(run_statuses: &ExecutionStatuses) -> TestFinishedStatus {
+    match run_statuses.describe() {
+        ExecutionDescription::Success { .. } => TestFinishedStatus::Passed,
+        ExecutionDescription::Flaky { .. } => TestFinishedStatus::Flaky,
+        ExecutionDescription::Failure { .. } => TestFinishedStatus::Failed,
+    }
+}
+
+fn output_stream(stream: OutputStream) -> OutputStreamSummary {
+    match stream {
+        OutputStream::Stdout => OutputStreamSummary::Stdout,
+        OutputStream::Stderr => OutputStreamSummary::Stderr,
+    }
+}
+
+fn attempt_summary(status: &ExecuteStatus) -> TestAttemptSummary {
+    let result = match status.result {
+        ExecutionResult::Pass => TestAttemptResult::Pass,
+        ExecutionResult::Leak => TestAttemptResult::Leak,
+        ExecutionResult::Fail { .. } => TestAttemptResult::Fail,
+        ExecutionResult::LeakFail => TestAttemptResult::LeakFail,
+        ExecutionResult::ExecFail => TestAttemptResult::ExecFail,
+        ExecutionResult::Timeout => TestAttemptResult::Timeout,
+        ExecutionResult::Hang => TestAttemptResult::Hang,
+        ExecutionResult::MemoryLimitExceeded => TestAttemptResult::MemoryLimitExceeded,
+        ExecutionResult::ExpectedFailure => TestAttemptResult::ExpectedFailure,
+        ExecutionResult::UnexpectedPass => TestAttemptResult::UnexpectedPass,
+        ExecutionResult::UnexpectedPassFail => TestAttemptResult::UnexpectedPassFail,
+    };
+    let signal = match status.result {
+        #[cfg(unix)]
+        ExecutionResult::Fail {
+            abort_status: Some(crate::runner::AbortStatus::UnixSignal(signal)),
+            ..
+        } => Some(signal),
+        _ => None,
+    };
+    TestAttemptSummary {
+        attempt: status.attempt,
+        total_attempts: status.total_attempts,
+        iteration: status.iteration,
+        result,
+        exit_code: status.exit_code,
+        signal,
+        time_taken: status.time_taken.as_secs_f64(),
+        slow: status.is_slow,
+        test_seed: status.test_seed,
+        stdout: String::from_utf8_lossy(&status.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&status.stderr).into_owned(),
+        output_chunks: status
+            .output_chunks
+            .iter()
+            .map(|chunk| OutputChunkSummary {
+                stream: output_stream(chunk.stream),
+                start: chunk.range.start,
+                end: chunk.range.end,
+                elapsed: chunk.elapsed.as_secs_f64(),
+            })
+            .collect(),
+        leaked_pids: status
+            .leaked_processes
+            .iter()
+            .map(|process| process.pid)
+            .collect(),
+        kept_tmpdir: status
+            .kept_tmpdir
+            .as_ref()
+            .map(|path| Utf8PathBuf::from(path.to_string_lossy().into_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        list::TestList,
+        runner::{OutputDifference, PassRateCheck, RunStats},
+        test_helpers::{fake_status, fake_suite, fake_test_case},
+    };
+    use bytes::Bytes;
+    use nextest_metadata::{BuildPlatform, MismatchReason};
+    use std::time::{Duration, SystemTime};
+    use uuid::Uuid;
+
+    static FAILED_ATTEMPT_JSON: &str = concat!(
+        r#"{"attempt":1,"total-attempts":2,"iteration":1,"result":"fail","exit-code":null,"#,
+        r#""signal":null,"time-taken":0.1,"slow":false,"test-seed":0,"stdout":"","#,
+        r#""stderr":"boom\n","output-chunks":[],"leaked-pids":[],"kept-tmpdir":null}"#,
+    );
+    static PASSED_ATTEMPT_JSON: &str = concat!(
+        r#"{"attempt":2,"total-attempts":2,"iteration":1,"result":"pass","exit-code":null,"#,
+        r#""signal":null,"time-taken":0.1,"slow":false,"test-seed":0,"stdout":"","#,
+        r#""stderr":"","output-chunks":[],"leaked-pids":[],"kept-tmpdir":null}"#,
+    );
+    static TEST_ID_JSON: &str = r#""binary-id":"my-crate","name":"tests::foo""#;
+
+    #[test]
+    fn test_json_lines() {
+        let suite = fake_suite("my-crate", BuildPlatform::Target);
+        let test_case = fake_test_case();
+        let test_instance = TestInstance::new("tests::foo", "/fake/binary", &suite, &test_case);
+        let fail = ExecutionResult::Fail {
+            abort_status: None,
+            leaked: false,
+        };
+        let failed_attempt = || fake_status(1, 2, fail, "boom\n");
+        let passed_attempt = fake_status(2, 2, ExecutionResult::Pass, "");
+        let test_list = TestList::empty();
+        let run_id = Uuid::from_u128(1);
+
+        let events = [
+            TestEvent::RunStarted {
+                test_list: &test_list,
+                run_id,
+                iterations: Some(1),
+                until_failure: false,
+                shuffle_seed: Some(5),
+                seed: 42,
+                previously_finished: None,
+                listing: true,
+            },
+            TestEvent::TestsAdded {
+                test_list: &test_list,
+            },
+            TestEvent::TestStarted {
+                test_instance,
+                current_stats: RunStats::default(),
+                running: 1,
+                cancel_state: None,
+            },
+            TestEvent::TestSlow {
+                test_instance,
+                elapsed: Duration::from_secs(60),
+            },
+            TestEvent::TestOutput {
+                test_instance,
+                stream: OutputStream::Stderr,
+                output: Bytes::from_static(b"boom\n"),
+                elapsed: Duration::from_millis(500),
+            },
+            TestEvent::TestRetry {
+                test_instance,
+                run_status: failed_attempt(),
+                delay: Duration::from_millis(1500),
+                deferred: true,
+            },
+            TestEvent::TestIsolationChecked {
+                test_instance,
+                run_status: failed_attempt(),
+            },
+            TestEvent::TestDeterminismChecked {
+                test_instance,
+                run_status: failed_attempt(),
+                difference: Some(OutputDifference::Failed),
+            },
+            TestEvent::TestFinished {
+                test_instance,
+                run_statuses: ExecutionStatuses::new(vec![failed_attempt(), passed_attempt]),
+                quarantined: true,
+                current_stats: RunStats::default(),
+                running: 0,
+                cancel_state: None,
+            },
+            TestEvent::TestPreviouslyFinished {
+                test_instance,
+                status: RunStatus::Skipped,
+                time_taken: Duration::from_millis(250),
+                current_stats: RunStats::default(),
+            },
+            TestEvent::TestSkipped {
+                test_instance,
+                reason: MismatchReason::Ignored,
+            },
+            TestEvent::TestNotRun { test_instance },
+            TestEvent::RunBeginCancel {
+                running: 1,
+                reason: CancelReason::Interrupt,
+            },
+            TestEvent::RunFinished {
+                run_id,
+                start_time: SystemTime::UNIX_EPOCH,
+                elapsed: Duration::from_millis(12500),
+                run_stats: RunStats {
+                    initial_run_count: 2,
+                    finished_count: 1,
+                    passed: 1,
+                    flaky: 1,
+                    ..RunStats::default()
+                },
+                pass_rates: vec![PassRateCheck {
+                    test_group: None,
+                    required: 0.5,
+                    passed: 1,
+                    failed: 0,
+                }],
+            },
+        ];
+
+        let mut reporter = JsonReporter { writer: Vec::new() };
+        for event in &events {
+            reporter.write_event(event).expect("event written");
+        }
+        let output = String::from_utf8(reporter.writer).expect("output is UTF-8");
+
+        let expected = [
+            concat!(
+                r#"{"type":"run-started","run-id":"00000000-0000-0000-0000-000000000001","#,
+                r#""test-count":0,"iterations":1,"shuffle-seed":5,"seed":42,"#,
+                r#""previously-finished":null,"listing":true}"#,
+            )
+            .to_owned(),
+            r#"{"type":"tests-added","test-count":0,"binary-ids":[]}"#.to_owned(),
+            format!(r#"{{"type":"test-started",{TEST_ID_JSON},"running":1}}"#),
+            format!(r#"{{"type":"test-slow",{TEST_ID_JSON},"elapsed":60.0}}"#),
+            format!(
+                r#"{{"type":"test-output",{TEST_ID_JSON},"stream":"stderr","output":"boom\n","elapsed":0.5}}"#
+            ),
+            format!(
+                r#"{{"type":"test-retry",{TEST_ID_JSON},"attempt":{FAILED_ATTEMPT_JSON},"delay":1.5,"deferred":true}}"#
+            ),
+            format!(
+                r#"{{"type":"test-isolation-checked",{TEST_ID_JSON},"attempt":{FAILED_ATTEMPT_JSON}}}"#
+            ),
+            format!(
+                r#"{{"type":"test-determinism-checked",{TEST_ID_JSON},"attempt":{FAILED_ATTEMPT_JSON},"nondeterministic":true}}"#
+            ),
+            format!(
+                r#"{{"type":"test-finished",{TEST_ID_JSON},"status":"flaky","quarantined":true,"attempts":[{FAILED_ATTEMPT_JSON},{PASSED_ATTEMPT_JSON}]}}"#
+            ),
+            // Tests that were skipped in the interrupted run are reported as having passed.
+            format!(
+                r#"{{"type":"test-previously-finished",{TEST_ID_JSON},"status":"passed","time-taken":0.25}}"#
+            ),
+            format!(r#"{{"type":"test-skipped",{TEST_ID_JSON},"reason":"ignored"}}"#),
+            format!(r#"{{"type":"test-not-run",{TEST_ID_JSON}}}"#),
+            r#"{"type":"run-begin-cancel","running":1,"reason":"interrupt"}"#.to_owned(),
+            // The run isn't a success, since not every test that was expected to be run finished.
+            concat!(
+                r#"{"type":"run-finished","run-id":"00000000-0000-0000-0000-000000000001","#,
+                r#""elapsed":12.5,"success":false,"initial-run-count":2,"finished-count":1,"#,
+                r#""passed":1,"flaky":1,"failed":0,"timed-out":0,"leaky":0,"exec-failed":0,"#,
+                r#""skipped":0,"quarantined-failed":0,"pass-rates":[{"test-group":null,"#,
+                r#""required":0.5,"passed":1,"failed":0}]}"#,
+            )
+            .to_owned(),
+        ];
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), expected.len(), "one line per event");
+        for (line, expected) in lines.iter().zip(&expected) {
+            assert_eq!(line, expected);
+        }
+        // Each line can be read back with nextest-metadata.
+        for line in lines {
+            serde_json::from_str::<RunEventSummary>(line).expect("line deserialized");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_attempt_signal() {
+        let status = fake_status(
+            1,
+            1,
+            ExecutionResult::Fail {
+                abort_status: Some(crate::runner::AbortStatus::UnixSignal(9)),
+                leaked: false,
+            },
+            "",
+        );
+        let summary = attempt_summary(&status);
+        assert_eq!(summary.result, TestAttemptResult::Fail);
+        assert_eq!(summary.signal, Some(9));
+        assert_eq!(summary.exit_code, None);
+    }
+}
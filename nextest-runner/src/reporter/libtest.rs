@@ -0,0 +1,498 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Writes test events in the JSON format libtest produces with `--format json`.
+//!
+//! libtest runs one binary at a time, so tools that read its output expect each binary's events to
+//! be grouped together into a suite. Since nextest runs tests from several binaries at once, events
+//! are held on to until every test in a binary has finished, and then written out together.
+
+use crate::{
+    errors::WriteEventError,
+    list::{TestInstance, TestList},
+    reporter::TestEvent,
+    runner::{ExecuteStatus, ExecutionResult},
+};
+use nextest_filtering::RunStatus;
+use nextest_metadata::MismatchReason;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+/// Writes libtest-compatible JSON lines for each test binary.
+pub(crate) struct LibtestReporter<'a, W> {
+    writer: W,
+    suites: BTreeMap<&'a str, LibtestSuite>,
+    // The number of times each test is run, or None if tests are run until one fails.
+    iterations: Option<usize>,
+}
+
+impl<'a> LibtestReporter<'a, io::Stdout> {
+    /// Creates a new reporter that writes to standard output.
+    pub(crate) fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<'a, W: Write> LibtestReporter<'a, W> {
+    pub(crate) fn new(writer: W) -> Self {
+        Self {
+            writer,
+            suites: BTreeMap::new(),
+            iterations: Some(1),
+        }
+    }
+
+    pub(crate) fn write_event(&mut self, event: &TestEvent<'a>) -> Result<(), WriteEventError> {
+        match event {
+            TestEvent::RunStarted {
+                test_list,
+                iterations,
+                ..
+            } => {
+                self.iterations = *iterations;
+                self.add_suites(test_list);
+            }
+            TestEvent::TestsAdded { test_list } => {
+                self.add_suites(test_list);
+            }
+            TestEvent::TestStarted { test_instance, .. } => {
+                let suite = self.suite(test_instance);
+                suite.start_time.get_or_insert_with(Instant::now);
+                suite.push(TestLine::new("started", test_instance.name));
+            }
+            TestEvent::TestSlow { test_instance, .. } => {
+                self.suite(test_instance)
+                    .push(TestLine::new("timeout", test_instance.name));
+            }
+            TestEvent::TestFinished {
+                test_instance,
+                run_statuses,
+                ..
+            } => {
+                let last_status = run_statuses.last_status();
+                let suite = self.suite(test_instance);
+                if last_status.result.is_success() {
+                    suite.passed += 1;
+                    suite.push(TestLine::with_time("ok", test_instance.name, last_status));
+                } else {
+                    suite.failed += 1;
+                    let mut line = TestLine::with_time("failed", test_instance.name, last_status);
+                    // As with libtest, both streams are reported together, in the order they
+                    // were written in.
+                    let output: Vec<u8> = last_status
+                        .interleaved_output()
+                        .flat_map(|(_, output)| output.iter().copied())
+                        .collect();
+                    line.stdout = Some(String::from_utf8_lossy(&output).into_owned());
+                    if last_status.result == ExecutionResult::Timeout {
+                        line.reason = Some("time limit exceeded");
+                    }
+                    suite.push(line);
+                }
+                self.finish_test(test_instance)?;
+            }
+            TestEvent::TestPreviouslyFinished {
+                test_instance,
+                status,
+                time_taken,
+                ..
+            } => {
+                let suite = self.suite(test_instance);
+                let event = match status {
+                    RunStatus::Failed => {
+                        suite.failed += 1;
+                        "failed"
+                    }
+                    _ => {
+                        suite.passed += 1;
+                        "ok"
+                    }
+                };
+                suite.push(TestLine::new("started", test_instance.name));
+                let mut line = TestLine::new(event, test_instance.name);
+                line.exec_time = Some(time_taken.as_secs_f64());
+                suite.push(line);
+                self.finish_test(test_instance)?;
+            }
+            TestEvent::TestSkipped {
+                test_instance,
+                reason,
+            } => {
+                let suite = self.suite(test_instance);
+                if *reason == MismatchReason::Ignored {
+                    suite.ignored += 1;
+                    suite.push(TestLine::new("started", test_instance.name));
+                    let mut line = TestLine::new("ignored", test_instance.name);
+                    line.message = test_instance.test_info.ignore_reason.clone();
+                    suite.push(line);
+                }
+                self.finish_test(test_instance)?;
+            }
+            TestEvent::TestNotRun { test_instance } => {
+                self.finish_test(test_instance)?;
+            }
+            TestEvent::RunFinished { .. } => {
+                // Suites that are still around had tests that didn't finish, for example because
+                // the run was canceled.
+                let binary_ids: Vec<_> = self.suites.keys().copied().collect();
+                for binary_id in binary_ids {
+                    self.flush(binary_id)?;
+                }
+            }
+            TestEvent::TestOutput { .. }
+            | TestEvent::TestRetry { .. }
+            | TestEvent::TestIsolationChecked { .. }
+            | TestEvent::TestDeterminismChecked { .. }
+            | TestEvent::RunBeginCancel { .. } => {}
+        }
+        Ok(())
+    }
+
+    fn add_suites(&mut self, test_list: &'a TestList<'a>) {
+        let iterations = self.iterations;
+        for (_, test_suite) in test_list.iter() {
+            if test_suite.status.test_count() == 0 {
+                continue;
+            }
+            let mut expected = 0;
+            for (_, test_info) in test_suite.status.test_cases() {
+                // Skipped tests are only reported once, however many iterations there are.
+                expected += match test_info.filter_match.is_match() {
+                    true => iterations,
+                    false => Some(1),
+                }
+                .unwrap_or(0);
+            }
+            // With --until-failure, the number of times tests are run isn't known ahead of time,
+            // so suites are only written out once the run finishes.
+            let expected = iterations.map(|_| expected);
+            self.suites.insert(
+                test_suite.binary_id.as_str(),
+                LibtestSuite::new(expected, test_suite.status.test_count()),
+            );
+        }
+    }
+
+    fn suite(&mut self, test_instance: &TestInstance<'a>) -> &mut LibtestSuite {
+        self.suites
+            .entry(test_instance.bin_info.binary_id.as_str())
+            .or_insert_with(|| LibtestSuite::new(None, 0))
+    }
+
+    fn finish_test(&mut self, test_instance: &TestInstance<'a>) -> Result<(), WriteEventError> {
+        let binary_id = test_instance.bin_info.binary_id.as_str();
+        let suite = self.suite(test_instance);
+        suite.finished += 1;
+        if suite
+            .expected
+            .map_or(false, |expected| suite.finished >= expected)
+        {
+            self.flush(binary_id)?;
+        }
+        Ok(())
+    }
+
+    /// Writes out the events for a binary, surrounded by the start and end of its suite.
+    fn flush(&mut self, binary_id: &str) -> Result<(), WriteEventError> {
+        let suite = match self.suites.remove(binary_id) {
+            Some(suite) => suite,
+            None => return Ok(()),
+        };
+        let mut out = Vec::new();
+        let test_count = suite.passed + suite.failed + suite.ignored;
+        // A suite whose tests were all filtered out isn't shown, as with cargo test.
+        if test_count == 0 && suite.lines.is_empty() {
+            return Ok(());
+        }
+        write_line(
+            &mut out,
+            &SuiteLine {
+                ty: "suite",
+                event: "started",
+                test_count: Some(test_count),
+                ..SuiteLine::default()
+            },
+        );
+        out.extend_from_slice(&suite.lines);
+        // Tests that were filtered out aren't reported individually, and neither are tests that
+        // weren't run at all, for example because the run was canceled.
+        let filtered_out = suite.total.saturating_sub(test_count);
+        write_line(
+            &mut out,
+            &SuiteLine {
+                ty: "suite",
+                event: if suite.failed == 0 { "ok" } else { "failed" },
+                passed: Some(suite.passed),
+                failed: Some(suite.failed),
+                ignored: Some(suite.ignored),
+                measured: Some(0),
+                filtered_out: Some(filtered_out),
+                exec_time: Some(
+                    suite
+                        .start_time
+                        .map_or(Duration::ZERO, |start_time| start_time.elapsed())
+                        .as_secs_f64(),
+                ),
+                ..SuiteLine::default()
+            },
+        );
+        self.writer.write_all(&out).map_err(WriteEventError::Io)?;
+        self.writer.flush().map_err(WriteEventError::Io)
+    }
+}
+
+/// The events for one test binary that haven't been written out yet.
+struct LibtestSuite {
+    /// The number of events that finish a test to expect before the suite is written out, or None
+    /// to wait until the run finishes.
+    expected: Option<usize>,
+    finished: usize,
+    /// The number of tests in the binary.
+    total: usize,
+    passed: usize,
+    failed: usize,
+    ignored: usize,
+    start_time: Option<Instant>,
+    lines: Vec<u8>,
+}
+
+impl LibtestSuite {
+    fn new(expected: Option<usize>, total: usize) -> Self {
+        Self {
+            expected,
+            finished: 0,
+            total,
+            passed: 0,
+            failed: 0,
+            ignored: 0,
+            start_time: None,
+            lines: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, line: TestLine<'_>) {
+        write_line(&mut self.lines, &line);
+    }
+}
+
+fn write_line(out: &mut Vec<u8>, line: &impl Serialize) {
+    serde_json::to_writer(&mut *out, line).expect("serializing to a Vec doesn't fail");
+    out.push(b'\n');
+}
+
+#[derive(Serialize)]
+struct TestLine<'a> {
+    #[serde(rename = "type")]
+    ty: &'static str,
+    event: &'static str,
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exec_time: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stdout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+impl<'a> TestLine<'a> {
+    fn new(event: &'static str, name: &'a str) -> Self {
+        Self {
+            ty: "test",
+            event,
+            name,
+            exec_time: None,
+            stdout: None,
+            reason: None,
+            message: None,
+        }
+    }
+
+    fn with_time(event: &'static str, name: &'a str, status: &ExecuteStatus) -> Self {
+        Self {
+            exec_time: Some(status.time_taken.as_secs_f64()),
+            ..Self::new(event, name)
+        }
+    }
+}
+
+#[derive(Default, Serialize)]
+struct SuiteLine {
+    #[serde(rename = "type")]
+    ty: &'static str,
+    event: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    test_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    passed: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failed: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ignored: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    measured: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filtered_out: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exec_time: Option<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        runner::{ExecutionStatuses, OutputChunk, OutputStream, RunStats},
+        test_helpers::{fake_status, fake_suite, fake_test_case},
+    };
+    use bytes::Bytes;
+    use nextest_metadata::{BuildPlatform, RustTestCaseSummary};
+    use std::time::SystemTime;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_libtest_lines() {
+        let failing_suite = fake_suite("failing", BuildPlatform::Target);
+        let passing_suite = fake_suite("passing", BuildPlatform::Target);
+        let test_case = fake_test_case();
+        let ignored_test_case = RustTestCaseSummary {
+            ignored: true,
+            ignore_reason: Some("slow".to_owned()),
+            ..fake_test_case()
+        };
+        let passes =
+            TestInstance::new("tests::passes", "/fake/passing", &passing_suite, &test_case);
+        let ignored = TestInstance::new(
+            "tests::ignored",
+            "/fake/passing",
+            &passing_suite,
+            &ignored_test_case,
+        );
+        let fails = TestInstance::new("tests::fails", "/fake/failing", &failing_suite, &test_case);
+
+        let mut fail_status = fake_status(
+            1,
+            1,
+            ExecutionResult::Fail {
+                abort_status: None,
+                leaked: false,
+            },
+            "boom\n",
+        );
+        fail_status.stdout = Bytes::from_static(b"running\n");
+        fail_status.output_chunks = vec![
+            OutputChunk {
+                stream: OutputStream::Stdout,
+                range: 0..8,
+                elapsed: Duration::ZERO,
+            },
+            OutputChunk {
+                stream: OutputStream::Stderr,
+                range: 0..5,
+                elapsed: Duration::from_millis(50),
+            },
+        ];
+
+        let started = |test_instance| TestEvent::TestStarted {
+            test_instance,
+            current_stats: RunStats::default(),
+            running: 1,
+            cancel_state: None,
+        };
+        let finished = |test_instance, status| TestEvent::TestFinished {
+            test_instance,
+            run_statuses: ExecutionStatuses::new(vec![status]),
+            quarantined: false,
+            current_stats: RunStats::default(),
+            running: 0,
+            cancel_state: None,
+        };
+        let events = [
+            started(passes),
+            started(fails),
+            finished(passes, fake_status(1, 1, ExecutionResult::Pass, "")),
+            TestEvent::TestSkipped {
+                test_instance: ignored,
+                reason: MismatchReason::Ignored,
+            },
+            finished(fails, fail_status),
+            TestEvent::RunFinished {
+                run_id: Uuid::new_v4(),
+                start_time: SystemTime::UNIX_EPOCH,
+                elapsed: Duration::from_secs(1),
+                run_stats: RunStats::default(),
+                pass_rates: vec![],
+            },
+        ];
+
+        let mut reporter = LibtestReporter::new(Vec::new());
+        for event in &events {
+            reporter.write_event(event).expect("event written");
+        }
+        let output = String::from_utf8(reporter.writer).expect("output is UTF-8");
+        let lines: Vec<_> = output.lines().collect();
+
+        // Suites are written out one after the other, in order of binary ID.
+        let expected = [
+            r#"{"type":"suite","event":"started","test_count":1}"#,
+            r#"{"type":"test","event":"started","name":"tests::fails"}"#,
+            r#"{"type":"test","event":"failed","name":"tests::fails","exec_time":0.1,"stdout":"running\nboom\n"}"#,
+            r#"{"type":"suite","event":"failed","passed":0,"failed":1,"ignored":0,"measured":0,"filtered_out":0,"exec_time":"#,
+            r#"{"type":"suite","event":"started","test_count":2}"#,
+            r#"{"type":"test","event":"started","name":"tests::passes"}"#,
+            r#"{"type":"test","event":"ok","name":"tests::passes","exec_time":0.1}"#,
+            r#"{"type":"test","event":"started","name":"tests::ignored"}"#,
+            r#"{"type":"test","event":"ignored","name":"tests::ignored","message":"slow"}"#,
+            r#"{"type":"suite","event":"ok","passed":1,"failed":0,"ignored":1,"measured":0,"filtered_out":0,"exec_time":"#,
+        ];
+        assert_eq!(lines.len(), expected.len(), "output: {output}");
+        for (line, expected) in lines.iter().zip(expected) {
+            // How long a suite took to run depends on the clock, so only the start of the suite's
+            // last line is checked.
+            if expected.ends_with(r#""exec_time":"#) {
+                assert!(line.starts_with(expected), "{line} starts with {expected}");
+            } else {
+                assert_eq!(*line, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_libtest_timeout() {
+        let suite = fake_suite("timing-out", BuildPlatform::Target);
+        let test_case = fake_test_case();
+        let test_instance = TestInstance::new("tests::hangs", "/fake/binary", &suite, &test_case);
+
+        let mut reporter = LibtestReporter::new(Vec::new());
+        reporter
+            .write_event(&TestEvent::TestFinished {
+                test_instance,
+                run_statuses: ExecutionStatuses::new(vec![fake_status(
+                    1,
+                    1,
+                    ExecutionResult::Timeout,
+                    "",
+                )]),
+                quarantined: false,
+                current_stats: RunStats::default(),
+                running: 0,
+                cancel_state: None,
+            })
+            .expect("event written");
+        // Nothing is written until the suite is done.
+        assert!(reporter.writer.is_empty());
+        reporter.flush("timing-out").expect("suite written");
+
+        let output = String::from_utf8(reporter.writer).expect("output is UTF-8");
+        assert_eq!(
+            output.lines().nth(1),
+            Some(
+                r#"{"type":"test","event":"failed","name":"tests::hangs","exec_time":0.1,"stdout":"","reason":"time limit exceeded"}"#
+            ),
+        );
+    }
+}
@@ -0,0 +1,311 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Writes a Markdown summary of a run, for `--summary-markdown`.
+//!
+//! The summary is meant to be read on a CI system's web interface, for example as a [GitHub
+//! Actions job summary](https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions#adding-a-job-summary).
+//! It's appended to the given file once the run finishes, since several steps may write to the
+//! same summary.
+
+use crate::{
+    errors::WriteEventError,
+    list::TestInstance,
+    reporter::{status_str, TestEvent},
+    runner::{ExecuteStatus, ExecutionDescription, RunStats},
+};
+use camino::Utf8PathBuf;
+use nextest_filtering::RunStatus;
+use std::{borrow::Cow, fmt::Write as _, fs::OpenOptions, io::Write, time::Duration};
+
+/// The number of slowest tests listed in the summary.
+const SLOWEST_COUNT: usize = 10;
+
+/// The number of lines of output shown for each test, counting from the end.
+const OUTPUT_LINES: usize = 100;
+
+/// Collects failed, flaky and slow tests, and appends a summary of them to a file at the end of the
+/// run.
+pub(crate) struct MarkdownSummary {
+    path: Utf8PathBuf,
+    failed: Vec<SummaryEntry>,
+    flaky: Vec<SummaryEntry>,
+    durations: Vec<(String, Duration)>,
+}
+
+struct SummaryEntry {
+    name: String,
+    label: Cow<'static, str>,
+    time_taken: Duration,
+    attempts: usize,
+    output: Option<String>,
+}
+
+impl MarkdownSummary {
+    pub(crate) fn new(path: Utf8PathBuf) -> Self {
+        Self {
+            path,
+            failed: Vec::new(),
+            flaky: Vec::new(),
+            durations: Vec::new(),
+        }
+    }
+
+    pub(crate) fn write_event(&mut self, event: &TestEvent<'_>) -> Result<(), WriteEventError> {
+        match event {
+            TestEvent::TestFinished {
+                test_instance,
+                run_statuses,
+                quarantined,
+                ..
+            } => {
+                let name = test_name(test_instance);
+                let last_status = run_statuses.last_status();
+                self.durations.push((name.clone(), last_status.time_taken));
+                match run_statuses.describe() {
+                    ExecutionDescription::Success { .. } => {}
+                    ExecutionDescription::Flaky { prior_statuses, .. } => {
+                        // Show the output of the first failure, which is usually the most
+                        // interesting one.
+                        self.flaky.push(SummaryEntry {
+                            name,
+                            label: "FLAKY".into(),
+                            time_taken: last_status.time_taken,
+                            attempts: run_statuses.len(),
+                            output: Some(combined_output(&prior_statuses[0])),
+                        });
+                    }
+                    ExecutionDescription::Failure { .. } => {
+                        let mut label = status_str(last_status.result);
+                        if *quarantined {
+                            label = format!("{label} (quarantined)").into();
+                        }
+                        self.failed.push(SummaryEntry {
+                            name,
+                            label,
+                            time_taken: last_status.time_taken,
+                            attempts: run_statuses.len(),
+                            output: Some(combined_output(last_status)),
+                        });
+                    }
+                }
+            }
+            TestEvent::TestPreviouslyFinished {
+                test_instance,
+                status: RunStatus::Failed,
+                time_taken,
+                ..
+            } => {
+                // The output of tests that finished before the run was resumed isn't known.
+                self.failed.push(SummaryEntry {
+                    name: test_name(test_instance),
+                    label: "FAIL (resumed)".into(),
+                    time_taken: *time_taken,
+                    attempts: 1,
+                    output: None,
+                });
+            }
+            TestEvent::RunFinished {
+                elapsed, run_stats, ..
+            } => {
+                let summary = self.render(run_stats, *elapsed);
+                let write = || {
+                    OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(&self.path)?
+                        .write_all(summary.as_bytes())
+                };
+                write().map_err(|error| WriteEventError::Fs {
+                    file: self.path.clone(),
+                    error,
+                })?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn render(&mut self, run_stats: &RunStats, elapsed: Duration) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "## nextest run {}\n",
+            if run_stats.is_success() {
+                "passed"
+            } else {
+                "failed"
+            }
+        );
+        let _ = write!(
+            out,
+            "**{} {} run in {}:** {} passed",
+            run_stats.finished_count,
+            if run_stats.finished_count == 1 {
+                "test"
+            } else {
+                "tests"
+            },
+            format_duration(elapsed),
+            run_stats.passed,
+        );
+        for (count, kind) in [
+            (run_stats.flaky, "flaky"),
+            (run_stats.failed, "failed"),
+            (run_stats.timed_out, "timed out"),
+            (run_stats.leaky, "leaky"),
+            (run_stats.exec_failed, "exec failed"),
+            (run_stats.skipped, "skipped"),
+        ] {
+            if count > 0 {
+                let _ = write!(out, ", {count} {kind}");
+            }
+        }
+        if run_stats.finished_count < run_stats.initial_run_count {
+            let _ = write!(
+                out,
+                ", {} not run",
+                run_stats.initial_run_count - run_stats.finished_count
+            );
+        }
+        out.push_str("\n\n");
+
+        self.failed.sort_by(|a, b| a.name.cmp(&b.name));
+        self.flaky.sort_by(|a, b| a.name.cmp(&b.name));
+        write_section(&mut out, "Failed tests", &self.failed);
+        write_section(&mut out, "Flaky tests", &self.flaky);
+
+        // Sort by duration, slowest first, and by name among tests that took as long.
+        self.durations
+            .sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then_with(|| a_name.cmp(b_name)));
+        if !self.durations.is_empty() {
+            out.push_str("### Slowest tests\n\n| Test | Duration |\n| --- | --- |\n");
+            for (name, time_taken) in self.durations.iter().take(SLOWEST_COUNT) {
+                let _ = writeln!(
+                    out,
+                    "| {} | {} |",
+                    code_cell(name),
+                    format_duration(*time_taken)
+                );
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+fn write_section(out: &mut String, title: &str, entries: &[SummaryEntry]) {
+    if entries.is_empty() {
+        return;
+    }
+    let _ = writeln!(
+        out,
+        "### {title} ({})\n\n| Test | Status | Duration | Attempts |\n| --- | --- | --- | --- |",
+        entries.len()
+    );
+    for entry in entries {
+        let _ = writeln!(
+            out,
+            "| {} | {} | {} | {} |",
+            code_cell(&entry.name),
+            entry.label,
+            format_duration(entry.time_taken),
+            entry.attempts
+        );
+    }
+    out.push('\n');
+
+    for entry in entries {
+        if let Some(output) = &entry.output {
+            let _ = writeln!(
+                out,
+                "<details>\n<summary><code>{}</code></summary>\n",
+                escape_html(&entry.name)
+            );
+            write_output(out, output);
+            out.push_str("\n</details>\n\n");
+        }
+    }
+}
+
+/// Writes the last lines of a test's output as a fenced code block.
+fn write_output(out: &mut String, output: &str) {
+    let output = output.trim_start_matches('\n').trim_end();
+    if output.is_empty() {
+        out.push_str("No output was captured.\n");
+        return;
+    }
+    let lines: Vec<_> = output.lines().collect();
+    let omitted = lines.len().saturating_sub(OUTPUT_LINES);
+    if omitted > 0 {
+        let _ = writeln!(out, "*{omitted} earlier lines omitted*\n");
+    }
+    // The fence has to be longer than any run of backticks within the output.
+    let longest_run = output.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    let _ = writeln!(out, "{fence}text");
+    for line in &lines[omitted..] {
+        let _ = writeln!(out, "{line}");
+    }
+    let _ = writeln!(out, "{fence}");
+}
+
+fn combined_output(status: &ExecuteStatus) -> String {
+    let mut output = String::from_utf8_lossy(&status.stdout).into_owned();
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push('\n');
+    }
+    output.push_str(&String::from_utf8_lossy(&status.stderr));
+    output
+}
+
+fn test_name(test_instance: &TestInstance<'_>) -> String {
+    format!(
+        "{} {}",
+        test_instance.bin_info.binary_id, test_instance.name
+    )
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.3}s", duration.as_secs_f64())
+}
+
+/// Formats a test name as code within a table cell.
+fn code_cell(name: &str) -> String {
+    // Pipes end the cell even within code spans, unless they're escaped.
+    format!("`{}`", name.replace('|', "\\|"))
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_output() {
+        let mut out = String::new();
+        write_output(&mut out, "uses ``` fences\n\n");
+        assert_eq!(out, "````text\nuses ``` fences\n````\n");
+
+        let mut out = String::new();
+        let output: String = (1..=OUTPUT_LINES + 2)
+            .map(|line| format!("line {line}\n"))
+            .collect();
+        write_output(&mut out, &output);
+        assert!(
+            out.starts_with("*2 earlier lines omitted*\n\n```text\nline 3\n"),
+            "out: {out}"
+        );
+        assert!(out.ends_with(&format!("line {}\n```\n", OUTPUT_LINES + 2)));
+    }
+
+    #[test]
+    fn test_code_cell() {
+        assert_eq!(code_cell("my-crate tests::a|b"), "`my-crate tests::a\\|b`");
+    }
+}
@@ -0,0 +1,398 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Writes a SARIF log of test failures, for `--message-format sarif`.
+//!
+//! [SARIF](https://sarifweb.azurewebsites.net/) is the format code scanning services like GitHub's
+//! ingest. Each test that fails or is flaky becomes a result pointing at the place the test panicked
+//! at, or else at the place it's defined. Since a SARIF log is a single JSON document, it's written
+//! out once the run finishes.
+
+use crate::{
+    errors::WriteEventError,
+    list::TestInstance,
+    reporter::{
+        aggregator::to_datetime,
+        github::{failure_message, test_location},
+        status_str, TestEvent,
+    },
+    runner::{ExecuteStatus, ExecutionDescription},
+};
+use chrono::SecondsFormat;
+use serde_json::{json, Value};
+use std::io::{self, Write};
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Collects a SARIF result for each test that fails or is flaky, and writes out the log at the end
+/// of the run.
+pub(crate) struct SarifReporter<W> {
+    writer: W,
+    run_properties: Value,
+    results: Vec<Value>,
+}
+
+impl SarifReporter<io::Stdout> {
+    /// Creates a new reporter that writes to standard output.
+    pub(crate) fn stdout() -> Self {
+        Self {
+            writer: io::stdout(),
+            run_properties: Value::Null,
+            results: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> SarifReporter<W> {
+    pub(crate) fn write_event(&mut self, event: &TestEvent<'_>) -> Result<(), WriteEventError> {
+        match event {
+            TestEvent::RunStarted {
+                iterations,
+                shuffle_seed,
+                seed,
+                ..
+            } => {
+                self.run_properties = json!({
+                    "iterations": iterations,
+                    "shuffleSeed": shuffle_seed,
+                    "seed": seed,
+                });
+            }
+            TestEvent::TestFinished {
+                test_instance,
+                run_statuses,
+                quarantined,
+                ..
+            } => match run_statuses.describe() {
+                ExecutionDescription::Success { .. } => {}
+                ExecutionDescription::Flaky { prior_statuses, .. } => {
+                    self.results.push(result(
+                        "flaky-test",
+                        "warning",
+                        "FLAKY",
+                        test_instance,
+                        &prior_statuses[0],
+                        run_statuses.len(),
+                        format!(
+                            "Test passed after {} failed attempts. First failure:\n{}",
+                            prior_statuses.len(),
+                            failure_message(&prior_statuses[0]),
+                        ),
+                    ));
+                }
+                ExecutionDescription::Failure { last_status, .. } => {
+                    self.results.push(result(
+                        "test-failure",
+                        // Failures of quarantined tests don't fail the run.
+                        if *quarantined { "warning" } else { "error" },
+                        &status_str(last_status.result),
+                        test_instance,
+                        last_status,
+                        run_statuses.len(),
+                        failure_message(last_status),
+                    ));
+                }
+            },
+            TestEvent::RunFinished {
+                run_id,
+                start_time,
+                elapsed,
+                run_stats,
+                ..
+            } => {
+                let end_time = to_datetime(*start_time + *elapsed);
+                let start_time = to_datetime(*start_time);
+                let mut properties = std::mem::take(&mut self.run_properties);
+                if let Value::Object(properties) = &mut properties {
+                    properties.insert("runId".to_owned(), json!(run_id.to_string()));
+                    properties.insert(
+                        "stats".to_owned(),
+                        json!({
+                            "initialRunCount": run_stats.initial_run_count,
+                            "finishedCount": run_stats.finished_count,
+                            "passed": run_stats.passed,
+                            "flaky": run_stats.flaky,
+                            "failed": run_stats.failed,
+                            "timedOut": run_stats.timed_out,
+                            "leaky": run_stats.leaky,
+                            "execFailed": run_stats.exec_failed,
+                            "skipped": run_stats.skipped,
+                        }),
+                    );
+                }
+                let log = json!({
+                    "$schema": SCHEMA,
+                    "version": "2.1.0",
+                    "runs": [{
+                        "tool": {
+                            "driver": {
+                                "name": "nextest",
+                                "informationUri": "https://nexte.st",
+                                "rules": rules(),
+                            },
+                        },
+                        "automationDetails": {
+                            "guid": run_id.to_string(),
+                        },
+                        "invocations": [{
+                            "executionSuccessful": run_stats.is_success(),
+                            "startTimeUtc": start_time.to_rfc3339_opts(SecondsFormat::Millis, true),
+                            "endTimeUtc": end_time.to_rfc3339_opts(SecondsFormat::Millis, true),
+                            "properties": properties,
+                        }],
+                        "originalUriBaseIds": {
+                            "%SRCROOT%": {
+                                "description": { "text": "The workspace root." },
+                            },
+                        },
+                        "results": std::mem::take(&mut self.results),
+                    }],
+                });
+
+                let mut out =
+                    serde_json::to_vec_pretty(&log).expect("serializing to a Vec doesn't fail");
+                out.push(b'\n');
+                self.writer.write_all(&out).map_err(WriteEventError::Io)?;
+                self.writer.flush().map_err(WriteEventError::Io)?;
+            }
+            TestEvent::TestsAdded { .. }
+            | TestEvent::TestStarted { .. }
+            | TestEvent::TestSlow { .. }
+            | TestEvent::TestOutput { .. }
+            | TestEvent::TestRetry { .. }
+            | TestEvent::TestIsolationChecked { .. }
+            | TestEvent::TestDeterminismChecked { .. }
+            | TestEvent::TestPreviouslyFinished { .. }
+            | TestEvent::TestSkipped { .. }
+            | TestEvent::TestNotRun { .. }
+            | TestEvent::RunBeginCancel { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+fn rules() -> Value {
+    json!([
+        {
+            "id": "test-failure",
+            "name": "TestFailure",
+            "shortDescription": { "text": "A test failed." },
+            "defaultConfiguration": { "level": "error" },
+        },
+        {
+            "id": "flaky-test",
+            "name": "FlakyTest",
+            "shortDescription": { "text": "A test failed, then passed when it was retried." },
+            "defaultConfiguration": { "level": "warning" },
+        },
+    ])
+}
+
+fn result(
+    rule_id: &str,
+    level: &str,
+    status_label: &str,
+    test_instance: &TestInstance<'_>,
+    status: &ExecuteStatus,
+    attempts: usize,
+    message: String,
+) -> Value {
+    let binary_id = &test_instance.bin_info.binary_id;
+    let locations: Vec<_> = test_location(test_instance, status)
+        .into_iter()
+        .map(|location| {
+            json!({
+                "physicalLocation": {
+                    "artifactLocation": {
+                        "uri": location.file,
+                        "uriBaseId": "%SRCROOT%",
+                    },
+                    "region": {
+                        "startLine": location.line,
+                        "startColumn": location.column,
+                    },
+                },
+            })
+        })
+        .collect();
+    json!({
+        "ruleId": rule_id,
+        "level": level,
+        "message": {
+            "text": format!("{status_label} {binary_id} {}: {message}", test_instance.name),
+        },
+        "locations": locations,
+        // Lets code scanning services track the same test's failures across runs, even as the
+        // place the test fails at moves around.
+        "partialFingerprints": {
+            "testName/v1": format!("{binary_id} {}", test_instance.name),
+        },
+        "properties": {
+            "binaryId": binary_id,
+            "testName": test_instance.name,
+            "status": status_label,
+            "attempts": attempts,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        list::TestList,
+        runner::{ExecutionResult, ExecutionStatuses, RunStats},
+        test_helpers::{fake_status, fake_suite, fake_test_case},
+    };
+    use nextest_metadata::{BuildPlatform, RustTestCaseSummary, RustTestLocationSummary};
+    use std::time::{Duration, SystemTime};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_sarif_log() {
+        let suite = fake_suite("my-crate", BuildPlatform::Target);
+        let test_case = fake_test_case();
+        let located_test_case = RustTestCaseSummary {
+            location: Some(RustTestLocationSummary {
+                path: "src/flaky.rs".into(),
+                line: 3,
+                column: 8,
+            }),
+            ..fake_test_case()
+        };
+        let fail = ExecutionResult::Fail {
+            abort_status: None,
+            leaked: false,
+        };
+        let panic = "thread 'tests::fails' panicked at src/lib.rs:10:5:\nboom\n";
+        let finished = |test_instance, statuses| TestEvent::TestFinished {
+            test_instance,
+            run_statuses: ExecutionStatuses::new(statuses),
+            quarantined: false,
+            current_stats: RunStats::default(),
+            running: 0,
+            cancel_state: None,
+        };
+
+        let test_list = TestList::empty();
+        let run_id = Uuid::new_v4();
+        let events = [
+            TestEvent::RunStarted {
+                test_list: &test_list,
+                run_id,
+                iterations: Some(1),
+                until_failure: false,
+                shuffle_seed: None,
+                seed: 42,
+                previously_finished: None,
+                listing: false,
+            },
+            finished(
+                TestInstance::new("tests::passes", "/fake/binary", &suite, &test_case),
+                vec![fake_status(1, 1, ExecutionResult::Pass, "")],
+            ),
+            finished(
+                TestInstance::new("tests::fails", "/fake/binary", &suite, &test_case),
+                vec![
+                    fake_status(1, 2, fail, panic),
+                    fake_status(2, 2, fail, panic),
+                ],
+            ),
+            finished(
+                TestInstance::new("tests::flaky", "/fake/binary", &suite, &located_test_case),
+                vec![
+                    fake_status(1, 2, fail, ""),
+                    fake_status(2, 2, ExecutionResult::Pass, ""),
+                ],
+            ),
+            TestEvent::RunFinished {
+                run_id,
+                start_time: SystemTime::UNIX_EPOCH,
+                elapsed: Duration::from_secs(1),
+                run_stats: RunStats {
+                    initial_run_count: 3,
+                    finished_count: 3,
+                    passed: 2,
+                    flaky: 1,
+                    failed: 1,
+                    ..RunStats::default()
+                },
+                pass_rates: vec![],
+            },
+        ];
+
+        let mut reporter = SarifReporter {
+            writer: Vec::new(),
+            run_properties: Value::Null,
+            results: Vec::new(),
+        };
+        for event in &events {
+            reporter.write_event(event).expect("event written");
+        }
+        let log: Value = serde_json::from_slice(&reporter.writer).expect("log is valid JSON");
+
+        assert_eq!(log["$schema"], SCHEMA);
+        assert_eq!(log["version"], "2.1.0");
+        let runs = log["runs"].as_array().expect("runs is an array");
+        assert_eq!(runs.len(), 1, "one run per log");
+        let run = &runs[0];
+        assert_eq!(run["tool"]["driver"]["name"], "nextest");
+        let rule_ids: Vec<_> = run["tool"]["driver"]["rules"]
+            .as_array()
+            .expect("rules is an array")
+            .iter()
+            .map(|rule| rule["id"].as_str().expect("rule ID is a string"))
+            .collect();
+        assert_eq!(rule_ids, ["test-failure", "flaky-test"]);
+        assert_eq!(run["automationDetails"]["guid"], run_id.to_string());
+        let invocation = &run["invocations"][0];
+        assert_eq!(invocation["executionSuccessful"], false);
+        assert_eq!(invocation["startTimeUtc"], "1970-01-01T00:00:00.000Z");
+        assert_eq!(invocation["endTimeUtc"], "1970-01-01T00:00:01.000Z");
+        assert_eq!(invocation["properties"]["seed"], 42);
+        assert_eq!(invocation["properties"]["stats"]["failed"], 1);
+
+        let results = run["results"].as_array().expect("results is an array");
+        assert_eq!(results.len(), 2, "passing tests have no results");
+        let location = |result: &Value| {
+            let locations = result["locations"]
+                .as_array()
+                .expect("locations is an array");
+            assert_eq!(locations.len(), 1, "each result has one location");
+            let physical = &locations[0]["physicalLocation"];
+            assert_eq!(physical["artifactLocation"]["uriBaseId"], "%SRCROOT%");
+            (
+                physical["artifactLocation"]["uri"].clone(),
+                physical["region"]["startLine"].clone(),
+                physical["region"]["startColumn"].clone(),
+            )
+        };
+
+        let failure = &results[0];
+        assert_eq!(failure["ruleId"], "test-failure");
+        assert_eq!(failure["level"], "error");
+        assert_eq!(
+            failure["message"]["text"],
+            "FAIL my-crate tests::fails: thread 'tests::fails' panicked at src/lib.rs:10:5:\nboom"
+        );
+        assert_eq!(
+            location(failure),
+            ("src/lib.rs".into(), 10.into(), 5.into()),
+            "failures point at where the test panicked"
+        );
+        assert_eq!(
+            failure["partialFingerprints"]["testName/v1"],
+            "my-crate tests::fails"
+        );
+        assert_eq!(failure["properties"]["attempts"], 2);
+
+        let flaky = &results[1];
+        assert_eq!(flaky["ruleId"], "flaky-test");
+        assert_eq!(flaky["level"], "warning");
+        assert_eq!(
+            location(flaky),
+            ("src/flaky.rs".into(), 3.into(), 8.into()),
+            "without a panic location, results point at where the test is defined"
+        );
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Groups failing tests by what they failed with, for the final summary.
+//!
+//! When something many tests share breaks, such as a fixture, they tend to fail with the same
+//! panic. Two failures have the same signature if they panicked at the same place with the same
+//! message, once numbers in the message are disregarded.
+
+use crate::{
+    reporter::heuristic_extract_description,
+    runner::{ExecuteStatus, ExecutionResult},
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// The place a test panicked at, as printed since Rust 1.73, with the message on the lines after.
+static PANICKED_AT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?m)^thread '[^']*'(?: \(\d+\))? panicked at ([^'\r\n]+?:\d+:\d+):\r?$")
+        .expect("regex is valid")
+});
+
+/// The message and place a test panicked at, as printed before Rust 1.73.
+static PANICKED_AT_OLD: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?ms)^thread '[^']*' panicked at '(.*?)', ([^'\r\n]+?:\d+(?::\d+)?)\r?$")
+        .expect("regex is valid")
+});
+
+static DIGITS: Lazy<Regex> = Lazy::new(|| Regex::new(r"\d+").expect("regex is valid"));
+
+/// The number of lines of a message shown for a signature, at most.
+const MAX_MESSAGE_LINES: usize = 8;
+
+/// What a test failed with.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(super) struct FailureSignature {
+    /// The failure with numbers in the message replaced, which failures are grouped by.
+    pub(super) key: String,
+
+    /// The failure as the test printed it.
+    pub(super) message: String,
+}
+
+impl FailureSignature {
+    /// Returns the signature of a failed attempt, if what it failed with can be worked out.
+    pub(super) fn new(status: &ExecuteStatus) -> Option<Self> {
+        match status.result {
+            ExecutionResult::Fail {
+                abort_status: Some(_),
+                ..
+            } => {
+                // Aborted tests are described by the signal or exception they aborted with.
+                let message = heuristic_extract_description(status.result, "", "")?;
+                Some(Self {
+                    key: message.clone(),
+                    message,
+                })
+            }
+            ExecutionResult::Fail {
+                abort_status: None, ..
+            } => Self::from_stderr(&String::from_utf8_lossy(&status.stderr)),
+            _ => None,
+        }
+    }
+
+    fn from_stderr(stderr: &str) -> Option<Self> {
+        let (location, message, start) = if let Some(captures) = PANICKED_AT.captures(stderr) {
+            let end = captures.get(0).expect("group 0 always matches").end();
+            let message = stderr[end..]
+                .lines()
+                .skip_while(|line| line.trim().is_empty())
+                .take_while(|line| {
+                    !line.starts_with("note: ") && !line.starts_with("stack backtrace:")
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            (captures[1].to_owned(), message, captures.get(0)?.start())
+        } else if let Some(captures) = PANICKED_AT_OLD.captures(stderr) {
+            (
+                captures[2].to_owned(),
+                captures[1].to_owned(),
+                captures.get(0)?.start(),
+            )
+        } else {
+            // Tests that return an error without panicking print just the error.
+            let line = stderr.lines().find(|line| line.starts_with("Error: "))?;
+            return Some(Self {
+                key: normalize(line),
+                message: line.to_owned(),
+            });
+        };
+
+        // Tests that return an error print it before panicking with a generic message, so include
+        // the error.
+        let mut message = message.trim_end().to_owned();
+        let prefix = stderr[..start].trim_end_matches(['\r', '\n']);
+        if let Some(error) = prefix
+            .rsplit('\n')
+            .next()
+            .filter(|line| line.starts_with("Error: "))
+        {
+            message = format!("{error}\n{message}");
+        }
+
+        let key = format!("{}: {}", location, normalize(&message));
+        let mut lines = message.lines();
+        let mut shown: Vec<_> = lines.by_ref().take(MAX_MESSAGE_LINES).collect();
+        if lines.next().is_some() {
+            shown.push("...");
+        }
+        Some(Self {
+            key,
+            message: format!("panicked at {}:\n{}", location, shown.join("\n")),
+        })
+    }
+}
+
+/// Replaces numbers, and collapses whitespace, so that messages which only differ in the values
+/// they print match.
+fn normalize(message: &str) -> String {
+    let message = message.split_whitespace().collect::<Vec<_>>().join(" ");
+    DIGITS.replace_all(&message, "N").into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_stderr() {
+        let first = FailureSignature::from_stderr(
+            "thread 'tests::a' panicked at src/fixture.rs:10:5:\n\
+             fixture failed to start on port 8080\n\
+             note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace\n",
+        )
+        .expect("signature found");
+        assert_eq!(
+            first.message,
+            "panicked at src/fixture.rs:10:5:\nfixture failed to start on port 8080"
+        );
+        let second = FailureSignature::from_stderr(
+            "some logging\n\
+             thread 'tests::b' (4242) panicked at src/fixture.rs:10:5:\n\
+             fixture failed to start on port 9090\n",
+        )
+        .expect("signature found");
+        assert_eq!(first.key, second.key);
+
+        let elsewhere = FailureSignature::from_stderr(
+            "thread 'tests::c' panicked at src/fixture.rs:12:5:\n\
+             fixture failed to start on port 8080\n",
+        )
+        .expect("signature found");
+        assert_ne!(first.key, elsewhere.key);
+
+        let old = FailureSignature::from_stderr(
+            "Error: Custom { kind: InvalidData, error: \"this is an error\" }\n\
+             thread 'test_result_failure' panicked at 'assertion failed: `(left == right)`\n  \
+             left: `1`,\n right: `0`', /rustc/fe5b13d6/library/test/src/lib.rs:186:5\n",
+        )
+        .expect("signature found");
+        assert_eq!(
+            old.key,
+            "/rustc/fe5b13d6/library/test/src/lib.rs:186:5: Error: Custom { kind: InvalidData, \
+             error: \"this is an error\" } assertion failed: `(left == right)` left: `N`, \
+             right: `N`"
+        );
+
+        assert_eq!(FailureSignature::from_stderr("nothing to see here\n"), None);
+    }
+}
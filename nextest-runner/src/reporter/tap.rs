@@ -0,0 +1,262 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Writes test results in the Test Anything Protocol (TAP), for `--message-format tap`.
+//!
+//! A test point is written out as each test finishes, followed by a YAML diagnostics block with how
+//! long the test took and, for tests that failed, its output. Since the number of tests that end up
+//! being run isn't known until the run is over (for example, if it's canceled), the plan is written
+//! at the end.
+
+use crate::{
+    errors::WriteEventError,
+    list::TestInstance,
+    reporter::{status_str, TestEvent},
+    runner::ExecuteStatus,
+};
+use nextest_filtering::RunStatus;
+use nextest_metadata::MismatchReason;
+use std::{
+    fmt::{self, Write as _},
+    io::{self, Write},
+    time::Duration,
+};
+
+/// Writes a TAP test point for each test as it finishes.
+pub(crate) struct TapReporter<W> {
+    writer: W,
+    test_points: usize,
+}
+
+impl TapReporter<io::Stdout> {
+    /// Creates a new reporter that writes to standard output.
+    pub(crate) fn stdout() -> Self {
+        Self {
+            writer: io::stdout(),
+            test_points: 0,
+        }
+    }
+}
+
+impl<W: Write> TapReporter<W> {
+    pub(crate) fn write_event(&mut self, event: &TestEvent<'_>) -> Result<(), WriteEventError> {
+        let mut out = String::new();
+        match event {
+            TestEvent::RunStarted { .. } => {
+                out.push_str("TAP version 14\n");
+            }
+            TestEvent::TestFinished {
+                test_instance,
+                run_statuses,
+                quarantined,
+                ..
+            } => {
+                let last_status = run_statuses.last_status();
+                let directive = match (last_status.result.is_success(), *quarantined) {
+                    // Quarantined tests that fail don't fail the run, which TAP expresses as a
+                    // TODO directive.
+                    (false, true) => Some("TODO quarantined"),
+                    _ => None,
+                };
+                self.push_test_point(
+                    &mut out,
+                    last_status.result.is_success(),
+                    test_instance,
+                    directive,
+                );
+                let mut diagnostics = Diagnostics::new(&mut out);
+                diagnostics.number("duration_ms", duration_ms(last_status.time_taken));
+                diagnostics.field("status", &status_str(last_status.result));
+                if run_statuses.len() > 1 {
+                    diagnostics.number("attempts", run_statuses.len());
+                }
+                if !last_status.result.is_success() {
+                    write_output(&mut diagnostics, last_status);
+                }
+                diagnostics.finish();
+            }
+            TestEvent::TestPreviouslyFinished {
+                test_instance,
+                status,
+                time_taken,
+                ..
+            } => {
+                self.push_test_point(&mut out, *status != RunStatus::Failed, test_instance, None);
+                let mut diagnostics = Diagnostics::new(&mut out);
+                diagnostics.number("duration_ms", duration_ms(*time_taken));
+                diagnostics.field("message", "finished before the run was resumed");
+                diagnostics.finish();
+            }
+            TestEvent::TestSkipped {
+                test_instance,
+                reason: MismatchReason::Ignored,
+            } => {
+                let directive = match &test_instance.test_info.ignore_reason {
+                    Some(reason) => format!("SKIP {}", escape_description(reason)),
+                    None => "SKIP ignored".to_owned(),
+                };
+                self.push_test_point(&mut out, true, test_instance, Some(&directive));
+            }
+            TestEvent::TestNotRun { test_instance } => {
+                self.push_test_point(
+                    &mut out,
+                    true,
+                    test_instance,
+                    Some("SKIP run time budget used up"),
+                );
+            }
+            TestEvent::RunFinished { .. } => {
+                let _ = writeln!(out, "1..{}", self.test_points);
+            }
+            TestEvent::TestsAdded { .. }
+            | TestEvent::TestStarted { .. }
+            | TestEvent::TestSlow { .. }
+            | TestEvent::TestOutput { .. }
+            | TestEvent::TestRetry { .. }
+            | TestEvent::TestIsolationChecked { .. }
+            | TestEvent::TestDeterminismChecked { .. }
+            | TestEvent::TestSkipped { .. }
+            | TestEvent::RunBeginCancel { .. } => {}
+        }
+
+        if !out.is_empty() {
+            self.writer
+                .write_all(out.as_bytes())
+                .map_err(WriteEventError::Io)?;
+            self.writer.flush().map_err(WriteEventError::Io)?;
+        }
+        Ok(())
+    }
+
+    fn push_test_point(
+        &mut self,
+        out: &mut String,
+        ok: bool,
+        test_instance: &TestInstance<'_>,
+        directive: Option<&str>,
+    ) {
+        self.test_points += 1;
+        let _ = write!(
+            out,
+            "{}{} - {} {}",
+            if ok { "ok " } else { "not ok " },
+            self.test_points,
+            escape_description(&test_instance.bin_info.binary_id),
+            escape_description(test_instance.name),
+        );
+        if let Some(directive) = directive {
+            let _ = write!(out, " # {directive}");
+        }
+        out.push('\n');
+    }
+}
+
+fn write_output(diagnostics: &mut Diagnostics<'_>, status: &ExecuteStatus) {
+    if let Some(exit_code) = status.exit_code {
+        diagnostics.number("exit_code", exit_code);
+    }
+    for (name, output) in [("stdout", &status.stdout), ("stderr", &status.stderr)] {
+        if !output.is_empty() {
+            diagnostics.block(name, &String::from_utf8_lossy(output));
+        }
+    }
+}
+
+fn duration_ms(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64() * 1000.0)
+}
+
+/// Escapes the characters that have a meaning in a TAP description.
+fn escape_description(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('#', "\\#")
+        .replace('\n', " ")
+}
+
+/// A YAML diagnostics block following a test point.
+struct Diagnostics<'a> {
+    out: &'a mut String,
+}
+
+impl<'a> Diagnostics<'a> {
+    fn new(out: &'a mut String) -> Self {
+        out.push_str("  ---\n");
+        Self { out }
+    }
+
+    fn number(&mut self, name: &str, value: impl fmt::Display) {
+        let _ = writeln!(self.out, "  {name}: {value}");
+    }
+
+    /// Writes a field whose value is a string, quoted so that YAML doesn't interpret it.
+    fn field(&mut self, name: &str, value: &str) {
+        let _ = write!(self.out, "  {name}: \"");
+        for c in value.chars() {
+            match c {
+                '"' => self.out.push_str("\\\""),
+                '\\' => self.out.push_str("\\\\"),
+                '\n' => self.out.push_str("\\n"),
+                '\t' => self.out.push_str("\\t"),
+                c if c.is_control() => {
+                    let _ = write!(self.out, "\\u{:04x}", c as u32);
+                }
+                c => self.out.push(c),
+            }
+        }
+        self.out.push_str("\"\n");
+    }
+
+    /// Writes a field whose value spans several lines, as a literal block scalar.
+    fn block(&mut self, name: &str, value: &str) {
+        let value = value.trim_end_matches('\n');
+        if value
+            .chars()
+            .any(|c| c.is_control() && c != '\n' && c != '\t')
+        {
+            // Block scalars can't contain control characters.
+            return self.field(name, value);
+        }
+        // Give the indentation explicitly, in case the first line starts with spaces.
+        let _ = writeln!(self.out, "  {name}: |2-");
+        for line in value.split('\n') {
+            let _ = writeln!(self.out, "    {line}");
+        }
+    }
+
+    fn finish(self) {
+        self.out.push_str("  ...\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostics() {
+        let mut out = String::new();
+        let mut diagnostics = Diagnostics::new(&mut out);
+        diagnostics.number("duration_ms", duration_ms(Duration::from_micros(1500)));
+        diagnostics.field("status", "say \"hi\"\n");
+        diagnostics.block("stdout", "  indented\n\nlast\n");
+        diagnostics.block("stderr", "bell\x07");
+        diagnostics.finish();
+        assert_eq!(
+            out,
+            "  ---\n\
+             \x20 duration_ms: 1.500\n\
+             \x20 status: \"say \\\"hi\\\"\\n\"\n\
+             \x20 stdout: |2-\n\
+             \x20     indented\n\
+             \x20   \n\
+             \x20   last\n\
+             \x20 stderr: \"bell\\u0007\"\n\
+             \x20 ...\n"
+        );
+    }
+
+    #[test]
+    fn test_escape_description() {
+        assert_eq!(escape_description("tests::a#b\\c"), "tests::a\\#b\\\\c");
+    }
+}
@@ -0,0 +1,241 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Writes TeamCity service messages, for `--message-format teamcity`.
+//!
+//! TeamCity reads `##teamcity[...]` lines from a build's output to report tests as they run. Since
+//! nextest runs several tests at the same time, each test's messages are written with a flow ID of
+//! its own, so that TeamCity can tell them apart.
+
+use crate::{
+    errors::WriteEventError,
+    list::TestInstance,
+    reporter::{heuristic_extract_description, status_str, TestEvent},
+    runner::ExecuteStatus,
+};
+use nextest_filtering::RunStatus;
+use nextest_metadata::MismatchReason;
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+    time::Duration,
+};
+
+/// Writes TeamCity service messages for each test.
+pub(crate) struct TeamcityReporter<W> {
+    writer: W,
+}
+
+impl TeamcityReporter<io::Stdout> {
+    /// Creates a new reporter that writes to standard output.
+    pub(crate) fn stdout() -> Self {
+        Self {
+            writer: io::stdout(),
+        }
+    }
+}
+
+impl<W: Write> TeamcityReporter<W> {
+    pub(crate) fn write_event(&mut self, event: &TestEvent<'_>) -> Result<(), WriteEventError> {
+        let mut out = String::new();
+        match event {
+            TestEvent::TestStarted { test_instance, .. } => {
+                ServiceMessage::new("testStarted", test_instance)
+                    .attr("captureStandardOutput", "false")
+                    .write_to(&mut out);
+            }
+            TestEvent::TestRetry {
+                test_instance,
+                run_status,
+                delay,
+                ..
+            } => {
+                let mut text = format!(
+                    "{} {}: attempt {} of {} failed with status {}, retrying",
+                    test_instance.bin_info.binary_id,
+                    test_instance.name,
+                    run_status.attempt,
+                    run_status.total_attempts,
+                    status_str(run_status.result),
+                );
+                if !delay.is_zero() {
+                    let _ = write!(text, " in {:.3}s", delay.as_secs_f64());
+                }
+                ServiceMessage::without_name("message", test_instance)
+                    .attr("text", &text)
+                    .attr("status", "WARNING")
+                    .write_to(&mut out);
+            }
+            TestEvent::TestFinished {
+                test_instance,
+                run_statuses,
+                ..
+            } => {
+                let last_status = run_statuses.last_status();
+                for (message, output) in [
+                    ("testStdOut", &last_status.stdout),
+                    ("testStdErr", &last_status.stderr),
+                ] {
+                    if !output.is_empty() {
+                        ServiceMessage::new(message, test_instance)
+                            .attr("out", &String::from_utf8_lossy(output))
+                            .write_to(&mut out);
+                    }
+                }
+                if !last_status.result.is_success() {
+                    write_failed(&mut out, test_instance, last_status, run_statuses.len());
+                }
+                write_finished(&mut out, test_instance, last_status.time_taken);
+            }
+            TestEvent::TestPreviouslyFinished {
+                test_instance,
+                status,
+                time_taken,
+                ..
+            } => {
+                ServiceMessage::new("testStarted", test_instance).write_to(&mut out);
+                if *status == RunStatus::Failed {
+                    ServiceMessage::new("testFailed", test_instance)
+                        .attr("message", "failed before the run was resumed")
+                        .write_to(&mut out);
+                }
+                write_finished(&mut out, test_instance, *time_taken);
+            }
+            TestEvent::TestSkipped {
+                test_instance,
+                reason: MismatchReason::Ignored,
+            } => {
+                let message = test_instance
+                    .test_info
+                    .ignore_reason
+                    .as_deref()
+                    .unwrap_or("ignored");
+                ServiceMessage::new("testIgnored", test_instance)
+                    .attr("message", message)
+                    .write_to(&mut out);
+            }
+            TestEvent::TestNotRun { test_instance } => {
+                ServiceMessage::new("testIgnored", test_instance)
+                    .attr("message", "not run: the run time budget was used up")
+                    .write_to(&mut out);
+            }
+            TestEvent::RunStarted { .. }
+            | TestEvent::TestsAdded { .. }
+            | TestEvent::TestSlow { .. }
+            | TestEvent::TestOutput { .. }
+            | TestEvent::TestIsolationChecked { .. }
+            | TestEvent::TestDeterminismChecked { .. }
+            | TestEvent::TestSkipped { .. }
+            | TestEvent::RunBeginCancel { .. }
+            | TestEvent::RunFinished { .. } => {}
+        }
+
+        if !out.is_empty() {
+            self.writer
+                .write_all(out.as_bytes())
+                .map_err(WriteEventError::Io)?;
+            self.writer.flush().map_err(WriteEventError::Io)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_failed(
+    out: &mut String,
+    test_instance: &TestInstance<'_>,
+    status: &ExecuteStatus,
+    attempts: usize,
+) {
+    let stdout = String::from_utf8_lossy(&status.stdout);
+    let stderr = String::from_utf8_lossy(&status.stderr);
+    let mut message = format!("test failed with status {}", status_str(status.result));
+    if attempts > 1 {
+        let _ = write!(message, " after {attempts} attempts");
+    }
+    let mut failed = ServiceMessage::new("testFailed", test_instance).attr("message", &message);
+    // The output of the test was already written out, but TeamCity shows the details next to the
+    // failure, so pick out the most relevant part if possible.
+    let details = heuristic_extract_description(status.result, &stdout, &stderr)
+        .unwrap_or_else(|| stderr.trim_end().to_owned());
+    if !details.is_empty() {
+        failed = failed.attr("details", &details);
+    }
+    failed.write_to(out);
+}
+
+fn write_finished(out: &mut String, test_instance: &TestInstance<'_>, time_taken: Duration) {
+    ServiceMessage::new("testFinished", test_instance)
+        .attr("duration", &time_taken.as_millis().to_string())
+        .write_to(out);
+}
+
+/// A `##teamcity[...]` service message.
+struct ServiceMessage {
+    line: String,
+}
+
+impl ServiceMessage {
+    /// Starts a message about a test, with its name and flow ID.
+    fn new(message: &str, test_instance: &TestInstance<'_>) -> Self {
+        let name = test_name(test_instance);
+        Self::without_name(message, test_instance).attr("name", &name)
+    }
+
+    /// Starts a message in a test's flow that isn't about the test itself.
+    fn without_name(message: &str, test_instance: &TestInstance<'_>) -> Self {
+        Self {
+            line: format!("##teamcity[{message}"),
+        }
+        .attr("flowId", &test_name(test_instance))
+    }
+
+    fn attr(mut self, name: &str, value: &str) -> Self {
+        let _ = write!(self.line, " {name}='{}'", escape(value));
+        self
+    }
+
+    fn write_to(mut self, out: &mut String) {
+        self.line.push_str("]\n");
+        out.push_str(&self.line);
+    }
+}
+
+fn test_name(test_instance: &TestInstance<'_>) -> String {
+    format!(
+        "{}::{}",
+        test_instance.bin_info.binary_id, test_instance.name
+    )
+}
+
+/// Escapes a value in a service message, as described in TeamCity's documentation.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '|' => escaped.push_str("||"),
+            '\'' => escaped.push_str("|'"),
+            '\n' => escaped.push_str("|n"),
+            '\r' => escaped.push_str("|r"),
+            '[' => escaped.push_str("|["),
+            ']' => escaped.push_str("|]"),
+            '\u{0085}' | '\u{2028}' | '\u{2029}' => {
+                let _ = write!(escaped, "|0x{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("assertion failed: `(left == right)`\r\n  left: 'a[0]' | \u{2028}"),
+            "assertion failed: `(left == right)`|r|n  left: |'a|[0|]|' || |0x2028"
+        );
+    }
+}
@@ -0,0 +1,416 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Writes an SVG timeline of a run, if `timeline.path` is set for the profile.
+//!
+//! Each attempt to run a test is drawn as a bar in the slot it ran in, so that gaps in scheduling,
+//! tests in a test group running one after the other, and tests that take up much of the run stand
+//! out. Nextest doesn't keep track of slots while running tests, so the slots are reconstructed
+//! afterwards from when each attempt started and finished.
+
+use crate::{
+    config::NextestProfile,
+    errors::WriteEventError,
+    list::TestInstance,
+    reporter::{html::escape, TestEvent},
+    runner::{test_query, ExecuteStatus},
+};
+use camino::Utf8PathBuf;
+use std::{collections::BTreeMap, fmt::Write as _, time::Duration, time::SystemTime};
+
+/// The width of the timeline in pixels.
+const WIDTH: f64 = 1200.0;
+
+/// The space to the left of the lanes, for their labels.
+const LEFT_MARGIN: f64 = 70.0;
+
+const RIGHT_MARGIN: f64 = 20.0;
+
+const LANE_HEIGHT: f64 = 18.0;
+
+const LANE_GAP: f64 = 4.0;
+
+/// The number of longest-running attempts listed under the timeline.
+const LONGEST_COUNT: usize = 5;
+
+/// Colors for test groups, in the order groups are first seen in.
+const GROUP_COLORS: &[&str] = &[
+    "#f28e2b", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#ff9da7", "#9c755f",
+];
+
+/// The color for tests that aren't in a test group.
+const DEFAULT_COLOR: &str = "#4e79a7";
+
+/// The color for attempts that failed.
+const FAILED_COLOR: &str = "#e15759";
+
+/// Collects when each test ran, and writes out a timeline of the run once it finishes.
+#[derive(Clone, Debug)]
+pub(crate) struct Timeline<'cfg> {
+    path: Utf8PathBuf,
+    profile: NextestProfile<'cfg>,
+    bars: Vec<Bar>,
+}
+
+/// An attempt to run a test.
+#[derive(Clone, Debug)]
+struct Bar {
+    name: String,
+    group: Option<String>,
+    label: String,
+    failed: bool,
+    start_time: SystemTime,
+    // The time since the start of the run that the attempt started at, filled out once the run is
+    // finished.
+    start: Duration,
+    duration: Duration,
+}
+
+impl<'cfg> Timeline<'cfg> {
+    pub(crate) fn new(path: Utf8PathBuf, profile: &NextestProfile<'cfg>) -> Self {
+        Self {
+            path,
+            profile: profile.clone(),
+            bars: Vec::new(),
+        }
+    }
+
+    pub(crate) fn write_event(&mut self, event: &TestEvent<'_>) -> Result<(), WriteEventError> {
+        match event {
+            TestEvent::TestFinished {
+                test_instance,
+                run_statuses,
+                ..
+            } => {
+                for status in run_statuses.iter() {
+                    let label = if run_statuses.len() > 1 {
+                        format!("attempt {} of {}", status.attempt, run_statuses.len())
+                    } else {
+                        String::new()
+                    };
+                    self.push(test_instance, status, label);
+                }
+            }
+            TestEvent::TestIsolationChecked {
+                test_instance,
+                run_status,
+            } => self.push(test_instance, run_status, "run on its own".to_owned()),
+            TestEvent::TestDeterminismChecked {
+                test_instance,
+                run_status,
+                ..
+            } => self.push(test_instance, run_status, "determinism check".to_owned()),
+            TestEvent::RunFinished {
+                run_id,
+                start_time,
+                elapsed,
+                ..
+            } => {
+                for bar in &mut self.bars {
+                    bar.start = bar
+                        .start_time
+                        .duration_since(*start_time)
+                        .unwrap_or_default();
+                }
+                let svg = render(&run_id.to_string(), &mut self.bars, *elapsed);
+
+                let dir = self
+                    .path
+                    .parent()
+                    .expect("timeline path must have a parent");
+                std::fs::create_dir_all(dir).map_err(|error| WriteEventError::Fs {
+                    file: dir.to_path_buf(),
+                    error,
+                })?;
+                std::fs::write(&self.path, svg).map_err(|error| WriteEventError::Fs {
+                    file: self.path.clone(),
+                    error,
+                })?;
+            }
+            TestEvent::RunStarted { .. }
+            | TestEvent::TestsAdded { .. }
+            | TestEvent::TestStarted { .. }
+            | TestEvent::TestSlow { .. }
+            | TestEvent::TestOutput { .. }
+            | TestEvent::TestRetry { .. }
+            | TestEvent::TestPreviouslyFinished { .. }
+            | TestEvent::TestSkipped { .. }
+            | TestEvent::TestNotRun { .. }
+            | TestEvent::RunBeginCancel { .. } => {}
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, test_instance: &TestInstance<'_>, status: &ExecuteStatus, label: String) {
+        let group = self
+            .profile
+            .overrides_for(&test_query(test_instance))
+            .test_group()
+            .map(|group| group.to_owned());
+        self.bars.push(Bar {
+            name: format!(
+                "{} {}",
+                test_instance.bin_info.binary_id, test_instance.name
+            ),
+            group,
+            label,
+            failed: !status.result.is_success(),
+            start_time: status.start_time,
+            start: Duration::ZERO,
+            duration: status.time_taken,
+        });
+    }
+}
+
+/// Sorts bars by the time they started at, and returns the slot each one ran in.
+///
+/// Each bar goes into the first slot that's free by the time it starts, which is the slot nextest
+/// would have run it in.
+fn assign_slots(bars: &mut [Bar]) -> Vec<usize> {
+    bars.sort_by(|a, b| a.start.cmp(&b.start).then_with(|| a.name.cmp(&b.name)));
+    // The time each slot is free from.
+    let mut free_from: Vec<Duration> = Vec::new();
+    bars.iter()
+        .map(|bar| {
+            let end = bar.start + bar.duration;
+            match free_from.iter().position(|free| *free <= bar.start) {
+                Some(slot) => {
+                    free_from[slot] = end;
+                    slot
+                }
+                None => {
+                    free_from.push(end);
+                    free_from.len() - 1
+                }
+            }
+        })
+        .collect()
+}
+
+fn render(run_id: &str, bars: &mut [Bar], elapsed: Duration) -> String {
+    let slots = assign_slots(bars);
+    let slot_count = slots.iter().max().map_or(0, |max| max + 1);
+    let elapsed_secs = elapsed.as_secs_f64().max(0.001);
+    let scale = (WIDTH - LEFT_MARGIN - RIGHT_MARGIN) / elapsed_secs;
+    let x = |time: Duration| LEFT_MARGIN + time.as_secs_f64() * scale;
+
+    let mut groups: BTreeMap<&str, &str> = BTreeMap::new();
+    for bar in bars.iter() {
+        if let Some(group) = &bar.group {
+            let next = GROUP_COLORS[groups.len() % GROUP_COLORS.len()];
+            groups.entry(group).or_insert(next);
+        }
+    }
+
+    let lanes_top = 50.0;
+    let lanes_bottom = lanes_top + slot_count as f64 * (LANE_HEIGHT + LANE_GAP);
+    let legend_top = lanes_bottom + 30.0;
+    let longest_top = legend_top + 30.0;
+    let longest_count = bars.len().min(LONGEST_COUNT);
+    let height = longest_top + 16.0 * (longest_count as f64 + 1.0) + 10.0;
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{height}" viewBox="0 0 {WIDTH} {height}" font-family="sans-serif" font-size="11">"##
+    );
+    out.push_str(
+        "<style>.lane{fill:#f2f2f2}.bar{stroke:#fff;stroke-width:0.5}\
+         .label{fill:#fff;pointer-events:none}.axis{stroke:#ccc}.muted{fill:#666}</style>\n",
+    );
+    out.push_str(r##"<rect width="100%" height="100%" fill="#fff"/>"##);
+    out.push('\n');
+
+    let busy: Duration = bars.iter().map(|bar| bar.duration).sum();
+    let utilization = if slot_count == 0 {
+        0.0
+    } else {
+        busy.as_secs_f64() / (elapsed_secs * slot_count as f64)
+    };
+    let _ = writeln!(
+        out,
+        r#"<text x="10" y="18" font-size="14" font-weight="bold">nextest run {}</text>"#,
+        escape(run_id)
+    );
+    let _ = writeln!(
+        out,
+        r#"<text x="10" y="34" class="muted">{} test {} across {slot_count} {} in {:.3}s; slots were busy {:.0}% of the time</text>"#,
+        bars.len(),
+        if bars.len() == 1 { "run" } else { "runs" },
+        if slot_count == 1 { "slot" } else { "slots" },
+        elapsed.as_secs_f64(),
+        utilization * 100.0
+    );
+
+    // Lanes, and the time axis across them.
+    for slot in 0..slot_count {
+        let y = lanes_top + slot as f64 * (LANE_HEIGHT + LANE_GAP);
+        let _ = writeln!(
+            out,
+            r#"<text x="{}" y="{}" text-anchor="end" class="muted">slot {}</text><rect class="lane" x="{LEFT_MARGIN}" y="{y}" width="{}" height="{LANE_HEIGHT}"/>"#,
+            LEFT_MARGIN - 6.0,
+            y + LANE_HEIGHT - 5.0,
+            slot + 1,
+            WIDTH - LEFT_MARGIN - RIGHT_MARGIN,
+        );
+    }
+    let step = tick_step(elapsed_secs);
+    let mut tick = 0.0;
+    while tick <= elapsed_secs + step / 1000.0 {
+        let tick_x = LEFT_MARGIN + tick * scale;
+        let _ = writeln!(
+            out,
+            r#"<line class="axis" x1="{tick_x:.1}" y1="{}" x2="{tick_x:.1}" y2="{}"/><text x="{tick_x:.1}" y="{}" text-anchor="middle" class="muted">{}s</text>"#,
+            lanes_top - 4.0,
+            lanes_bottom,
+            lanes_bottom + 12.0,
+            (tick * 1000.0).round() / 1000.0,
+        );
+        tick += step;
+    }
+
+    for (bar, slot) in bars.iter().zip(&slots) {
+        let bar_x = x(bar.start);
+        let bar_width = (bar.duration.as_secs_f64() * scale).max(1.0);
+        let y = lanes_top + *slot as f64 * (LANE_HEIGHT + LANE_GAP);
+        let color = if bar.failed {
+            FAILED_COLOR
+        } else {
+            bar.group
+                .as_deref()
+                .and_then(|group| groups.get(group).copied())
+                .unwrap_or(DEFAULT_COLOR)
+        };
+        let mut title = format!("{} ({:.3}s", bar.name, bar.duration.as_secs_f64());
+        if let Some(group) = &bar.group {
+            let _ = write!(title, ", test group {group}");
+        }
+        if !bar.label.is_empty() {
+            let _ = write!(title, ", {}", bar.label);
+        }
+        if bar.failed {
+            title.push_str(", failed");
+        }
+        title.push(')');
+        let _ = write!(
+            out,
+            r#"<rect class="bar" x="{bar_x:.1}" y="{y}" width="{bar_width:.1}" height="{LANE_HEIGHT}" fill="{color}"><title>{}</title></rect>"#,
+            escape(&title)
+        );
+        // Label bars that are wide enough to fit some of the test's name.
+        let fits = ((bar_width - 6.0) / 6.5) as usize;
+        if fits >= 4 {
+            let _ = write!(
+                out,
+                r#"<text class="label" x="{:.1}" y="{}">{}</text>"#,
+                bar_x + 3.0,
+                y + LANE_HEIGHT - 5.0,
+                escape(&truncate_name(&bar.name, fits))
+            );
+        }
+        out.push('\n');
+    }
+
+    // A legend of test groups.
+    let mut legend_x = LEFT_MARGIN;
+    let mut legend = vec![("not in a test group", DEFAULT_COLOR)];
+    legend.extend(groups.iter().map(|(group, color)| (*group, *color)));
+    legend.push(("failed attempt", FAILED_COLOR));
+    for (name, color) in legend {
+        let _ = writeln!(
+            out,
+            r#"<rect x="{legend_x:.1}" y="{}" width="10" height="10" fill="{color}"/><text x="{:.1}" y="{legend_top}">{}</text>"#,
+            legend_top - 9.0,
+            legend_x + 14.0,
+            escape(name)
+        );
+        legend_x += 14.0 + 6.5 * name.chars().count() as f64 + 20.0;
+    }
+
+    // The attempts that took the longest, since they bound how short the run can get.
+    let mut longest: Vec<&Bar> = bars.iter().collect();
+    longest.sort_by(|a, b| {
+        b.duration
+            .cmp(&a.duration)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    if longest_count > 0 {
+        let _ = writeln!(
+            out,
+            r#"<text x="10" y="{longest_top}" font-weight="bold">Longest test runs</text>"#
+        );
+    }
+    for (i, bar) in longest.iter().take(longest_count).enumerate() {
+        let _ = writeln!(
+            out,
+            r#"<text x="10" y="{}">{:.3}s ({:.0}% of the run): {}</text>"#,
+            longest_top + 16.0 * (i as f64 + 1.0),
+            bar.duration.as_secs_f64(),
+            bar.duration.as_secs_f64() / elapsed_secs * 100.0,
+            escape(&bar.name)
+        );
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+/// Returns the time between ticks on the axis, aiming for about 10 ticks.
+fn tick_step(elapsed_secs: f64) -> f64 {
+    let raw = elapsed_secs / 10.0;
+    let magnitude = 10f64.powf(raw.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|factor| factor * magnitude)
+        .find(|step| *step >= raw)
+        .unwrap_or(10.0 * magnitude)
+}
+
+fn truncate_name(name: &str, max_chars: usize) -> String {
+    if name.chars().count() <= max_chars {
+        return name.to_owned();
+    }
+    let mut truncated: String = name.chars().take(max_chars - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(name: &str, start_ms: u64, duration_ms: u64) -> Bar {
+        Bar {
+            name: name.to_owned(),
+            group: None,
+            label: String::new(),
+            failed: false,
+            start_time: SystemTime::UNIX_EPOCH,
+            start: Duration::from_millis(start_ms),
+            duration: Duration::from_millis(duration_ms),
+        }
+    }
+
+    #[test]
+    fn test_assign_slots() {
+        let mut bars = vec![
+            bar("d", 300, 100),
+            bar("a", 0, 500),
+            bar("b", 0, 300),
+            bar("c", 100, 100),
+            bar("e", 500, 100),
+        ];
+        let slots = assign_slots(&mut bars);
+        let names: Vec<_> = bars.iter().map(|bar| bar.name.as_str()).collect();
+        assert_eq!(names, ["a", "b", "c", "d", "e"]);
+        // b ends at 300, so d can go in its slot. c starts while both a and b are running.
+        assert_eq!(slots, [0, 1, 2, 1, 0]);
+    }
+
+    #[test]
+    fn test_tick_step() {
+        assert_eq!(tick_step(10.0), 1.0);
+        assert_eq!(tick_step(12.0), 2.0);
+        assert_eq!(tick_step(45.0), 5.0);
+        assert_eq!(tick_step(300.0), 50.0);
+    }
+}
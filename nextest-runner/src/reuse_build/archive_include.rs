@@ -0,0 +1,469 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::errors::ArchiveCreateError;
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use regex::Regex;
+use serde::{de::Error as _, Deserialize};
+use std::fmt;
+
+/// The location within an archive that files from the workspace are stored at.
+///
+/// While extracting an archive, files under this prefix are written out to the workspace, not to
+/// the destination directory.
+pub const WORKSPACE_ARCHIVE_PREFIX: &str = "workspace";
+
+/// A set of files within the workspace to include in archives, configured through
+/// `archive.include` in a profile.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ArchiveInclude {
+    path: IncludeGlob,
+    #[serde(default = "default_max_size", deserialize_with = "deserialize_size")]
+    max_size: u64,
+}
+
+impl ArchiveInclude {
+    /// The default value for [`Self::max_size`]: 64 MiB.
+    pub const DEFAULT_MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+    /// Returns the glob, relative to the workspace root, that files are matched against.
+    pub fn glob(&self) -> &str {
+        &self.path.glob
+    }
+
+    /// Returns the maximum total size of the files matched by this include, in bytes.
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    /// Finds the files in the workspace matched by this include.
+    ///
+    /// Returns a list of absolute paths, sorted by their path relative to the workspace root.
+    /// Directories matched by the glob are included recursively. Symlinks to directories are not
+    /// followed.
+    pub(crate) fn find_files(
+        &self,
+        workspace_root: &Utf8Path,
+    ) -> Result<Vec<Utf8PathBuf>, ArchiveCreateError> {
+        let mut files = Vec::new();
+        let mut total_size = 0;
+
+        let base_dir = workspace_root.join(&self.path.base_dir);
+        // The base directory might itself be a file (or not exist at all), if the glob has no
+        // wildcards.
+        let base_matched = self.path.matches(&self.path.base_dir);
+        match base_dir.metadata() {
+            Ok(metadata) if metadata.is_dir() => {
+                self.walk_dir(
+                    workspace_root,
+                    &base_dir,
+                    base_matched,
+                    &mut files,
+                    &mut total_size,
+                )?;
+            }
+            Ok(metadata) => {
+                if base_matched {
+                    total_size += metadata.len();
+                    files.push(base_dir);
+                }
+            }
+            Err(_) => {
+                // Nonexistent base directories don't match anything.
+            }
+        }
+
+        if total_size > self.max_size {
+            return Err(ArchiveCreateError::IncludeTooLarge {
+                glob: self.path.glob.clone(),
+                size: total_size,
+                max_size: self.max_size,
+            });
+        }
+
+        files.sort_unstable();
+        Ok(files)
+    }
+
+    fn walk_dir(
+        &self,
+        workspace_root: &Utf8Path,
+        dir: &Utf8Path,
+        parent_matched: bool,
+        files: &mut Vec<Utf8PathBuf>,
+        total_size: &mut u64,
+    ) -> Result<(), ArchiveCreateError> {
+        let entries = dir
+            .read_dir_utf8()
+            .map_err(|error| ArchiveCreateError::InputFileRead {
+                path: dir.to_owned(),
+                is_dir: Some(true),
+                error,
+            })?;
+        for entry in entries {
+            let entry = entry.map_err(|error| ArchiveCreateError::DirEntryRead {
+                path: dir.to_owned(),
+                error,
+            })?;
+            let path = entry.path();
+            let rel_path = path
+                .strip_prefix(workspace_root)
+                .expect("walked paths are within the workspace root");
+            let matched = parent_matched || self.path.matches(rel_path);
+
+            // file_type doesn't follow symlinks, so symlinks to directories are treated like
+            // files. They're skipped below since they can't be archived.
+            let file_type =
+                entry
+                    .file_type()
+                    .map_err(|error| ArchiveCreateError::InputFileRead {
+                        path: path.to_owned(),
+                        is_dir: None,
+                        error,
+                    })?;
+            if file_type.is_dir() {
+                self.walk_dir(workspace_root, path, matched, files, total_size)?;
+            } else if matched {
+                let metadata =
+                    path.metadata()
+                        .map_err(|error| ArchiveCreateError::InputFileRead {
+                            path: path.to_owned(),
+                            is_dir: Some(false),
+                            error,
+                        })?;
+                if metadata.is_file() {
+                    *total_size += metadata.len();
+                    files.push(path.to_owned());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn default_max_size() -> u64 {
+    ArchiveInclude::DEFAULT_MAX_SIZE
+}
+
+/// A glob relative to the workspace root.
+#[derive(Clone)]
+struct IncludeGlob {
+    glob: String,
+    // The longest leading sequence of components without wildcards.
+    base_dir: Utf8PathBuf,
+    regex: Regex,
+}
+
+impl IncludeGlob {
+    fn new(glob: &str) -> Result<Self, String> {
+        let path = Utf8Path::new(glob);
+        if glob.is_empty() {
+            return Err("path must not be empty".to_owned());
+        }
+        if glob.contains('\\') {
+            return Err(format!(
+                "path `{glob}` must use forward slashes as separators"
+            ));
+        }
+        // Check components by splitting on "/", since Utf8Path::components normalizes away
+        // interior "." components.
+        if path.has_root()
+            || path
+                .components()
+                .any(|component| matches!(component, Utf8Component::Prefix(_)))
+            || glob
+                .split('/')
+                .any(|component| matches!(component, "" | "." | ".."))
+        {
+            return Err(format!(
+                "path `{glob}` must be relative to the workspace root, and must not contain `.` or `..`"
+            ));
+        }
+
+        let mut base_dir = Utf8PathBuf::new();
+        for component in glob.split('/') {
+            if component.contains(['*', '?', '[']) {
+                break;
+            }
+            base_dir.push(component);
+        }
+
+        let regex = Regex::new(&glob_to_regex(glob))
+            .map_err(|error| format!("path `{glob}` is not a valid glob: {error}"))?;
+        Ok(Self {
+            glob: glob.to_owned(),
+            base_dir,
+            regex,
+        })
+    }
+
+    fn matches(&self, rel_path: &Utf8Path) -> bool {
+        // Globs always use forward slashes.
+        let rel_path = rel_path.as_str().replace('\\', "/");
+        self.regex.is_match(&rel_path)
+    }
+}
+
+impl fmt::Debug for IncludeGlob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("IncludeGlob").field(&self.glob).finish()
+    }
+}
+
+impl<'de> Deserialize<'de> for IncludeGlob {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let glob = String::deserialize(deserializer)?;
+        Self::new(&glob).map_err(D::Error::custom)
+    }
+}
+
+/// Converts a glob to an anchored regex.
+///
+/// `*` and `?` match within a component, `**` matches any number of components, and `[...]`
+/// matches a character class.
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    // "**/" matches zero or more leading components.
+                    chars.next();
+                    regex.push_str("(?:[^/]+/)*");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                regex.push('[');
+                if chars.peek() == Some(&'!') {
+                    chars.next();
+                    regex.push('^');
+                }
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                    if c == '\\' || c == '[' {
+                        regex.push('\\');
+                    }
+                    regex.push(c);
+                }
+                regex.push(']');
+            }
+            other => regex.push_str(&regex::escape(other.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+/// Deserializes a size, either as an integer number of bytes or a string like "10MB" or "1GiB".
+pub(crate) fn deserialize_size<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct V;
+
+    impl<'de2> serde::de::Visitor<'de2> for V {
+        type Value = u64;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(
+                formatter,
+                "an integer number of bytes or a string like \"10MB\" or \"1GiB\""
+            )
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            parse_size(v).ok_or_else(|| E::invalid_value(serde::de::Unexpected::Str(v), &self))
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            v.try_into()
+                .map_err(|_| E::invalid_value(serde::de::Unexpected::Signed(v), &self))
+        }
+    }
+
+    deserializer.deserialize_any(V)
+}
+
+fn parse_size(input: &str) -> Option<u64> {
+    let input = input.trim();
+    let digits_end = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(digits_end);
+    let number: u64 = number.parse().ok()?;
+    let multiplier: u64 = match unit.trim() {
+        "" | "B" => 1,
+        "KB" => 1000,
+        "KiB" => 1024,
+        "MB" => 1000 * 1000,
+        "MiB" => 1024 * 1024,
+        "GB" => 1000 * 1000 * 1000,
+        "GiB" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    number.checked_mul(multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_include_glob_matches() {
+        let cases: &[(&str, &str, &str, bool)] = &[
+            ("fixtures", "fixtures", "fixtures", true),
+            ("fixtures", "fixtures", "fixtures-other", false),
+            (
+                "fixtures/golden.txt",
+                "fixtures/golden.txt",
+                "fixtures/golden.txt",
+                true,
+            ),
+            ("fixtures/*.txt", "fixtures", "fixtures/golden.txt", true),
+            (
+                "fixtures/*.txt",
+                "fixtures",
+                "fixtures/nested/golden.txt",
+                false,
+            ),
+            ("fixtures/**/*.txt", "fixtures", "fixtures/golden.txt", true),
+            (
+                "fixtures/**/*.txt",
+                "fixtures",
+                "fixtures/a/b/golden.txt",
+                true,
+            ),
+            ("fixtures/**", "fixtures", "fixtures/a/b/golden.txt", true),
+            ("**/data", "", "crates/foo/data", true),
+            ("test-?.json", "", "test-1.json", true),
+            ("test-?.json", "", "test-10.json", false),
+            ("test-[!0-4].json", "", "test-7.json", true),
+            ("test-[!0-4].json", "", "test-3.json", false),
+            ("a+b/(c).txt", "a+b/(c).txt", "a+b/(c).txt", true),
+        ];
+
+        for &(glob, base_dir, path, expected) in cases {
+            let include_glob = IncludeGlob::new(glob).unwrap();
+            assert_eq!(
+                include_glob.base_dir, base_dir,
+                "base dir for {glob} matches"
+            );
+            assert_eq!(
+                include_glob.matches(path.as_ref()),
+                expected,
+                "{glob} matching {path}"
+            );
+        }
+
+        for invalid in [
+            "",
+            "/abs/path",
+            "../outside",
+            "fixtures/./foo",
+            "fixtures\\foo",
+        ] {
+            IncludeGlob::new(invalid).expect_err(&format!("{invalid} should be rejected"));
+        }
+    }
+
+    #[test]
+    fn test_find_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let workspace_root: &Utf8Path = temp_dir.path().try_into().unwrap();
+        for (path, contents) in [
+            ("fixtures/golden.txt", "golden"),
+            ("fixtures/nested/data.bin", "0123456789"),
+            ("fixtures/nested/data.txt", "data"),
+            ("src/lib.rs", ""),
+        ] {
+            let path = workspace_root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+
+        let make_include = |glob: &str, max_size: u64| ArchiveInclude {
+            path: IncludeGlob::new(glob).unwrap(),
+            max_size,
+        };
+        let find_files = |include: &ArchiveInclude| {
+            include
+                .find_files(workspace_root)
+                .unwrap()
+                .into_iter()
+                .map(|path| {
+                    path.strip_prefix(workspace_root)
+                        .unwrap()
+                        .as_str()
+                        .replace('\\', "/")
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let default_max_size = ArchiveInclude::DEFAULT_MAX_SIZE;
+        assert_eq!(
+            find_files(&make_include("fixtures", default_max_size)),
+            vec![
+                "fixtures/golden.txt",
+                "fixtures/nested/data.bin",
+                "fixtures/nested/data.txt"
+            ],
+        );
+        assert_eq!(
+            find_files(&make_include("**/*.txt", default_max_size)),
+            vec!["fixtures/golden.txt", "fixtures/nested/data.txt"],
+        );
+        assert_eq!(
+            find_files(&make_include("fixtures/golden.txt", default_max_size)),
+            vec!["fixtures/golden.txt"],
+        );
+        assert_eq!(
+            find_files(&make_include("does-not-exist/**", default_max_size)),
+            Vec::<String>::new(),
+        );
+
+        let error = make_include("fixtures/nested", 10)
+            .find_files(workspace_root)
+            .expect_err("total size is larger than max-size");
+        assert!(
+            matches!(
+                error,
+                ArchiveCreateError::IncludeTooLarge {
+                    size: 14,
+                    max_size: 10,
+                    ..
+                }
+            ),
+            "unexpected error: {error:?}"
+        );
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024"), Some(1024));
+        assert_eq!(parse_size("10MB"), Some(10_000_000));
+        assert_eq!(parse_size("10 MiB"), Some(10 * 1024 * 1024));
+        assert_eq!(parse_size("2GiB"), Some(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("10mb"), None);
+        assert_eq!(parse_size("MB"), None);
+        assert_eq!(parse_size("-1"), None);
+    }
+}
@@ -56,17 +56,42 @@ impl ArchiveReporter {
             }
             ArchiveEvent::Archived {
                 file_count,
+                baseline,
+                baseline_file_count,
                 output_file,
                 elapsed,
             } => {
                 write!(writer, "{:>12} ", "Archived".style(self.styles.success))?;
-                writeln!(
+                write!(
                     writer,
                     "{} files to {} in {}",
                     file_count.style(self.styles.bold),
                     output_file.style(self.styles.bold),
                     format_duration(elapsed),
                 )?;
+                if let Some(baseline) = baseline {
+                    write!(
+                        writer,
+                        " ({} unchanged files left in baseline {})",
+                        baseline_file_count.style(self.styles.bold),
+                        baseline.style(self.styles.bold),
+                    )?;
+                }
+                writeln!(writer)?;
+            }
+            ArchiveEvent::DebuginfoArchived {
+                file_count,
+                output_file,
+                elapsed,
+            } => {
+                write!(writer, "{:>12} ", "Archived".style(self.styles.success))?;
+                writeln!(
+                    writer,
+                    "debug info for {} files to {} in {}",
+                    file_count.style(self.styles.bold),
+                    output_file.style(self.styles.bold),
+                    format_duration(elapsed),
+                )?;
             }
             ArchiveEvent::ExtractStarted {
                 test_binary_count,
@@ -99,6 +124,34 @@ impl ArchiveReporter {
                     format_duration(elapsed),
                 )?;
             }
+            ArchiveEvent::BaselineExtracted {
+                file_count,
+                baseline_file,
+                elapsed,
+            } => {
+                write!(writer, "{:>12} ", "Extracted".style(self.styles.success))?;
+                writeln!(
+                    writer,
+                    "{} unchanged files from baseline {} in {}",
+                    file_count.style(self.styles.bold),
+                    baseline_file.style(self.styles.bold),
+                    format_duration(elapsed),
+                )?;
+            }
+            ArchiveEvent::DebuginfoExtracted {
+                file_count,
+                debuginfo_file,
+                elapsed,
+            } => {
+                write!(writer, "{:>12} ", "Extracted".style(self.styles.success))?;
+                writeln!(
+                    writer,
+                    "debug info for {} files from {} in {}",
+                    file_count.style(self.styles.bold),
+                    debuginfo_file.style(self.styles.bold),
+                    format_duration(elapsed),
+                )?;
+            }
         }
 
         Ok(())
@@ -176,6 +229,13 @@ pub enum ArchiveEvent<'a> {
         /// The number of files archived.
         file_count: usize,
 
+        /// For delta archives, the path to the baseline archive.
+        baseline: Option<&'a Utf8Path>,
+
+        /// The number of files left out of a delta archive because they're unchanged from the
+        /// baseline.
+        baseline_file_count: usize,
+
         /// The archive output file.
         output_file: &'a Utf8Path,
 
@@ -183,6 +243,18 @@ pub enum ArchiveEvent<'a> {
         elapsed: Duration,
     },
 
+    /// Debug info split out of binaries was written out to a separate debuginfo archive.
+    DebuginfoArchived {
+        /// The number of files containing debug info.
+        file_count: usize,
+
+        /// The debuginfo archive.
+        output_file: &'a Utf8Path,
+
+        /// How long it took to create the debuginfo archive.
+        elapsed: Duration,
+    },
+
     /// The extraction process started.
     ExtractStarted {
         /// The number of test binaries to extract.
@@ -209,4 +281,28 @@ pub enum ArchiveEvent<'a> {
         /// How long it took to extract the archive.
         elapsed: Duration,
     },
+
+    /// The unchanged files for a delta archive were extracted from its baseline.
+    BaselineExtracted {
+        /// The number of files extracted from the baseline.
+        file_count: usize,
+
+        /// The baseline archive.
+        baseline_file: &'a Utf8Path,
+
+        /// How long it took to extract the files.
+        elapsed: Duration,
+    },
+
+    /// Debug info for stripped binaries was extracted from a debuginfo archive.
+    DebuginfoExtracted {
+        /// The number of files containing debug info.
+        file_count: usize,
+
+        /// The debuginfo archive.
+        debuginfo_file: &'a Utf8Path,
+
+        /// How long it took to extract the files.
+        elapsed: Duration,
+    },
 }
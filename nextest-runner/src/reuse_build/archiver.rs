@@ -1,21 +1,28 @@
 // Copyright (c) The nextest Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use super::{ArchiveEvent, BINARIES_METADATA_FILE_NAME, CARGO_METADATA_FILE_NAME};
+use super::{
+    baseline::BaselineManifest, debuginfo::DebuginfoStripper, ArchiveBaseline, ArchiveDebuginfo,
+    ArchiveEvent, ArchiveInclude, BASELINE_MANIFEST_FILE_NAME, BINARIES_METADATA_FILE_NAME,
+    CARGO_METADATA_FILE_NAME, WORKSPACE_ARCHIVE_PREFIX,
+};
 use crate::{
-    errors::{ArchiveCreateError, UnknownArchiveFormat},
+    errors::{ArchiveCreateError, InvalidArchiveCompression, UnknownArchiveFormat},
     helpers::convert_rel_path_to_forward_slash,
     list::{BinaryList, OutputFormat, SerializableFormat},
     reuse_build::PathMapper,
 };
 use atomicwrites::{AtomicFile, OverwriteBehavior};
 use camino::{Utf8Path, Utf8PathBuf};
+use flate2::write::GzEncoder;
+use serde::Deserialize;
 use std::{
-    collections::HashSet,
+    collections::{BTreeSet, HashSet},
+    fmt,
     io::{self, BufWriter, Write},
-    time::{Instant, SystemTime},
+    str::FromStr,
+    time::Instant,
 };
-use zstd::Encoder;
 
 /// Archive format.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -23,11 +30,31 @@ use zstd::Encoder;
 pub enum ArchiveFormat {
     /// A Zstandard-compressed tarball.
     TarZst,
+
+    /// A gzip-compressed tarball.
+    TarGz,
+
+    /// An uncompressed tarball.
+    Tar,
 }
 
 impl ArchiveFormat {
     /// The list of supported formats as a list of (file extension, format) pairs.
-    pub const SUPPORTED_FORMATS: &'static [(&'static str, Self)] = &[(".tar.zst", Self::TarZst)];
+    pub const SUPPORTED_FORMATS: &'static [(&'static str, Self)] = &[
+        (".tar.zst", Self::TarZst),
+        (".tar.gz", Self::TarGz),
+        (".tgz", Self::TarGz),
+        (".tar", Self::Tar),
+    ];
+
+    /// Returns the canonical file extension for this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::TarZst => ".tar.zst",
+            Self::TarGz => ".tar.gz",
+            Self::Tar => ".tar",
+        }
+    }
 
     /// Automatically detects an archive format from a given file name, and returns an error if the
     /// detection failed.
@@ -43,17 +70,147 @@ impl ArchiveFormat {
             file_name: file_name.to_owned(),
         })
     }
+
+    /// Detects an archive format from the first few bytes of an archive, returning `None` if the
+    /// contents aren't recognized.
+    ///
+    /// Compressed streams are recognized by their magic numbers. Uncompressed tarballs are
+    /// recognized by the `ustar` magic in the header of the first entry, so at least
+    /// [`Self::DETECT_LEN`] bytes should be passed in.
+    pub fn autodetect_from_contents(contents: &[u8]) -> Option<Self> {
+        const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+        const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+        const TAR_MAGIC: &[u8] = b"ustar";
+        const TAR_MAGIC_OFFSET: usize = 257;
+
+        if contents.starts_with(ZSTD_MAGIC) {
+            Some(Self::TarZst)
+        } else if contents.starts_with(GZIP_MAGIC) {
+            Some(Self::TarGz)
+        } else if contents.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len())
+            == Some(TAR_MAGIC)
+        {
+            Some(Self::Tar)
+        } else {
+            None
+        }
+    }
+
+    /// The number of bytes [`Self::autodetect_from_contents`] needs to look at.
+    pub const DETECT_LEN: usize = 512;
+}
+
+/// The compression to use while creating an archive.
+///
+/// This can be parsed from a string of the form `zstd`, `zstd:<level>`, `gzip` or `none`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ArchiveCompression {
+    /// Zstandard compression at the given level (-7 to 22, higher is more compressed + slower).
+    ///
+    /// A level of 0 means the Zstandard default.
+    Zstd {
+        /// The compression level.
+        level: i32,
+    },
+
+    /// gzip compression at the default level.
+    Gzip,
+
+    /// No compression.
+    None,
+}
+
+impl ArchiveCompression {
+    /// Returns the archive format produced by this compression.
+    pub fn format(self) -> ArchiveFormat {
+        match self {
+            Self::Zstd { .. } => ArchiveFormat::TarZst,
+            Self::Gzip => ArchiveFormat::TarGz,
+            Self::None => ArchiveFormat::Tar,
+        }
+    }
+}
+
+impl Default for ArchiveCompression {
+    fn default() -> Self {
+        Self::Zstd { level: 0 }
+    }
+}
+
+impl FromStr for ArchiveCompression {
+    type Err = InvalidArchiveCompression;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (algorithm, level) = match input.split_once(':') {
+            Some((algorithm, level)) => (algorithm, Some(level)),
+            None => (input, None),
+        };
+
+        match (algorithm, level) {
+            ("zstd", None) => Ok(Self::default()),
+            ("zstd", Some(level)) => match level.parse() {
+                Ok(level) => Ok(Self::Zstd { level }),
+                Err(_) => Err(InvalidArchiveCompression::new(
+                    input,
+                    "zstd level must be an integer",
+                )),
+            },
+            ("gzip", None) => Ok(Self::Gzip),
+            ("none", None) => Ok(Self::None),
+            ("gzip" | "none", Some(_)) => Err(InvalidArchiveCompression::new(
+                input,
+                "a compression level can only be specified for zstd",
+            )),
+            _ => Err(InvalidArchiveCompression::new(
+                input,
+                "expected `zstd`, `zstd:<level>`, `gzip` or `none`",
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ArchiveCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Zstd { level } => write!(f, "zstd:{level}"),
+            Self::Gzip => write!(f, "gzip"),
+            Self::None => write!(f, "none"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ArchiveCompression {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
 }
 
 /// Archives test binaries along with metadata to the given file.
 ///
-/// The output file is a Zstandard-compressed tarball (`.tar.zst`).
+/// The output file is a tarball, compressed according to `compression`. Files within
+/// `workspace_root` matched by `include` are also added to the archive.
+///
+/// If `baseline` is specified, a delta archive is created: files with the same contents as in the
+/// baseline are left out, and a manifest referencing the baseline is written out instead.
+///
+/// Debug info is handled according to `debuginfo`. With [`ArchiveDebuginfo::Split`], the debug
+/// info is written out to a separate archive at `debuginfo_file`, with the same compression.
+#[allow(clippy::too_many_arguments)]
 pub fn archive_to_file<'a, F>(
     binary_list: &'a BinaryList,
     cargo_metadata: &'a str,
     path_mapper: &'a PathMapper,
-    format: ArchiveFormat,
-    zstd_level: i32,
+    compression: ArchiveCompression,
+    workspace_root: &'a Utf8Path,
+    include: &'a [ArchiveInclude],
+    baseline: Option<&'a ArchiveBaseline>,
+    debuginfo: ArchiveDebuginfo,
+    debuginfo_file: &'a Utf8Path,
     output_file: &'a Utf8Path,
     mut callback: F,
 ) -> Result<(), ArchiveCreateError>
@@ -66,7 +223,7 @@ where
     let linked_path_count = binary_list.rust_build_meta.linked_paths.len();
     let start_time = Instant::now();
 
-    let file_count = file
+    let (file_count, baseline_file_count, stripper) = file
         .write(|file| {
             callback(ArchiveEvent::ArchiveStarted {
                 test_binary_count,
@@ -80,12 +237,15 @@ where
                 binary_list,
                 cargo_metadata,
                 path_mapper,
-                format,
-                zstd_level,
+                compression,
+                workspace_root,
+                include,
+                baseline,
+                DebuginfoStripper::new(debuginfo)?,
                 file,
             )?;
-            let (_, file_count) = archiver.archive()?;
-            Ok(file_count)
+            let (_, file_count, baseline_file_count, stripper) = archiver.archive()?;
+            Ok((file_count, baseline_file_count, stripper))
         })
         .map_err(|err| match err {
             atomicwrites::Error::Internal(err) => ArchiveCreateError::OutputArchiveIo(err),
@@ -96,63 +256,111 @@ where
 
     callback(ArchiveEvent::Archived {
         file_count,
+        baseline: baseline.map(|baseline| baseline.path()),
+        baseline_file_count,
         output_file,
         elapsed,
     })
     .map_err(ArchiveCreateError::ReporterIo)?;
 
+    if debuginfo == ArchiveDebuginfo::Split {
+        let start_time = Instant::now();
+        let (_temp_dir, debug_files) = stripper
+            .expect("splitting debug info creates a stripper")
+            .into_debug_files();
+        write_debuginfo_archive(&debug_files, compression, debuginfo_file)?;
+
+        callback(ArchiveEvent::DebuginfoArchived {
+            file_count: debug_files.len(),
+            output_file: debuginfo_file,
+            elapsed: start_time.elapsed(),
+        })
+        .map_err(ArchiveCreateError::ReporterIo)?;
+    }
+
     Ok(())
 }
 
+fn write_debuginfo_archive(
+    debug_files: &[(Utf8PathBuf, Utf8PathBuf)],
+    compression: ArchiveCompression,
+    output_file: &Utf8Path,
+) -> Result<(), ArchiveCreateError> {
+    let file = AtomicFile::new(output_file, OverwriteBehavior::AllowOverwrite);
+    file.write(|file| {
+        let mut builder = tar::Builder::new(ArchiveWriter::new(compression, file)?);
+        builder.mode(tar::HeaderMode::Deterministic);
+        for (rel_path, src_path) in debug_files {
+            builder.append_path_with_name(src_path, rel_path)?;
+        }
+        let buf_writer = builder.into_inner()?.finish()?;
+        buf_writer.into_inner().map_err(|err| err.into_error())?;
+        Ok(())
+    })
+    .map_err(|err| ArchiveCreateError::DebuginfoArchiveIo {
+        path: output_file.to_owned(),
+        error: match err {
+            atomicwrites::Error::Internal(err) | atomicwrites::Error::User(err) => err,
+        },
+    })
+}
+
+/// The mtime for files written out from memory, matching the one used for files on disk by
+/// [`tar::HeaderMode::Deterministic`] on Unix.
+const DETERMINISTIC_MTIME: u64 = 1153704088;
+
 struct Archiver<'a, W: Write> {
     binary_list: &'a BinaryList,
     cargo_metadata: &'a str,
     path_mapper: &'a PathMapper,
-    builder: tar::Builder<Encoder<'static, BufWriter<W>>>,
-    unix_timestamp: u64,
+    workspace_root: &'a Utf8Path,
+    include: &'a [ArchiveInclude],
+    baseline: Option<&'a ArchiveBaseline>,
+    stripper: Option<DebuginfoStripper>,
+    builder: tar::Builder<ArchiveWriter<W>>,
     added_files: HashSet<Utf8PathBuf>,
+    // Files left out because they're unchanged from the baseline.
+    baseline_files: BTreeSet<Utf8PathBuf>,
 }
 
 impl<'a, W: Write> Archiver<'a, W> {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         binary_list: &'a BinaryList,
         cargo_metadata: &'a str,
         path_mapper: &'a PathMapper,
-        format: ArchiveFormat,
-        compression_level: i32,
+        compression: ArchiveCompression,
+        workspace_root: &'a Utf8Path,
+        include: &'a [ArchiveInclude],
+        baseline: Option<&'a ArchiveBaseline>,
+        stripper: Option<DebuginfoStripper>,
         writer: W,
     ) -> Result<Self, ArchiveCreateError> {
-        let buf_writer = BufWriter::new(writer);
-        let builder = match format {
-            ArchiveFormat::TarZst => {
-                let mut encoder = zstd::Encoder::new(buf_writer, compression_level)
-                    .map_err(ArchiveCreateError::OutputArchiveIo)?;
-                encoder
-                    .include_checksum(true)
-                    .map_err(ArchiveCreateError::OutputArchiveIo)?;
-                encoder
-                    .multithread(num_cpus::get() as u32)
-                    .map_err(ArchiveCreateError::OutputArchiveIo)?;
-                tar::Builder::new(encoder)
-            }
-        };
-
-        let unix_timestamp = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .expect("current time should be after 1970-01-01")
-            .as_secs();
+        let writer =
+            ArchiveWriter::new(compression, writer).map_err(ArchiveCreateError::OutputArchiveIo)?;
+        let mut builder = tar::Builder::new(writer);
+        // Archives are reproducible: don't record mtimes, owners or permissions other than the
+        // executable bit.
+        builder.mode(tar::HeaderMode::Deterministic);
 
         Ok(Self {
             binary_list,
             cargo_metadata,
             path_mapper,
+            workspace_root,
+            include,
+            baseline,
+            stripper,
             builder,
-            unix_timestamp,
             added_files: HashSet::new(),
+            baseline_files: BTreeSet::new(),
         })
     }
 
-    fn archive(mut self) -> Result<(W, usize), ArchiveCreateError> {
+    #[allow(clippy::type_complexity)]
+    fn archive(
+        mut self,
+    ) -> Result<(W, usize, usize, Option<DebuginfoStripper>), ArchiveCreateError> {
         // Add the binaries metadata first so that while unarchiving, reports are instant.
         let binaries_metadata = self
             .binary_list
@@ -166,6 +374,7 @@ impl<'a, W: Write> Archiver<'a, W> {
         // Write all discovered binaries into the archive.
         let target_dir = &self.binary_list.rust_build_meta.target_directory;
 
+        // rust_binaries is sorted by ID, so this order is stable across builds.
         for binary in &self.binary_list.rust_binaries {
             let rel_path = binary
                 .path
@@ -230,22 +439,75 @@ impl<'a, W: Write> Archiver<'a, W> {
             self.append_dir_one_level(&rel_path, &src_path)?;
         }
 
-        // TODO: add extra files.
+        // Write the custom target spec file, if any, to the root of the target directory.
+        if let Some(spec_file) = self
+            .binary_list
+            .rust_build_meta
+            .target_triple
+            .as_ref()
+            .and_then(|triple| triple.target_spec_file())
+        {
+            let file_name = spec_file
+                .file_name()
+                .expect("target spec files always have a file name");
+            let rel_path = Utf8Path::new("target").join(file_name);
+            let rel_path = convert_rel_path_to_forward_slash(&rel_path);
+
+            self.append_path(spec_file, &rel_path)?;
+        }
+
+        // Write files from the workspace matched by archive.include.
+        for include in self.include {
+            let files = include.find_files(self.workspace_root)?;
+            if files.is_empty() {
+                log::warn!(
+                    target: "nextest-runner",
+                    "archive include `{}` didn't match any files in the workspace",
+                    include.glob(),
+                );
+            }
+            for src_path in files {
+                let rel_path = src_path
+                    .strip_prefix(self.workspace_root)
+                    .expect("included files must be within the workspace root");
+                let rel_path = Utf8Path::new(WORKSPACE_ARCHIVE_PREFIX).join(rel_path);
+                let rel_path = convert_rel_path_to_forward_slash(&rel_path);
+
+                self.append_path(&src_path, &rel_path)?;
+            }
+        }
+
+        // For delta archives, record the files to be taken from the baseline.
+        let baseline_file_count = self.baseline_files.len();
+        if let Some(baseline) = self.baseline {
+            let manifest = BaselineManifest {
+                baseline_checksum: baseline.checksum().clone(),
+                files: std::mem::take(&mut self.baseline_files),
+            };
+            let manifest = serde_json::to_string_pretty(&manifest)
+                .expect("serializing the baseline manifest never fails");
+            self.append_from_memory(BASELINE_MANIFEST_FILE_NAME, &manifest)?;
+        }
 
         // Finish writing the archive.
-        let encoder = self
+        let writer = self
             .builder
             .into_inner()
             .map_err(ArchiveCreateError::OutputArchiveIo)?;
-        // Finish writing the zstd stream.
-        let buf_writer = encoder
+        // Finish writing the compressed stream.
+        let buf_writer = writer
             .finish()
             .map_err(ArchiveCreateError::OutputArchiveIo)?;
         let writer = buf_writer
             .into_inner()
             .map_err(|err| ArchiveCreateError::OutputArchiveIo(err.into_error()))?;
 
-        Ok((writer, self.added_files.len()))
+        Ok((
+            writer,
+            self.added_files.len(),
+            baseline_file_count,
+            self.stripper,
+        ))
     }
 
     // ---
@@ -255,8 +517,10 @@ impl<'a, W: Write> Archiver<'a, W> {
     fn append_from_memory(&mut self, name: &str, contents: &str) -> Result<(), ArchiveCreateError> {
         let mut header = tar::Header::new_gnu();
         header.set_size(contents.len() as u64);
-        header.set_mtime(self.unix_timestamp);
-        header.set_mode(0o664);
+        header.set_mtime(DETERMINISTIC_MTIME);
+        header.set_mode(0o644);
+        header.set_uid(0);
+        header.set_gid(0);
         header.set_cksum();
 
         self.builder
@@ -273,20 +537,23 @@ impl<'a, W: Write> Archiver<'a, W> {
         rel_path: &Utf8Path,
         src_path: &Utf8Path,
     ) -> Result<(), ArchiveCreateError> {
-        // In case of a symlink pointing to a directory, is_dir is false, but src.is_dir() will return true
-        for entry in
-            src_path
-                .read_dir_utf8()
-                .map_err(|error| ArchiveCreateError::InputFileRead {
-                    path: src_path.to_owned(),
-                    is_dir: Some(true),
-                    error,
-                })?
-        {
-            let entry = entry.map_err(|error| ArchiveCreateError::DirEntryRead {
+        let mut entries = src_path
+            .read_dir_utf8()
+            .map_err(|error| ArchiveCreateError::InputFileRead {
+                path: src_path.to_owned(),
+                is_dir: Some(true),
+                error,
+            })?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|error| ArchiveCreateError::DirEntryRead {
                 path: src_path.to_owned(),
                 error,
             })?;
+        // Directory entries are returned in an arbitrary order, so sort them for reproducibility.
+        entries.sort_unstable_by(|a, b| a.file_name().cmp(b.file_name()));
+
+        // In case of a symlink pointing to a directory, is_dir is false, but src.is_dir() will return true
+        for entry in entries {
             let src = entry.path();
             let file_type =
                 entry
@@ -307,23 +574,105 @@ impl<'a, W: Write> Archiver<'a, W> {
 
     fn append_path(&mut self, src: &Utf8Path, dest: &Utf8Path) -> Result<(), ArchiveCreateError> {
         // Check added_files to ensure we aren't adding duplicate files.
-        if !self.added_files.contains(dest) {
-            self.builder
-                .append_path_with_name(src, dest)
-                .map_err(|error| ArchiveCreateError::InputFileRead {
+        if self.added_files.contains(dest) || self.baseline_files.contains(dest) {
+            return Ok(());
+        }
+
+        // Strip debug info out of files in the target directory, if requested.
+        let stripped = match &mut self.stripper {
+            Some(stripper) if dest.starts_with("target") => stripper.strip(src, dest)?,
+            _ => None,
+        };
+        let src = stripped.as_deref().unwrap_or(src);
+
+        if let Some(baseline) = self.baseline {
+            let unchanged = baseline.contains_unchanged(dest, src).map_err(|error| {
+                ArchiveCreateError::InputFileRead {
                     path: src.to_owned(),
                     is_dir: Some(false),
                     error,
-                })?;
-            self.added_files.insert(dest.into());
+                }
+            })?;
+            if unchanged {
+                self.baseline_files.insert(dest.into());
+                return Ok(());
+            }
         }
+
+        self.builder
+            .append_path_with_name(src, dest)
+            .map_err(|error| ArchiveCreateError::InputFileRead {
+                path: src.to_owned(),
+                is_dir: Some(false),
+                error,
+            })?;
+        self.added_files.insert(dest.into());
         Ok(())
     }
 }
 
+/// The writer a tarball is written to, compressing data as configured.
+enum ArchiveWriter<W: Write> {
+    Zstd(zstd::Encoder<'static, BufWriter<W>>),
+    Gzip(GzEncoder<BufWriter<W>>),
+    None(BufWriter<W>),
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    fn new(compression: ArchiveCompression, writer: W) -> io::Result<Self> {
+        let buf_writer = BufWriter::new(writer);
+        let writer = match compression {
+            ArchiveCompression::Zstd { level } => {
+                let mut encoder = zstd::Encoder::new(buf_writer, level)?;
+                encoder.include_checksum(true)?;
+                // Multithreaded zstd output doesn't depend on the number of threads, so archives
+                // are reproducible across machines.
+                encoder.multithread(num_cpus::get() as u32)?;
+                Self::Zstd(encoder)
+            }
+            ArchiveCompression::Gzip => {
+                Self::Gzip(GzEncoder::new(buf_writer, flate2::Compression::default()))
+            }
+            ArchiveCompression::None => Self::None(buf_writer),
+        };
+        Ok(writer)
+    }
+
+    fn finish(self) -> io::Result<BufWriter<W>> {
+        match self {
+            Self::Zstd(encoder) => encoder.finish(),
+            Self::Gzip(encoder) => encoder.finish(),
+            Self::None(buf_writer) => Ok(buf_writer),
+        }
+    }
+}
+
+impl<W: Write> Write for ArchiveWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Zstd(encoder) => encoder.write(buf),
+            Self::Gzip(encoder) => encoder.write(buf),
+            Self::None(buf_writer) => buf_writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Zstd(encoder) => encoder.flush(),
+            Self::Gzip(encoder) => encoder.flush(),
+            Self::None(buf_writer) => buf_writer.flush(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::list::{RustBuildMeta, RustTestBinary};
+    use maplit::btreeset;
+    use nextest_metadata::{BuildPlatform, RustTestBinaryKind};
+    use std::collections::BTreeMap;
+    use tempfile::TempDir;
 
     #[test]
     fn test_archive_format_autodetect() {
@@ -335,7 +684,156 @@ mod tests {
             ArchiveFormat::autodetect("foo/bar.tar.zst".as_ref()).unwrap(),
             ArchiveFormat::TarZst,
         );
+        assert_eq!(
+            ArchiveFormat::autodetect("foo.tar.gz".as_ref()).unwrap(),
+            ArchiveFormat::TarGz,
+        );
+        assert_eq!(
+            ArchiveFormat::autodetect("foo.tar".as_ref()).unwrap(),
+            ArchiveFormat::Tar,
+        );
         ArchiveFormat::autodetect("foo".as_ref()).unwrap_err();
         ArchiveFormat::autodetect("/".as_ref()).unwrap_err();
     }
+
+    #[test]
+    fn test_archive_format_autodetect_from_contents() {
+        for compression in [
+            ArchiveCompression::Zstd { level: 3 },
+            ArchiveCompression::Gzip,
+            ArchiveCompression::None,
+        ] {
+            let mut builder =
+                tar::Builder::new(ArchiveWriter::new(compression, Vec::new()).unwrap());
+            let mut header = tar::Header::new_gnu();
+            header.set_size(3);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, "target/foo", &b"foo"[..])
+                .unwrap();
+            let contents = builder.into_inner().unwrap().finish().unwrap();
+            let contents = contents.into_inner().unwrap();
+
+            assert_eq!(
+                ArchiveFormat::autodetect_from_contents(&contents),
+                Some(compression.format()),
+                "format detected correctly for {compression}",
+            );
+        }
+
+        assert_eq!(ArchiveFormat::autodetect_from_contents(b"foo"), None);
+        assert_eq!(ArchiveFormat::autodetect_from_contents(&[]), None);
+    }
+
+    #[test]
+    fn test_archive_compression_parse() {
+        let valid = [
+            ("zstd", ArchiveCompression::Zstd { level: 0 }),
+            ("zstd:19", ArchiveCompression::Zstd { level: 19 }),
+            ("zstd:-1", ArchiveCompression::Zstd { level: -1 }),
+            ("gzip", ArchiveCompression::Gzip),
+            ("none", ArchiveCompression::None),
+        ];
+        for (input, expected) in valid {
+            assert_eq!(
+                input.parse::<ArchiveCompression>().unwrap(),
+                expected,
+                "{input} parsed correctly",
+            );
+        }
+
+        let invalid = ["", "zstd:", "zstd:high", "gzip:9", "none:1", "xz"];
+        for input in invalid {
+            input
+                .parse::<ArchiveCompression>()
+                .expect_err(&format!("{input} should fail to parse"));
+        }
+    }
+
+    #[test]
+    fn test_archive_reproducible() {
+        // Create the same files twice, in different orders. (The target directory is recorded in
+        // the binaries metadata, so it must stay the same.)
+        let contents = [
+            ("deps/foo-0123", "foo"),
+            ("deps/bar-4567", "bar"),
+            ("build/foo/out/libfoo.so", "libfoo"),
+            ("build/foo/out/libbar.so", "libbar"),
+            ("build/foo/out/libbaz.so", "libbaz"),
+        ];
+        let temp_dir = TempDir::new().expect("temp dir created");
+        let first = archive_files(temp_dir.path(), contents.iter());
+        std::fs::remove_dir_all(temp_dir.path().join("target")).unwrap();
+        let second = archive_files(temp_dir.path(), contents.iter().rev());
+        assert!(first == second, "archives are byte-for-byte identical");
+
+        let decompressed = zstd::decode_all(first.as_slice()).expect("archive decompressed");
+        let mut archive = tar::Archive::new(io::Cursor::new(decompressed));
+        for entry in archive.entries().unwrap() {
+            let entry = entry.unwrap();
+            let header = entry.header();
+            let path = entry.path().unwrap().into_owned();
+            assert_eq!(
+                header.mtime().unwrap(),
+                DETERMINISTIC_MTIME,
+                "mtime normalized for {path:?}"
+            );
+            assert_eq!(header.uid().unwrap(), 0, "uid normalized for {path:?}");
+            assert_eq!(header.gid().unwrap(), 0, "gid normalized for {path:?}");
+        }
+    }
+
+    fn archive_files<'a>(
+        dir: &std::path::Path,
+        contents: impl Iterator<Item = &'a (&'a str, &'a str)>,
+    ) -> Vec<u8> {
+        let dir: &Utf8Path = dir.try_into().expect("temp dir is valid UTF-8");
+        let target_dir = dir.join("target");
+        let debug_dir = target_dir.join("debug");
+        for (path, contents) in contents {
+            let path = debug_dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, contents).unwrap();
+        }
+
+        let mut rust_build_meta = RustBuildMeta::new(&target_dir, None);
+        rust_build_meta.linked_paths.insert(
+            "debug/build/foo/out".into(),
+            btreeset! { "foo-package-id".to_owned() },
+        );
+        let rust_binaries = ["foo-0123", "bar-4567"]
+            .into_iter()
+            .map(|name| RustTestBinary {
+                id: format!("package::{name}"),
+                path: debug_dir.join("deps").join(name),
+                package_id: "package-id".to_owned(),
+                kind: RustTestBinaryKind::LIB,
+                name: name.to_owned(),
+                build_platform: BuildPlatform::Target,
+                doctest: None,
+                features: vec![],
+                env: BTreeMap::new(),
+            })
+            .collect();
+        let binary_list = BinaryList {
+            rust_build_meta,
+            rust_binaries,
+        };
+
+        let path_mapper = PathMapper::noop();
+        let archiver = Archiver::new(
+            &binary_list,
+            "{}",
+            &path_mapper,
+            ArchiveCompression::default(),
+            dir,
+            &[],
+            None,
+            None,
+            Vec::new(),
+        )
+        .expect("archiver created");
+        let (archive, _, _, _) = archiver.archive().expect("archive created");
+        archive
+    }
 }
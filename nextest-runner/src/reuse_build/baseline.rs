@@ -0,0 +1,212 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Support for delta archives, which only contain the files that changed since a baseline archive.
+
+use super::{
+    fetch::DigestReader, unarchiver::ArchiveReader, ArchiveChecksum, ArchiveFormat,
+    BASELINE_MANIFEST_FILE_NAME, BINARIES_METADATA_FILE_NAME, CARGO_METADATA_FILE_NAME,
+};
+use crate::errors::{ArchiveCreateError, ArchiveReadError};
+use camino::{Utf8Path, Utf8PathBuf};
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    io::{self, Read},
+};
+
+/// The files in an existing archive, used to create a delta archive against it.
+///
+/// Created with [`ArchiveBaseline::read`], and passed in to
+/// [`archive_to_file`](super::archive_to_file).
+#[derive(Clone, Debug)]
+pub struct ArchiveBaseline {
+    path: Utf8PathBuf,
+    checksum: ArchiveChecksum,
+    files: HashMap<Utf8PathBuf, BaselineFile>,
+}
+
+impl ArchiveBaseline {
+    /// Reads the baseline archive at `path`, hashing every file in it.
+    ///
+    /// Delta archives can't be used as baselines.
+    pub fn read(path: &Utf8Path, format: ArchiveFormat) -> Result<Self, ArchiveCreateError> {
+        let map_err = |error| ArchiveCreateError::BaselineRead {
+            path: path.to_owned(),
+            error: Box::new(error),
+        };
+
+        let mut file = fs::File::open(path).map_err(|err| map_err(ArchiveReadError::Io(err)))?;
+        let mut context = digest::Context::new(&digest::SHA256);
+        let mut files = HashMap::new();
+
+        let mut archive_reader =
+            ArchiveReader::new(DigestReader::new(&mut file, Some(&mut context)), format)
+                .map_err(map_err)?;
+        for entry in archive_reader.entries().map_err(map_err)? {
+            let (mut entry, entry_path) = entry.map_err(map_err)?;
+            if entry_path == BASELINE_MANIFEST_FILE_NAME {
+                return Err(ArchiveCreateError::BaselineIsDelta(path.to_owned()));
+            }
+            if !entry.header().entry_type().is_file()
+                || entry_path == BINARIES_METADATA_FILE_NAME
+                || entry_path == CARGO_METADATA_FILE_NAME
+            {
+                continue;
+            }
+
+            let size = entry.size();
+            let sha256 = sha256(&mut entry).map_err(|err| map_err(ArchiveReadError::Io(err)))?;
+            files.insert(entry_path, BaselineFile { size, sha256 });
+        }
+        drop(archive_reader);
+
+        // Read any trailing data after the end of the tarball, so the checksum covers the entire
+        // file.
+        io::copy(
+            &mut DigestReader::new(&mut file, Some(&mut context)),
+            &mut io::sink(),
+        )
+        .map_err(|err| map_err(ArchiveReadError::Io(err)))?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            checksum: ArchiveChecksum::from_context(context),
+            files,
+        })
+    }
+
+    /// Returns the path to the baseline archive.
+    pub fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+
+    /// Returns the checksum of the baseline archive.
+    pub fn checksum(&self) -> &ArchiveChecksum {
+        &self.checksum
+    }
+
+    /// Returns true if the baseline contains a file at `archive_path` with the same contents as
+    /// `src`.
+    pub(super) fn contains_unchanged(
+        &self,
+        archive_path: &Utf8Path,
+        src: &Utf8Path,
+    ) -> io::Result<bool> {
+        let baseline_file = match self.files.get(archive_path) {
+            Some(baseline_file) => baseline_file,
+            None => return Ok(false),
+        };
+        // Only hash the file if the sizes match.
+        let metadata = fs::metadata(src)?;
+        if !metadata.is_file() || metadata.len() != baseline_file.size {
+            return Ok(false);
+        }
+        Ok(sha256(fs::File::open(src)?)? == baseline_file.sha256)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct BaselineFile {
+    size: u64,
+    sha256: [u8; 32],
+}
+
+/// The manifest stored in a delta archive, listing the files to be taken from its baseline.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct BaselineManifest {
+    /// The checksum of the baseline archive.
+    pub(crate) baseline_checksum: ArchiveChecksum,
+
+    /// The paths within the baseline archive of the files that were left out of the delta.
+    pub(crate) files: BTreeSet<Utf8PathBuf>,
+}
+
+fn sha256(mut reader: impl Read) -> io::Result<[u8; 32]> {
+    let mut context = digest::Context::new(&digest::SHA256);
+    io::copy(
+        &mut DigestReader::new(&mut reader, Some(&mut context)),
+        &mut io::sink(),
+    )?;
+    let mut sha256 = [0; 32];
+    sha256.copy_from_slice(context.finish().as_ref());
+    Ok(sha256)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_read_baseline() {
+        let temp_dir = TempDir::new().expect("temp dir created");
+        let temp_dir: &Utf8Path = temp_dir.path().try_into().expect("temp dir is valid UTF-8");
+
+        let baseline_path = temp_dir.join("baseline.tar");
+        write_tar(
+            &baseline_path,
+            &[
+                (BINARIES_METADATA_FILE_NAME, b"{}"),
+                ("target/debug/deps/foo-0123", b"foo"),
+                ("target/debug/deps/bar-4567", b"bar"),
+                ("workspace/fixtures/baz", b"baz"),
+            ],
+        );
+        let baseline =
+            ArchiveBaseline::read(&baseline_path, ArchiveFormat::Tar).expect("baseline read");
+        assert_eq!(
+            baseline.checksum(),
+            &ArchiveChecksum::from_context({
+                let mut context = digest::Context::new(&digest::SHA256);
+                context.update(&fs::read(&baseline_path).unwrap());
+                context
+            }),
+            "checksum covers the whole file",
+        );
+        assert!(
+            !baseline
+                .files
+                .contains_key(Utf8Path::new(BINARIES_METADATA_FILE_NAME)),
+            "metadata files are skipped"
+        );
+
+        let src = temp_dir.join("src");
+        fs::write(&src, b"foo").unwrap();
+        assert!(baseline
+            .contains_unchanged("target/debug/deps/foo-0123".as_ref(), &src)
+            .unwrap());
+        assert!(!baseline
+            .contains_unchanged("target/debug/deps/bar-4567".as_ref(), &src)
+            .unwrap());
+        assert!(!baseline
+            .contains_unchanged("workspace/fixtures/qux".as_ref(), &src)
+            .unwrap());
+        fs::write(&src, b"baz").unwrap();
+        assert!(baseline
+            .contains_unchanged("workspace/fixtures/baz".as_ref(), &src)
+            .unwrap());
+
+        let delta_path = temp_dir.join("delta.tar");
+        write_tar(&delta_path, &[(BASELINE_MANIFEST_FILE_NAME, b"{}")]);
+        match ArchiveBaseline::read(&delta_path, ArchiveFormat::Tar) {
+            Err(ArchiveCreateError::BaselineIsDelta(path)) => assert_eq!(path, delta_path),
+            other => panic!("expected BaselineIsDelta, found {other:?}"),
+        }
+    }
+
+    fn write_tar(path: &Utf8Path, files: &[(&str, &[u8])]) {
+        let mut builder = tar::Builder::new(fs::File::create(path).unwrap());
+        for (name, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o664);
+            header.set_cksum();
+            builder.append_data(&mut header, name, *contents).unwrap();
+        }
+        builder.finish().unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Re-signing binaries extracted from archives on macOS.
+//!
+//! On Apple Silicon, every binary must be signed, and the linker produces ad-hoc signatures that
+//! can be invalidated while binaries are moved across machines. Binaries with invalid signatures
+//! (or a quarantine attribute, if the archive was downloaded through a browser) are killed by the
+//! kernel, so they're re-signed after being extracted.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{
+    fs,
+    io::{self, Read},
+    process::Command,
+};
+
+/// Finds all Mach-O files within `dir`, recursively. Symlinks are not followed.
+pub(super) fn find_mach_o_files(dir: &Utf8Path) -> io::Result<Vec<Utf8PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        for entry in dir.read_dir_utf8()? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                dirs.push(entry.path().to_owned());
+            } else if file_type.is_file() && is_mach_o(entry.path())? {
+                files.push(entry.path().to_owned());
+            }
+        }
+    }
+    files.sort_unstable();
+    Ok(files)
+}
+
+fn is_mach_o(path: &Utf8Path) -> io::Result<bool> {
+    // 32 and 64-bit Mach-O files in either byte order, and universal ("fat") binaries.
+    const MACH_O_MAGICS: &[[u8; 4]] = &[
+        [0xfe, 0xed, 0xfa, 0xce],
+        [0xfe, 0xed, 0xfa, 0xcf],
+        [0xce, 0xfa, 0xed, 0xfe],
+        [0xcf, 0xfa, 0xed, 0xfe],
+        [0xca, 0xfe, 0xba, 0xbe],
+    ];
+
+    let mut magic = [0; 4];
+    match fs::File::open(path)?.read_exact(&mut magic) {
+        Ok(()) => Ok(MACH_O_MAGICS.contains(&magic)),
+        // Files shorter than the magic aren't Mach-O files.
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+/// Clears the quarantine attribute from `files`, and re-signs them with ad-hoc signatures.
+///
+/// Failures are logged as warnings rather than returned, since binaries with valid signatures
+/// still work.
+pub(super) fn resign(files: &[Utf8PathBuf]) {
+    if files.is_empty() {
+        return;
+    }
+
+    // Missing attributes cause errors, so ignore the result.
+    let _ = Command::new("xattr")
+        .args(["-d", "com.apple.quarantine"])
+        .args(files)
+        .output();
+
+    match Command::new("codesign")
+        .args(["--force", "--sign", "-"])
+        .args(files)
+        .output()
+    {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => {
+            log::warn!(
+                target: "nextest-runner",
+                "re-signing extracted binaries failed with {}, tests might be killed on startup:\n{}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim(),
+            );
+        }
+        Err(error) => {
+            log::warn!(
+                target: "nextest-runner",
+                "error running `codesign` to re-sign extracted binaries, \
+                 tests might be killed on startup: {error}",
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_mach_o_files() {
+        let temp_dir = TempDir::new().expect("temp dir created");
+        let temp_dir: &Utf8Path = temp_dir.path().try_into().expect("temp dir is valid UTF-8");
+
+        let files: &[(&str, &[u8])] = &[
+            ("debug/deps/foo-0123", &[0xcf, 0xfa, 0xed, 0xfe, 0x07, 0x00]),
+            ("debug/deps/libbar.dylib", &[0xca, 0xfe, 0xba, 0xbe]),
+            ("debug/deps/foo-0123.d", b"debug/deps/foo-0123: src/lib.rs"),
+            ("debug/baz", b"\x7fELF\x02\x01"),
+            ("debug/short", &[0xcf, 0xfa]),
+        ];
+        for (path, contents) in files {
+            let path = temp_dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, contents).unwrap();
+        }
+
+        assert_eq!(
+            find_mach_o_files(temp_dir).expect("files found"),
+            vec![
+                temp_dir.join("debug/deps/foo-0123"),
+                temp_dir.join("debug/deps/libbar.dylib"),
+            ],
+        );
+    }
+}
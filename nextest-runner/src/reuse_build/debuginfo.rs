@@ -0,0 +1,214 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Support for stripping debug info out of binaries while archiving them.
+
+use super::{ArchiveCompression, ArchiveFormat};
+use crate::errors::ArchiveCreateError;
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::Deserialize;
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, Read},
+    process::Command,
+};
+use tempfile::TempDir;
+
+/// What to do with debug info in binaries while archiving them, configured through
+/// `archive.debuginfo` in a profile.
+///
+/// Debug info is only stripped from ELF files, using `objcopy` (or the program in the `OBJCOPY`
+/// environment variable). Other files, e.g. Mach-O and PE binaries that keep their debug info in
+/// separate files anyway, are archived as-is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum ArchiveDebuginfo {
+    /// Keep debug info in binaries.
+    Keep,
+
+    /// Strip debug info from binaries.
+    Strip,
+
+    /// Strip debug info from binaries, and write it out to a separate debuginfo archive.
+    ///
+    /// Stripped binaries are linked to their debug info with a `.gnu_debuglink` section, so
+    /// backtraces are symbolized if the debuginfo archive is extracted alongside the main archive.
+    Split,
+}
+
+impl ArchiveDebuginfo {
+    /// Returns the default path for the debuginfo archive written out alongside `archive_file`.
+    ///
+    /// For example, the debuginfo archive for `my-archive.tar.zst` is
+    /// `my-archive-debuginfo.tar.zst`.
+    pub fn default_debuginfo_file(
+        archive_file: &Utf8Path,
+        compression: ArchiveCompression,
+    ) -> Utf8PathBuf {
+        let file_name = archive_file.file_name().unwrap_or("archive");
+        let (stem, extension) = match ArchiveFormat::autodetect(archive_file) {
+            Ok(_) => {
+                let (extension, _) = ArchiveFormat::SUPPORTED_FORMATS
+                    .iter()
+                    .find(|(extension, _)| file_name.ends_with(extension))
+                    .expect("autodetect succeeded so an extension matches");
+                (&file_name[..file_name.len() - extension.len()], *extension)
+            }
+            Err(_) => (file_name, compression.format().extension()),
+        };
+        archive_file.with_file_name(format!("{stem}-debuginfo{extension}"))
+    }
+}
+
+impl Default for ArchiveDebuginfo {
+    fn default() -> Self {
+        Self::Keep
+    }
+}
+
+/// Strips debug info out of files on their way into an archive.
+pub(super) struct DebuginfoStripper {
+    split: bool,
+    objcopy: OsString,
+    temp_dir: TempDir,
+    next_id: usize,
+    // Files containing debug info split out of binaries, as (path within the archive, path on
+    // disk) pairs.
+    debug_files: Vec<(Utf8PathBuf, Utf8PathBuf)>,
+}
+
+impl DebuginfoStripper {
+    /// Creates a new stripper, or returns `None` if debug info should be kept.
+    pub(super) fn new(debuginfo: ArchiveDebuginfo) -> Result<Option<Self>, ArchiveCreateError> {
+        let split = match debuginfo {
+            ArchiveDebuginfo::Keep => return Ok(None),
+            ArchiveDebuginfo::Strip => false,
+            ArchiveDebuginfo::Split => true,
+        };
+        let temp_dir = tempfile::Builder::new()
+            .prefix("nextest-debuginfo-")
+            .tempdir()
+            .map_err(ArchiveCreateError::DebuginfoTempDirCreate)?;
+        let objcopy = std::env::var_os("OBJCOPY").unwrap_or_else(|| "objcopy".into());
+
+        Ok(Some(Self {
+            split,
+            objcopy,
+            temp_dir,
+            next_id: 0,
+            debug_files: Vec::new(),
+        }))
+    }
+
+    /// Strips debug info out of `src`, which will be written to `dest` within the archive.
+    ///
+    /// Returns the path to the stripped file, or `None` if `src` isn't an ELF file.
+    pub(super) fn strip(
+        &mut self,
+        src: &Utf8Path,
+        dest: &Utf8Path,
+    ) -> Result<Option<Utf8PathBuf>, ArchiveCreateError> {
+        let is_elf = is_elf(src).map_err(|error| ArchiveCreateError::InputFileRead {
+            path: src.to_owned(),
+            is_dir: Some(false),
+            error,
+        })?;
+        if !is_elf {
+            return Ok(None);
+        }
+
+        let file_name = dest.file_name().expect("archive paths have a file name");
+        // Files from different directories can have the same name, so give each one its own
+        // directory. (The debug link only records the file name of the debug info file, so it must
+        // be named the same as in the archive.)
+        let out_dir = self.temp_dir.path().join(self.next_id.to_string());
+        self.next_id += 1;
+        let out_dir: Utf8PathBuf = out_dir
+            .try_into()
+            .expect("temporary directory paths are valid UTF-8");
+        fs::create_dir(&out_dir).map_err(ArchiveCreateError::DebuginfoTempDirCreate)?;
+        let stripped = out_dir.join(file_name);
+
+        let mut command = Command::new(&self.objcopy);
+        if self.split {
+            let debug_file_name = format!("{file_name}.debug");
+            let debug_file = out_dir.join(&debug_file_name);
+            self.run_objcopy(
+                src,
+                Command::new(&self.objcopy)
+                    .arg("--only-keep-debug")
+                    .arg(src)
+                    .arg(&debug_file),
+            )?;
+            command.arg(format!("--add-gnu-debuglink={debug_file}"));
+            self.debug_files
+                .push((dest.with_file_name(debug_file_name), debug_file));
+        }
+        self.run_objcopy(src, command.arg("--strip-debug").arg(src).arg(&stripped))?;
+
+        Ok(Some(stripped))
+    }
+
+    /// Returns the files containing debug info split out of binaries, as (path within the archive,
+    /// path on disk) pairs, along with the temporary directory they're stored in.
+    pub(super) fn into_debug_files(self) -> (TempDir, Vec<(Utf8PathBuf, Utf8PathBuf)>) {
+        (self.temp_dir, self.debug_files)
+    }
+
+    fn run_objcopy(&self, src: &Utf8Path, command: &mut Command) -> Result<(), ArchiveCreateError> {
+        let output = command
+            .output()
+            .map_err(|error| ArchiveCreateError::ObjcopyExec {
+                objcopy: self.objcopy.to_string_lossy().into_owned(),
+                error,
+            })?;
+        if !output.status.success() {
+            return Err(ArchiveCreateError::ObjcopyFailed {
+                path: src.to_owned(),
+                status: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn is_elf(path: &Utf8Path) -> io::Result<bool> {
+    const ELF_MAGIC: &[u8] = b"\x7fELF";
+
+    let mut magic = Vec::with_capacity(ELF_MAGIC.len());
+    fs::File::open(path)?
+        .take(ELF_MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    Ok(magic == ELF_MAGIC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_debuginfo_file() {
+        let zstd = ArchiveCompression::default();
+        let cases = [
+            ("my-archive.tar.zst", zstd, "my-archive-debuginfo.tar.zst"),
+            ("foo/bar.tgz", zstd, "foo/bar-debuginfo.tgz"),
+            ("bar.tar", zstd, "bar-debuginfo.tar"),
+            ("archive", zstd, "archive-debuginfo.tar.zst"),
+            (
+                "archive.bin",
+                ArchiveCompression::Gzip,
+                "archive.bin-debuginfo.tar.gz",
+            ),
+        ];
+        for (archive_file, compression, expected) in cases {
+            assert_eq!(
+                ArchiveDebuginfo::default_debuginfo_file(archive_file.as_ref(), compression),
+                expected,
+                "default debuginfo file for {archive_file} is correct",
+            );
+        }
+    }
+}
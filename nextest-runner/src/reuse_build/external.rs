@@ -0,0 +1,188 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Support for test binaries built outside of Cargo.
+
+use super::{MetadataOrPath, MetadataWithRemap, ReuseBuildInfo};
+use crate::{errors::ExternalBinariesError, list::BinaryList};
+use guppy::graph::PackageGraph;
+use nextest_metadata::ExternalBinaryListSummary;
+use serde_json::json;
+
+impl ReuseBuildInfo {
+    /// Constructs a [`ReuseBuildInfo`] for test binaries built outside of Cargo.
+    ///
+    /// The package graph is constructed out of the packages in the list, so Cargo doesn't need to
+    /// be invoked.
+    pub fn from_external(
+        summary: ExternalBinaryListSummary,
+    ) -> Result<Self, ExternalBinariesError> {
+        if summary.format_version != ExternalBinaryListSummary::FORMAT_VERSION {
+            return Err(ExternalBinariesError::UnsupportedFormatVersion {
+                format_version: summary.format_version,
+                supported: ExternalBinaryListSummary::FORMAT_VERSION,
+            });
+        }
+        for (binary_id, binary) in &summary.binary_list.rust_binaries {
+            if !summary.packages.contains_key(&binary.package_id) {
+                return Err(ExternalBinariesError::UnknownPackage {
+                    binary_id: binary_id.clone(),
+                    package_id: binary.package_id.clone(),
+                });
+            }
+        }
+        if let Some(binary_id) = summary
+            .env
+            .keys()
+            .find(|binary_id| !summary.binary_list.rust_binaries.contains_key(*binary_id))
+        {
+            return Err(ExternalBinariesError::UnknownBinary {
+                binary_id: binary_id.clone(),
+            });
+        }
+
+        let cargo_metadata_json = external_cargo_metadata(&summary);
+        let graph = PackageGraph::from_json(&cargo_metadata_json)
+            .map_err(ExternalBinariesError::PackageGraph)?;
+
+        let mut env = summary.env;
+        let mut binary_list = BinaryList::from_summary(summary.binary_list);
+        for binary in &mut binary_list.rust_binaries {
+            if let Some(binary_env) = env.remove(&binary.id) {
+                binary.env = binary_env;
+            }
+        }
+
+        Ok(Self {
+            cargo_metadata: Some(MetadataWithRemap {
+                metadata: MetadataOrPath::metadata((cargo_metadata_json, graph)),
+                remap: None,
+            }),
+            binaries_metadata: Some(MetadataWithRemap {
+                metadata: MetadataOrPath::metadata(binary_list),
+                remap: None,
+            }),
+            external: true,
+            ..Default::default()
+        })
+    }
+}
+
+/// Synthesizes `cargo metadata` output for the packages in an external binary list.
+///
+/// Each package is a workspace member with no dependencies or targets, and a manifest path within
+/// its directory. The manifest doesn't need to exist.
+fn external_cargo_metadata(summary: &ExternalBinaryListSummary) -> String {
+    let packages: Vec<_> = summary
+        .packages
+        .iter()
+        .map(|(package_id, package)| {
+            json!({
+                "name": package.name,
+                "version": package.version,
+                "id": package_id,
+                "dependencies": [],
+                "targets": [],
+                "features": {},
+                "manifest_path": summary.workspace_root.join(&package.path).join("Cargo.toml"),
+            })
+        })
+        .collect();
+
+    json!({
+        "packages": packages,
+        "workspace_members": summary.packages.keys().collect::<Vec<_>>(),
+        "resolve": null,
+        "workspace_root": summary.workspace_root,
+        "target_directory": summary.binary_list.rust_build_meta.target_directory,
+        "version": 1,
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_from_external() {
+        let summary: ExternalBinaryListSummary = serde_json::from_str(indoc! {r#"
+            {
+                "format-version": 1,
+                "workspace-root": "/fake/workspace",
+                "packages": {
+                    "//fake/lib:lib": { "name": "fake-lib", "path": "fake/lib" }
+                },
+                "rust-build-meta": {
+                    "target-directory": "/fake/buck-out",
+                    "base-output-directories": [],
+                    "non-test-binaries": {},
+                    "linked-paths": ["/fake/buck-out/libs"],
+                    "target-triple": "x86_64-unknown-linux-gnu"
+                },
+                "rust-binaries": {
+                    "fake-lib": {
+                        "binary-id": "fake-lib",
+                        "binary-name": "fake-lib-test",
+                        "package-id": "//fake/lib:lib",
+                        "kind": "lib",
+                        "binary-path": "/fake/buck-out/fake-lib-test",
+                        "build-platform": "target"
+                    }
+                },
+                "env": {
+                    "fake-lib": { "FIXTURES_DIR": "/fake/workspace/fixtures" }
+                }
+            }
+        "#})
+        .expect("valid external binary list");
+
+        let info = ReuseBuildInfo::from_external(summary.clone()).expect("valid external binaries");
+        assert!(info.is_external(), "build info is for external binaries");
+
+        let graph = match info.cargo_metadata() {
+            Some(MetadataOrPath::Metadata(graph_data)) => &graph_data.1,
+            other => panic!("expected package graph, found {other:?}"),
+        };
+        let package = graph
+            .metadata(&guppy::PackageId::new("//fake/lib:lib"))
+            .expect("package is in graph");
+        assert_eq!(package.name(), "fake-lib");
+        assert_eq!(package.version().to_string(), "0.0.0");
+        assert_eq!(
+            package.manifest_path(),
+            "/fake/workspace/fake/lib/Cargo.toml"
+        );
+        assert!(package.in_workspace(), "package is a workspace member");
+
+        let binary_list = match info.binaries_metadata() {
+            Some(MetadataOrPath::Metadata(binary_list)) => binary_list,
+            other => panic!("expected binary list, found {other:?}"),
+        };
+        assert_eq!(binary_list.rust_binaries.len(), 1);
+        assert_eq!(
+            binary_list.rust_binaries[0]
+                .env
+                .get("FIXTURES_DIR")
+                .map(String::as_str),
+            Some("/fake/workspace/fixtures"),
+        );
+
+        let mut unknown_package = summary.clone();
+        unknown_package.packages.clear();
+        let error = ReuseBuildInfo::from_external(unknown_package).expect_err("unknown package");
+        assert!(
+            matches!(error, ExternalBinariesError::UnknownPackage { .. }),
+            "error is about the package: {error}"
+        );
+
+        let mut unknown_binary = summary;
+        unknown_binary.binary_list.rust_binaries.clear();
+        let error = ReuseBuildInfo::from_external(unknown_binary).expect_err("unknown binary");
+        assert!(
+            matches!(error, ExternalBinariesError::UnknownBinary { .. }),
+            "error is about the binary: {error}"
+        );
+    }
+}
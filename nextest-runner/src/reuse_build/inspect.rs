@@ -0,0 +1,434 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Inspecting the contents of archives without extracting them.
+
+use super::{
+    baseline::BaselineManifest, unarchiver::ArchiveReader, ArchiveChecksum, ArchiveFormat,
+    BASELINE_MANIFEST_FILE_NAME, BINARIES_METADATA_FILE_NAME, CARGO_METADATA_FILE_NAME,
+    WORKSPACE_ARCHIVE_PREFIX,
+};
+use crate::{
+    errors::{ArchiveReadError, WriteTestListError},
+    helpers::convert_rel_path_to_forward_slash,
+    list::{OutputFormat, Styles},
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use nextest_metadata::{ArchiveFileKind, ArchiveFileSummary, ArchiveSummary, BinaryListSummary};
+use owo_colors::OwoColorize;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    io::{self, Read, Write},
+};
+
+/// The contents of a nextest archive, read without extracting it.
+#[derive(Clone, Debug)]
+pub struct ArchiveContents {
+    archive_file: Utf8PathBuf,
+    binary_list: BinaryListSummary,
+    archive_size: u64,
+    baseline_checksum: Option<ArchiveChecksum>,
+    files: BTreeMap<Utf8PathBuf, ArchiveFileSummary>,
+}
+
+impl ArchiveContents {
+    /// Reads the archive at `archive_file`.
+    pub fn read(archive_file: &Utf8Path, format: ArchiveFormat) -> Result<Self, ArchiveReadError> {
+        let file = fs::File::open(archive_file).map_err(ArchiveReadError::Io)?;
+        let archive_size = file.metadata().map_err(ArchiveReadError::Io)?.len();
+
+        let mut binary_list = None;
+        let mut baseline_checksum = None;
+        let mut sizes = Vec::new();
+
+        let mut archive_reader = ArchiveReader::new(file, format)?;
+        for entry in archive_reader.entries()? {
+            let (mut entry, path) = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            if path == BINARIES_METADATA_FILE_NAME {
+                let mut json = String::new();
+                entry
+                    .read_to_string(&mut json)
+                    .map_err(ArchiveReadError::Io)?;
+                let summary: BinaryListSummary = serde_json::from_str(&json).map_err(|error| {
+                    ArchiveReadError::MetadataDeserializeError {
+                        path: Utf8Path::new(BINARIES_METADATA_FILE_NAME),
+                        error,
+                    }
+                })?;
+                binary_list = Some(summary);
+            } else if path == BASELINE_MANIFEST_FILE_NAME {
+                let manifest: BaselineManifest =
+                    serde_json::from_reader(&mut entry).map_err(|error| {
+                        ArchiveReadError::MetadataDeserializeError {
+                            path: Utf8Path::new(BASELINE_MANIFEST_FILE_NAME),
+                            error,
+                        }
+                    })?;
+                baseline_checksum = Some(manifest.baseline_checksum);
+            }
+            sizes.push((path, entry.size()));
+        }
+
+        let binary_list = binary_list.ok_or(ArchiveReadError::MetadataFileNotFound(
+            Utf8Path::new(BINARIES_METADATA_FILE_NAME),
+        ))?;
+
+        let classifier = FileClassifier::new(&binary_list);
+        let files = sizes
+            .into_iter()
+            .map(|(path, size)| {
+                let kind = classifier.classify(&path);
+                (path, ArchiveFileSummary { kind, size })
+            })
+            .collect();
+
+        Ok(Self {
+            archive_file: archive_file.to_owned(),
+            binary_list,
+            archive_size,
+            baseline_checksum,
+            files,
+        })
+    }
+
+    /// Returns the metadata about test binaries stored in the archive.
+    pub fn binary_list(&self) -> &BinaryListSummary {
+        &self.binary_list
+    }
+
+    /// For delta archives, returns the checksum of the baseline archive.
+    pub fn baseline_checksum(&self) -> Option<&ArchiveChecksum> {
+        self.baseline_checksum.as_ref()
+    }
+
+    /// Returns the total size of the files in the archive once extracted, in bytes.
+    pub fn total_size(&self) -> u64 {
+        self.files.values().map(|file| file.size).sum()
+    }
+
+    /// Returns a serializable summary of the archive.
+    pub fn to_summary(&self) -> ArchiveSummary {
+        ArchiveSummary {
+            binary_list: self.binary_list.clone(),
+            archive_size: self.archive_size,
+            total_size: self.total_size(),
+            baseline_checksum: self
+                .baseline_checksum
+                .as_ref()
+                .map(|checksum| checksum.to_string()),
+            files: self.files.clone(),
+        }
+    }
+
+    /// Outputs a description of the archive in the given format.
+    pub fn write(
+        &self,
+        output_format: OutputFormat,
+        writer: impl Write,
+        colorize: bool,
+    ) -> Result<(), WriteTestListError> {
+        match output_format {
+            OutputFormat::Human { verbose } => self
+                .write_human(writer, verbose, colorize)
+                .map_err(WriteTestListError::Io),
+            OutputFormat::Serializable(format) => format
+                .to_writer(&self.to_summary(), writer)
+                .map_err(WriteTestListError::Json),
+        }
+    }
+
+    fn write_human(&self, mut writer: impl Write, verbose: bool, colorize: bool) -> io::Result<()> {
+        let mut styles = Styles::default();
+        if colorize {
+            styles.colorize();
+        }
+        let build_meta = &self.binary_list.rust_build_meta;
+
+        writeln!(
+            writer,
+            "{} {}",
+            "archive:".style(styles.field),
+            self.archive_file
+        )?;
+        writeln!(
+            writer,
+            "{} {} ({} extracted)",
+            "size:".style(styles.field),
+            format_size(self.archive_size),
+            format_size(self.total_size()),
+        )?;
+        if let Some(checksum) = &self.baseline_checksum {
+            writeln!(
+                writer,
+                "{} delta against {}",
+                "baseline:".style(styles.field),
+                checksum
+            )?;
+        }
+        writeln!(
+            writer,
+            "{} {}",
+            "target triple:".style(styles.field),
+            build_meta.target_triple
+        )?;
+        if let Some(rustc_version) = &build_meta.rustc_version {
+            writeln!(
+                writer,
+                "{} {}",
+                "rustc version:".style(styles.field),
+                rustc_version
+            )?;
+        }
+        if let Some(features) = &build_meta.features {
+            writeln!(writer, "{} {}", "features:".style(styles.field), features)?;
+        }
+        if let Some(profile) = &build_meta.profile {
+            writeln!(writer, "{} {}", "profile:".style(styles.field), profile)?;
+        }
+
+        writeln!(
+            writer,
+            "{} {}",
+            "test binaries:".style(styles.field),
+            self.binary_list.rust_binaries.len()
+        )?;
+        for (binary_id, binary) in &self.binary_list.rust_binaries {
+            let archive_path = target_archive_path(
+                binary
+                    .binary_path
+                    .strip_prefix(&build_meta.target_directory)
+                    .unwrap_or(&binary.binary_path),
+            );
+            match self.files.get(&archive_path) {
+                Some(file) => write!(
+                    writer,
+                    "  {} ({})",
+                    binary_id.style(styles.binary_id),
+                    format_size(file.size),
+                )?,
+                None => write!(
+                    writer,
+                    "  {} (in baseline)",
+                    binary_id.style(styles.binary_id),
+                )?,
+            }
+            if verbose {
+                write!(writer, ": {archive_path}")?;
+            }
+            writeln!(writer)?;
+        }
+
+        for (kind, name) in [
+            (ArchiveFileKind::NON_TEST_BINARY, "non-test binaries:"),
+            (ArchiveFileKind::LINKED_PATH, "linked path files:"),
+            (ArchiveFileKind::WORKSPACE, "included files:"),
+            (ArchiveFileKind::OTHER, "other files:"),
+        ] {
+            let files: Vec<_> = self
+                .files
+                .iter()
+                .filter(|(_, file)| file.kind == kind)
+                .collect();
+            if files.is_empty() {
+                continue;
+            }
+            let size = files.iter().map(|(_, file)| file.size).sum();
+            writeln!(
+                writer,
+                "{} {} ({})",
+                name.style(styles.field),
+                files.len(),
+                format_size(size),
+            )?;
+            // Files included from the workspace are always listed out, since they're chosen by
+            // the user.
+            if verbose || kind == ArchiveFileKind::WORKSPACE {
+                for (path, file) in files {
+                    writeln!(writer, "  {} ({})", path, format_size(file.size))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Figures out the kind of each file in an archive, based on its binary list.
+struct FileClassifier {
+    test_binaries: HashSet<Utf8PathBuf>,
+    non_test_binaries: HashSet<Utf8PathBuf>,
+    linked_paths: HashSet<Utf8PathBuf>,
+}
+
+impl FileClassifier {
+    fn new(binary_list: &BinaryListSummary) -> Self {
+        let build_meta = &binary_list.rust_build_meta;
+        let test_binaries = binary_list
+            .rust_binaries
+            .values()
+            .filter_map(|binary| {
+                let rel_path = binary
+                    .binary_path
+                    .strip_prefix(&build_meta.target_directory)
+                    .ok()?;
+                Some(target_archive_path(rel_path))
+            })
+            .collect();
+        let non_test_binaries = build_meta
+            .non_test_binaries
+            .values()
+            .flatten()
+            .map(|binary| target_archive_path(&binary.path))
+            .collect();
+        let linked_paths = build_meta
+            .linked_paths
+            .iter()
+            .map(|linked_path| target_archive_path(linked_path))
+            .collect();
+
+        Self {
+            test_binaries,
+            non_test_binaries,
+            linked_paths,
+        }
+    }
+
+    fn classify(&self, path: &Utf8Path) -> ArchiveFileKind {
+        if path == BINARIES_METADATA_FILE_NAME
+            || path == CARGO_METADATA_FILE_NAME
+            || path == BASELINE_MANIFEST_FILE_NAME
+        {
+            ArchiveFileKind::METADATA
+        } else if path.starts_with(WORKSPACE_ARCHIVE_PREFIX) {
+            ArchiveFileKind::WORKSPACE
+        } else if self.test_binaries.contains(path) {
+            ArchiveFileKind::TEST_BINARY
+        } else if self.non_test_binaries.contains(path) {
+            ArchiveFileKind::NON_TEST_BINARY
+        } else if path
+            .parent()
+            .map_or(false, |parent| self.linked_paths.contains(parent))
+        {
+            // Only the top-level files in linked paths are archived.
+            ArchiveFileKind::LINKED_PATH
+        } else {
+            ArchiveFileKind::OTHER
+        }
+    }
+}
+
+/// Returns the path within an archive of a file in the target directory.
+fn target_archive_path(rel_path: &Utf8Path) -> Utf8PathBuf {
+    convert_rel_path_to_forward_slash(&Utf8Path::new("target").join(rel_path))
+}
+
+fn format_size(size: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+
+    if size < 1024 {
+        return format!("{size} B");
+    }
+    let mut size = size as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for next_unit in &UNITS[1..] {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next_unit;
+    }
+    format!("{size:.1} {unit}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nextest_metadata::{
+        BuildPlatform, RustBuildMetaSummary, RustNonTestBinaryKind, RustNonTestBinarySummary,
+        RustTestBinaryKind, RustTestBinarySummary,
+    };
+
+    #[test]
+    fn test_classify() {
+        let binary_list = BinaryListSummary {
+            rust_build_meta: RustBuildMetaSummary {
+                target_directory: "/fake/target".into(),
+                base_output_directories: Default::default(),
+                non_test_binaries: [(
+                    "my-package".to_owned(),
+                    [RustNonTestBinarySummary {
+                        name: "my-bin".to_owned(),
+                        kind: RustNonTestBinaryKind::BIN_EXE,
+                        path: "debug/my-bin".into(),
+                    }]
+                    .into_iter()
+                    .collect(),
+                )]
+                .into_iter()
+                .collect(),
+                linked_paths: ["debug/build/my-package-0123/out".into()]
+                    .into_iter()
+                    .collect(),
+                linked_path_packages: Default::default(),
+                target_triple: "x86_64-unknown-linux-gnu".to_owned(),
+                target_spec_file: None,
+                rustc_version: None,
+                features: None,
+                profile: None,
+            },
+            rust_binaries: [(
+                "my-package".to_owned(),
+                RustTestBinarySummary {
+                    binary_id: "my-package".to_owned(),
+                    binary_name: "my-package".to_owned(),
+                    package_id: "my-package 0.1.0".to_owned(),
+                    kind: RustTestBinaryKind::LIB,
+                    binary_path: "/fake/target/debug/deps/my_package-4567".into(),
+                    build_platform: BuildPlatform::Target,
+                    doctest: None,
+                    features: vec![],
+                },
+            )]
+            .into_iter()
+            .collect(),
+        };
+
+        let classifier = FileClassifier::new(&binary_list);
+        let cases = [
+            (BINARIES_METADATA_FILE_NAME, ArchiveFileKind::METADATA),
+            (BASELINE_MANIFEST_FILE_NAME, ArchiveFileKind::METADATA),
+            (
+                "target/debug/deps/my_package-4567",
+                ArchiveFileKind::TEST_BINARY,
+            ),
+            ("target/debug/my-bin", ArchiveFileKind::NON_TEST_BINARY),
+            (
+                "target/debug/build/my-package-0123/out/libfoo.so",
+                ArchiveFileKind::LINKED_PATH,
+            ),
+            ("workspace/fixtures/foo.txt", ArchiveFileKind::WORKSPACE),
+            ("target/my-target.json", ArchiveFileKind::OTHER),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                classifier.classify(Utf8Path::new(path)),
+                expected,
+                "{path} classified correctly"
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.0 KiB");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+}
@@ -0,0 +1,321 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::list::BinaryList;
+use guppy::{graph::PackageGraph, PackageId};
+use nextest_metadata::{FeatureSelectionSummary, RustcVersionSummary};
+use std::{collections::BTreeSet, fmt};
+
+/// The build configuration for the current invocation, to be compared against a reused build.
+///
+/// Each field is `None` if it isn't known or shouldn't be checked.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CurrentBuildConfig {
+    /// The version of rustc available to the current invocation.
+    pub rustc_version: Option<RustcVersionSummary>,
+
+    /// The Cargo feature selection passed in to the current invocation.
+    pub features: Option<FeatureSelectionSummary>,
+
+    /// The Cargo profile passed in to the current invocation.
+    pub profile: Option<String>,
+
+    /// The target triple passed in to the current invocation.
+    pub target_triple: Option<String>,
+}
+
+/// A difference between a reused build and the current invocation.
+///
+/// Returned by [`ReuseBuildMismatch::compute`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReuseBuildMismatch {
+    /// The build was produced by a different version of rustc.
+    RustcVersion {
+        /// The version of rustc the build was produced by.
+        build: RustcVersionSummary,
+
+        /// The version of rustc available to the current invocation.
+        current: RustcVersionSummary,
+    },
+
+    /// The build was produced with a different Cargo feature selection.
+    Features {
+        /// The feature selection the build was produced with.
+        build: FeatureSelectionSummary,
+
+        /// The feature selection passed in to the current invocation.
+        current: FeatureSelectionSummary,
+    },
+
+    /// The build was produced with a different Cargo profile.
+    Profile {
+        /// The profile the build was produced with.
+        build: String,
+
+        /// The profile passed in to the current invocation.
+        current: String,
+    },
+
+    /// The build was produced for a different target triple.
+    TargetTriple {
+        /// The target triple the build was produced for.
+        build: String,
+
+        /// The target triple passed in to the current invocation.
+        current: String,
+    },
+
+    /// Test binaries in the build belong to packages that aren't in the current workspace.
+    MissingPackages {
+        /// The IDs of the missing packages.
+        package_ids: BTreeSet<String>,
+    },
+}
+
+impl ReuseBuildMismatch {
+    /// Compares a reused build against the current build configuration and package graph.
+    ///
+    /// Information missing on either side, e.g. because the build was produced by an older
+    /// version of nextest, is not compared.
+    pub fn compute(
+        binary_list: &BinaryList,
+        graph: &PackageGraph,
+        current: &CurrentBuildConfig,
+    ) -> Vec<Self> {
+        let rust_build_meta = &binary_list.rust_build_meta;
+        let mut mismatches = Vec::new();
+
+        if let (Some(build), Some(current)) =
+            (&rust_build_meta.target_triple, &current.target_triple)
+        {
+            if &build.triple != current {
+                mismatches.push(Self::TargetTriple {
+                    build: build.triple.clone(),
+                    current: current.clone(),
+                });
+            }
+        }
+
+        let package_ids: BTreeSet<_> = binary_list
+            .rust_binaries
+            .iter()
+            .filter(|binary| {
+                graph
+                    .metadata(&PackageId::new(binary.package_id.clone()))
+                    .is_err()
+            })
+            .map(|binary| binary.package_id.clone())
+            .collect();
+        if !package_ids.is_empty() {
+            mismatches.push(Self::MissingPackages { package_ids });
+        }
+
+        if let (Some(build), Some(current)) =
+            (&rust_build_meta.rustc_version, &current.rustc_version)
+        {
+            if build != current {
+                mismatches.push(Self::RustcVersion {
+                    build: build.clone(),
+                    current: current.clone(),
+                });
+            }
+        }
+        if let (Some(build), Some(current)) = (&rust_build_meta.features, &current.features) {
+            if build != current {
+                mismatches.push(Self::Features {
+                    build: build.clone(),
+                    current: current.clone(),
+                });
+            }
+        }
+        if let (Some(build), Some(current)) = (&rust_build_meta.profile, &current.profile) {
+            if build != current {
+                mismatches.push(Self::Profile {
+                    build: build.clone(),
+                    current: current.clone(),
+                });
+            }
+        }
+
+        mismatches
+    }
+
+    /// Returns true if this mismatch means that tests can't be run from the reused build at all.
+    ///
+    /// Other mismatches might only cause tests to behave differently.
+    pub fn is_incompatible(&self) -> bool {
+        match self {
+            Self::TargetTriple { .. } | Self::MissingPackages { .. } => true,
+            Self::RustcVersion { .. } | Self::Features { .. } | Self::Profile { .. } => false,
+        }
+    }
+}
+
+impl fmt::Display for ReuseBuildMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RustcVersion { build, current } => {
+                write!(
+                    f,
+                    "built with rustc {build}, but current rustc is {current}"
+                )
+            }
+            Self::Features { build, current } => {
+                write!(f, "built with {build}, but {current} specified")
+            }
+            Self::Profile { build, current } => {
+                write!(
+                    f,
+                    "built with profile `{build}`, but profile `{current}` specified"
+                )
+            }
+            Self::TargetTriple { build, current } => {
+                write!(
+                    f,
+                    "built for target `{build}`, but target `{current}` specified"
+                )
+            }
+            Self::MissingPackages { package_ids } => {
+                // Package IDs are long, so only show the first few.
+                const MAX_SHOWN: usize = 3;
+
+                write!(f, "built from packages not in the current workspace: ")?;
+                for (i, package_id) in package_ids.iter().take(MAX_SHOWN).enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "`{package_id}`")?;
+                }
+                if package_ids.len() > MAX_SHOWN {
+                    write!(f, " and {} more", package_ids.len() - MAX_SHOWN)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cargo_config::TargetTriple,
+        list::{RustBuildMeta, RustTestBinary},
+    };
+    use guppy::CargoMetadata;
+    use maplit::btreeset;
+    use nextest_metadata::{BuildPlatform, RustTestBinaryKind};
+    use std::collections::BTreeMap;
+
+    static FIXTURE_JSON: &str = include_str!("../../../fixtures/cargo-metadata.json");
+    static PACKAGE_ID: &str =
+        "metadata-helper 0.1.0 (path+file:///Users/fakeuser/local/testcrates/metadata/metadata-helper)";
+    static MISSING_PACKAGE_ID: &str =
+        "missing-package 0.1.0 (path+file:///Users/fakeuser/local/testcrates/missing-package)";
+
+    #[test]
+    fn test_compute_mismatches() {
+        let graph = CargoMetadata::parse_json(FIXTURE_JSON)
+            .expect("fixture is valid JSON")
+            .build_graph()
+            .expect("fixture is valid PackageGraph");
+
+        let mut rust_build_meta = RustBuildMeta::new(
+            "/fake/target",
+            TargetTriple::deserialize("x86_64-unknown-linux-gnu".to_owned(), None),
+        );
+        rust_build_meta.rustc_version = Some(RustcVersionSummary {
+            release: "1.63.0".to_owned(),
+            commit_hash: Some("4b91a6ea7258a947e59c6522cd5898e7c0a6a88f".to_owned()),
+        });
+        rust_build_meta.features = Some(FeatureSelectionSummary {
+            features: btreeset! { "foo".to_owned() },
+            all_features: false,
+            no_default_features: true,
+        });
+        rust_build_meta.profile = Some("test".to_owned());
+        let mut binary_list = BinaryList {
+            rust_build_meta,
+            rust_binaries: vec![RustTestBinary {
+                id: "metadata-helper".to_owned(),
+                path: "/fake/target/debug/deps/metadata_helper-0123".into(),
+                package_id: PACKAGE_ID.to_owned(),
+                kind: RustTestBinaryKind::LIB,
+                name: "metadata-helper".to_owned(),
+                build_platform: BuildPlatform::Target,
+                doctest: None,
+                features: vec![],
+                env: BTreeMap::new(),
+            }],
+        };
+
+        // Nothing known about the current invocation.
+        assert_eq!(
+            ReuseBuildMismatch::compute(&binary_list, &graph, &CurrentBuildConfig::default()),
+            vec![],
+        );
+
+        let current = CurrentBuildConfig {
+            rustc_version: binary_list.rust_build_meta.rustc_version.clone(),
+            features: binary_list.rust_build_meta.features.clone(),
+            profile: Some("release".to_owned()),
+            target_triple: Some("x86_64-unknown-linux-gnu".to_owned()),
+        };
+        let mismatches = ReuseBuildMismatch::compute(&binary_list, &graph, &current);
+        assert_eq!(
+            mismatches,
+            vec![ReuseBuildMismatch::Profile {
+                build: "test".to_owned(),
+                current: "release".to_owned(),
+            }],
+        );
+        assert!(!mismatches[0].is_incompatible());
+
+        let current = CurrentBuildConfig {
+            rustc_version: Some(RustcVersionSummary {
+                release: "1.64.0".to_owned(),
+                commit_hash: None,
+            }),
+            features: Some(FeatureSelectionSummary::default()),
+            profile: None,
+            target_triple: Some("aarch64-unknown-linux-gnu".to_owned()),
+        };
+        let mut missing_binary = binary_list.rust_binaries[0].clone();
+        missing_binary.package_id = MISSING_PACKAGE_ID.to_owned();
+        binary_list.rust_binaries.push(missing_binary);
+
+        let mismatches = ReuseBuildMismatch::compute(&binary_list, &graph, &current);
+        assert_eq!(
+            mismatches
+                .iter()
+                .map(|mismatch| (mismatch.to_string(), mismatch.is_incompatible()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "built for target `x86_64-unknown-linux-gnu`, but target \
+                     `aarch64-unknown-linux-gnu` specified"
+                        .to_owned(),
+                    true,
+                ),
+                (
+                    format!(
+                        "built from packages not in the current workspace: `{MISSING_PACKAGE_ID}`"
+                    ),
+                    true,
+                ),
+                (
+                    "built with rustc 1.63.0 (4b91a6ea7258a947e59c6522cd5898e7c0a6a88f), but \
+                     current rustc is 1.64.0"
+                        .to_owned(),
+                    false,
+                ),
+                (
+                    "built with --no-default-features --features foo, but default features \
+                     specified"
+                        .to_owned(),
+                    false,
+                ),
+            ],
+        );
+    }
+}
@@ -9,21 +9,45 @@
 
 use crate::{
     errors::{
-        ArchiveExtractError, ArchiveReadError, PathMapperConstructError, PathMapperConstructKind,
+        ArchiveExtractError, ArchiveReadError, InvalidPathRemap, PathMapperConstructError,
+        PathMapperConstructKind,
     },
     list::BinaryList,
 };
+use baseline::BaselineManifest;
 use camino::{Utf8Path, Utf8PathBuf};
+use fetch::DigestReader;
 use guppy::graph::PackageGraph;
-use std::{fs, io, sync::Arc};
+use serde::Deserialize;
+use std::{
+    fmt, fs,
+    io::{self, Read},
+    str::FromStr,
+    sync::Arc,
+    time::Instant,
+};
 use tempfile::TempDir;
 
+mod archive_include;
 mod archive_reporter;
 mod archiver;
+mod baseline;
+mod codesign;
+mod debuginfo;
+mod external;
+mod fetch;
+mod inspect;
+mod mismatch;
 mod unarchiver;
 
+pub use archive_include::*;
 pub use archive_reporter::*;
 pub use archiver::*;
+pub use baseline::ArchiveBaseline;
+pub use debuginfo::ArchiveDebuginfo;
+pub use fetch::{is_archive_url, ArchiveChecksum};
+pub use inspect::ArchiveContents;
+pub use mismatch::*;
 pub use unarchiver::*;
 
 /// The name of the file in which Cargo metadata is stored.
@@ -32,6 +56,9 @@ pub const CARGO_METADATA_FILE_NAME: &str = "target/nextest/cargo-metadata.json";
 /// The name of the file in which binaries metadata is stored.
 pub const BINARIES_METADATA_FILE_NAME: &str = "target/nextest/binaries-metadata.json";
 
+/// The name of the file in which the baseline for a delta archive is recorded.
+pub const BASELINE_MANIFEST_FILE_NAME: &str = "target/nextest/archive-baseline.json";
+
 /// Reuse build information.
 #[derive(Debug, Default)]
 pub struct ReuseBuildInfo {
@@ -41,6 +68,15 @@ pub struct ReuseBuildInfo {
     /// Binaries metadata JSON and remapping for the target directory.
     pub binaries_metadata: Option<MetadataWithRemap<BinaryList>>,
 
+    /// The directory the archive was extracted to, if any.
+    dest_dir: Option<Utf8PathBuf>,
+
+    /// For delta archives, the baseline the archive needs to be layered over.
+    pending_baseline: Option<PendingBaseline>,
+
+    /// True if the test binaries were built outside of Cargo.
+    external: bool,
+
     /// Optional temporary directory used for cleanup.
     _temp_dir: Option<TempDir>,
 }
@@ -54,14 +90,20 @@ impl ReuseBuildInfo {
         Self {
             cargo_metadata,
             binaries_metadata,
+            dest_dir: None,
+            pending_baseline: None,
+            external: false,
             _temp_dir: None,
         }
     }
 
     /// Extracts an archive and constructs a [`ReuseBuildInfo`] from it.
+    ///
+    /// If `checksum` is specified, the archive is verified against it.
     pub fn extract_archive<F>(
         archive_file: &Utf8Path,
         format: ArchiveFormat,
+        checksum: Option<&ArchiveChecksum>,
         dest: ExtractDestination,
         callback: F,
         workspace_remap: Option<&Utf8Path>,
@@ -69,17 +111,110 @@ impl ReuseBuildInfo {
     where
         F: for<'e> FnMut(ArchiveEvent<'e>) -> io::Result<()>,
     {
-        let mut file = fs::File::open(archive_file)
+        let file = fs::File::open(archive_file)
             .map_err(|err| ArchiveExtractError::Read(ArchiveReadError::Io(err)))?;
 
-        let mut unarchiver = Unarchiver::new(&mut file, format);
+        Self::extract_impl(file, format, checksum, dest, callback, workspace_remap)
+    }
+
+    /// Fetches an archive from an `http://`, `https://` or `s3://` URL, extracting it while it's
+    /// being downloaded, and constructs a [`ReuseBuildInfo`] from it.
+    ///
+    /// If `format` is `None`, it is detected from the contents of the archive, falling back to the
+    /// extension in the URL. If `checksum` is specified, the archive is verified against it.
+    ///
+    /// For `s3://` URLs, requests are signed with credentials from the `AWS_ACCESS_KEY_ID`,
+    /// `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN` environment variables, if set.
+    pub fn extract_archive_from_url<F>(
+        url: &str,
+        format: Option<ArchiveFormat>,
+        checksum: Option<&ArchiveChecksum>,
+        dest: ExtractDestination,
+        callback: F,
+        workspace_remap: Option<&Utf8Path>,
+    ) -> Result<Self, ArchiveExtractError>
+    where
+        F: for<'e> FnMut(ArchiveEvent<'e>) -> io::Result<()>,
+    {
+        let mut response = fetch::fetch(url).map_err(ArchiveExtractError::Fetch)?;
+
+        match format {
+            Some(format) => {
+                Self::extract_impl(response, format, checksum, dest, callback, workspace_remap)
+            }
+            None => {
+                // Peek at the start of the archive to detect its format. The bytes read here are
+                // chained back on before extraction.
+                let mut head = Vec::with_capacity(ArchiveFormat::DETECT_LEN);
+                (&mut response)
+                    .take(ArchiveFormat::DETECT_LEN as u64)
+                    .read_to_end(&mut head)
+                    .map_err(|err| ArchiveExtractError::Read(ArchiveReadError::Io(err)))?;
+                let format = match ArchiveFormat::autodetect_from_contents(&head) {
+                    Some(format) => format,
+                    None => ArchiveFormat::autodetect(Utf8Path::new(response.url().path()))
+                        .map_err(|err| {
+                            ArchiveExtractError::Read(ArchiveReadError::UnknownFormat(err))
+                        })?,
+                };
+                Self::extract_impl(
+                    io::Cursor::new(head).chain(response),
+                    format,
+                    checksum,
+                    dest,
+                    callback,
+                    workspace_remap,
+                )
+            }
+        }
+    }
+
+    fn extract_impl<R, F>(
+        mut reader: R,
+        format: ArchiveFormat,
+        checksum: Option<&ArchiveChecksum>,
+        dest: ExtractDestination,
+        callback: F,
+        workspace_remap: Option<&Utf8Path>,
+    ) -> Result<Self, ArchiveExtractError>
+    where
+        R: io::Read,
+        F: for<'e> FnMut(ArchiveEvent<'e>) -> io::Result<()>,
+    {
+        let mut context = checksum.map(|checksum| checksum.context());
+
+        let unarchiver = Unarchiver::new(DigestReader::new(&mut reader, context.as_mut()), format);
         let ExtractInfo {
             dest_dir,
             temp_dir,
             binary_list,
             cargo_metadata_json,
             graph,
-        } = unarchiver.extract(dest, callback)?;
+            baseline_manifest,
+        } = unarchiver.extract(dest, workspace_remap, callback)?;
+
+        if let (Some(checksum), Some(mut context)) = (checksum, context) {
+            // Read any trailing data after the end of the tarball, so the digest covers the
+            // entire archive.
+            io::copy(
+                &mut DigestReader::new(&mut reader, Some(&mut context)),
+                &mut io::sink(),
+            )
+            .map_err(|err| ArchiveExtractError::Read(ArchiveReadError::Io(err)))?;
+            checksum
+                .verify(context)
+                .map_err(|actual| ArchiveExtractError::ChecksumMismatch {
+                    expected: checksum.to_string(),
+                    actual,
+                })?;
+        }
+
+        let pending_baseline = baseline_manifest.map(|manifest| PendingBaseline {
+            manifest,
+            workspace_root: workspace_remap
+                .unwrap_or_else(|| graph.workspace().root())
+                .to_owned(),
+        });
 
         let cargo_metadata = MetadataWithRemap {
             metadata: MetadataOrPath::metadata((cargo_metadata_json, graph)),
@@ -93,10 +228,146 @@ impl ReuseBuildInfo {
         Ok(Self {
             cargo_metadata: Some(cargo_metadata),
             binaries_metadata: Some(binaries_metadata),
+            dest_dir: Some(dest_dir),
+            pending_baseline,
+            external: false,
             _temp_dir: temp_dir,
         })
     }
 
+    /// If the extracted archive is a delta archive, returns the checksum of the baseline archive
+    /// it needs to be layered over with [`Self::extract_baseline`].
+    pub fn baseline_checksum(&self) -> Option<&ArchiveChecksum> {
+        self.pending_baseline
+            .as_ref()
+            .map(|pending| &pending.manifest.baseline_checksum)
+    }
+
+    /// Extracts the files left out of a delta archive from its baseline, layering them over the
+    /// files already extracted.
+    ///
+    /// The baseline is verified against the checksum recorded in the delta archive. Does nothing
+    /// if the extracted archive isn't a delta archive.
+    pub fn extract_baseline<F>(
+        &mut self,
+        baseline_file: &Utf8Path,
+        format: ArchiveFormat,
+        mut callback: F,
+    ) -> Result<(), ArchiveExtractError>
+    where
+        F: for<'e> FnMut(ArchiveEvent<'e>) -> io::Result<()>,
+    {
+        let pending = match self.pending_baseline.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+
+        let mut file = fs::File::open(baseline_file)
+            .map_err(|err| ArchiveExtractError::Read(ArchiveReadError::Io(err)))?;
+        let start_time = Instant::now();
+        let checksum = &pending.manifest.baseline_checksum;
+        let mut context = checksum.context();
+
+        let unarchiver = Unarchiver::new(DigestReader::new(&mut file, Some(&mut context)), format);
+        let file_count = unarchiver.extract_baseline_files(
+            &pending.manifest.files,
+            self.dest_dir
+                .as_deref()
+                .expect("delta archives are extracted to a directory"),
+            &pending.workspace_root,
+        )?;
+
+        // Read any trailing data after the end of the tarball, so the digest covers the entire
+        // archive.
+        io::copy(
+            &mut DigestReader::new(&mut file, Some(&mut context)),
+            &mut io::sink(),
+        )
+        .map_err(|err| ArchiveExtractError::Read(ArchiveReadError::Io(err)))?;
+        checksum
+            .verify(context)
+            .map_err(|actual| ArchiveExtractError::BaselineMismatch {
+                expected: checksum.to_string(),
+                actual,
+            })?;
+
+        callback(ArchiveEvent::BaselineExtracted {
+            file_count,
+            baseline_file,
+            elapsed: start_time.elapsed(),
+        })
+        .map_err(ArchiveExtractError::ReporterIo)?;
+
+        Ok(())
+    }
+
+    /// Extracts debug info split out of the binaries in the extracted archive from a debuginfo
+    /// archive, placing it next to the binaries.
+    ///
+    /// Does nothing if no archive was extracted.
+    pub fn extract_debuginfo<F>(
+        &self,
+        debuginfo_file: &Utf8Path,
+        format: ArchiveFormat,
+        mut callback: F,
+    ) -> Result<(), ArchiveExtractError>
+    where
+        F: for<'e> FnMut(ArchiveEvent<'e>) -> io::Result<()>,
+    {
+        let dest_dir = match &self.dest_dir {
+            Some(dest_dir) => dest_dir,
+            None => return Ok(()),
+        };
+
+        let file = fs::File::open(debuginfo_file)
+            .map_err(|err| ArchiveExtractError::Read(ArchiveReadError::Io(err)))?;
+        let start_time = Instant::now();
+        let file_count = Unarchiver::new(file, format).extract_debuginfo_files(dest_dir)?;
+
+        callback(ArchiveEvent::DebuginfoExtracted {
+            file_count,
+            debuginfo_file,
+            elapsed: start_time.elapsed(),
+        })
+        .map_err(ArchiveExtractError::ReporterIo)?;
+
+        Ok(())
+    }
+
+    /// On macOS, re-signs the Mach-O binaries in the extracted archive with ad-hoc signatures, and
+    /// clears any quarantine attributes on them.
+    ///
+    /// Signatures can be invalidated while binaries are moved across machines, which causes them to
+    /// be killed on startup. Failures are logged as warnings. Does nothing on other platforms, or
+    /// if no archive was extracted.
+    pub fn resign_extracted_binaries(&self) {
+        let dest_dir = match &self.dest_dir {
+            Some(dest_dir) if cfg!(target_os = "macos") => dest_dir,
+            _ => return,
+        };
+
+        let target_dir = dest_dir.join("target");
+        let start_time = Instant::now();
+        match codesign::find_mach_o_files(&target_dir) {
+            Ok(files) => {
+                codesign::resign(&files);
+                log::debug!(
+                    target: "nextest-runner",
+                    "re-signed {} extracted binaries in {:?}",
+                    files.len(),
+                    start_time.elapsed(),
+                );
+            }
+            Err(error) => {
+                log::warn!(
+                    target: "nextest-runner",
+                    "error finding binaries to re-sign in `{target_dir}`, \
+                     tests might be killed on startup: {error}",
+                );
+            }
+        }
+    }
+
     /// Returns the Cargo metadata.
     pub fn cargo_metadata(&self) -> Option<&MetadataOrPath<(String, PackageGraph)>> {
         self.cargo_metadata.as_ref().map(|m| &m.metadata)
@@ -107,6 +378,20 @@ impl ReuseBuildInfo {
         self.binaries_metadata.as_ref().map(|m| &m.metadata)
     }
 
+    /// Returns true if the build is being reused from an extracted archive.
+    #[inline]
+    pub fn is_extracted(&self) -> bool {
+        self.dest_dir.is_some()
+    }
+
+    /// Returns true if the test binaries were built outside of Cargo.
+    ///
+    /// See [`from_external`](Self::from_external).
+    #[inline]
+    pub fn is_external(&self) -> bool {
+        self.external
+    }
+
     /// Returns true if any component of the build is being reused.
     #[inline]
     pub fn is_active(&self) -> bool {
@@ -128,6 +413,12 @@ impl ReuseBuildInfo {
     }
 }
 
+#[derive(Debug)]
+struct PendingBaseline {
+    manifest: BaselineManifest,
+    workspace_root: Utf8PathBuf,
+}
+
 /// Metadata as either deserialized contents or a path, along with a possible directory remap.
 #[derive(Clone, Debug)]
 pub struct MetadataWithRemap<T> {
@@ -165,6 +456,77 @@ impl<T> From<Utf8PathBuf> for MetadataOrPath<T> {
     }
 }
 
+/// A rule to remap paths starting with one prefix to another, of the form `<FROM>=<TO>`.
+///
+/// Rules are passed in to [`PathMapper::new`], through `--path-remap` on the command line or
+/// `path-remap` in the config.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathRemap {
+    from: Utf8PathBuf,
+    to: Utf8PathBuf,
+}
+
+impl PathRemap {
+    /// Creates a new rule remapping paths starting with `from` to `to`.
+    pub fn new(from: impl Into<Utf8PathBuf>, to: impl Into<Utf8PathBuf>) -> Self {
+        Self {
+            from: from.into(),
+            to: to.into(),
+        }
+    }
+
+    /// Returns the prefix that paths on the build machine start with.
+    pub fn from(&self) -> &Utf8Path {
+        &self.from
+    }
+
+    /// Returns the directory paths starting with [`Self::from`] are remapped to.
+    pub fn to(&self) -> &Utf8Path {
+        &self.to
+    }
+
+    /// Returns a copy of this rule with a relative destination resolved against `base_dir`.
+    pub fn relative_to(&self, base_dir: &Utf8Path) -> Self {
+        Self {
+            from: self.from.clone(),
+            to: base_dir.join(&self.to),
+        }
+    }
+}
+
+impl FromStr for PathRemap {
+    type Err = InvalidPathRemap;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (from, to) = input
+            .split_once('=')
+            .ok_or_else(|| InvalidPathRemap::new(input, "expected `<FROM>=<TO>`"))?;
+        if from.is_empty() || to.is_empty() {
+            return Err(InvalidPathRemap::new(
+                input,
+                "both sides of `=` must be non-empty",
+            ));
+        }
+        Ok(Self::new(from, to))
+    }
+}
+
+impl fmt::Display for PathRemap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.from, self.to)
+    }
+}
+
+impl<'de> Deserialize<'de> for PathRemap {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 /// A helper for path remapping.
 ///
 /// This is useful when running tests in a different directory, or a different computer, from building them.
@@ -172,15 +534,20 @@ impl<T> From<Utf8PathBuf> for MetadataOrPath<T> {
 pub struct PathMapper {
     workspace: Option<(Utf8PathBuf, Utf8PathBuf)>,
     target_dir: Option<(Utf8PathBuf, Utf8PathBuf)>,
+    rules: Vec<(Utf8PathBuf, Utf8PathBuf)>,
 }
 
 impl PathMapper {
     /// Constructs the path mapper.
+    ///
+    /// Paths not within the original workspace root or target directory are remapped by the
+    /// first rule in `remaps` they match, if any.
     pub fn new(
         orig_workspace_root: impl Into<Utf8PathBuf>,
         workspace_remap: Option<&Utf8Path>,
         orig_target_dir: impl Into<Utf8PathBuf>,
         target_dir_remap: Option<&Utf8Path>,
+        remaps: &[PathRemap],
     ) -> Result<Self, PathMapperConstructError> {
         let workspace_root = workspace_remap
             .map(|root| Self::canonicalize_dir(root, PathMapperConstructKind::WorkspaceRoot))
@@ -188,10 +555,18 @@ impl PathMapper {
         let target_dir = target_dir_remap
             .map(|dir| Self::canonicalize_dir(dir, PathMapperConstructKind::WorkspaceRoot))
             .transpose()?;
+        let rules = remaps
+            .iter()
+            .map(|remap| {
+                let to = Self::canonicalize_dir(&remap.to, PathMapperConstructKind::PathRemap)?;
+                Ok((remap.from.clone(), to))
+            })
+            .collect::<Result<_, _>>()?;
 
         Ok(Self {
             workspace: workspace_root.map(|w| (orig_workspace_root.into(), w)),
             target_dir: target_dir.map(|d| (orig_target_dir.into(), d)),
+            rules,
         })
     }
 
@@ -200,6 +575,7 @@ impl PathMapper {
         Self {
             workspace: None,
             target_dir: None,
+            rules: Vec::new(),
         }
     }
 
@@ -239,23 +615,24 @@ impl PathMapper {
     }
 
     pub(crate) fn map_cwd(&self, path: Utf8PathBuf) -> Utf8PathBuf {
-        match &self.workspace {
-            Some((from, to)) => match path.strip_prefix(from) {
-                Ok(p) => to.join(p),
-                Err(_) => path,
-            },
-            None => path,
-        }
+        self.map_impl(self.workspace.as_ref(), path)
     }
 
     pub(crate) fn map_binary(&self, path: Utf8PathBuf) -> Utf8PathBuf {
-        match &self.target_dir {
-            Some((from, to)) => match path.strip_prefix(from) {
-                Ok(p) => to.join(p),
-                Err(_) => path,
-            },
-            None => path,
+        self.map_impl(self.target_dir.as_ref(), path)
+    }
+
+    fn map_impl(
+        &self,
+        remap: Option<&(Utf8PathBuf, Utf8PathBuf)>,
+        path: Utf8PathBuf,
+    ) -> Utf8PathBuf {
+        for (from, to) in remap.into_iter().chain(&self.rules) {
+            if let Ok(p) = path.strip_prefix(from) {
+                return to.join(p);
+            }
         }
+        path
     }
 }
 
@@ -302,6 +679,7 @@ mod tests {
             Some(&rel_workspace_root),
             &orig_target_dir,
             Some(&rel_target_dir),
+            &[],
         )
         .expect("remapped paths exist");
 
@@ -314,4 +692,71 @@ mod tests {
             target_dir_path.join("foobar")
         );
     }
+
+    #[test]
+    fn test_path_mapper_rules() {
+        let temp_dir = TempDir::new().expect("new temp dir created");
+        let temp_dir: Utf8PathBuf = temp_dir
+            .path()
+            .canonicalize()
+            .expect("temp dir canonicalized correctly")
+            .try_into()
+            .expect("temp dir is valid UTF-8");
+        for dir in ["workspace", "deps", "vendor"] {
+            fs::create_dir(temp_dir.join(dir)).expect("dir created");
+        }
+
+        let remaps = [
+            PathRemap::new("/build/deps/vendor", temp_dir.join("vendor")),
+            PathRemap::new("/build/deps", temp_dir.join("deps")),
+            // Never used, since the rule above matches first.
+            PathRemap::new("/build/deps/other", temp_dir.join("workspace")),
+        ];
+        let path_mapper = PathMapper::new(
+            "/build/workspace",
+            Some(&temp_dir.join("workspace")),
+            "/build/workspace/target",
+            None,
+            &remaps,
+        )
+        .expect("remapped paths exist");
+
+        let cases = [
+            ("/build/workspace/foo", temp_dir.join("workspace/foo")),
+            ("/build/deps/vendor/bar", temp_dir.join("vendor/bar")),
+            ("/build/deps/other/baz", temp_dir.join("deps/other/baz")),
+            ("/build/depsx/qux", "/build/depsx/qux".into()),
+            ("/elsewhere/quux", "/elsewhere/quux".into()),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                path_mapper.map_cwd(input.into()),
+                expected,
+                "{input} mapped correctly"
+            );
+        }
+        assert_eq!(
+            path_mapper.map_binary("/build/deps/vendor/bin".into()),
+            temp_dir.join("vendor/bin"),
+            "rules apply to binaries"
+        );
+    }
+
+    #[test]
+    fn test_path_remap_parse() {
+        assert_eq!(
+            "/build/deps=deps".parse::<PathRemap>().unwrap(),
+            PathRemap::new("/build/deps", "deps"),
+        );
+        assert_eq!(
+            "/build/a=b=c".parse::<PathRemap>().unwrap(),
+            PathRemap::new("/build/a", "b=c"),
+            "only the first = is a separator"
+        );
+        for input in ["", "/build", "=deps", "/build="] {
+            input
+                .parse::<PathRemap>()
+                .expect_err(&format!("{input} should fail to parse"));
+        }
+    }
 }
@@ -1,35 +1,36 @@
 // Copyright (c) The nextest Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use super::{ArchiveEvent, ArchiveFormat, BINARIES_METADATA_FILE_NAME, CARGO_METADATA_FILE_NAME};
+use super::{
+    baseline::BaselineManifest, ArchiveEvent, ArchiveFormat, BASELINE_MANIFEST_FILE_NAME,
+    BINARIES_METADATA_FILE_NAME, CARGO_METADATA_FILE_NAME, WORKSPACE_ARCHIVE_PREFIX,
+};
 use crate::{
     errors::{ArchiveExtractError, ArchiveReadError},
     list::BinaryList,
 };
 use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use flate2::read::GzDecoder;
 use guppy::{graph::PackageGraph, CargoMetadata};
 use nextest_metadata::BinaryListSummary;
-use std::{
-    fs,
-    io::{self, Seek},
-    time::Instant,
-};
+use std::{collections::BTreeSet, fs, io, time::Instant};
 use tempfile::TempDir;
 
 #[derive(Debug)]
-pub(crate) struct Unarchiver<'a> {
-    file: &'a mut fs::File,
+pub(crate) struct Unarchiver<R> {
+    reader: R,
     format: ArchiveFormat,
 }
 
-impl<'a> Unarchiver<'a> {
-    pub(crate) fn new(file: &'a mut fs::File, format: ArchiveFormat) -> Self {
-        Self { file, format }
+impl<R: io::Read> Unarchiver<R> {
+    pub(crate) fn new(reader: R, format: ArchiveFormat) -> Self {
+        Self { reader, format }
     }
 
     pub(crate) fn extract<F>(
-        &mut self,
+        self,
         dest: ExtractDestination,
+        workspace_remap: Option<&Utf8Path>,
         mut callback: F,
     ) -> Result<ExtractInfo, ArchiveExtractError>
     where
@@ -85,19 +86,19 @@ impl<'a> Unarchiver<'a> {
         let start_time = Instant::now();
 
         // Extract the archive.
-        self.file
-            .seek(io::SeekFrom::Start(0))
-            .map_err(|error| ArchiveExtractError::Read(ArchiveReadError::Io(error)))?;
         let mut archive_reader =
-            ArchiveReader::new(self.file, self.format).map_err(ArchiveExtractError::Read)?;
+            ArchiveReader::new(self.reader, self.format).map_err(ArchiveExtractError::Read)?;
 
         // Will be filled out by the for loop below\
         let mut binary_list = None;
-        let mut graph_data = None;
+        let mut graph_data: Option<(String, PackageGraph)> = None;
+        let mut baseline_manifest = None;
         let binaries_metadata_path = Utf8Path::new(BINARIES_METADATA_FILE_NAME);
         let cargo_metadata_path = Utf8Path::new(CARGO_METADATA_FILE_NAME);
+        let baseline_manifest_path = Utf8Path::new(BASELINE_MANIFEST_FILE_NAME);
 
         let mut file_count = 0;
+        let mut skipped_workspace_files = Vec::new();
 
         for entry in archive_reader
             .entries()
@@ -106,6 +107,28 @@ impl<'a> Unarchiver<'a> {
             file_count += 1;
             let (mut entry, path) = entry.map_err(ArchiveExtractError::Read)?;
 
+            if let Ok(rel_path) = path.strip_prefix(WORKSPACE_ARCHIVE_PREFIX) {
+                // Files from the workspace are written out to the (possibly remapped) workspace
+                // root. For archives created by nextest, the Cargo metadata is always before them.
+                let workspace_root = match (workspace_remap, &graph_data) {
+                    (Some(workspace_remap), _) => workspace_remap,
+                    (None, Some((_, graph))) => graph.workspace().root(),
+                    (None, None) => {
+                        return Err(ArchiveExtractError::Read(
+                            ArchiveReadError::MetadataFileNotFound(cargo_metadata_path),
+                        ));
+                    }
+                };
+                unpack_workspace_entry(
+                    &mut entry,
+                    &path,
+                    rel_path,
+                    workspace_root,
+                    &mut skipped_workspace_files,
+                )?;
+                continue;
+            }
+
             entry
                 .unpack_in(&dest_dir)
                 .map_err(|error| ArchiveExtractError::WriteFile {
@@ -166,6 +189,18 @@ impl<'a> Unarchiver<'a> {
                 })?;
                 graph_data = Some((json, package_graph));
                 continue;
+            } else if path == baseline_manifest_path {
+                // This is a delta archive: read the files to be taken from the baseline.
+                let file = fs::File::open(dest_dir.join(baseline_manifest_path))
+                    .map_err(|error| ArchiveExtractError::WriteFile { path, error })?;
+                let manifest: BaselineManifest = serde_json::from_reader(io::BufReader::new(file))
+                    .map_err(|error| {
+                        ArchiveExtractError::Read(ArchiveReadError::MetadataDeserializeError {
+                            path: baseline_manifest_path,
+                            error,
+                        })
+                    })?;
+                baseline_manifest = Some(manifest);
             }
         }
 
@@ -187,6 +222,8 @@ impl<'a> Unarchiver<'a> {
             }
         };
 
+        warn_skipped_workspace_files(&skipped_workspace_files);
+
         let elapsed = start_time.elapsed();
         // Report end extraction.
         callback(ArchiveEvent::Extracted {
@@ -202,8 +239,138 @@ impl<'a> Unarchiver<'a> {
             binary_list,
             cargo_metadata_json,
             graph,
+            baseline_manifest,
         })
     }
+
+    /// Extracts the files in `files` from a baseline archive, layering them over a delta archive
+    /// that was extracted to `dest_dir`.
+    ///
+    /// Returns the number of files extracted.
+    pub(crate) fn extract_baseline_files(
+        self,
+        files: &BTreeSet<Utf8PathBuf>,
+        dest_dir: &Utf8Path,
+        workspace_root: &Utf8Path,
+    ) -> Result<usize, ArchiveExtractError> {
+        let mut archive_reader =
+            ArchiveReader::new(self.reader, self.format).map_err(ArchiveExtractError::Read)?;
+
+        let mut file_count = 0;
+        let mut skipped_workspace_files = Vec::new();
+
+        for entry in archive_reader
+            .entries()
+            .map_err(ArchiveExtractError::Read)?
+        {
+            let (mut entry, path) = entry.map_err(ArchiveExtractError::Read)?;
+            if !files.contains(&path) {
+                continue;
+            }
+            file_count += 1;
+
+            if let Ok(rel_path) = path.strip_prefix(WORKSPACE_ARCHIVE_PREFIX) {
+                unpack_workspace_entry(
+                    &mut entry,
+                    &path,
+                    rel_path,
+                    workspace_root,
+                    &mut skipped_workspace_files,
+                )?;
+            } else {
+                entry
+                    .unpack_in(dest_dir)
+                    .map_err(|error| ArchiveExtractError::WriteFile {
+                        path: path.clone(),
+                        error,
+                    })?;
+            }
+        }
+
+        warn_skipped_workspace_files(&skipped_workspace_files);
+
+        Ok(file_count)
+    }
+
+    /// Extracts the files in a debuginfo archive to `dest_dir`, next to the binaries they were
+    /// split out of.
+    ///
+    /// Returns the number of files extracted.
+    pub(crate) fn extract_debuginfo_files(
+        self,
+        dest_dir: &Utf8Path,
+    ) -> Result<usize, ArchiveExtractError> {
+        let mut archive_reader =
+            ArchiveReader::new(self.reader, self.format).map_err(ArchiveExtractError::Read)?;
+
+        let mut file_count = 0;
+        for entry in archive_reader
+            .entries()
+            .map_err(ArchiveExtractError::Read)?
+        {
+            let (mut entry, path) = entry.map_err(ArchiveExtractError::Read)?;
+            // Debuginfo archives only contain files within the target directory.
+            if path.starts_with(WORKSPACE_ARCHIVE_PREFIX) {
+                continue;
+            }
+            entry
+                .unpack_in(dest_dir)
+                .map_err(|error| ArchiveExtractError::WriteFile { path, error })?;
+            file_count += 1;
+        }
+
+        Ok(file_count)
+    }
+}
+
+/// Writes out a file from the workspace to `workspace_root`, unless it already exists.
+fn unpack_workspace_entry<R: io::Read>(
+    entry: &mut ArchiveEntry<'_, R>,
+    path: &Utf8Path,
+    rel_path: &Utf8Path,
+    workspace_root: &Utf8Path,
+    skipped_workspace_files: &mut Vec<Utf8PathBuf>,
+) -> Result<(), ArchiveExtractError> {
+    if !entry.header().entry_type().is_file() {
+        return Err(ArchiveExtractError::Read(
+            ArchiveReadError::WorkspaceEntryNotFile(path.to_owned()),
+        ));
+    }
+
+    let dest_path = workspace_root.join(rel_path);
+    // Never overwrite files that are already in the workspace.
+    if dest_path.exists() {
+        skipped_workspace_files.push(rel_path.to_owned());
+        return Ok(());
+    }
+    if let Some(parent) = dest_path.parent() {
+        fs::create_dir_all(parent).map_err(|error| ArchiveExtractError::WriteFile {
+            path: path.to_owned(),
+            error,
+        })?;
+    }
+    entry
+        .unpack(&dest_path)
+        .map_err(|error| ArchiveExtractError::WriteFile {
+            path: path.to_owned(),
+            error,
+        })?;
+    Ok(())
+}
+
+fn warn_skipped_workspace_files(skipped_workspace_files: &[Utf8PathBuf]) {
+    if !skipped_workspace_files.is_empty() {
+        let mut s = String::new();
+        for path in skipped_workspace_files {
+            s.push_str("  - ");
+            s.push_str(path.as_str());
+            s.push('\n');
+        }
+        log::warn!(
+            target: "nextest-runner",
+            "these files included in the archive already exist in the workspace, and were not overwritten:\n{s}",
+        );
+    }
 }
 
 #[derive(Debug)]
@@ -222,27 +389,35 @@ pub(crate) struct ExtractInfo {
 
     /// The [`PackageGraph`] read from the archive.
     pub graph: PackageGraph,
+
+    /// For delta archives, the manifest referencing the baseline.
+    pub baseline_manifest: Option<BaselineManifest>,
 }
 
-struct ArchiveReader<'a> {
-    archive: tar::Archive<zstd::Decoder<'static, io::BufReader<&'a mut fs::File>>>,
+pub(super) struct ArchiveReader<R: io::Read> {
+    archive: tar::Archive<ArchiveDecoder<R>>,
 }
 
-impl<'a> ArchiveReader<'a> {
-    fn new(file: &'a mut fs::File, format: ArchiveFormat) -> Result<Self, ArchiveReadError> {
-        let archive = match format {
+impl<R: io::Read> ArchiveReader<R> {
+    pub(super) fn new(reader: R, format: ArchiveFormat) -> Result<Self, ArchiveReadError> {
+        let decoder = match format {
             ArchiveFormat::TarZst => {
-                let decoder = zstd::Decoder::new(file).map_err(ArchiveReadError::Io)?;
-                tar::Archive::new(decoder)
+                ArchiveDecoder::Zstd(zstd::Decoder::new(reader).map_err(ArchiveReadError::Io)?)
             }
+            ArchiveFormat::TarGz => {
+                ArchiveDecoder::Gzip(Box::new(GzDecoder::new(io::BufReader::new(reader))))
+            }
+            ArchiveFormat::Tar => ArchiveDecoder::None(io::BufReader::new(reader)),
         };
-        Ok(Self { archive })
+        Ok(Self {
+            archive: tar::Archive::new(decoder),
+        })
     }
 
-    fn entries<'r>(
+    pub(super) fn entries<'r>(
         &'r mut self,
     ) -> Result<
-        impl Iterator<Item = Result<(ArchiveEntry<'r, 'a>, Utf8PathBuf), ArchiveReadError>>,
+        impl Iterator<Item = Result<(ArchiveEntry<'r, R>, Utf8PathBuf), ArchiveReadError>>,
         ArchiveReadError,
     > {
         let entries = self.archive.entries().map_err(ArchiveReadError::Io)?;
@@ -252,8 +427,8 @@ impl<'a> ArchiveReader<'a> {
             // Validation: entry paths must be valid UTF-8.
             let path = entry_path(&entry)?;
 
-            // Validation: paths start with "target".
-            if !path.starts_with("target") {
+            // Validation: paths start with "target" or "workspace".
+            if !path.starts_with("target") && !path.starts_with(WORKSPACE_ARCHIVE_PREFIX) {
                 return Err(ArchiveReadError::NoTargetPrefix(path));
             }
 
@@ -298,7 +473,7 @@ impl<'a> ArchiveReader<'a> {
 }
 
 /// Given an entry, returns its path as a `Utf8Path`.
-fn entry_path(entry: &ArchiveEntry<'_, '_>) -> Result<Utf8PathBuf, ArchiveReadError> {
+fn entry_path<R: io::Read>(entry: &ArchiveEntry<'_, R>) -> Result<Utf8PathBuf, ArchiveReadError> {
     let path_bytes = entry.path_bytes();
     let path_str = std::str::from_utf8(&path_bytes)
         .map_err(|_| ArchiveReadError::NonUtf8Path(path_bytes.to_vec()))?;
@@ -323,4 +498,22 @@ pub enum ExtractDestination {
     },
 }
 
-type ArchiveEntry<'r, 'a> = tar::Entry<'r, zstd::Decoder<'static, io::BufReader<&'a mut fs::File>>>;
+/// The decompressed stream a tarball is read from.
+pub(super) enum ArchiveDecoder<R: io::Read> {
+    Zstd(zstd::Decoder<'static, io::BufReader<R>>),
+    // GzDecoder stores its state inline, so box it to keep this enum small.
+    Gzip(Box<GzDecoder<io::BufReader<R>>>),
+    None(io::BufReader<R>),
+}
+
+impl<R: io::Read> io::Read for ArchiveDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Zstd(decoder) => decoder.read(buf),
+            Self::Gzip(decoder) => decoder.read(buf),
+            Self::None(reader) => reader.read(buf),
+        }
+    }
+}
+
+pub(super) type ArchiveEntry<'r, R> = tar::Entry<'r, ArchiveDecoder<R>>;
@@ -0,0 +1,1091 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Recording the outcomes of test runs.
+//!
+//! The outcome of the last run with each profile is stored in the profile's store directory, and is
+//! used by the `failed()`, `flaky()` and `skipped()` filter expression predicates. How long each
+//! test took is kept across runs, and is used by the `slower_than()` and `faster_than()`
+//! predicates.
+//!
+//! While a run is in progress, each test that finishes is also appended to a checkpoint for the run.
+//! If the run is interrupted, the checkpoint is kept so that the run can be resumed with
+//! `--resume`, and it's removed once every test in the run has finished.
+
+use crate::{
+    config::NextestProfile,
+    errors::{RunStoreReadError, WriteEventError},
+    list::{RustTestSuiteStatus, TestList},
+    reporter::{CancelReason, TestEvent},
+    runner::{ExecutionDescription, ExecutionStatuses},
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use nextest_filtering::{PreviousRun, RunStatus};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::OpenOptions,
+    io::{self, Write},
+    time::Duration,
+};
+use uuid::Uuid;
+
+/// The outcome of the last test run with a profile.
+#[derive(Clone, Debug)]
+pub struct RunStore {
+    path: Utf8PathBuf,
+    timings_path: Utf8PathBuf,
+    checkpoints_dir: Utf8PathBuf,
+}
+
+impl RunStore {
+    const FORMAT_VERSION: u32 = 1;
+
+    /// Creates a new `RunStore` for the given profile.
+    pub fn new(profile: &NextestProfile<'_>) -> Self {
+        Self::from_store_dir(profile.store_dir())
+    }
+
+    fn from_store_dir(store_dir: &Utf8Path) -> Self {
+        Self {
+            path: store_dir.join("last-run.json"),
+            timings_path: store_dir.join("timings.json"),
+            checkpoints_dir: store_dir.join("checkpoints"),
+        }
+    }
+
+    /// Returns the path the outcome of the last run is stored at.
+    pub fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+
+    /// Returns the path test timings are stored at.
+    pub fn timings_path(&self) -> &Utf8Path {
+        &self.timings_path
+    }
+
+    /// Returns the path the checkpoint for the given run is stored at while the run is in progress.
+    pub fn checkpoint_path(&self, run_id: Uuid) -> Utf8PathBuf {
+        self.checkpoints_dir.join(format!("{run_id}.jsonl"))
+    }
+
+    /// Reads the checkpoint of an interrupted run, so that the run can be resumed.
+    ///
+    /// Returns an error if there's no checkpoint for the run, for example because every test in it
+    /// finished.
+    pub fn read_checkpoint(&self, run_id: Uuid) -> Result<ResumedRun, RunStoreReadError> {
+        let path = self.checkpoint_path(run_id);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Err(RunStoreReadError::CheckpointNotFound { run_id, path })
+            }
+            Err(error) => return Err(RunStoreReadError::Read { path, error }),
+        };
+        parse_checkpoint(&path, run_id, &contents)
+    }
+
+    /// Reads the statuses of tests in the last run, or returns `None` if no run has been recorded.
+    pub fn read_last_run(&self) -> Result<Option<PreviousRun>, RunStoreReadError> {
+        let recorded: RecordedRun = match read_record(&self.path)? {
+            Some(recorded) => recorded,
+            None => return Ok(None),
+        };
+        check_format_version(&self.path, recorded.format_version)?;
+
+        let mut previous_run = PreviousRun::new();
+        for (binary_id, tests) in recorded.tests {
+            for (test_name, status) in tests {
+                previous_run.insert(binary_id.clone(), test_name, status.into());
+            }
+        }
+        Ok(Some(previous_run))
+    }
+
+    /// Reads how long tests took the last time they were run, or returns `None` if no timings have
+    /// been recorded.
+    pub fn read_timings(&self) -> Result<Option<TestTimings>, RunStoreReadError> {
+        read_timings(&self.timings_path)
+    }
+
+    fn write(&self, recorded: &RecordedRun<&str>) -> io::Result<()> {
+        write_record(&self.path, recorded)
+    }
+
+    fn write_timings(&self, timings: &TestTimings) -> io::Result<()> {
+        let tests = timings
+            .tests
+            .iter()
+            .map(|(binary_id, tests)| {
+                let tests = tests
+                    .iter()
+                    .map(|(test_name, duration)| (test_name.as_str(), duration.as_secs_f64()))
+                    .collect();
+                (binary_id.as_str(), tests)
+            })
+            .collect();
+        write_record(
+            &self.timings_path,
+            &RecordedTimings {
+                format_version: Self::FORMAT_VERSION,
+                tests,
+            },
+        )
+    }
+}
+
+/// An interrupted run, read from its checkpoint with [`RunStore::read_checkpoint`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ResumedRun {
+    run_id: Uuid,
+    // binary ID -> names of the tests the run was started with
+    tests: BTreeMap<String, BTreeSet<String>>,
+    // binary ID -> test name -> how the test finished
+    finished: BTreeMap<String, BTreeMap<String, FinishedTest>>,
+}
+
+impl ResumedRun {
+    /// Returns the ID of the run.
+    pub fn run_id(&self) -> Uuid {
+        self.run_id
+    }
+
+    /// Iterates over the tests the run was started with, returning the binary ID and test name.
+    ///
+    /// This includes tests that finished before the run was interrupted.
+    pub fn test_ids(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.tests.iter().flat_map(|(binary_id, tests)| {
+            tests
+                .iter()
+                .map(move |test_name| (binary_id.as_str(), test_name.as_str()))
+        })
+    }
+
+    /// Returns how the given test finished, or `None` if it didn't finish before the run was
+    /// interrupted.
+    pub fn finished(&self, binary_id: &str, test_name: &str) -> Option<FinishedTest> {
+        self.finished.get(binary_id)?.get(test_name).copied()
+    }
+
+    /// Returns the number of tests that finished before the run was interrupted.
+    pub fn finished_count(&self) -> usize {
+        self.finished.values().map(BTreeMap::len).sum()
+    }
+}
+
+/// How a test in a [`ResumedRun`] finished before the run was interrupted.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FinishedTest {
+    /// Whether the test passed, failed or was flaky.
+    pub status: RunStatus,
+
+    /// How long the last attempt of the test took.
+    pub time_taken: Duration,
+}
+
+/// Keeps track of the tests that didn't fail in a run, so that the rest can be run again.
+///
+/// This is used to run failed tests again once a run finishes, when asked to from the dashboard.
+/// The tests are run again by resuming the run with [`Self::into_resumed_run`]: every test that
+/// didn't fail counts as finished, and the tests that failed or didn't finish are run again.
+#[derive(Clone, Debug, Default)]
+pub struct FailureRetry {
+    run_id: Option<Uuid>,
+    // Runs with several iterations can't be resumed, and canceled runs aren't run again.
+    resumable: bool,
+    tests: BTreeMap<String, BTreeSet<String>>,
+    finished: BTreeMap<String, BTreeMap<String, FinishedTest>>,
+    failed: usize,
+}
+
+impl FailureRetry {
+    /// Creates a new `FailureRetry`, to be passed every event in a run.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a test event.
+    pub fn write_event(&mut self, event: &TestEvent<'_>) {
+        match event {
+            TestEvent::RunStarted {
+                test_list,
+                run_id,
+                iterations,
+                until_failure,
+                ..
+            } => {
+                self.run_id = Some(*run_id);
+                self.resumable = *iterations == Some(1) && !until_failure;
+                self.add_tests(test_list);
+            }
+            TestEvent::TestsAdded { test_list } => self.add_tests(test_list),
+            TestEvent::TestFinished {
+                test_instance,
+                run_statuses,
+                ..
+            } => {
+                let status = match run_statuses.describe() {
+                    ExecutionDescription::Success { .. } => RunStatus::Passed,
+                    ExecutionDescription::Flaky { .. } => RunStatus::Flaky,
+                    ExecutionDescription::Failure { .. } => RunStatus::Failed,
+                };
+                self.insert(
+                    &test_instance.bin_info.binary_id,
+                    test_instance.name,
+                    FinishedTest {
+                        status,
+                        time_taken: run_statuses.last_status().time_taken,
+                    },
+                );
+            }
+            TestEvent::TestPreviouslyFinished {
+                test_instance,
+                status,
+                time_taken,
+                ..
+            } => {
+                self.insert(
+                    &test_instance.bin_info.binary_id,
+                    test_instance.name,
+                    FinishedTest {
+                        status: *status,
+                        time_taken: *time_taken,
+                    },
+                );
+            }
+            // A run canceled with fail-fast is run again, but not one canceled by a signal.
+            TestEvent::RunBeginCancel { reason, .. } if *reason > CancelReason::TestFailure => {
+                self.resumable = false;
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the number of tests that failed.
+    pub fn failed_count(&self) -> usize {
+        self.failed
+    }
+
+    /// Returns a run that resumes the recorded one, in which the tests that failed are run again.
+    ///
+    /// Returns `None` if no tests failed, if the run was canceled other than by fail-fast, or if the run can't be resumed
+    /// because it was run with `--iterations` or `--until-failure`.
+    pub fn into_resumed_run(self) -> Option<ResumedRun> {
+        match (self.run_id, self.resumable && self.failed > 0) {
+            (Some(run_id), true) => Some(ResumedRun {
+                run_id,
+                tests: self.tests,
+                finished: self.finished,
+            }),
+            _ => None,
+        }
+    }
+
+    fn add_tests(&mut self, test_list: &TestList<'_>) {
+        for test_instance in test_list.iter_tests() {
+            if test_instance.test_info.filter_match.is_match() {
+                self.tests
+                    .entry(test_instance.bin_info.binary_id.clone())
+                    .or_default()
+                    .insert(test_instance.name.to_owned());
+            }
+        }
+    }
+
+    fn insert(&mut self, binary_id: &str, test_name: &str, finished: FinishedTest) {
+        if finished.status == RunStatus::Failed {
+            self.failed += 1;
+        } else {
+            self.finished
+                .entry(binary_id.to_owned())
+                .or_default()
+                .insert(test_name.to_owned(), finished);
+        }
+    }
+}
+
+fn parse_checkpoint(
+    path: &Utf8Path,
+    run_id: Uuid,
+    contents: &str,
+) -> Result<ResumedRun, RunStoreReadError> {
+    let deserialize_error = |error| RunStoreReadError::Deserialize {
+        path: path.to_owned(),
+        error,
+    };
+    let mut lines = contents.lines();
+    let header: CheckpointHeader =
+        serde_json::from_str(lines.next().unwrap_or_default()).map_err(deserialize_error)?;
+    check_format_version(path, header.format_version)?;
+
+    let mut tests = header.tests;
+    let mut finished: BTreeMap<String, BTreeMap<_, _>> = BTreeMap::new();
+    let mut lines = lines.peekable();
+    while let Some(line) = lines.next() {
+        let entry = match serde_json::from_str(line) {
+            Ok(CheckpointLine::Entry(entry)) => entry,
+            Ok(CheckpointLine::TestsAdded(added)) => {
+                for (binary_id, names) in added.tests_added {
+                    tests.entry(binary_id).or_default().extend(names);
+                }
+                continue;
+            }
+            // The last line is cut short if nextest was killed while writing it out.
+            Err(_) if lines.peek().is_none() => break,
+            Err(error) => return Err(deserialize_error(error)),
+        };
+        finished.entry(entry.binary_id).or_default().insert(
+            entry.name,
+            FinishedTest {
+                status: entry.status.into(),
+                time_taken: Duration::from_secs_f64(entry.time_taken),
+            },
+        );
+    }
+    Ok(ResumedRun {
+        run_id,
+        tests,
+        finished,
+    })
+}
+
+/// How long tests took the last time they were run.
+///
+/// Unlike test statuses, timings are kept across runs: a test that wasn't run keeps the timing it
+/// had the last time it was.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TestTimings {
+    // binary ID -> test name -> duration
+    tests: BTreeMap<String, BTreeMap<String, Duration>>,
+}
+
+impl TestTimings {
+    /// Reads timings from a file previously written to a run store's
+    /// [timings path](RunStore::timings_path), for example one copied over from another machine.
+    pub fn read_from(path: &Utf8Path) -> Result<Self, RunStoreReadError> {
+        read_timings(path)?.ok_or_else(|| RunStoreReadError::Read {
+            path: path.to_owned(),
+            error: io::ErrorKind::NotFound.into(),
+        })
+    }
+
+    /// Returns how long the given test took the last time it was run, or `None` if it hasn't been
+    /// timed.
+    pub fn get(&self, binary_id: &str, test_name: &str) -> Option<Duration> {
+        self.tests.get(binary_id)?.get(test_name).copied()
+    }
+
+    /// Iterates over timed tests, returning the binary ID, test name and duration.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str, Duration)> + '_ {
+        self.tests.iter().flat_map(|(binary_id, tests)| {
+            tests.iter().map(move |(test_name, duration)| {
+                (binary_id.as_str(), test_name.as_str(), *duration)
+            })
+        })
+    }
+
+    /// Returns true if no tests have been timed.
+    pub fn is_empty(&self) -> bool {
+        self.tests.is_empty()
+    }
+
+    pub(crate) fn insert(&mut self, binary_id: &str, test_name: &str, duration: Duration) {
+        self.tests
+            .entry(binary_id.to_owned())
+            .or_default()
+            .insert(test_name.to_owned(), duration);
+    }
+}
+
+fn read_timings(path: &Utf8Path) -> Result<Option<TestTimings>, RunStoreReadError> {
+    let recorded: RecordedTimings = match read_record(path)? {
+        Some(recorded) => recorded,
+        None => return Ok(None),
+    };
+    check_format_version(path, recorded.format_version)?;
+
+    let tests = recorded
+        .tests
+        .into_iter()
+        .map(|(binary_id, tests)| {
+            let tests = tests
+                .into_iter()
+                .map(|(test_name, secs)| (test_name, Duration::from_secs_f64(secs)))
+                .collect();
+            (binary_id, tests)
+        })
+        .collect();
+    Ok(Some(TestTimings { tests }))
+}
+
+fn read_record<T: DeserializeOwned>(path: &Utf8Path) -> Result<Option<T>, RunStoreReadError> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => {
+            return Err(RunStoreReadError::Read {
+                path: path.to_owned(),
+                error,
+            })
+        }
+    };
+    serde_json::from_str(&contents)
+        .map(Some)
+        .map_err(|error| RunStoreReadError::Deserialize {
+            path: path.to_owned(),
+            error,
+        })
+}
+
+fn check_format_version(path: &Utf8Path, format_version: u32) -> Result<(), RunStoreReadError> {
+    if format_version != RunStore::FORMAT_VERSION {
+        return Err(RunStoreReadError::UnsupportedFormatVersion {
+            path: path.to_owned(),
+            format_version,
+            supported: RunStore::FORMAT_VERSION,
+        });
+    }
+    Ok(())
+}
+
+fn write_record(path: &Utf8Path, record: &impl Serialize) -> io::Result<()> {
+    // Write to a temporary file first so that an interrupted write never leaves a partial record
+    // behind.
+    let dir = path.parent().expect("run store path has a parent");
+    std::fs::create_dir_all(dir)?;
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    serde_json::to_writer(&mut file, record)?;
+    file.persist(path).map_err(|err| err.error)?;
+    Ok(())
+}
+
+fn append_line(path: &Utf8Path, record: &impl Serialize) -> io::Result<()> {
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    // Each line is written out in one go, so that if nextest is killed, at most the last line is
+    // cut short.
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(&line)
+}
+
+/// Returns true if a test failed because it was terminated when the run was canceled, in which case
+/// it's run again if the run is resumed.
+pub(crate) fn terminated_by_cancel(
+    run_statuses: &ExecutionStatuses,
+    cancel_state: Option<CancelReason>,
+) -> bool {
+    // Tests still running are left to finish if the run is canceled because tests failed, or
+    // because building them did.
+    cancel_state > Some(CancelReason::BuildFailure)
+        && !run_statuses.last_status().result.is_success()
+}
+
+/// Records the statuses of tests as a run progresses, and writes them out once it's finished.
+#[derive(Clone, Debug)]
+pub(crate) struct RunRecorder<'a> {
+    store: RunStore,
+    tests: BTreeMap<&'a str, BTreeMap<&'a str, RecordedStatus>>,
+    timings: TestTimings,
+    // Set while the run is checkpointed, which it is unless tests are run several times.
+    checkpoint_path: Option<Utf8PathBuf>,
+    // The number of tests that failed because they were terminated when the run was canceled.
+    terminated: usize,
+}
+
+impl<'a> RunRecorder<'a> {
+    pub(crate) fn new(profile: &NextestProfile<'_>) -> Self {
+        Self {
+            store: RunStore::new(profile),
+            tests: BTreeMap::new(),
+            timings: TestTimings::default(),
+            checkpoint_path: None,
+            terminated: 0,
+        }
+    }
+
+    pub(crate) fn write_event(&mut self, event: &TestEvent<'a>) -> Result<(), WriteEventError> {
+        match event {
+            TestEvent::RunStarted {
+                test_list,
+                run_id,
+                iterations,
+                until_failure,
+                ..
+            } => {
+                if *iterations == Some(1) && !until_failure {
+                    self.start_checkpoint(test_list, *run_id)?;
+                }
+
+                // Timings are kept across runs, except for tests that no longer exist. Failing to
+                // read them only means that they'll be recorded afresh.
+                self.timings = match self.store.read_timings() {
+                    Ok(timings) => timings.unwrap_or_default(),
+                    Err(error) => {
+                        log::debug!("discarding previously recorded test timings: {error}");
+                        TestTimings::default()
+                    }
+                };
+                self.add_tests(test_list);
+            }
+            TestEvent::TestsAdded { test_list } => {
+                // The tests are added to the checkpoint, so that a resumed run is made up of them
+                // too.
+                if let Some(path) = &self.checkpoint_path {
+                    let added = CheckpointTestsAdded {
+                        tests_added: checkpoint_tests(test_list),
+                    };
+                    if !added.tests_added.is_empty() {
+                        append_line(path, &added).map_err(|error| WriteEventError::Fs {
+                            file: path.clone(),
+                            error,
+                        })?;
+                    }
+                }
+                self.add_tests(test_list);
+            }
+            TestEvent::TestFinished {
+                test_instance,
+                run_statuses,
+                cancel_state,
+                ..
+            } => {
+                let status = match run_statuses.describe() {
+                    ExecutionDescription::Success { .. } => RecordedStatus::Passed,
+                    ExecutionDescription::Flaky { .. } => RecordedStatus::Flaky,
+                    ExecutionDescription::Failure { .. } => RecordedStatus::Failed,
+                };
+                self.timings.insert(
+                    &test_instance.bin_info.binary_id,
+                    test_instance.name,
+                    run_statuses.last_status().time_taken,
+                );
+                self.tests
+                    .entry(&test_instance.bin_info.binary_id)
+                    .or_default()
+                    .insert(test_instance.name, status);
+
+                if let Some(path) = &self.checkpoint_path {
+                    // Terminated tests are run again when the run is resumed.
+                    if terminated_by_cancel(run_statuses, *cancel_state) {
+                        self.terminated += 1;
+                        return Ok(());
+                    }
+                    let entry = CheckpointEntry {
+                        binary_id: test_instance.bin_info.binary_id.as_str(),
+                        name: test_instance.name,
+                        status,
+                        time_taken: run_statuses.last_status().time_taken.as_secs_f64(),
+                    };
+                    append_line(path, &entry).map_err(|error| WriteEventError::Fs {
+                        file: path.clone(),
+                        error,
+                    })?;
+                }
+            }
+            TestEvent::TestPreviouslyFinished {
+                test_instance,
+                status,
+                time_taken,
+                ..
+            } => {
+                let status = match status {
+                    RunStatus::Passed => RecordedStatus::Passed,
+                    RunStatus::Failed => RecordedStatus::Failed,
+                    RunStatus::Flaky => RecordedStatus::Flaky,
+                    RunStatus::Skipped => RecordedStatus::Skipped,
+                };
+                self.timings.insert(
+                    &test_instance.bin_info.binary_id,
+                    test_instance.name,
+                    *time_taken,
+                );
+                self.tests
+                    .entry(&test_instance.bin_info.binary_id)
+                    .or_default()
+                    .insert(test_instance.name, status);
+            }
+            TestEvent::RunFinished { run_stats, .. } => {
+                // Once every test has finished, there's nothing left to resume.
+                if let Some(path) = self.checkpoint_path.take() {
+                    if run_stats.finished_count >= run_stats.initial_run_count
+                        && self.terminated == 0
+                    {
+                        std::fs::remove_file(&path)
+                            .map_err(|error| WriteEventError::Fs { file: path, error })?;
+                    }
+                }
+
+                let recorded = RecordedRun {
+                    format_version: RunStore::FORMAT_VERSION,
+                    tests: std::mem::take(&mut self.tests),
+                };
+                self.store
+                    .write(&recorded)
+                    .map_err(|error| WriteEventError::Fs {
+                        file: self.store.path.clone(),
+                        error,
+                    })?;
+                self.store
+                    .write_timings(&self.timings)
+                    .map_err(|error| WriteEventError::Fs {
+                        file: self.store.timings_path.clone(),
+                        error,
+                    })?;
+            }
+            TestEvent::TestStarted { .. }
+            | TestEvent::TestSlow { .. }
+            | TestEvent::TestOutput { .. }
+            | TestEvent::TestRetry { .. }
+            | TestEvent::TestIsolationChecked { .. }
+            | TestEvent::TestDeterminismChecked { .. }
+            | TestEvent::TestSkipped { .. }
+            | TestEvent::TestNotRun { .. }
+            | TestEvent::RunBeginCancel { .. } => {}
+        }
+        Ok(())
+    }
+
+    fn add_tests(&mut self, test_list: &'a TestList<'a>) {
+        // Tests that don't finish, whether because they're filtered out or because the run is
+        // canceled, are recorded as skipped.
+        for test_instance in test_list.iter_tests() {
+            self.tests
+                .entry(&test_instance.bin_info.binary_id)
+                .or_default()
+                .insert(test_instance.name, RecordedStatus::Skipped);
+        }
+
+        for (_, suite) in test_list.iter() {
+            if let RustTestSuiteStatus::Listed { test_cases } = &suite.status {
+                if let Some(tests) = self.timings.tests.get_mut(&suite.binary_id) {
+                    tests.retain(|test_name, _| test_cases.contains_key(test_name));
+                }
+            }
+        }
+        self.timings.tests.retain(|_, tests| !tests.is_empty());
+    }
+
+    fn start_checkpoint(
+        &mut self,
+        test_list: &'a TestList<'a>,
+        run_id: Uuid,
+    ) -> Result<(), WriteEventError> {
+        let path = self.store.checkpoint_path(run_id);
+        // A resumed run keeps adding to the checkpoint of the run it resumes.
+        if !path.exists() {
+            let header = CheckpointHeader {
+                format_version: RunStore::FORMAT_VERSION,
+                tests: checkpoint_tests(test_list),
+            };
+            std::fs::create_dir_all(&self.store.checkpoints_dir)
+                .and_then(|()| append_line(&path, &header))
+                .map_err(|error| WriteEventError::Fs {
+                    file: path.clone(),
+                    error,
+                })?;
+        }
+        self.checkpoint_path = Some(path);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct RecordedRun<S: Ord = String> {
+    format_version: u32,
+    // binary ID -> test name -> status
+    tests: BTreeMap<S, BTreeMap<S, RecordedStatus>>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct RecordedTimings<S: Ord = String> {
+    format_version: u32,
+    // binary ID -> test name -> duration in seconds
+    tests: BTreeMap<S, BTreeMap<S, f64>>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct CheckpointHeader<S: Ord = String> {
+    format_version: u32,
+    // binary ID -> names of the tests the run was started with
+    tests: BTreeMap<S, BTreeSet<S>>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct CheckpointEntry<S = String> {
+    binary_id: S,
+    name: S,
+    status: RecordedStatus,
+    // The time the last attempt took, in seconds.
+    time_taken: f64,
+}
+
+// Written out when tests are listed while the run is in progress.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct CheckpointTestsAdded<S: Ord = String> {
+    // binary ID -> names of the tests added to the run
+    tests_added: BTreeMap<S, BTreeSet<S>>,
+}
+
+// A line after the checkpoint header.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum CheckpointLine {
+    Entry(CheckpointEntry),
+    TestsAdded(CheckpointTestsAdded),
+}
+
+/// Returns the tests in this list that are run, by binary ID.
+fn checkpoint_tests<'a>(test_list: &'a TestList<'a>) -> BTreeMap<&'a str, BTreeSet<&'a str>> {
+    let mut tests: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+    for test_instance in test_list.iter_tests() {
+        if test_instance.test_info.filter_match.is_match() {
+            tests
+                .entry(test_instance.bin_info.binary_id.as_str())
+                .or_default()
+                .insert(test_instance.name);
+        }
+    }
+    tests
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum RecordedStatus {
+    Passed,
+    Failed,
+    Flaky,
+    Skipped,
+}
+
+impl From<RecordedStatus> for RunStatus {
+    fn from(status: RecordedStatus) -> Self {
+        match status {
+            RecordedStatus::Passed => RunStatus::Passed,
+            RecordedStatus::Failed => RunStatus::Failed,
+            RecordedStatus::Flaky => RunStatus::Flaky,
+            RecordedStatus::Skipped => RunStatus::Skipped,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        list::TestInstance,
+        runner::{ExecutionResult, RunStats},
+        test_helpers::{fake_status, fake_suite, fake_test_case},
+    };
+    use nextest_metadata::BuildPlatform;
+
+    #[test]
+    fn test_run_store_roundtrip() {
+        let temp_dir = tempfile::tempdir().expect("created temp dir");
+        let temp_dir = Utf8Path::from_path(temp_dir.path()).expect("temp dir is valid UTF-8");
+        let store = RunStore::from_store_dir(temp_dir);
+        assert_eq!(
+            store.read_last_run().expect("missing record is fine"),
+            None,
+            "no run recorded yet"
+        );
+
+        let mut tests = BTreeMap::new();
+        tests.insert(
+            "my-package::my-binary",
+            [
+                ("tests::passed", RecordedStatus::Passed),
+                ("tests::failed", RecordedStatus::Failed),
+                ("tests::flaky", RecordedStatus::Flaky),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        store
+            .write(&RecordedRun {
+                format_version: RunStore::FORMAT_VERSION,
+                tests,
+            })
+            .expect("wrote run record");
+
+        let previous_run = store
+            .read_last_run()
+            .expect("read run record")
+            .expect("run was recorded");
+        assert_eq!(
+            previous_run.status("my-package::my-binary", "tests::failed"),
+            Some(RunStatus::Failed)
+        );
+        assert_eq!(
+            previous_run.status("my-package::my-binary", "tests::flaky"),
+            Some(RunStatus::Flaky)
+        );
+        assert_eq!(
+            previous_run.status("my-package::my-binary", "tests::missing"),
+            None
+        );
+
+        std::fs::write(store.path(), r#"{ "format-version": 2, "tests": {} }"#)
+            .expect("wrote newer run record");
+        assert!(
+            matches!(
+                store.read_last_run(),
+                Err(RunStoreReadError::UnsupportedFormatVersion {
+                    format_version: 2,
+                    ..
+                })
+            ),
+            "newer format versions are rejected"
+        );
+    }
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let temp_dir = tempfile::tempdir().expect("created temp dir");
+        let temp_dir = Utf8Path::from_path(temp_dir.path()).expect("temp dir is valid UTF-8");
+        let store = RunStore::from_store_dir(temp_dir);
+        let run_id = Uuid::new_v4();
+        assert!(
+            matches!(
+                store.read_checkpoint(run_id),
+                Err(RunStoreReadError::CheckpointNotFound { .. })
+            ),
+            "no checkpoint written yet"
+        );
+
+        let path = store.checkpoint_path(run_id);
+        std::fs::create_dir_all(path.parent().unwrap()).expect("created checkpoints dir");
+        let mut tests = BTreeMap::new();
+        tests.insert(
+            "my-package::my-binary",
+            ["tests::passed", "tests::failed", "tests::unfinished"]
+                .into_iter()
+                .collect(),
+        );
+        append_line(
+            &path,
+            &CheckpointHeader {
+                format_version: RunStore::FORMAT_VERSION,
+                tests,
+            },
+        )
+        .expect("wrote header");
+        for (name, status) in [
+            ("tests::passed", RecordedStatus::Passed),
+            ("tests::failed", RecordedStatus::Failed),
+        ] {
+            let entry = CheckpointEntry {
+                binary_id: "my-package::my-binary",
+                name,
+                status,
+                time_taken: 1.5,
+            };
+            append_line(&path, &entry).expect("wrote entry");
+        }
+        // Tests listed while the run was in progress are part of it too.
+        let mut tests_added = BTreeMap::new();
+        tests_added.insert(
+            "my-package::other-binary",
+            ["tests::added"].into_iter().collect(),
+        );
+        append_line(&path, &CheckpointTestsAdded { tests_added }).expect("wrote added tests");
+
+        let resumed = store.read_checkpoint(run_id).expect("read checkpoint");
+        assert_eq!(resumed.run_id(), run_id);
+        assert_eq!(resumed.test_ids().count(), 4);
+        assert_eq!(resumed.finished_count(), 2);
+        assert_eq!(
+            resumed.finished("my-package::my-binary", "tests::failed"),
+            Some(FinishedTest {
+                status: RunStatus::Failed,
+                time_taken: Duration::from_millis(1500),
+            })
+        );
+        assert_eq!(
+            resumed.finished("my-package::my-binary", "tests::unfinished"),
+            None
+        );
+
+        // A line cut short at the end is left out, but not one in the middle.
+        let mut contents = std::fs::read_to_string(&path).expect("read checkpoint");
+        contents.push_str(r#"{"binary-id": "my-package::my-binary", "na"#);
+        std::fs::write(&path, &contents).expect("wrote truncated checkpoint");
+        assert_eq!(
+            store.read_checkpoint(run_id).expect("read checkpoint"),
+            resumed
+        );
+        contents.push_str("\n{}\n");
+        std::fs::write(&path, &contents).expect("wrote corrupted checkpoint");
+        assert!(
+            matches!(
+                store.read_checkpoint(run_id),
+                Err(RunStoreReadError::Deserialize { .. })
+            ),
+            "corrupted lines are rejected"
+        );
+    }
+
+    #[test]
+    fn test_timings_roundtrip() {
+        let temp_dir = tempfile::tempdir().expect("created temp dir");
+        let temp_dir = Utf8Path::from_path(temp_dir.path()).expect("temp dir is valid UTF-8");
+        let store = RunStore::from_store_dir(temp_dir);
+        assert_eq!(
+            store.read_timings().expect("missing record is fine"),
+            None,
+            "no timings recorded yet"
+        );
+
+        let mut timings = TestTimings::default();
+        timings.insert(
+            "my-package::my-binary",
+            "tests::fast",
+            Duration::from_millis(250),
+        );
+        timings.insert(
+            "my-package::my-binary",
+            "tests::slow",
+            Duration::from_secs(12),
+        );
+        store.write_timings(&timings).expect("wrote timings");
+
+        let read = store
+            .read_timings()
+            .expect("read timings")
+            .expect("timings were recorded");
+        assert_eq!(read, timings);
+        assert_eq!(
+            read.get("my-package::my-binary", "tests::slow"),
+            Some(Duration::from_secs(12))
+        );
+        assert_eq!(read.get("my-package::my-binary", "tests::missing"), None);
+        assert_eq!(read.iter().count(), 2);
+    }
+
+    #[test]
+    fn test_failure_retry() {
+        let suite = fake_suite("my-crate", BuildPlatform::Target);
+        let test_case = fake_test_case();
+        let fail = ExecutionResult::Fail {
+            abort_status: None,
+            leaked: false,
+        };
+        let test_list = TestList::empty();
+        let run_id = Uuid::new_v4();
+        let run_started = |iterations| TestEvent::RunStarted {
+            test_list: &test_list,
+            run_id,
+            iterations,
+            until_failure: false,
+            shuffle_seed: None,
+            seed: 0,
+            previously_finished: None,
+            listing: false,
+        };
+        let finished = |name, statuses| TestEvent::TestFinished {
+            test_instance: TestInstance::new(name, "/fake/binary", &suite, &test_case),
+            run_statuses: ExecutionStatuses::new(statuses),
+            quarantined: false,
+            current_stats: RunStats::default(),
+            running: 0,
+            cancel_state: None,
+        };
+        let previously_finished = |name, status| TestEvent::TestPreviouslyFinished {
+            test_instance: TestInstance::new(name, "/fake/binary", &suite, &test_case),
+            status,
+            time_taken: Duration::from_secs(1),
+            current_stats: RunStats::default(),
+        };
+        let events = [
+            finished(
+                "tests::passed",
+                vec![fake_status(1, 1, ExecutionResult::Pass, "")],
+            ),
+            finished(
+                "tests::flaky",
+                vec![
+                    fake_status(1, 2, fail, ""),
+                    fake_status(2, 2, ExecutionResult::Pass, ""),
+                ],
+            ),
+            finished("tests::failed", vec![fake_status(1, 1, fail, "")]),
+            previously_finished("tests::previously-passed", RunStatus::Passed),
+            previously_finished("tests::previously-failed", RunStatus::Failed),
+        ];
+
+        let mut retry = FailureRetry::new();
+        retry.write_event(&run_started(Some(1)));
+        for event in &events {
+            retry.write_event(event);
+        }
+        assert_eq!(retry.failed_count(), 2);
+        let resumed_run = retry.into_resumed_run().expect("failed tests are retried");
+        assert_eq!(resumed_run.run_id(), run_id, "the run keeps its ID");
+        assert_eq!(resumed_run.finished_count(), 3);
+        for (test_name, status) in [
+            ("tests::passed", Some(RunStatus::Passed)),
+            ("tests::flaky", Some(RunStatus::Flaky)),
+            ("tests::previously-passed", Some(RunStatus::Passed)),
+            ("tests::failed", None),
+            ("tests::previously-failed", None),
+        ] {
+            assert_eq!(
+                resumed_run
+                    .finished("my-crate", test_name)
+                    .map(|finished| finished.status),
+                status,
+                "{test_name} is run again if it failed"
+            );
+        }
+
+        let mut retry = FailureRetry::new();
+        retry.write_event(&run_started(Some(1)));
+        retry.write_event(&events[0]);
+        assert_eq!(
+            retry.into_resumed_run(),
+            None,
+            "nothing to retry without failures"
+        );
+
+        let mut retry = FailureRetry::new();
+        retry.write_event(&run_started(Some(2)));
+        retry.write_event(&events[2]);
+        assert_eq!(
+            retry.into_resumed_run(),
+            None,
+            "runs with several iterations can't be resumed"
+        );
+
+        let mut retry = FailureRetry::new();
+        retry.write_event(&run_started(Some(1)));
+        retry.write_event(&events[2]);
+        retry.write_event(&TestEvent::RunBeginCancel {
+            running: 0,
+            reason: CancelReason::Signal,
+        });
+        assert_eq!(
+            retry.into_resumed_run(),
+            None,
+            "canceled runs aren't run again"
+        );
+
+        let mut retry = FailureRetry::new();
+        retry.write_event(&run_started(Some(1)));
+        retry.write_event(&events[2]);
+        retry.write_event(&TestEvent::RunBeginCancel {
+            running: 0,
+            reason: CancelReason::TestFailure,
+        });
+        assert!(
+            retry.into_resumed_run().is_some(),
+            "runs stopped by fail-fast are run again"
+        );
+    }
+}
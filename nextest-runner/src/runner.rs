@@ -6,33 +6,60 @@
 //! The main structure in this module is [`TestRunner`].
 
 use crate::{
-    config::{NextestProfile, ProfileOverrides, TestThreads},
+    bench::{criterion_home, CRITERION_HOME_ENV},
+    captured_output::{interleave, CapturedOutput, OutputLimits},
+    clean_env::EnvCleaner,
+    config::{
+        AdaptiveThreads, JobserverMode, LeakedProcessPolicy, NextestProfile, ProfileOverrides,
+        RetryCondition, RetryPolicy, RetrySchedule, StdinMode, TerminateStep, TestOrder,
+        TestThreads,
+    },
+    cpu_affinity::{CpuPinner, PinnedCpus},
+    determinism::compare_runs,
     errors::{ConfigureHandleInheritanceError, TestRunnerBuildError},
     helpers::convert_build_platform,
-    list::{TestInstance, TestList},
+    list::{TestInstance, TestList, TestListBatches},
+    memory_limit::MemoryLimiter,
+    network_isolation::{is_isolation_error, isolate_network},
     reporter::{CancelReason, FinalStatusLevel, StatusLevel, TestEvent},
+    run_store::{FinishedTest, ResumedRun, RunStore, TestTimings},
     signal::{SignalEvent, SignalHandler, SignalHandlerKind},
     stopwatch::{StopwatchEnd, StopwatchStart},
+    system_load::LoadSample,
     target_runner::TargetRunner,
+    test_tmpdir::TestTmpdirs,
 };
 use async_scoped::TokioScope;
-use bytes::Bytes;
-use futures::prelude::*;
-use nextest_filtering::{BinaryQuery, TestQuery};
+use bytes::{Bytes, BytesMut};
+use futures::{future::BoxFuture, prelude::*};
+use nextest_filtering::{BinaryQuery, FilteringExpr, RunStatus, TestQuery};
 use nextest_metadata::{FilterMatch, MismatchReason};
+use once_cell::sync::OnceCell;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
 use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, HashMap},
     convert::Infallible,
+    fmt, io,
     marker::PhantomData,
     num::NonZeroUsize,
+    ops::Range,
+    path::PathBuf,
     process::Stdio,
-    sync::atomic::{AtomicBool, Ordering},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, Once,
+    },
     time::{Duration, SystemTime},
 };
 use tokio::{
-    io::{AsyncReadExt, BufReader},
+    io::{AsyncReadExt, AsyncWriteExt, BufReader},
     process::Child,
     runtime::Runtime,
-    sync::mpsc::UnboundedSender,
+    sync::{
+        mpsc::{UnboundedReceiver, UnboundedSender},
+        oneshot, Notify, Semaphore, SemaphorePermit,
+    },
 };
 use uuid::Uuid;
 
@@ -40,9 +67,21 @@ use uuid::Uuid;
 #[derive(Debug, Default)]
 pub struct TestRunnerBuilder {
     no_capture: bool,
+    live_output: bool,
+    live_output_filter: Vec<FilteringExpr>,
+    bench: bool,
     retries: Option<usize>,
     fail_fast: Option<bool>,
+    max_fail: Option<usize>,
+    iterations: Option<usize>,
+    until_failure: bool,
+    shuffle: bool,
+    shuffle_seed: Option<u64>,
+    seed: Option<u64>,
+    check_determinism: bool,
+    resumed_run: Option<ResumedRun>,
     test_threads: Option<TestThreads>,
+    max_run_time: Option<Duration>,
 }
 
 impl TestRunnerBuilder {
@@ -54,6 +93,35 @@ impl TestRunnerBuilder {
         self
     }
 
+    /// Sets live-output mode.
+    ///
+    /// In this mode, output is still captured and tests are still run in parallel, but each line a
+    /// test writes is also reported as soon as it's written, with
+    /// [`TestEvent::TestOutput`](crate::reporter::TestEvent::TestOutput).
+    pub fn set_live_output(&mut self, live_output: bool) -> &mut Self {
+        self.live_output = live_output;
+        self
+    }
+
+    /// Sets filter expressions for tests to run in live-output mode.
+    ///
+    /// Tests that match any of the expressions are run as if live-output mode were set, along
+    /// with tests that set `live-output` through overrides. Other tests are run as usual.
+    pub fn set_live_output_filter(&mut self, exprs: Vec<FilteringExpr>) -> &mut Self {
+        self.live_output_filter = exprs;
+        self
+    }
+
+    /// Sets bench mode.
+    ///
+    /// In this mode, `--bench` is passed in to test binaries so that benchmarks are measured rather
+    /// than run once as tests, and criterion is pointed at `<target-dir>/criterion` for its results.
+    /// Benchmarks are always run serially: `test_threads` will always be 1.
+    pub fn set_bench(&mut self, bench: bool) -> &mut Self {
+        self.bench = bench;
+        self
+    }
+
     /// Sets the number of retries for this test runner.
     pub fn set_retries(&mut self, retries: usize) -> &mut Self {
         self.retries = Some(retries);
@@ -66,12 +134,97 @@ impl TestRunnerBuilder {
         self
     }
 
+    /// Sets the number of tests that can fail before the run is canceled.
+    ///
+    /// This overrides the fail-fast value, which cancels the run after one test fails if set.
+    pub fn set_max_fail(&mut self, max_fail: usize) -> &mut Self {
+        self.max_fail = Some(max_fail);
+        self
+    }
+
+    /// Sets the number of times each test is run.
+    ///
+    /// Each iteration of a test is run and retried independently of the others.
+    pub fn set_iterations(&mut self, iterations: usize) -> &mut Self {
+        self.iterations = Some(iterations);
+        self
+    }
+
+    /// Sets until-failure mode.
+    ///
+    /// In this mode, tests are run over and over until one of them fails, and the run is canceled
+    /// as soon as that happens. The number of iterations, if set, caps how many times tests are
+    /// run.
+    pub fn set_until_failure(&mut self, until_failure: bool) -> &mut Self {
+        self.until_failure = until_failure;
+        self
+    }
+
+    /// Sets whether tests are started in a random order.
+    ///
+    /// This overrides the test-order setting. The order is picked with a random seed, unless one is
+    /// set with [`Self::set_shuffle_seed`].
+    pub fn set_shuffle(&mut self, shuffle: bool) -> &mut Self {
+        self.shuffle = shuffle;
+        self
+    }
+
+    /// Sets the seed to shuffle tests with, which also turns shuffling on.
+    ///
+    /// The same seed always gives the same order for the same list of tests.
+    pub fn set_shuffle_seed(&mut self, seed: u64) -> &mut Self {
+        self.shuffle_seed = Some(seed);
+        self
+    }
+
+    /// Sets the seed that the `NEXTEST_TEST_SEED` value given to each test is derived from.
+    ///
+    /// Each test gets a different value, which stays the same across retries but changes between
+    /// iterations. If this isn't set, a random seed is used.
+    pub fn set_seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Resumes an interrupted run, read from its checkpoint.
+    ///
+    /// The run keeps the ID of the interrupted run. Tests that finished before it was interrupted
+    /// aren't run again: they're reported with
+    /// [`TestEvent::TestPreviouslyFinished`](crate::reporter::TestEvent::TestPreviouslyFinished)
+    /// instead, and count towards the run's statistics. The test list should be restricted to the
+    /// tests the interrupted run was started with.
+    pub fn set_resumed_run(&mut self, resumed_run: ResumedRun) -> &mut Self {
+        self.resumed_run = Some(resumed_run);
+        self
+    }
+
+    /// Sets whether tests that pass are run again to check that their output is the same.
+    ///
+    /// Each test that passes is run a second time right away, and its standard output and standard
+    /// error are compared with the first run's, leaving out the parts of lines that match the
+    /// determinism-ignore patterns. Tests whose output differs, or that fail the second time, fail
+    /// the run.
+    pub fn set_check_determinism(&mut self, check_determinism: bool) -> &mut Self {
+        self.check_determinism = check_determinism;
+        self
+    }
+
     /// Sets the number of tests to run simultaneously.
     pub fn set_test_threads(&mut self, test_threads: TestThreads) -> &mut Self {
         self.test_threads = Some(test_threads);
         self
     }
 
+    /// Sets the run time budget for this test runner.
+    ///
+    /// Once the run has taken this long, no more tests are started and tests still running are
+    /// terminated. Tests that wouldn't finish within the budget, going by how long they took the
+    /// last time they were run, aren't started either.
+    pub fn set_max_run_time(&mut self, max_run_time: Duration) -> &mut Self {
+        self.max_run_time = Some(max_run_time);
+        self
+    }
+
     /// Creates a new test runner.
     pub fn build<'a>(
         self,
@@ -80,7 +233,7 @@ impl TestRunnerBuilder {
         handler_kind: SignalHandlerKind,
         target_runner: TargetRunner,
     ) -> Result<TestRunner<'a>, TestRunnerBuildError> {
-        let test_threads = match self.no_capture {
+        let test_threads = match self.no_capture || self.bench {
             true => 1,
             false => self
                 .test_threads
@@ -88,12 +241,86 @@ impl TestRunnerBuilder {
                 .compute(),
         };
         let (retries, ignore_retry_overrides) = match self.retries {
-            Some(retries) => (retries, true),
+            Some(retries) => (RetryPolicy::new_without_delay(retries), true),
             None => (profile.retries(), false),
         };
-        let fail_fast = self.fail_fast.unwrap_or_else(|| profile.fail_fast());
+        let adaptive_threads = match self.no_capture || self.bench {
+            true => None,
+            false => profile.adaptive_threads(),
+        };
+        let retry_if = profile.retry_if();
+        let retry_schedule = profile.retry_schedule();
+        let check_failures_in_isolation = profile.check_failures_in_isolation();
+        let iterations = match (self.iterations, self.until_failure) {
+            (Some(iterations), _) => Some(iterations.max(1)),
+            (None, true) => None,
+            (None, false) => Some(1),
+        };
+        let max_fail = match (self.until_failure, self.max_fail) {
+            (true, _) => Some(1),
+            (false, Some(max_fail)) => Some(max_fail),
+            (false, None) => self
+                .fail_fast
+                .unwrap_or_else(|| profile.fail_fast())
+                .then(|| 1),
+        };
         let slow_timeout = profile.slow_timeout();
         let leak_timeout = profile.leak_timeout();
+        let terminate_grace_period = profile.terminate_grace_period();
+        let terminate_signals = profile.terminate_signals();
+        // With no-capture, nextest doesn't see the output of tests.
+        let no_output_timeout = match self.no_capture {
+            true => None,
+            false => profile.no_output_timeout(),
+        };
+        let leaked_processes = profile.leaked_processes();
+        let strict_xfail = profile.strict_xfail();
+        let test_groups = profile
+            .test_groups()
+            .iter()
+            .map(|(name, group)| {
+                let semaphore = Semaphore::new(group.max_threads().compute());
+                (name.clone(), semaphore)
+            })
+            .collect();
+        let resource_pools = profile
+            .resources()
+            .iter()
+            .map(|(name, &count)| (name.clone(), ResourcePool::new(count)))
+            .collect();
+        let shuffle_seed = match (self.shuffle, self.shuffle_seed) {
+            (_, Some(seed)) => Some(seed),
+            (true, None) => Some(rand::random()),
+            (false, None) => None,
+        };
+        let slowest_first =
+            shuffle_seed.is_none() && profile.test_order() == TestOrder::SlowestFirst;
+        // Timings are also used to tell whether tests would finish within the run time budget.
+        let timings = match slowest_first || self.max_run_time.is_some() {
+            true => read_scheduling_timings(&profile),
+            false => None,
+        };
+        let jobserver = JobserverTokens::new(profile.jobserver(), test_threads)
+            .map_err(TestRunnerBuildError::JobserverSetup)?;
+        let memory_limit = profile.memory_limit();
+        let pin_cpus = profile.pin_cpus();
+        let nice = profile.nice();
+        let network_isolation = profile.network_isolation();
+        let stdin = profile.stdin();
+        let env_cleaner = profile
+            .clean_env()
+            .then(|| EnvCleaner::new(profile.env_allowlist()));
+        let run_id = self
+            .resumed_run
+            .as_ref()
+            .map_or_else(Uuid::new_v4, ResumedRun::run_id);
+        let test_tmpdir = profile.test_tmpdir();
+        let test_tmpdirs = test_tmpdir.map(|_| TestTmpdirs::new(format!("nextest-{run_id}")));
+        let keep_tmpdirs_on_failure =
+            test_tmpdir.map_or(false, |test_tmpdir| test_tmpdir.keep_on_failure());
+        let output_limits = profile
+            .captured_output()
+            .map(|config| OutputLimits::new(&config));
 
         let runtime = Runtime::new().map_err(TestRunnerBuildError::TokioRuntimeCreate)?;
         let _guard = runtime.enter();
@@ -104,18 +331,53 @@ impl TestRunnerBuilder {
         Ok(TestRunner {
             inner: TestRunnerInner {
                 no_capture: self.no_capture,
+                live_output: self.live_output,
+                live_output_filter: self.live_output_filter,
+                bench: self.bench,
                 profile,
                 test_threads,
-                // The number of tries = retries + 1.
-                global_tries: retries + 1,
+                adaptive_threads,
+                test_groups,
+                resource_pools,
+                jobserver,
+                memory_limit,
+                memory_limiter: OnceCell::new(),
+                pin_cpus,
+                cpu_pinner: OnceCell::new(),
+                nice,
+                network_isolation,
+                network_isolation_warning: Once::new(),
+                test_tmpdirs,
+                keep_tmpdirs_on_failure,
+                output_limits,
+                stdin,
+                env_cleaner,
+                retries,
+                retry_if,
+                retry_schedule,
+                check_failures_in_isolation,
                 ignore_retry_overrides,
-                fail_fast,
+                max_fail,
+                iterations,
+                until_failure: self.until_failure,
                 slow_timeout,
                 leak_timeout,
+                terminate_grace_period,
+                terminate_signals,
+                no_output_timeout,
+                leaked_processes,
+                strict_xfail,
                 test_list,
+                timings,
+                slowest_first,
+                shuffle_seed,
+                seed: self.seed.unwrap_or_else(rand::random),
+                check_determinism: self.check_determinism,
+                resumed_run: self.resumed_run,
+                max_run_time: self.max_run_time,
                 target_runner,
                 runtime,
-                run_id: Uuid::new_v4(),
+                run_id,
             },
             handler,
         })
@@ -155,34 +417,247 @@ impl<'a> TestRunner<'a> {
         F: FnMut(TestEvent<'a>) -> Result<(), E> + Send,
         E: Send,
     {
-        self.inner.try_execute(&mut self.handler, callback)
+        self.inner.try_execute(
+            &mut self.handler,
+            None::<(
+                &TestListBatches<'a>,
+                fn(&TestListSender<'a, 'a, E>) -> Result<(), E>,
+            )>,
+            callback,
+        )
+    }
+
+    /// Executes tests while more of them are being listed, each one in its own process.
+    ///
+    /// `list_tests` is called once the run has started, on a thread where it's allowed to block.
+    /// It sends futures that create test lists through the [`TestListSender`] passed in to it. As
+    /// soon as each of those lists is created, it's added to `batches` and its tests start running
+    /// alongside the tests in the list this runner was built with. The run finishes once
+    /// `list_tests` has returned and every one of its tests has finished.
+    ///
+    /// If `list_tests` or the creation of a test list fails, the run is canceled and the first of
+    /// those errors is returned.
+    pub fn try_execute_while_listing<'g, E, F, L>(
+        &mut self,
+        batches: &'a TestListBatches<'g>,
+        list_tests: L,
+        callback: F,
+    ) -> Result<RunStats, E>
+    where
+        'g: 'a,
+        F: FnMut(TestEvent<'a>) -> Result<(), E> + Send,
+        L: FnOnce(&TestListSender<'a, 'g, E>) -> Result<(), E> + Send,
+        E: Send,
+    {
+        self.inner
+            .try_execute(&mut self.handler, Some((batches, list_tests)), callback)
+    }
+}
+
+/// Sends test lists to be run as they're created, in [`TestRunner::try_execute_while_listing`].
+pub struct TestListSender<'a, 'g, E> {
+    sender: futures::channel::mpsc::UnboundedSender<BoxFuture<'a, Result<TestList<'g>, E>>>,
+    canceled: Arc<AtomicBool>,
+}
+
+impl<'a, 'g, E> TestListSender<'a, 'g, E> {
+    /// Sends a future that creates a test list. The tests in the list are run once it finishes.
+    ///
+    /// Futures sent are run at the same time as each other, on the runner's Tokio runtime.
+    pub fn send(&self, test_list: impl Future<Output = Result<TestList<'g>, E>> + Send + 'a) {
+        // Failure to send means the run is over.
+        let _ = self.sender.unbounded_send(test_list.boxed());
+    }
+
+    /// Returns true if the run was canceled, in which case no more test lists need to be sent.
+    pub fn is_canceled(&self) -> bool {
+        self.canceled.load(Ordering::Acquire)
+    }
+}
+
+impl<'a, 'g, E> fmt::Debug for TestListSender<'a, 'g, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TestListSender")
+            .field("canceled", &self.is_canceled())
+            .finish_non_exhaustive()
     }
 }
 
 #[derive(Debug)]
 struct TestRunnerInner<'a> {
     no_capture: bool,
+    // Set if lines of output are reported as tests write them.
+    live_output: bool,
+    // Lines of output are also reported for tests that match any of these.
+    live_output_filter: Vec<FilteringExpr>,
+    bench: bool,
     profile: NextestProfile<'a>,
     test_threads: usize,
-    global_tries: usize,
+    // Set if the number of tests running at a time is scaled with system load.
+    adaptive_threads: Option<AdaptiveThreads>,
+    // Limits how many tests in each test group run at the same time.
+    test_groups: HashMap<String, Semaphore>,
+    // Hands out tokens for resources consumed by tests.
+    resource_pools: HashMap<String, ResourcePool>,
+    // Set if each running test holds a token from a jobserver.
+    jobserver: Option<JobserverTokens>,
+    // The memory limit for tests that don't have one set through overrides.
+    memory_limit: Option<u64>,
+    // Set up once a test with a memory limit is run.
+    memory_limiter: OnceCell<MemoryLimiter>,
+    // Whether tests that don't set pin-cpus through overrides are pinned to their own CPUs.
+    pin_cpus: bool,
+    // Set up once a test that's pinned to CPUs is run.
+    cpu_pinner: OnceCell<CpuPinner>,
+    // The nice value for tests that don't set one through overrides.
+    nice: Option<i32>,
+    // Whether tests that don't set network-isolation through overrides are run without network
+    // access.
+    network_isolation: bool,
+    // Used to warn once if network isolation isn't supported on this platform, or if tests can't be
+    // started with it.
+    network_isolation_warning: Once,
+    // Set if each test is given its own temporary directory.
+    test_tmpdirs: Option<TestTmpdirs>,
+    keep_tmpdirs_on_failure: bool,
+    // Set if the output captured from each test is cut down once it gets too long.
+    output_limits: Option<OutputLimits>,
+    // What tests that don't set stdin through overrides are given as standard input.
+    stdin: StdinMode,
+    // Set if tests are run in a clean environment.
+    env_cleaner: Option<EnvCleaner>,
+    retries: RetryPolicy,
+    // Set if failed attempts of tests that don't set retry-if through overrides are only retried
+    // if they match this.
+    retry_if: Option<&'a RetryCondition>,
+    retry_schedule: RetrySchedule,
+    check_failures_in_isolation: bool,
     ignore_retry_overrides: bool,
-    fail_fast: bool,
+    // The number of failed tests the run is canceled after, if any.
+    max_fail: Option<usize>,
+    // The number of times each test is run, or None if tests are run until one fails with no limit.
+    iterations: Option<usize>,
+    until_failure: bool,
     slow_timeout: crate::config::SlowTimeout,
     leak_timeout: Duration,
+    terminate_grace_period: Duration,
+    // Set if the signals sent to tests that time out are configured.
+    terminate_signals: Option<&'a [TerminateStep]>,
+    no_output_timeout: Option<Duration>,
+    leaked_processes: LeakedProcessPolicy,
+    strict_xfail: bool,
     test_list: &'a TestList<'a>,
+    // Set if tests are started slowest first or there's a run time budget, and timings were
+    // recorded for the profile.
+    timings: Option<TestTimings>,
+    slowest_first: bool,
+    // Set if tests are started in an order shuffled with this seed.
+    shuffle_seed: Option<u64>,
+    // The seed that each test's NEXTEST_TEST_SEED is derived from.
+    seed: u64,
+    check_determinism: bool,
+    resumed_run: Option<ResumedRun>,
+    max_run_time: Option<Duration>,
     target_runner: TargetRunner,
     runtime: Runtime,
     run_id: Uuid,
 }
 
 impl<'a> TestRunnerInner<'a> {
-    fn try_execute<E, F>(
+    /// Returns how the test finished before the run being resumed was interrupted, if it's being
+    /// run and it did.
+    fn previously_finished(
+        &self,
+        resumed_run: &ResumedRun,
+        test_instance: &TestInstance<'_>,
+    ) -> Option<FinishedTest> {
+        if !test_instance.test_info.filter_match.is_match() {
+            return None;
+        }
+        resumed_run.finished(&test_instance.bin_info.binary_id, test_instance.name)
+    }
+
+    /// Returns a tally to count passing and failing tests in, if any pass rates are required.
+    fn pass_rate_tally(&self) -> Option<PassRateTally<'a>> {
+        let group_rates: BTreeMap<_, _> = self
+            .profile
+            .test_groups()
+            .iter()
+            .filter_map(|(name, group)| Some((name.as_str(), group.required_pass_rate()?)))
+            .collect();
+        let required = self.profile.required_pass_rate();
+        if required.is_none() && group_rates.is_empty() {
+            return None;
+        }
+
+        let test_groups = self.pass_rate_groups(self.test_list).into_iter().collect();
+        Some(PassRateTally {
+            required,
+            group_rates,
+            test_groups,
+            counts: BTreeMap::new(),
+        })
+    }
+
+    /// Returns the tests in these lists in the order they're started in.
+    fn ordered_tests(
+        &self,
+        test_lists: &[&'a TestList<'a>],
+        shuffle_rng: &mut Option<StdRng>,
+    ) -> Vec<(TestInstance<'a>, &'a TestList<'a>)> {
+        let mut tests: Vec<_> = test_lists
+            .iter()
+            .flat_map(|&test_list| test_list.iter_tests().map(move |test| (test, test_list)))
+            .collect();
+        if let (Some(timings), true) = (&self.timings, self.slowest_first) {
+            // The sort is stable, so tests that took as long as each other stay in listing order.
+            tests
+                .sort_by_key(|(test, _)| Reverse(timings.get(&test.bin_info.binary_id, test.name)));
+        }
+        if let Some(rng) = shuffle_rng {
+            tests.shuffle(rng);
+        }
+        tests
+    }
+
+    /// Returns the test groups that tests in this list count towards the pass rates of.
+    fn pass_rate_groups(&self, test_list: &'a TestList<'a>) -> Vec<((&'a str, &'a str), &'a str)> {
+        let group_rates: BTreeSet<_> = self
+            .profile
+            .test_groups()
+            .iter()
+            .filter(|(_, group)| group.required_pass_rate().is_some())
+            .map(|(name, _)| name.as_str())
+            .collect();
+        if group_rates.is_empty() {
+            return vec![];
+        }
+        test_list
+            .iter_tests()
+            .filter_map(|test_instance| {
+                let overrides = self.profile.overrides_for(&test_query(&test_instance));
+                let name = group_rates.get(overrides.test_group()?)?;
+                Some((
+                    (
+                        test_instance.bin_info.binary_id.as_str(),
+                        test_instance.name,
+                    ),
+                    *name,
+                ))
+            })
+            .collect()
+    }
+
+    fn try_execute<'g, E, F, L>(
         &self,
         signal_handler: &mut SignalHandler,
+        listing: Option<(&'a TestListBatches<'g>, L)>,
         callback: F,
     ) -> Result<RunStats, E>
     where
+        'g: 'a,
         F: FnMut(TestEvent<'a>) -> Result<(), E> + Send,
+        L: FnOnce(&TestListSender<'a, 'g, E>) -> Result<(), E> + Send,
         E: Send,
     {
         // TODO: add support for other test-running approaches, measure performance.
@@ -190,136 +665,627 @@ impl<'a> TestRunnerInner<'a> {
         // This is move so that sender is moved into it. When the scope finishes the sender is
         // dropped, and the receiver below completes iteration.
 
-        let canceled = AtomicBool::new(false);
-        let canceled_ref = &canceled;
+        // This is shared with the code listing tests, if any, so that it can stop once the run is
+        // canceled.
+        let canceled = Arc::new(AtomicBool::new(false));
+        let canceled_ref = &*canceled;
+        let canceled_arc = &canceled;
 
         let mut ctx = CallbackContext::new(
             callback,
             self.run_id,
-            self.test_list.run_count(),
-            self.fail_fast,
+            self.test_list.run_count() * self.iterations.unwrap_or(1),
+            self.max_fail,
+            self.pass_rate_tally(),
         );
 
         // Send the initial event.
         // (Don't need to set the canceled atomic if this fails because the run hasn't started
         // yet.)
-        ctx.run_started(self.test_list)?;
+        ctx.run_started(
+            self.test_list,
+            self.iterations,
+            self.until_failure,
+            self.shuffle_seed,
+            self.seed,
+            self.resumed_run.as_ref().map(ResumedRun::finished_count),
+            listing.is_some(),
+        )?;
+
+        // Tests that finished before the run was interrupted are reported right away.
+        if let Some(resumed_run) = &self.resumed_run {
+            for test_instance in self.test_list.iter_tests() {
+                if let Some(finished) = self.previously_finished(resumed_run, &test_instance) {
+                    ctx.previously_finished(test_instance, finished)?;
+                }
+            }
+        }
 
         // Stores the first error that occurred. This error is propagated up.
         let mut first_error = None;
+        // Stores the first error that occurred while listing tests, until the run is canceled
+        // because of it.
+        let listing_error = Mutex::new(None);
+        let listing_error_ref = &listing_error;
 
         let ctx_mut = &mut ctx;
         let first_error_mut = &mut first_error;
 
         let _guard = self.runtime.enter();
 
+        // Tests are terminated once the run time budget is used up.
+        let run_deadline = self
+            .max_run_time
+            .map(|max_run_time| tokio::time::Instant::now() + max_run_time);
+
         // 4 is greater than the number of messages that will ever be sent over this channel.
         // Also, hold a receiver open so there are no spurious SendErrors on the sender.
         let (forward_sender, _forward_receiver) =
             tokio::sync::broadcast::channel::<SignalForwardEvent>(4);
         let forward_sender_ref = &forward_sender;
 
+        // Tests waiting for a slot in their test group or for resource tokens shouldn't take up a
+        // test thread, so if any test groups or resources are defined, every test is queued up
+        // front and test threads are handed out by a semaphore instead.
+        let thread_semaphore = Semaphore::new(self.test_threads);
+        let thread_semaphore_ref = &thread_semaphore;
+        let concurrency = if self.test_groups.is_empty() && self.resource_pools.is_empty() {
+            self.test_threads
+        } else if listing.is_some() {
+            // The number of tests isn't known yet.
+            usize::MAX
+        } else {
+            self.test_list.run_count().max(1)
+        };
+        // In adaptive mode, tests holding a test thread also wait for the number of tests running
+        // at a time to drop below the current limit.
+        let adaptive_limiter = self
+            .adaptive_threads
+            .map(|_| AdaptiveLimiter::new(self.test_threads));
+        let adaptive_limiter_ref = adaptive_limiter.as_ref();
+
+        // Failed tests with retries deferred to the end of the run, if retry-schedule is set to do
+        // that.
+        let deferred_retries = Mutex::new(Vec::new());
+        let deferred_retries_ref = &deferred_retries;
+        // Tests to run again on their own once every test has finished, along with the attempt
+        // number and iteration to run them with, if check-failures-in-isolation is set.
+        let failed_tests =
+            Mutex::new(Vec::<(TestInstance<'a>, &'a TestList<'a>, usize, usize)>::new());
+        let failed_tests_ref = &failed_tests;
+
         TokioScope::scope_and_block(move |scope| {
             let (run_sender, mut run_receiver) = tokio::sync::mpsc::unbounded_channel();
+            // Test lists are passed on to be run once they've been reported.
+            let (added_sender, added_receiver) = futures::channel::mpsc::unbounded();
+
+            if let Some((batches, list_tests)) = listing {
+                let (list_sender, list_receiver) = futures::channel::mpsc::unbounded();
+                let sender = TestListSender {
+                    sender: list_sender,
+                    canceled: canceled_arc.clone(),
+                };
+                let listing_sender = run_sender.clone();
+                let failed_sender = run_sender.clone();
+
+                // list_tests is allowed to block, for example while waiting for binaries to be
+                // built. The sender is dropped once it returns.
+                scope.spawn_cancellable(
+                    async move {
+                        if let Err(err) = tokio::task::block_in_place(move || list_tests(&sender)) {
+                            listing_failed(listing_error_ref, err, &failed_sender);
+                        }
+                    },
+                    || (),
+                );
+
+                let list_fut = async move {
+                    let mut test_lists = list_receiver.buffer_unordered(usize::MAX);
+                    while let Some(test_list) = test_lists.next().await {
+                        let test_list: &'a TestList<'a> = match test_list {
+                            Ok(test_list) => batches.push(test_list),
+                            Err(err) => {
+                                listing_failed(listing_error_ref, err, &listing_sender);
+                                continue;
+                            }
+                        };
+                        // Failure to send means the receiver was dropped.
+                        let _ = listing_sender.send(InternalTestEvent::TestsAdded {
+                            test_list,
+                            run_count: test_list.run_count() * self.iterations.unwrap_or(1),
+                            pass_rate_groups: self.pass_rate_groups(test_list),
+                        });
+                        // Tests that finished before the run was interrupted are reported right
+                        // away, as with the tests in the initial list.
+                        if let Some(resumed_run) = &self.resumed_run {
+                            for test_instance in test_list.iter_tests() {
+                                if let Some(finished) =
+                                    self.previously_finished(resumed_run, &test_instance)
+                                {
+                                    let _ = listing_sender.send(
+                                        InternalTestEvent::PreviouslyFinished {
+                                            test_instance,
+                                            finished,
+                                        },
+                                    );
+                                }
+                            }
+                        }
+                        // This is sent after the events above, so that the list is reported
+                        // before any of its tests are started.
+                        let _ = added_sender.unbounded_send(test_list);
+                    }
+                };
+                scope.spawn_cancellable(list_fut, || ());
+            } else {
+                // No lists are added, so tests only come from the initial list.
+                drop(added_sender);
+            }
 
             {
-                let run_fut = futures::stream::iter(self.test_list.iter_tests())
-                    .map(move |test_instance| {
-                        let this_run_sender = run_sender.clone();
-
-                        async move {
-                            // Subscribe to the receiver *before* checking canceled_ref. The ordering is
-                            // important to avoid race conditions with the code that first sets
-                            // canceled_ref and then sends the notification.
-                            let mut this_forward_receiver = forward_sender_ref.subscribe();
-
-                            if canceled_ref.load(Ordering::Acquire) {
-                                // Check for test cancellation.
+                let iteration_sender = run_sender.clone();
+                let isolation_sender = run_sender.clone();
+                // deferred is set for tests whose retries were deferred to the end of the run.
+                let run_one = move |test_instance: TestInstance<'a>,
+                                    test_list: &'a TestList<'a>,
+                                    iteration: usize,
+                                    deferred: Option<DeferredRetry>| {
+                    let this_run_sender = run_sender.clone();
+
+                    async move {
+                        // Subscribe to the receiver *before* checking canceled_ref. The ordering is
+                        // important to avoid race conditions with the code that first sets
+                        // canceled_ref and then sends the notification.
+                        let mut this_forward_receiver = forward_sender_ref.subscribe();
+
+                        // These tests were already reported, before any others were run.
+                        if let Some(resumed_run) = &self.resumed_run {
+                            if self
+                                .previously_finished(resumed_run, &test_instance)
+                                .is_some()
+                            {
                                 return;
                             }
+                        }
 
-                            let query = TestQuery {
-                                binary_query: BinaryQuery {
-                                    package_id: test_instance.bin_info.package.id(),
-                                    kind: test_instance.bin_info.kind.as_str(),
-                                    binary_name: &test_instance.bin_info.binary_name,
-                                    platform: convert_build_platform(
-                                        test_instance.bin_info.build_platform,
-                                    ),
-                                },
-                                test_name: test_instance.name,
-                            };
-                            let overrides = self.profile.overrides_for(&query);
-                            let total_attempts =
-                                match (self.ignore_retry_overrides, overrides.retries()) {
-                                    (true, _) | (false, None) => self.global_tries,
-                                    (false, Some(retries)) => retries + 1,
-                                };
+                        if let Some(retry) = &deferred {
+                            // Wait out the delay between retries before taking up a test
+                            // thread.
+                            tokio::select! {
+                                () = tokio::time::sleep_until(retry.ready_at) => {}
+                                _ = this_forward_receiver.recv() => {}
+                            }
+                        }
 
-                            if let FilterMatch::Mismatch { reason } =
-                                test_instance.test_info.filter_match
-                            {
+                        if canceled_ref.load(Ordering::Acquire) {
+                            // Check for test cancellation.
+                            match deferred {
+                                Some(retry) => {
+                                    finish_deferred_retry(&this_run_sender, test_instance, retry)
+                                }
+                                None if test_instance.test_info.filter_match.is_match() => {
+                                    let _ = this_run_sender.send(InternalTestEvent::NotStarted {
+                                        test_instance,
+                                        over_budget: false,
+                                    });
+                                }
+                                None => {}
+                            }
+                            return;
+                        }
+
+                        let overrides = self.profile.overrides_for(&test_query(&test_instance));
+                        let retry_policy = match (self.ignore_retry_overrides, overrides.retries())
+                        {
+                            (true, _) | (false, None) => self.retries,
+                            (false, Some(retries)) => retries,
+                        };
+                        // The number of tries = retries + 1.
+                        let total_attempts = retry_policy.count() + 1;
+                        let retry_if = overrides.retry_if().or(self.retry_if);
+                        let quarantined = overrides.quarantine().unwrap_or(false);
+
+                        if let FilterMatch::Mismatch { reason } =
+                            test_instance.test_info.filter_match
+                        {
+                            // Skipped tests are only reported once, however many iterations
+                            // there are.
+                            if iteration == 1 {
                                 // Failure to send means the receiver was dropped.
                                 let _ = this_run_sender.send(InternalTestEvent::Skipped {
                                     test_instance,
                                     reason,
                                 });
+                            }
+                            return;
+                        }
+
+                        let group_semaphore = overrides
+                            .test_group()
+                            .and_then(|name| self.test_groups.get(name));
+                        let _group_permit = match group_semaphore {
+                            Some(semaphore) => Some(
+                                semaphore
+                                    .acquire()
+                                    .await
+                                    .expect("semaphore is never closed"),
+                            ),
+                            None => None,
+                        };
+                        // Resources are acquired in the same order by every test, so tests
+                        // consuming several resources can't deadlock each other.
+                        let mut resource_tokens = vec![];
+                        for (name, &count) in overrides.resources().into_iter().flatten() {
+                            if let Some(pool) = self.resource_pools.get(name) {
+                                resource_tokens.push((name, pool.acquire(count).await));
+                            }
+                        }
+                        let resource_env: Vec<_> = resource_tokens
+                            .iter()
+                            .map(|(name, tokens)| {
+                                (format!("NEXTEST_RESOURCE_{name}"), tokens.to_string())
+                            })
+                            .collect();
+                        let threads_required = overrides
+                            .threads_required()
+                            .map_or(1, |threads| threads.compute(self.test_threads));
+                        let _thread_permit = thread_semaphore_ref
+                            .acquire_many(
+                                u32::try_from(threads_required)
+                                    .expect("thread count fits in a u32"),
+                            )
+                            .await
+                            .expect("semaphore is never closed");
+                        let _adaptive_permit = match adaptive_limiter_ref {
+                            Some(limiter) => Some(limiter.acquire(threads_required).await),
+                            None => None,
+                        };
+                        let _jobserver_token = match &self.jobserver {
+                            Some(jobserver) => Some(jobserver.acquire().await),
+                            None => None,
+                        };
+                        if canceled_ref.load(Ordering::Acquire) {
+                            // The run was canceled while this test was waiting to start.
+                            match deferred {
+                                Some(retry) => {
+                                    finish_deferred_retry(&this_run_sender, test_instance, retry)
+                                }
+                                None => {
+                                    let _ = this_run_sender.send(InternalTestEvent::NotStarted {
+                                        test_instance,
+                                        over_budget: false,
+                                    });
+                                }
+                            }
+                            return;
+                        }
+                        // Deferred retries are only stopped by the run time budget running
+                        // out, since the test has already been run.
+                        if let (Some(run_deadline), None) = (run_deadline, &deferred) {
+                            let expected = self.timings.as_ref().and_then(|timings| {
+                                timings.get(&test_instance.bin_info.binary_id, test_instance.name)
+                            });
+                            let now = tokio::time::Instant::now();
+                            if now >= run_deadline
+                                || expected.map_or(false, |expected| now + expected > run_deadline)
+                            {
+                                // The test wouldn't finish within the run time budget.
+                                let _ = this_run_sender.send(InternalTestEvent::NotStarted {
+                                    test_instance,
+                                    over_budget: true,
+                                });
                                 return;
                             }
+                        }
+                        let pinned_cpus = if overrides.pin_cpus().unwrap_or(self.pin_cpus) {
+                            self.cpu_pinner
+                                .get_or_init(CpuPinner::new)
+                                .pin(threads_required)
+                        } else {
+                            None
+                        };
+
+                        let test_seed = derive_test_seed(self.seed, &test_instance, iteration);
+                        // Retries are only deferred the first time a test fails.
+                        let may_defer =
+                            self.retry_schedule != RetrySchedule::Immediate && deferred.is_none();
+                        // retries_started is when the first attempt finished, if it failed.
+                        let (mut run_statuses, mut retries_started) = match deferred {
+                            Some(retry) => {
+                                // Failure to send means the receiver was dropped.
+                                let _ = this_run_sender.send(InternalTestEvent::Resumed);
+                                // Time spent waiting for the rest of the run doesn't count
+                                // towards the retry time.
+                                let now = tokio::time::Instant::now();
+                                let retries_started =
+                                    now.checked_sub(retry.retry_time).unwrap_or(now);
+                                (retry.run_statuses, Some(retries_started))
+                            }
+                            None => {
+                                // Failure to send means the receiver was dropped.
+                                let _ = this_run_sender
+                                    .send(InternalTestEvent::Started { test_instance });
+                                (vec![], None)
+                            }
+                        };
 
-                            // Failure to send means the receiver was dropped.
-                            let _ =
-                                this_run_sender.send(InternalTestEvent::Started { test_instance });
+                        loop {
+                            let attempt = run_statuses.len() + 1;
 
-                            let mut run_statuses = vec![];
+                            let run_status = self
+                                .run_test(
+                                    test_instance,
+                                    test_list,
+                                    attempt,
+                                    test_seed,
+                                    &overrides,
+                                    &resource_env,
+                                    pinned_cpus.as_ref(),
+                                    run_deadline,
+                                    &this_run_sender,
+                                    &mut this_forward_receiver,
+                                )
+                                .await
+                                .into_external(attempt, total_attempts, iteration, self.iterations);
+
+                            if run_status.result.is_success() {
+                                // The test succeeded.
+                                run_statuses.push(run_status);
+                                break;
+                            }
 
-                            loop {
-                                let attempt = run_statuses.len() + 1;
+                            let retries_started =
+                                *retries_started.get_or_insert_with(tokio::time::Instant::now);
+                            // Failures that don't match retry-if are reported right away.
+                            let delay = match attempt < total_attempts
+                                && !canceled_ref.load(Ordering::Acquire)
+                                && retry_if.map_or(true, |cond| cond.matches(&run_status))
+                            {
+                                true => retry_delay(&retry_policy, attempt, retries_started),
+                                false => None,
+                            };
+                            if let (Some(delay), true) = (delay, may_defer) {
+                                // Retry this test once every test has been run once, giving up
+                                // its test thread until then.
+                                let _ = this_run_sender.send(InternalTestEvent::Retry {
+                                    test_instance,
+                                    run_status: Box::new(run_status.clone()),
+                                    delay,
+                                    deferred: true,
+                                });
+                                run_statuses.push(run_status);
+                                let retry = DeferredRetry {
+                                    run_statuses,
+                                    iteration,
+                                    quarantined,
+                                    retry_time: retries_started.elapsed() + delay,
+                                    ready_at: tokio::time::Instant::now() + delay,
+                                };
+                                deferred_retries_ref
+                                    .lock()
+                                    .expect("deferred retries lock isn't poisoned")
+                                    .push((test_instance, test_list, retry));
+                                return;
+                            } else if let Some(delay) = delay {
+                                // Retry this test: send a retry event, wait out the delay,
+                                // then retry the loop.
+                                let _ = this_run_sender.send(InternalTestEvent::Retry {
+                                    test_instance,
+                                    run_status: Box::new(run_status.clone()),
+                                    delay,
+                                    deferred: false,
+                                });
+                                run_statuses.push(run_status);
+                                if !delay.is_zero() {
+                                    tokio::select! {
+                                        () = tokio::time::sleep(delay) => {}
+                                        _ = this_forward_receiver.recv() => {
+                                            // The run was canceled by a signal, so give up on
+                                            // this test.
+                                            break;
+                                        }
+                                    }
+                                    if canceled_ref.load(Ordering::Acquire) {
+                                        break;
+                                    }
+                                }
+                            } else {
+                                // This test failed and is out of retries.
+                                run_statuses.push(run_status);
+                                break;
+                            }
+                        }
 
-                                let run_status = self
+                        // At this point, either:
+                        // * the test has succeeded, or
+                        // * the test has failed and we've run out of retries.
+                        // In either case, the test is finished.
+                        let failed = run_statuses
+                            .last()
+                            .map_or(false, |run_status| !run_status.result.is_success());
+                        if let (Some(first), true) = (run_statuses.last(), self.check_determinism) {
+                            // Tests that passed are run again right away, keeping their test
+                            // thread and resources.
+                            if !failed && !canceled_ref.load(Ordering::Acquire) {
+                                let rerun = self
                                     .run_test(
                                         test_instance,
-                                        attempt,
+                                        test_list,
+                                        first.attempt,
+                                        test_seed,
                                         &overrides,
+                                        &resource_env,
+                                        pinned_cpus.as_ref(),
+                                        run_deadline,
                                         &this_run_sender,
                                         &mut this_forward_receiver,
                                     )
                                     .await
-                                    .into_external(attempt, total_attempts);
-
-                                if run_status.result.is_success() {
-                                    // The test succeeded.
-                                    run_statuses.push(run_status);
-                                    break;
-                                } else if attempt < total_attempts
-                                    && !canceled_ref.load(Ordering::Acquire)
-                                {
-                                    // Retry this test: send a retry event, then retry the loop.
-                                    let _ = this_run_sender.send(InternalTestEvent::Retry {
+                                    .into_external(
+                                        first.attempt,
+                                        total_attempts,
+                                        iteration,
+                                        self.iterations,
+                                    );
+                                let difference =
+                                    compare_runs(first, &rerun, self.profile.determinism_ignore());
+                                let _ =
+                                    this_run_sender.send(InternalTestEvent::DeterminismChecked {
                                         test_instance,
-                                        run_status: run_status.clone(),
+                                        run_status: Box::new(rerun),
+                                        difference,
                                     });
-                                    run_statuses.push(run_status);
-                                } else {
-                                    // This test failed and is out of retries.
-                                    run_statuses.push(run_status);
-                                    break;
-                                }
                             }
-
-                            // At this point, either:
-                            // * the test has succeeded, or
-                            // * the test has failed and we've run out of retries.
-                            // In either case, the test is finished.
-                            let _ = this_run_sender.send(InternalTestEvent::Finished {
-                                test_instance,
-                                run_statuses: ExecutionStatuses::new(run_statuses),
-                            });
                         }
-                    })
-                    // buffer_unordered means tests are spawned in order but returned in any order.
-                    .buffer_unordered(self.test_threads)
-                    .collect();
+                        if failed && self.check_failures_in_isolation {
+                            let mut failed_tests = failed_tests_ref
+                                .lock()
+                                .expect("failed tests lock isn't poisoned");
+                            // Tests that fail in several iterations are only checked once.
+                            if !failed_tests
+                                .iter()
+                                .any(|(failed, ..)| failed.sort_key() == test_instance.sort_key())
+                            {
+                                failed_tests.push((
+                                    test_instance,
+                                    test_list,
+                                    run_statuses.len() + 1,
+                                    iteration,
+                                ));
+                            }
+                        }
+                        let _ = this_run_sender.send(InternalTestEvent::Finished {
+                            test_instance,
+                            run_statuses: ExecutionStatuses::new(run_statuses),
+                            quarantined,
+                        });
+                    }
+                };
+                let retry_concurrency = match self.retry_schedule {
+                    RetrySchedule::DeferredSerial => 1,
+                    RetrySchedule::Immediate | RetrySchedule::Deferred => concurrency,
+                };
+                // The futures running each phase are boxed, since otherwise the compiler can't tell
+                // that this future is Send.
+                let iterations = self.iterations;
+                let mut shuffle_rng = self.shuffle_seed.map(StdRng::seed_from_u64);
+                let run_fut = async move {
+                    let run_one = &run_one;
+                    let run_deferred_retries = move || {
+                        let deferred_retries = std::mem::take(
+                            &mut *deferred_retries_ref
+                                .lock()
+                                .expect("deferred retries lock isn't poisoned"),
+                        );
+                        futures::stream::iter(deferred_retries)
+                            .map(move |(test_instance, test_list, retry)| {
+                                run_one(test_instance, test_list, retry.iteration, Some(retry))
+                            })
+                            .buffer_unordered(retry_concurrency)
+                            .collect::<()>()
+                            .boxed()
+                    };
+                    // Lists added while the first iteration is running are run in later iterations
+                    // too.
+                    let mut test_lists = vec![self.test_list];
+                    let mut added_receiver = Some(added_receiver);
+                    // Each iteration finishes before the next one starts, so iterations of a test
+                    // are never run at the same time.
+                    for iteration in 1.. {
+                        if iterations.map_or(false, |iterations| iteration > iterations) {
+                            break;
+                        }
+                        if iteration > 1 {
+                            // No more iterations are started once the run is canceled.
+                            if canceled_ref.load(Ordering::Acquire) {
+                                break;
+                            }
+                            let run_count = test_lists
+                                .iter()
+                                .map(|test_list| test_list.run_count())
+                                .sum();
+                            // Running until failure with no tests to run would never finish.
+                            if iterations.is_none() && run_count == 0 {
+                                break;
+                            }
+                            if iterations.is_none() {
+                                // Failure to send means the receiver was dropped.
+                                let _ = iteration_sender
+                                    .send(InternalTestEvent::IterationStarted { run_count });
+                            }
+                        }
+                        // Each iteration is started in a different order.
+                        let tests = self.ordered_tests(&test_lists, &mut shuffle_rng);
+                        // Lists added during the first iteration are started in the order they're
+                        // added in, with tests ordered within each list.
+                        let added_tests = futures::stream::iter(added_receiver.take())
+                            .flatten()
+                            .map(|test_list| {
+                                test_lists.push(test_list);
+                                let tests = self.ordered_tests(&[test_list], &mut shuffle_rng);
+                                futures::stream::iter(tests)
+                            })
+                            .flatten();
+                        futures::stream::iter(tests)
+                            .chain(added_tests)
+                            .map(|(test_instance, test_list)| {
+                                run_one(test_instance, test_list, iteration, None)
+                            })
+                            // buffer_unordered means tests are spawned in order but returned in
+                            // any order.
+                            .buffer_unordered(concurrency)
+                            .collect::<()>()
+                            .boxed()
+                            .await;
+                        if iterations.is_none() {
+                            // When running until failure, a failure that's retried later must be
+                            // known about before the next iteration, so that the run stops.
+                            run_deferred_retries().await;
+                            // Wait for the events of this iteration to be handled, so that a
+                            // failure in it cancels the run before the next iteration starts.
+                            let (done_sender, done_receiver) = oneshot::channel();
+                            // Failure to send means the receiver was dropped.
+                            let _ = iteration_sender
+                                .send(InternalTestEvent::IterationFinished { done_sender });
+                            let _ = done_receiver.await;
+                        }
+                    }
+                    // Every test has been run once, so deferred retries can be run now.
+                    run_deferred_retries().await;
+                    // Every test has finished, so tests that failed can be run on their own now.
+                    let failed_tests = std::mem::take(
+                        &mut *failed_tests_ref
+                            .lock()
+                            .expect("failed tests lock isn't poisoned"),
+                    );
+                    for (test_instance, test_list, attempt, iteration) in failed_tests {
+                        // Subscribe to the receiver *before* checking canceled_ref, as above.
+                        let mut forward_receiver = forward_sender_ref.subscribe();
+                        if canceled_ref.load(Ordering::Acquire) {
+                            break;
+                        }
+                        self.check_in_isolation(
+                            test_instance,
+                            test_list,
+                            attempt,
+                            iteration,
+                            run_deadline,
+                            &isolation_sender,
+                            &mut forward_receiver,
+                        )
+                        .await;
+                    }
+                };
+                let run_fut = match (self.adaptive_threads, adaptive_limiter_ref) {
+                    (Some(adaptive_threads), Some(limiter)) => {
+                        let adapt_fut = adapt_threads(limiter, adaptive_threads, self.test_threads);
+                        // adapt_fut never finishes, so this finishes once all tests are done.
+                        future::select(Box::pin(run_fut), Box::pin(adapt_fut))
+                            .map(|_| ())
+                            .left_future()
+                    }
+                    _ => run_fut.right_future(),
+                };
 
                 // Run the stream to completion.
                 scope.spawn_cancellable(run_fut, || ());
@@ -327,6 +1293,11 @@ impl<'a> TestRunnerInner<'a> {
 
             let exec_fut = async move {
                 let mut signals_done = false;
+                let mut deadline_done = false;
+                let run_deadline_sleep = tokio::time::sleep_until(
+                    run_deadline.unwrap_or_else(tokio::time::Instant::now),
+                );
+                tokio::pin!(run_deadline_sleep);
 
                 loop {
                     let internal_event = tokio::select! {
@@ -348,6 +1319,10 @@ impl<'a> TestRunnerInner<'a> {
                                 }
                             }
                         },
+                        () = &mut run_deadline_sleep, if run_deadline.is_some() && !deadline_done => {
+                            deadline_done = true;
+                            InternalEvent::RunTimeExceeded
+                        },
                     };
 
                     match ctx_mut.handle_event(internal_event) {
@@ -375,6 +1350,24 @@ impl<'a> TestRunnerInner<'a> {
                                         *first_error_mut = err;
                                     }
                                 }
+                                InternalError::ListingCanceled(err) => {
+                                    // Listing tests failed, so the run is canceled with the error
+                                    // that caused that.
+                                    if first_error_mut.is_none() {
+                                        *first_error_mut = listing_error_ref
+                                            .lock()
+                                            .expect("listing error lock isn't poisoned")
+                                            .take()
+                                            .or(err);
+                                    }
+                                }
+                                InternalError::RunTimeCanceled(err) => {
+                                    // The run time budget has been used up. Tests still running
+                                    // terminate themselves at the deadline.
+                                    if first_error_mut.is_none() {
+                                        *first_error_mut = err;
+                                    }
+                                }
                                 InternalError::SignalCanceled(forward_event, err) => {
                                     // A signal has caused cancellation to begin.
                                     if first_error_mut.is_none() {
@@ -418,11 +1411,17 @@ impl<'a> TestRunnerInner<'a> {
     // ---
 
     /// Run an individual test in its own process.
+    #[allow(clippy::too_many_arguments)]
     async fn run_test(
         &self,
         test: TestInstance<'a>,
+        test_list: &'a TestList<'a>,
         attempt: usize,
+        test_seed: u64,
         overrides: &ProfileOverrides,
+        resource_env: &[(String, String)],
+        pinned_cpus: Option<&PinnedCpus<'_>>,
+        run_deadline: Option<tokio::time::Instant>,
         run_sender: &UnboundedSender<InternalTestEvent<'a>>,
         forward_receiver: &mut tokio::sync::broadcast::Receiver<SignalForwardEvent>,
     ) -> InternalExecuteStatus {
@@ -431,46 +1430,200 @@ impl<'a> TestRunnerInner<'a> {
         match self
             .run_test_inner(
                 test,
+                test_list,
                 attempt,
+                test_seed,
                 &stopwatch,
                 overrides,
+                resource_env,
+                pinned_cpus,
+                run_deadline,
                 run_sender,
                 forward_receiver,
             )
             .await
         {
             Ok(run_status) => run_status,
-            Err(_) => InternalExecuteStatus {
+            Err(err) => InternalExecuteStatus {
                 // TODO: can we return more information in stdout/stderr? investigate this
                 stdout: Bytes::new(),
-                stderr: Bytes::new(),
+                stderr: Bytes::from(format!("error running test: {err}\n")),
+                output_chunks: vec![],
                 result: ExecutionResult::ExecFail,
+                exit_code: None,
+                leaked_processes: vec![],
+                kept_tmpdir: None,
+                test_seed,
                 stopwatch_end: stopwatch.end(),
                 is_slow: false,
             },
         }
     }
 
+    /// Runs a test that failed again once no other tests are running, and reports whether it still
+    /// fails.
+    #[allow(clippy::too_many_arguments)]
+    async fn check_in_isolation(
+        &self,
+        test_instance: TestInstance<'a>,
+        test_list: &'a TestList<'a>,
+        attempt: usize,
+        iteration: usize,
+        run_deadline: Option<tokio::time::Instant>,
+        run_sender: &UnboundedSender<InternalTestEvent<'a>>,
+        forward_receiver: &mut tokio::sync::broadcast::Receiver<SignalForwardEvent>,
+    ) {
+        let overrides = self.profile.overrides_for(&test_query(&test_instance));
+        // No other tests are running, so these are available right away.
+        let mut resource_tokens = vec![];
+        for (name, &count) in overrides.resources().into_iter().flatten() {
+            if let Some(pool) = self.resource_pools.get(name) {
+                resource_tokens.push((name, pool.acquire(count).await));
+            }
+        }
+        let resource_env: Vec<_> = resource_tokens
+            .iter()
+            .map(|(name, tokens)| (format!("NEXTEST_RESOURCE_{name}"), tokens.to_string()))
+            .collect();
+        let _jobserver_token = match &self.jobserver {
+            Some(jobserver) => Some(jobserver.acquire().await),
+            None => None,
+        };
+
+        let run_status = self
+            .run_test(
+                test_instance,
+                test_list,
+                attempt,
+                derive_test_seed(self.seed, &test_instance, iteration),
+                &overrides,
+                &resource_env,
+                None,
+                run_deadline,
+                run_sender,
+                forward_receiver,
+            )
+            .await
+            .into_external(attempt, attempt, iteration, self.iterations);
+        // Failure to send means the receiver was dropped.
+        let _ = run_sender.send(InternalTestEvent::IsolationChecked {
+            test_instance,
+            run_status: Box::new(run_status),
+        });
+    }
+
+    /// Returns a buffer for the output an attempt of `test` writes to `stream`.
+    fn captured_output(&self, test: &TestInstance<'a>, stream: OutputStream) -> CapturedOutput {
+        match &self.output_limits {
+            Some(output_limits) => output_limits.buffer(test.name, stream),
+            None => CapturedOutput::unlimited(stream),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     async fn run_test_inner(
         &self,
         test: TestInstance<'a>,
+        test_list: &'a TestList<'a>,
         attempt: usize,
+        test_seed: u64,
         stopwatch: &StopwatchStart,
         overrides: &ProfileOverrides,
+        resource_env: &[(String, String)],
+        pinned_cpus: Option<&PinnedCpus<'_>>,
+        run_deadline: Option<tokio::time::Instant>,
         run_sender: &UnboundedSender<InternalTestEvent<'a>>,
         forward_receiver: &mut tokio::sync::broadcast::Receiver<SignalForwardEvent>,
     ) -> std::io::Result<InternalExecuteStatus> {
-        let mut cmd = test.make_expression(self.test_list, &self.target_runner);
+        let live_output = self.live_output
+            || overrides.live_output().unwrap_or(false)
+            || self
+                .live_output_filter
+                .iter()
+                .any(|expr| expr.matches_test(&test_query(&test)));
+        let mut cmd = test.make_expression(test_list, &self.target_runner);
+        if let Some(env_cleaner) = &self.env_cleaner {
+            env_cleaner.apply(&mut cmd);
+        }
+        if let Some(working_directory) = overrides.working_directory() {
+            cmd.current_dir(working_directory);
+        }
+        if self.bench {
+            cmd.arg("--bench");
+            cmd.env(
+                CRITERION_HOME_ENV,
+                criterion_home(&test_list.rust_build_meta().target_directory),
+            );
+        }
 
         // Debug environment variable for testing.
         cmd.env("__NEXTEST_ATTEMPT", format!("{}", attempt));
         cmd.env("NEXTEST_RUN_ID", format!("{}", self.run_id));
-        cmd.stdin(Stdio::null());
+        cmd.env("NEXTEST_TEST_SEED", test_seed.to_string());
+        cmd.envs(resource_env.iter().map(|(key, value)| (key, value)));
+        if let Some(jobserver) = &self.jobserver {
+            // This passes the jobserver on to tools run by the test.
+            jobserver.client.configure(&mut cmd);
+        }
+        // Set if the test is given a string as standard input, which is written once it's spawned.
+        let stdin_contents = match overrides.stdin().unwrap_or(&self.stdin) {
+            StdinMode::Null => {
+                cmd.stdin(Stdio::null());
+                None
+            }
+            StdinMode::Inherit => {
+                cmd.stdin(Stdio::inherit());
+                None
+            }
+            StdinMode::File(path) => {
+                let file = std::fs::File::open(path).map_err(|err| {
+                    io::Error::new(
+                        err.kind(),
+                        format!("failed to open stdin file `{path}`: {err}"),
+                    )
+                })?;
+                cmd.stdin(file);
+                None
+            }
+            StdinMode::String(contents) => {
+                cmd.stdin(Stdio::piped());
+                Some(contents.clone())
+            }
+        };
+        let tmpdir = match &self.test_tmpdirs {
+            Some(tmpdirs) => Some(tmpdirs.create(&mut cmd, test.name)?),
+            None => None,
+        };
         imp::cmd_pre_exec(&mut cmd);
+        if let Some(nice) = overrides.nice().or(self.nice) {
+            imp::set_priority(&mut cmd, nice);
+        }
+        let network_isolation = overrides
+            .network_isolation()
+            .unwrap_or(self.network_isolation);
+        if network_isolation && !isolate_network(&mut cmd) {
+            self.network_isolation_warning.call_once(|| {
+                log::warn!(
+                    target: "nextest-runner",
+                    "network isolation is only supported on Linux, running tests with network access",
+                );
+            });
+        }
+
+        let memory_limit = overrides.memory_limit().or(self.memory_limit);
+        let test_memory_limit = memory_limit.map(|limit| {
+            self.memory_limiter
+                .get_or_init(|| MemoryLimiter::new(format!("nextest-{}", self.run_id)))
+                .apply(&mut cmd, limit)
+        });
+        if let Some(pinned_cpus) = pinned_cpus {
+            cmd.env("NEXTEST_PINNED_CPUS", pinned_cpus.to_string());
+            pinned_cpus.apply(&mut cmd);
+        }
 
         // If creating a job fails, we might be on an old system. Ignore this -- job objects are a
         // best-effort thing.
-        let job = imp::Job::create().ok();
+        let job = imp::create_job(memory_limit, pinned_cpus).ok();
 
         if !self.no_capture {
             // Capture stdout and stderr.
@@ -479,15 +1632,49 @@ impl<'a> TestRunnerInner<'a> {
         };
 
         let mut cmd = tokio::process::Command::from(cmd);
-        let mut child = cmd.spawn()?;
-
-        // If assigning the child to the job fails, ignore this. This can happen if the process has
+        let mut child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(err) => {
+                if network_isolation && is_isolation_error(&err) {
+                    // This is likely to affect every test run with network isolation, so point at
+                    // the setting once rather than leaving each test's error to speak for itself.
+                    self.network_isolation_warning.call_once(|| {
+                        log::warn!(
+                            target: "nextest-runner",
+                            "failed to start test in a network namespace ({err}); if user \
+                             namespaces are disabled on this system, set `network-isolation = \
+                             false` to run tests with network access",
+                        );
+                    });
+                }
+                return Err(err);
+            }
+        };
+        // The ID is no longer available once the child has been waited on.
+        let pid = child.id();
+        if let (Some(contents), Some(mut child_stdin)) = (stdin_contents, child.stdin.take()) {
+            // Write the contents in the background, since the test might not read all of them.
+            tokio::spawn(async move {
+                // This fails if the test exits without reading everything, which is fine.
+                let _ = child_stdin.write_all(contents.as_bytes()).await;
+            });
+        }
+
+        // If assigning the child to the job fails, ignore this. This can happen if the process has
         // exited.
         let _ = imp::assign_process_to_job(&child, job.as_ref());
 
         let mut status: Option<ExecutionResult> = None;
         let slow_timeout = overrides.slow_timeout().unwrap_or(self.slow_timeout);
         let leak_timeout = overrides.leak_timeout().unwrap_or(self.leak_timeout);
+        let grace_period = overrides
+            .terminate_grace_period()
+            .unwrap_or(self.terminate_grace_period);
+        let terminate_signals = overrides.terminate_signals().or(self.terminate_signals);
+        let no_output_timeout = match self.no_capture {
+            true => None,
+            false => overrides.no_output_timeout().or(self.no_output_timeout),
+        };
         let mut is_slow = false;
 
         let mut interval = tokio::time::interval(slow_timeout.period);
@@ -498,19 +1685,45 @@ impl<'a> TestRunnerInner<'a> {
 
         let child_stdout = child.stdout.take().map(BufReader::new);
         let child_stderr = child.stderr.take().map(BufReader::new);
-        let mut stdout = bytes::BytesMut::with_capacity(4096);
-        let mut stderr = bytes::BytesMut::with_capacity(4096);
+        let mut stdout = self.captured_output(&test, OutputStream::Stdout);
+        let mut stderr = self.captured_output(&test, OutputStream::Stderr);
+        // Numbers chunks of output in the order they're read in, across both streams.
+        let chunk_seq = AtomicUsize::new(0);
+        // When the test last wrote to stdout or stderr.
+        let last_output = Mutex::new(tokio::time::Instant::now());
+        // The state of the test's processes when it was considered hung, if they could be described.
+        let mut hang_description: Option<String> = None;
+        // Set if the test was terminated because the run time budget was used up.
+        let mut over_budget = false;
 
         let (res, leaked) = {
             // Set up futures for reading from stdout and stderr.
             let stdout_fut = async {
                 if let Some(mut child_stdout) = child_stdout {
+                    let mut chunk = BytesMut::with_capacity(4096);
+                    // Output that hasn't been reported live yet.
+                    let mut unreported = BytesMut::new();
                     loop {
-                        stdout.reserve(4096);
-                        let bytes_read = child_stdout.read_buf(&mut stdout).await?;
+                        chunk.clear();
+                        let bytes_read = child_stdout.read_buf(&mut chunk).await?;
+                        let seq = chunk_seq.fetch_add(1, Ordering::Relaxed);
+                        stdout.push(&chunk, seq, stopwatch.elapsed()).await;
+                        if live_output {
+                            unreported.extend_from_slice(&chunk);
+                            report_output_lines(
+                                test,
+                                OutputStream::Stdout,
+                                &mut unreported,
+                                stopwatch.elapsed(),
+                                bytes_read == 0,
+                                run_sender,
+                            );
+                        }
                         if bytes_read == 0 {
                             break;
                         }
+                        *last_output.lock().expect("lock isn't poisoned") =
+                            tokio::time::Instant::now();
                     }
                 }
                 Ok::<_, std::io::Error>(())
@@ -520,12 +1733,30 @@ impl<'a> TestRunnerInner<'a> {
 
             let stderr_fut = async {
                 if let Some(mut child_stderr) = child_stderr {
+                    let mut chunk = BytesMut::with_capacity(4096);
+                    // Output that hasn't been reported live yet.
+                    let mut unreported = BytesMut::new();
                     loop {
-                        stderr.reserve(4096);
-                        let bytes_read = child_stderr.read_buf(&mut stderr).await?;
+                        chunk.clear();
+                        let bytes_read = child_stderr.read_buf(&mut chunk).await?;
+                        let seq = chunk_seq.fetch_add(1, Ordering::Relaxed);
+                        stderr.push(&chunk, seq, stopwatch.elapsed()).await;
+                        if live_output {
+                            unreported.extend_from_slice(&chunk);
+                            report_output_lines(
+                                test,
+                                OutputStream::Stderr,
+                                &mut unreported,
+                                stopwatch.elapsed(),
+                                bytes_read == 0,
+                                run_sender,
+                            );
+                        }
                         if bytes_read == 0 {
                             break;
                         }
+                        *last_output.lock().expect("lock isn't poisoned") =
+                            tokio::time::Instant::now();
                     }
                 }
                 Ok::<_, std::io::Error>(())
@@ -534,6 +1765,14 @@ impl<'a> TestRunnerInner<'a> {
             let mut stderr_done = false;
 
             let res = loop {
+                // If the test writes something out before this fires, the sleep is just restarted.
+                let last_output_at = *last_output.lock().expect("lock isn't poisoned");
+                let no_output_sleep = tokio::time::sleep_until(
+                    last_output_at + no_output_timeout.unwrap_or_default(),
+                );
+                let run_deadline_sleep = tokio::time::sleep_until(
+                    run_deadline.unwrap_or_else(tokio::time::Instant::now),
+                );
                 tokio::select! {
                     res = &mut stdout_fut, if !stdout_done => {
                         stdout_done = true;
@@ -566,19 +1805,34 @@ impl<'a> TestRunnerInner<'a> {
                                 // attempt to terminate the slow test.
                                 // as there is a race between shutting down a slow test and its own completion
                                 // we silently ignore errors to avoid printing false warnings.
-                                imp::terminate_child(&mut child, TerminateMode::Timeout, forward_receiver, job.as_ref()).await;
+                                imp::terminate_child(&mut child, TerminateMode::Timeout, grace_period, terminate_signals, forward_receiver, job.as_ref()).await;
                                 status = Some(ExecutionResult::Timeout);
                                 // Don't break here to give the wait task a chance to finish.
                             }
                         }
                     }
+                    () = no_output_sleep, if no_output_timeout.is_some() && status.is_none() => {
+                        let no_output_timeout = no_output_timeout.expect("checked above");
+                        let last_output_at = *last_output.lock().expect("lock isn't poisoned");
+                        if last_output_at.elapsed() >= no_output_timeout {
+                            // Describe the test's processes before they're terminated.
+                            hang_description = imp::describe_hung(pid);
+                            imp::terminate_child(&mut child, TerminateMode::Timeout, grace_period, terminate_signals, forward_receiver, job.as_ref()).await;
+                            status = Some(ExecutionResult::Hang);
+                        }
+                    }
+                    () = run_deadline_sleep, if run_deadline.is_some() && status.is_none() => {
+                        imp::terminate_child(&mut child, TerminateMode::Timeout, grace_period, terminate_signals, forward_receiver, job.as_ref()).await;
+                        status = Some(ExecutionResult::Timeout);
+                        over_budget = true;
+                    }
                     recv = forward_receiver.recv() => {
                         // The sender stays open longer than the whole loop, and the buffer is big
                         // enough for all messages ever sent through this channel, so a RecvError
                         // should never happen.
                         let forward_event = recv.expect("a RecvError should never happen here");
 
-                        imp::terminate_child(&mut child, TerminateMode::Signal(forward_event), forward_receiver, job.as_ref()).await;
+                        imp::terminate_child(&mut child, TerminateMode::Signal(forward_event), grace_period, terminate_signals, forward_receiver, job.as_ref()).await;
                     }
                 };
             };
@@ -615,9 +1869,60 @@ impl<'a> TestRunnerInner<'a> {
         let output = res?;
         let exit_status = output;
 
+        if let (Some(ExecutionResult::Hang), Some(no_output_timeout)) = (status, no_output_timeout)
+        {
+            let mut message = format!(
+                "\n--- nextest: no output for {:?}, terminated as hung ---\n",
+                no_output_timeout
+            );
+            if let Some(description) = hang_description {
+                message.push_str("processes before termination:\n");
+                message.push_str(&description);
+            }
+            stderr
+                .push(
+                    message.as_bytes(),
+                    chunk_seq.fetch_add(1, Ordering::Relaxed),
+                    stopwatch.elapsed(),
+                )
+                .await;
+        }
+        if over_budget {
+            stderr
+                .push(
+                    b"\n--- nextest: run time budget used up, terminated ---\n",
+                    chunk_seq.fetch_add(1, Ordering::Relaxed),
+                    stopwatch.elapsed(),
+                )
+                .await;
+        }
+        let stdout = stdout.finish().await;
+        let stderr = stderr.finish().await;
+        let output_chunks = interleave(&stdout, &stderr);
+
+        let leaked_processes = match self.leaked_processes {
+            LeakedProcessPolicy::Ignore => vec![],
+            LeakedProcessPolicy::Warn | LeakedProcessPolicy::Kill | LeakedProcessPolicy::Fail => {
+                imp::leaked_processes(pid, job.as_ref())
+            }
+        };
+        if self.leaked_processes == LeakedProcessPolicy::Kill && !leaked_processes.is_empty() {
+            imp::kill_leaked_processes(pid, job.as_ref());
+        }
+        let fail_leaked =
+            self.leaked_processes == LeakedProcessPolicy::Fail && !leaked_processes.is_empty();
+
+        // Doctests marked should_panic are expected to exit with a failure.
+        let should_panic = test
+            .bin_info
+            .doctest
+            .as_ref()
+            .map_or(false, |doctest| doctest.should_panic);
         let status = status.unwrap_or_else(|| {
-            if exit_status.success() {
-                if leaked {
+            if exit_status.success() != should_panic {
+                if fail_leaked {
+                    ExecutionResult::LeakFail
+                } else if leaked {
                     ExecutionResult::Leak
                 } else {
                     ExecutionResult::Pass
@@ -637,18 +1942,40 @@ impl<'a> TestRunnerInner<'a> {
                         let abort_status = None;
                     }
                 }
-                ExecutionResult::Fail {
-                    abort_status,
-                    leaked,
+                match &test_memory_limit {
+                    Some(test_memory_limit) if test_memory_limit.exceeded(&stderr.output) => {
+                        ExecutionResult::MemoryLimitExceeded
+                    }
+                    _ => ExecutionResult::Fail {
+                        abort_status,
+                        leaked,
+                    },
                 }
             }
         });
+        let status = match overrides.expected_failure().unwrap_or(false) {
+            true => status.to_expected_failure(self.strict_xfail),
+            false => status,
+        };
+
+        // The directory is removed when it's dropped, unless it's kept here.
+        let kept_tmpdir = match tmpdir {
+            Some(tmpdir) if self.keep_tmpdirs_on_failure && !status.is_success() => {
+                Some(tmpdir.keep())
+            }
+            _ => None,
+        };
 
         Ok(InternalExecuteStatus {
             // TODO: replace with Bytes
-            stdout: stdout.freeze(),
-            stderr: stderr.freeze(),
+            stdout: stdout.output.freeze(),
+            stderr: stderr.output.freeze(),
+            output_chunks,
             result: status,
+            exit_code: exit_status.code(),
+            leaked_processes,
+            kept_tmpdir,
+            test_seed,
             stopwatch_end: stopwatch.end(),
             is_slow,
         })
@@ -664,7 +1991,7 @@ pub struct ExecutionStatuses {
 
 #[allow(clippy::len_without_is_empty)] // RunStatuses is never empty
 impl ExecutionStatuses {
-    fn new(statuses: Vec<ExecuteStatus>) -> Self {
+    pub(crate) fn new(statuses: Vec<ExecuteStatus>) -> Self {
         Self { statuses }
     }
 
@@ -806,12 +2133,31 @@ pub struct ExecuteStatus {
     pub attempt: usize,
     /// The total number of times this test can be run. Equal to `1 + retries`.
     pub total_attempts: usize,
+    /// The iteration of the test this is an attempt of. In the range `[1, total_iterations]`.
+    pub iteration: usize,
+    /// The total number of times this test is run, not counting retries. Set with `--iterations`.
+    ///
+    /// This is `None` if tests are run until one fails, with no limit on the number of iterations.
+    pub total_iterations: Option<usize>,
     /// Standard output for this test.
     pub stdout: Bytes,
     /// Standard error for this test.
     pub stderr: Bytes,
+    /// The parts of `stdout` and `stderr`, in the order the test wrote them in.
+    ///
+    /// The order is approximate: output is read from each stream in chunks, and chunks read from
+    /// different streams at around the same time may be out of order.
+    pub output_chunks: Vec<OutputChunk>,
     /// The result of execution this test: pass, fail or execution error.
     pub result: ExecutionResult,
+    /// The exit code of the test process, if it exited normally.
+    pub exit_code: Option<i32>,
+    /// Processes this test left running after it exited, if nextest looked for them.
+    pub leaked_processes: Vec<LeakedProcess>,
+    /// The temporary directory this test was given, if it was kept after the test failed.
+    pub kept_tmpdir: Option<PathBuf>,
+    /// The value of `NEXTEST_TEST_SEED` the test was run with.
+    pub test_seed: u64,
     /// The time at which the test started.
     pub start_time: SystemTime,
     /// The time it took for the test to run.
@@ -820,22 +2166,54 @@ pub struct ExecuteStatus {
     pub is_slow: bool,
 }
 
+impl ExecuteStatus {
+    /// Returns the output of this test in the order it was written in, across standard output and
+    /// standard error.
+    pub fn interleaved_output(&self) -> impl Iterator<Item = (OutputStream, &[u8])> + '_ {
+        self.output_chunks.iter().map(|chunk| {
+            let output = match chunk.stream {
+                OutputStream::Stdout => &self.stdout,
+                OutputStream::Stderr => &self.stderr,
+            };
+            (chunk.stream, &output[chunk.range.clone()])
+        })
+    }
+}
+
 struct InternalExecuteStatus {
     stdout: Bytes,
     stderr: Bytes,
+    output_chunks: Vec<OutputChunk>,
     result: ExecutionResult,
+    exit_code: Option<i32>,
+    leaked_processes: Vec<LeakedProcess>,
+    kept_tmpdir: Option<PathBuf>,
+    test_seed: u64,
     stopwatch_end: StopwatchEnd,
     is_slow: bool,
 }
 
 impl InternalExecuteStatus {
-    fn into_external(self, attempt: usize, total_attempts: usize) -> ExecuteStatus {
+    fn into_external(
+        self,
+        attempt: usize,
+        total_attempts: usize,
+        iteration: usize,
+        total_iterations: Option<usize>,
+    ) -> ExecuteStatus {
         ExecuteStatus {
             attempt,
             total_attempts,
+            iteration,
+            total_iterations,
             stdout: self.stdout,
             stderr: self.stderr,
+            output_chunks: self.output_chunks,
             result: self.result,
+            exit_code: self.exit_code,
+            leaked_processes: self.leaked_processes,
+            kept_tmpdir: self.kept_tmpdir,
+            test_seed: self.test_seed,
             start_time: self.stopwatch_end.start_time,
             time_taken: self.stopwatch_end.duration,
             is_slow: self.is_slow,
@@ -843,11 +2221,48 @@ impl InternalExecuteStatus {
     }
 }
 
+/// Derives the `NEXTEST_TEST_SEED` value for an iteration of a test from the run's seed.
+///
+/// This is an FNV-1a hash of the seed, the test and the iteration, mixed with the finalizer from
+/// SplitMix64 so that similar inputs give very different values. Unlike the hashers in the standard
+/// library, it gives the same values on every platform and with every version of Rust.
+fn derive_test_seed(seed: u64, test_instance: &TestInstance<'_>, iteration: usize) -> u64 {
+    mix_test_seed(
+        seed,
+        &test_instance.bin_info.binary_id,
+        test_instance.name,
+        iteration,
+    )
+}
+
+fn mix_test_seed(seed: u64, binary_id: &str, name: &str, iteration: usize) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let bytes = seed
+        .to_le_bytes()
+        .into_iter()
+        .chain(binary_id.bytes())
+        // Separators keep a binary ID and test name from running into each other.
+        .chain([0xff])
+        .chain(name.bytes())
+        .chain([0xff])
+        .chain((iteration as u64).to_le_bytes());
+    for byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
+
 /// Statistics for a test run.
 #[derive(Copy, Clone, Default, Debug, Eq, PartialEq)]
 pub struct RunStats {
     /// The total number of tests that were expected to be run at the beginning.
     ///
+    /// With `--iterations`, each iteration of a test counts separately here and in the other
+    /// counts.
+    ///
     /// If the test run is canceled, this will be more than `finished_count` at the end.
     pub initial_run_count: usize,
 
@@ -880,6 +2295,32 @@ pub struct RunStats {
 
     /// The number of tests that were skipped.
     pub skipped: usize,
+
+    /// The number of tests that were expected to fail and did. Included in `passed`.
+    pub xfailed: usize,
+
+    /// The number of tests that were expected to fail but passed, and weren't marked as failed
+    /// for it. Included in `passed`.
+    pub xpassed: usize,
+
+    /// The number of quarantined tests that were run. Quarantined tests that passed are included
+    /// in `passed`, but quarantined tests that failed aren't counted as failed.
+    pub quarantined: usize,
+
+    /// The number of quarantined tests that failed.
+    pub quarantined_failed: usize,
+
+    /// The number of tests that passed, but whose output differed or that failed when they were
+    /// run again, with `--check-determinism`. Included in `passed`.
+    pub nondeterministic: usize,
+
+    /// The number of tests that finished before the run was interrupted, if it's resumed with
+    /// `--resume`. Included in `finished_count`, and in `passed`, `flaky` or `failed`.
+    pub previously_finished: usize,
+
+    /// If any pass rates are required with `required-pass-rate`, whether enough tests passed
+    /// for all of them to be met. Set once the run has finished.
+    pub pass_rate_met: Option<bool>,
 }
 
 impl RunStats {
@@ -887,13 +2328,17 @@ impl RunStats {
     ///
     /// A run can be marked as failed if any of the following are true:
     /// * the run was canceled: the initial run count is greater than the final run count
-    /// * any tests failed
-    /// * any tests encountered an execution failure
+    /// * any tests failed or encountered an execution failure, unless pass rates are required and
+    ///   they were all met
+    /// * any tests were found to be nondeterministic
     pub fn is_success(&self) -> bool {
         if self.initial_run_count > self.finished_count {
             return false;
         }
-        if self.any_failed() {
+        if self.any_failed() && self.pass_rate_met != Some(true) {
+            return false;
+        }
+        if self.nondeterministic > 0 {
             return false;
         }
         true
@@ -902,11 +2347,39 @@ impl RunStats {
     /// Returns true if any tests failed or were timed out.
     #[inline]
     pub fn any_failed(&self) -> bool {
-        self.failed > 0 || self.exec_failed > 0 || self.timed_out > 0
+        self.failed_count() > 0
+    }
+
+    /// Returns the number of tests that failed, encountered an execution failure or timed out.
+    #[inline]
+    pub fn failed_count(&self) -> usize {
+        self.failed + self.exec_failed + self.timed_out
+    }
+
+    fn on_test_previously_finished(&mut self, status: RunStatus) {
+        self.finished_count += 1;
+        self.previously_finished += 1;
+        match status {
+            RunStatus::Passed => self.passed += 1,
+            RunStatus::Flaky => {
+                self.passed += 1;
+                self.flaky += 1;
+            }
+            RunStatus::Failed => self.failed += 1,
+            RunStatus::Skipped => self.skipped += 1,
+        }
     }
 
-    fn on_test_finished(&mut self, run_statuses: &ExecutionStatuses) {
+    fn on_test_finished(&mut self, run_statuses: &ExecutionStatuses, quarantined: bool) {
         self.finished_count += 1;
+        if quarantined {
+            self.quarantined += 1;
+            // Failures of quarantined tests are only counted here, so they don't fail the run.
+            if !run_statuses.last_status().result.is_success() {
+                self.quarantined_failed += 1;
+                return;
+            }
+        }
         // run_statuses is guaranteed to have at least one element.
         // * If the last element is success, treat it as success (and possibly flaky).
         // * If the last element is a failure, use it to determine fail/exec fail.
@@ -936,13 +2409,29 @@ impl RunStats {
                     self.flaky += 1;
                 }
             }
-            ExecutionResult::Fail { .. } => {
+            ExecutionResult::ExpectedFailure | ExecutionResult::UnexpectedPass => {
+                self.passed += 1;
+                match last_status.result {
+                    ExecutionResult::ExpectedFailure => self.xfailed += 1,
+                    _ => self.xpassed += 1,
+                }
+                if last_status.is_slow {
+                    self.passed_slow += 1;
+                }
+                if run_statuses.len() > 1 {
+                    self.flaky += 1;
+                }
+            }
+            ExecutionResult::Fail { .. }
+            | ExecutionResult::MemoryLimitExceeded
+            | ExecutionResult::LeakFail
+            | ExecutionResult::UnexpectedPassFail => {
                 self.failed += 1;
                 if last_status.is_slow {
                     self.failed_slow += 1;
                 }
             }
-            ExecutionResult::Timeout => self.timed_out += 1,
+            ExecutionResult::Timeout | ExecutionResult::Hang => self.timed_out += 1,
             ExecutionResult::ExecFail => self.exec_failed += 1,
         }
     }
@@ -969,23 +2458,123 @@ enum SignalForwardEvent {
     Twice,
 }
 
-struct CallbackContext<F, E> {
+/// How many of the tests that count towards a pass rate required with `required-pass-rate`
+/// passed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PassRateCheck {
+    /// The test group whose tests count towards this pass rate, or `None` for the tests that
+    /// aren't in a test group with its own.
+    pub test_group: Option<String>,
+
+    /// The fraction of these tests, between 0 and 1, that must pass.
+    pub required: f64,
+
+    /// The number of these tests that passed.
+    pub passed: usize,
+
+    /// The number of these tests that failed.
+    pub failed: usize,
+}
+
+impl PassRateCheck {
+    /// Returns the fraction of these tests that passed, between 0 and 1.
+    ///
+    /// This is 1 if none of them were run.
+    pub fn pass_rate(&self) -> f64 {
+        match self.passed + self.failed {
+            0 => 1.0,
+            total => self.passed as f64 / total as f64,
+        }
+    }
+
+    /// Returns true if enough of these tests passed.
+    pub fn is_met(&self) -> bool {
+        self.pass_rate() >= self.required
+    }
+}
+
+/// Counts how many tests passed and failed towards each required pass rate.
+struct PassRateTally<'a> {
+    // The pass rate required of tests that aren't in a test group with its own.
+    required: Option<f64>,
+    // Test group name -> required pass rate.
+    group_rates: BTreeMap<&'a str, f64>,
+    // (binary ID, test name) -> the test group whose pass rate the test counts towards.
+    test_groups: HashMap<(&'a str, &'a str), &'a str>,
+    // Test group, or None for the rest of the tests -> (passed, failed).
+    counts: BTreeMap<Option<&'a str>, (usize, usize)>,
+}
+
+impl<'a> PassRateTally<'a> {
+    fn record(&mut self, test_instance: &TestInstance<'a>, passed: bool) {
+        let test_group = self
+            .test_groups
+            .get(&(
+                test_instance.bin_info.binary_id.as_str(),
+                test_instance.name,
+            ))
+            .copied();
+        let (passed_count, failed_count) = self.counts.entry(test_group).or_default();
+        match passed {
+            true => *passed_count += 1,
+            false => *failed_count += 1,
+        }
+    }
+
+    /// Returns the checks for each required pass rate, and whether all of them were met.
+    fn finish(&self) -> (Vec<PassRateCheck>, bool) {
+        let counts = |test_group| self.counts.get(&test_group).copied().unwrap_or_default();
+        let mut checks = vec![];
+        let (passed, failed) = counts(None);
+        match self.required {
+            Some(required) => checks.push(PassRateCheck {
+                test_group: None,
+                required,
+                passed,
+                failed,
+            }),
+            // Without a pass rate of their own, every one of these tests must pass.
+            None if failed > 0 => return (checks, false),
+            None => {}
+        }
+        for (&name, &required) in &self.group_rates {
+            let (passed, failed) = counts(Some(name));
+            checks.push(PassRateCheck {
+                test_group: Some(name.to_owned()),
+                required,
+                passed,
+                failed,
+            });
+        }
+        let met = checks.iter().all(PassRateCheck::is_met);
+        (checks, met)
+    }
+}
+
+struct CallbackContext<'a, F, E> {
     callback: F,
     run_id: Uuid,
     stopwatch: StopwatchStart,
     run_stats: RunStats,
-    fail_fast: bool,
+    max_fail: Option<usize>,
     running: usize,
     cancel_state: Option<CancelReason>,
     signal_count: Option<SignalCount>,
+    pass_rate_tally: Option<PassRateTally<'a>>,
     phantom: PhantomData<E>,
 }
 
-impl<'a, F, E> CallbackContext<F, E>
+impl<'a, F, E> CallbackContext<'a, F, E>
 where
     F: FnMut(TestEvent<'a>) -> Result<(), E> + Send,
 {
-    fn new(callback: F, run_id: Uuid, initial_run_count: usize, fail_fast: bool) -> Self {
+    fn new(
+        callback: F,
+        run_id: Uuid,
+        initial_run_count: usize,
+        max_fail: Option<usize>,
+        pass_rate_tally: Option<PassRateTally<'a>>,
+    ) -> Self {
         Self {
             callback,
             run_id,
@@ -994,18 +2583,52 @@ where
                 initial_run_count,
                 ..RunStats::default()
             },
-            fail_fast,
+            max_fail,
             running: 0,
             cancel_state: None,
             signal_count: None,
+            pass_rate_tally,
             phantom: PhantomData,
         }
     }
 
-    fn run_started(&mut self, test_list: &'a TestList) -> Result<(), E> {
+    #[allow(clippy::too_many_arguments)]
+    fn run_started(
+        &mut self,
+        test_list: &'a TestList,
+        iterations: Option<usize>,
+        until_failure: bool,
+        shuffle_seed: Option<u64>,
+        seed: u64,
+        previously_finished: Option<usize>,
+        listing: bool,
+    ) -> Result<(), E> {
         (self.callback)(TestEvent::RunStarted {
             test_list,
             run_id: self.run_id,
+            iterations,
+            until_failure,
+            shuffle_seed,
+            seed,
+            previously_finished,
+            listing,
+        })
+    }
+
+    fn previously_finished(
+        &mut self,
+        test_instance: TestInstance<'a>,
+        finished: FinishedTest,
+    ) -> Result<(), E> {
+        self.run_stats.on_test_previously_finished(finished.status);
+        if let Some(tally) = &mut self.pass_rate_tally {
+            tally.record(&test_instance, finished.status != RunStatus::Failed);
+        }
+        (self.callback)(TestEvent::TestPreviouslyFinished {
+            test_instance,
+            status: finished.status,
+            time_taken: finished.time_taken,
+            current_stats: self.run_stats,
         })
     }
 
@@ -1029,27 +2652,119 @@ where
                 elapsed,
             })
             .map_err(InternalError::Error),
+            InternalEvent::Test(InternalTestEvent::Output {
+                test_instance,
+                stream,
+                output,
+                elapsed,
+            }) => (self.callback)(TestEvent::TestOutput {
+                test_instance,
+                stream,
+                output,
+                elapsed,
+            })
+            .map_err(InternalError::Error),
             InternalEvent::Test(InternalTestEvent::Retry {
                 test_instance,
                 run_status,
-            }) => (self.callback)(TestEvent::TestRetry {
+                delay,
+                deferred,
+            }) => {
+                // Tests waiting for deferred retries aren't running.
+                if deferred {
+                    self.running -= 1;
+                }
+                (self.callback)(TestEvent::TestRetry {
+                    test_instance,
+                    run_status: *run_status,
+                    delay,
+                    deferred,
+                })
+                .map_err(InternalError::Error)
+            }
+            InternalEvent::Test(InternalTestEvent::TestsAdded {
+                test_list,
+                run_count,
+                pass_rate_groups,
+            }) => {
+                self.run_stats.initial_run_count += run_count;
+                if let Some(tally) = &mut self.pass_rate_tally {
+                    tally.test_groups.extend(pass_rate_groups);
+                }
+                (self.callback)(TestEvent::TestsAdded { test_list }).map_err(InternalError::Error)
+            }
+            InternalEvent::Test(InternalTestEvent::PreviouslyFinished {
+                test_instance,
+                finished,
+            }) => self
+                .previously_finished(test_instance, finished)
+                .map_err(InternalError::Error),
+            InternalEvent::Test(InternalTestEvent::ListingFailed) => Err(
+                InternalError::ListingCanceled(self.begin_cancel(CancelReason::BuildFailure).err()),
+            ),
+            InternalEvent::Test(InternalTestEvent::Resumed) => {
+                self.running += 1;
+                Ok(())
+            }
+            InternalEvent::Test(InternalTestEvent::IterationStarted { run_count }) => {
+                self.run_stats.initial_run_count += run_count;
+                Ok(())
+            }
+            InternalEvent::Test(InternalTestEvent::IterationFinished { done_sender }) => {
+                // Every event sent before this one has been handled by now.
+                let _ = done_sender.send(());
+                Ok(())
+            }
+            InternalEvent::Test(InternalTestEvent::IsolationChecked {
                 test_instance,
                 run_status,
+            }) => (self.callback)(TestEvent::TestIsolationChecked {
+                test_instance,
+                run_status: *run_status,
             })
             .map_err(InternalError::Error),
+            InternalEvent::Test(InternalTestEvent::DeterminismChecked {
+                test_instance,
+                run_status,
+                difference,
+            }) => {
+                if difference.is_some() {
+                    self.run_stats.nondeterministic += 1;
+                }
+                (self.callback)(TestEvent::TestDeterminismChecked {
+                    test_instance,
+                    run_status: *run_status,
+                    difference,
+                })
+                .map_err(InternalError::Error)
+            }
             InternalEvent::Test(InternalTestEvent::Finished {
                 test_instance,
                 run_statuses,
+                quarantined,
             }) => {
                 self.running -= 1;
-                self.run_stats.on_test_finished(&run_statuses);
+                self.run_stats.on_test_finished(&run_statuses, quarantined);
+                // Quarantined tests don't count towards pass rates, as they don't fail the run.
+                if let (Some(tally), false) = (&mut self.pass_rate_tally, quarantined) {
+                    tally.record(
+                        &test_instance,
+                        run_statuses.last_status().result.is_success(),
+                    );
+                }
 
-                // should this run be canceled because of a failure?
-                let fail_cancel = self.fail_fast && !run_statuses.last_status().result.is_success();
+                // should this run be canceled because of a failure? Quarantined tests never cancel
+                // the run.
+                let fail_cancel = !quarantined
+                    && !run_statuses.last_status().result.is_success()
+                    && self
+                        .max_fail
+                        .map_or(false, |max_fail| self.run_stats.failed_count() >= max_fail);
 
                 (self.callback)(TestEvent::TestFinished {
                     test_instance,
                     run_statuses,
+                    quarantined,
                     current_stats: self.run_stats,
                     running: self.running,
                     cancel_state: self.cancel_state,
@@ -1076,6 +2791,27 @@ where
                 })
                 .map_err(InternalError::Error)
             }
+            InternalEvent::Test(InternalTestEvent::NotStarted {
+                test_instance,
+                over_budget,
+            }) => {
+                let res = match over_budget {
+                    true => Err(InternalError::RunTimeCanceled(
+                        self.begin_cancel(CancelReason::RunTimeBudget).err(),
+                    )),
+                    false => Ok(()),
+                };
+                // Only tests that weren't started because of the run time budget are reported, so
+                // that a later run can pick them up.
+                if self.cancel_state == Some(CancelReason::RunTimeBudget) {
+                    (self.callback)(TestEvent::TestNotRun { test_instance })
+                        .map_err(InternalError::Error)?;
+                }
+                res
+            }
+            InternalEvent::RunTimeExceeded => Err(InternalError::RunTimeCanceled(
+                self.begin_cancel(CancelReason::RunTimeBudget).err(),
+            )),
             InternalEvent::Signal(event) => {
                 let signal_count = self.increment_signal_count();
                 let forward_event = signal_count.to_forward_event(event);
@@ -1122,11 +2858,20 @@ where
 
     fn run_finished(&mut self) -> Result<(), E> {
         let stopwatch_end = self.stopwatch.end();
+        let pass_rates = match &self.pass_rate_tally {
+            Some(tally) => {
+                let (checks, met) = tally.finish();
+                self.run_stats.pass_rate_met = Some(met);
+                checks
+            }
+            None => vec![],
+        };
         (self.callback)(TestEvent::RunFinished {
             start_time: stopwatch_end.start_time,
             run_id: self.run_id,
             elapsed: stopwatch_end.duration,
             run_stats: self.run_stats,
+            pass_rates,
         })
     }
 }
@@ -1135,6 +2880,7 @@ where
 enum InternalEvent<'a> {
     Test(InternalTestEvent<'a>),
     Signal(SignalEvent),
+    RunTimeExceeded,
 }
 
 #[derive(Debug)]
@@ -1146,27 +2892,147 @@ enum InternalTestEvent<'a> {
         test_instance: TestInstance<'a>,
         elapsed: Duration,
     },
+    Output {
+        test_instance: TestInstance<'a>,
+        stream: OutputStream,
+        output: Bytes,
+        elapsed: Duration,
+    },
     Retry {
         test_instance: TestInstance<'a>,
-        run_status: ExecuteStatus,
+        // Boxed since statuses are much larger than the other variants.
+        run_status: Box<ExecuteStatus>,
+        delay: Duration,
+        deferred: bool,
+    },
+    // A list of tests was added while tests were running. run_count is the number of tests in it
+    // to run, across all iterations if their number is known.
+    TestsAdded {
+        test_list: &'a TestList<'a>,
+        run_count: usize,
+        pass_rate_groups: Vec<((&'a str, &'a str), &'a str)>,
+    },
+    // A test in a list that was added finished before the run being resumed was interrupted.
+    PreviouslyFinished {
+        test_instance: TestInstance<'a>,
+        finished: FinishedTest,
+    },
+    // Listing tests failed, and the error was stored to be returned.
+    ListingFailed,
+    // A test whose retries were deferred to the end of the run is running again.
+    Resumed,
+    // Another iteration of the tests was started, with no limit on the number of iterations.
+    IterationStarted {
+        run_count: usize,
+    },
+    // An iteration finished, with no limit on the number of iterations. done_sender is notified
+    // once this event is handled.
+    IterationFinished {
+        done_sender: oneshot::Sender<()>,
+    },
+    IsolationChecked {
+        test_instance: TestInstance<'a>,
+        // Boxed since statuses are much larger than the other variants.
+        run_status: Box<ExecuteStatus>,
+    },
+    DeterminismChecked {
+        test_instance: TestInstance<'a>,
+        // Boxed since statuses are much larger than the other variants.
+        run_status: Box<ExecuteStatus>,
+        difference: Option<OutputDifference>,
     },
     Finished {
         test_instance: TestInstance<'a>,
         run_statuses: ExecutionStatuses,
+        quarantined: bool,
     },
     Skipped {
         test_instance: TestInstance<'a>,
         reason: MismatchReason,
     },
+    // The test wasn't started because the run was canceled, or, if over_budget is set, because it
+    // wouldn't have finished within the run time budget.
+    NotStarted {
+        test_instance: TestInstance<'a>,
+        over_budget: bool,
+    },
 }
 
 #[derive(Debug)]
 enum InternalError<E> {
     Error(E),
     TestFailureCanceled(Option<E>),
+    ListingCanceled(Option<E>),
+    RunTimeCanceled(Option<E>),
     SignalCanceled(SignalForwardEvent, Option<E>),
 }
 
+/// Stores the error that listing tests failed with, unless there already was one, and cancels the
+/// run.
+fn listing_failed<'a, E>(
+    listing_error: &Mutex<Option<E>>,
+    err: E,
+    run_sender: &UnboundedSender<InternalTestEvent<'a>>,
+) {
+    let mut listing_error = listing_error
+        .lock()
+        .expect("listing error lock isn't poisoned");
+    if listing_error.is_none() {
+        *listing_error = Some(err);
+    }
+    // Failure to send means the receiver was dropped.
+    let _ = run_sender.send(InternalTestEvent::ListingFailed);
+}
+
+/// Reports the lines of `unreported` and removes them from it, in live-output mode.
+///
+/// Only complete lines are reported until the stream ends, at which point whatever is left is.
+fn report_output_lines<'a>(
+    test_instance: TestInstance<'a>,
+    stream: OutputStream,
+    unreported: &mut BytesMut,
+    elapsed: Duration,
+    at_end: bool,
+    run_sender: &UnboundedSender<InternalTestEvent<'a>>,
+) {
+    let end = match at_end {
+        true => unreported.len(),
+        false => match unreported.iter().rposition(|&b| b == b'\n') {
+            Some(newline) => newline + 1,
+            None => return,
+        },
+    };
+    if end > 0 {
+        // Failure to send means the receiver was dropped.
+        let _ = run_sender.send(InternalTestEvent::Output {
+            test_instance,
+            stream,
+            output: unreported.split_to(end).freeze(),
+            elapsed,
+        });
+    }
+}
+
+/// The stream a test wrote output to.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum OutputStream {
+    /// Standard output.
+    Stdout,
+    /// Standard error.
+    Stderr,
+}
+
+/// A part of the output captured from a test, as listed in [`ExecuteStatus::output_chunks`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OutputChunk {
+    /// The stream the output was written to.
+    pub stream: OutputStream,
+    /// The range of [`ExecuteStatus::stdout`] or [`ExecuteStatus::stderr`] that this chunk is.
+    pub range: Range<usize>,
+    /// The time since the test started at which this chunk was read.
+    pub elapsed: Duration,
+}
+
 /// Whether a test passed, failed or an error occurred while executing the test.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum ExecutionResult {
@@ -1192,20 +3058,98 @@ pub enum ExecutionResult {
     ExecFail,
     /// The test was terminated due to timeout.
     Timeout,
+    /// The test was terminated for producing no output for longer than its no-output timeout.
+    Hang,
+    /// The test was stopped for using more memory than its memory limit.
+    MemoryLimitExceeded,
+    /// The test passed, but left processes running after it exited, and tests that do are marked
+    /// as failed.
+    LeakFail,
+    /// The test was expected to fail, and it failed.
+    ///
+    /// This is treated as a pass.
+    ExpectedFailure,
+    /// The test was expected to fail, but it passed.
+    ///
+    /// This is treated as a pass.
+    UnexpectedPass,
+    /// The test was expected to fail, but it passed, and tests that do are marked as failed.
+    UnexpectedPassFail,
 }
 
 impl ExecutionResult {
     /// Returns true if the test was successful.
     pub fn is_success(self) -> bool {
         match self {
-            ExecutionResult::Pass | ExecutionResult::Leak => true,
-            ExecutionResult::Fail { .. } | ExecutionResult::ExecFail | ExecutionResult::Timeout => {
-                false
+            ExecutionResult::Pass
+            | ExecutionResult::Leak
+            | ExecutionResult::ExpectedFailure
+            | ExecutionResult::UnexpectedPass => true,
+            ExecutionResult::Fail { .. }
+            | ExecutionResult::ExecFail
+            | ExecutionResult::Timeout
+            | ExecutionResult::Hang
+            | ExecutionResult::MemoryLimitExceeded
+            | ExecutionResult::LeakFail
+            | ExecutionResult::UnexpectedPassFail => false,
+        }
+    }
+
+    /// Returns the result of a test that's expected to fail, given what the result would otherwise
+    /// have been.
+    ///
+    /// Only tests that fail their assertions count as expected failures: tests that couldn't be
+    /// run, or that were stopped by nextest, still fail.
+    fn to_expected_failure(self, strict: bool) -> Self {
+        match self {
+            ExecutionResult::Pass | ExecutionResult::Leak => match strict {
+                true => ExecutionResult::UnexpectedPassFail,
+                false => ExecutionResult::UnexpectedPass,
+            },
+            ExecutionResult::Fail { .. } | ExecutionResult::LeakFail => {
+                ExecutionResult::ExpectedFailure
             }
+            other => other,
         }
     }
 }
 
+/// How a test that passed behaved differently when it was run again, with `--check-determinism`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum OutputDifference {
+    /// The test failed when it was run again.
+    Failed,
+
+    /// A line of standard output differed.
+    Stdout(LineDifference),
+
+    /// A line of standard error differed.
+    Stderr(LineDifference),
+}
+
+/// The first line that differed in the output of two runs of a test.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LineDifference {
+    /// The line number, starting from 1.
+    pub line: usize,
+
+    /// The line in the first run, or None if the output ended before it.
+    pub first: Option<String>,
+
+    /// The line in the second run, or None if the output ended before it.
+    pub second: Option<String>,
+}
+
+/// A process that was still running after the test that started it exited.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LeakedProcess {
+    /// The process ID.
+    pub pid: u32,
+
+    /// The command line of the process, if known.
+    pub command: Option<String>,
+}
+
 /// A regular exit code or Windows NT abort status for a test.
 ///
 /// Returned as part of the [`ExecutionResult::Fail`] variant.
@@ -1242,12 +3186,16 @@ pub fn configure_handle_inheritance(
 mod imp {
     use super::*;
     pub(super) use win32job::Job;
-    use win32job::JobError;
+    use win32job::{ExtendedLimitInfo, JobError};
     use windows::Win32::{
         Foundation::{SetHandleInformation, HANDLE, HANDLE_FLAGS, HANDLE_FLAG_INHERIT},
         System::{
             Console::{GetStdHandle, STD_ERROR_HANDLE, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE},
-            JobObjects::TerminateJobObject,
+            JobObjects::{TerminateJobObject, JOB_OBJECT_LIMIT_JOB_MEMORY},
+            Threading::{
+                ABOVE_NORMAL_PRIORITY_CLASS, BELOW_NORMAL_PRIORITY_CLASS, HIGH_PRIORITY_CLASS,
+                IDLE_PRIORITY_CLASS, NORMAL_PRIORITY_CLASS,
+            },
         },
     };
 
@@ -1287,6 +3235,44 @@ mod imp {
         // TODO: set process group on Windows for better ctrl-C handling.
     }
 
+    /// Starts the process with the priority class closest to the nice value.
+    pub(super) fn set_priority(cmd: &mut std::process::Command, nice: i32) {
+        use std::os::windows::process::CommandExt;
+
+        let priority_class = match nice {
+            i32::MIN..=-15 => HIGH_PRIORITY_CLASS,
+            -14..=-1 => ABOVE_NORMAL_PRIORITY_CLASS,
+            0 => NORMAL_PRIORITY_CLASS,
+            1..=9 => BELOW_NORMAL_PRIORITY_CLASS,
+            _ => IDLE_PRIORITY_CLASS,
+        };
+        cmd.creation_flags(priority_class.0);
+    }
+
+    pub(super) fn create_job(
+        memory_limit: Option<u64>,
+        pinned_cpus: Option<&PinnedCpus<'_>>,
+    ) -> Result<Job, JobError> {
+        if memory_limit.is_none() && pinned_cpus.is_none() {
+            return Job::create();
+        }
+
+        let mut info = ExtendedLimitInfo::new();
+        if let Some(limit) = memory_limit {
+            // This limits the memory committed by all processes in the job together.
+            info.0.JobMemoryLimit = usize::try_from(limit).unwrap_or(usize::MAX);
+            info.0.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_JOB_MEMORY.0;
+        }
+        if let Some(pinned_cpus) = pinned_cpus {
+            // Every process in the job is pinned, including processes started by the test.
+            let mask = pinned_cpus
+                .cpus()
+                .fold(0usize, |mask, cpu| mask | (1 << cpu));
+            info.limit_affinity(mask);
+        }
+        Job::create_with_limit_info(&mut info)
+    }
+
     pub(super) fn assign_process_to_job(
         child: &tokio::process::Child,
         job: Option<&Job>,
@@ -1308,21 +3294,75 @@ mod imp {
         Ok(())
     }
 
+    pub(super) fn describe_hung(_pid: Option<u32>) -> Option<String> {
+        // Processes aren't described on Windows.
+        None
+    }
+
+    pub(super) fn leaked_processes(_pid: Option<u32>, job: Option<&Job>) -> Vec<LeakedProcess> {
+        // Processes started by the test stay in its job object, even once it has exited.
+        let pids = job
+            .and_then(|job| job.query_process_id_list().ok())
+            .unwrap_or_default();
+        let mut processes: Vec<_> = pids
+            .into_iter()
+            .map(|pid| LeakedProcess {
+                pid: pid as u32,
+                command: None,
+            })
+            .collect();
+        processes.sort_by_key(|process| process.pid);
+        processes
+    }
+
+    pub(super) fn kill_leaked_processes(_pid: Option<u32>, job: Option<&Job>) {
+        if let Some(job) = job {
+            unsafe {
+                // Ignore the error here -- it's likely due to the processes exiting.
+                TerminateJobObject(HANDLE(job.handle() as isize), 1);
+            }
+        }
+    }
+
     pub(super) async fn terminate_child(
         child: &mut Child,
         mode: TerminateMode,
-        _forward_receiver: &mut tokio::sync::broadcast::Receiver<SignalForwardEvent>,
+        grace_period: Duration,
+        // Tests that time out are killed immediately on Windows.
+        _timeout_signals: Option<&[TerminateStep]>,
+        forward_receiver: &mut tokio::sync::broadcast::Receiver<SignalForwardEvent>,
         job: Option<&Job>,
     ) {
-        // Ignore signal events since Windows propagates them to child processes (this may change if
-        // we start assigning processes to groups on Windows).
-        if mode != TerminateMode::Timeout {
-            return;
+        if let TerminateMode::Signal(SignalForwardEvent::Once(_)) = mode {
+            // Windows propagates signals to child processes (this may change if we start assigning
+            // processes to groups on Windows), so give the test a chance to shut down by itself.
+            let sleep = tokio::time::sleep(grace_period);
+            tokio::select! {
+                biased;
+
+                _ = child.wait() => {
+                    // The process exited.
+                    return;
+                }
+                recv = forward_receiver.recv() => {
+                    // The sender stays open longer than the whole loop, and the buffer is big
+                    // enough for all messages ever sent through this channel, so a RecvError
+                    // should never happen.
+                    let _ = recv.expect("a RecvError should never happen here");
+
+                    // Receiving a signal while in this state always means kill immediately.
+                }
+                _ = sleep => {
+                    // The process didn't exit -- need to do a hard shutdown.
+                }
+            }
         }
+
         if let Some(job) = job {
             let handle = job.handle();
             unsafe {
-                // Ignore the error here -- it's likely due to the process exiting.
+                // Ignore the error here -- it's likely due to the process exiting. Terminating the
+                // job kills every process in it, including processes started by the test.
                 // Note: 1 is the exit code returned by Windows.
                 TerminateJobObject(HANDLE(handle as isize), 1);
             }
@@ -1335,6 +3375,10 @@ mod imp {
 #[cfg(unix)]
 mod imp {
     use super::*;
+    use crate::{
+        config::TerminateSignal,
+        process_tree::{self, EscapedProcesses},
+    };
     use libc::{SIGHUP, SIGINT, SIGKILL, SIGTERM};
     use std::os::unix::process::CommandExt;
 
@@ -1373,13 +3417,27 @@ mod imp {
         };
     }
 
+    /// Sets the nice value of the process before it runs the test.
+    pub(super) fn set_priority(cmd: &mut std::process::Command, nice: i32) {
+        unsafe {
+            cmd.pre_exec(move || {
+                // Raising the priority above nextest's own typically needs privileges. Failing to
+                // change it isn't fatal, so ignore errors.
+                libc::setpriority(libc::PRIO_PROCESS, 0, nice);
+                Ok(())
+            })
+        };
+    }
+
     #[derive(Debug)]
     pub(super) struct Job(());
 
-    impl Job {
-        pub(super) fn create() -> Result<Self, Infallible> {
-            Ok(Self(()))
-        }
+    pub(super) fn create_job(
+        _memory_limit: Option<u64>,
+        _pinned_cpus: Option<&PinnedCpus<'_>>,
+    ) -> Result<Job, Infallible> {
+        // Memory limits and CPU affinities are set up on the test process instead.
+        Ok(Job(()))
     }
 
     pub(super) fn assign_process_to_job(
@@ -1392,66 +3450,517 @@ mod imp {
     pub(super) async fn terminate_child(
         child: &mut Child,
         mode: TerminateMode,
+        grace_period: Duration,
+        timeout_signals: Option<&[TerminateStep]>,
         forward_receiver: &mut tokio::sync::broadcast::Receiver<SignalForwardEvent>,
         _job: Option<&Job>,
     ) {
         match child.id() {
             Some(pid) => {
                 let pid = pid as i32;
-                let term_signal = match mode {
-                    TerminateMode::Timeout => SIGTERM,
-                    TerminateMode::Signal(SignalForwardEvent::Once(SignalEvent::Hangup)) => SIGHUP,
-                    TerminateMode::Signal(SignalForwardEvent::Once(SignalEvent::Term)) => SIGTERM,
+                // The signals to send in order, along with how long to wait after each one.
+                let steps: Vec<(i32, Duration)> = match mode {
+                    TerminateMode::Timeout => match timeout_signals {
+                        Some(steps) => steps
+                            .iter()
+                            .map(|step| (signal_number(step.signal()), step.wait()))
+                            .collect(),
+                        None => vec![(SIGTERM, grace_period)],
+                    },
+                    TerminateMode::Signal(SignalForwardEvent::Once(SignalEvent::Hangup)) => {
+                        vec![(SIGHUP, grace_period)]
+                    }
+                    TerminateMode::Signal(SignalForwardEvent::Once(SignalEvent::Term)) => {
+                        vec![(SIGTERM, grace_period)]
+                    }
                     TerminateMode::Signal(SignalForwardEvent::Once(SignalEvent::Interrupt)) => {
-                        SIGINT
+                        vec![(SIGINT, grace_period)]
                     }
-                    TerminateMode::Signal(SignalForwardEvent::Twice) => SIGKILL,
+                    TerminateMode::Signal(SignalForwardEvent::Twice) => vec![],
                 };
-                unsafe {
-                    // We set up a process group in cmd_pre_exec -- now
-                    // send a signal to that group.
-                    libc::kill(-pid, term_signal)
+                // Processes started by the test can leave its process group, so look for those
+                // and signal them along with the group.
+                let escaped = EscapedProcesses::find(pid);
+                let signal_all = |signal| {
+                    unsafe {
+                        // We set up a process group in cmd_pre_exec -- now
+                        // send a signal to that group.
+                        libc::kill(-pid, signal)
+                    };
+                    escaped.signal(signal);
                 };
 
-                if term_signal == SIGKILL {
-                    // SIGKILL guarantees the process group is dead.
-                    return;
-                }
+                for (signal, wait) in steps {
+                    signal_all(signal);
 
-                // give the process a grace period of 10s
-                let sleep = tokio::time::sleep(Duration::from_secs(10));
-                tokio::select! {
-                    biased;
-
-                    _ = child.wait() => {
-                        // The process exited.
-                    }
-                    recv = forward_receiver.recv() => {
-                        // The sender stays open longer than the whole loop, and the buffer is big
-                        // enough for all messages ever sent through this channel, so a RecvError
-                        // should never happen.
-                        let _ = recv.expect("a RecvError should never happen here");
+                    // Give the test and every process it started time to exit.
+                    let sleep = tokio::time::sleep(wait);
+                    tokio::select! {
+                        biased;
 
-                        // Receiving a signal while in this state always means kill immediately.
-                        unsafe {
-                            // Send SIGKILL to the entire process group.
-                            libc::kill(-pid, SIGKILL);
+                        _ = wait_for_tree(child, pid, &escaped) => {
+                            // The processes exited.
+                            return;
                         }
-                    }
-                    _ = sleep => {
-                        // The process didn't exit -- need to do a hard shutdown.
-                        unsafe {
-                            // Send SIGKILL to the entire process group.
-                            libc::kill(-pid, SIGKILL);
+                        recv = forward_receiver.recv() => {
+                            // The sender stays open longer than the whole loop, and the buffer is
+                            // big enough for all messages ever sent through this channel, so a
+                            // RecvError should never happen.
+                            let _ = recv.expect("a RecvError should never happen here");
+
+                            // Receiving a signal while in this state always means kill
+                            // immediately.
+                            break;
+                        }
+                        _ = sleep => {
+                            // The processes didn't exit -- move on to the next signal.
                         }
                     }
                 }
+
+                // The processes didn't exit -- need to do a hard shutdown. SIGKILL guarantees the
+                // process group is dead.
+                signal_all(SIGKILL);
             }
             None => {
                 // This means that the process has already exited.
             }
         }
     }
+
+    fn signal_number(signal: TerminateSignal) -> i32 {
+        match signal {
+            TerminateSignal::Hup => SIGHUP,
+            TerminateSignal::Int => SIGINT,
+            TerminateSignal::Quit => libc::SIGQUIT,
+            TerminateSignal::Abrt => libc::SIGABRT,
+            TerminateSignal::Usr1 => libc::SIGUSR1,
+            TerminateSignal::Usr2 => libc::SIGUSR2,
+            TerminateSignal::Alrm => libc::SIGALRM,
+            TerminateSignal::Term => SIGTERM,
+        }
+    }
+
+    /// Describes the processes in the test's process group, to help diagnose a hung test.
+    pub(super) fn describe_hung(pid: Option<u32>) -> Option<String> {
+        pid.map(|pid| process_tree::describe_group(pid as i32))
+    }
+
+    pub(super) fn leaked_processes(pid: Option<u32>, _job: Option<&Job>) -> Vec<LeakedProcess> {
+        // The test process led its process group, which the processes it started stay in unless
+        // they move out of it.
+        pid.map_or_else(Vec::new, |pid| process_tree::group_processes(pid as i32))
+    }
+
+    pub(super) fn kill_leaked_processes(pid: Option<u32>, _job: Option<&Job>) {
+        if let Some(pid) = pid {
+            unsafe {
+                // Send SIGKILL to the entire process group.
+                libc::kill(-(pid as i32), SIGKILL);
+            }
+        }
+    }
+
+    /// Waits for the test process, the rest of its process group and any processes that escaped it
+    /// to exit.
+    async fn wait_for_tree(child: &mut Child, pid: i32, escaped: &EscapedProcesses) {
+        let _ = child.wait().await;
+        // Now that the test process has been reaped, poll for the rest of the processes.
+        let mut interval = tokio::time::interval(Duration::from_millis(50));
+        loop {
+            // Signal 0 only checks whether any process in the group exists.
+            let group_running = unsafe { libc::kill(-pid, 0) } == 0;
+            if !group_running && !escaped.any_running() {
+                return;
+            }
+            interval.tick().await;
+        }
+    }
+}
+
+/// How often system load is sampled in adaptive mode.
+const ADAPT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Scales the number of tests running at a time with system load, forever.
+async fn adapt_threads(limiter: &AdaptiveLimiter, adaptive_threads: AdaptiveThreads, max: usize) {
+    let min = adaptive_threads.min_threads().min(max);
+    let mut interval = tokio::time::interval(ADAPT_INTERVAL);
+    // The first tick completes immediately: skip it so that tests get to start first.
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let sample = LoadSample::current();
+        if !sample.is_known() {
+            log::debug!(
+                target: "nextest-runner",
+                "system load isn't available on this platform, running {max} tests at a time",
+            );
+            return future::pending().await;
+        }
+
+        let limit = limiter.limit();
+        let next = sample.next_limit(limit, min, max, adaptive_threads.max_load());
+        if next != limit {
+            log::debug!(
+                target: "nextest-runner",
+                "scaling tests running at a time from {limit} to {next} \
+                 (load per CPU: {:?}, available memory: {:?})",
+                sample.load_per_cpu,
+                sample.available_memory,
+            );
+            limiter.set_limit(next);
+        }
+    }
+}
+
+/// Limits the number of tests running at a time in adaptive mode.
+///
+/// Unlike a semaphore, the limit can be lowered while tests are running: tests that are already
+/// running keep running, and new tests wait until enough of them finish.
+#[derive(Debug)]
+struct AdaptiveLimiter {
+    // (limit, running)
+    state: Mutex<(usize, usize)>,
+    notify: Notify,
+}
+
+impl AdaptiveLimiter {
+    fn new(limit: usize) -> Self {
+        Self {
+            state: Mutex::new((limit, 0)),
+            notify: Notify::new(),
+        }
+    }
+
+    fn limit(&self) -> usize {
+        self.state.lock().expect("lock isn't poisoned").0
+    }
+
+    fn set_limit(&self, limit: usize) {
+        self.state.lock().expect("lock isn't poisoned").0 = limit;
+        self.notify.notify_waiters();
+    }
+
+    /// Waits for `threads` slots to be free.
+    ///
+    /// A test that needs more slots than the limit runs once no other tests are running.
+    async fn acquire(&self, threads: usize) -> AdaptivePermit<'_> {
+        loop {
+            // Created before checking the state, so that notifications in between aren't missed.
+            let notified = self.notify.notified();
+            {
+                let mut state = self.state.lock().expect("lock isn't poisoned");
+                let (limit, running) = &mut *state;
+                if *running == 0 || *running + threads <= *limit {
+                    *running += threads;
+                    return AdaptivePermit {
+                        limiter: self,
+                        threads,
+                    };
+                }
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Slots handed out by an [`AdaptiveLimiter`], which are returned to it when dropped.
+#[derive(Debug)]
+struct AdaptivePermit<'a> {
+    limiter: &'a AdaptiveLimiter,
+    threads: usize,
+}
+
+impl Drop for AdaptivePermit<'_> {
+    fn drop(&mut self) {
+        self.limiter.state.lock().expect("lock isn't poisoned").1 -= self.threads;
+        self.limiter.notify.notify_waiters();
+    }
+}
+
+/// The tokens of a resource that tests can consume, such as GPUs.
+#[derive(Debug)]
+struct ResourcePool {
+    semaphore: Semaphore,
+    free: Mutex<BTreeSet<usize>>,
+}
+
+impl ResourcePool {
+    fn new(count: usize) -> Self {
+        Self {
+            semaphore: Semaphore::new(count),
+            free: Mutex::new((0..count).collect()),
+        }
+    }
+
+    /// Waits for `count` tokens to be free, then hands out the ones with the lowest indexes.
+    async fn acquire(&self, count: usize) -> ResourceTokens<'_> {
+        let count = u32::try_from(count).expect("token count fits in a u32");
+        let permit = self
+            .semaphore
+            .acquire_many(count)
+            .await
+            .expect("semaphore is never closed");
+        let mut free = self.free.lock().expect("lock isn't poisoned");
+        let indexes: Vec<_> = free.iter().take(count as usize).copied().collect();
+        for index in &indexes {
+            free.remove(index);
+        }
+        ResourceTokens {
+            pool: self,
+            indexes,
+            _permit: permit,
+        }
+    }
+}
+
+/// Tokens handed out by a [`ResourcePool`], which are returned to it when dropped.
+///
+/// Displays as the indexes of the tokens, separated by commas.
+#[derive(Debug)]
+struct ResourceTokens<'a> {
+    pool: &'a ResourcePool,
+    indexes: Vec<usize>,
+    // This is released after the tokens are returned to the pool, in drop order.
+    _permit: SemaphorePermit<'a>,
+}
+
+impl fmt::Display for ResourceTokens<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, index) in self.indexes.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{index}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ResourceTokens<'_> {
+    fn drop(&mut self) {
+        let mut free = self.pool.free.lock().expect("lock isn't poisoned");
+        free.extend(self.indexes.drain(..));
+    }
+}
+
+/// Hands out tokens from a GNU make jobserver, one for each running test.
+///
+/// Like make, this process starts out with an implicit token that isn't in the jobserver. It's
+/// handed out first, and tokens are only read from the jobserver while it's in use.
+#[derive(Debug)]
+struct JobserverTokens {
+    client: jobserver::Client,
+    helper: jobserver::HelperThread,
+    implicit_free: Mutex<bool>,
+    implicit_freed: Notify,
+    // Only one test waits for a token from the helper thread at a time.
+    waiting: tokio::sync::Mutex<JobserverWaiting>,
+}
+
+#[derive(Debug)]
+struct JobserverWaiting {
+    receiver: UnboundedReceiver<io::Result<jobserver::Acquired>>,
+    // Set if a token has been requested from the helper thread but not received yet.
+    requested: bool,
+}
+
+impl JobserverTokens {
+    /// Connects to an inherited jobserver or, in provide mode, creates a new one with one token
+    /// per test thread if none was inherited.
+    fn new(mode: JobserverMode, test_threads: usize) -> io::Result<Option<Self>> {
+        if mode == JobserverMode::Off {
+            return Ok(None);
+        }
+        // SAFETY: the file descriptors or handles named in the environment are only used if they
+        // were inherited by this process, and nextest doesn't close inherited file descriptors.
+        let client = match unsafe { jobserver::Client::from_env() } {
+            Some(client) => {
+                log::debug!(target: "nextest-runner", "using inherited jobserver");
+                client
+            }
+            None if mode == JobserverMode::Provide => {
+                // The implicit token counts as one of the test threads.
+                jobserver::Client::new(test_threads.saturating_sub(1))?
+            }
+            None => {
+                log::debug!(target: "nextest-runner", "no jobserver inherited");
+                return Ok(None);
+            }
+        };
+        Self::from_client(client).map(Some)
+    }
+
+    fn from_client(client: jobserver::Client) -> io::Result<Self> {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        // Reading a token blocks, so it's done on a helper thread.
+        let helper = client.clone().into_helper_thread(move |token| {
+            // Failure to send means the receiver was dropped, in which case the token is
+            // released right away.
+            let _ = sender.send(token);
+        })?;
+        Ok(Self {
+            client,
+            helper,
+            implicit_free: Mutex::new(true),
+            implicit_freed: Notify::new(),
+            waiting: tokio::sync::Mutex::new(JobserverWaiting {
+                receiver,
+                requested: false,
+            }),
+        })
+    }
+
+    /// Waits for either the implicit token or a token from the jobserver to be free.
+    async fn acquire(&self) -> JobserverToken<'_> {
+        if let Some(token) = self.try_acquire_implicit() {
+            return token;
+        }
+        let mut waiting = self.waiting.lock().await;
+        let JobserverWaiting {
+            receiver,
+            requested,
+        } = &mut *waiting;
+        loop {
+            // Created before checking the implicit token, so that it being freed in between isn't
+            // missed.
+            let implicit_freed = self.implicit_freed.notified();
+            if let Some(token) = self.try_acquire_implicit() {
+                return token;
+            }
+            if !*requested {
+                self.helper.request_token();
+                *requested = true;
+            }
+            match future::select(Box::pin(receiver.recv()), Box::pin(implicit_freed)).await {
+                future::Either::Left((token, _)) => {
+                    *requested = false;
+                    match token.expect("helper thread is alive while tokens are handed out") {
+                        Ok(acquired) => return JobserverToken::Acquired { _token: acquired },
+                        Err(err) => {
+                            log::warn!(
+                                target: "nextest-runner",
+                                "failed to read token from jobserver, starting test anyway\n({err})",
+                            );
+                            return JobserverToken::Unavailable;
+                        }
+                    }
+                }
+                future::Either::Right(((), _)) => {}
+            }
+        }
+    }
+
+    fn try_acquire_implicit(&self) -> Option<JobserverToken<'_>> {
+        let mut implicit_free = self.implicit_free.lock().expect("lock isn't poisoned");
+        std::mem::replace(&mut *implicit_free, false).then(|| JobserverToken::Implicit(self))
+    }
+}
+
+/// A token handed out by [`JobserverTokens`], which is released when dropped.
+#[derive(Debug)]
+enum JobserverToken<'a> {
+    Implicit(&'a JobserverTokens),
+    // This is released back to the jobserver when dropped.
+    Acquired { _token: jobserver::Acquired },
+    // Reading a token failed, so the test runs without one.
+    Unavailable,
+}
+
+impl Drop for JobserverToken<'_> {
+    fn drop(&mut self) {
+        if let Self::Implicit(tokens) = self {
+            *tokens.implicit_free.lock().expect("lock isn't poisoned") = true;
+            tokens.implicit_freed.notify_one();
+        }
+    }
+}
+
+/// Returns how long to wait before retrying a test whose attempt `attempt` failed, or `None` if the
+/// retry would take retries of the test past the policy's `max-retry-time`.
+///
+/// `retries_started` is when the first attempt of the test finished.
+fn retry_delay(
+    policy: &RetryPolicy,
+    attempt: usize,
+    retries_started: tokio::time::Instant,
+) -> Option<Duration> {
+    let mut delay = policy.delay(attempt);
+    if policy.jitter() {
+        delay = delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0));
+    }
+    match policy.max_retry_time() {
+        Some(max_retry_time) if retries_started.elapsed() + delay > max_retry_time => None,
+        _ => Some(delay),
+    }
+}
+
+/// Returns the query that overrides for `test_instance` are matched against.
+pub(crate) fn test_query<'t>(test_instance: &TestInstance<'t>) -> TestQuery<'t> {
+    TestQuery {
+        binary_query: BinaryQuery {
+            package_id: test_instance.bin_info.package.id(),
+            binary_id: &test_instance.bin_info.binary_id,
+            kind: test_instance.bin_info.kind.as_str(),
+            features: &test_instance.bin_info.features,
+            binary_name: &test_instance.bin_info.binary_name,
+            binary_path: test_instance.binary,
+            platform: convert_build_platform(test_instance.bin_info.build_platform),
+        },
+        test_name: test_instance.name,
+    }
+}
+
+/// A failed test whose retries were deferred to the end of the run.
+#[derive(Debug)]
+struct DeferredRetry {
+    run_statuses: Vec<ExecuteStatus>,
+    iteration: usize,
+    quarantined: bool,
+    // How long retrying the test has taken so far, including the delay before the next attempt.
+    retry_time: Duration,
+    // When the delay before the next attempt is over.
+    ready_at: tokio::time::Instant,
+}
+
+/// Finishes a test whose deferred retries aren't going to be run, with the attempts it's had.
+fn finish_deferred_retry<'a>(
+    run_sender: &UnboundedSender<InternalTestEvent<'a>>,
+    test_instance: TestInstance<'a>,
+    retry: DeferredRetry,
+) {
+    // Failure to send means the receiver was dropped.
+    let _ = run_sender.send(InternalTestEvent::Resumed);
+    let _ = run_sender.send(InternalTestEvent::Finished {
+        test_instance,
+        run_statuses: ExecutionStatuses::new(retry.run_statuses),
+        quarantined: retry.quarantined,
+    });
+}
+
+/// Reads the timings to start tests slowest first with, or returns `None` to start them in listing
+/// order if none are available.
+fn read_scheduling_timings(profile: &NextestProfile<'_>) -> Option<TestTimings> {
+    match RunStore::new(profile).read_timings() {
+        Ok(Some(timings)) => Some(timings),
+        Ok(None) => {
+            log::debug!(
+                target: "nextest-runner",
+                "no test timings recorded with this profile, starting tests in listing order",
+            );
+            None
+        }
+        Err(err) => {
+            let cause = std::error::Error::source(&err)
+                .map(|source| format!(": {source}"))
+                .unwrap_or_default();
+            log::warn!(
+                target: "nextest-runner",
+                "failed to read test timings, starting tests in listing order\n({err}{cause})",
+            );
+            None
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -1463,7 +3972,12 @@ enum TerminateMode {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::NextestConfig;
+    use crate::{
+        config::NextestConfig,
+        list::{RustTestSuite, RustTestSuiteStatus},
+    };
+    use guppy::{graph::PackageGraph, CargoMetadata, PackageId};
+    use nextest_metadata::{BuildPlatform, RustTestBinaryKind, RustTestCaseSummary};
 
     #[test]
     fn no_capture_settings() {
@@ -1483,6 +3997,282 @@ mod tests {
         assert_eq!(runner.inner.test_threads, 1, "tests run serially");
     }
 
+    #[test]
+    fn execute_while_listing() {
+        let test_list = TestList::empty();
+        let config = NextestConfig::default_config("/fake/dir");
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+        let describe = |event: TestEvent<'_>| match event {
+            TestEvent::RunStarted { listing, .. } => format!("started, listing: {listing}"),
+            TestEvent::TestsAdded { test_list } => {
+                format!("added {} binaries", test_list.binary_count())
+            }
+            TestEvent::RunBeginCancel { reason, .. } => format!("cancel: {reason:?}"),
+            TestEvent::RunFinished { run_stats, .. } => {
+                format!("finished, success: {}", run_stats.is_success())
+            }
+            other => panic!("unexpected event {other:?}"),
+        };
+
+        // Lists sent while the run is in progress are added to it.
+        let batches = TestListBatches::new();
+        let mut runner = TestRunnerBuilder::default()
+            .build(
+                &test_list,
+                profile.clone(),
+                SignalHandlerKind::Noop,
+                TargetRunner::empty(),
+            )
+            .unwrap();
+        let mut events = vec![];
+        let run_stats = runner
+            .try_execute_while_listing(
+                &batches,
+                |sender| {
+                    assert!(!sender.is_canceled(), "run isn't canceled yet");
+                    sender.send(async { Ok(TestList::empty()) });
+                    sender.send(async { Ok(TestList::empty()) });
+                    Ok(())
+                },
+                |event| {
+                    events.push(describe(event));
+                    Ok::<_, &str>(())
+                },
+            )
+            .expect("run succeeded");
+        assert!(run_stats.is_success());
+        assert_eq!(batches.iter().count(), 2, "both lists were added");
+        assert_eq!(
+            events,
+            vec![
+                "started, listing: true",
+                "added 0 binaries",
+                "added 0 binaries",
+                "finished, success: true",
+            ],
+        );
+
+        // If listing fails, the run is canceled and the error is returned.
+        let batches = TestListBatches::new();
+        let mut runner = TestRunnerBuilder::default()
+            .build(
+                &test_list,
+                profile,
+                SignalHandlerKind::Noop,
+                TargetRunner::empty(),
+            )
+            .unwrap();
+        let mut events = vec![];
+        let res = runner.try_execute_while_listing(
+            &batches,
+            |_| Err("build failed"),
+            |event| {
+                events.push(describe(event));
+                Ok(())
+            },
+        );
+        assert_eq!(res.unwrap_err(), "build failed");
+        assert_eq!(batches.iter().count(), 0, "no lists were added");
+        assert_eq!(
+            events,
+            vec![
+                "started, listing: true",
+                "cancel: BuildFailure",
+                "finished, success: true",
+            ],
+        );
+    }
+
+    #[test]
+    fn not_started_over_budget() {
+        static FIXTURE_JSON: &str = include_str!("../../fixtures/cargo-metadata.json");
+        let graph: PackageGraph = CargoMetadata::parse_json(FIXTURE_JSON)
+            .expect("fixture is valid JSON")
+            .build_graph()
+            .expect("fixture is valid PackageGraph");
+        let package = graph
+            .metadata(&PackageId::new(
+                "metadata-base 0.1.0 (path+file:///Users/fakeuser/local/testcrates/metadata/metadata-base)",
+            ))
+            .expect("package ID is valid");
+        let suite = RustTestSuite {
+            binary_id: "metadata-base".to_owned(),
+            package,
+            binary_name: "metadata-base".to_owned(),
+            kind: RustTestBinaryKind::LIB,
+            cwd: "/fake/cwd".into(),
+            build_platform: BuildPlatform::Target,
+            non_test_binaries: BTreeSet::new(),
+            artifact_binaries: BTreeSet::new(),
+            cdylibs: BTreeSet::new(),
+            doctest: None,
+            features: vec![],
+            single_test: false,
+            env: BTreeMap::new(),
+            target_triple: None,
+            status: RustTestSuiteStatus::Skipped,
+        };
+        let test_case = RustTestCaseSummary {
+            ignored: false,
+            ignore_reason: None,
+            location: None,
+            tags: BTreeSet::new(),
+            filter_match: FilterMatch::Matches,
+        };
+        let test_a = TestInstance::new("tests::a", "/fake/binary", &suite, &test_case);
+        let test_b = TestInstance::new("tests::b", "/fake/binary", &suite, &test_case);
+
+        let mut events = Vec::new();
+        let mut ctx = CallbackContext::<_, ()>::new(
+            |event| {
+                events.push(match event {
+                    TestEvent::RunBeginCancel { reason, .. } => format!("cancel: {reason:?}"),
+                    TestEvent::TestNotRun { test_instance } => {
+                        format!("not run: {}", test_instance.name)
+                    }
+                    other => panic!("unexpected event {other:?}"),
+                });
+                Ok(())
+            },
+            Uuid::new_v4(),
+            2,
+            None,
+            None,
+        );
+
+        // A test that wouldn't finish within the run time budget cancels the run, and is reported
+        // as not run.
+        let res = ctx.handle_event(InternalEvent::Test(InternalTestEvent::NotStarted {
+            test_instance: test_a,
+            over_budget: true,
+        }));
+        assert!(
+            matches!(res, Err(InternalError::RunTimeCanceled(None))),
+            "run is canceled because of the run time budget"
+        );
+        assert_eq!(ctx.cancel_state, Some(CancelReason::RunTimeBudget));
+        // Tests that aren't started after that are reported as not run too.
+        ctx.handle_event(InternalEvent::Test(InternalTestEvent::NotStarted {
+            test_instance: test_b,
+            over_budget: false,
+        }))
+        .expect("test not started after cancellation is handled");
+        drop(ctx);
+
+        assert_eq!(
+            events,
+            vec![
+                "cancel: RunTimeBudget",
+                "not run: tests::a",
+                "not run: tests::b"
+            ],
+        );
+    }
+
+    #[test]
+    fn pass_rate_tally() {
+        let mut tally = PassRateTally {
+            required: None,
+            group_rates: [("fuzz", 0.9)].into_iter().collect(),
+            test_groups: HashMap::new(),
+            counts: BTreeMap::new(),
+        };
+        tally.counts.insert(Some("fuzz"), (9, 1));
+        let (checks, met) = tally.finish();
+        assert!(met, "90% of the fuzz tests passed");
+        assert_eq!(
+            checks,
+            vec![PassRateCheck {
+                test_group: Some("fuzz".to_owned()),
+                required: 0.9,
+                passed: 9,
+                failed: 1,
+            }]
+        );
+
+        // Tests outside groups must all pass unless the profile requires a pass rate.
+        tally.counts.insert(None, (99, 1));
+        assert!(!tally.finish().1, "a test outside groups failed");
+        tally.required = Some(0.98);
+        let (checks, met) = tally.finish();
+        assert!(met, "99% of the other tests passed");
+        assert_eq!(checks.len(), 2);
+        assert_eq!(checks[0].pass_rate(), 0.99);
+
+        tally.counts.insert(Some("fuzz"), (8, 2));
+        assert!(!tally.finish().1, "only 80% of the fuzz tests passed");
+    }
+
+    #[test]
+    fn test_seeds() {
+        let seed = mix_test_seed(42, "my-crate", "tests::a", 1);
+        assert_eq!(
+            seed,
+            mix_test_seed(42, "my-crate", "tests::a", 1),
+            "seeds are deterministic"
+        );
+        assert_ne!(seed, mix_test_seed(43, "my-crate", "tests::a", 1));
+        assert_ne!(seed, mix_test_seed(42, "my-crate", "tests::b", 1));
+        assert_ne!(seed, mix_test_seed(42, "my-crate", "tests::a", 2));
+        assert_ne!(
+            mix_test_seed(42, "ab", "c", 1),
+            mix_test_seed(42, "a", "bc", 1),
+            "binary IDs and names are kept apart"
+        );
+    }
+
+    #[test]
+    fn resource_pool() {
+        let runtime = Runtime::new().unwrap();
+        let pool = ResourcePool::new(3);
+        runtime.block_on(async {
+            let first = pool.acquire(1).await;
+            let second = pool.acquire(2).await;
+            assert_eq!(first.to_string(), "0");
+            assert_eq!(second.to_string(), "1,2");
+
+            // Tokens are handed out again once they're returned.
+            drop(first);
+            let third = pool.acquire(1).await;
+            assert_eq!(third.to_string(), "0");
+            assert!(
+                pool.semaphore.try_acquire().is_err(),
+                "all tokens are in use"
+            );
+            drop(second);
+            assert_eq!(pool.acquire(2).await.to_string(), "1,2");
+        });
+    }
+
+    #[test]
+    fn jobserver_tokens() {
+        let runtime = Runtime::new().unwrap();
+        let client = jobserver::Client::new(1).expect("jobserver created");
+        let tokens = JobserverTokens::from_client(client).expect("helper thread started");
+        runtime.block_on(async {
+            let first = tokens.acquire().await;
+            assert!(matches!(first, JobserverToken::Implicit(_)));
+            let second = tokens.acquire().await;
+            assert!(matches!(second, JobserverToken::Acquired { .. }));
+
+            // With both tokens in use, the next test waits for one of them to be released.
+            let third = tokens.acquire();
+            futures::pin_mut!(third);
+            assert!(futures::poll!(&mut third).is_pending());
+            drop(first);
+            let third = tokio::time::timeout(Duration::from_secs(5), third)
+                .await
+                .expect("implicit token handed out once it's released");
+            assert!(matches!(third, JobserverToken::Implicit(_)));
+
+            drop(second);
+            let fourth = tokio::time::timeout(Duration::from_secs(5), tokens.acquire())
+                .await
+                .expect("jobserver token handed out once it's released");
+            assert!(matches!(fourth, JobserverToken::Acquired { .. }));
+        });
+    }
+
     #[test]
     fn test_is_success() {
         assert!(RunStats::default().is_success(), "empty run => success");
@@ -1495,6 +4285,17 @@ mod tests {
             .is_success(),
             "initial run count = final run count => success"
         );
+        assert!(
+            RunStats {
+                initial_run_count: 42,
+                finished_count: 42,
+                quarantined: 2,
+                quarantined_failed: 1,
+                ..RunStats::default()
+            }
+            .is_success(),
+            "quarantined failed => success"
+        );
         assert!(
             !RunStats {
                 initial_run_count: 42,
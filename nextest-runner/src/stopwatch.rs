@@ -26,6 +26,10 @@ impl StopwatchStart {
         }
     }
 
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.instant.elapsed()
+    }
+
     pub(crate) fn end(&self) -> StopwatchEnd {
         StopwatchEnd {
             start_time: self.start_time,
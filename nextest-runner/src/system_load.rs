@@ -0,0 +1,163 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Sampling system load and memory pressure, for scaling the number of tests running at a time.
+
+/// Below this fraction of available memory, the number of tests running at a time is halved.
+const LOW_MEMORY: f64 = 0.1;
+
+/// The number of tests running at a time is only increased with at least this fraction of memory
+/// available.
+const RECOVERED_MEMORY: f64 = 0.2;
+
+/// The number of tests running at a time is only increased while the load is below this fraction
+/// of the maximum load.
+const RECOVERED_LOAD: f64 = 0.8;
+
+/// A sample of how loaded the system is.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct LoadSample {
+    /// The 1-minute load average divided by the number of logical CPUs, if known.
+    pub(crate) load_per_cpu: Option<f64>,
+
+    /// The fraction of memory that's available, if known.
+    pub(crate) available_memory: Option<f64>,
+}
+
+impl LoadSample {
+    /// Samples the current system load and memory pressure.
+    pub(crate) fn current() -> Self {
+        Self {
+            load_per_cpu: imp::load_average().map(|load| load / num_cpus::get() as f64),
+            available_memory: imp::available_memory(),
+        }
+    }
+
+    /// Returns true if anything is known about the system.
+    pub(crate) fn is_known(&self) -> bool {
+        self.load_per_cpu.is_some() || self.available_memory.is_some()
+    }
+
+    /// Returns the number of tests to run at a time next, given the number running at a time now.
+    ///
+    /// The number is halved under memory pressure, decreased by one while the system is overloaded
+    /// and increased by one once it has recovered, staying between `min` and `max`.
+    pub(crate) fn next_limit(
+        &self,
+        current: usize,
+        min: usize,
+        max: usize,
+        max_load: f64,
+    ) -> usize {
+        let low_memory = matches!(self.available_memory, Some(memory) if memory < LOW_MEMORY);
+        let overloaded = matches!(self.load_per_cpu, Some(load) if load > max_load);
+        let recovered = self.is_known()
+            && self
+                .available_memory
+                .map_or(true, |memory| memory >= RECOVERED_MEMORY)
+            && self
+                .load_per_cpu
+                .map_or(true, |load| load < max_load * RECOVERED_LOAD);
+
+        let next = if low_memory {
+            current / 2
+        } else if overloaded {
+            current.saturating_sub(1)
+        } else if recovered {
+            current + 1
+        } else {
+            current
+        };
+        next.clamp(min, max)
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    pub(super) fn load_average() -> Option<f64> {
+        let mut load = [0.0];
+        // SAFETY: the buffer has room for the one sample requested.
+        let samples = unsafe { libc::getloadavg(load.as_mut_ptr(), 1) };
+        (samples == 1).then(|| load[0])
+    }
+
+    #[cfg(target_os = "linux")]
+    pub(super) fn available_memory() -> Option<f64> {
+        let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+        super::parse_meminfo(&meminfo)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub(super) fn available_memory() -> Option<f64> {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    pub(super) fn load_average() -> Option<f64> {
+        None
+    }
+
+    pub(super) fn available_memory() -> Option<f64> {
+        None
+    }
+}
+
+/// Returns the fraction of memory that's available from the contents of `/proc/meminfo`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_meminfo(meminfo: &str) -> Option<f64> {
+    let field = |name: &str| {
+        meminfo.lines().find_map(|line| {
+            let value = line.strip_prefix(name)?.strip_prefix(':')?;
+            value
+                .trim()
+                .trim_end_matches("kB")
+                .trim()
+                .parse::<u64>()
+                .ok()
+        })
+    };
+    let total = field("MemTotal")?;
+    let available = field("MemAvailable")?;
+    (total > 0).then(|| available as f64 / total as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use test_case::test_case;
+
+    #[test_case(None, None, 4 => 4 ; "nothing known")]
+    #[test_case(Some(0.5), Some(0.5), 4 => 5 ; "recovered")]
+    #[test_case(Some(0.5), None, 8 => 8 ; "recovered at max")]
+    #[test_case(Some(0.9), Some(0.5), 4 => 4 ; "between thresholds")]
+    #[test_case(Some(1.5), Some(0.5), 4 => 3 ; "overloaded")]
+    #[test_case(Some(1.5), Some(0.5), 2 => 2 ; "overloaded at min")]
+    #[test_case(Some(0.5), Some(0.05), 7 => 3 ; "low memory")]
+    #[test_case(None, Some(0.15), 4 => 4 ; "memory recovering")]
+    fn next_limit(
+        load_per_cpu: Option<f64>,
+        available_memory: Option<f64>,
+        current: usize,
+    ) -> usize {
+        let sample = LoadSample {
+            load_per_cpu,
+            available_memory,
+        };
+        sample.next_limit(current, 2, 8, 1.0)
+    }
+
+    #[test]
+    fn test_parse_meminfo() {
+        let meminfo = indoc! {"
+            MemTotal:       16000000 kB
+            MemFree:         1000000 kB
+            MemAvailable:    4000000 kB
+            Buffers:          100000 kB
+        "};
+        assert_eq!(parse_meminfo(meminfo), Some(0.25));
+        assert_eq!(parse_meminfo("MemTotal: 1000 kB\n"), None);
+    }
+}
@@ -5,13 +5,15 @@
 
 use crate::{
     cargo_config::{
-        CargoConfig, CargoConfigSource, CargoConfigs, DiscoveredConfig, Runner, TargetTriple,
+        CargoConfig, CargoConfigSource, CargoConfigs, DiscoveredConfig, Runner,
+        TargetDefinitionLocation, TargetTriple,
     },
     errors::TargetRunnerError,
 };
 use camino::{Utf8Path, Utf8PathBuf};
 use nextest_metadata::BuildPlatform;
-use std::fmt;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt};
 use target_spec::Platform;
 
 /// A [target runner](https://doc.rust-lang.org/cargo/reference/config.html#targettriplerunner)
@@ -20,6 +22,8 @@ use target_spec::Platform;
 pub struct TargetRunner {
     host: Option<PlatformRunner>,
     target: Option<PlatformRunner>,
+    // Target platform runners keyed by target triple, for test runs across more than one target.
+    triple_runners: BTreeMap<String, Option<PlatformRunner>>,
 }
 
 impl TargetRunner {
@@ -37,7 +41,33 @@ impl TargetRunner {
             host.clone()
         };
 
-        Ok(Self { host, target })
+        Ok(Self {
+            host,
+            target,
+            triple_runners: BTreeMap::new(),
+        })
+    }
+
+    /// Combines target runners for several target triples into one, for test runs across more
+    /// than one target.
+    ///
+    /// The host runner is taken from the first target runner. Use
+    /// [`Self::for_build_platform_and_triple`] to look up the runner for a particular test binary.
+    pub fn for_target_triples(runners: impl IntoIterator<Item = (String, TargetRunner)>) -> Self {
+        let mut host = None;
+        let mut triple_runners = BTreeMap::new();
+        for (index, (triple, runner)) in runners.into_iter().enumerate() {
+            if index == 0 {
+                host = runner.host;
+            }
+            triple_runners.insert(triple, runner.target);
+        }
+
+        Self {
+            host,
+            target: None,
+            triple_runners,
+        }
     }
 
     /// Creates an empty target runner that does not delegate to any runner binaries.
@@ -45,6 +75,7 @@ impl TargetRunner {
         Self {
             host: None,
             target: None,
+            triple_runners: BTreeMap::new(),
         }
     }
 
@@ -69,6 +100,23 @@ impl TargetRunner {
         }
     }
 
+    /// Returns the [`PlatformRunner`] for the given build platform, and for binaries built for the
+    /// target platform, the given target triple.
+    ///
+    /// The target triple is only used if this runner was created with
+    /// [`Self::for_target_triples`].
+    pub fn for_build_platform_and_triple(
+        &self,
+        build_platform: BuildPlatform,
+        target_triple: Option<&str>,
+    ) -> Option<&PlatformRunner> {
+        let triple_runner = target_triple.and_then(|triple| self.triple_runners.get(triple));
+        match (build_platform, triple_runner) {
+            (BuildPlatform::Target, Some(runner)) => runner.as_ref(),
+            _ => self.for_build_platform(build_platform),
+        }
+    }
+
     /// Returns the platform runners for all build platforms.
     #[inline]
     pub fn all_build_platforms(&self) -> [(BuildPlatform, Option<&PlatformRunner>); 2] {
@@ -94,20 +142,72 @@ impl PlatformRunner {
         configs: &CargoConfigs,
         triple: Option<&TargetTriple>,
     ) -> Result<Option<Self>, TargetRunnerError> {
-        let target = match triple {
-            Some(target) => Platform::from_triple(
-                target_spec::Triple::new(target.triple.to_owned()).map_err(|error| {
-                    TargetRunnerError::FailedToParseTargetTriple {
-                        triple: target.triple.to_owned(),
-                        error,
-                    }
-                })?,
-                target_spec::TargetFeatures::Unknown,
-            ),
-            None => Platform::current().map_err(TargetRunnerError::UnknownHostPlatform)?,
+        // For custom targets, the target table and environment variable are named after the file
+        // stem of the target spec file rather than the LLVM target.
+        let (target_table, target) = match triple {
+            Some(triple) => {
+                let target = match &triple.location {
+                    TargetDefinitionLocation::Builtin => Some(Platform::from_triple(
+                        target_spec::Triple::new(triple.triple.to_owned()).map_err(|error| {
+                            TargetRunnerError::FailedToParseTargetTriple {
+                                triple: triple.triple.to_owned(),
+                                error,
+                            }
+                        })?,
+                        target_spec::TargetFeatures::Unknown,
+                    )),
+                    TargetDefinitionLocation::DirectFile(path) => Self::custom_platform(path)?,
+                };
+                (triple.triple.clone(), target)
+            }
+            None => {
+                let host = Platform::current().map_err(TargetRunnerError::UnknownHostPlatform)?;
+                (host.triple_str().to_owned(), Some(host))
+            }
         };
 
-        Self::find_config(configs, target)
+        Self::find_config_impl(configs, &target_table, target.as_ref())
+    }
+
+    // Determines the platform for a custom target from the `llvm-target` key in its target spec
+    // file. This is used to evaluate `cfg()` expressions.
+    //
+    // The target spec file may not be available, e.g. if tests are being run from an archive that
+    // hasn't been extracted yet. In that case, `cfg()` expressions are not evaluated.
+    fn custom_platform(path: &Utf8Path) -> Result<Option<Platform>, TargetRunnerError> {
+        #[derive(Deserialize)]
+        struct TargetSpec {
+            #[serde(rename = "llvm-target")]
+            llvm_target: String,
+        }
+
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(error) => {
+                log::debug!(
+                    "unable to read target spec file `{path}`, not matching cfg() expressions: \
+                     {error}"
+                );
+                return Ok(None);
+            }
+        };
+        let spec: TargetSpec = serde_json::from_str(&contents).map_err(|error| {
+            TargetRunnerError::TargetSpecFileParse {
+                path: path.to_owned(),
+                error,
+            }
+        })?;
+        let triple = target_spec::Triple::new(spec.llvm_target.clone()).map_err(|error| {
+            TargetRunnerError::FailedToParseTargetTriple {
+                triple: spec.llvm_target,
+                error,
+            }
+        })?;
+
+        Ok(Some(Platform::from_triple(
+            triple,
+            target_spec::TargetFeatures::Unknown,
+        )))
     }
 
     /// Attempts to find a target runner for the specified target from a
@@ -118,6 +218,14 @@ impl PlatformRunner {
     pub fn find_config(
         configs: &CargoConfigs,
         target: target_spec::Platform,
+    ) -> Result<Option<Self>, TargetRunnerError> {
+        Self::find_config_impl(configs, target.triple_str(), Some(&target))
+    }
+
+    fn find_config_impl(
+        configs: &CargoConfigs,
+        target_table: &str,
+        target: Option<&Platform>,
     ) -> Result<Option<Self>, TargetRunnerError> {
         // Now that we've found all of the config files that could declare
         // a runner that matches our target triple, we need to actually find
@@ -127,16 +235,21 @@ impl PlatformRunner {
             match discovered_config {
                 DiscoveredConfig::CliOption { config, source }
                 | DiscoveredConfig::File { config, source } => {
-                    if let Some(runner) =
-                        Self::from_cli_option_or_file(&target, config, source, configs.cwd())?
-                    {
+                    if let Some(runner) = Self::from_cli_option_or_file(
+                        target_table,
+                        target,
+                        config,
+                        source,
+                        configs.cwd(),
+                    )? {
                         return Ok(Some(runner));
                     }
                 }
                 DiscoveredConfig::Env => {
                     // Check if we have a CARGO_TARGET_{TRIPLE}_RUNNER environment variable
                     // set, and if so use that.
-                    if let Some(tr) = Self::from_env(Self::runner_env_var(&target), configs.cwd())?
+                    if let Some(tr) =
+                        Self::from_env(Self::runner_env_var_for(target_table), configs.cwd())?
                     {
                         return Ok(Some(tr));
                     }
@@ -148,19 +261,20 @@ impl PlatformRunner {
     }
 
     fn from_cli_option_or_file(
-        target: &target_spec::Platform,
+        target_table: &str,
+        target: Option<&Platform>,
         config: &CargoConfig,
         source: &CargoConfigSource,
         cwd: &Utf8Path,
     ) -> Result<Option<Self>, TargetRunnerError> {
         if let Some(targets) = &config.target {
             // First lookup by the exact triple, as that one always takes precedence
-            if let Some(parent) = targets.get(target.triple_str()) {
+            if let Some(parent) = targets.get(target_table) {
                 if let Some(runner) = &parent.runner {
                     return Ok(Some(Self::parse_runner(
                         PlatformRunnerSource::CargoConfig {
                             source: source.clone(),
-                            target_table: target.triple_str().into(),
+                            target_table: target_table.into(),
                         },
                         runner.clone(),
                         cwd,
@@ -168,6 +282,11 @@ impl PlatformRunner {
                 }
             }
 
+            let target = match target {
+                Some(target) => target,
+                None => return Ok(None),
+            };
+
             // Next check if there are target.'cfg(..)' expressions that match
             // the target. cargo states that it is not allowed for more than
             // 1 cfg runner to match the target, but we let cargo handle that
@@ -217,7 +336,11 @@ impl PlatformRunner {
     // Not part of the public API. Exposed for testing only.
     #[doc(hidden)]
     pub fn runner_env_var(target: &Platform) -> String {
-        let triple_str = target.triple_str().to_ascii_uppercase().replace('-', "_");
+        Self::runner_env_var_for(target.triple_str())
+    }
+
+    fn runner_env_var_for(target_table: &str) -> String {
+        let triple_str = target_table.to_ascii_uppercase().replace('-', "_");
         format!("CARGO_TARGET_{}_RUNNER", triple_str)
     }
 
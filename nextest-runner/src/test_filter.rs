@@ -16,9 +16,13 @@ use crate::{
     partition::{Partitioner, PartitionerBuilder},
 };
 use aho_corasick::AhoCorasick;
-use nextest_filtering::{BinaryQuery, FilteringExpr, TestQuery};
+use nextest_filtering::{BinaryQuery, FilteringExpr, TestQuery, TestTags};
 use nextest_metadata::{FilterMatch, MismatchReason};
-use std::{fmt, str::FromStr};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    str::FromStr,
+};
 
 /// Whether to run ignored tests.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -78,7 +82,12 @@ pub struct TestFilterBuilder {
     run_ignored: RunIgnored,
     partitioner_builder: Option<PartitionerBuilder>,
     name_match: NameMatch,
+    skip_match: NameMatch,
+    exact: bool,
     exprs: Vec<FilteringExpr>,
+    test_tags: TestTags,
+    // Test names keyed by binary ID.
+    test_ids: Option<BTreeMap<String, BTreeSet<String>>>,
 }
 
 #[derive(Clone, Debug)]
@@ -106,6 +115,34 @@ impl PartialEq for NameMatch {
 
 impl Eq for NameMatch {}
 
+impl NameMatch {
+    fn new(patterns: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        let mut patterns: Vec<_> = patterns.into_iter().map(|s| s.into()).collect();
+        patterns.sort_unstable();
+
+        if patterns.is_empty() {
+            NameMatch::EmptyPatterns
+        } else {
+            let matcher = Box::new(AhoCorasick::new_auto_configured(&patterns));
+
+            NameMatch::MatchSet { patterns, matcher }
+        }
+    }
+
+    /// Returns whether any pattern matches the test name, or `None` if there are no patterns.
+    fn is_match(&self, test_name: &str, exact: bool) -> Option<bool> {
+        match self {
+            NameMatch::EmptyPatterns => None,
+            NameMatch::MatchSet { patterns, .. } if exact => Some(
+                patterns
+                    .binary_search_by(|p| p.as_str().cmp(test_name))
+                    .is_ok(),
+            ),
+            NameMatch::MatchSet { matcher, .. } => Some(matcher.is_match(test_name)),
+        }
+    }
+}
+
 impl TestFilterBuilder {
     /// Creates a new `TestFilterBuilder` from the given patterns.
     ///
@@ -116,22 +153,15 @@ impl TestFilterBuilder {
         patterns: impl IntoIterator<Item = impl Into<String>>,
         exprs: Vec<FilteringExpr>,
     ) -> Self {
-        let mut patterns: Vec<_> = patterns.into_iter().map(|s| s.into()).collect();
-        patterns.sort_unstable();
-
-        let name_match = if patterns.is_empty() {
-            NameMatch::EmptyPatterns
-        } else {
-            let matcher = Box::new(AhoCorasick::new_auto_configured(&patterns));
-
-            NameMatch::MatchSet { patterns, matcher }
-        };
-
         Self {
             run_ignored,
             partitioner_builder,
-            name_match,
+            name_match: NameMatch::new(patterns),
+            skip_match: NameMatch::EmptyPatterns,
+            exact: false,
             exprs,
+            test_tags: TestTags::default(),
+            test_ids: None,
         }
     }
 
@@ -141,20 +171,100 @@ impl TestFilterBuilder {
             run_ignored,
             partitioner_builder: None,
             name_match: NameMatch::EmptyPatterns,
+            skip_match: NameMatch::EmptyPatterns,
+            exact: false,
             exprs: Vec::new(),
+            test_tags: TestTags::default(),
+            test_ids: None,
         }
     }
 
+    /// Excludes tests with names that contain any of the given patterns.
+    ///
+    /// This is equivalent to libtest's `--skip` option.
+    pub fn set_skip_patterns(
+        &mut self,
+        patterns: impl IntoIterator<Item = impl Into<String>>,
+    ) -> &mut Self {
+        self.skip_match = NameMatch::new(patterns);
+        self
+    }
+
+    /// If true, makes the patterns passed into [`Self::new`] and [`Self::set_skip_patterns`] match
+    /// test names exactly rather than as substrings.
+    ///
+    /// This is equivalent to libtest's `--exact` option.
+    pub fn set_exact(&mut self, exact: bool) -> &mut Self {
+        self.exact = exact;
+        self
+    }
+
+    /// Sets the tags assigned to tests, as reported in test lists.
+    ///
+    /// To match against tags, the expressions passed into [`Self::new`] must be parsed with the
+    /// same tags.
+    pub fn set_test_tags(&mut self, test_tags: TestTags) -> &mut Self {
+        self.test_tags = test_tags;
+        self
+    }
+
+    /// Restricts the filter to the tests with the given IDs, each of which is a binary ID and a
+    /// test name.
+    ///
+    /// Tests must be in this list as well as match the patterns and expressions passed into
+    /// [`Self::new`], and the run-ignored option still applies.
+    pub fn set_test_ids(
+        &mut self,
+        test_ids: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> &mut Self {
+        let mut map: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+        for (binary_id, test_name) in test_ids {
+            map.entry(binary_id.into())
+                .or_default()
+                .insert(test_name.into());
+        }
+        self.test_ids = Some(map);
+        self
+    }
+
+    /// Returns the tags assigned to the given test.
+    pub fn test_tags_for(
+        &self,
+        test_binary: &RustTestArtifact<'_>,
+        test_name: &str,
+    ) -> BTreeSet<String> {
+        self.test_tags.tags_for(&TestQuery {
+            binary_query: BinaryQuery {
+                package_id: test_binary.package.id(),
+                binary_id: &test_binary.binary_id,
+                kind: test_binary.kind.as_str(),
+                features: &test_binary.features,
+                binary_name: &test_binary.binary_name,
+                binary_path: &test_binary.binary_path,
+                platform: convert_build_platform(test_binary.build_platform),
+            },
+            test_name,
+        })
+    }
+
     /// Returns a value indicating whether this binary should or should not be run to obtain the
     /// list of tests within it.
     ///
     /// This method is implemented directly on `TestFilterBuilder`. The statefulness of `TestFilter`
     /// is only used for counted test partitioning, and is not currently relevant for binaries.
     pub fn should_obtain_test_list_from_binary(&self, test_binary: &RustTestArtifact<'_>) -> bool {
+        if let Some(test_ids) = &self.test_ids {
+            if !test_ids.contains_key(&test_binary.binary_id) {
+                return false;
+            }
+        }
         let query = BinaryQuery {
             package_id: test_binary.package.id(),
+            binary_id: &test_binary.binary_id,
             kind: test_binary.kind.as_str(),
+            features: &test_binary.features,
             binary_name: &test_binary.binary_name,
+            binary_path: &test_binary.binary_path,
             platform: convert_build_platform(test_binary.build_platform),
         };
         if self.exprs.is_empty() {
@@ -238,10 +348,11 @@ impl<'filter> TestFilter<'filter> {
                     }
                 }
             })
+            .or_else(|| self.filter_test_ids_mismatch(test_binary, test_name))
             // Note that partition-based filtering MUST come after all other kinds of filtering,
             // so that count-based bucketing applies after ignored, name and expression matching.
             // This also means that mutable count state must be maintained by the partitioner.
-            .or_else(|| self.filter_partition_mismatch(test_name))
+            .or_else(|| self.filter_partition_mismatch(test_binary, test_name))
             .unwrap_or(FilterMatch::Matches)
     }
 
@@ -267,15 +378,14 @@ impl<'filter> TestFilter<'filter> {
     }
 
     fn filter_name_match(&self, test_name: &str) -> FilterNameMatch {
-        match &self.builder.name_match {
-            NameMatch::EmptyPatterns => FilterNameMatch::MatchEmptyPatterns,
-            NameMatch::MatchSet { matcher, .. } => {
-                if matcher.is_match(test_name) {
-                    FilterNameMatch::MatchWithPatterns
-                } else {
-                    FilterNameMatch::Mismatch(MismatchReason::String)
-                }
-            }
+        let exact = self.builder.exact;
+        if self.builder.skip_match.is_match(test_name, exact) == Some(true) {
+            return FilterNameMatch::Mismatch(MismatchReason::String);
+        }
+        match self.builder.name_match.is_match(test_name, exact) {
+            None => FilterNameMatch::MatchEmptyPatterns,
+            Some(true) => FilterNameMatch::MatchWithPatterns,
+            Some(false) => FilterNameMatch::Mismatch(MismatchReason::String),
         }
     }
 
@@ -287,8 +397,11 @@ impl<'filter> TestFilter<'filter> {
         let query = TestQuery {
             binary_query: BinaryQuery {
                 package_id: test_binary.package.id(),
+                binary_id: &test_binary.binary_id,
                 kind: test_binary.kind.as_str(),
+                features: &test_binary.features,
                 binary_name: &test_binary.binary_name,
+                binary_path: &test_binary.binary_path,
                 platform: convert_build_platform(test_binary.build_platform),
             },
             test_name,
@@ -307,9 +420,34 @@ impl<'filter> TestFilter<'filter> {
         }
     }
 
-    fn filter_partition_mismatch(&mut self, test_name: &str) -> Option<FilterMatch> {
+    fn filter_test_ids_mismatch(
+        &self,
+        test_binary: &RustTestArtifact<'_>,
+        test_name: &str,
+    ) -> Option<FilterMatch> {
+        let test_ids = self.builder.test_ids.as_ref()?;
+        let is_match = test_ids
+            .get(&test_binary.binary_id)
+            .map_or(false, |test_names| test_names.contains(test_name));
+        if is_match {
+            None
+        } else {
+            Some(FilterMatch::Mismatch {
+                reason: MismatchReason::TestIds,
+            })
+        }
+    }
+
+    fn filter_partition_mismatch(
+        &mut self,
+        test_binary: &RustTestArtifact<'_>,
+        test_name: &str,
+    ) -> Option<FilterMatch> {
         let partition_match = match &mut self.partitioner {
-            Some(partitioner) => partitioner.test_matches(test_name),
+            Some(partitioner) => partitioner.test_matches(&TestQuery {
+                binary_query: test_binary.to_binary_query(),
+                test_name,
+            }),
             None => true,
         };
         if partition_match {
@@ -402,6 +540,29 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_exact_and_skip() {
+        let mut test_filter =
+            TestFilterBuilder::new(RunIgnored::Default, None, ["foo::bar"], Vec::new());
+        test_filter.set_skip_patterns(["baz"]);
+        let single_filter = test_filter.build();
+        assert!(single_filter.filter_name_match("foo::bar").is_match());
+        assert!(single_filter.filter_name_match("foo::bar::quux").is_match());
+        assert!(!single_filter.filter_name_match("foo::bar::baz").is_match());
+
+        test_filter.set_exact(true);
+        let single_filter = test_filter.build();
+        assert!(single_filter.filter_name_match("foo::bar").is_match());
+        assert!(!single_filter.filter_name_match("foo::bar::quux").is_match());
+
+        // With --exact, skip patterns also have to match exactly.
+        let mut test_filter = TestFilterBuilder::any(RunIgnored::Default);
+        test_filter.set_skip_patterns(["foo::bar"]).set_exact(true);
+        let single_filter = test_filter.build();
+        assert!(!single_filter.filter_name_match("foo::bar").is_match());
+        assert!(single_filter.filter_name_match("foo::bar::baz").is_match());
+    }
+
     // /// Creates a fake test binary instance.
     // fn make_test_binary() -> TestBinary {
     //     TestBinary {
@@ -0,0 +1,96 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Fake test suites, test cases and statuses for unit tests.
+
+use crate::{
+    list::{RustTestSuite, RustTestSuiteStatus},
+    runner::{ExecuteStatus, ExecutionResult},
+};
+use bytes::Bytes;
+use guppy::{graph::PackageGraph, CargoMetadata, PackageId};
+use nextest_metadata::{BuildPlatform, FilterMatch, RustTestBinaryKind, RustTestCaseSummary};
+use once_cell::sync::Lazy;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{Duration, SystemTime},
+};
+
+pub(crate) static PACKAGE_GRAPH_FIXTURE: Lazy<PackageGraph> = Lazy::new(|| {
+    static FIXTURE_JSON: &str = include_str!("../../fixtures/cargo-metadata.json");
+    let metadata = CargoMetadata::parse_json(FIXTURE_JSON).expect("fixture is valid JSON");
+    metadata
+        .build_graph()
+        .expect("fixture is valid PackageGraph")
+});
+
+pub(crate) static METADATA_BASE_ID: &str =
+    "metadata-base 0.1.0 (path+file:///Users/fakeuser/local/testcrates/metadata/metadata-base)";
+
+/// Returns a test suite in the `metadata-base` package of the fixture package graph.
+///
+/// Suites built for the host are proc-macro tests, and suites built for the target are library
+/// tests.
+pub(crate) fn fake_suite(binary_id: &str, build_platform: BuildPlatform) -> RustTestSuite<'static> {
+    let package = PACKAGE_GRAPH_FIXTURE
+        .metadata(&PackageId::new(METADATA_BASE_ID))
+        .expect("package ID is valid");
+    RustTestSuite {
+        binary_id: binary_id.to_owned(),
+        package,
+        binary_name: binary_id.to_owned(),
+        kind: match build_platform {
+            BuildPlatform::Host => RustTestBinaryKind::PROC_MACRO,
+            BuildPlatform::Target => RustTestBinaryKind::LIB,
+        },
+        cwd: "/fake/cwd".into(),
+        build_platform,
+        non_test_binaries: BTreeSet::new(),
+        artifact_binaries: BTreeSet::new(),
+        cdylibs: BTreeSet::new(),
+        doctest: None,
+        features: vec![],
+        single_test: false,
+        env: BTreeMap::new(),
+        target_triple: None,
+        status: RustTestSuiteStatus::Skipped,
+    }
+}
+
+/// Returns a test case that matches the filter and isn't ignored.
+pub(crate) fn fake_test_case() -> RustTestCaseSummary {
+    RustTestCaseSummary {
+        ignored: false,
+        ignore_reason: None,
+        location: None,
+        tags: BTreeSet::new(),
+        filter_match: FilterMatch::Matches,
+    }
+}
+
+/// Returns the status of an attempt at running a test, which took 100ms and started at the Unix
+/// epoch.
+pub(crate) fn fake_status(
+    attempt: usize,
+    total_attempts: usize,
+    result: ExecutionResult,
+    stderr: &'static str,
+) -> ExecuteStatus {
+    ExecuteStatus {
+        attempt,
+        total_attempts,
+        iteration: 1,
+        total_iterations: Some(1),
+        stdout: Bytes::new(),
+        stderr: Bytes::from_static(stderr.as_bytes()),
+        output_chunks: vec![],
+        result,
+        exit_code: None,
+        leaked_processes: vec![],
+        kept_tmpdir: None,
+        test_seed: 0,
+        start_time: SystemTime::UNIX_EPOCH,
+        time_taken: Duration::from_millis(100),
+        is_slow: false,
+    }
+}
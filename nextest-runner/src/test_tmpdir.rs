@@ -0,0 +1,128 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Per-test temporary directories, for the `test-tmpdir` setting.
+//!
+//! Each test attempt is given a fresh directory within `nextest-<run-id>` in the system temporary
+//! directory, and `TMPDIR`, `TMP` and `TEMP` are pointed at it. The directory is removed once the
+//! attempt finishes, unless the attempt failed and `keep-on-failure` is set.
+
+use std::{
+    io,
+    path::PathBuf,
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The environment variables pointed at a test's temporary directory.
+///
+/// `TMPDIR` is read on Unix, and `TMP` and `TEMP` are read on Windows.
+const TMPDIR_ENV_VARS: &[&str] = &["NEXTEST_TEST_TMPDIR", "TMPDIR", "TMP", "TEMP"];
+
+/// The longest a test name can be in the name of its temporary directory.
+const MAX_NAME_LEN: usize = 64;
+
+/// Creates temporary directories for tests within a directory for the test run.
+///
+/// The directory for the test run is removed when this is dropped, if it's empty.
+#[derive(Debug)]
+pub(crate) struct TestTmpdirs {
+    root: PathBuf,
+    next_id: AtomicUsize,
+}
+
+impl TestTmpdirs {
+    /// Creates temporary directories for tests within `run_dir_name` in the system temporary
+    /// directory.
+    pub(crate) fn new(run_dir_name: String) -> Self {
+        Self {
+            root: std::env::temp_dir().join(run_dir_name),
+            next_id: AtomicUsize::new(0),
+        }
+    }
+
+    /// Creates a fresh directory for an attempt of the test `test_name`, and points the process
+    /// started by `cmd` at it.
+    pub(crate) fn create(&self, cmd: &mut Command, test_name: &str) -> io::Result<TestTmpdir> {
+        std::fs::create_dir_all(&self.root)?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let path = self.root.join(dir_name(id, test_name));
+        std::fs::create_dir(&path)?;
+        for var in TMPDIR_ENV_VARS {
+            cmd.env(var, &path);
+        }
+        Ok(TestTmpdir { path: Some(path) })
+    }
+}
+
+impl Drop for TestTmpdirs {
+    fn drop(&mut self) {
+        // This fails if the directory was never created, or if directories were kept.
+        let _ = std::fs::remove_dir(&self.root);
+    }
+}
+
+/// A temporary directory for a test attempt, which is removed when this is dropped unless it's
+/// kept.
+#[derive(Debug)]
+pub(crate) struct TestTmpdir {
+    // None once the directory is kept.
+    path: Option<PathBuf>,
+}
+
+impl TestTmpdir {
+    /// Keeps the directory around after the test run, and returns its path.
+    pub(crate) fn keep(mut self) -> PathBuf {
+        self.path.take().expect("path is only taken here")
+    }
+}
+
+impl Drop for TestTmpdir {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            // This can fail if processes started by the test are still writing to the directory.
+            if let Err(err) = std::fs::remove_dir_all(path) {
+                log::debug!(
+                    target: "nextest-runner",
+                    "failed to remove temporary directory `{}`: {err}",
+                    path.display(),
+                );
+            }
+        }
+    }
+}
+
+/// Returns the name of the temporary directory for a test, which is made unique by `id`.
+///
+/// The test name is included to make kept directories easier to find. Characters that aren't
+/// allowed in file names on some platforms are replaced, and long names are truncated.
+fn dir_name(id: usize, test_name: &str) -> String {
+    let name: String = test_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(MAX_NAME_LEN)
+        .collect();
+    format!("{id}-{name}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dir_name() {
+        assert_eq!(dir_name(0, "tests::basic"), "0-tests__basic");
+        assert_eq!(
+            dir_name(12, "src/lib.rs - foo (line 3)"),
+            "12-src_lib.rs_-_foo__line_3_"
+        );
+        let long_name = "a".repeat(100);
+        assert_eq!(dir_name(3, &long_name).len(), 2 + MAX_NAME_LEN);
+    }
+}
@@ -9,7 +9,7 @@ use nextest_metadata::{BuildPlatform, FilterMatch, MismatchReason};
 use nextest_runner::{
     config::NextestConfig,
     list::BinaryList,
-    reporter::heuristic_extract_description,
+    reporter::{heuristic_extract_description, ReporterStderr, TestEvent, TestReporterBuilder},
     runner::{ExecutionDescription, ExecutionResult, TestRunnerBuilder},
     signal::SignalHandlerKind,
     target_runner::TargetRunner,
@@ -364,6 +364,44 @@ fn test_string_filters_without_filter_expr() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_test_ids_filter() -> Result<()> {
+    set_rustflags();
+
+    let mut test_filter =
+        TestFilterBuilder::new(RunIgnored::Default, None, Vec::<String>::new(), vec![]);
+    test_filter.set_test_ids([
+        ("nextest-tests", "tests::call_dylib_add_two"),
+        ("cdylib-link", "test_multiply_two"),
+        ("nextest-tests::basic", "test_does_not_exist"),
+    ]);
+    let test_list = FIXTURE_TARGETS.make_test_list(&test_filter, &TargetRunner::empty());
+    for test in test_list.iter_tests() {
+        if (test.bin_info.binary_id == "nextest-tests" && test.name == "tests::call_dylib_add_two")
+            || (test.bin_info.binary_id == "cdylib-link" && test.name == "test_multiply_two")
+        {
+            assert!(
+                test.test_info.filter_match.is_match(),
+                "expected test {test:?} to be a match, but it isn't"
+            );
+        } else {
+            let expected_test = get_expected_test(&test.bin_info.binary_id, test.name);
+            let reason = if expected_test.status.is_ignored() {
+                MismatchReason::Ignored
+            } else {
+                MismatchReason::TestIds
+            };
+            assert_eq!(
+                test.test_info.filter_match,
+                FilterMatch::Mismatch { reason },
+                "expected test {test:?} to mismatch due to {reason}"
+            )
+        }
+    }
+
+    Ok(())
+}
+
 #[test_case(
     None
     ; "retry overrides obeyed"
@@ -382,7 +420,7 @@ fn test_retries(retries: Option<usize>) -> Result<()> {
         .profile("with-retries")
         .expect("with-retries config is valid");
 
-    let profile_retries = profile.retries();
+    let profile_retries = profile.retries().count();
     assert_eq!(profile_retries, 2, "retries set in with-retries profile");
 
     let mut builder = TestRunnerBuilder::default();
@@ -571,3 +609,158 @@ fn test_termination() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_until_failure_no_tests() -> Result<()> {
+    set_rustflags();
+
+    let expr = FilteringExpr::parse("none()", &*PACKAGE_GRAPH).unwrap();
+    let test_filter =
+        TestFilterBuilder::new(RunIgnored::Default, None, Vec::<String>::new(), vec![expr]);
+    let test_list = FIXTURE_TARGETS.make_test_list(&test_filter, &TargetRunner::empty());
+    assert_eq!(test_list.run_count(), 0, "no tests are run");
+    let config = load_config();
+    let profile = config.profile("default").expect("default config is valid");
+
+    let mut builder = TestRunnerBuilder::default();
+    builder.set_until_failure(true);
+    let mut runner = builder
+        .build(
+            &test_list,
+            profile,
+            SignalHandlerKind::Noop,
+            TargetRunner::empty(),
+        )
+        .unwrap();
+
+    // With nothing to run, this should stop after the first iteration.
+    let (instance_statuses, run_stats) = execute_collect(&mut runner);
+    assert_eq!(run_stats.finished_count, 0, "no tests finished");
+    assert_eq!(
+        instance_statuses.len(),
+        test_list.test_count(),
+        "every test is reported as skipped exactly once"
+    );
+    for instance_value in instance_statuses.values() {
+        assert!(
+            matches!(instance_value.status, InstanceStatus::Skipped(_)),
+            "test should be skipped, actual {:?}",
+            instance_value.status
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_until_failure_deferred_retries() -> Result<()> {
+    set_rustflags();
+
+    let expr = FilteringExpr::parse("test(=test_failure_assert)", &*PACKAGE_GRAPH).unwrap();
+    let test_filter =
+        TestFilterBuilder::new(RunIgnored::Default, None, Vec::<String>::new(), vec![expr]);
+    let test_list = FIXTURE_TARGETS.make_test_list(&test_filter, &TargetRunner::empty());
+    let config = load_config();
+    let profile = config
+        .profile("with-deferred-retries")
+        .expect("with-deferred-retries config is valid");
+
+    let mut builder = TestRunnerBuilder::default();
+    builder.set_until_failure(true);
+    let mut runner = builder
+        .build(
+            &test_list,
+            profile,
+            SignalHandlerKind::Noop,
+            TargetRunner::empty(),
+        )
+        .unwrap();
+
+    // The retry is deferred to the end of the first iteration, and its failure stops the run.
+    let (instance_statuses, run_stats) = execute_collect(&mut runner);
+    assert_eq!(run_stats.failed, 1, "test failed once");
+    let (_, instance_value) = instance_statuses
+        .iter()
+        .find(|(&(_, name), _)| name == "test_failure_assert")
+        .expect("test_failure_assert should be present");
+    match &instance_value.status {
+        InstanceStatus::Finished(run_statuses) => {
+            assert_eq!(run_statuses.len(), 2, "test should be tried twice");
+            assert_eq!(run_statuses.last_status().iteration, 1, "first iteration");
+        }
+        InstanceStatus::Skipped(_) => panic!("test_failure_assert should have been run"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_max_run_time() -> Result<()> {
+    set_rustflags();
+
+    let expr = FilteringExpr::parse("test(=test_success)", &*PACKAGE_GRAPH).unwrap();
+    let test_filter =
+        TestFilterBuilder::new(RunIgnored::Default, None, Vec::<String>::new(), vec![expr]);
+    let test_list = FIXTURE_TARGETS.make_test_list(&test_filter, &TargetRunner::empty());
+    let config = load_config();
+    let profile = config
+        .profile("with-junit")
+        .expect("with-junit config is valid");
+    let junit_path = profile.store_dir().join("junit.xml");
+
+    let mut builder = TestRunnerBuilder::default();
+    // With no time to run tests in, none of them are started.
+    builder.set_max_run_time(Duration::ZERO);
+    let mut runner = builder
+        .build(
+            &test_list,
+            profile.clone(),
+            SignalHandlerKind::Noop,
+            TargetRunner::empty(),
+        )
+        .unwrap();
+
+    let mut buf = Vec::new();
+    let mut reporter = TestReporterBuilder::default().build(
+        &test_list,
+        &profile,
+        ReporterStderr::Buffer(&mut buf),
+    );
+    let mut not_run = Vec::new();
+    let run_stats = runner.execute(|event| {
+        match &event {
+            TestEvent::TestNotRun { test_instance } => not_run.push(test_instance.name),
+            TestEvent::TestStarted { test_instance, .. } => {
+                panic!("{} should not have been started", test_instance.name)
+            }
+            _ => {}
+        }
+        reporter.report_event(event).expect("event reported");
+    });
+    drop(reporter);
+
+    assert_eq!(run_stats.finished_count, 0, "no tests finished");
+    assert_eq!(
+        not_run.len(),
+        test_list.run_count(),
+        "every test is reported as not run"
+    );
+    let output = String::from_utf8(buf).expect("reporter output is UTF-8");
+    assert!(
+        output.contains(&format!(
+            "NOT RUN {} tests not run due to the run time budget",
+            test_list.run_count()
+        )),
+        "reporter lists tests that weren't run: {output}"
+    );
+    let junit = std::fs::read_to_string(&junit_path).expect("JUnit report was written");
+    assert_eq!(
+        junit
+            .matches("not run: the run time budget was used up")
+            .count(),
+        test_list.run_count(),
+        "JUnit report has tests that weren't run: {junit}"
+    );
+
+    Ok(())
+}
@@ -3,7 +3,9 @@
 
 use crate::fixtures::*;
 use color_eyre::Result;
-use nextest_runner::cargo_config::{CargoConfigs, TargetTriple, TargetTripleSource};
+use nextest_runner::cargo_config::{
+    CargoConfigs, TargetDefinitionLocation, TargetTriple, TargetTripleSource,
+};
 
 #[test]
 fn parses_target_cli_option() {
@@ -18,6 +20,7 @@ fn parses_target_cli_option() {
         Some(TargetTriple {
             triple: "aarch64-unknown-linux-gnu".to_owned(),
             source: TargetTripleSource::CliOption,
+            location: TargetDefinitionLocation::Builtin,
         })
     )
 }
@@ -35,6 +38,7 @@ fn parses_cargo_env() {
         Some(TargetTriple {
             triple: "x86_64-unknown-linux-musl".to_owned(),
             source: TargetTripleSource::Env,
+            location: TargetDefinitionLocation::Builtin,
         })
     )
 }
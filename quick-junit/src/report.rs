@@ -294,6 +294,11 @@ pub struct TestCase {
     /// The status of this test.
     pub status: TestCaseStatus,
 
+    /// Custom properties set for this test case, e.g. tags.
+    ///
+    /// Test case properties aren't part of the Jenkins spec, but are supported by many tools.
+    pub properties: Vec<Property>,
+
     /// Data written to standard output while the test case was executed.
     pub system_out: Option<Output>,
 
@@ -314,6 +319,7 @@ impl TestCase {
             timestamp: None,
             time: None,
             status,
+            properties: vec![],
             system_out: None,
             system_err: None,
             extra: IndexMap::new(),
@@ -344,6 +350,23 @@ impl TestCase {
         self
     }
 
+    /// Adds a property to this test case.
+    pub fn add_property(&mut self, property: impl Into<Property>) -> &mut Self {
+        self.properties.push(property.into());
+        self
+    }
+
+    /// Adds several properties to this test case.
+    pub fn add_properties(
+        &mut self,
+        properties: impl IntoIterator<Item = impl Into<Property>>,
+    ) -> &mut Self {
+        for property in properties {
+            self.add_property(property);
+        }
+        self
+    }
+
     /// Sets standard output.
     pub fn set_system_out(&mut self, system_out: impl AsRef<str>) -> &mut Self {
         self.system_out = Some(Output::new(system_out.as_ref()));
@@ -178,6 +178,7 @@ fn serialize_test_case(
         timestamp,
         time,
         status,
+        properties,
         system_out,
         system_err,
         extra,
@@ -204,6 +205,14 @@ fn serialize_test_case(
     }
     writer.write_event(Event::Start(testcase_tag))?;
 
+    if !properties.is_empty() {
+        serialize_empty_start_tag(PROPERTIES_TAG, writer)?;
+        for property in properties {
+            serialize_property(property, writer)?;
+        }
+        serialize_end_tag(PROPERTIES_TAG, writer)?;
+    }
+
     match status {
         TestCaseStatus::Success { flaky_runs } => {
             for rerun in flaky_runs {
@@ -53,7 +53,8 @@ fn basic_report() -> Report {
     let mut test_case = TestCase::new("testcase1", test_case_status);
     test_case
         .set_system_err("some sort of failure output")
-        .set_time(Duration::from_millis(4242));
+        .set_time(Duration::from_millis(4242))
+        .add_property(Property::new("tag", "gpu"));
     test_suite.add_test_case(test_case);
 
     // ---